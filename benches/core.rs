@@ -0,0 +1,104 @@
+use cgmath::Point2;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use hinterland::bullet::BulletDrawable;
+use hinterland::bullet::collision::{CollisionSystem, Contacts, SpatialGrid};
+use hinterland::character::{controls::CharacterInputState, Player};
+use hinterland::data;
+use hinterland::ecs::{Builder, RunNow, World, WorldExt};
+use hinterland::game::constants::{MAP_FILE_PATH, PLAYER_MAX_HEALTH};
+use hinterland::game::health::Health;
+use hinterland::shaders::Position;
+use hinterland::terrain::{materials::TileMaterials, path_finding::PathFinder, tile_map::Terrain};
+use hinterland::terrain_object::{terrain_objects::terrain_objects, TerrainObjectDrawable};
+use hinterland::zombie::ZombieDrawable;
+
+// About the first wave, a late wave and the hordes the zombie level of detail is meant for
+const HORDES: [usize; 3] = [40, 1000, 4000];
+// A few players firing at once
+const BULLETS: usize = 64;
+
+// Spread over a square around the player like a wave closing in
+fn horde_positions(count: usize) -> Vec<Position> {
+  (0..count).map(|idx| Position::new((idx % 64) as f32 * 25.0 - 800.0, (idx / 64) as f32 * 25.0 - 800.0)).collect()
+}
+
+fn bullet_positions() -> Vec<Position> {
+  (0..BULLETS).map(|idx| Position::new((idx % 8) as f32 * 40.0 - 160.0, (idx / 8) as f32 * 40.0 - 160.0)).collect()
+}
+
+fn path_finding(c: &mut Criterion) {
+  let mut group = c.benchmark_group("path_finding");
+  let mut paths = PathFinder::new();
+  // Around the first house, a zombie's usual chase and a route across the map
+  let routes = [("around_house", (54, 54), (57, 54)), ("chase", (60, 60), (66, 63)), ("across_map", (5, 5), (120, 120))];
+  for &(name, start, end) in routes.iter() {
+    let (start, end) = (Point2::new(start.0, start.1), Point2::new(end.0, end.1));
+    group.bench_function(name, |b| b.iter(|| paths.next_tile(black_box(start), black_box(end))));
+  }
+  group.finish();
+}
+
+fn spatial_grid(c: &mut Criterion) {
+  let mut group = c.benchmark_group("spatial_grid");
+  let bullets = bullet_positions();
+  for &count in HORDES.iter() {
+    let positions = horde_positions(count);
+    let mut grid = SpatialGrid::default();
+    group.bench_with_input(BenchmarkId::new("rebuild", count), &positions, |b, positions| {
+      b.iter(|| grid.rebuild(positions.iter().cloned().enumerate()))
+    });
+    grid.rebuild(positions.iter().cloned().enumerate());
+    group.bench_with_input(BenchmarkId::new("query", count), &bullets, |b, bullets| {
+      b.iter(|| bullets.iter().map(|&p| grid.nearby(p).count()).sum::<usize>())
+    });
+  }
+  group.finish();
+}
+
+fn collision(c: &mut Criterion) {
+  let mut group = c.benchmark_group("collision");
+  for &count in HORDES.iter() {
+    let mut world = World::new();
+    world.register::<ZombieDrawable>();
+    world.register::<BulletDrawable>();
+    world.register::<TerrainObjectDrawable>();
+    world.register::<CharacterInputState>();
+    world.register::<Player>();
+    world.register::<Health>();
+    world.insert(Contacts::default());
+
+    for position in horde_positions(count) {
+      world.create_entity().with(ZombieDrawable::new(position)).build();
+    }
+    for (idx, position) in bullet_positions().into_iter().enumerate() {
+      world.create_entity().with(BulletDrawable::new(position, idx as f32 * 5.6)).build();
+    }
+    for object in terrain_objects() {
+      world.create_entity().with(object).build();
+    }
+    world.create_entity()
+      .with(CharacterInputState::new())
+      .with(Player::new(0))
+      .with(Health::new(PLAYER_MAX_HEALTH))
+      .build();
+
+    let mut system = CollisionSystem::default();
+    group.bench_function(BenchmarkId::from_parameter(count), |b| b.iter(|| system.run_now(&world)));
+  }
+  group.finish();
+}
+
+// The map is read from the tiled file when a run starts
+fn map(c: &mut Criterion) {
+  let mut group = c.benchmark_group("map");
+  group.sample_size(20);
+  group.bench_function("parse", |b| b.iter(|| data::load_map_file(MAP_FILE_PATH)));
+  group.bench_function("terrain", |b| b.iter(Terrain::new));
+  let tile_map = data::load_map_file(MAP_FILE_PATH);
+  group.bench_function("materials", |b| b.iter(|| TileMaterials::new(black_box(&tile_map))));
+  group.finish();
+}
+
+criterion_group!(benches, path_finding, spatial_grid, collision, map);
+criterion_main!(benches);
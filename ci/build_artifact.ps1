@@ -3,6 +3,10 @@ mkdir hinterland\assets
 copy assets\*.png hinterland\assets
 copy assets\*.json hinterland\assets
 copy -Recurse assets\maps hinterland\assets\maps
-copy -Recurse assets\audio hinterland\assets\audio
+copy -Recurse assets\sounds hinterland\assets\sounds
+copy -Recurse assets\music hinterland\assets\music
+copy -Recurse assets\scripts hinterland\assets\scripts
+copy -Recurse assets\lang hinterland\assets\lang
+copy -Recurse assets\presets hinterland\assets\presets
 copy target\release\hinterland.exe hinterland\hinterland.exe
 7z a -tzip hinterland-windows.zip hinterland
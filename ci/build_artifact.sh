@@ -8,7 +8,11 @@ mkdir -p hinterland/assets
 cp assets/*.png hinterland/assets
 cp assets/*.json hinterland/assets
 cp -r assets/maps hinterland/assets
-cp -r assets/audio hinterland/assets
+cp -r assets/sounds hinterland/assets
+cp -r assets/music hinterland/assets
+cp -r assets/scripts hinterland/assets
+cp -r assets/lang hinterland/assets
+cp -r assets/presets hinterland/assets
 cp target/release/hinterland hinterland
 tar zcf $PACKAGE_NAME hinterland
 rm -rf hinterland
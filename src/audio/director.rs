@@ -0,0 +1,89 @@
+use std::time::Instant;
+
+use crate::audio::mixer::Mixer;
+use crate::audio::music::MusicTrack;
+use crate::character::controls::CharacterInputState;
+use crate::character::Player;
+use crate::ecs::{self, Join, Read, ReadStorage, ReaderId, Write};
+use crate::game::constants::{COMBAT_DAMAGE_INTENSITY, COMBAT_INTENSITY_DECAY_SECONDS, COMBAT_INTENSITY_ZOMBIES, CRITICAL_HEALTH_RATIO,
+                             MUSIC_COMBAT_DISTANCE, MUSIC_COMBAT_INTENSITY};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::health::Health;
+use crate::game::spawner::is_boss_wave;
+use crate::game::state::{GameState, GameStates};
+use crate::graphics::distance;
+use crate::zombie::ZombieDrawable;
+
+// Turns gameplay into mixer state: the music track, the combat intensity for the music stems
+// and the muffled filter while the player is at critical health
+pub struct AudioDirectorSystem {
+  reader: ReaderId<GameEvent>,
+  last_frame: Instant,
+  wave: usize,
+  damage: f32,
+  is_in_combat: bool,
+}
+
+impl AudioDirectorSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> AudioDirectorSystem {
+    AudioDirectorSystem {
+      reader,
+      last_frame: Instant::now(),
+      wave: 0,
+      damage: 0.0,
+      is_in_combat: false,
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for AudioDirectorSystem {
+  type SystemData = (ReadStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, Health>,
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterInputState>,
+                     Read<'a, GameStates>,
+                     Read<'a, GameEvents>,
+                     Write<'a, Mixer>);
+
+  fn run(&mut self, (zombies, health, players, character_input, states, events, mut mixer): Self::SystemData) {
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+    let state = states.current();
+
+    // Recent damage fades out over time, only while the simulation runs
+    if state.is_simulating() {
+      self.damage = (self.damage - delta / COMBAT_INTENSITY_DECAY_SECONDS).max(0.0);
+    }
+    for event in events.read(&mut self.reader) {
+      match *event {
+        GameEvent::WaveStarted(wave) => self.wave = wave,
+        GameEvent::PlayerDamaged(..) => self.damage = (self.damage + COMBAT_DAMAGE_INTENSITY).min(1.0),
+        _ => (),
+      }
+    }
+
+    let nearby = zombies.join()
+      .filter(|z| !z.is_dead() && distance(z.transform.position.x(), z.transform.position.y()) < MUSIC_COMBAT_DISTANCE)
+      .count();
+    let intensity = (nearby as f32 / COMBAT_INTENSITY_ZOMBIES as f32 + self.damage).min(1.0);
+    // Combat music holds until the intensity has completely calmed down
+    self.is_in_combat = if self.is_in_combat { intensity > 0.0 } else { intensity >= MUSIC_COMBAT_INTENSITY };
+    let is_critical = (&health, &players).join()
+      .any(|(h, p)| p.index == 0 && !h.is_dead() && h.current / h.max < CRITICAL_HEALTH_RATIO);
+
+    if let Some((ci, _)) = (&character_input, &players).join().find(|(_, p)| p.index == 0) {
+      mixer.listener = ci.movement;
+    }
+    mixer.intensity = intensity;
+    mixer.is_ducked = states.contains(GameState::Paused);
+    mixer.is_muffled = is_critical && state != GameState::Menu && state != GameState::GameOver && state != GameState::Lobby;
+    mixer.music = match state {
+      GameState::Menu | GameState::GameOver | GameState::Lobby | GameState::Browser => Some(MusicTrack::Menu),
+      GameState::Paused | GameState::Settings | GameState::Controls | GameState::Records | GameState::Leaderboard | GameState::Stats | GameState::Profiles |
+      GameState::SaveSlots | GameState::LoadSlots | GameState::Inventory => mixer.music,
+      GameState::Playing if !self.is_in_combat => Some(MusicTrack::Exploration),
+      GameState::Playing if is_boss_wave(self.wave) => Some(MusicTrack::Boss),
+      GameState::Playing => Some(MusicTrack::Combat),
+    };
+  }
+}
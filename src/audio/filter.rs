@@ -0,0 +1,74 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::game::constants::LOW_PASS_OPEN_HZ;
+
+// Cutoff frequency in Hz shared with every playing source, so it can be swept while they play
+pub type Cutoff = Arc<AtomicU32>;
+
+pub fn new_cutoff() -> Cutoff {
+  Arc::new(AtomicU32::new(LOW_PASS_OPEN_HZ))
+}
+
+// One pole low-pass filter, samples pass untouched while the cutoff is fully open
+pub struct LowPass<S> {
+  source: S,
+  cutoff: Cutoff,
+  previous: Vec<f32>,
+  channel: usize,
+}
+
+impl<S> LowPass<S> where S: Source<Item=f32> {
+  pub fn new(source: S, cutoff: Cutoff) -> LowPass<S> {
+    let channels = usize::from(source.channels()).max(1);
+    LowPass {
+      source,
+      cutoff,
+      previous: vec![0.0; channels],
+      channel: 0,
+    }
+  }
+}
+
+impl<S> Iterator for LowPass<S> where S: Source<Item=f32> {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    let sample = self.source.next()?;
+    let channel = self.channel;
+    self.channel = (self.channel + 1) % self.previous.len();
+
+    let cutoff = self.cutoff.load(Ordering::Relaxed);
+    let filtered = if cutoff >= LOW_PASS_OPEN_HZ {
+      sample
+    } else {
+      let dt = 1.0 / self.source.sample_rate() as f32;
+      let rc = 1.0 / (2.0 * PI * cutoff as f32);
+      self.previous[channel] + dt / (rc + dt) * (sample - self.previous[channel])
+    };
+    self.previous[channel] = filtered;
+    Some(filtered)
+  }
+}
+
+impl<S> Source for LowPass<S> where S: Source<Item=f32> {
+  fn current_frame_len(&self) -> Option<usize> {
+    self.source.current_frame_len()
+  }
+
+  fn channels(&self) -> u16 {
+    self.source.channels()
+  }
+
+  fn sample_rate(&self) -> u32 {
+    self.source.sample_rate()
+  }
+
+  fn total_duration(&self) -> Option<Duration> {
+    self.source.total_duration()
+  }
+}
@@ -0,0 +1,204 @@
+use json::JsonValue;
+
+use crate::audio::music::MusicTrack;
+use crate::game::constants::{AUDIO_FALLOFF_DISTANCE, AUDIO_PAN_DISTANCE, DUCKED_GAIN};
+use crate::graphics::{distance, is_line_blocked};
+use crate::shaders::Position;
+use crate::terrain::materials::Material;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sound {
+  Gunshot,
+  ZombieGroan,
+  Hit,
+  Pickup,
+  UiClick,
+  Banner,
+  FootstepGrass,
+  FootstepWater,
+  FootstepStone,
+}
+
+impl Sound {
+  pub fn from_name(name: &str) -> Option<Sound> {
+    match name {
+      "gunshot" => Some(Sound::Gunshot),
+      "zombie_groan" => Some(Sound::ZombieGroan),
+      "hit" => Some(Sound::Hit),
+      "pickup" => Some(Sound::Pickup),
+      "ui_click" => Some(Sound::UiClick),
+      "banner" => Some(Sound::Banner),
+      "footstep_grass" => Some(Sound::FootstepGrass),
+      "footstep_water" => Some(Sound::FootstepWater),
+      "footstep_stone" => Some(Sound::FootstepStone),
+      _ => None,
+    }
+  }
+
+  pub fn footstep(material: Material) -> Sound {
+    match material {
+      Material::Grass => Sound::FootstepGrass,
+      Material::Water => Sound::FootstepWater,
+      Material::Stone => Sound::FootstepStone,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bus {
+  Effects,
+  Interface,
+  Music,
+}
+
+impl Bus {
+  fn from_name(name: &str) -> Option<Bus> {
+    match name {
+      "effects" => Some(Bus::Effects),
+      "interface" => Some(Bus::Interface),
+      "music" => Some(Bus::Music),
+      _ => None,
+    }
+  }
+}
+
+// One manifest entry, exclusive sounds don't restart while a voice of theirs is still playing.
+// An entry with several files is a sample set, each play takes the next one.
+pub struct SoundDefinition {
+  pub sound: Sound,
+  pub paths: Vec<String>,
+  pub volume: f32,
+  pub bus: Bus,
+  pub is_exclusive: bool,
+}
+
+pub fn parse_manifest(value: &JsonValue) -> Result<Vec<SoundDefinition>, String> {
+  value.entries()
+    .map(|(name, entry)| {
+      let sound = Sound::from_name(name).ok_or_else(|| format!("Unknown sound {}", name))?;
+      let paths = if entry["file"].is_array() {
+        entry["file"].members().filter_map(|f| f.as_str()).map(|f| f.to_string()).collect::<Vec<_>>()
+      } else {
+        entry["file"].as_str().map(|f| vec![f.to_string()]).unwrap_or_default()
+      };
+      if paths.is_empty() {
+        return Err(format!("Sound {} has no file", name));
+      }
+      let bus = Bus::from_name(entry["bus"].as_str().unwrap_or("effects")).ok_or_else(|| format!("Sound {} has an unknown bus", name))?;
+      Ok(SoundDefinition {
+        sound,
+        paths,
+        volume: entry["volume"].as_f32().unwrap_or(1.0),
+        bus,
+        is_exclusive: entry["exclusive"].as_bool().unwrap_or(false),
+      })
+    })
+    .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+// Emitters are in the screen relative space, where the listening player stays at the origin
+pub enum MixerCommand {
+  Play(SoundHandle, Sound, f32, Option<Position>),
+  Stop(SoundHandle),
+  SetVolume(SoundHandle, f32),
+  SetPosition(SoundHandle, Position),
+}
+
+// The listener is the player's map position, emitters are offsets from the player like everywhere else
+pub fn is_occluded(listener: Position, emitter: Position) -> bool {
+  is_line_blocked(listener, listener - emitter)
+}
+
+// Returns the distance attenuation and the stereo pan, from -1.0 (left) to 1.0 (right)
+pub fn spatialize(emitter: Position) -> (f32, f32) {
+  let attenuation = (1.0 - distance(emitter.x(), emitter.y()) / AUDIO_FALLOFF_DISTANCE).max(0.0);
+  let pan = (emitter.x() / AUDIO_PAN_DISTANCE).clamp(-1.0, 1.0);
+  (attenuation, pan)
+}
+
+// Systems queue sounds here, the audio system owns the output device and plays them
+pub struct Mixer {
+  pub master_volume: f32,
+  pub effects_volume: f32,
+  pub interface_volume: f32,
+  pub music_volume: f32,
+  pub music: Option<MusicTrack>,
+  pub intensity: f32,
+  pub is_muffled: bool,
+  // Music and effects are turned down under the pause menu
+  pub is_ducked: bool,
+  pub listener: Position,
+  next_handle: u64,
+  pub commands: Vec<MixerCommand>,
+}
+
+impl Mixer {
+  pub fn new() -> Mixer {
+    Mixer {
+      master_volume: 1.0,
+      effects_volume: 1.0,
+      interface_volume: 1.0,
+      music_volume: 1.0,
+      music: None,
+      intensity: 0.0,
+      is_muffled: false,
+      is_ducked: false,
+      listener: Position::origin(),
+      next_handle: 0,
+      commands: Vec::new(),
+    }
+  }
+
+  pub fn play(&mut self, sound: Sound) -> SoundHandle {
+    self.play_with_volume(sound, 1.0)
+  }
+
+  pub fn play_with_volume(&mut self, sound: Sound, volume: f32) -> SoundHandle {
+    self.queue(sound, volume, None)
+  }
+
+  pub fn play_at(&mut self, sound: Sound, position: Position) -> SoundHandle {
+    self.queue(sound, 1.0, Some(position))
+  }
+
+  fn queue(&mut self, sound: Sound, volume: f32, position: Option<Position>) -> SoundHandle {
+    let handle = SoundHandle(self.next_handle);
+    self.next_handle += 1;
+    self.commands.push(MixerCommand::Play(handle, sound, volume, position));
+    handle
+  }
+
+  pub fn stop(&mut self, handle: SoundHandle) {
+    self.commands.push(MixerCommand::Stop(handle));
+  }
+
+  pub fn set_volume(&mut self, handle: SoundHandle, volume: f32) {
+    self.commands.push(MixerCommand::SetVolume(handle, volume));
+  }
+
+  pub fn set_position(&mut self, handle: SoundHandle, position: Position) {
+    self.commands.push(MixerCommand::SetPosition(handle, position));
+  }
+
+  pub fn bus_gain(&self, bus: Bus) -> f32 {
+    let bus_volume = match bus {
+      Bus::Effects => self.effects_volume,
+      Bus::Interface => self.interface_volume,
+      Bus::Music => self.music_volume,
+    };
+    let duck = match bus {
+      Bus::Effects | Bus::Music if self.is_ducked => DUCKED_GAIN,
+      _ => 1.0,
+    };
+    self.master_volume * bus_volume * duck
+  }
+}
+
+impl Default for Mixer {
+  fn default() -> Mixer {
+    Mixer::new()
+  }
+}
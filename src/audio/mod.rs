@@ -1,66 +1,277 @@
+use std::collections::HashMap;
 use std::{fs::File, io::BufReader};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
-use crossbeam_channel as channel;
 use rodio;
-use rodio::Sink;
-use specs;
-use specs::prelude::ReadStorage;
+use rodio::{Sink, Source, SpatialSink};
+use rodio::source::{Buffered, SamplesConverter};
 
-use crate::character::{CharacterDrawable, controls::CharacterInputState};
-use crate::game::constants::PISTOL_AUDIO_PATH;
+use crate::audio::mixer::{Bus, is_occluded, Mixer, MixerCommand, parse_manifest, Sound, SoundDefinition, SoundHandle, spatialize};
+use crate::audio::filter::{Cutoff, LowPass, new_cutoff};
+use crate::audio::music::{layer_gain, MusicTrack, parse_playlist, TrackDefinition};
+use crate::data;
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::{LOW_PASS_OPEN_HZ, MAX_VOICES, MUFFLE_FADE_SECONDS, MUFFLED_CUTOFF_HZ, MUSIC_CROSSFADE_SECONDS,
+                             MUSIC_PLAYLIST_PATH, OCCLUDED_CUTOFF_HZ, OCCLUDED_GAIN, SOUND_MANIFEST_PATH};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::shaders::Position;
 
-#[derive(Clone, Copy, PartialEq)]
-pub enum Effects {
-  PistolFire,
-  None,
+pub mod director;
+pub mod filter;
+pub mod mixer;
+pub mod music;
+
+type SoundData = Buffered<rodio::Decoder<BufReader<File>>>;
+
+const LEFT_EAR: [f32; 3] = [-1.0, 0.0, 0.0];
+const RIGHT_EAR: [f32; 3] = [1.0, 0.0, 0.0];
+
+struct Voice {
+  sound: Sound,
+  volume: f32,
+  emitter: Option<Position>,
+  occlusion: f32,
+  cutoff: Cutoff,
+  sink: SpatialSink,
 }
 
-pub struct AudioSystem {
-  effects: Effects,
+fn occlusion_gain(occlusion: f32) -> f32 {
+  1.0 - occlusion * (1.0 - OCCLUDED_GAIN)
+}
+
+// Voices behind solid tiles are quieter and muffled, 0.0 is a clear line and 1.0 fully occluded
+fn occlusion_target(emitter: Option<Position>, mixer: &Mixer) -> f32 {
+  match emitter {
+    Some(position) if is_occluded(mixer.listener, position) => 1.0,
+    _ => 0.0,
+  }
+}
+
+// The track itself is the first layer, its stems follow in playlist order
+struct MusicLayer {
+  path: String,
+  intensity: Option<f32>,
+  gain: f32,
   sink: Sink,
-  queue: channel::Receiver<Effects>,
+}
+
+struct MusicVoice {
+  track: MusicTrack,
+  fade: f32,
+  layers: Vec<MusicLayer>,
+}
+
+// Every source goes through the shared low-pass, so the whole mix can be muffled at once
+fn filtered<S>(source: S, cutoff: &Cutoff) -> LowPass<SamplesConverter<S, f32>>
+  where S: Source, S::Item: rodio::Sample {
+  LowPass::new(source.convert_samples(), cutoff.clone())
+}
+
+// Non positional voices stay centered between the ears at full volume
+fn voice_placement(emitter: Option<Position>) -> (f32, [f32; 3]) {
+  let (attenuation, pan) = emitter.map_or((1.0, 0.0), spatialize);
+  (attenuation, [pan, 0.0, 0.0])
+}
+
+pub struct AudioSystem {
+  device: rodio::Device,
+  sounds: HashMap<Sound, (SoundDefinition, Vec<SoundData>)>,
+  next_variant: usize,
+  voices: HashMap<SoundHandle, Voice>,
+  tracks: HashMap<MusicTrack, TrackDefinition>,
+  music: Vec<MusicVoice>,
+  cutoff: Cutoff,
+  cutoff_hz: f32,
+  last_frame: Instant,
+  reader: ReaderId<GameEvent>,
+}
+
+fn decode(path: &str) -> rodio::Decoder<BufReader<File>> {
+  let file = match File::open(path) {
+    Ok(f) => f,
+    Err(e) => panic!("Sound {} not found: {}", path, e),
+  };
+  match rodio::Decoder::new(BufReader::new(file)) {
+    Ok(decoder) => decoder,
+    Err(e) => panic!("Sound {} decode error {}", path, e),
+  }
 }
 
 impl AudioSystem {
-  pub fn new() -> (AudioSystem, channel::Sender<Effects>) {
-    #[allow(deprecated)]
-      let (tx, rx) = channel::unbounded();
-    let endpoint = rodio::default_output_device().unwrap();
-
-    (AudioSystem {
-      effects: Effects::None,
-      sink: Sink::new(&endpoint),
-      queue: rx,
-    }, tx)
+  pub fn new(reader: ReaderId<GameEvent>) -> AudioSystem {
+    let device = rodio::default_output_device().expect("Audio output device error");
+    let definitions = match parse_manifest(&data::load_json(SOUND_MANIFEST_PATH)) {
+      Ok(definitions) => definitions,
+      Err(e) => panic!("Sound manifest {} error {}", SOUND_MANIFEST_PATH, e),
+    };
+    let sounds = definitions.into_iter()
+      .map(|definition| {
+        let data = definition.paths.iter().map(|path| decode(path).buffered()).collect();
+        (definition.sound, (definition, data))
+      })
+      .collect();
+    let tracks = match parse_playlist(&data::load_json(MUSIC_PLAYLIST_PATH)) {
+      Ok(tracks) => tracks.into_iter().map(|t| (t.track, t)).collect(),
+      Err(e) => panic!("Music playlist {} error {}", MUSIC_PLAYLIST_PATH, e),
+    };
+
+    AudioSystem {
+      device,
+      sounds,
+      next_variant: 0,
+      voices: HashMap::new(),
+      tracks,
+      music: Vec::new(),
+      cutoff: new_cutoff(),
+      cutoff_hz: LOW_PASS_OPEN_HZ as f32,
+      last_frame: Instant::now(),
+      reader,
+    }
   }
 
-  fn play_effect(&mut self) {
-    let file = File::open(PISTOL_AUDIO_PATH).unwrap();
-    let pistol_data = rodio::Decoder::new(BufReader::new(file)).unwrap();
-    if self.sink.empty() {
-      self.sink.append(pistol_data);
+  fn start_music(&self, definition: &TrackDefinition) -> MusicVoice {
+    let layers = Some((definition.path.clone(), None)).into_iter()
+      .chain(definition.stems.iter().map(|stem| (stem.path.clone(), Some(stem.intensity))))
+      .map(|(path, intensity)| {
+        let sink = Sink::new(&self.device);
+        sink.set_volume(0.0);
+        sink.append(filtered(decode(&path), &self.cutoff));
+        MusicLayer { path, intensity, gain: 0.0, sink }
+      })
+      .collect();
+    MusicVoice { track: definition.track, fade: 0.0, layers }
+  }
+
+  // Music is streamed from disk, the wanted track fades in while every other one fades out.
+  // Stems follow the combat intensity within their track.
+  fn update_music(&mut self, mixer: &Mixer, delta: f32) {
+    let wanted = mixer.music.filter(|track| self.tracks.contains_key(track));
+    if let Some(track) = wanted {
+      if !self.music.iter().any(|m| m.track == track) {
+        let voice = self.start_music(&self.tracks[&track]);
+        self.music.push(voice);
+      }
+    }
+
+    let step = delta / MUSIC_CROSSFADE_SECONDS;
+    for voice in &mut self.music {
+      voice.fade = if Some(voice.track) == wanted { (voice.fade + step).min(1.0) } else { (voice.fade - step).max(0.0) };
+      let volume = voice.fade * self.tracks[&voice.track].volume * mixer.bus_gain(Bus::Music);
+      for layer in &mut voice.layers {
+        let target = layer_gain(layer.intensity, mixer.intensity);
+        layer.gain = if layer.gain < target { (layer.gain + step).min(target) } else { (layer.gain - step).max(target) };
+        // Layers loop by streaming the file again once it has ended
+        if layer.sink.empty() {
+          layer.sink.append(filtered(decode(&layer.path), &self.cutoff));
+        }
+        layer.sink.set_volume(volume * layer.gain);
+      }
     }
+    self.music.retain(|m| m.fade > 0.0 || Some(m.track) == wanted);
+  }
+
+  fn update_filter(&mut self, mixer: &Mixer, delta: f32) {
+    let target = if mixer.is_muffled { MUFFLED_CUTOFF_HZ } else { LOW_PASS_OPEN_HZ } as f32;
+    self.cutoff_hz += (target - self.cutoff_hz) * (delta / MUFFLE_FADE_SECONDS).min(1.0);
+    self.cutoff.store(self.cutoff_hz.round() as u32, Ordering::Relaxed);
   }
-}
 
-impl<'a> specs::prelude::System<'a> for AudioSystem {
-  type SystemData = (ReadStorage<'a, CharacterInputState>,
-                     ReadStorage<'a, CharacterDrawable>);
+  // Occlusion closes the voice filter further than the global muffle, never opens it
+  fn voice_cutoff(&self, occlusion: f32) -> u32 {
+    let occluded = self.cutoff_hz.min(OCCLUDED_CUTOFF_HZ as f32);
+    (self.cutoff_hz + (occluded - self.cutoff_hz) * occlusion).round() as u32
+  }
 
-  fn run(&mut self, (character_input, character_drawable): Self::SystemData) {
-    use specs::join::Join;
+  fn gain(&self, sound: Sound, volume: f32, mixer: &Mixer) -> f32 {
+    self.sounds.get(&sound)
+      .map_or(0.0, |(definition, _)| definition.volume * volume * mixer.bus_gain(definition.bus))
+  }
 
-    while let Ok(effect) = self.queue.try_recv() {
-      match effect {
-        Effects::PistolFire => self.effects = Effects::PistolFire,
-        _ => self.effects = Effects::None,
+  // Sounds missing from the manifest are skipped, so events can be wired before their assets exist
+  fn play(&mut self, handle: SoundHandle, sound: Sound, volume: f32, emitter: Option<Position>, mixer: &Mixer) {
+    let gain = self.gain(sound, volume, mixer);
+    if let Some((definition, data)) = self.sounds.get(&sound) {
+      let is_playing = self.voices.values().any(|v| v.sound == sound);
+      if (definition.is_exclusive && is_playing) || self.voices.len() >= MAX_VOICES {
+        return;
       }
+      let (attenuation, placement) = voice_placement(emitter);
+      let occlusion = occlusion_target(emitter, mixer);
+      let cutoff = new_cutoff();
+      cutoff.store(self.voice_cutoff(occlusion), Ordering::Relaxed);
+      let sink = SpatialSink::new(&self.device, placement, LEFT_EAR, RIGHT_EAR);
+      sink.set_volume(gain * attenuation * occlusion_gain(occlusion));
+      sink.append(filtered(data[self.next_variant % data.len()].clone(), &cutoff));
+      self.next_variant += 1;
+      self.voices.insert(handle, Voice { sound, volume, emitter, occlusion, cutoff, sink });
     }
+  }
+}
+
+// Sounds made by the player have no emitter, they are heard at the listener
+fn event_sound(event: &GameEvent) -> Option<(Sound, Option<Position>)> {
+  match *event {
+    GameEvent::BulletFired(_) => Some((Sound::Gunshot, None)),
+    GameEvent::ZombieKilled(position, _) => Some((Sound::Hit, Some(position))),
+    GameEvent::PlayerDamaged(..) => Some((Sound::ZombieGroan, None)),
+    // The cue of the wave banner
+    GameEvent::WaveStarted(_) => Some((Sound::Banner, None)),
+    GameEvent::PickupCollected(position) => Some((Sound::Pickup, Some(position))),
+    GameEvent::ZombieHit(..) | GameEvent::BulletHit(_) | GameEvent::PlayerKilled(_) | GameEvent::Toast(_) => None,
+  }
+}
+
+impl<'a> ecs::System<'a> for AudioSystem {
+  type SystemData = (Write<'a, Mixer>,
+                     Read<'a, GameEvents>);
 
-    for (ci, cd) in (&character_input, &character_drawable).join() {
-      if let Effects::PistolFire = self.effects {
-        if ci.is_shooting && cd.stats.ammunition > 0 { self.play_effect() }
+  fn run(&mut self, (mut mixer, events): Self::SystemData) {
+    for (sound, emitter) in events.read(&mut self.reader).filter_map(event_sound) {
+      match emitter {
+        Some(position) => mixer.play_at(sound, position),
+        None => mixer.play(sound),
+      };
+    }
+
+    for command in std::mem::take(&mut mixer.commands) {
+      match command {
+        MixerCommand::Play(handle, sound, volume, emitter) => self.play(handle, sound, volume, emitter, &mixer),
+        MixerCommand::Stop(handle) => {
+          if let Some(voice) = self.voices.remove(&handle) {
+            voice.sink.stop();
+          }
+        }
+        MixerCommand::SetVolume(handle, volume) => {
+          if let Some(voice) = self.voices.get_mut(&handle) {
+            voice.volume = volume;
+          }
+        }
+        MixerCommand::SetPosition(handle, position) => {
+          if let Some(voice) = self.voices.get_mut(&handle) {
+            voice.emitter = Some(position);
+          }
+        }
       }
     }
+
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+    self.update_filter(&mixer, delta);
+
+    // Gain, pan and occlusion are recomputed every frame, so volume changes and moving emitters reach playing voices
+    let step = (delta / MUFFLE_FADE_SECONDS).min(1.0);
+    let mut voices = std::mem::take(&mut self.voices);
+    for voice in voices.values_mut() {
+      let (attenuation, placement) = voice_placement(voice.emitter);
+      voice.occlusion += (occlusion_target(voice.emitter, &mixer) - voice.occlusion) * step;
+      voice.cutoff.store(self.voice_cutoff(voice.occlusion), Ordering::Relaxed);
+      voice.sink.set_emitter_position(placement);
+      voice.sink.set_volume(self.gain(voice.sound, voice.volume, &mixer) * attenuation * occlusion_gain(voice.occlusion));
+    }
+    voices.retain(|_, voice| !voice.sink.empty());
+    self.voices = voices;
+
+    self.update_music(&mixer, delta);
   }
 }
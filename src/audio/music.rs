@@ -0,0 +1,67 @@
+use json::JsonValue;
+
+use crate::game::constants::MUSIC_STEM_FADE_RANGE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MusicTrack {
+  Menu,
+  Exploration,
+  Combat,
+  Boss,
+}
+
+impl MusicTrack {
+  fn from_name(name: &str) -> Option<MusicTrack> {
+    match name {
+      "menu" => Some(MusicTrack::Menu),
+      "exploration" => Some(MusicTrack::Exploration),
+      "combat" => Some(MusicTrack::Combat),
+      "boss" => Some(MusicTrack::Boss),
+      _ => None,
+    }
+  }
+}
+
+// A stem is layered on top of its track and is fully in at the given combat intensity
+pub struct StemDefinition {
+  pub path: String,
+  pub intensity: f32,
+}
+
+pub struct TrackDefinition {
+  pub track: MusicTrack,
+  pub path: String,
+  pub volume: f32,
+  pub stems: Vec<StemDefinition>,
+}
+
+// The track plays at full gain, a stem fades in over the range below the intensity it is fully in at
+pub fn layer_gain(full_at: Option<f32>, intensity: f32) -> f32 {
+  full_at.map_or(1.0, |full| ((intensity - full) / MUSIC_STEM_FADE_RANGE + 1.0).clamp(0.0, 1.0))
+}
+
+fn parse_stem(name: &str, value: &JsonValue) -> Result<StemDefinition, String> {
+  let path = value["file"].as_str().ok_or_else(|| format!("Track {} has a stem without a file", name))?;
+  Ok(StemDefinition {
+    path: path.to_string(),
+    intensity: value["intensity"].as_f32().unwrap_or(1.0),
+  })
+}
+
+pub fn parse_playlist(value: &JsonValue) -> Result<Vec<TrackDefinition>, String> {
+  value.entries()
+    .map(|(name, entry)| {
+      let track = MusicTrack::from_name(name).ok_or_else(|| format!("Unknown track {}", name))?;
+      let path = entry["file"].as_str().ok_or_else(|| format!("Track {} has no file", name))?;
+      let stems = entry["stems"].members()
+        .map(|stem| parse_stem(name, stem))
+        .collect::<Result<Vec<_>, _>>()?;
+      Ok(TrackDefinition {
+        track,
+        path: path.to_string(),
+        volume: entry["volume"].as_f32().unwrap_or(1.0),
+        stems,
+      })
+    })
+    .collect()
+}
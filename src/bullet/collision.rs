@@ -1,25 +1,156 @@
-use specs;
-use specs::prelude::WriteStorage;
+use std::collections::HashMap;
 
-use crate::bullet::bullets::Bullets;
+use rayon::prelude::*;
 
-pub struct CollisionSystem;
+use crate::bullet::BulletDrawable;
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Entities, Entity, ReadStorage, Write};
+use crate::game::constants::{COLLISION_CELL_SIZE, PARALLEL_MIN_BULLETS, PICKUP_BOX, PLAYER_HIT_BOX, ZOMBIE_HIT_BOX};
+use crate::game::health::Health;
+use crate::game::scratch::{ScratchRows, VecPool};
+use crate::graphics::{can_move, can_move_to_tile, overlaps};
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::ZombieDrawable;
 
-#[derive(Clone, PartialEq)]
+// The spatial grid, the lists of the step's zombies, bullets and players and the contact lists of
+// the bullets are kept between steps, they are emptied instead of dropped
+#[derive(Default)]
+pub struct CollisionSystem {
+  grid: SpatialGrid,
+  zombies: Vec<(Entity, Position)>,
+  bullets: Vec<(Entity, Position)>,
+  players: Vec<(usize, Position)>,
+  bullet_contacts: ScratchRows<Contact>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Collision {
   Flying,
   Hit,
   OutOfBounds,
 }
 
-impl<'a> specs::prelude::System<'a> for CollisionSystem {
-  type SystemData = (WriteStorage<'a, Bullets>);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Contact {
+  BulletZombie { bullet: Entity, zombie: Entity },
+  ZombiePlayer { zombie: Entity, player: usize },
+  PlayerPickup { pickup: Entity, player: usize },
+  BulletTile { bullet: Entity, collision: Collision },
+}
+
+#[derive(Default)]
+pub struct Contacts(pub Vec<Contact>);
+
+// Buckets positions into square cells so a query only visits the surrounding cells
+#[derive(Default)]
+pub struct SpatialGrid {
+  cells: HashMap<(i32, i32), Vec<usize>>,
+  // Emptied cells, handed to the occupied cells of the next rebuild
+  spare: VecPool<usize>,
+}
+
+impl SpatialGrid {
+  pub fn rebuild<I>(&mut self, positions: I)
+    where I: Iterator<Item=(usize, Position)> {
+    for (_, indices) in self.cells.drain() {
+      self.spare.give(indices);
+    }
+    for (idx, position) in positions {
+      let spare = &mut self.spare;
+      self.cells.entry(SpatialGrid::cell(position)).or_insert_with(|| spare.take()).push(idx);
+    }
+  }
+
+  fn cell(position: Position) -> (i32, i32) {
+    ((position.x() / COLLISION_CELL_SIZE).floor() as i32, (position.y() / COLLISION_CELL_SIZE).floor() as i32)
+  }
 
-  fn run(&mut self, mut bullets: Self::SystemData) {
-    use specs::join::Join;
+  // Callers keep query extents below the cell size
+  pub fn nearby(&self, position: Position) -> impl Iterator<Item=usize> + '_ {
+    let (x, y) = SpatialGrid::cell(position);
+    (x - 1..=x + 1)
+      .flat_map(move |cx| (y - 1..=y + 1).map(move |cy| (cx, cy)))
+      .filter_map(move |key| self.cells.get(&key))
+      .flat_map(|indices| indices.iter().cloned())
+  }
+}
+
+impl<'a> ecs::System<'a> for CollisionSystem {
+  type SystemData = (Entities<'a>,
+                     ReadStorage<'a, BulletDrawable>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, Health>,
+                     Write<'a, Contacts>);
+
+  fn run(&mut self, (entities, bullets, zombies, terrain_objects, character_input, players, health, mut contacts): Self::SystemData) {
+    use crate::ecs::Join;
+
+    contacts.0.clear();
+
+    // Everything is placed relative to the first player, a dedicated server has no player 0 and
+    // goes by the lowest index that joined
+    let ci = match (&character_input, &players).join().min_by_key(|(_, p)| p.index) {
+      Some((ci, _)) => ci,
+      None => return,
+    };
+
+    self.zombies.clear();
+    self.zombies.extend((&entities, &zombies).join()
+      .filter(|(_, z)| !z.is_dead())
+      .map(|(e, z)| (e, z.transform.position)));
+    self.bullets.clear();
+    self.bullets.extend((&entities, &bullets).join().map(|(e, b)| (e, b.transform.position)));
+    // The other players stand as far from the origin as the first one's movement is ahead of theirs,
+    // the dead ones are out of reach
+    self.players.clear();
+    self.players.extend((&character_input, &players, &health).join()
+      .filter(|(_, _, h)| !h.is_dead())
+      .map(|(other, p, _)| (p.index, ci.movement - other.movement)));
+    self.players.sort_unstable_by_key(|&(index, _)| index);
+    self.grid.rebuild(self.zombies.iter().map(|&(_, position)| position).enumerate());
+    let (grid, zombies) = (&self.grid, &self.zombies);
+
+    let bullet_contacts = |&(bullet, position): &(Entity, Position)| {
+      let tile = if !can_move(position) {
+        Some(Contact::BulletTile { bullet, collision: Collision::OutOfBounds })
+      } else if !can_move_to_tile(ci.movement - position) {
+        Some(Contact::BulletTile { bullet, collision: Collision::Hit })
+      } else {
+        None
+      };
+      tile.into_iter().chain(grid.nearby(position)
+        .filter(move |&idx| overlaps(zombies[idx].1, position, ZOMBIE_HIT_BOX[0], ZOMBIE_HIT_BOX[1]))
+        .map(move |idx| Contact::BulletZombie { bullet, zombie: zombies[idx].0 }))
+    };
+    // The contacts keep the order of the bullets either way, hits are applied the same. Split up,
+    // every bullet fills a list of its own that is joined in order afterwards.
+    if self.bullets.len() < PARALLEL_MIN_BULLETS {
+      contacts.0.extend(self.bullets.iter().flat_map(bullet_contacts));
+    } else {
+      let rows = self.bullet_contacts.rows(self.bullets.len());
+      rows.par_iter_mut().zip(self.bullets.par_iter())
+        .for_each(|(row, bullet)| row.extend(bullet_contacts(bullet)));
+      contacts.0.extend(rows.iter().flatten().cloned());
+    }
+
+    for &(player, at) in &self.players {
+      for idx in grid.nearby(at) {
+        let (zombie, position) = zombies[idx];
+        if overlaps(at, position, PLAYER_HIT_BOX[0], PLAYER_HIT_BOX[1]) {
+          contacts.0.push(Contact::ZombiePlayer { zombie, player });
+        }
+      }
+    }
 
-    for bs in (&mut bullets).join() {
-      Bullets::remove_old_bullets(bs);
+    // A pickup reached by several players goes to the first of them
+    for (pickup, o) in (&entities, &terrain_objects).join().filter(|(_, o)| o.object_type == TerrainTexture::Ammo) {
+      if let Some(&(player, _)) = self.players.iter().find(|&&(_, at)| overlaps(at, o.transform.position, PICKUP_BOX[0], PICKUP_BOX[1])) {
+        contacts.0.push(Contact::PlayerPickup { pickup, player });
+      }
     }
   }
 }
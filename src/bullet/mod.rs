@@ -3,20 +3,16 @@ use std::f32::consts::PI;
 
 use cgmath::Point2;
 use gfx;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
-
-use crate::bullet::bullets::Bullets;
-use crate::bullet::collision::Collision;
-use crate::character::controls::CharacterInputState;
-use crate::game::constants::{ASPECT_RATIO, BULLET_SPEED, VIEW_DISTANCE};
-use crate::gfx_app::{ColorFormat, DepthFormat};
-use crate::graphics::{camera::CameraInputState, can_move, dimensions::{Dimensions, get_projection, get_view_matrix}};
-use crate::graphics::can_move_to_tile;
+
+use crate::bullet::collision::{Collision, Contact, Contacts};
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Entities, Read, ReadStorage, WriteStorage};
+use crate::game::constants::BULLET_SPEED;
+use crate::graphics::{camera::CameraInputState, DeltaTime, dimensions::Dimensions, direction_movement, render_graph::{ConstantCache, PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader}, viewport::View};
 use crate::graphics::mesh::PlainMesh;
-use crate::shaders::{bullet_pipeline, Position, Projection, Rotation};
+use crate::graphics::transform::Transform;
+use crate::shaders::{bullet_pipeline, Position, Rotation};
 
-pub mod bullets;
 pub mod collision;
 
 const SHADER_VERT: &[u8] = include_bytes!("../shaders/bullet.v.glsl");
@@ -26,118 +22,129 @@ const SCALING_FACTOR: f32 = 5.0 / 3.0;
 
 #[derive(PartialEq)]
 pub struct BulletDrawable {
-  projection: Projection,
-  pub position: Position,
-  pub rotation: Rotation,
-  previous_position: Position,
-  offset_delta: Position,
+  pub transform: Transform,
   pub movement_direction: Point2<f32>,
   pub status: collision::Collision,
 }
 
 impl BulletDrawable {
-  pub fn new(position: Position, movement_direction: Point2<f32>, direction: f32) -> BulletDrawable {
-    let view = get_view_matrix(VIEW_DISTANCE);
-    let projection = get_projection(view, ASPECT_RATIO);
-    let rotation = Rotation::new(direction * PI / 180.0);
+  pub fn new(position: Position, direction: f32) -> BulletDrawable {
     BulletDrawable {
-      projection,
-      position,
-      rotation,
-      previous_position: Position::origin(),
-      offset_delta: Position::origin(),
-      movement_direction,
+      transform: Transform::new(position).with_rotation(direction * PI / 180.0),
+      movement_direction: direction_movement(direction),
       status: Collision::Flying,
     }
   }
 
-  pub fn update(&mut self, world_to_clip: &Projection, ci: &CharacterInputState) {
-    self.projection = *world_to_clip;
-
-    self.offset_delta =
-      if (ci.movement.x() - self.previous_position.x()).abs() > f32::EPSILON ||
-        (ci.movement.y() - self.previous_position.y()).abs() > f32::EPSILON {
-        ci.movement - self.previous_position
-      } else {
-        self.offset_delta
-      };
-
-    self.previous_position = Position::new(
-      ci.movement.x() - (self.movement_direction.x * BULLET_SPEED / SCALING_FACTOR),
-      ci.movement.y() + (self.movement_direction.y * BULLET_SPEED));
-
-    self.position = self.position + self.offset_delta +
-      Position::new(self.movement_direction.x * BULLET_SPEED / SCALING_FACTOR, -self.movement_direction.y * BULLET_SPEED);
-
-    let tile_pos = ci.movement - self.position;
-
-    self.status = if !can_move(self.position) {
-      Collision::OutOfBounds
-    } else if !can_move_to_tile(tile_pos) {
-      Collision::Hit
-    } else {
-      Collision::Flying
-    }
+  pub fn update(&mut self, ci: &CharacterInputState, delta: f32) {
+    let speed = BULLET_SPEED * delta;
+    self.transform.follow_camera(ci);
+    self.transform.translate(Position::new(self.movement_direction.x * speed / SCALING_FACTOR, -self.movement_direction.y * speed));
   }
 }
 
+impl ecs::Component for BulletDrawable {
+  type Storage = ecs::DenseVecStorage<BulletDrawable>;
+}
+
 pub struct BulletDrawSystem<R: gfx::Resources> {
   bundle: gfx::pso::bundle::Bundle<R, bullet_pipeline::Data<R>>,
+  pso_updates: PipelineUpdates<R, bullet_pipeline::Meta>,
+  position: ConstantCache<Position>,
+  rotation: ConstantCache<Rotation>,
 }
 
 impl<R: gfx::Resources> BulletDrawSystem<R> {
   pub fn new<F>(factory: &mut F,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>) -> BulletDrawSystem<R>
+                targets: &RenderTargets<R>,
+                reloader: &mut ShaderReloader<F>) -> BulletDrawSystem<R>
     where F: gfx::Factory<R> {
     use gfx::traits::FactoryExt;
 
     let mesh = PlainMesh::new_with_data(factory, Point2::new(2.4, 0.8), None, None, None);
 
-    let pso = factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, bullet_pipeline::new())
-      .expect("Bullet shader loading error");
+    let (pso, pso_updates) = PassDescriptor::new("Bullet",
+                                                 ShaderFile::new("bullet.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("bullet.f.glsl", SHADER_FRAG),
+                                                 bullet_pipeline::new())
+      .build(factory, reloader);
 
     let pipeline_data = bullet_pipeline::Data {
       vbuf: mesh.vertex_buffer,
-      projection_cb: factory.create_constant_buffer(1),
+      globals: targets.globals.clone(),
       position_cb: factory.create_constant_buffer(1),
       rotation_cb: factory.create_constant_buffer(1),
-      out_color: rtv,
-      out_depth: dsv,
+      scissor: targets.scissor(),
+      out_color: targets.color.clone(),
+      out_depth: targets.depth.clone(),
     };
 
     BulletDrawSystem {
       bundle: gfx::Bundle::new(mesh.slice, pso, pipeline_data),
+      pso_updates,
+      position: ConstantCache::default(),
+      rotation: ConstantCache::default(),
     }
   }
 
   pub fn draw<C>(&mut self,
                  drawable: &BulletDrawable,
+                 view: &View,
                  encoder: &mut gfx::Encoder<R, C>)
     where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.projection_cb, &drawable.projection);
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    encoder.update_constant_buffer(&self.bundle.data.rotation_cb, &drawable.rotation);
+    if !view.is_visible(drawable.transform.position) {
+      return;
+    }
+    self.pso_updates.apply(&mut self.bundle.pso);
+    self.bundle.data.scissor = view.scissor;
+    self.position.update(&self.bundle.data.position_cb, view.position(drawable.transform.position), encoder);
+    self.rotation.update(&self.bundle.data.rotation_cb, Rotation::new(drawable.transform.rotation), encoder);
     self.bundle.encode(encoder);
   }
 }
 
 pub struct PreDrawSystem;
 
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
+impl<'a> ecs::System<'a> for PreDrawSystem {
   type SystemData = (ReadStorage<'a, CameraInputState>,
-                     WriteStorage<'a, Bullets>,
+                     WriteStorage<'a, BulletDrawable>,
                      ReadStorage<'a, CharacterInputState>,
-                     Read<'a, Dimensions>);
-
-  fn run(&mut self, (camera_input, mut bullets, character_input, dim): Self::SystemData) {
-    use specs::join::Join;
-
-    for (camera, bs, ci) in (&camera_input, &mut bullets, &character_input).join() {
-      let world_to_clip = dim.world_to_projection(camera);
+                     ReadStorage<'a, Player>,
+                     Read<'a, Dimensions>,
+                     Read<'a, DeltaTime>);
+
+  fn run(&mut self, (camera_input, mut bullets, character_input, players, dim, dt): Self::SystemData) {
+    use crate::ecs::Join;
+
+    // Bullets are placed relative to the first player and culled by its view
+    if let Some((camera, ci, _)) = (&camera_input, &character_input, &players).join().min_by_key(|(_, _, p)| p.index) {
+      for b in (&mut bullets).join() {
+        // Bullets out of view stay where they are until the camera comes back to them
+        if dim.split_screen || camera.is_visible(b.transform.position) {
+          b.update(ci, dt.0 as f32);
+        } else {
+          b.transform.follow_camera(ci);
+        }
+      }
+    }
+  }
+}
 
-      for b in &mut bs.bullets {
-        b.update(&world_to_clip, ci);
+// Bullets that hit a wall or left the map are despawned with the step
+pub struct ImpactSystem;
+
+impl<'a> ecs::System<'a> for ImpactSystem {
+  type SystemData = (Entities<'a>,
+                     WriteStorage<'a, BulletDrawable>,
+                     Read<'a, Contacts>);
+
+  fn run(&mut self, (entities, mut bullets, contacts): Self::SystemData) {
+    for contact in &contacts.0 {
+      if let Contact::BulletTile { bullet, collision } = *contact {
+        if let Some(b) = bullets.get_mut(bullet) {
+          b.status = collision;
+          entities.delete(bullet).expect("Bullet despawn error");
+        }
       }
     }
   }
@@ -1,14 +1,63 @@
+use crate::game::constants::{MAGAZINE_SIZE, MAX_SPREAD, RELOAD_DURATION, SHOT_SPREAD, SPREAD_RECOVERY};
+
 #[derive(Clone, Default)]
 pub struct CharacterStats {
   pub ammunition: usize,
   pub magazines: usize,
+  // Seconds left of the reload in progress
+  reload_remaining: f32,
+  // Degrees the next shot can stray from the aim
+  pub spread: f32,
 }
 
 impl CharacterStats {
   pub fn new() -> CharacterStats {
     CharacterStats {
-      ammunition: 10,
+      ammunition: MAGAZINE_SIZE,
       magazines: 1,
+      reload_remaining: 0.0,
+      spread: 0.0,
+    }
+  }
+
+  pub fn is_reloading(&self) -> bool {
+    self.reload_remaining > 0.0
+  }
+
+  pub fn can_reload(&self) -> bool {
+    self.magazines > 0 && self.ammunition < MAGAZINE_SIZE && !self.is_reloading()
+  }
+
+  pub fn start_reload(&mut self) {
+    self.reload_remaining = RELOAD_DURATION;
+  }
+
+  pub fn fire(&mut self) {
+    self.ammunition -= 1;
+    self.spread = (self.spread + SHOT_SPREAD).min(MAX_SPREAD);
+  }
+
+  pub fn recover_spread(&mut self, delta: f32) {
+    self.spread = (self.spread - SPREAD_RECOVERY * delta).max(0.0);
+  }
+
+  // The magazine is swapped when the reload finishes
+  pub fn update_reload(&mut self, delta: f32) {
+    if self.is_reloading() {
+      self.reload_remaining -= delta;
+      if !self.is_reloading() {
+        self.ammunition = MAGAZINE_SIZE;
+        self.magazines = self.magazines.saturating_sub(1);
+      }
+    }
+  }
+
+  // From 0 to 1 while reloading
+  pub fn reload_progress(&self) -> Option<f32> {
+    if self.is_reloading() {
+      Some(1.0 - self.reload_remaining / RELOAD_DURATION)
+    } else {
+      None
     }
   }
 }
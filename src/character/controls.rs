@@ -1,14 +1,15 @@
 use crossbeam_channel as channel;
-use specs;
-use specs::prelude::{Read, WriteStorage};
 
-use crate::character::CharacterDrawable;
+use crate::character::{CharacterDrawable, Player};
+use crate::ecs::{self, Read, ReadStorage, Write, WriteStorage};
 use crate::game::constants::{CHARACTER_X_SPEED, CHARACTER_Y_SPEED};
+use crate::game::input_buffer::{BufferedAction, InputBuffer};
 use crate::graphics::{camera::CameraInputState, can_move_to_tile, DeltaTime, orientation::{Orientation, Stance}};
 use crate::shaders::Position;
 
 pub struct CharacterInputState {
   pub movement: Position,
+  pub previous_movement: Position,
   pub orientation: Orientation,
   pub is_colliding: bool,
   pub is_shooting: bool,
@@ -18,17 +19,21 @@ impl CharacterInputState {
   pub fn new() -> CharacterInputState {
     CharacterInputState {
       movement: Position::origin(),
+      previous_movement: Position::origin(),
       orientation: Orientation::Normal,
       is_colliding: false,
       is_shooting: false,
     }
   }
 
-  pub fn update(&mut self, camera: &mut CameraInputState, css: &CharacterControlSystem) {
-    if css.y_move.is_none() && css.x_move.is_none() {
+  pub fn update(&mut self, camera: &mut CameraInputState, css: &CharacterControlSystem, delta: f32) {
+    let x_move = css.x_move.map(|x| x * delta);
+    let y_move = css.y_move.map(|y| y * delta);
+
+    if y_move.is_none() && x_move.is_none() {
       self.orientation = Orientation::Normal;
-    } else if css.x_move.is_none() {                  // Horizontal/vertical movement
-      if let Some(y) = css.y_move {
+    } else if x_move.is_none() {                  // Horizontal/vertical movement
+      if let Some(y) = y_move {
         let vertical_movement = self.movement + Position::new(0.0, y);
         if !self.is_colliding || can_move_to_tile(vertical_movement) {
           self.movement = vertical_movement;
@@ -40,9 +45,9 @@ impl CharacterInputState {
           };
         }
       }
-    } else if let Some(x) = css.x_move {        // Diagonal movement
+    } else if let Some(x) = x_move {        // Diagonal movement
       let horizontal_move = self.movement + Position::new(x, 0.0);
-      if let Some(y) = css.y_move {
+      if let Some(y) = y_move {
         let horizontal_movement = Position::new(x / 1.5, 0.0);
         let vertical_movement = Position::new(0.0, y / 1.666);
         if !self.is_colliding || can_move_to_tile(self.movement + horizontal_movement + vertical_movement) {
@@ -57,7 +62,7 @@ impl CharacterInputState {
             _ => Orientation::Normal,
           };
         }
-      } else if css.y_move.is_none() && !self.is_colliding || can_move_to_tile(horizontal_move) {
+      } else if y_move.is_none() && !self.is_colliding || can_move_to_tile(horizontal_move) {
         let horizontal_movement = Position::new(x, 0.0);
         self.movement = self.movement + horizontal_movement;
         camera.movement = camera.movement + horizontal_movement;
@@ -78,10 +83,11 @@ impl Default for CharacterInputState {
   }
 }
 
-impl specs::prelude::Component for CharacterInputState {
-  type Storage = specs::storage::VecStorage<CharacterInputState>;
+impl ecs::Component for CharacterInputState {
+  type Storage = ecs::DenseVecStorage<CharacterInputState>;
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CharacterControl {
   Left,
   Right,
@@ -97,37 +103,44 @@ pub enum CharacterControl {
 
 pub struct CharacterControlSystem {
   queue: channel::Receiver<CharacterControl>,
+  player_index: usize,
   x_move: Option<f32>,
   y_move: Option<f32>,
   cool_down: f64,
   is_ctrl_pressed: bool,
-  is_reloading: bool,
 }
 
 impl CharacterControlSystem {
-  pub fn new() -> (CharacterControlSystem, channel::Sender<CharacterControl>) {
+  pub fn new(player_index: usize) -> (CharacterControlSystem, channel::Sender<CharacterControl>) {
     let (tx, rx) = channel::unbounded();
     (CharacterControlSystem {
       queue: rx,
+      player_index,
       x_move: None,
       y_move: None,
       cool_down: 1.0,
       is_ctrl_pressed: false,
-      is_reloading: false,
     }, tx)
   }
 }
 
-impl<'a> specs::prelude::System<'a> for CharacterControlSystem {
+impl<'a> ecs::System<'a> for CharacterControlSystem {
   type SystemData = (WriteStorage<'a, CharacterInputState>,
                      WriteStorage<'a, CharacterDrawable>,
                      WriteStorage<'a, CameraInputState>,
-                     Read<'a, DeltaTime>);
+                     ReadStorage<'a, Player>,
+                     Read<'a, DeltaTime>,
+                     Write<'a, InputBuffer>);
 
-  fn run(&mut self, (mut character_input, mut character, mut camera_input, d): Self::SystemData) {
-    use specs::join::Join;
+  fn run(&mut self, (mut character_input, mut character, mut camera_input, players, d, mut buffer): Self::SystemData) {
+    use crate::ecs::Join;
 
     let delta = d.0;
+    let player_index = self.player_index;
+
+    for (ci, _) in (&mut character_input, &players).join().filter(|(_, p)| p.index == player_index) {
+      ci.previous_movement = ci.movement;
+    }
 
     if self.cool_down == 0.0 {
       self.cool_down += 0.1;
@@ -143,18 +156,20 @@ impl<'a> specs::prelude::System<'a> for CharacterControlSystem {
           CharacterControl::XMoveStop => self.x_move = None,
           CharacterControl::CtrlPressed => self.is_ctrl_pressed = true,
           CharacterControl::CtrlReleased => self.is_ctrl_pressed = false,
-          CharacterControl::ReloadPressed => self.is_reloading = true,
-          CharacterControl::ReloadReleased => self.is_reloading = false,
+          CharacterControl::ReloadPressed => buffer.press(player_index, BufferedAction::Reload),
+          CharacterControl::ReloadReleased => (),
         }
       }
 
-      for (ci, c, camera) in (&mut character_input, &mut character, &mut camera_input).join() {
+      for (ci, c, camera, _) in (&mut character_input, &mut character, &mut camera_input, &players).join()
+        .filter(|(_, _, _, p)| p.index == player_index) {
         if c.stance != Stance::NormalDeath {
-          ci.update(camera, self);
+          ci.update(camera, self, delta as f32);
         }
-        if self.is_reloading && c.stats.magazines > 0 && c.stats.ammunition < 10 {
-          c.stats.ammunition = 10;
-          c.stats.magazines -= 1;
+        c.stats.update_reload(delta as f32);
+        c.stats.recover_spread(delta as f32);
+        if c.stats.can_reload() && buffer.consume(player_index, |a| a == BufferedAction::Reload).is_some() {
+          c.stats.start_reload();
         }
       }
     }
@@ -1,82 +1,67 @@
-use std;
-
 use cgmath::Point2;
 use gfx;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
 
+use crate::bullet::collision::{Contact, Contacts};
 use crate::character::{character_stats::CharacterStats, controls::CharacterInputState};
-use crate::critter::{CharacterSprite, CritterData};
-use crate::data;
-use crate::game::constants::{AMMO_POSITIONS, ASPECT_RATIO, CHARACTER_SHEET_TOTAL_WIDTH, RUN_SPRITE_OFFSET, SPRITE_OFFSET, VIEW_DISTANCE, SMALL_HILLS};
-use crate::gfx_app::{ColorFormat, DepthFormat};
+use crate::critter::{CharacterSprite, palette::CritterPalette};
+use crate::data::{self, atlas::SpriteSheet};
+use crate::ecs::{self, Entities, Read, ReadStorage, Write, WriteStorage};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::health::Health;
+use crate::game::settings::Settings;
+use crate::game::constants::{MAX_MAGAZINES, SMALL_HILLS, ZOMBIE_ATTACK_DAMAGE};
 use crate::gfx_app::mouse_controls::MouseInputState;
-use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, get_projection, get_view_matrix}, get_orientation_from_center, orientation::{Orientation, Stance}, overlaps, texture::load_texture, check_terrain_elevation};
+use crate::graphics::{DeltaTime, dimensions::Dimensions, get_orientation_from_center, orientation::{Orientation, Stance}, check_terrain_elevation};
+use crate::graphics::render_graph::{CritterPass, RenderTargets, ShaderReloader};
+use crate::graphics::viewport::View;
 use crate::graphics::mesh::{RectangularTexturedMesh, Geometry};
 use crate::graphics::texture::Texture;
-use crate::shaders::{CharacterSheet, critter_pipeline, Position, Projection};
-use crate::terrain_object::{terrain_objects::TerrainObjects, TerrainObjectDrawable, TerrainTexture};
-use crate::zombie::{ZombieDrawable, zombies::Zombies};
+use crate::graphics::assets::{Assets, TextureAsset};
+use crate::graphics::transform::{StepCadence, Transform};
+use crate::shaders::{Position, SpriteFrame};
+use crate::terrain_object::TerrainObjectDrawable;
+use crate::zombie::ZombieDrawable;
 
 pub mod controls;
-mod character_stats;
-
-const SHADER_VERT: &[u8] = include_bytes!("../shaders/character.v.glsl");
-const SHADER_FRAG: &[u8] = include_bytes!("../shaders/character.f.glsl");
+pub mod character_stats;
 
 #[derive(Clone)]
 pub struct CharacterDrawable {
   pub stats: CharacterStats,
-  projection: Projection,
   pub position: Position,
-  orientation: Orientation,
+  pub orientation: Orientation,
   pub stance: Stance,
   direction: Orientation,
+  pub steps: StepCadence,
+  // Picked in the lobby
+  pub skin: CritterPalette,
+}
+
+// What the characters need to know about the zombies reaching them this step
+pub struct ZombieHits<'a> {
+  pub contacts: &'a Contacts,
+  pub zombies: &'a ReadStorage<'a, ZombieDrawable>,
+  pub damage_scale: f32,
+  pub delta: f32,
 }
 
 impl CharacterDrawable {
   pub fn new() -> CharacterDrawable {
-    let view = get_view_matrix(VIEW_DISTANCE);
-    let projection = get_projection(view, ASPECT_RATIO);
     let stats = CharacterStats::new();
     CharacterDrawable {
       stats,
-      projection,
       position: Position::origin(),
       orientation: Orientation::Right,
       stance: Stance::Walking,
       direction: Orientation::Right,
+      steps: StepCadence::default(),
+      skin: CritterPalette::Original,
     }
   }
 
-  pub fn update(&mut self, world_to_clip: &Projection, ci: &CharacterInputState, mouse_input: &MouseInputState,
-                dimensions: &Dimensions, objs: &mut Vec<TerrainObjectDrawable>, zombies: &[ZombieDrawable]) {
-    self.projection = *world_to_clip;
-
+  pub fn update(&mut self, ci: &CharacterInputState, mouse_input: &MouseInputState, dimensions: &Dimensions) {
     self.position.position[1] = check_terrain_elevation(ci.movement - self.position, &SMALL_HILLS);
 
-    fn zombie_not_dead(z: &ZombieDrawable) -> bool {
-      z.stance != Stance::NormalDeath &&
-        z.stance != Stance::CriticalDeath
-    }
-
-    for idx in 0..AMMO_POSITIONS.len() {
-      self.ammo_pick_up(ci.movement, objs, idx);
-    }
-
-    if !cfg!(feature = "godmode") &&
-      zombies.iter()
-        .any(|z|
-          zombie_not_dead(z) &&
-            overlaps(ci.movement,
-                     ci.movement - z.position,
-                     15.0,
-                     30.0)) {
-      self.stance = Stance::NormalDeath;
-      println!("Player died");
-      std::process::exit(0);
-    }
-
     if ci.is_shooting && mouse_input.left_click_point.is_some() && !ci.is_colliding {
       self.stance = Stance::Firing;
       self.orientation = get_orientation_from_center(mouse_input, dimensions);
@@ -88,10 +73,45 @@ impl CharacterDrawable {
     }
   }
 
-  fn ammo_pick_up(&mut self, movement: Position, objs: &mut Vec<TerrainObjectDrawable>, idx: usize) {
-    if objs.len() > idx && objs[idx].object_type == TerrainTexture::Ammo && overlaps(movement, movement - objs[idx].position, 20.0, 20.0) {
-      self.stats.magazines = 2;
-      objs.remove(idx);
+  // The offset is where the player stands relative to the first one, the attacker is reported
+  // relative to the player
+  pub fn take_zombie_hits(&mut self, player: usize, offset: Position, hits: &ZombieHits, health: &mut Health, events: &mut GameEvents) {
+    let attacker = hits.contacts.0.iter()
+      .find_map(|c| match *c {
+        Contact::ZombiePlayer { zombie, player: hit } if hit == player => hits.zombies.get(zombie).map(|z| z.transform.position - offset),
+        _ => None,
+      });
+    let attacker = match attacker {
+      Some(attacker) => attacker,
+      None => return,
+    };
+    let damage = if cfg!(feature = "godmode") { 0.0 } else { ZOMBIE_ATTACK_DAMAGE * hits.damage_scale * hits.delta };
+    events.single_write(GameEvent::PlayerDamaged(player, attacker, damage));
+
+    if health.take_damage(damage) {
+      self.stance = Stance::NormalDeath;
+      events.single_write(GameEvent::PlayerKilled(player));
+    }
+  }
+
+  pub fn update_companion(&mut self, ci: &CharacterInputState, main_movement: Position) {
+    let position = main_movement - ci.movement;
+    self.position = position + Position::new(0.0, check_terrain_elevation(ci.movement, &SMALL_HILLS));
+    self.orientation = ci.orientation;
+    self.stance = if ci.is_colliding { Stance::Still } else { Stance::Walking };
+  }
+
+  // The pickup is despawned with the step
+  fn ammo_pick_up(&mut self, player: usize, objects: &ReadStorage<TerrainObjectDrawable>, entities: &Entities, contacts: &Contacts, events: &mut GameEvents) {
+    let pick_up = contacts.0.iter()
+      .find_map(|c| match *c {
+        Contact::PlayerPickup { pickup, player: picker } if picker == player => objects.get(pickup).map(|o| (pickup, o.transform.position)),
+        _ => None,
+      });
+    if let Some((pickup, position)) = pick_up {
+      self.stats.magazines = MAX_MAGAZINES;
+      events.single_write(GameEvent::PickupCollected(position));
+      entities.delete(pickup).expect("Pickup despawn error");
     }
   }
 }
@@ -102,102 +122,113 @@ impl Default for CharacterDrawable {
   }
 }
 
-impl specs::prelude::Component for CharacterDrawable {
-  type Storage = specs::storage::VecStorage<CharacterDrawable>;
+impl ecs::Component for CharacterDrawable {
+  type Storage = ecs::DenseVecStorage<CharacterDrawable>;
+}
+
+pub struct Player {
+  pub index: usize,
+}
+
+impl Player {
+  pub fn new(index: usize) -> Player {
+    Player {
+      index,
+    }
+  }
+}
+
+impl ecs::Component for Player {
+  type Storage = ecs::DenseVecStorage<Player>;
 }
 
 pub struct CharacterDrawSystem<R: gfx::Resources> {
-  bundle: gfx::pso::bundle::Bundle<R, critter_pipeline::Data<R>>,
-  data: Vec<CritterData>,
+  pass: CritterPass<R>,
+  sheet: SpriteSheet,
 }
 
 impl<R: gfx::Resources> CharacterDrawSystem<R> {
   pub fn new<F>(factory: &mut F,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>) -> CharacterDrawSystem<R>
+                targets: &RenderTargets<R>,
+                assets: &Assets<R>,
+                reloader: &mut ShaderReloader<F>) -> CharacterDrawSystem<R>
     where F: gfx::Factory<R> {
-    use gfx::traits::FactoryExt;
-
-    let charter_bytes = &include_bytes!("../../assets/character.png")[..];
-    let char_texture = load_texture(factory, charter_bytes);
+    let char_texture = assets.texture(TextureAsset::Character);
 
     let rect_mesh =
       RectangularTexturedMesh::new(factory, Texture::new(char_texture, None), Geometry::Rectangle, Point2::new(20.0, 28.0), None, None, None);
 
-    let pso = factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, critter_pipeline::new())
-      .expect("Character shader loading error");
-
-    let pipeline_data = critter_pipeline::Data {
-      vbuf: rect_mesh.mesh.vertex_buffer,
-      projection_cb: factory.create_constant_buffer(1),
-      position_cb: factory.create_constant_buffer(1),
-      character_sprite_cb: factory.create_constant_buffer(1),
-      charactersheet: (rect_mesh.mesh.texture.raw, factory.create_sampler_linear()),
-      out_color: rtv,
-      out_depth: dsv,
-    };
-
-    let data = data::load_character();
+    let sheet = data::load_character();
 
     CharacterDrawSystem {
-      bundle: gfx::Bundle::new(rect_mesh.mesh.slice, pso, pipeline_data),
-      data,
+      pass: CritterPass::new(factory, targets, "Character", rect_mesh, reloader),
+      sheet,
     }
   }
 
-  fn get_next_sprite(&self, character_idx: usize, character_fire_idx: usize, drawable: &mut CharacterDrawable) -> CharacterSheet {
-    let sprite_idx =
-      if drawable.orientation == Orientation::Normal && drawable.stance == Stance::Walking {
-        (drawable.direction as usize * 28 + RUN_SPRITE_OFFSET)
-      } else if drawable.stance == Stance::Walking {
-        drawable.direction = drawable.orientation;
-        (drawable.orientation as usize * 28 + character_idx + RUN_SPRITE_OFFSET)
-      } else {
-        (drawable.orientation as usize * 8 + character_fire_idx)
-      } as usize;
-
-    let elements_x = CHARACTER_SHEET_TOTAL_WIDTH / (self.data[sprite_idx].data[2] + SPRITE_OFFSET);
-    CharacterSheet {
-      x_div: elements_x,
-      y_div: 0.0,
-      row_idx: 0,
-      index: sprite_idx as f32,
+  // The sheet has 16 directions, every second one is drawn
+  fn get_next_sprite(&self, character_idx: usize, character_fire_idx: usize, drawable: &mut CharacterDrawable) -> SpriteFrame {
+    if drawable.orientation == Orientation::Normal && drawable.stance == Stance::Walking {
+      self.sheet.frame("run", drawable.direction as usize * 2, 0)
+    } else if drawable.stance == Stance::Walking {
+      drawable.direction = drawable.orientation;
+      self.sheet.frame("run", drawable.orientation as usize * 2, character_idx)
+    } else {
+      self.sheet.frame("fire", drawable.orientation as usize * 2, character_fire_idx)
     }
   }
 
   pub fn draw<C>(&mut self,
-                 mut drawable: &mut CharacterDrawable,
+                 drawable: &mut CharacterDrawable,
                  character: &CharacterSprite,
+                 view: &View,
                  encoder: &mut gfx::Encoder<R, C>)
     where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.projection_cb, &drawable.projection);
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    encoder.update_constant_buffer(&self.bundle.data.character_sprite_cb,
-                                   &self.get_next_sprite(character.character_idx,
-                                                         character.character_fire_idx,
-                                                         &mut drawable));
-    self.bundle.encode(encoder);
+    let sprite = self.get_next_sprite(character.character_idx, character.character_fire_idx, drawable);
+    self.pass.draw(drawable.position, &sprite, drawable.skin, view, encoder);
   }
 }
 
 pub struct PreDrawSystem;
 
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
-  type SystemData = (WriteStorage<'a, CharacterDrawable>,
-                     ReadStorage<'a, CameraInputState>,
+impl<'a> ecs::System<'a> for PreDrawSystem {
+  type SystemData = (Entities<'a>,
+                     WriteStorage<'a, CharacterDrawable>,
                      ReadStorage<'a, CharacterInputState>,
                      ReadStorage<'a, MouseInputState>,
-                     WriteStorage<'a, TerrainObjects>,
-                     ReadStorage<'a, Zombies>,
-                     Read<'a, Dimensions>);
-
-  fn run(&mut self, (mut character, camera_input, character_input, mouse_input, mut terrain_objects, zombies, dim): Self::SystemData) {
-    use specs::join::Join;
-
-    for (c, camera, ci, mi, to, zs) in
-        (&mut character, &camera_input, &character_input, &mouse_input, &mut terrain_objects, &zombies).join() {
-      let world_to_clip = dim.world_to_projection(camera);
-      c.update(&world_to_clip, ci, mi, &dim, &mut to.objects, &zs.zombies);
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     ReadStorage<'a, Player>,
+                     WriteStorage<'a, Health>,
+                     WriteStorage<'a, Transform>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     Read<'a, Contacts>,
+                     Read<'a, Dimensions>,
+                     Read<'a, DeltaTime>,
+                     Read<'a, Settings>,
+                     Write<'a, GameEvents>);
+
+  fn run(&mut self, (entities, mut character, character_input, mouse_input, terrain_objects, players, mut health, mut transforms, zombies, contacts, dim, dt, settings, mut events): Self::SystemData) {
+    use crate::ecs::Join;
+
+    let main_movement = (&character_input, &players).join()
+      .min_by_key(|(_, p)| p.index)
+      .map_or(Position::origin(), |(ci, _)| ci.movement);
+    let hits = ZombieHits { contacts: &contacts, zombies: &zombies, damage_scale: settings.difficulty.damage_scale(), delta: dt.0 as f32 };
+    for (e, c, ci, p, h, t) in (&entities, &mut character, &character_input, &players, &mut health, &mut transforms).join() {
+      if !h.is_dead() {
+        c.take_zombie_hits(p.index, main_movement - ci.movement, &hits, h, &mut events);
+        c.ammo_pick_up(p.index, &terrain_objects, &entities, &contacts, &mut events);
+      }
+      // Only the player with the mouse aims with it, the others are placed relative to the first one
+      match mouse_input.get(e) {
+        Some(mi) => c.update(ci, mi, &dim),
+        None => c.update_companion(ci, main_movement),
+      }
+      // A dead player lies where it fell, its controls stop at the stance
+      if h.is_dead() {
+        c.stance = Stance::NormalDeath;
+      }
+      t.position = c.position;
     }
   }
 }
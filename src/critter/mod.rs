@@ -1,4 +1,6 @@
-use specs;
+use crate::ecs;
+
+pub mod palette;
 
 pub struct CharacterSprite {
   pub character_idx: usize,
@@ -31,16 +33,6 @@ impl CharacterSprite {
   }
 }
 
-impl specs::prelude::Component for CharacterSprite {
-  type Storage = specs::storage::VecStorage<CharacterSprite>;
-}
-
-pub struct CritterData {
-  pub data: [f32; 4]
-}
-
-impl CritterData {
-  pub fn new(data: [f32; 4]) -> CritterData {
-    CritterData { data }
-  }
+impl ecs::Component for CharacterSprite {
+  type Storage = ecs::DenseVecStorage<CharacterSprite>;
 }
@@ -0,0 +1,59 @@
+pub const PALETTE_WIDTH: usize = 16;
+pub const PALETTE_ROWS: usize = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CritterPalette {
+  #[default]
+  Original,
+  Toxic,
+  Frozen,
+}
+
+const PALETTE_VARIANTS: [CritterPalette; PALETTE_ROWS] = [CritterPalette::Toxic, CritterPalette::Frozen];
+const PALETTES: [CritterPalette; PALETTE_ROWS + 1] = [CritterPalette::Original, CritterPalette::Toxic, CritterPalette::Frozen];
+
+impl CritterPalette {
+  // Zero keeps the sprite sheet colors, other values select a palette row starting from one
+  pub fn index(self) -> f32 {
+    self as usize as f32
+  }
+
+  pub fn from_index(idx: usize) -> CritterPalette {
+    PALETTES.get(idx).copied().unwrap_or(CritterPalette::Original)
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      CritterPalette::Original => "original",
+      CritterPalette::Toxic => "toxic",
+      CritterPalette::Frozen => "frozen",
+    }
+  }
+
+  // Players pick one as their skin in the lobby
+  pub fn cycle(self) -> CritterPalette {
+    CritterPalette::from_index((self as usize + 1) % PALETTES.len())
+  }
+
+  fn ramp(self) -> Option<([f32; 3], [f32; 3])> {
+    match self {
+      CritterPalette::Original => None,
+      CritterPalette::Toxic => Some(([0.05, 0.15, 0.02], [0.7, 1.0, 0.3])),
+      CritterPalette::Frozen => Some(([0.02, 0.05, 0.2], [0.75, 0.9, 1.0])),
+    }
+  }
+}
+
+// Sprite luminance is used as the palette index, so each row is a dark to light ramp
+pub fn palette_texture_data() -> Vec<u8> {
+  PALETTE_VARIANTS.iter()
+    .filter_map(|palette| palette.ramp())
+    .flat_map(|(dark, light)| {
+      (0..PALETTE_WIDTH).flat_map(move |idx| {
+        let t = idx as f32 / (PALETTE_WIDTH - 1) as f32;
+        let mix = |channel: usize| ((dark[channel] + (light[channel] - dark[channel]) * t) * 255.0) as u8;
+        vec![mix(0), mix(1), mix(2), 255]
+      })
+    })
+    .collect()
+}
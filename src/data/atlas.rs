@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use image::{self, RgbaImage};
+use json::JsonValue;
+use tracing::info;
+
+use crate::game::constants::{ATLAS_MAX_WIDTH, ATLAS_PADDING};
+use crate::shaders::SpriteFrame;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rect {
+  x: u32,
+  y: u32,
+  w: u32,
+  h: u32,
+}
+
+impl Rect {
+  fn from_json(value: &JsonValue) -> Result<Rect, String> {
+    let field = |name: &str| value[name].as_u32().ok_or_else(|| format!("Sprite rect field {} missing", name));
+    Ok(Rect { x: value["x"].as_u32().unwrap_or(0), y: value["y"].as_u32().unwrap_or(0), w: field("w")?, h: field("h")? })
+  }
+
+  fn to_json(self) -> JsonValue {
+    object! { "x" => self.x, "y" => self.y, "w" => self.w, "h" => self.h }
+  }
+}
+
+// Frames are named <animation>_<direction>_<frame>, the same key in the sheet JSON and as file name
+fn parse_frame_name(name: &str) -> Option<(&str, usize, usize)> {
+  let mut parts = name.rsplitn(3, '_');
+  let frame = parts.next()?.parse().ok()?;
+  let direction = parts.next()?.parse().ok()?;
+  Some((parts.next()?, direction, frame))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Animation {
+  start: usize,
+  directions: usize,
+  frames: usize,
+}
+
+// The frames of a packed sheet grouped by animation and direction, read from the JSON the packer
+// writes next to the atlas. Directions and frames come from the frame names.
+pub struct SpriteSheet {
+  frames: Vec<SpriteFrame>,
+  animations: HashMap<String, Animation>,
+}
+
+impl SpriteSheet {
+  pub fn from_json(sheet: &JsonValue) -> Result<SpriteSheet, String> {
+    let size = Rect::from_json(&sheet["meta"]["size"])?;
+    let mut named = Vec::new();
+    for (name, value) in sheet["frames"].entries() {
+      let (animation, direction, frame) = parse_frame_name(name).ok_or_else(|| format!("Sprite frame name {} isn't <animation>_<direction>_<frame>", name))?;
+      let source = Rect::from_json(&value["sourceSize"]).or_else(|_| Rect::from_json(&value["frame"]))?;
+      named.push((animation.to_string(), direction, frame, Rect::from_json(&value["frame"])?, Rect::from_json(&value["spriteSourceSize"]).unwrap_or(Rect { x: 0, y: 0, ..source }), source));
+    }
+    // The cell is the part of the sheet a quad shows, every source image sits at its x and y
+    let cell = Rect::from_json(&sheet["meta"]["cell"]).unwrap_or_else(|_| Rect {
+      x: 0,
+      y: 0,
+      w: named.iter().map(|f| f.5.w).max().unwrap_or(1),
+      h: named.iter().map(|f| f.5.h).max().unwrap_or(1),
+    });
+    named.sort_by(|a, b| (&a.0, a.1, a.2).cmp(&(&b.0, b.1, b.2)));
+
+    let mut animations: HashMap<String, Animation> = HashMap::new();
+    let mut frames = Vec::with_capacity(named.len());
+    for (idx, (animation, direction, frame, rect, trimmed, _)) in named.iter().enumerate() {
+      let entry = animations.entry(animation.clone()).or_insert(Animation { start: idx, directions: 0, frames: 0 });
+      entry.directions = entry.directions.max(direction + 1);
+      entry.frames = entry.frames.max(frame + 1);
+      frames.push(SpriteFrame {
+        rect: [rect.x as f32 / size.w as f32, rect.y as f32 / size.h as f32, rect.w as f32 / size.w as f32, rect.h as f32 / size.h as f32],
+        trim: [(cell.x + trimmed.x) as f32 / cell.w as f32, (cell.y + trimmed.y) as f32 / cell.h as f32, rect.w as f32 / cell.w as f32, rect.h as f32 / cell.h as f32],
+      });
+    }
+    // Every direction needs every frame, the lookup counts on it
+    if let Some((name, _)) = animations.iter().find(|(name, a)| named.iter().filter(|f| &f.0 == *name).count() != a.directions * a.frames) {
+      return Err(format!("Sprite animation {} is missing frames", name));
+    }
+    Ok(SpriteSheet { frames, animations })
+  }
+
+  // Directions and frames past the end of the animation wrap around
+  pub fn frame(&self, animation: &str, direction: usize, frame: usize) -> SpriteFrame {
+    let a = self.animations.get(animation).unwrap_or_else(|| panic!("Sprite animation {} not found", animation));
+    self.frames[a.start + (direction % a.directions) * a.frames + frame % a.frames]
+  }
+}
+
+// The smallest rect holding every pixel that isn't fully transparent, a blank frame keeps one pixel
+fn trim_rect(image: &RgbaImage) -> Rect {
+  let opaque = image.enumerate_pixels().filter(|(_, _, p)| p[3] > 0).map(|(x, y, _)| (x, y));
+  let bounds = opaque.fold(None, |b: Option<(u32, u32, u32, u32)>, (x, y)| Some(match b {
+    None => (x, y, x, y),
+    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+  }));
+  match bounds {
+    Some((x0, y0, x1, y1)) => Rect { x: x0, y: y0, w: x1 - x0 + 1, h: y1 - y0 + 1 },
+    None => Rect { x: 0, y: 0, w: 1, h: 1 },
+  }
+}
+
+// Packs trimmed frames into rows of an atlas, tallest first. Returns the atlas and its sheet JSON.
+pub fn pack_frames(name: &str, frames: &[(String, RgbaImage)]) -> Result<(RgbaImage, JsonValue), String> {
+  let mut trimmed = frames.iter().map(|(key, image)| (key, image, trim_rect(image))).collect::<Vec<_>>();
+  if let Some((key, _, rect)) = trimmed.iter().find(|(_, _, rect)| rect.w + 2 * ATLAS_PADDING > ATLAS_MAX_WIDTH) {
+    return Err(format!("Sprite frame {} is wider than the atlas, {} px", key, rect.w));
+  }
+  trimmed.sort_by(|a, b| b.2.h.cmp(&a.2.h).then_with(|| a.0.cmp(b.0)));
+
+  let (mut x, mut y, mut row_height, mut width) = (ATLAS_PADDING, ATLAS_PADDING, 0, 0);
+  let mut placed = Vec::with_capacity(trimmed.len());
+  for (key, image, rect) in trimmed {
+    if x + rect.w + ATLAS_PADDING > ATLAS_MAX_WIDTH {
+      x = ATLAS_PADDING;
+      y += row_height + ATLAS_PADDING;
+      row_height = 0;
+    }
+    placed.push((key, image, rect, Rect { x, y, w: rect.w, h: rect.h }));
+    x += rect.w + ATLAS_PADDING;
+    width = width.max(x);
+    row_height = row_height.max(rect.h);
+  }
+  let height = y + row_height + ATLAS_PADDING;
+
+  let mut atlas = RgbaImage::new(width, height);
+  let mut sheet_frames = JsonValue::new_object();
+  let (mut cell_w, mut cell_h) = (1, 1);
+  placed.sort_by(|a, b| a.0.cmp(b.0));
+  for (key, image, trim, frame) in placed {
+    for (px, py) in (0..trim.w).flat_map(|px| (0..trim.h).map(move |py| (px, py))) {
+      atlas.put_pixel(frame.x + px, frame.y + py, *image.get_pixel(trim.x + px, trim.y + py));
+    }
+    let (source_w, source_h) = image.dimensions();
+    cell_w = cell_w.max(source_w);
+    cell_h = cell_h.max(source_h);
+    sheet_frames[key.as_str()] = object! {
+      "frame" => frame.to_json(),
+      "rotated" => false,
+      "trimmed" => (trim.w, trim.h) != (source_w, source_h),
+      "spriteSourceSize" => trim.to_json(),
+      "sourceSize" => object! { "w" => source_w, "h" => source_h }
+    };
+  }
+  let sheet = object! {
+    "frames" => sheet_frames,
+    "meta" => object! {
+      "app" => "hinterland --pack_sprites",
+      "image" => format!("{}.png", name),
+      "format" => "RGBA8888",
+      "size" => object! { "w" => width, "h" => height },
+      "cell" => Rect { x: 0, y: 0, w: cell_w, h: cell_h }.to_json()
+    }
+  };
+  Ok((atlas, sheet))
+}
+
+// Packs the frame PNGs of a directory into <dir>.png and <dir>.json next to it
+pub fn pack_directory(dir: &str) -> Result<(), String> {
+  let dir = Path::new(dir.trim_end_matches('/'));
+  let name = dir.file_name().and_then(|n| n.to_str()).ok_or_else(|| format!("Sprite directory {} has no name", dir.display()))?;
+  let mut frames = Vec::new();
+  for entry in fs::read_dir(dir).map_err(|e| format!("Sprite directory {} error {}", dir.display(), e))? {
+    let path = entry.map_err(|e| e.to_string())?.path();
+    if path.extension().and_then(|ext| ext.to_str()) == Some("png") {
+      let key = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+      if parse_frame_name(&key).is_none() {
+        return Err(format!("Sprite frame {} isn't named <animation>_<direction>_<frame>.png", path.display()));
+      }
+      let image = image::open(&path).map_err(|e| format!("Sprite frame {} error {}", path.display(), e))?;
+      frames.push((key, image.to_rgba()));
+    }
+  }
+  if frames.is_empty() {
+    return Err(format!("Sprite directory {} has no frame PNGs", dir.display()));
+  }
+  let (atlas, sheet) = pack_frames(name, &frames)?;
+  let (width, height) = atlas.dimensions();
+  let out = dir.with_extension("png");
+  image::save_buffer(&out, &atlas.into_raw(), width, height, image::RGBA(8)).map_err(|e| format!("Atlas {} write error {}", out.display(), e))?;
+  let out = dir.with_extension("json");
+  fs::write(&out, sheet.pretty(2)).map_err(|e| format!("Sprite sheet {} write error {}", out.display(), e))?;
+  info!("Packed {} frames into {} {}x{}", frames.len(), dir.with_extension("png").display(), width, height);
+  Ok(())
+}
@@ -1,25 +1,27 @@
-use std::{fs::File, io::BufReader, io::prelude::*, path::Path, string::String, vec::Vec};
+use std::{fs::File, io::BufReader, io::prelude::*, path::Path, string::String};
 
 use json;
 use json::JsonValue;
 use tiled;
 use tiled::Map;
 
-use crate::critter::CritterData;
-use crate::game::constants::{CHARACTER_BUF_LENGTH, CHARACTER_JSON_PATH, ZOMBIE_JSON_PATH};
+use crate::data::atlas::SpriteSheet;
+use crate::game::constants::{CHARACTER_JSON_PATH, ZOMBIE_JSON_PATH};
+
+pub mod atlas;
 
 pub fn load_map_file(filename: &str) -> Map {
-  let file = match File::open(&Path::new(&filename)) {
-    Ok(f) => f,
-    Err(e) => panic!("File {} not found: {}", filename, e),
-  };
-  let reader = BufReader::new(file);
-  match tiled::parse(reader) {
+  match parse_map_file(filename) {
     Ok(m) => m,
-    Err(e) => panic!("Map parse error {:?}", e)
+    Err(e) => panic!("{}", e),
   }
 }
 
+pub fn parse_map_file(filename: &str) -> Result<Map, String> {
+  let file = File::open(filename).map_err(|e| format!("File {} not found: {}", filename, e))?;
+  tiled::parse(BufReader::new(file)).map_err(|e| format!("Map parse error {:?}", e))
+}
+
 pub fn get_map_tile(map: &Map, layer_index: usize, x: usize, y: usize) -> u32 {
   let layer = match map.layers.get(layer_index) {
     None => panic!("Layer_index value out of index {:?}", map.layers),
@@ -48,72 +50,26 @@ fn read_sprite_file(filename: &str) -> String {
   }
 }
 
-fn get_frame_data(character: &JsonValue, key: &str) -> CritterData {
-  CritterData::new([
-    character["frames"][key]["frame"]["x"].as_f32().unwrap(),
-    character["frames"][key]["frame"]["y"].as_f32().unwrap(),
-    character["frames"][key]["frame"]["w"].as_f32().unwrap(),
-    character["frames"][key]["frame"]["h"].as_f32().unwrap(),
-  ])
-}
-
-pub fn load_character() -> Vec<CritterData> {
-  let mut sprites = Vec::with_capacity(CHARACTER_BUF_LENGTH + 64);
-  let character_json = read_sprite_file(CHARACTER_JSON_PATH);
-  let character = match json::parse(&character_json) {
+pub fn load_json(filename: &str) -> JsonValue {
+  match json::parse(&read_sprite_file(filename)) {
     Ok(res) => res,
-    Err(e) => panic!("Character {} parse error {:?}", CHARACTER_JSON_PATH, e),
-  };
-
-  for x in 0..16 {
-    for y in 0..14 {
-      let key = &format!("run_{}_{}", x, y);
-      sprites.push(get_frame_data(&character, key));
-    }
-  }
-
-  for x in 0..15 {
-    for y in 0..4 {
-      let key = &format!("fire_{}_{}", x, y);
-      sprites.push(get_frame_data(&character, key));
-    }
+    Err(e) => panic!("{} parse error {:?}", filename, e),
   }
+}
 
-  sprites
+// The game runs without its scripts when they are missing, so this one doesn't panic
+pub fn load_script(filename: &str) -> Result<String, String> {
+  std::fs::read_to_string(filename).map_err(|e| format!("File {} not found: {}", filename, e))
 }
 
-pub fn load_zombie() -> Vec<CritterData> {
-  let mut sprites = Vec::with_capacity(256);
-  let zombie_json = read_sprite_file(ZOMBIE_JSON_PATH);
-  let zombie = match json::parse(&zombie_json) {
-    Ok(res) => res,
-    Err(e) => panic!("Zombie {} parse error {:?}", ZOMBIE_JSON_PATH, e),
-  };
+pub fn load_sprite_sheet(filename: &str) -> SpriteSheet {
+  SpriteSheet::from_json(&load_json(filename)).unwrap_or_else(|e| panic!("Sprite sheet {} error {}", filename, e))
+}
 
-  for x in 0..7 {
-    for y in 0..7 {
-      let key = &format!("critical_{}_{}", x, y);
-      sprites.push(get_frame_data(&zombie, key));
-    }
-  }
-  for x in 0..7 {
-    for y in 0..5 {
-      let key = &format!("normal_{}_{}", x, y);
-      sprites.push(get_frame_data(&zombie, key));
-    }
-  }
-  for x in 0..7 {
-    for y in 0..4 {
-      let key = &format!("still_{}_{}", x, y);
-      sprites.push(get_frame_data(&zombie, key));
-    }
-  }
+pub fn load_character() -> SpriteSheet {
+  load_sprite_sheet(CHARACTER_JSON_PATH)
+}
 
-  for x in 0..7 {
-    for y in 0..7 {
-      let key = &format!("walk_{}_{}", x, y);
-      sprites.push(get_frame_data(&zombie, key));
-    }
-  }
-  sprites
+pub fn load_zombie() -> SpriteSheet {
+  load_sprite_sheet(ZOMBIE_JSON_PATH)
 }
@@ -0,0 +1,7 @@
+// The ECS the game runs on. Systems, components and resources take these types from here instead of
+// from specs, so they all use the same data access API and the next ECS upgrade starts in this
+// module rather than in every system.
+pub use specs::prelude::{Builder, Component, Dispatcher, DispatcherBuilder, Entities, Entity, Join, LazyUpdate, ParJoin, Read,
+                         ReadStorage, RunNow, System, World, WorldExt, Write, WriteStorage};
+pub use specs::shrev::{EventChannel, ReaderId};
+pub use specs::storage::{DenseVecStorage, HashMapStorage, VecStorage};
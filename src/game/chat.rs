@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crossbeam_channel as channel;
+
+use crate::ecs::{self, Read, Write};
+use crate::game::constants::{CHAT_DURATION, CHAT_LINES, CHAT_MAX_LENGTH};
+use crate::game::localization::Localization;
+use crate::net::lobby::Lobby;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChatControl {
+  Open,
+  Close,
+  Character(char),
+  Backspace,
+  Submit,
+}
+
+// Players are numbered from 1 in the lines, like in the lobby
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChatLine {
+  Said(usize, String),
+  Muted(usize),
+  Unmuted(usize),
+  Usage,
+}
+
+impl ChatLine {
+  pub fn text(&self, strings: &Localization) -> String {
+    let player = |index: usize| ("player", (index + 1).to_string());
+    match self {
+      ChatLine::Said(index, text) => strings.format("chat.said", &[player(*index), ("text", text.clone())]),
+      ChatLine::Muted(index) => strings.format("chat.muted", &[player(*index)]),
+      ChatLine::Unmuted(index) => strings.format("chat.unmuted", &[player(*index)]),
+      ChatLine::Usage => strings.get("chat.usage").to_string(),
+    }
+  }
+}
+
+// Lines longer than a chat line are cut, a client's line is cut again by the host
+pub fn chat_text(text: &str) -> String {
+  text.trim().chars().filter(|c| !c.is_control()).take(CHAT_MAX_LENGTH).collect()
+}
+
+// Text chat of a LAN session. Typed lines wait in outgoing for the session to send them and the
+// lines of muted players are dropped as they arrive.
+#[derive(Default)]
+pub struct Chat {
+  pub is_open: bool,
+  input: String,
+  // The latest lines with their age in seconds
+  history: VecDeque<(ChatLine, f32)>,
+  pub outgoing: Vec<String>,
+  muted: Vec<usize>,
+}
+
+impl Chat {
+  pub fn input(&self) -> &str {
+    &self.input
+  }
+
+  pub fn type_character(&mut self, c: char) {
+    if self.input.chars().count() < CHAT_MAX_LENGTH {
+      self.input.push(c);
+    }
+  }
+
+  pub fn is_muted(&self, index: usize) -> bool {
+    self.muted.contains(&index)
+  }
+
+  pub fn receive(&mut self, line: ChatLine) {
+    if let ChatLine::Said(index, _) = line {
+      if self.is_muted(index) {
+        return;
+      }
+    }
+    if self.history.len() == CHAT_LINES {
+      self.history.pop_front();
+    }
+    self.history.push_back((line, 0.0));
+  }
+
+  // `/mute 2` and `/unmute 2` take the player number, anything else is said by the own player
+  pub fn submit(&mut self, own: usize) {
+    let input = std::mem::take(&mut self.input);
+    let mut words = input.split_whitespace();
+    match (words.next(), words.next().and_then(|n| n.parse::<usize>().ok())) {
+      (Some("/mute"), Some(player)) if player >= 1 && player - 1 != own => {
+        if !self.is_muted(player - 1) {
+          self.muted.push(player - 1);
+        }
+        self.receive(ChatLine::Muted(player - 1));
+      }
+      (Some("/unmute"), Some(player)) if player >= 1 => {
+        self.muted.retain(|&index| index != player - 1);
+        self.receive(ChatLine::Unmuted(player - 1));
+      }
+      (Some("/mute"), _) | (Some("/unmute"), _) => self.receive(ChatLine::Usage),
+      _ => {
+        let text = chat_text(&input);
+        if !text.is_empty() {
+          self.receive(ChatLine::Said(own, text.clone()));
+          self.outgoing.push(text);
+        }
+      }
+    }
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    for (_, age) in &mut self.history {
+      *age += delta;
+    }
+  }
+
+  // Lines fade after a while, an open chat shows all that are kept
+  pub fn lines(&self) -> impl Iterator<Item=&ChatLine> + '_ {
+    let is_open = self.is_open;
+    self.history.iter()
+      .filter(move |&&(_, age)| is_open || age < CHAT_DURATION)
+      .map(|(line, _)| line)
+  }
+}
+
+// Runs every frame with the menu, the session sends the submitted lines after the simulation steps
+pub struct ChatSystem {
+  queue: channel::Receiver<ChatControl>,
+  last_frame: Instant,
+}
+
+impl ChatSystem {
+  pub fn new() -> (ChatSystem, channel::Sender<ChatControl>) {
+    let (tx, rx) = channel::unbounded();
+    (ChatSystem {
+      queue: rx,
+      last_frame: Instant::now(),
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for ChatSystem {
+  type SystemData = (Read<'a, Lobby>,
+                     Write<'a, Chat>);
+
+  fn run(&mut self, (lobby, mut chat): Self::SystemData) {
+    chat.tick(self.last_frame.elapsed().as_secs_f32());
+    self.last_frame = Instant::now();
+
+    while let Ok(control) = self.queue.try_recv() {
+      match control {
+        ChatControl::Open => chat.is_open = true,
+        ChatControl::Close => chat.is_open = false,
+        ChatControl::Character(c) => chat.type_character(c),
+        ChatControl::Backspace => {
+          chat.input.pop();
+        }
+        ChatControl::Submit => chat.submit(lobby.local.index),
+      }
+    }
+  }
+}
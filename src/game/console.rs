@@ -0,0 +1,94 @@
+use crossbeam_channel as channel;
+
+use crate::ecs::{self, Write};
+use crate::game::constants::CONSOLE_LOG_LINES;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsoleControl {
+  Open,
+  Close,
+  Character(char),
+  Backspace,
+  Submit,
+}
+
+// Debug console, submitted lines are evaluated by the script system as rhai code
+pub struct Console {
+  pub is_visible: bool,
+  input: String,
+  log: Vec<String>,
+  pub commands: Vec<String>,
+  pub lines: Vec<String>,
+}
+
+impl Console {
+  pub fn new() -> Console {
+    Console {
+      is_visible: false,
+      input: String::new(),
+      log: Vec::new(),
+      commands: Vec::new(),
+      lines: Vec::new(),
+    }
+  }
+
+  pub fn print(&mut self, line: String) {
+    self.log.push(line);
+    if self.log.len() > CONSOLE_LOG_LINES {
+      self.log.remove(0);
+    }
+    self.refresh_lines();
+  }
+
+  fn refresh_lines(&mut self) {
+    self.lines = self.log.iter()
+      .cloned()
+      .chain(Some(format!("> {}_", self.input)))
+      .collect();
+  }
+}
+
+impl Default for Console {
+  fn default() -> Console {
+    Console::new()
+  }
+}
+
+// Runs every frame with the menu, commands wait for the script system while the simulation is suspended
+pub struct ConsoleSystem {
+  queue: channel::Receiver<ConsoleControl>,
+}
+
+impl ConsoleSystem {
+  pub fn new() -> (ConsoleSystem, channel::Sender<ConsoleControl>) {
+    let (tx, rx) = channel::unbounded();
+    (ConsoleSystem {
+      queue: rx,
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for ConsoleSystem {
+  type SystemData = Write<'a, Console>;
+
+  fn run(&mut self, mut console: Self::SystemData) {
+    while let Ok(control) = self.queue.try_recv() {
+      match control {
+        ConsoleControl::Open => console.is_visible = true,
+        ConsoleControl::Close => console.is_visible = false,
+        ConsoleControl::Character(c) => console.input.push(c),
+        ConsoleControl::Backspace => {
+          console.input.pop();
+        }
+        ConsoleControl::Submit => {
+          let command = std::mem::take(&mut console.input);
+          if !command.trim().is_empty() {
+            console.print(format!("> {}", command));
+            console.commands.push(command);
+          }
+        }
+      }
+      console.refresh_lines();
+    }
+  }
+}
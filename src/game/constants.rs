@@ -6,35 +6,165 @@ pub const TILE_WIDTH: f32 = TILE_SIZE * 2.0;
 
 pub const Y_OFFSET: f32 = TILES_PCS_W as f32 / 2.0 * TILE_WIDTH;
 
-pub const CHARACTER_BUF_LENGTH: usize = 224;
+pub const CRITTER_INSTANCE_BUF_LENGTH: usize = 256;
+// Line end points the debug shapes upload at once, even so no line is split between two uploads
+pub const DEBUG_VERTEX_BUF_LENGTH: usize = 4096;
+// Tiles in each direction of the first player the debug shapes outline
+pub const DEBUG_TILE_RANGE: i32 = 8;
+
+pub const SIMULATION_STEP: f64 = 1.0 / 60.0;
+pub const MAX_FRAME_TIME: f64 = 0.25;
+// Recorded frames a fast forwarded replay plays at most per drawn frame
+pub const MAX_REPLAY_SPEED: u32 = 8;
+// Steps a buffered press waits for its action to become possible
+pub const INPUT_BUFFER_STEPS: u32 = 6;
 
 pub const RESOLUTION_X: u32 = 1600;
 pub const RESOLUTION_Y: u32 = 900;
 
-pub const ASPECT_RATIO: f32 = (RESOLUTION_X / RESOLUTION_Y) as f32;
-
 pub const VIEW_DISTANCE: f32 = 300.0;
+pub const CAMERA_MIN_DISTANCE: f32 = 200.0;
+pub const CAMERA_MAX_DISTANCE: f32 = 600.0;
+pub const CULLING_MARGIN: f32 = 200.0;
+pub const COLLISION_CELL_SIZE: f32 = 64.0;
+// Half extents of the collision boxes. Bullets hit zombies within the first, zombies reach the
+// player within the second and the player picks up ammo within the third.
+pub const ZOMBIE_HIT_BOX: [f32; 2] = [15.0, 15.0];
+pub const PLAYER_HIT_BOX: [f32; 2] = [15.0, 30.0];
+pub const PICKUP_BOX: [f32; 2] = [20.0, 20.0];
+// Below these counts a step is quicker on one thread than split up between the rayon workers
+pub const PARALLEL_MIN_ZOMBIES: usize = 256;
+pub const PARALLEL_MIN_BULLETS: usize = 64;
+// Zombies out of view update every few steps, those past this many view extents even less often
+pub const ZOMBIE_LOD_FAR_RANGE: f32 = 3.0;
+pub const ZOMBIE_LOD_MID_INTERVAL: u64 = 4;
+pub const ZOMBIE_LOD_FAR_INTERVAL: u64 = 16;
+// Frame governor, quality steps down after this many frames over budget and back up after this many
+// frames below the headroom share of the budget. Each level has its heat haze source limit per view,
+// the governor only changes what is drawn so the simulation stays the same at every level.
+pub const FRAME_BUDGET_MS: f64 = 20.0;
+pub const GOVERNOR_HEADROOM: f64 = 0.7;
+pub const GOVERNOR_DOWNGRADE_FRAMES: u32 = 30;
+pub const GOVERNOR_RESTORE_FRAMES: u32 = 180;
+pub const GOVERNOR_HEAT_HAZE_LIMITS: [usize; 4] = [usize::MAX, 32, 8, 0];
+pub const AMBIENT_LIGHT: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+pub const HEAT_HAZE_RADIUS: f32 = 20.0;
+pub const HEAT_HAZE_STRENGTH: f32 = 0.006;
+
+// Speeds are per second and scaled by DeltaTime
+pub const BULLET_SPEED: f32 = 1800.0;
+pub const CHARACTER_X_SPEED: f32 = 180.0;
+pub const CHARACTER_Y_SPEED: f32 = 180.0;
+pub const ZOMBIE_SPEED: f32 = 60.0;
+// Zombies closer to the player run after it along the path finder's route
+pub const ZOMBIE_CHASE_DISTANCE: f32 = 400.0;
+pub const CAMERA_ZOOM_SPEED: f32 = 120.0;
+
+pub const PLAYER_MAX_HEALTH: f32 = 100.0;
+pub const MAGAZINE_SIZE: usize = 10;
+pub const MAX_MAGAZINES: usize = 2;
+// Seconds
+pub const RELOAD_DURATION: f32 = 1.2;
+// Degrees a shot can stray from the aim, each shot widens it up to the maximum and it recovers per second
+pub const SHOT_SPREAD: f32 = 2.0;
+pub const MAX_SPREAD: f32 = 10.0;
+pub const SPREAD_RECOVERY: f32 = 8.0;
+pub const PLAYER_MAX_STAMINA: f32 = 100.0;
+// Per second
+pub const STAMINA_DRAIN: f32 = 8.0;
+pub const STAMINA_RECOVERY: f32 = 20.0;
+// Status effect durations in seconds
+pub const HURT_DURATION: f32 = 1.0;
+pub const EXHAUSTED_DURATION: f32 = 3.0;
+pub const ZOMBIE_MAX_HEALTH: f32 = 1.0;
+pub const BULLET_DAMAGE: f32 = 0.5;
+// Per second of contact
+pub const ZOMBIE_ATTACK_DAMAGE: f32 = 50.0;
+pub const SCORE_PER_KILL: usize = 100;
+// Kills less than this many seconds apart keep a streak going
+pub const STREAK_WINDOW: f32 = 3.0;
+// Streak kills per multiplier step
+pub const STREAK_STEP: usize = 5;
+pub const MAX_SCORE_MULTIPLIER: usize = 4;
 
-pub const CHARACTER_SHEET_TOTAL_WIDTH: f32 = 16_128f32;
-pub const SPRITE_OFFSET: f32 = 2.0;
+pub const ZOMBIE_WAVE_RADIUS: f32 = 1000.0;
+pub const ZOMBIE_WAVE_GROWTH: usize = 8;
+pub const ZOMBIE_WAVE_DELAY: u64 = 3;
+// Every fifth wave is a boss wave with its own music and banner
+pub const BOSS_WAVE_INTERVAL: usize = 5;
 
-pub const ZOMBIE_SHEET_TOTAL_WIDTH: f32 = 9_184f32;
+// Operations a single script hook or console command may run, counted so every run stops at the same point
+pub const SCRIPT_MAX_OPERATIONS: u64 = 50_000;
 
-pub const BULLET_SPEED: f32 = 15.0;
-pub const CHARACTER_X_SPEED: f32 = 3.0;
-pub const CHARACTER_Y_SPEED: f32 = 3.0;
+pub const MAX_VOICES: usize = 16;
+pub const AUDIO_FALLOFF_DISTANCE: f32 = 1200.0;
+pub const AUDIO_PAN_DISTANCE: f32 = 600.0;
+pub const MUSIC_CROSSFADE_SECONDS: f32 = 2.0;
+pub const MUSIC_COMBAT_DISTANCE: f32 = 400.0;
+pub const MUSIC_COMBAT_INTENSITY: f32 = 0.5;
+pub const MUSIC_STEM_FADE_RANGE: f32 = 0.25;
+pub const COMBAT_INTENSITY_ZOMBIES: usize = 6;
+pub const COMBAT_DAMAGE_INTENSITY: f32 = 0.05;
+pub const COMBAT_INTENSITY_DECAY_SECONDS: f32 = 5.0;
+pub const CRITICAL_HEALTH_RATIO: f32 = 0.25;
+pub const LOW_PASS_OPEN_HZ: u32 = 20_000;
+pub const MUFFLED_CUTOFF_HZ: u32 = 800;
+pub const MUFFLE_FADE_SECONDS: f32 = 0.5;
+pub const OCCLUDED_GAIN: f32 = 0.4;
+pub const DUCKED_GAIN: f32 = 0.35;
+pub const OCCLUDED_CUTOFF_HZ: u32 = 1_200;
+pub const VOLUME_STEP: f32 = 0.1;
+pub const FOOTSTEP_INTERVAL: f32 = 0.4;
+pub const FOOTSTEP_ZOMBIE_DISTANCE: f32 = 500.0;
 
 pub const GAME_TITLE: &str = "Hinterland";
 
 //Assets
 pub const ZOMBIE_JSON_PATH: &str = "assets/zombie.json";
 pub const CHARACTER_JSON_PATH: &str = "assets/character.json";
-pub const PISTOL_AUDIO_PATH: &str = "assets/audio/pistol.ogg";
+// Sprite sheets packed with --pack_sprites, rows of trimmed frames with this many px around each
+pub const ATLAS_MAX_WIDTH: u32 = 2048;
+pub const ATLAS_PADDING: u32 = 2;
+pub const SOUND_MANIFEST_PATH: &str = "assets/sounds/manifest.json";
+pub const MUSIC_PLAYLIST_PATH: &str = "assets/music/playlist.json";
 pub const MAP_FILE_PATH: &str = "assets/maps/tilemap.tmx";
-
-pub const RUN_SPRITE_OFFSET: usize = 64;
-pub const ZOMBIE_STILL_SPRITE_OFFSET: usize = 32;
-pub const NORMAL_DEATH_SPRITE_OFFSET: usize = 64;
+pub const PREFABS_PATH: &str = "assets/prefabs.json";
+// String tables are named by language code, like assets/lang/en.json
+pub const LANGUAGE_DIR: &str = "assets/lang";
+// Key binding presets that come with the game, exported and shared ones go in the profile's presets folder
+pub const PRESET_DIR: &str = "assets/presets";
+pub const USER_PRESET_DIR: &str = "presets";
+// Config file name and the directory it gets inside the platform's config directory
+pub const CONFIG_PATH: &str = "settings.toml";
+// The JSON config of earlier versions, imported once into the TOML one
+pub const LEGACY_CONFIG_PATH: &str = "config.json";
+pub const CONFIG_DIR: &str = "hinterland";
+// Profiles are listed in the game's config directory, each keeps its files in its own directory
+pub const PROFILES_PATH: &str = "profiles.json";
+pub const MAX_PROFILES: usize = 4;
+// Save slots of a profile, quick save uses the first. The version goes up when a change makes
+// older saves unreadable.
+pub const SAVE_SLOTS: usize = 3;
+pub const SAVE_VERSION: usize = 1;
+// Auto-saves kept, one is taken when a wave starts
+pub const AUTOSAVES: usize = 3;
+// High scores and run history of a profile, the version goes up when fields are added
+pub const RECORDS_PATH: &str = "records.json";
+pub const RECORDS_VERSION: usize = 1;
+// Finished runs kept in the history, the newest first
+pub const RECORD_RUNS: usize = 10;
+// Lifetime statistics of a profile
+pub const STATS_PATH: &str = "stats.json";
+pub const STATS_VERSION: usize = 1;
+// Online leaderboard, the top list is fetched in full and shown a page at a time. The timeout is
+// the default of the config, in ms.
+pub const LEADERBOARD_SIZE: usize = 100;
+pub const LEADERBOARD_PAGE: usize = 10;
+pub const LEADERBOARD_NAME_LENGTH: usize = 16;
+pub const LEADERBOARD_TIMEOUT: u64 = 5000;
+// Pixel scrolling and pinching are turned into mouse wheel lines
+pub const ZOOM_PIXELS_PER_STEP: f64 = 20.0;
+pub const GAMEPLAY_SCRIPT_PATH: &str = "assets/scripts/gameplay.rhai";
 
 // Object positions
 pub const AMMO_POSITIONS: [[i32; 2]; 4] = [ [ -13, -12 ], [ -15, 8 ], [ 16, -8 ], [ 1, 14 ] ];
@@ -53,10 +183,99 @@ pub const SMALL_HILLS: [[i32; 2]; 3] = [[4, 2], [20, -2], [-14, -6]];
 
 pub const GAME_VERSION: &str = "v0.3.12";
 
-pub const HUD_TEXTS: [&str; 15] = [GAME_VERSION, "Ammo 0", "Ammo 1", "Ammo 2", "Ammo 3",
-  "Ammo 4", "Ammo 5", "Ammo 6",
-  "Ammo 7", "Ammo 8", "Ammo 9", "Ammo 10",
-  "Magazines 0/2", "Magazines 1/2", "Magazines 2/2"];
+// HUD layout in logical pixels
+pub const HUD_MARGIN: f32 = 16.0;
+pub const HUD_BAR_WIDTH: f32 = 200.0;
+pub const HUD_BAR_HEIGHT: f32 = 12.0;
+pub const HUD_SPACING: f32 = 6.0;
+pub const HUD_ICON_SIZE: f32 = 20.0;
+// Text line heights
+pub const HUD_SMALL_TEXT: f32 = 14.0;
+pub const HUD_TEXT: f32 = 20.0;
+// World distance from the player within which pickups show their tooltip
+pub const TOOLTIP_RANGE: f32 = 250.0;
+// Logical pixels from the tip of the off-screen objective arrow to its base
+pub const OBJECTIVE_ARROW_SIZE: f32 = 24.0;
+// Seconds a banner takes to slide in or out and the seconds it holds still in between
+pub const BANNER_SLIDE: f32 = 0.4;
+pub const BANNER_HOLD: f32 = 1.6;
+// Toasts shown at once and the seconds each stays, the oldest makes room for a new one
+pub const MAX_TOASTS: usize = 4;
+pub const TOAST_DURATION: f32 = 3.0;
+// Combat log lines kept and the seconds each stays
+pub const COMBAT_LOG_LINES: usize = 8;
+pub const COMBAT_LOG_DURATION: f32 = 8.0;
+// Chat lines kept, the seconds each stays while the chat is closed and the characters of a line
+pub const CHAT_LINES: usize = 8;
+pub const CHAT_DURATION: f32 = 10.0;
+pub const CHAT_MAX_LENGTH: usize = 120;
+pub const MENU_WIDTH: f32 = 440.0;
+pub const MENU_ITEM_HEIGHT: f32 = 32.0;
+// Preset import errors listed on the controls page, the rest are counted
+pub const PRESET_ERROR_LINES: usize = 4;
+pub const WEAPON_SLOTS: usize = 3;
+pub const INVENTORY_SLOT_WIDTH: f32 = 150.0;
+pub const INVENTORY_SLOT_HEIGHT: f32 = 48.0;
+// Seconds a damage indicator takes to fade out
+pub const DAMAGE_INDICATOR_FADE: f32 = 0.5;
+pub const CROSSHAIR_GAP: f32 = 6.0;
+// Crosshair gap per degree of spread
+pub const CROSSHAIR_SPREAD_GAP: f32 = 2.0;
+
+// Profiler overlay
+pub const PROFILER_MAX_LINES: usize = 16;
+pub const PROFILER_REFRESH_INTERVAL: f64 = 250.0;
+// Frames kept for the frame time graph and the frame time at its top
+pub const FRAME_GRAPH_SAMPLES: usize = 120;
+pub const FRAME_GRAPH_MAX_MS: f64 = 50.0;
+
+// Log filter environment variable, in the RUST_LOG syntax like `info,hinterland::net=debug`
+pub const LOG_FILTER_ENV: &str = "HINTERLAND_LOG";
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+// Debug console, the log and the input line share the overlay lines
+pub const CONSOLE_LOG_LINES: usize = PROFILER_MAX_LINES - 1;
+
+// Shader hot reload
+pub const SHADER_RELOAD_INTERVAL: f64 = 500.0;
+
+// Loading screen, how long it waits for a decoded texture before drawing its next frame in ms
+pub const ASSET_POLL_INTERVAL: u64 = 16;
+
+// Config and data file hot reload in debug builds
+pub const ASSET_RELOAD_INTERVAL: f64 = 500.0;
+
+// Crash reports keep the log lines of the last frames and a snapshot of the run at most this many ms old
+pub const CRASH_LOG_FRAMES: u64 = 600;
+pub const CRASH_SNAPSHOT_INTERVAL: f64 = 1000.0;
+
+// LAN co-op, the host counts as a player. Times in ms.
+pub const MAX_PLAYERS: usize = 4;
+pub const NET_PORT: u16 = 7777;
+// The largest UDP payload
+pub const NET_PACKET_SIZE: usize = 65_507;
+pub const NET_TIMEOUT: f64 = 5000.0;
+pub const NET_JOIN_INTERVAL: f64 = 1000.0;
+// Positions are sent in tenths of a unit, zombies and pickups only to players this close to them
+pub const NET_POSITION_SCALE: f32 = 10.0;
+pub const NET_INTEREST_RADIUS: f32 = 1200.0;
+// States a client can acknowledge, older ones are sent in full again
+pub const NET_SNAPSHOT_HISTORY: usize = 32;
+// Inputs a client keeps replaying on top of the host's state until the host applied them
+pub const NET_INPUT_HISTORY: usize = 120;
+// Hosts announce themselves to the LAN on another port, the browser lists this many
+pub const NET_DISCOVERY_PORT: u16 = 7778;
+pub const NET_ADVERTISE_INTERVAL: f64 = 1000.0;
+pub const NET_SESSIONS: usize = 4;
+// Clients learn the addresses of the others this often, to find a new host when theirs is lost
+pub const NET_PEERS_INTERVAL: f64 = 1000.0;
+// Lockstep input is given the step this many steps ahead, so it reaches every peer before the step
+// runs. Inputs and turns not yet acknowledged are sent again, up to the window.
+pub const LOCKSTEP_DELAY: u64 = 4;
+pub const LOCKSTEP_WINDOW: usize = 120;
+// Steps between the state hashes peers compare and the hashes kept
+pub const LOCKSTEP_HASH_INTERVAL: u64 = 60;
+pub const LOCKSTEP_HASHES: usize = 8;
 
-pub const CURRENT_AMMO_TEXT: &str = "Ammo 10";
-pub const CURRENT_MAGAZINE_TEXT: &str = "Magazines 2/2";
+// Shader tweak panel
+pub const SHADER_PARAM_STEP: f32 = 0.05;
@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use json::JsonValue;
+use tracing::{info, warn};
+
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{Join, ReaderId, World, WorldExt};
+use crate::game::constants::{CRASH_LOG_FRAMES, CRASH_SNAPSHOT_INTERVAL, GAME_VERSION};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::profiles::user_file;
+use crate::game::save::SaveGame;
+use crate::game::score::Score;
+use crate::game::snapshot::Snapshot;
+use crate::game::state::GameStates;
+use crate::shaders::Position;
+
+// What is known of the game at the last drawn frame. The world itself can't be reached from a
+// panic, so the recorder keeps this copy up to date for the panic hook.
+pub struct CrashReport {
+  pub seed: u64,
+  pub frame: u64,
+  pub state: String,
+  pub wave: usize,
+  pub time: f32,
+  pub positions: Vec<Position>,
+  // Frame numbered lines of the frames that had game events or a state change
+  pub log: VecDeque<(u64, String)>,
+  pub save: Option<SaveGame>,
+  is_written: bool,
+}
+
+impl CrashReport {
+  pub fn new(seed: u64) -> CrashReport {
+    CrashReport {
+      seed,
+      frame: 0,
+      state: String::new(),
+      wave: 0,
+      time: 0.0,
+      positions: Vec::new(),
+      log: VecDeque::new(),
+      save: None,
+      is_written: false,
+    }
+  }
+
+  pub fn log(&mut self, line: String) {
+    self.log.push_back((self.frame, line));
+    while let Some(&(frame, _)) = self.log.front() {
+      if frame + CRASH_LOG_FRAMES > self.frame {
+        break;
+      }
+      self.log.pop_front();
+    }
+  }
+
+  pub fn to_json(&self, message: &str) -> JsonValue {
+    object! {
+      "version" => GAME_VERSION,
+      "message" => message,
+      "seed" => self.seed,
+      "frame" => self.frame,
+      "state" => self.state.clone(),
+      "wave" => self.wave,
+      "time" => self.time,
+      "positions" => JsonValue::Array(self.positions.iter().map(Snapshot::to_json).collect()),
+      "log" => JsonValue::Array(self.log.iter().map(|(frame, line)| format!("{} {}", frame, line).into()).collect()),
+      "save" => self.save.as_ref().map_or(JsonValue::Null, Snapshot::to_json)
+    }
+  }
+}
+
+// Writes a crash file next to the profile's saves, once, after the default panic message
+fn write_report(report: &mut CrashReport, message: &str) {
+  if report.is_written {
+    return;
+  }
+  report.is_written = true;
+  let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+  let filename = user_file(&format!("crash_{}.json", secs));
+  match fs::write(&filename, report.to_json(message).pretty(2)) {
+    Ok(()) => info!("Crash report written to {}", filename.display()),
+    Err(e) => warn!("Crash report {} write error {}", filename.display(), e),
+  }
+}
+
+pub struct CrashRecorder {
+  report: Arc<Mutex<CrashReport>>,
+  reader: ReaderId<GameEvent>,
+  last_snapshot: Instant,
+}
+
+impl CrashRecorder {
+  pub fn install(world: &mut World, seed: u64) -> CrashRecorder {
+    let report = Arc::new(Mutex::new(CrashReport::new(seed)));
+    let hook_report = report.clone();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+      default_hook(info);
+      // The panicking thread may be the one holding the report, it is left out then
+      match hook_report.try_lock() {
+        Ok(mut report) => write_report(&mut report, &info.to_string()),
+        Err(_) => write_report(&mut CrashReport::new(seed), &info.to_string()),
+      }
+    }));
+    CrashRecorder {
+      report,
+      reader: world.write_resource::<GameEvents>().register_reader(),
+      last_snapshot: Instant::now(),
+    }
+  }
+
+  // Called once per drawn frame, the run is snapshotted only every so often
+  pub fn record(&mut self, world: &World) {
+    let mut report = self.report.lock().expect("Crash report lock error");
+    report.frame += 1;
+    let state = format!("{:?}", world.read_resource::<GameStates>().current());
+    let events = world.read_resource::<GameEvents>().read(&mut self.reader).map(|e| format!("{:?}", e)).collect::<Vec<_>>();
+    if state != report.state || !events.is_empty() {
+      let line = format!("{}: {}", state, events.join(", "));
+      report.log(line);
+    }
+    report.state = state;
+    let score = world.read_resource::<Score>();
+    report.wave = score.wave;
+    report.time = score.time;
+    report.positions = (&world.read_storage::<Player>(), &world.read_storage::<CharacterInputState>())
+      .join()
+      .map(|(_, ci)| ci.movement)
+      .collect();
+    if self.last_snapshot.elapsed().as_secs_f64() * 1000.0 >= CRASH_SNAPSHOT_INTERVAL {
+      self.last_snapshot = Instant::now();
+      report.save = Some(SaveGame::capture(world));
+    }
+  }
+}
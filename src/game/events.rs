@@ -0,0 +1,56 @@
+use tracing::{debug, info, trace};
+
+use crate::ecs::{self, EventChannel, Read, ReaderId};
+use crate::shaders::Position;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+  BulletFired(Position),
+  // Every hit, including the one that kills, with the damage dealt
+  ZombieHit(Position, f32),
+  // The prefab name of the zombie, or "zombie" for one spawned without a prefab
+  ZombieKilled(Position, &'static str),
+  // A bullet reached a zombie, counted apart from other damage for the accuracy
+  BulletHit(Position),
+  // Index of the player, the position of the attacker relative to it and the damage of the step
+  PlayerDamaged(usize, Position, f32),
+  PlayerKilled(usize),
+  PickupCollected(Position),
+  WaveStarted(usize),
+  // A short message for the toast stack, as a string key
+  Toast(&'static str),
+}
+
+pub type GameEvents = EventChannel<GameEvent>;
+
+// Writes the events of every step to the log. Hits and kills are at debug level, so they only show
+// when the log filter asks for them.
+pub struct EventLogSystem {
+  reader: ReaderId<GameEvent>,
+}
+
+impl EventLogSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> EventLogSystem {
+    EventLogSystem {
+      reader,
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for EventLogSystem {
+  type SystemData = Read<'a, GameEvents>;
+
+  fn run(&mut self, events: Self::SystemData) {
+    for event in events.read(&mut self.reader) {
+      match *event {
+        GameEvent::ZombieHit(position, damage) => debug!(%position, damage, "zombie hit"),
+        GameEvent::ZombieKilled(position, prefab) => debug!(%position, prefab, "zombie killed"),
+        GameEvent::PlayerDamaged(player, attacker, damage) => debug!(player, %attacker, damage, "player damaged"),
+        GameEvent::PlayerKilled(player) => info!(player, "player killed"),
+        GameEvent::WaveStarted(wave) => info!(wave, "wave started"),
+        GameEvent::PickupCollected(position) => debug!(%position, "pickup collected"),
+        GameEvent::BulletFired(..) | GameEvent::BulletHit(..) | GameEvent::Toast(..) => trace!(?event),
+      }
+    }
+  }
+}
@@ -0,0 +1,33 @@
+use crate::ecs;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+  pub current: f32,
+  pub max: f32,
+}
+
+impl Health {
+  pub fn new(max: f32) -> Health {
+    Health {
+      current: max,
+      max,
+    }
+  }
+
+  pub fn is_dead(&self) -> bool {
+    self.current <= 0.0
+  }
+
+  // Returns true only for the hit that kills, so death is reported once
+  pub fn take_damage(&mut self, amount: f32) -> bool {
+    if self.is_dead() {
+      return false;
+    }
+    self.current = (self.current - amount).max(0.0);
+    self.is_dead()
+  }
+}
+
+impl ecs::Component for Health {
+  type Storage = ecs::DenseVecStorage<Health>;
+}
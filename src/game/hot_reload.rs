@@ -0,0 +1,127 @@
+use std::fs;
+use std::time::{Instant, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::audio::mixer::Mixer;
+use crate::data;
+use crate::ecs::{World, WorldExt};
+use crate::game::constants::{ASSET_RELOAD_INTERVAL, MAP_FILE_PATH, PREFABS_PATH, TILES_PCS_H, TILES_PCS_W};
+use crate::game::localization::Localization;
+use crate::game::prefab::Prefabs;
+use crate::game::settings::{config_path, Settings};
+use crate::gfx_app::input::Rebinding;
+use crate::terrain::materials::TileMaterials;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Asset {
+  Config,
+  Prefabs,
+  Map,
+}
+
+impl Asset {
+  // The config follows the active profile, so its path is looked up on every check
+  pub fn path(self) -> String {
+    match self {
+      Asset::Config => config_path(),
+      Asset::Prefabs => PREFABS_PATH.to_string(),
+      Asset::Map => MAP_FILE_PATH.to_string(),
+    }
+  }
+}
+
+pub struct WatchedFile {
+  modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+  pub fn new(path: &str) -> WatchedFile {
+    WatchedFile { modified: last_modified(path) }
+  }
+
+  // A file showing up counts as a change, one going missing doesn't
+  pub fn has_changed(&mut self, path: &str) -> bool {
+    let modified = last_modified(path);
+    if modified.is_some() && modified != self.modified {
+      self.modified = modified;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+fn last_modified(path: &str) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Polls the config and the data files like the shader reloader polls shaders
+pub struct AssetReloader {
+  files: Vec<(Asset, WatchedFile)>,
+  last_check: Instant,
+}
+
+impl AssetReloader {
+  pub fn new() -> AssetReloader {
+    AssetReloader {
+      files: [Asset::Config, Asset::Prefabs, Asset::Map].iter()
+        .map(|&asset| (asset, WatchedFile::new(&asset.path())))
+        .collect(),
+      last_check: Instant::now(),
+    }
+  }
+
+  pub fn changed(&mut self) -> Vec<Asset> {
+    if self.last_check.elapsed().as_secs_f64() * 1000.0 < ASSET_RELOAD_INTERVAL {
+      return Vec::new();
+    }
+    self.last_check = Instant::now();
+    self.files.iter_mut()
+      .filter_map(|(asset, file)| if file.has_changed(&asset.path()) { Some(*asset) } else { None })
+      .collect()
+  }
+}
+
+impl Default for AssetReloader {
+  fn default() -> Self {
+    AssetReloader::new()
+  }
+}
+
+// Settings, strings and key bindings of the active profile. Zoom settings apply on the next start.
+pub fn reload_config(world: &mut World) {
+  let settings = Settings::load(&config_path());
+  settings.apply(&mut world.write_resource::<Mixer>());
+  world.insert(Localization::load(settings.language));
+  world.insert(settings);
+  world.write_resource::<Rebinding>().key_names.clear();
+}
+
+// A broken file is reported and the running world keeps what it had. Reloaded prefabs apply to
+// the next spawns, the map reloads the tile materials while the tile graphics need a restart.
+pub fn reload(world: &mut World, asset: Asset) {
+  let path = asset.path();
+  let result = match asset {
+    Asset::Config => {
+      reload_config(world);
+      Ok(())
+    }
+    Asset::Prefabs => fs::read_to_string(&path)
+      .map_err(|e| e.to_string())
+      .and_then(|contents| json::parse(&contents).map_err(|e| e.to_string()))
+      .and_then(|value| Prefabs::from_json(&value))
+      .map(|prefabs| world.insert(prefabs)),
+    Asset::Map => data::parse_map_file(&path)
+      .and_then(|map| if map.width as usize >= TILES_PCS_W && map.height as usize >= TILES_PCS_H {
+        Ok(map)
+      } else {
+        Err(format!("Map size {}x{} is smaller than {}x{}", map.width, map.height, TILES_PCS_W, TILES_PCS_H))
+      })
+      .map(|map| world.insert(TileMaterials::new(&map))),
+  };
+  match result {
+    Ok(()) => info!("Reloaded {}", path),
+    Err(e) => warn!("{} reload error: {}", path, e),
+  }
+}
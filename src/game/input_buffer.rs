@@ -0,0 +1,66 @@
+use cgmath::Point2;
+
+use crate::ecs::{self, Write};
+use crate::game::constants::INPUT_BUFFER_STEPS;
+
+// Edge triggered actions, held ones like movement and aiming stay as state in their control systems
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferedAction {
+  Fire(Point2<f32>),
+  Reload,
+}
+
+struct BufferedPress {
+  player_index: usize,
+  action: BufferedAction,
+  age: u32,
+}
+
+// A press is kept until a simulation step can act on it, so a press and release between two steps,
+// or a press that arrives before the state it needs, is not lost. Stale presses are dropped.
+pub struct InputBuffer {
+  presses: Vec<BufferedPress>,
+}
+
+impl InputBuffer {
+  pub fn new() -> InputBuffer {
+    InputBuffer {
+      presses: Vec::new(),
+    }
+  }
+
+  pub fn press(&mut self, player_index: usize, action: BufferedAction) {
+    self.presses.push(BufferedPress { player_index, action, age: 0 });
+  }
+
+  // Takes the oldest matching press of the player
+  pub fn consume<F>(&mut self, player_index: usize, is_match: F) -> Option<BufferedAction>
+    where F: Fn(BufferedAction) -> bool {
+    let idx = self.presses.iter().position(|p| p.player_index == player_index && is_match(p.action))?;
+    Some(self.presses.remove(idx).action)
+  }
+
+  pub fn expire(&mut self) {
+    for press in &mut self.presses {
+      press.age += 1;
+    }
+    self.presses.retain(|p| p.age <= INPUT_BUFFER_STEPS);
+  }
+}
+
+impl Default for InputBuffer {
+  fn default() -> InputBuffer {
+    InputBuffer::new()
+  }
+}
+
+// Runs after the input stage, a press gets the configured number of steps to be consumed
+pub struct InputBufferSystem;
+
+impl<'a> ecs::System<'a> for InputBufferSystem {
+  type SystemData = Write<'a, InputBuffer>;
+
+  fn run(&mut self, mut buffer: Self::SystemData) {
+    buffer.expire();
+  }
+}
@@ -0,0 +1,253 @@
+use crossbeam_channel as channel;
+
+use crate::bullet::BulletDrawable;
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Entities, ReadStorage, Write, WriteStorage};
+use crate::game::constants::{CAMERA_MAX_DISTANCE, CAMERA_MIN_DISTANCE, PROFILER_MAX_LINES};
+use crate::game::health::Health;
+use crate::game::status::{Stamina, StatusEffects};
+use crate::graphics::camera::CameraInputState;
+use crate::graphics::transform::Transform;
+use crate::shaders::Position;
+use crate::terrain_object::TerrainObjectDrawable;
+use crate::terrain_shape::TerrainShapeDrawable;
+use crate::zombie::ZombieDrawable;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InspectorControl {
+  Toggle,
+  NextEntity,
+  PreviousEntity,
+  NextField,
+  Increase,
+  Decrease,
+  Pause,
+  Step,
+}
+
+// Component values that can be changed, an entity shows the ones of the components it has
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+  MovementX,
+  MovementY,
+  Health,
+  Stamina,
+  CameraDistance,
+  PositionX,
+  PositionY,
+}
+
+const FIELDS: [Field; 7] = [Field::MovementX, Field::MovementY, Field::Health, Field::Stamina, Field::CameraDistance, Field::PositionX, Field::PositionY];
+
+impl Field {
+  fn name(self) -> &'static str {
+    match self {
+      Field::MovementX => "input movement x",
+      Field::MovementY => "input movement y",
+      Field::Health => "health",
+      Field::Stamina => "stamina",
+      Field::CameraDistance => "camera distance",
+      Field::PositionX => "transform x",
+      Field::PositionY => "transform y",
+    }
+  }
+
+  fn step(self) -> f32 {
+    match self {
+      Field::MovementX | Field::MovementY | Field::PositionX | Field::PositionY => 10.0,
+      Field::Health | Field::Stamina => 5.0,
+      Field::CameraDistance => 20.0,
+    }
+  }
+}
+
+// Lists the live entities one at a time with their component values, and holds the simulation
+// while it is paused
+pub struct Inspector {
+  pub is_visible: bool,
+  pub is_paused: bool,
+  // Single steps asked for while paused
+  steps: u32,
+  selected: usize,
+  field: usize,
+  pub lines: Vec<String>,
+}
+
+impl Inspector {
+  pub fn new() -> Inspector {
+    Inspector {
+      is_visible: false,
+      is_paused: false,
+      steps: 0,
+      selected: 0,
+      field: 0,
+      lines: Vec::new(),
+    }
+  }
+
+  // The steps the simulation takes this frame, while paused only the ones stepped through
+  pub fn allowed_steps(&mut self, steps: u32) -> u32 {
+    if self.is_paused {
+      std::mem::take(&mut self.steps)
+    } else {
+      steps
+    }
+  }
+}
+
+impl Default for Inspector {
+  fn default() -> Inspector {
+    Inspector::new()
+  }
+}
+
+// Runs every frame with the menu, so it keeps working while the simulation is paused
+pub struct InspectorSystem {
+  queue: channel::Receiver<InspectorControl>,
+}
+
+impl InspectorSystem {
+  pub fn new() -> (InspectorSystem, channel::Sender<InspectorControl>) {
+    let (tx, rx) = channel::unbounded();
+    (InspectorSystem {
+      queue: rx,
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for InspectorSystem {
+  type SystemData = (Entities<'a>,
+                     ReadStorage<'a, Player>,
+                     WriteStorage<'a, CharacterInputState>,
+                     WriteStorage<'a, Health>,
+                     WriteStorage<'a, Stamina>,
+                     WriteStorage<'a, CameraInputState>,
+                     WriteStorage<'a, Transform>,
+                     ReadStorage<'a, StatusEffects>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, BulletDrawable>,
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     ReadStorage<'a, TerrainShapeDrawable>,
+                     Write<'a, Inspector>);
+
+  fn run(&mut self, (entities, players, mut character_input, mut health, mut stamina, mut camera, mut transforms, status,
+                     zombies, bullets, terrain_objects, terrain_shapes, mut inspector): Self::SystemData) {
+    use crate::ecs::Join;
+
+    let inspector = &mut *inspector;
+    let count = entities.join().count();
+    let mut adjust = 0.0;
+    while let Ok(control) = self.queue.try_recv() {
+      match control {
+        InspectorControl::Toggle => inspector.is_visible = !inspector.is_visible,
+        _ if !inspector.is_visible => (),
+        InspectorControl::NextEntity => {
+          inspector.selected += 1;
+          inspector.field = 0;
+        }
+        InspectorControl::PreviousEntity => {
+          inspector.selected = (inspector.selected + count.max(1) - 1) % count.max(1);
+          inspector.field = 0;
+        }
+        InspectorControl::NextField => inspector.field += 1,
+        InspectorControl::Increase => adjust += 1.0,
+        InspectorControl::Decrease => adjust -= 1.0,
+        InspectorControl::Pause => {
+          inspector.is_paused = !inspector.is_paused;
+          inspector.steps = 0;
+        }
+        InspectorControl::Step if inspector.is_paused => inspector.steps += 1,
+        InspectorControl::Step => (),
+      }
+    }
+    if !inspector.is_visible {
+      return;
+    }
+
+    inspector.lines.clear();
+    let state = if inspector.is_paused { "paused" } else { "running" };
+    if count == 0 {
+      inspector.lines.push(format!("no entities, simulation {}", state));
+      return;
+    }
+    // Entities come and go, the selection stays at the same place in the list
+    inspector.selected %= count;
+    let entity = entities.join().nth(inspector.selected).expect("Inspected entity error");
+    inspector.lines.push(format!("entity {} ({} of {}), simulation {}", entity.id(), inspector.selected + 1, count, state));
+
+    let fields = FIELDS.iter()
+      .cloned()
+      .filter(|field| match field {
+        Field::MovementX | Field::MovementY => character_input.contains(entity),
+        Field::Health => health.contains(entity),
+        Field::Stamina => stamina.contains(entity),
+        Field::CameraDistance => camera.contains(entity),
+        Field::PositionX | Field::PositionY => transforms.contains(entity),
+      })
+      .collect::<Vec<_>>();
+    if !fields.is_empty() {
+      inspector.field %= fields.len();
+    }
+
+    if let (Some(&field), true) = (fields.get(inspector.field), adjust != 0.0) {
+      let change = adjust * field.step();
+      match field {
+        Field::MovementX | Field::MovementY => if let Some(ci) = character_input.get_mut(entity) {
+          let shift = if field == Field::MovementX { Position::new(change, 0.0) } else { Position::new(0.0, change) };
+          ci.movement = ci.movement + shift;
+        },
+        Field::Health => if let Some(h) = health.get_mut(entity) {
+          h.current = (h.current + change).clamp(0.0, h.max);
+        },
+        Field::Stamina => if let Some(s) = stamina.get_mut(entity) {
+          s.current = (s.current + change).clamp(0.0, s.max);
+        },
+        Field::CameraDistance => if let Some(c) = camera.get_mut(entity) {
+          c.distance = (c.distance + change).clamp(CAMERA_MIN_DISTANCE, CAMERA_MAX_DISTANCE);
+        },
+        Field::PositionX | Field::PositionY => if let Some(t) = transforms.get_mut(entity) {
+          let shift = if field == Field::PositionX { Position::new(change, 0.0) } else { Position::new(0.0, change) };
+          t.position = t.position + shift;
+        },
+      }
+    }
+
+    if let Some(p) = players.get(entity) {
+      inspector.lines.push(format!("player {}", p.index));
+    }
+    if let Some(ci) = character_input.get(entity) {
+      inspector.lines.push(format!("input previous {}, shooting {}, colliding {}", ci.previous_movement, ci.is_shooting, ci.is_colliding));
+    }
+    if let Some(s) = status.get(entity) {
+      let effects = s.active().map(|e| format!("{:?}", e)).collect::<Vec<_>>();
+      inspector.lines.push(format!("status effects [{}]", effects.join(", ")));
+    }
+    if let Some(z) = zombies.get(entity) {
+      inspector.lines.push(format!("{} at {}, health {:.2}", z.kind, z.transform.position, z.health.current));
+    }
+    if let Some(b) = bullets.get(entity) {
+      inspector.lines.push(format!("bullet at {}", b.transform.position));
+    }
+    if let Some(o) = terrain_objects.get(entity) {
+      inspector.lines.push(format!("terrain object at {}", o.transform.position));
+    }
+    if let Some(ts) = terrain_shapes.get(entity) {
+      inspector.lines.push(format!("hill piece at {}", ts.transform.position));
+    }
+
+    for (idx, &field) in fields.iter().enumerate() {
+      let value = match field {
+        Field::MovementX => character_input.get(entity).map(|ci| ci.movement.x()),
+        Field::MovementY => character_input.get(entity).map(|ci| ci.movement.y()),
+        Field::Health => health.get(entity).map(|h| h.current),
+        Field::Stamina => stamina.get(entity).map(|s| s.current),
+        Field::CameraDistance => camera.get(entity).map(|c| c.distance),
+        Field::PositionX => transforms.get(entity).map(|t| t.position.x()),
+        Field::PositionY => transforms.get(entity).map(|t| t.position.y()),
+      };
+      let marker = if idx == inspector.field { ">" } else { " " };
+      inspector.lines.push(format!("{} {} {:.2}", marker, field.name(), value.unwrap_or_default()));
+    }
+    inspector.lines.truncate(PROFILER_MAX_LINES);
+  }
+}
@@ -0,0 +1,190 @@
+use cgmath::Point2;
+use json::JsonValue;
+
+use crate::game::constants::{INVENTORY_SLOT_HEIGHT, INVENTORY_SLOT_WIDTH, WEAPON_SLOTS};
+use crate::game::localization::Localization;
+use crate::game::snapshot::Snapshot;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::TextAlign;
+use crate::hud::widgets::{Anchor, Panel, Stacking, Widget};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weapon {
+  Pistol,
+}
+
+impl Weapon {
+  pub fn name_key(self) -> &'static str {
+    match self {
+      Weapon::Pistol => "weapon.pistol",
+    }
+  }
+
+  fn name(self) -> &'static str {
+    match self {
+      Weapon::Pistol => "pistol",
+    }
+  }
+}
+
+// Weapon slots of the run, the pistol starts in the first one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Loadout {
+  pub slots: [Option<Weapon>; WEAPON_SLOTS],
+}
+
+impl Loadout {
+  pub fn new() -> Loadout {
+    let mut slots = [None; WEAPON_SLOTS];
+    slots[0] = Some(Weapon::Pistol);
+    Loadout {
+      slots,
+    }
+  }
+
+  pub fn swap(&mut self, from: usize, to: usize) {
+    if from < WEAPON_SLOTS && to < WEAPON_SLOTS {
+      self.slots.swap(from, to);
+    }
+  }
+}
+
+impl Default for Loadout {
+  fn default() -> Loadout {
+    Loadout::new()
+  }
+}
+
+// Empty slots are null
+impl Snapshot for Loadout {
+  fn to_json(&self) -> JsonValue {
+    JsonValue::Array(self.slots.iter().map(|slot| slot.map_or(JsonValue::Null, |weapon| weapon.name().into())).collect())
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Loadout, String> {
+    if value.len() != WEAPON_SLOTS {
+      return Err(format!("Loadout has {} slots instead of {}", value.len(), WEAPON_SLOTS));
+    }
+    let mut slots = [None; WEAPON_SLOTS];
+    for (slot, value) in slots.iter_mut().zip(value.members()) {
+      *slot = match value.as_str() {
+        None if value.is_null() => None,
+        Some("pistol") => Some(Weapon::Pistol),
+        _ => return Err(format!("Unknown weapon {}", value)),
+      };
+    }
+    Ok(Loadout {
+      slots,
+    })
+  }
+}
+
+// The slot row of the inventory screen. A slot is picked up by pressing it, with the mouse or
+// confirm, and put down on another slot to swap the two. The panel's pressed slot is the one held.
+pub struct InventoryScreen {
+  pub slots: Panel,
+}
+
+impl InventoryScreen {
+  pub fn new() -> InventoryScreen {
+    InventoryScreen {
+      slots: Panel {
+        align: TextAlign::Center,
+        ..Panel::new(Anchor::Center, Stacking::Horizontal, INVENTORY_SLOT_WIDTH, INVENTORY_SLOT_HEIGHT)
+      },
+    }
+  }
+
+  pub fn open(&mut self, loadout: &Loadout, strings: &Localization) {
+    self.refresh_widgets(loadout, strings);
+    self.slots.hovered = None;
+    self.slots.pressed = None;
+    self.slots.focus_first();
+  }
+
+  pub fn held(&self) -> Option<usize> {
+    self.slots.pressed
+  }
+
+  // Returns true when the focus moved
+  pub fn point(&mut self, cursor: Point2<f32>, dimensions: &Dimensions) -> bool {
+    let scale = dimensions.hidpi_factor;
+    self.slots.hovered = self.slots.widget_at(cursor.x * scale, cursor.y * scale, dimensions);
+    match self.slots.hovered {
+      Some(idx) if self.slots.focused != Some(idx) => {
+        self.slots.focused = Some(idx);
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // Starts a drag, a slot that is already held waits for the release to be put down
+  pub fn press(&mut self, cursor: Point2<f32>, dimensions: &Dimensions) -> bool {
+    self.point(cursor, dimensions);
+    if self.slots.pressed.is_none() {
+      self.slots.pressed = self.slots.hovered;
+    }
+    self.slots.hovered.is_some()
+  }
+
+  // Dropping on another slot swaps them, dropping on the same one keeps it held until the next
+  // click, anywhere else puts it back. Returns true when the loadout changed.
+  pub fn release(&mut self, loadout: &mut Loadout, strings: &Localization) -> bool {
+    match (self.slots.pressed, self.slots.hovered) {
+      (Some(from), Some(to)) if from != to => self.put_down(from, to, loadout, strings),
+      (Some(_), Some(_)) => false,
+      _ => {
+        self.slots.pressed = None;
+        false
+      }
+    }
+  }
+
+  pub fn select(&mut self, step: isize) {
+    self.slots.focus_step(step);
+  }
+
+  // Picks up the focused slot or puts the held one down on it
+  pub fn confirm(&mut self, loadout: &mut Loadout, strings: &Localization) -> bool {
+    match (self.slots.pressed, self.slots.focused) {
+      (Some(from), Some(to)) => self.put_down(from, to, loadout, strings),
+      (None, focused) => {
+        self.slots.pressed = focused;
+        false
+      }
+      _ => false,
+    }
+  }
+
+  // Returns true when a held slot was let go, otherwise backing out closes the screen
+  pub fn cancel(&mut self) -> bool {
+    self.slots.pressed.take().is_some()
+  }
+
+  fn put_down(&mut self, from: usize, to: usize, loadout: &mut Loadout, strings: &Localization) -> bool {
+    self.slots.pressed = None;
+    if from == to {
+      return false;
+    }
+    loadout.swap(from, to);
+    self.refresh_widgets(loadout, strings);
+    true
+  }
+
+  pub fn refresh_widgets(&mut self, loadout: &Loadout, strings: &Localization) {
+    let widgets = loadout.slots.iter().enumerate()
+      .map(|(idx, weapon)| {
+        let name = weapon.map_or(strings.get("inventory.empty"), |weapon| strings.get(weapon.name_key()));
+        Widget::Button(strings.format("inventory.slot", &[("slot", (idx + 1).to_string()), ("weapon", name.to_string())]))
+      })
+      .collect();
+    self.slots.set_widgets(widgets);
+  }
+}
+
+impl Default for InventoryScreen {
+  fn default() -> InventoryScreen {
+    InventoryScreen::new()
+  }
+}
@@ -0,0 +1,258 @@
+use std::thread;
+
+use crossbeam_channel as channel;
+use json::JsonValue;
+use tracing::warn;
+
+use crate::ecs::{self, Write};
+use crate::game::constants::{GAME_VERSION, LEADERBOARD_NAME_LENGTH, LEADERBOARD_PAGE, LEADERBOARD_SIZE, LEADERBOARD_TIMEOUT};
+use crate::game::localization::Localization;
+use crate::game::profiles::Profiles;
+use crate::game::records::RunRecord;
+use crate::game::score::format_time;
+use crate::game::settings::load_config;
+use crate::game::snapshot::{field_f32, field_usize};
+
+const LEADERBOARD_SECTION: &str = "leaderboard";
+
+// The `leaderboard` section of the config. Without an endpoint nothing is sent, the secret signs
+// the submitted runs and the name defaults to the profile's. The timeout is in ms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardSettings {
+  pub endpoint: String,
+  pub secret: String,
+  pub name: String,
+  pub timeout: u64,
+}
+
+impl LeaderboardSettings {
+  pub fn load(filename: &str) -> LeaderboardSettings {
+    LeaderboardSettings::from_json(&load_config(filename)[LEADERBOARD_SECTION], Profiles::load().active_name())
+  }
+
+  pub fn from_json(leaderboard: &JsonValue, profile_name: &str) -> LeaderboardSettings {
+    LeaderboardSettings {
+      endpoint: leaderboard["endpoint"].as_str().unwrap_or("").trim_end_matches('/').to_string(),
+      secret: leaderboard["secret"].as_str().unwrap_or("").to_string(),
+      name: player_name(leaderboard["name"].as_str().unwrap_or(profile_name)),
+      timeout: leaderboard["timeout"].as_u64().unwrap_or(LEADERBOARD_TIMEOUT),
+    }
+  }
+}
+
+// Names come from other players, only printable characters are shown
+pub fn player_name(name: &str) -> String {
+  name.trim().chars().filter(|c| !c.is_control()).take(LEADERBOARD_NAME_LENGTH).collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+  pub name: String,
+  pub score: usize,
+  pub kills: usize,
+  pub wave: usize,
+  pub time: f32,
+}
+
+impl LeaderboardEntry {
+  pub fn from_json(value: &JsonValue) -> Result<LeaderboardEntry, String> {
+    Ok(LeaderboardEntry {
+      name: player_name(value["name"].as_str().ok_or("Field name missing")?),
+      score: field_usize(value, "score")?,
+      kills: field_usize(value, "kills")?,
+      wave: field_usize(value, "wave")?,
+      time: field_f32(value, "time")?,
+    })
+  }
+}
+
+// The body of a submitted run, the signature of it goes along in a header
+pub fn submission(run: &RunRecord, name: &str) -> JsonValue {
+  object! {
+    "name" => name,
+    "score" => run.score,
+    "kills" => run.kills,
+    "wave" => run.wave,
+    "time" => run.time,
+    "version" => GAME_VERSION
+  }
+}
+
+// The endpoint answers a fetch with `{"scores": [...]}`, the best first
+pub fn top_entries(value: &JsonValue) -> Result<Vec<LeaderboardEntry>, String> {
+  if !value["scores"].is_array() {
+    return Err("Field scores missing".to_string());
+  }
+  value["scores"].members().take(LEADERBOARD_SIZE).map(LeaderboardEntry::from_json).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LeaderboardStatus {
+  Disabled,
+  Loading,
+  Loaded,
+  Failed,
+}
+
+// What the records menu shows of the leaderboard, copied by the menu whenever it changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardTable {
+  pub status: LeaderboardStatus,
+  pub entries: Vec<LeaderboardEntry>,
+}
+
+impl LeaderboardTable {
+  pub fn pages(&self) -> usize {
+    self.entries.len().div_ceil(LEADERBOARD_PAGE).max(1)
+  }
+
+  pub fn lines(&self, page: usize, strings: &Localization) -> Vec<String> {
+    match self.status {
+      LeaderboardStatus::Disabled => vec![strings.get("leaderboard.disabled").to_string()],
+      LeaderboardStatus::Loading => vec![strings.get("leaderboard.loading").to_string()],
+      LeaderboardStatus::Failed => vec![strings.get("leaderboard.failed").to_string()],
+      LeaderboardStatus::Loaded if self.entries.is_empty() => vec![strings.get("leaderboard.empty").to_string()],
+      LeaderboardStatus::Loaded => self.entries.iter().enumerate()
+        .skip(page * LEADERBOARD_PAGE)
+        .take(LEADERBOARD_PAGE)
+        .map(|(idx, entry)| strings.format("leaderboard.entry", &[("rank", (idx + 1).to_string()), ("name", entry.name.clone()),
+          ("score", entry.score.to_string()), ("wave", entry.wave.to_string()), ("time", format_time(entry.time))]))
+        .collect(),
+    }
+  }
+}
+
+impl Default for LeaderboardTable {
+  fn default() -> LeaderboardTable {
+    LeaderboardTable {
+      status: LeaderboardStatus::Disabled,
+      entries: Vec::new(),
+    }
+  }
+}
+
+pub enum LeaderboardRequest {
+  Submit(JsonValue),
+  Fetch,
+}
+
+// The endpoint's answer, or why there is none
+type LeaderboardResponse = (LeaderboardRequest, Result<JsonValue, String>);
+
+// Requests go to a thread of their own, so a slow endpoint never holds up a frame
+#[derive(Default)]
+pub struct Leaderboard {
+  pub table: LeaderboardTable,
+  name: String,
+  requests: Option<channel::Sender<LeaderboardRequest>>,
+  responses: Option<channel::Receiver<LeaderboardResponse>>,
+}
+
+impl Leaderboard {
+  pub fn new(settings: LeaderboardSettings) -> Leaderboard {
+    if !cfg!(feature = "leaderboard") || settings.endpoint.is_empty() {
+      return Leaderboard::default();
+    }
+    let (request_tx, request_rx) = channel::unbounded();
+    let (response_tx, response_rx) = channel::unbounded();
+    let name = settings.name.clone();
+    thread::spawn(move || {
+      for request in request_rx.iter() {
+        let response = send_request(&settings, &request);
+        if response_tx.send((request, response)).is_err() {
+          break;
+        }
+      }
+    });
+    Leaderboard {
+      table: LeaderboardTable { status: LeaderboardStatus::Loading, entries: Vec::new() },
+      name,
+      requests: Some(request_tx),
+      responses: Some(response_rx),
+    }
+  }
+
+  pub fn submit(&self, run: &RunRecord) {
+    if let Some(ref requests) = self.requests {
+      requests.send(LeaderboardRequest::Submit(submission(run, &self.name))).expect("Leaderboard submit error");
+    }
+  }
+
+  pub fn fetch(&mut self) {
+    if let Some(ref requests) = self.requests {
+      self.table.status = LeaderboardStatus::Loading;
+      requests.send(LeaderboardRequest::Fetch).expect("Leaderboard fetch error");
+    }
+  }
+
+  // A failed submission is only reported, the run stays in the local records
+  pub fn poll(&mut self) {
+    let responses = match self.responses {
+      Some(ref responses) => responses.try_iter().collect::<Vec<_>>(),
+      None => return,
+    };
+    for response in responses {
+      match response {
+        (LeaderboardRequest::Submit(_), Ok(_)) => (),
+        (LeaderboardRequest::Submit(run), Err(e)) => warn!("Leaderboard submit {} error {}", run.dump(), e),
+        (LeaderboardRequest::Fetch, result) => match result.and_then(|value| top_entries(&value)) {
+          Ok(entries) => self.table = LeaderboardTable { status: LeaderboardStatus::Loaded, entries },
+          Err(e) => {
+            warn!("Leaderboard fetch error {}", e);
+            self.table.status = LeaderboardStatus::Failed;
+          }
+        },
+      }
+    }
+  }
+}
+
+// HMAC-SHA256 of the body with the shared secret, in hex
+#[cfg(feature = "leaderboard")]
+pub fn signature(secret: &str, body: &str) -> String {
+  use hmac::{Hmac, Mac};
+  use sha2::Sha256;
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("Leaderboard signature key error");
+  mac.update(body.as_bytes());
+  mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(feature = "leaderboard")]
+fn send_request(settings: &LeaderboardSettings, request: &LeaderboardRequest) -> Result<JsonValue, String> {
+  use std::time::Duration;
+
+  let agent = ureq::AgentBuilder::new().timeout(Duration::from_millis(settings.timeout)).build();
+  let url = format!("{}/scores", settings.endpoint);
+  let response = match request {
+    LeaderboardRequest::Submit(run) => {
+      let body = run.dump();
+      agent.post(&url)
+        .set("Content-Type", "application/json")
+        .set("X-Signature", &signature(&settings.secret, &body))
+        .send_string(&body)
+    }
+    LeaderboardRequest::Fetch => agent.get(&url).query("limit", &LEADERBOARD_SIZE.to_string()).call(),
+  };
+  let body = response.map_err(|e| e.to_string())?.into_string().map_err(|e| e.to_string())?;
+  // A submission may answer with an empty body
+  if body.trim().is_empty() {
+    return Ok(JsonValue::Null);
+  }
+  json::parse(&body).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "leaderboard"))]
+fn send_request(_: &LeaderboardSettings, _: &LeaderboardRequest) -> Result<JsonValue, String> {
+  Err("Built without the leaderboard feature".to_string())
+}
+
+pub struct LeaderboardSystem;
+
+impl<'a> ecs::System<'a> for LeaderboardSystem {
+  type SystemData = Write<'a, Leaderboard>;
+
+  fn run(&mut self, mut leaderboard: Self::SystemData) {
+    leaderboard.poll();
+  }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+
+use json::JsonValue;
+use tracing::warn;
+
+use crate::game::constants::LANGUAGE_DIR;
+use crate::game::settings::Language;
+
+fn load_table(language: Language) -> JsonValue {
+  let filename = format!("{}/{}.json", LANGUAGE_DIR, language.code());
+  match fs::read_to_string(&filename).map(|contents| json::parse(&contents)) {
+    Ok(Ok(value)) => value,
+    Ok(Err(e)) => {
+      warn!("String table {} parse error {:?}", filename, e);
+      JsonValue::new_object()
+    }
+    Err(e) => {
+      warn!("String table {} not found: {}", filename, e);
+      JsonValue::new_object()
+    }
+  }
+}
+
+fn to_map(table: &JsonValue) -> HashMap<String, String> {
+  table.entries()
+    .filter_map(|(key, value)| value.as_str().map(|s| (key.to_string(), s.to_string())))
+    .collect()
+}
+
+// Player facing text by key. Keys missing from the language fall back to English, and to the key
+// itself when English misses it too, so a gap shows up on screen instead of as an empty line.
+#[derive(Clone, Debug, Default)]
+pub struct Localization {
+  language: Option<Language>,
+  strings: HashMap<String, String>,
+  fallback: HashMap<String, String>,
+}
+
+impl Localization {
+  pub fn load(language: Language) -> Localization {
+    let fallback = load_table(Language::English);
+    let table = if language == Language::English { JsonValue::new_object() } else { load_table(language) };
+    Localization::from_json(language, &table, &fallback)
+  }
+
+  pub fn from_json(language: Language, table: &JsonValue, fallback: &JsonValue) -> Localization {
+    Localization {
+      language: Some(language),
+      strings: to_map(table),
+      fallback: to_map(fallback),
+    }
+  }
+
+  pub fn language(&self) -> Option<Language> {
+    self.language
+  }
+
+  pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+    self.strings.get(key)
+      .or_else(|| self.fallback.get(key))
+      .map_or(key, |s| s.as_str())
+  }
+
+  // Fills {name} placeholders, translations may order them as they need
+  pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+    args.iter().fold(self.get(key).to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+  }
+}
@@ -1,23 +1,76 @@
 use num::Integer;
 use rand;
 use rand::distributions::uniform::SampleUniform;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+pub mod chat;
+pub mod console;
+pub mod crash;
 pub mod constants;
+pub mod events;
+pub mod health;
+pub mod hot_reload;
+pub mod input_buffer;
+pub mod inspector;
+pub mod inventory;
+pub mod leaderboard;
+pub mod localization;
+pub mod prefab;
+pub mod profiles;
+pub mod records;
+pub mod save;
+pub mod scratch;
+pub mod score;
+pub mod scripting;
+pub mod settings;
+pub mod snapshot;
+pub mod spawner;
+pub mod stats;
+pub mod state;
+pub mod status;
+pub mod storage;
 
-pub fn get_random_bool() -> bool {
-  let mut rnd = rand::thread_rng();
-  rnd.gen()
+// Gameplay randomness goes through this resource, so a fixed seed with the fixed step replays the same world
+pub struct GameRng(StdRng);
+
+impl GameRng {
+  pub fn new(seed: Option<u64>) -> GameRng {
+    GameRng(seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64))
+  }
+
+  // Seeds the generators of the entities updated in parallel during one step
+  pub fn next_seed(&mut self) -> u64 {
+    self.0.gen()
+  }
+
+  // The generator of one entity for the step, the same whichever thread updates it
+  pub fn for_entity(seed: u64, idx: usize) -> GameRng {
+    GameRng(StdRng::seed_from_u64(seed ^ (idx as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> GameRng {
+    GameRng::new(None)
+  }
 }
 
-pub fn get_rand_from_range<T>(min: T, max: T) -> T
+pub fn get_random_bool(rng: &mut GameRng) -> bool {
+  rng.0.gen()
+}
+
+pub fn get_rand_from_range<T>(rng: &mut GameRng, min: T, max: T) -> T
   where T: Integer + SampleUniform {
-  let mut rnd = rand::thread_rng();
-  rnd.gen_range(min, max)
+  rng.0.gen_range(min, max)
+}
+
+// Uniform in -max..max, zero when there is no range
+pub fn get_rand_offset(rng: &mut GameRng, max: f32) -> f32 {
+  if max > 0.0 { rng.0.gen_range(-max, max) } else { 0.0 }
 }
 
 #[allow(dead_code)]
-pub fn get_weighted_random(weight: f32) -> bool {
-  let mut rnd = rand::thread_rng();
-  rnd.gen::<f32>() < weight
+pub fn get_weighted_random(rng: &mut GameRng, weight: f32) -> bool {
+  rng.0.gen::<f32>() < weight
 }
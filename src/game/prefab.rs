@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use json::JsonValue;
+
+use crate::critter::palette::CritterPalette;
+use crate::data;
+use crate::game::health::Health;
+use crate::game::snapshot::{field_f32, Snapshot};
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::ZombieDrawable;
+
+// The archetype tells which drawable an instance spawns as
+#[derive(Clone, Copy, PartialEq)]
+pub enum Archetype {
+  Zombie,
+  TerrainObject(TerrainTexture),
+}
+
+pub enum Instance {
+  Zombie(ZombieDrawable),
+  TerrainObject(TerrainObjectDrawable),
+}
+
+// Prefab names go into game events, which are Copy, so each distinct name is leaked once and shared
+pub fn intern(name: &str) -> &'static str {
+  static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+  let mut names = NAMES.lock().expect("Prefab name lock error");
+  match names.iter().find(|&&interned| interned == name) {
+    Some(&interned) => interned,
+    None => {
+      let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+      names.push(interned);
+      interned
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Prefab {
+  pub name: &'static str,
+  pub archetype: Archetype,
+  pub health: Option<f32>,
+  pub palette: CritterPalette,
+  pub nameplate: Option<String>,
+}
+
+fn terrain_texture(value: &JsonValue) -> Result<TerrainTexture, String> {
+  match value.as_str() {
+    Some("ammo") => Ok(TerrainTexture::Ammo),
+    Some("house") => Ok(TerrainTexture::House),
+    Some("tree") => Ok(TerrainTexture::Tree),
+    _ => Err(format!("Unknown terrain object {}", value)),
+  }
+}
+
+impl Prefab {
+  // Each entry lists components by name, exactly one of them picks the archetype
+  fn from_json(name: &str, value: &JsonValue) -> Result<Prefab, String> {
+    let archetype = match (value.has_key("zombie"), value.has_key("terrain_object")) {
+      (true, false) => Archetype::Zombie,
+      (false, true) => Archetype::TerrainObject(terrain_texture(&value["terrain_object"])?),
+      _ => return Err("Prefab needs either a zombie or a terrain_object component".to_string()),
+    };
+    let health = if value.has_key("health") { Some(field_f32(&value["health"], "max")?) } else { None };
+    let palette = if value.has_key("palette") { CritterPalette::from_json(&value["palette"])? } else { CritterPalette::Original };
+
+    let nameplate = value["nameplate"].as_str().map(String::from);
+
+    Ok(Prefab {
+      name: intern(name),
+      archetype,
+      health,
+      palette,
+      nameplate,
+    })
+  }
+
+  pub fn instantiate(&self, position: Position) -> Instance {
+    match self.archetype {
+      Archetype::Zombie => {
+        let mut zombie = ZombieDrawable::new(position);
+        zombie.kind = self.name;
+        zombie.palette = self.palette;
+        zombie.nameplate = self.nameplate.clone();
+        if let Some(max) = self.health {
+          zombie.health = Health::new(max);
+        }
+        Instance::Zombie(zombie)
+      }
+      Archetype::TerrainObject(texture) => Instance::TerrainObject(TerrainObjectDrawable::new(position, texture)),
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct Prefabs {
+  prefabs: HashMap<String, Prefab>,
+}
+
+impl Prefabs {
+  pub fn load(filename: &str) -> Prefabs {
+    match Prefabs::from_json(&data::load_json(filename)) {
+      Ok(prefabs) => prefabs,
+      Err(e) => panic!("Prefabs {} error {}", filename, e),
+    }
+  }
+
+  pub fn from_json(value: &JsonValue) -> Result<Prefabs, String> {
+    let prefabs = value.entries()
+      .map(|(name, entry)| Prefab::from_json(name, entry)
+        .map(|prefab| (name.to_string(), prefab))
+        .map_err(|e| format!("{}: {}", name, e)))
+      .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(Prefabs { prefabs })
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Prefab> {
+    self.prefabs.get(name)
+  }
+}
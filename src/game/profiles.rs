@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::PathBuf;
+
+use json::JsonValue;
+use tracing::warn;
+
+use crate::game::constants::{CONFIG_DIR, MAX_PROFILES, PROFILES_PATH};
+
+// The game's directory in the platform's config directory, such as ~/.config/hinterland on Linux.
+// Files stay next to the game where there is none.
+fn root_dir() -> PathBuf {
+  match dirs::config_dir().map(|dir| dir.join(CONFIG_DIR)) {
+    Some(dir) => match fs::create_dir_all(&dir) {
+      Ok(()) => dir,
+      Err(e) => {
+        warn!("Config directory {} create error {}", dir.display(), e);
+        PathBuf::new()
+      }
+    },
+    None => PathBuf::new(),
+  }
+}
+
+// A file of the active profile, such as its config or a save slot
+pub fn user_file(name: &str) -> PathBuf {
+  Profiles::load().file(name)
+}
+
+// Every profile has its own settings, bindings, records and save slots. The list and the active
+// one are kept in profiles.json, where the names can be changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profiles {
+  pub names: Vec<String>,
+  pub active: usize,
+}
+
+impl Profiles {
+  pub fn new() -> Profiles {
+    Profiles {
+      names: (1..=MAX_PROFILES).map(|idx| format!("Player {}", idx)).collect(),
+      active: 0,
+    }
+  }
+
+  pub fn load() -> Profiles {
+    let filename = root_dir().join(PROFILES_PATH);
+    match fs::read_to_string(&filename) {
+      Ok(contents) => match json::parse(&contents) {
+        Ok(value) => Profiles::from_json(&value),
+        Err(e) => {
+          warn!("Profiles {} parse error {:?}, using defaults", filename.display(), e);
+          Profiles::new()
+        }
+      },
+      Err(_) => Profiles::new(),
+    }
+  }
+
+  pub fn save(&self) {
+    let filename = root_dir().join(PROFILES_PATH);
+    if let Err(e) = fs::write(&filename, self.to_json().pretty(2)) {
+      warn!("Profiles {} save error {}", filename.display(), e);
+    }
+  }
+
+  // Missing names keep their defaults, there are always as many profiles
+  pub fn from_json(value: &JsonValue) -> Profiles {
+    let mut profiles = Profiles::new();
+    for (name, value) in profiles.names.iter_mut().zip(value["profiles"].members()) {
+      if let Some(value) = value.as_str() {
+        *name = value.to_string();
+      }
+    }
+    profiles.active = value["active"].as_usize().filter(|&idx| idx < MAX_PROFILES).unwrap_or(0);
+    profiles
+  }
+
+  pub fn to_json(&self) -> JsonValue {
+    object! {
+      "active" => self.active,
+      "profiles" => self.names.clone()
+    }
+  }
+
+  pub fn active_name(&self) -> &str {
+    &self.names[self.active]
+  }
+
+  // The first profile uses the game's directory, so files from before there were profiles stay in it
+  pub fn dir(&self, idx: usize) -> PathBuf {
+    let root = root_dir();
+    if idx == 0 {
+      return root;
+    }
+    let dir = root.join(format!("profile_{}", idx + 1));
+    if let Err(e) = fs::create_dir_all(&dir) {
+      warn!("Profile directory {} create error {}", dir.display(), e);
+    }
+    dir
+  }
+
+  pub fn file(&self, name: &str) -> PathBuf {
+    self.dir(self.active).join(name)
+  }
+}
+
+impl Default for Profiles {
+  fn default() -> Profiles {
+    Profiles::new()
+  }
+}
@@ -0,0 +1,152 @@
+use json::JsonValue;
+use tracing::warn;
+
+use crate::game::constants::{RECORD_RUNS, RECORDS_PATH, RECORDS_VERSION};
+use crate::game::localization::Localization;
+use crate::game::profiles::user_file;
+use crate::game::score::{format_time, Score};
+use crate::game::snapshot::{field_f32, field_usize, Snapshot};
+use crate::game::storage;
+
+pub fn records_path() -> String {
+  user_file(RECORDS_PATH).to_string_lossy().into_owned()
+}
+
+// The summary of a finished run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunRecord {
+  pub score: usize,
+  pub kills: usize,
+  pub wave: usize,
+  pub best_streak: usize,
+  // Seconds survived
+  pub time: f32,
+}
+
+impl RunRecord {
+  pub fn new(score: &Score) -> RunRecord {
+    RunRecord {
+      score: score.score,
+      kills: score.kills,
+      wave: score.wave,
+      best_streak: score.best_streak,
+      time: score.time,
+    }
+  }
+
+  pub fn line(&self, strings: &Localization) -> String {
+    strings.format("records.run", &[("time", format_time(self.time)), ("kills", self.kills.to_string()),
+      ("wave", self.wave.to_string()), ("score", self.score.to_string())])
+  }
+}
+
+// Fields added in later versions have to fall back to a default, so older files keep loading
+impl Snapshot for RunRecord {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "score" => self.score,
+      "kills" => self.kills,
+      "wave" => self.wave,
+      "best_streak" => self.best_streak,
+      "time" => self.time
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<RunRecord, String> {
+    Ok(RunRecord {
+      score: field_usize(value, "score")?,
+      kills: field_usize(value, "kills")?,
+      wave: field_usize(value, "wave")?,
+      best_streak: field_usize(value, "best_streak")?,
+      time: field_f32(value, "time")?,
+    })
+  }
+}
+
+// All time bests and the latest runs. The bests are kept apart, they outlive the runs that set them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Records {
+  pub best_score: usize,
+  pub most_kills: usize,
+  pub longest_time: f32,
+  pub runs: Vec<RunRecord>,
+}
+
+impl Records {
+  // Returns the string keys of the records the run broke
+  pub fn add(&mut self, run: RunRecord) -> Vec<&'static str> {
+    let mut broken = Vec::new();
+    if run.score > self.best_score {
+      self.best_score = run.score;
+      broken.push("records.new_best_score");
+    }
+    if run.kills > self.most_kills {
+      self.most_kills = run.kills;
+      broken.push("records.new_most_kills");
+    }
+    if run.time > self.longest_time {
+      self.longest_time = run.time;
+      broken.push("records.new_longest_time");
+    }
+    self.runs.insert(0, run);
+    self.runs.truncate(RECORD_RUNS);
+    broken
+  }
+
+  // The records menu page
+  pub fn lines(&self, strings: &Localization) -> Vec<String> {
+    if self.runs.is_empty() {
+      return vec![strings.get("records.empty").to_string()];
+    }
+    vec![
+      strings.format("records.best_score", &[("score", self.best_score.to_string())]),
+      strings.format("records.most_kills", &[("kills", self.most_kills.to_string())]),
+      strings.format("records.longest_time", &[("time", format_time(self.longest_time))]),
+      strings.get("records.recent").to_string(),
+    ].into_iter()
+      .chain(self.runs.iter().map(|run| run.line(strings)))
+      .collect()
+  }
+
+  // A missing file means no runs yet, a broken one that can't be repaired or one from a newer version starts over
+  pub fn load(filename: &str) -> Records {
+    match storage::read_json(filename).and_then(|value| value.map(|value| Records::from_json(&value)).transpose()) {
+      Ok(records) => records.unwrap_or_default(),
+      Err(e) => {
+        warn!("Records {} load error {}", filename, e);
+        Records::default()
+      }
+    }
+  }
+
+  pub fn save(&self, filename: &str) {
+    if let Err(e) = storage::write_json(filename, &self.to_json()) {
+      warn!("Records {} save error {}", filename, e);
+    }
+  }
+}
+
+impl Snapshot for Records {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "version" => RECORDS_VERSION,
+      "best_score" => self.best_score,
+      "most_kills" => self.most_kills,
+      "longest_time" => self.longest_time,
+      "runs" => JsonValue::Array(self.runs.iter().map(Snapshot::to_json).collect())
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Records, String> {
+    let version = field_usize(value, "version")?;
+    if version > RECORDS_VERSION {
+      return Err(format!("Records version {} is newer than the supported {}", version, RECORDS_VERSION));
+    }
+    Ok(Records {
+      best_score: field_usize(value, "best_score")?,
+      most_kills: field_usize(value, "most_kills")?,
+      longest_time: field_f32(value, "longest_time")?,
+      runs: value["runs"].members().map(RunRecord::from_json).collect::<Result<_, _>>()?,
+    })
+  }
+}
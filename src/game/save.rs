@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+
+use json::JsonValue;
+use tracing::warn;
+
+use crate::ecs::{World, WorldExt};
+use crate::game::constants::{AUTOSAVES, SAVE_VERSION};
+use crate::game::inventory::Loadout;
+use crate::game::profiles::user_file;
+use crate::game::score::Score;
+use crate::game::snapshot::{field_usize, Snapshot, WorldSnapshot};
+use crate::game::spawner::WaveState;
+use crate::game::storage;
+
+// The player's save slots and the auto-saves taken when a wave starts, the newest auto-save first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveFile {
+  Slot(usize),
+  Auto(usize),
+}
+
+impl SaveFile {
+  pub fn path(self) -> String {
+    let name = match self {
+      SaveFile::Slot(idx) => format!("save_{}.json", idx + 1),
+      SaveFile::Auto(idx) => format!("autosave_{}.json", idx + 1),
+    };
+    user_file(&name).to_string_lossy().into_owned()
+  }
+}
+
+// Moves every file one place down the list, the last one is overwritten and the first left free
+pub fn rotate_files(paths: &[String]) {
+  for pair in paths.windows(2).rev() {
+    if Path::new(&pair[0]).exists() {
+      if let Err(e) = fs::rename(&pair[0], &pair[1]) {
+        warn!("Save {} move error {}", pair[0], e);
+      }
+    }
+  }
+}
+
+pub fn autosave(world: &World) {
+  let paths = (0..AUTOSAVES).map(|idx| SaveFile::Auto(idx).path()).collect::<Vec<_>>();
+  rotate_files(&paths);
+  SaveGame::capture(world).save(&paths[0]);
+}
+
+// A saved run: the world snapshot with the progress and loadout kept in resources. The version
+// is written with every save, a save from a newer version of the game is refused.
+pub struct SaveGame {
+  pub world: WorldSnapshot,
+  pub score: Score,
+  pub loadout: Loadout,
+}
+
+impl SaveGame {
+  pub fn capture(world: &World) -> SaveGame {
+    SaveGame {
+      world: WorldSnapshot::capture(world),
+      score: (*world.read_resource::<Score>()).clone(),
+      loadout: (*world.read_resource::<Loadout>()).clone(),
+    }
+  }
+
+  // Expects the entities of a fresh run, the spawner carries on with the saved wave
+  pub fn restore(&self, world: &mut World) {
+    self.world.restore(world);
+    world.insert(self.score.clone());
+    world.insert(self.loadout.clone());
+    world.insert(WaveState::resume(self.score.wave));
+  }
+
+  pub fn save(&self, filename: &str) {
+    if let Err(e) = storage::write_json(filename, &self.to_json()) {
+      warn!("Save {} write error {}", filename, e);
+    }
+  }
+
+  // The wave and time survived of a slot for the slot pages, None for an empty or broken slot
+  pub fn peek(file: SaveFile) -> Option<(usize, f32)> {
+    SaveGame::load(&file.path()).map(|save| (save.score.wave, save.score.time))
+  }
+
+  pub fn load(filename: &str) -> Option<SaveGame> {
+    let result = storage::read_json(filename)
+      .and_then(|value| value.map(|value| SaveGame::from_json(&value)).transpose());
+    match result {
+      Ok(save) => save,
+      Err(e) => {
+        warn!("Save {} load error {}", filename, e);
+        None
+      }
+    }
+  }
+}
+
+impl Snapshot for SaveGame {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "version" => SAVE_VERSION,
+      "world" => self.world.to_json(),
+      "score" => self.score.to_json(),
+      "loadout" => self.loadout.to_json()
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<SaveGame, String> {
+    let version = field_usize(value, "version")?;
+    if version > SAVE_VERSION {
+      return Err(format!("Save version {} is newer than the supported {}", version, SAVE_VERSION));
+    }
+    Ok(SaveGame {
+      world: WorldSnapshot::from_json(&value["world"])?,
+      score: Score::from_json(&value["score"])?,
+      loadout: Loadout::from_json(&value["loadout"])?,
+    })
+  }
+}
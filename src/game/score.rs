@@ -0,0 +1,167 @@
+use json::JsonValue;
+
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::{MAX_SCORE_MULTIPLIER, SCORE_PER_KILL, STREAK_STEP, STREAK_WINDOW};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::localization::Localization;
+use crate::game::snapshot::{field_f32, field_usize, Snapshot};
+use crate::graphics::DeltaTime;
+
+// Minutes and seconds
+pub fn format_time(time: f32) -> String {
+  let seconds = time as usize;
+  format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Progress of the current run, shown on the HUD and in the summary after death
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+  pub kills: usize,
+  pub score: usize,
+  pub wave: usize,
+  pub streak: usize,
+  pub best_streak: usize,
+  // Seconds survived
+  pub time: f32,
+  streak_remaining: f32,
+}
+
+impl Score {
+  pub fn new() -> Score {
+    Score {
+      kills: 0,
+      score: 0,
+      wave: 1,
+      streak: 0,
+      best_streak: 0,
+      time: 0.0,
+      streak_remaining: 0.0,
+    }
+  }
+
+  // Every few kills of a streak raise the multiplier by one, up to the cap
+  pub fn multiplier(&self) -> usize {
+    (1 + self.streak / STREAK_STEP).min(MAX_SCORE_MULTIPLIER)
+  }
+
+  pub fn register_kill(&mut self) {
+    self.kills += 1;
+    self.streak += 1;
+    self.best_streak = self.best_streak.max(self.streak);
+    self.streak_remaining = STREAK_WINDOW;
+    self.score += SCORE_PER_KILL * self.multiplier();
+  }
+
+  pub fn break_streak(&mut self) {
+    self.streak = 0;
+    self.streak_remaining = 0.0;
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    self.time += delta;
+    if self.streak > 0 {
+      self.streak_remaining -= delta;
+      if self.streak_remaining <= 0.0 {
+        self.break_streak();
+      }
+    }
+  }
+
+  pub fn summary(&self, strings: &Localization) -> Vec<String> {
+    vec![
+      strings.format("summary.survived", &[("time", format_time(self.time))]),
+      strings.format("summary.kills", &[("kills", self.kills.to_string())]),
+      strings.format("summary.waves_cleared", &[("waves", self.wave.saturating_sub(1).to_string())]),
+      strings.format("summary.score", &[("score", self.score.to_string())]),
+      strings.format("summary.best_streak", &[("streak", self.best_streak.to_string())]),
+    ]
+  }
+
+  pub fn hud_lines(&self, strings: &Localization) -> Vec<String> {
+    let multiplier = self.multiplier();
+    let score = if multiplier > 1 {
+      strings.format("hud.score_multiplier", &[("score", self.score.to_string()), ("multiplier", multiplier.to_string())])
+    } else {
+      strings.format("hud.score", &[("score", self.score.to_string())])
+    };
+    vec![
+      score,
+      strings.format("hud.kills", &[("kills", self.kills.to_string())]),
+      strings.format("hud.wave", &[("wave", self.wave.to_string())]),
+    ]
+  }
+}
+
+impl Default for Score {
+  fn default() -> Score {
+    Score::new()
+  }
+}
+
+// A running streak is not kept, it would run out before a loaded game is back in action
+impl Snapshot for Score {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "kills" => self.kills,
+      "score" => self.score,
+      "wave" => self.wave,
+      "best_streak" => self.best_streak,
+      "time" => self.time
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Score, String> {
+    Ok(Score {
+      kills: field_usize(value, "kills")?,
+      score: field_usize(value, "score")?,
+      wave: field_usize(value, "wave")?,
+      best_streak: field_usize(value, "best_streak")?,
+      time: field_f32(value, "time")?,
+      ..Score::new()
+    })
+  }
+}
+
+pub struct ScoreSystem {
+  reader: ReaderId<GameEvent>,
+}
+
+impl ScoreSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> ScoreSystem {
+    ScoreSystem {
+      reader,
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for ScoreSystem {
+  type SystemData = (Write<'a, Score>,
+                     Write<'a, GameEvents>,
+                     Read<'a, DeltaTime>);
+
+  fn run(&mut self, (mut score, mut events, dt): Self::SystemData) {
+    score.tick(dt.0 as f32);
+    let mut toasts = Vec::new();
+    for event in events.read(&mut self.reader) {
+      match *event {
+        GameEvent::ZombieKilled(..) => {
+          let multiplier = score.multiplier();
+          score.register_kill();
+          if score.kills == 1 {
+            toasts.push("toast.first_blood");
+          }
+          if score.multiplier() == MAX_SCORE_MULTIPLIER && multiplier < MAX_SCORE_MULTIPLIER {
+            toasts.push("toast.max_multiplier");
+          }
+        }
+        // Getting hit ends the streak
+        GameEvent::PlayerDamaged(..) => score.break_streak(),
+        GameEvent::WaveStarted(wave) => score.wave = wave,
+        _ => (),
+      }
+    }
+    for toast in toasts {
+      events.single_write(GameEvent::Toast(toast));
+    }
+  }
+}
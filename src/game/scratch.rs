@@ -0,0 +1,84 @@
+use std::ops::Deref;
+
+// A buffer a system keeps between frames. It is refilled every frame instead of collected anew, so
+// once it has grown to its usual size a frame allocates nothing.
+pub struct Scratch<T>(Vec<T>);
+
+impl<T> Scratch<T> {
+  pub fn new() -> Scratch<T> {
+    Scratch(Vec::new())
+  }
+
+  // Drops what the last frame left and returns the new items
+  pub fn fill<I>(&mut self, items: I) -> &[T]
+    where I: IntoIterator<Item=T> {
+    self.0.clear();
+    self.0.extend(items);
+    &self.0
+  }
+}
+
+impl<T> Default for Scratch<T> {
+  fn default() -> Scratch<T> {
+    Scratch::new()
+  }
+}
+
+impl<T> Deref for Scratch<T> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.0
+  }
+}
+
+// Emptied Vecs waiting to be handed out again with their capacity
+pub struct VecPool<T> {
+  free: Vec<Vec<T>>,
+}
+
+impl<T> VecPool<T> {
+  pub fn new() -> VecPool<T> {
+    VecPool { free: Vec::new() }
+  }
+
+  pub fn take(&mut self) -> Vec<T> {
+    self.free.pop().unwrap_or_default()
+  }
+
+  pub fn give(&mut self, mut items: Vec<T>) {
+    items.clear();
+    self.free.push(items);
+  }
+}
+
+impl<T> Default for VecPool<T> {
+  fn default() -> VecPool<T> {
+    VecPool::new()
+  }
+}
+
+// An emptied list per item of a frame, each keeping its capacity. Lists past the items of a frame
+// are left for a later, longer one.
+pub struct ScratchRows<T>(Vec<Vec<T>>);
+
+impl<T> ScratchRows<T> {
+  pub fn new() -> ScratchRows<T> {
+    ScratchRows(Vec::new())
+  }
+
+  pub fn rows(&mut self, len: usize) -> &mut [Vec<T>] {
+    if self.0.len() < len {
+      self.0.resize_with(len, Vec::new);
+    }
+    let rows = &mut self.0[..len];
+    rows.iter_mut().for_each(Vec::clear);
+    rows
+  }
+}
+
+impl<T> Default for ScratchRows<T> {
+  fn default() -> ScratchRows<T> {
+    ScratchRows::new()
+  }
+}
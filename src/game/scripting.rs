@@ -0,0 +1,224 @@
+use std::sync::{Arc, Mutex};
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use tracing::warn;
+
+use crate::character::Player;
+use crate::critter::palette::CritterPalette;
+use crate::data;
+use crate::ecs::{self, Join, LazyUpdate, Read, ReadStorage, ReaderId, Write, WriteStorage};
+use crate::game::console::Console;
+use crate::game::constants::{GAMEPLAY_SCRIPT_PATH, SCRIPT_MAX_OPERATIONS};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::GameRng;
+use crate::game::health::Health;
+use crate::game::prefab::Prefabs;
+use crate::game::scratch::Scratch;
+use crate::game::spawner;
+use crate::graphics::{distance, GameTime};
+use crate::shaders::Position;
+use crate::zombie::ZombieDrawable;
+
+enum ScriptCommand {
+  SpawnZombie(Position, CritterPalette),
+  SpawnPickup(Position),
+  SpawnPrefab(String, Position),
+  DamageZombies(Position, f32, f32),
+}
+
+// Script functions only queue commands and read this snapshot, the system applies them afterwards
+#[derive(Default)]
+struct ScriptContext {
+  commands: Vec<ScriptCommand>,
+  zombie_count: i64,
+  player_health: f64,
+  wave: i64,
+}
+
+fn palette_from_index(index: i64) -> CritterPalette {
+  match index {
+    1 => CritterPalette::Toxic,
+    2 => CritterPalette::Frozen,
+    _ => CritterPalette::Original,
+  }
+}
+
+fn create_engine(context: &Arc<Mutex<ScriptContext>>) -> Engine {
+  let mut engine = Engine::new();
+
+  let ctx = context.clone();
+  engine.register_fn("spawn_zombie", move |x: f64, y: f64, palette: i64| {
+    let command = ScriptCommand::SpawnZombie(Position::new(x as f32, y as f32), palette_from_index(palette));
+    ctx.lock().expect("Script context lock error").commands.push(command);
+  });
+  let ctx = context.clone();
+  engine.register_fn("spawn_pickup", move |x: f64, y: f64| {
+    let command = ScriptCommand::SpawnPickup(Position::new(x as f32, y as f32));
+    ctx.lock().expect("Script context lock error").commands.push(command);
+  });
+  let ctx = context.clone();
+  engine.register_fn("spawn_prefab", move |name: &str, x: f64, y: f64| {
+    let command = ScriptCommand::SpawnPrefab(name.to_string(), Position::new(x as f32, y as f32));
+    ctx.lock().expect("Script context lock error").commands.push(command);
+  });
+  let ctx = context.clone();
+  engine.register_fn("damage_zombies", move |x: f64, y: f64, radius: f64, amount: f64| {
+    let command = ScriptCommand::DamageZombies(Position::new(x as f32, y as f32), radius as f32, amount as f32);
+    ctx.lock().expect("Script context lock error").commands.push(command);
+  });
+  let ctx = context.clone();
+  engine.register_fn("zombie_count", move || ctx.lock().expect("Script context lock error").zombie_count);
+  let ctx = context.clone();
+  engine.register_fn("player_health", move || ctx.lock().expect("Script context lock error").player_health);
+  let ctx = context.clone();
+  engine.register_fn("wave", move || ctx.lock().expect("Script context lock error").wave);
+
+  // Runaway scripts are stopped after a fixed count of operations rather than a time budget, so
+  // replays and lockstep peers stop them at the same point
+  engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+  engine
+}
+
+pub struct ScriptSystem {
+  engine: Engine,
+  ast: AST,
+  scope: Scope<'static>,
+  context: Arc<Mutex<ScriptContext>>,
+  reader: ReaderId<GameEvent>,
+  hooks: Scratch<GameEvent>,
+}
+
+impl ScriptSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> ScriptSystem {
+    match data::load_script(GAMEPLAY_SCRIPT_PATH) {
+      Ok(script) => ScriptSystem::with_script(reader, &script),
+      Err(e) => {
+        warn!("{}, the game runs without scripts", e);
+        ScriptSystem::with_script(reader, "")
+      }
+    }
+  }
+
+  // A script that doesn't compile or fails while it is set up is left out, without any hooks
+  pub fn with_script(reader: ReaderId<GameEvent>, script: &str) -> ScriptSystem {
+    let context = Arc::new(Mutex::new(ScriptContext::default()));
+    let engine = create_engine(&context);
+    let mut scope = Scope::new();
+    let ast = match engine.compile(script) {
+      Ok(ast) => match engine.run_ast_with_scope(&mut scope, &ast) {
+        Ok(()) => ast,
+        Err(e) => {
+          warn!("Script {} init error {}, the game runs without scripts", GAMEPLAY_SCRIPT_PATH, e);
+          scope.clear();
+          AST::empty()
+        }
+      },
+      Err(e) => {
+        warn!("Script {} compile error {}, the game runs without scripts", GAMEPLAY_SCRIPT_PATH, e);
+        AST::empty()
+      }
+    };
+
+    ScriptSystem {
+      engine,
+      ast,
+      scope,
+      context,
+      reader,
+      hooks: Scratch::new(),
+    }
+  }
+
+  fn has_hook(&self, name: &str) -> bool {
+    self.ast.iter_functions().any(|f| f.name == name)
+  }
+
+  // Returns false when the hook runs out of operations, the remaining hooks of this step are skipped
+  fn call_hook<A: rhai::FuncArgs>(&mut self, name: &str, args: A) -> bool {
+    if !self.has_hook(name) {
+      return true;
+    }
+    match self.engine.call_fn::<Dynamic>(&mut self.scope, &self.ast, name, args) {
+      Ok(_) => true,
+      Err(e) => {
+        warn!("Script hook {} error {}", name, e);
+        !matches!(*e, rhai::EvalAltResult::ErrorTooManyOperations(..))
+      }
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for ScriptSystem {
+  type SystemData = (WriteStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, Health>,
+                     ReadStorage<'a, Player>,
+                     Read<'a, LazyUpdate>,
+                     Read<'a, Prefabs>,
+                     Read<'a, GameTime>,
+                     Write<'a, GameEvents>,
+                     Write<'a, GameRng>,
+                     Write<'a, Console>);
+
+  fn run(&mut self, (mut zombies, health, players, lazy, prefabs, game_time, mut events, mut rng, mut console): Self::SystemData) {
+    let hooks = self.hooks.fill(events.read(&mut self.reader)
+      .filter(|event| matches!(event, GameEvent::WaveStarted(_) | GameEvent::ZombieKilled(..)))
+      .cloned());
+
+    {
+      let mut context = self.context.lock().expect("Script context lock error");
+      context.zombie_count = zombies.join().filter(|z| !z.is_dead()).count() as i64;
+      context.player_health = (&health, &players).join()
+        .min_by_key(|(_, p)| p.index)
+        .map_or(0.0, |(h, _)| f64::from(h.current));
+      for event in hooks {
+        if let GameEvent::WaveStarted(wave) = *event {
+          context.wave = wave as i64;
+        }
+      }
+    }
+
+    let mut within_budget = true;
+    // Hooks need the system, so the events are copied out of its buffer one by one
+    for idx in 0..self.hooks.len() {
+      if !within_budget {
+        break;
+      }
+      within_budget = match self.hooks[idx] {
+        GameEvent::WaveStarted(wave) => self.call_hook("on_wave", (wave as i64,)),
+        GameEvent::ZombieKilled(position, _) => self.call_hook("on_zombie_killed", (f64::from(position.x()), f64::from(position.y()))),
+        _ => true,
+      };
+    }
+    if within_budget {
+      self.call_hook("on_tick", (game_time.0 as i64,));
+    }
+
+    // Console commands share the gameplay scope, so they see the script's variables
+    for command in std::mem::take(&mut console.commands) {
+      let result = match self.engine.eval_with_scope::<Dynamic>(&mut self.scope, &command) {
+        Ok(value) if value.is::<()>() => continue,
+        Ok(value) => value.to_string(),
+        Err(e) => format!("error {}", e),
+      };
+      console.print(result);
+    }
+
+    let commands = std::mem::take(&mut self.context.lock().expect("Script context lock error").commands);
+    for command in commands {
+      match command {
+        ScriptCommand::SpawnZombie(position, palette) => spawner::spawn_zombie(&lazy, position, palette),
+        ScriptCommand::SpawnPickup(position) => spawner::spawn_pickup(&lazy, position),
+        ScriptCommand::SpawnPrefab(name, position) => spawner::spawn_prefab(&lazy, &prefabs, &name, position),
+        ScriptCommand::DamageZombies(center, radius, amount) => {
+          for z in (&mut zombies).join().filter(|z| !z.is_dead()) {
+            let offset = z.transform.position - center;
+            if distance(offset.x(), offset.y()) >= radius {
+              continue;
+            }
+            z.take_damage(amount, &mut events, &mut rng);
+          }
+        }
+      }
+    }
+  }
+}
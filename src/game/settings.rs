@@ -0,0 +1,408 @@
+use std::fs;
+use std::path::Path;
+
+use json::JsonValue;
+use tracing::warn;
+
+use crate::audio::mixer::Mixer;
+use crate::game::constants::{CONFIG_PATH, FRAME_BUDGET_MS, GOVERNOR_DOWNGRADE_FRAMES, GOVERNOR_HEADROOM, GOVERNOR_RESTORE_FRAMES, LEGACY_CONFIG_PATH,
+                             RESOLUTION_X, RESOLUTION_Y};
+use crate::game::profiles::Profiles;
+
+const AUDIO_SECTION: &str = "audio";
+const VIDEO_SECTION: &str = "video";
+const GAMEPLAY_SECTION: &str = "gameplay";
+const INTERFACE_SECTION: &str = "interface";
+
+// Window sizes offered in the settings menu
+pub const RESOLUTIONS: &[(u32, u32)] = &[(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+// The config file of the active profile. The first start moves a config file from next to the game
+// to the first profile, or imports the JSON config of an earlier version, any other new profile starts
+// with the default settings.
+pub fn config_path() -> String {
+  let profiles = Profiles::load();
+  let path = profiles.file(CONFIG_PATH);
+  if !path.exists() {
+    let filename = path.to_string_lossy();
+    let legacy = profiles.file(LEGACY_CONFIG_PATH);
+    if profiles.active == 0 && Path::new(CONFIG_PATH).exists() {
+      if let Err(e) = fs::copy(CONFIG_PATH, &path) {
+        warn!("Config {} copy error {}", filename, e);
+      }
+    } else if legacy.exists() {
+      import_legacy_config(&legacy.to_string_lossy(), &filename);
+    } else if profiles.active == 0 && Path::new(LEGACY_CONFIG_PATH).exists() {
+      import_legacy_config(LEGACY_CONFIG_PATH, &filename);
+    } else {
+      Settings::new().save(&filename);
+    }
+  }
+  path.to_string_lossy().into_owned()
+}
+
+// Writes a config.json of an earlier version as TOML, the JSON file is left where it was
+pub fn import_legacy_config(legacy: &str, filename: &str) {
+  let config = match fs::read_to_string(legacy).map(|contents| json::parse(&contents)) {
+    Ok(Ok(config)) => config,
+    _ => {
+      warn!("Config {} import error, using defaults", legacy);
+      JsonValue::new_object()
+    }
+  };
+  write_config(filename, &config);
+}
+
+// The config file has one section per subsystem, each section is saved without touching the others.
+// Sections are TOML tables that the rest of the game reads like any other JSON.
+pub fn load_config(filename: &str) -> JsonValue {
+  match fs::read_to_string(filename) {
+    Ok(contents) => match contents.parse::<toml::Table>() {
+      Ok(table) => toml_to_json(toml::Value::Table(table)),
+      Err(e) => {
+        warn!("Config {} parse error {}, using defaults", filename, e);
+        JsonValue::new_object()
+      }
+    },
+    // A missing config file is normal on the first start
+    Err(_) => JsonValue::new_object(),
+  }
+}
+
+fn write_config(filename: &str, config: &JsonValue) {
+  let contents = match json_to_toml(config) {
+    Some(toml::Value::Table(table)) => toml::to_string_pretty(&table).map_err(|e| e.to_string()),
+    _ => Err("not a table".to_string()),
+  };
+  if let Err(e) = contents.and_then(|contents| fs::write(filename, contents).map_err(|e| e.to_string())) {
+    warn!("Config {} save error {}", filename, e);
+  }
+}
+
+fn toml_to_json(value: toml::Value) -> JsonValue {
+  match value {
+    toml::Value::String(s) => s.into(),
+    toml::Value::Integer(i) => i.into(),
+    toml::Value::Float(f) => f.into(),
+    toml::Value::Boolean(b) => b.into(),
+    toml::Value::Datetime(d) => d.to_string().into(),
+    toml::Value::Array(values) => JsonValue::Array(values.into_iter().map(toml_to_json).collect()),
+    toml::Value::Table(table) => {
+      let mut object = JsonValue::new_object();
+      for (key, value) in table {
+        object[key.as_str()] = toml_to_json(value);
+      }
+      object
+    }
+  }
+}
+
+// TOML has no null, unset values are left out and read back as null
+fn json_to_toml(value: &JsonValue) -> Option<toml::Value> {
+  match value {
+    JsonValue::Null => None,
+    JsonValue::Short(_) | JsonValue::String(_) => value.as_str().map(|s| toml::Value::String(s.to_string())),
+    JsonValue::Number(_) => value.as_i64().map(toml::Value::Integer).or_else(|| value.as_f64().map(toml::Value::Float)),
+    JsonValue::Boolean(b) => Some(toml::Value::Boolean(*b)),
+    JsonValue::Array(values) => Some(toml::Value::Array(values.iter().filter_map(json_to_toml).collect())),
+    JsonValue::Object(_) => Some(toml::Value::Table(value.entries()
+      .filter_map(|(key, value)| json_to_toml(value).map(|value| (key.to_string(), value)))
+      .collect())),
+  }
+}
+
+pub fn save_config_section(filename: &str, section: &str, value: JsonValue) {
+  save_config_sections(filename, vec![(section, value)]);
+}
+
+pub fn save_config_sections(filename: &str, sections: Vec<(&str, JsonValue)>) {
+  let mut config = load_config(filename);
+  for (section, value) in sections {
+    config[section] = value;
+  }
+  write_config(filename, &config);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+  Easy,
+  Normal,
+  Hard,
+}
+
+const DIFFICULTIES: &[Difficulty] = &[Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+impl Difficulty {
+  pub fn name(self) -> &'static str {
+    match self {
+      Difficulty::Easy => "easy",
+      Difficulty::Normal => "normal",
+      Difficulty::Hard => "hard",
+    }
+  }
+
+  fn from_name(name: &str) -> Option<Difficulty> {
+    DIFFICULTIES.iter().find(|d| d.name() == name).cloned()
+  }
+
+  // Scales the damage zombies deal to players
+  pub fn damage_scale(self) -> f32 {
+    match self {
+      Difficulty::Easy => 0.5,
+      Difficulty::Normal => 1.0,
+      Difficulty::Hard => 1.5,
+    }
+  }
+
+  // Simulation speed while the inventory is open, easy stops the world
+  pub fn inventory_time_scale(self) -> f64 {
+    match self {
+      Difficulty::Easy => 0.0,
+      Difficulty::Normal => 0.25,
+      Difficulty::Hard => 0.5,
+    }
+  }
+
+  pub fn cycle(self, step: isize) -> Difficulty {
+    let idx = DIFFICULTIES.iter().position(|&d| d == self).unwrap_or(0) as isize;
+    DIFFICULTIES[(idx + step).rem_euclid(DIFFICULTIES.len() as isize) as usize]
+  }
+}
+
+// Languages with a string table in assets/lang, named by their file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+  English,
+  German,
+  French,
+}
+
+const LANGUAGES: &[Language] = &[Language::English, Language::German, Language::French];
+
+impl Language {
+  pub fn code(self) -> &'static str {
+    match self {
+      Language::English => "en",
+      Language::German => "de",
+      Language::French => "fr",
+    }
+  }
+
+  // Shown in the language itself, so it can be found from any other one
+  pub fn native_name(self) -> &'static str {
+    match self {
+      Language::English => "English",
+      Language::German => "Deutsch",
+      Language::French => "Français",
+    }
+  }
+
+  fn from_code(code: &str) -> Option<Language> {
+    LANGUAGES.iter().find(|l| l.code() == code).cloned()
+  }
+
+  pub fn cycle(self, step: isize) -> Language {
+    let idx = LANGUAGES.iter().position(|&l| l == self).unwrap_or(0) as isize;
+    LANGUAGES[(idx + step).rem_euclid(LANGUAGES.len() as isize) as usize]
+  }
+}
+
+// When the frame governor lowers and restores the quality, kept in the governor object of the video section
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GovernorSettings {
+  pub enabled: bool,
+  pub budget_ms: f64,
+  // Share of the budget a frame has to stay below for the quality to come back
+  pub headroom: f64,
+  pub downgrade_frames: u32,
+  pub restore_frames: u32,
+}
+
+impl GovernorSettings {
+  pub fn new() -> GovernorSettings {
+    GovernorSettings {
+      enabled: true,
+      budget_ms: FRAME_BUDGET_MS,
+      headroom: GOVERNOR_HEADROOM,
+      downgrade_frames: GOVERNOR_DOWNGRADE_FRAMES,
+      restore_frames: GOVERNOR_RESTORE_FRAMES,
+    }
+  }
+
+  pub fn from_json(governor: &JsonValue) -> GovernorSettings {
+    let defaults = GovernorSettings::new();
+    GovernorSettings {
+      enabled: governor["enabled"].as_bool().unwrap_or(defaults.enabled),
+      budget_ms: governor["budget_ms"].as_f64().filter(|&ms| ms > 0.0).unwrap_or(defaults.budget_ms),
+      headroom: governor["headroom"].as_f64().map_or(defaults.headroom, |h| h.clamp(0.0, 1.0)),
+      downgrade_frames: governor["downgrade_frames"].as_u32().unwrap_or(defaults.downgrade_frames).max(1),
+      restore_frames: governor["restore_frames"].as_u32().unwrap_or(defaults.restore_frames).max(1),
+    }
+  }
+
+  pub fn to_json(self) -> JsonValue {
+    object! {
+      "enabled" => self.enabled,
+      "budget_ms" => self.budget_ms,
+      "headroom" => self.headroom,
+      "downgrade_frames" => self.downgrade_frames,
+      "restore_frames" => self.restore_frames,
+    }
+  }
+}
+
+impl Default for GovernorSettings {
+  fn default() -> GovernorSettings {
+    GovernorSettings::new()
+  }
+}
+
+// Fullscreen is switched while running, the resolution of windowed mode and vsync are read when the window is created
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VideoSettings {
+  pub fullscreen: bool,
+  pub resolution: (u32, u32),
+  pub vsync: bool,
+  pub governor: GovernorSettings,
+}
+
+impl VideoSettings {
+  pub fn new() -> VideoSettings {
+    VideoSettings {
+      fullscreen: true,
+      resolution: (RESOLUTION_X, RESOLUTION_Y),
+      vsync: true,
+      governor: GovernorSettings::new(),
+    }
+  }
+
+  pub fn from_json(video: &JsonValue) -> VideoSettings {
+    let defaults = VideoSettings::new();
+    let resolution = (video["resolution"][0].as_u32(), video["resolution"][1].as_u32());
+    VideoSettings {
+      fullscreen: video["fullscreen"].as_bool().unwrap_or(defaults.fullscreen),
+      resolution: match resolution {
+        (Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+        _ => defaults.resolution,
+      },
+      vsync: video["vsync"].as_bool().unwrap_or(defaults.vsync),
+      governor: GovernorSettings::from_json(&video["governor"]),
+    }
+  }
+
+  pub fn to_json(self) -> JsonValue {
+    object! {
+      "fullscreen" => self.fullscreen,
+      "resolution" => array![self.resolution.0, self.resolution.1],
+      "vsync" => self.vsync,
+      "governor" => self.governor.to_json(),
+    }
+  }
+
+  pub fn cycle_resolution(&mut self, step: isize) {
+    let idx = RESOLUTIONS.iter().position(|&r| r == self.resolution).map_or(-1, |idx| idx as isize);
+    let next = if idx < 0 && step < 0 { 0 } else { idx + step };
+    self.resolution = RESOLUTIONS[next.rem_euclid(RESOLUTIONS.len() as isize) as usize];
+  }
+}
+
+impl Default for VideoSettings {
+  fn default() -> VideoSettings {
+    VideoSettings::new()
+  }
+}
+
+// User preferences, kept in the audio, video, gameplay and interface sections of the config file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+  pub master_volume: f32,
+  pub music_volume: f32,
+  pub effects_volume: f32,
+  pub video: VideoSettings,
+  pub difficulty: Difficulty,
+  pub language: Language,
+  pub combat_log: bool,
+  // Saves the run when a wave starts
+  pub autosave: bool,
+  // Seeds every game started without --seed, only set by editing the config file
+  pub seed: Option<u64>,
+}
+
+fn volume(value: &JsonValue, default: f32) -> f32 {
+  value.as_f32().map_or(default, |v| v.clamp(0.0, 1.0))
+}
+
+impl Settings {
+  pub fn new() -> Settings {
+    Settings {
+      master_volume: 1.0,
+      music_volume: 0.7,
+      effects_volume: 1.0,
+      video: VideoSettings::new(),
+      difficulty: Difficulty::Normal,
+      language: Language::English,
+      combat_log: false,
+      autosave: true,
+      seed: None,
+    }
+  }
+
+  pub fn load(filename: &str) -> Settings {
+    Settings::from_json(&load_config(filename))
+  }
+
+  pub fn save(&self, filename: &str) {
+    let mut gameplay = object! { "difficulty" => self.difficulty.name(), "autosave" => self.autosave };
+    if let Some(seed) = self.seed {
+      gameplay["seed"] = seed.into();
+    }
+    save_config_sections(filename, vec![
+      (AUDIO_SECTION, self.audio_json()),
+      (VIDEO_SECTION, self.video.to_json()),
+      (GAMEPLAY_SECTION, gameplay),
+      (INTERFACE_SECTION, object! { "language" => self.language.code(), "combat_log" => self.combat_log }),
+    ]);
+  }
+
+  pub fn from_json(config: &JsonValue) -> Settings {
+    let defaults = Settings::new();
+    let audio = &config[AUDIO_SECTION];
+    Settings {
+      master_volume: volume(&audio["master"], defaults.master_volume),
+      music_volume: volume(&audio["music"], defaults.music_volume),
+      effects_volume: volume(&audio["effects"], defaults.effects_volume),
+      video: VideoSettings::from_json(&config[VIDEO_SECTION]),
+      difficulty: config[GAMEPLAY_SECTION]["difficulty"].as_str()
+        .and_then(Difficulty::from_name)
+        .unwrap_or(defaults.difficulty),
+      language: config[INTERFACE_SECTION]["language"].as_str()
+        .and_then(Language::from_code)
+        .unwrap_or(defaults.language),
+      combat_log: config[INTERFACE_SECTION]["combat_log"].as_bool().unwrap_or(defaults.combat_log),
+      autosave: config[GAMEPLAY_SECTION]["autosave"].as_bool().unwrap_or(defaults.autosave),
+      seed: config[GAMEPLAY_SECTION]["seed"].as_u64(),
+    }
+  }
+
+  fn audio_json(self) -> JsonValue {
+    object! {
+      "master" => self.master_volume,
+      "music" => self.music_volume,
+      "effects" => self.effects_volume,
+    }
+  }
+
+  // Volumes are set on the mixer buses, single sounds never look at the settings
+  pub fn apply(&self, mixer: &mut Mixer) {
+    mixer.master_volume = self.master_volume;
+    mixer.music_volume = self.music_volume;
+    mixer.effects_volume = self.effects_volume;
+    mixer.interface_volume = self.effects_volume;
+  }
+}
+
+impl Default for Settings {
+  fn default() -> Settings {
+    Settings::new()
+  }
+}
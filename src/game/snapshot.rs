@@ -0,0 +1,215 @@
+use json::JsonValue;
+
+use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+use crate::critter::palette::CritterPalette;
+use crate::ecs::{Builder, Join, World, WorldExt};
+use crate::game::health::Health;
+use crate::graphics::orientation::Stance;
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::ZombieDrawable;
+
+// Gameplay state to and from JSON, the base for save games, replays and networking
+pub trait Snapshot: Sized {
+  fn to_json(&self) -> JsonValue;
+  fn from_json(value: &JsonValue) -> Result<Self, String>;
+}
+
+pub fn field_f32(value: &JsonValue, name: &str) -> Result<f32, String> {
+  value[name].as_f32().ok_or_else(|| format!("Snapshot field \"{}\" is not a number", name))
+}
+
+pub fn field_usize(value: &JsonValue, name: &str) -> Result<usize, String> {
+  value[name].as_usize().ok_or_else(|| format!("Snapshot field \"{}\" is not an unsigned integer", name))
+}
+
+fn field_list<T: Snapshot>(value: &JsonValue, name: &str) -> Result<Vec<T>, String> {
+  if !value[name].is_array() {
+    return Err(format!("Snapshot field \"{}\" is not a list", name));
+  }
+  value[name].members().map(T::from_json).collect()
+}
+
+fn list_to_json<T: Snapshot>(items: &[T]) -> JsonValue {
+  JsonValue::Array(items.iter().map(Snapshot::to_json).collect())
+}
+
+impl Snapshot for Position {
+  fn to_json(&self) -> JsonValue {
+    object! { "x" => self.x(), "y" => self.y() }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Position, String> {
+    Ok(Position::new(field_f32(value, "x")?, field_f32(value, "y")?))
+  }
+}
+
+impl Snapshot for Stance {
+  fn to_json(&self) -> JsonValue {
+    let name = match self {
+      Stance::Walking => "Walking",
+      Stance::Running => "Running",
+      Stance::Firing => "Firing",
+      Stance::Still => "Still",
+      Stance::NormalDeath => "NormalDeath",
+      Stance::CriticalDeath => "CriticalDeath",
+    };
+    name.into()
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Stance, String> {
+    match value.as_str() {
+      Some("Walking") => Ok(Stance::Walking),
+      Some("Running") => Ok(Stance::Running),
+      Some("Firing") => Ok(Stance::Firing),
+      Some("Still") => Ok(Stance::Still),
+      Some("NormalDeath") => Ok(Stance::NormalDeath),
+      Some("CriticalDeath") => Ok(Stance::CriticalDeath),
+      _ => Err(format!("Unknown stance {}", value)),
+    }
+  }
+}
+
+impl Snapshot for CritterPalette {
+  fn to_json(&self) -> JsonValue {
+    (self.index() as usize).into()
+  }
+
+  fn from_json(value: &JsonValue) -> Result<CritterPalette, String> {
+    match value.as_usize() {
+      Some(0) => Ok(CritterPalette::Original),
+      Some(1) => Ok(CritterPalette::Toxic),
+      Some(2) => Ok(CritterPalette::Frozen),
+      _ => Err(format!("Unknown palette {}", value)),
+    }
+  }
+}
+
+pub struct PlayerSnapshot {
+  pub index: usize,
+  pub movement: Position,
+  pub stance: Stance,
+  pub health: f32,
+  pub ammunition: usize,
+  pub magazines: usize,
+}
+
+impl Snapshot for PlayerSnapshot {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "index" => self.index,
+      "movement" => self.movement.to_json(),
+      "stance" => self.stance.to_json(),
+      "health" => self.health,
+      "ammunition" => self.ammunition,
+      "magazines" => self.magazines
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<PlayerSnapshot, String> {
+    Ok(PlayerSnapshot {
+      index: field_usize(value, "index")?,
+      movement: Position::from_json(&value["movement"])?,
+      stance: Stance::from_json(&value["stance"])?,
+      health: field_f32(value, "health")?,
+      ammunition: field_usize(value, "ammunition")?,
+      magazines: field_usize(value, "magazines")?,
+    })
+  }
+}
+
+// Bullets are short lived and left out, remaining ammo pickups cover the terrain changes
+pub struct WorldSnapshot {
+  pub players: Vec<PlayerSnapshot>,
+  pub zombies: Vec<ZombieDrawable>,
+  pub pickups: Vec<Position>,
+}
+
+impl WorldSnapshot {
+  pub fn capture(world: &World) -> WorldSnapshot {
+    let players = (&world.read_storage::<Player>(), &world.read_storage::<CharacterDrawable>(), &world.read_storage::<CharacterInputState>(), &world.read_storage::<Health>())
+      .join()
+      .map(|(p, cd, ci, h)| PlayerSnapshot {
+        index: p.index,
+        movement: ci.movement,
+        stance: cd.stance.clone(),
+        health: h.current,
+        ammunition: cd.stats.ammunition,
+        magazines: cd.stats.magazines,
+      })
+      .collect();
+
+    let zombies = world.read_storage::<ZombieDrawable>()
+      .join()
+      .cloned()
+      .collect();
+
+    let pickups = world.read_storage::<TerrainObjectDrawable>()
+      .join()
+      .filter(|o| o.object_type == TerrainTexture::Ammo)
+      .map(|o| o.transform.position)
+      .collect();
+
+    WorldSnapshot { players, zombies, pickups }
+  }
+
+  pub fn restore(&self, world: &mut World) {
+    for (p, cd, ci, h) in (&world.read_storage::<Player>(), &mut world.write_storage::<CharacterDrawable>(),
+                           &mut world.write_storage::<CharacterInputState>(), &mut world.write_storage::<Health>()).join() {
+      if let Some(player) = self.players.iter().find(|player| player.index == p.index) {
+        // A restored position is a jump, there is no step to interpolate
+        ci.movement = player.movement;
+        ci.previous_movement = player.movement;
+        h.current = player.health;
+        cd.stance = player.stance.clone();
+        cd.stats.ammunition = player.ammunition;
+        cd.stats.magazines = player.magazines;
+      }
+    }
+
+    let zombies = (&world.entities(), &world.read_storage::<ZombieDrawable>()).join()
+      .map(|(e, _)| e)
+      .collect::<Vec<_>>();
+    let pickups = (&world.entities(), &world.read_storage::<TerrainObjectDrawable>()).join()
+      .filter(|(_, o)| o.object_type == TerrainTexture::Ammo)
+      .map(|(e, _)| e)
+      .collect::<Vec<_>>();
+    world.delete_entities(&zombies).expect("Zombie despawn error");
+    world.delete_entities(&pickups).expect("Pickup despawn error");
+    for (idx, z) in self.zombies.iter().enumerate() {
+      let mut zombie = z.clone();
+      zombie.spawn_order = idx;
+      world.create_entity().with(zombie).build();
+    }
+    for p in &self.pickups {
+      world.create_entity().with(TerrainObjectDrawable::new(*p, TerrainTexture::Ammo)).build();
+    }
+  }
+
+  pub fn stringify(&self) -> String {
+    json::stringify(self.to_json())
+  }
+
+  pub fn parse(source: &str) -> Result<WorldSnapshot, String> {
+    let value = json::parse(source).map_err(|e| format!("Snapshot parse error {}", e))?;
+    WorldSnapshot::from_json(&value)
+  }
+}
+
+impl Snapshot for WorldSnapshot {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "players" => list_to_json(&self.players),
+      "zombies" => list_to_json(&self.zombies),
+      "pickups" => list_to_json(&self.pickups)
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<WorldSnapshot, String> {
+    Ok(WorldSnapshot {
+      players: field_list(value, "players")?,
+      zombies: field_list(value, "zombies")?,
+      pickups: field_list(value, "pickups")?,
+    })
+  }
+}
@@ -0,0 +1,183 @@
+use tracing::{debug, warn};
+
+use crate::bullet::BulletDrawable;
+use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+use crate::critter::CharacterSprite;
+use crate::critter::palette::CritterPalette;
+use crate::ecs::{self, Builder, Component, Entity, Join, LazyUpdate, Read, ReadStorage, World, WorldExt, Write};
+use crate::game::constants::{AMMO_POSITIONS, BOSS_WAVE_INTERVAL, PLAYER_MAX_HEALTH, PLAYER_MAX_STAMINA, ZOMBIE_WAVE_DELAY, ZOMBIE_WAVE_GROWTH, ZOMBIE_WAVE_RADIUS};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::health::Health;
+use crate::game::prefab::{Instance, Prefabs};
+use crate::game::status::{Stamina, StatusEffects};
+use crate::graphics::{camera::CameraInputState, GameTime, set_position};
+use crate::graphics::transform::{Parent, Transform};
+use crate::net::NetMode;
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::{ZombieDrawable, zombies::wave_palette};
+
+// Works with both World::create_entity and LazyUpdate::create_entity, so players can join mid-game
+pub fn spawn_player<B: Builder>(builder: B, index: usize) -> Entity {
+  builder
+    .with(CharacterDrawable::new())
+    .with(CharacterSprite::new())
+    .with(CameraInputState::new())
+    .with(CharacterInputState::new())
+    .with(Player::new(index))
+    .with(Health::new(PLAYER_MAX_HEALTH))
+    .with(Stamina::new(PLAYER_MAX_STAMINA))
+    .with(StatusEffects::new())
+    .with(Transform::new(Position::origin()))
+    .build()
+}
+
+// Weapon sprites, laser sights and health bars hang off their owner and despawn with it
+pub fn spawn_attachment<B: Builder>(builder: B, parent: Entity, offset: Position) -> Entity {
+  builder
+    .with(Transform::new(Position::origin()))
+    .with(Parent::new(parent, offset))
+    .build()
+}
+
+// Systems spawn without the entities, the entity is created once the world is maintained
+fn spawn<C: Component + Send + Sync>(lazy: &LazyUpdate, component: C) {
+  lazy.exec_mut(move |world: &mut World| {
+    world.create_entity().with(component).build();
+  });
+}
+
+// Numbered after the zombies already in the world, which are the same on every lockstep peer
+fn spawn_zombie_drawable(lazy: &LazyUpdate, mut zombie: ZombieDrawable) {
+  lazy.exec_mut(move |world: &mut World| {
+    zombie.spawn_order = world.read_storage::<ZombieDrawable>().join().map(|z| z.spawn_order + 1).max().unwrap_or(0);
+    world.create_entity().with(zombie).build();
+  });
+}
+
+pub fn spawn_zombie(lazy: &LazyUpdate, position: Position, palette: CritterPalette) {
+  debug!(%position, "zombie spawned");
+  let mut zombie = ZombieDrawable::new(position);
+  zombie.palette = palette;
+  spawn_zombie_drawable(lazy, zombie);
+}
+
+pub fn spawn_bullet(lazy: &LazyUpdate, position: Position, direction: f32) {
+  spawn(lazy, BulletDrawable::new(position, direction));
+}
+
+pub fn spawn_pickup(lazy: &LazyUpdate, position: Position) {
+  spawn(lazy, TerrainObjectDrawable::new(position, TerrainTexture::Ammo));
+}
+
+pub fn spawn_prefab(lazy: &LazyUpdate, prefabs: &Prefabs, name: &str, position: Position) {
+  match prefabs.get(name).map(|prefab| prefab.instantiate(position)) {
+    Some(Instance::Zombie(zombie)) => spawn_zombie_drawable(lazy, zombie),
+    Some(Instance::TerrainObject(object)) => spawn(lazy, object),
+    None => {
+      warn!("Unknown prefab {}", name);
+      return;
+    }
+  }
+  debug!(prefab = name, %position, "prefab spawned");
+}
+
+pub fn despawn_dead_zombies(lazy: &LazyUpdate) {
+  lazy.exec_mut(|world: &mut World| {
+    let dead = (&world.entities(), &world.read_storage::<ZombieDrawable>()).join()
+      .filter(|(_, z)| z.is_dead())
+      .map(|(e, _)| e)
+      .collect::<Vec<_>>();
+    world.delete_entities(&dead).expect("Zombie despawn error");
+  });
+}
+
+fn wave_prefab(idx: usize) -> &'static str {
+  match wave_palette(idx) {
+    CritterPalette::Original => "zombie",
+    CritterPalette::Toxic => "toxic_zombie",
+    CritterPalette::Frozen => "frozen_zombie",
+  }
+}
+
+// Kept as a resource, so a restarted run starts again from the first wave
+pub struct WaveState {
+  pub wave: usize,
+  cleared_at: Option<u64>,
+}
+
+impl WaveState {
+  pub fn new() -> WaveState {
+    WaveState {
+      wave: 1,
+      cleared_at: None,
+    }
+  }
+
+  // A loaded game goes on with the wave it was saved in
+  pub fn resume(wave: usize) -> WaveState {
+    WaveState {
+      wave,
+      cleared_at: None,
+    }
+  }
+}
+
+impl Default for WaveState {
+  fn default() -> WaveState {
+    WaveState::new()
+  }
+}
+
+pub fn is_boss_wave(wave: usize) -> bool {
+  wave.is_multiple_of(BOSS_WAVE_INTERVAL)
+}
+
+fn spawn_wave(wave: usize, lazy: &LazyUpdate, prefabs: &Prefabs) {
+  let count = ZOMBIE_WAVE_GROWTH * (wave + 1);
+  for idx in 0..count {
+    let angle = idx as f32 / count as f32 * 2.0 * std::f32::consts::PI;
+    let position = Position::new(ZOMBIE_WAVE_RADIUS * angle.cos(), ZOMBIE_WAVE_RADIUS * angle.sin());
+    spawn_prefab(lazy, prefabs, wave_prefab(idx), position);
+  }
+  if is_boss_wave(wave) {
+    spawn_prefab(lazy, prefabs, "boss_zombie", Position::new(0.0, ZOMBIE_WAVE_RADIUS));
+  }
+  let ammo = AMMO_POSITIONS[wave % AMMO_POSITIONS.len()];
+  spawn_prefab(lazy, prefabs, "ammo", set_position(ammo[0], ammo[1]));
+}
+
+pub struct SpawnSystem;
+
+impl<'a> ecs::System<'a> for SpawnSystem {
+  type SystemData = (ReadStorage<'a, ZombieDrawable>,
+                     Read<'a, LazyUpdate>,
+                     Read<'a, Prefabs>,
+                     Read<'a, GameTime>,
+                     Write<'a, WaveState>,
+                     Write<'a, GameEvents>,
+                     Read<'a, NetMode>);
+
+  fn run(&mut self, (zombies, lazy, prefabs, game_time, mut state, mut events, net_mode): Self::SystemData) {
+    // A client's zombies come from the host
+    if net_mode.is_client() {
+      return;
+    }
+    let is_cleared = zombies.join().all(ZombieDrawable::is_dead);
+    if !is_cleared {
+      return;
+    }
+
+    match state.cleared_at {
+      None => state.cleared_at = Some(game_time.0),
+      Some(cleared_at) if game_time.0 - cleared_at >= ZOMBIE_WAVE_DELAY => {
+        state.cleared_at = None;
+        state.wave += 1;
+        despawn_dead_zombies(&lazy);
+        spawn_wave(state.wave, &lazy, &prefabs);
+        events.single_write(GameEvent::WaveStarted(state.wave));
+      }
+      _ => (),
+    }
+  }
+}
@@ -0,0 +1,923 @@
+use cgmath::Point2;
+use crossbeam_channel as channel;
+use tracing::info;
+
+use crate::audio::mixer::{Mixer, Sound};
+use crate::character::Player;
+use crate::ecs::{self, Read, ReadStorage, ReaderId, World, WorldExt, Write};
+use crate::game::constants::{AUTOSAVES, GAME_TITLE, MAX_PLAYERS, MENU_ITEM_HEIGHT, MENU_WIDTH, PRESET_ERROR_LINES, SAVE_SLOTS, VOLUME_STEP};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::health::Health;
+use crate::game::inventory::{InventoryScreen, Loadout};
+use crate::game::leaderboard::{Leaderboard, LeaderboardTable};
+use crate::game::localization::Localization;
+use crate::game::profiles::Profiles;
+use crate::game::records::{Records, records_path, RunRecord};
+use crate::game::save::{SaveFile, SaveGame};
+use crate::game::score::{format_time, Score};
+use crate::game::settings::{config_path, Difficulty, Settings};
+use crate::game::stats::{Stats, stats_path};
+use crate::gfx_app::input::{Action, action_name, Preset, PresetRequest, PresetResult, presets, Rebinding};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::TextAlign;
+use crate::hud::widgets::{Anchor, Panel, Stacking, Widget};
+use crate::net::lobby::Lobby;
+use crate::net::NetMode;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+  Menu,
+  Playing,
+  Paused,
+  Settings,
+  Controls,
+  Records,
+  // The online top list, opened from the records
+  Leaderboard,
+  Stats,
+  Profiles,
+  SaveSlots,
+  LoadSlots,
+  Inventory,
+  GameOver,
+  // Waiting for the players of a LAN session, and the sessions found on the LAN
+  Lobby,
+  Browser,
+}
+
+impl GameState {
+  // Gameplay systems only run while playing, other states keep drawing the last simulated frame
+  pub fn is_simulating(self) -> bool {
+    self == GameState::Playing
+  }
+
+  // How fast the simulation advances, the inventory slows it down unless the difficulty stops it
+  pub fn time_scale(self, difficulty: Difficulty) -> f64 {
+    match self {
+      GameState::Playing => 1.0,
+      GameState::Inventory => difficulty.inventory_time_scale(),
+      _ => 0.0,
+    }
+  }
+
+  fn has_menu(self) -> bool {
+    !self.is_simulating() && self != GameState::Inventory
+  }
+
+  fn menu_items(self) -> &'static [MenuItem] {
+    match self {
+      GameState::Menu => &[MenuItem::Play, MenuItem::HostGame, MenuItem::JoinGame, MenuItem::LoadGame, MenuItem::Records, MenuItem::Stats, MenuItem::Profiles,
+        MenuItem::Settings, MenuItem::Quit],
+      GameState::Paused => &[MenuItem::Resume, MenuItem::SaveGame, MenuItem::LoadGame, MenuItem::Settings, MenuItem::QuitToMenu,
+        MenuItem::Quit],
+      GameState::Settings => &[MenuItem::MasterVolume, MenuItem::MusicVolume, MenuItem::EffectsVolume, MenuItem::Fullscreen,
+        MenuItem::Resolution, MenuItem::Vsync, MenuItem::Difficulty, MenuItem::AutoSave, MenuItem::Language,
+        MenuItem::CombatLog, MenuItem::Controls, MenuItem::Back],
+      GameState::Controls => &[MenuItem::Binding(Action::MoveUp), MenuItem::Binding(Action::MoveDown),
+        MenuItem::Binding(Action::MoveLeft), MenuItem::Binding(Action::MoveRight), MenuItem::Binding(Action::Aim),
+        MenuItem::Binding(Action::Reload), MenuItem::Binding(Action::Inventory), MenuItem::Binding(Action::ZoomIn), MenuItem::Binding(Action::ZoomOut),
+        MenuItem::Binding(Action::Chat), MenuItem::Preset,
+        MenuItem::ExportBindings, MenuItem::Back],
+      GameState::Records => &[MenuItem::Leaderboard, MenuItem::Back],
+      GameState::Leaderboard => &[MenuItem::LeaderboardPage, MenuItem::Back],
+      GameState::Stats => &[MenuItem::Back],
+      // One item for each of the MAX_PROFILES profiles and the SAVE_SLOTS slots
+      GameState::Profiles => &[MenuItem::Profile(0), MenuItem::Profile(1), MenuItem::Profile(2), MenuItem::Profile(3), MenuItem::Back],
+      GameState::SaveSlots => &[MenuItem::SaveSlot(0), MenuItem::SaveSlot(1), MenuItem::SaveSlot(2), MenuItem::Back],
+      // And the AUTOSAVES auto-saves
+      GameState::LoadSlots => &[MenuItem::SaveSlot(0), MenuItem::SaveSlot(1), MenuItem::SaveSlot(2), MenuItem::AutoSaveSlot(0),
+        MenuItem::AutoSaveSlot(1), MenuItem::AutoSaveSlot(2), MenuItem::Back],
+      GameState::GameOver => &[MenuItem::Restart, MenuItem::Quit],
+      GameState::Lobby => &[MenuItem::Skin, MenuItem::Ready, MenuItem::StartMatch, MenuItem::LeaveLobby],
+      // One item for each of the NET_SESSIONS sessions
+      GameState::Browser => &[MenuItem::Session(0), MenuItem::Session(1), MenuItem::Session(2), MenuItem::Session(3), MenuItem::Back],
+      GameState::Playing | GameState::Inventory => &[],
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StateTransition {
+  Push(GameState),
+  Pop,
+  Switch(GameState),
+  // Drops the whole stack
+  Reset(GameState),
+}
+
+pub struct GameStates {
+  stack: Vec<GameState>,
+  pending: Vec<StateTransition>,
+  pub is_quitting: bool,
+  // The world is rebuilt for a new run after the transitions of the frame
+  pub is_restarting: bool,
+  // The run is written to or replaced from a save slot after the transitions of the frame
+  pub save_slot: Option<usize>,
+  pub load_slot: Option<SaveFile>,
+  // The profile to switch to, its settings are loaded after the transitions of the frame
+  pub profile: Option<usize>,
+  // The LAN session to switch to after the transitions of the frame
+  pub net: Option<NetMode>,
+}
+
+impl GameStates {
+  pub fn new(initial: GameState) -> GameStates {
+    GameStates {
+      stack: vec![initial],
+      pending: Vec::new(),
+      is_quitting: false,
+      is_restarting: false,
+      save_slot: None,
+      load_slot: None,
+      profile: None,
+      net: None,
+    }
+  }
+
+  pub fn current(&self) -> GameState {
+    *self.stack.last().expect("Game state stack is empty")
+  }
+
+  pub fn contains(&self, state: GameState) -> bool {
+    self.stack.contains(&state)
+  }
+
+  // Transitions are applied between frames, so every system in a frame sees the same state
+  pub fn request(&mut self, transition: StateTransition) {
+    self.pending.push(transition);
+  }
+
+  // Returns the exited and entered state when the active state changes
+  fn transition(&mut self, transition: StateTransition) -> Option<(GameState, GameState)> {
+    let previous = self.current();
+    match transition {
+      StateTransition::Push(state) => self.stack.push(state),
+      StateTransition::Pop if self.stack.len() > 1 => {
+        self.stack.pop();
+      }
+      StateTransition::Pop => return None,
+      StateTransition::Switch(state) => {
+        self.stack.pop();
+        self.stack.push(state);
+      }
+      StateTransition::Reset(state) => self.stack = vec![state],
+    }
+    if previous == self.current() {
+      None
+    } else {
+      Some((previous, self.current()))
+    }
+  }
+}
+
+impl Default for GameStates {
+  fn default() -> GameStates {
+    GameStates::new(GameState::Playing)
+  }
+}
+
+pub fn on_enter(state: GameState, world: &mut World) {
+  if state == GameState::SaveSlots || state == GameState::LoadSlots {
+    let mut menu = world.write_resource::<Menu>();
+    menu.slots = (0..SAVE_SLOTS).map(|idx| SaveGame::peek(SaveFile::Slot(idx))).collect();
+    menu.autosaves = (0..AUTOSAVES).map(|idx| SaveGame::peek(SaveFile::Auto(idx))).collect();
+  }
+  if state == GameState::Controls {
+    let mut menu = world.write_resource::<Menu>();
+    menu.presets = presets();
+    menu.preset = 0;
+  }
+  if state.has_menu() {
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    world.write_resource::<Menu>().open(state, &settings, &strings);
+  }
+  if state == GameState::Lobby {
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    let lobby = world.read_resource::<Lobby>();
+    world.write_resource::<Menu>().show_details(lobby.lines(&strings), &settings, &strings);
+  }
+  if state == GameState::Inventory {
+    let strings = world.read_resource::<Localization>();
+    world.write_resource::<InventoryScreen>().open(&world.read_resource::<Loadout>(), &strings);
+  }
+  if state == GameState::Records {
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    let lines = world.read_resource::<Records>().lines(&strings);
+    world.write_resource::<Menu>().show_details(lines, &settings, &strings);
+  }
+  if state == GameState::Leaderboard {
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    let mut leaderboard = world.write_resource::<Leaderboard>();
+    leaderboard.fetch();
+    world.write_resource::<Menu>().show_leaderboard(&leaderboard.table, &settings, &strings);
+  }
+  if state == GameState::Stats {
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    let lines = world.read_resource::<Stats>().lines(&strings);
+    world.write_resource::<Menu>().show_details(lines, &settings, &strings);
+  }
+  // The run's stats are kept whenever it stops, so quitting from the pause menu loses nothing
+  if state == GameState::Paused || state == GameState::GameOver {
+    world.read_resource::<Stats>().save(&stats_path());
+  }
+  if state == GameState::GameOver {
+    info!("Player died");
+    let settings = *world.read_resource::<Settings>();
+    let strings = world.read_resource::<Localization>();
+    let score = world.read_resource::<Score>();
+    let mut records = world.write_resource::<Records>();
+    let run = RunRecord::new(&score);
+    let broken = records.add(run);
+    records.save(&records_path());
+    world.read_resource::<Leaderboard>().submit(&run);
+    let summary = score.summary(&strings).into_iter()
+      .chain(broken.into_iter().map(|key| strings.get(key).to_string()))
+      .collect();
+    world.write_resource::<Menu>().show_details(summary, &settings, &strings);
+  }
+}
+
+fn on_exit(state: GameState, world: &mut World) {
+  if state.has_menu() {
+    world.write_resource::<Menu>().close();
+  }
+}
+
+pub fn apply_transitions(world: &mut World) {
+  let pending = std::mem::take(&mut world.write_resource::<GameStates>().pending);
+  for transition in pending {
+    let changed = world.write_resource::<GameStates>().transition(transition);
+    if let Some((exited, entered)) = changed {
+      on_exit(exited, world);
+      on_enter(entered, world);
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MenuItem {
+  Play,
+  HostGame,
+  JoinGame,
+  Session(usize),
+  Skin,
+  Ready,
+  StartMatch,
+  LeaveLobby,
+  Restart,
+  Resume,
+  SaveGame,
+  LoadGame,
+  SaveSlot(usize),
+  AutoSaveSlot(usize),
+  Records,
+  Leaderboard,
+  LeaderboardPage,
+  Stats,
+  Profiles,
+  Profile(usize),
+  Settings,
+  MasterVolume,
+  MusicVolume,
+  EffectsVolume,
+  Fullscreen,
+  Resolution,
+  Vsync,
+  Difficulty,
+  AutoSave,
+  Language,
+  CombatLog,
+  Controls,
+  Binding(Action),
+  Preset,
+  ExportBindings,
+  Back,
+  QuitToMenu,
+  Quit,
+}
+
+fn volume_label(key: &str, volume: f32, strings: &Localization) -> String {
+  strings.format(key, &[("value", (volume * 100.0).round().to_string())])
+}
+
+fn on_off(value: bool, strings: &Localization) -> String {
+  strings.get(if value { "menu.on" } else { "menu.off" }).to_string()
+}
+
+impl MenuItem {
+  fn label(self, settings: &Settings, strings: &Localization) -> String {
+    match self {
+      MenuItem::Play => strings.get("menu.new_game").to_string(),
+      MenuItem::HostGame => strings.get("menu.host_game").to_string(),
+      MenuItem::JoinGame => strings.get("menu.join_game").to_string(),
+      MenuItem::Session(idx) => strings.format("menu.session", &[("session", (idx + 1).to_string())]),
+      MenuItem::Skin => strings.get("menu.skin").to_string(),
+      MenuItem::Ready => strings.get("menu.ready").to_string(),
+      MenuItem::StartMatch => strings.get("menu.start_match").to_string(),
+      MenuItem::LeaveLobby => strings.get("menu.leave_lobby").to_string(),
+      MenuItem::Restart => strings.get("menu.restart").to_string(),
+      MenuItem::Resume => strings.get("menu.resume").to_string(),
+      MenuItem::SaveGame => strings.get("menu.save_game").to_string(),
+      MenuItem::LoadGame => strings.get("menu.load_game").to_string(),
+      MenuItem::SaveSlot(idx) => strings.format("menu.slot", &[("slot", (idx + 1).to_string())]),
+      MenuItem::AutoSaveSlot(idx) => strings.format("menu.autosave_slot", &[("slot", (idx + 1).to_string())]),
+      MenuItem::Records => strings.get("menu.records").to_string(),
+      MenuItem::Leaderboard => strings.get("menu.leaderboard").to_string(),
+      MenuItem::LeaderboardPage => strings.get("menu.leaderboard_page").to_string(),
+      MenuItem::Stats => strings.get("menu.stats").to_string(),
+      MenuItem::Profiles => strings.get("menu.profiles").to_string(),
+      MenuItem::Profile(idx) => strings.format("menu.profile", &[("profile", (idx + 1).to_string())]),
+      MenuItem::Settings => strings.get("menu.settings").to_string(),
+      MenuItem::MasterVolume => volume_label("menu.master_volume", settings.master_volume, strings),
+      MenuItem::MusicVolume => volume_label("menu.music_volume", settings.music_volume, strings),
+      MenuItem::EffectsVolume => volume_label("menu.effects_volume", settings.effects_volume, strings),
+      MenuItem::Fullscreen => strings.format("menu.fullscreen", &[("state", on_off(settings.video.fullscreen, strings))]),
+      MenuItem::Resolution => strings.format("menu.resolution", &[("width", settings.video.resolution.0.to_string()),
+        ("height", settings.video.resolution.1.to_string())]),
+      MenuItem::Vsync => strings.format("menu.vsync", &[("state", on_off(settings.video.vsync, strings))]),
+      MenuItem::Difficulty => strings.format("menu.difficulty",
+        &[("difficulty", strings.get(&format!("difficulty.{}", settings.difficulty.name())).to_string())]),
+      // Each language is named in itself, so it can be found again from any of the others
+      MenuItem::Language => strings.format("menu.language", &[("language", settings.language.native_name().to_string())]),
+      MenuItem::AutoSave => strings.format("menu.autosave", &[("state", on_off(settings.autosave, strings))]),
+      MenuItem::CombatLog => strings.format("menu.combat_log", &[("state", on_off(settings.combat_log, strings))]),
+      MenuItem::Controls => strings.get("menu.controls").to_string(),
+      MenuItem::Binding(action) => strings.get(&format!("action.{}", action_name(action))).to_string(),
+      MenuItem::Preset => strings.get("menu.preset").to_string(),
+      MenuItem::ExportBindings => strings.get("menu.export_bindings").to_string(),
+      MenuItem::Back => strings.get("menu.back").to_string(),
+      MenuItem::QuitToMenu => strings.get("menu.quit_to_menu").to_string(),
+      MenuItem::Quit => strings.get("menu.quit").to_string(),
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuControl {
+  Up,
+  Down,
+  Left,
+  Right,
+  Select,
+  Back,
+  // Cursor positions in logical window pixels
+  Point(Point2<f32>),
+  Press(Point2<f32>),
+  Release,
+  Inventory,
+  QuickSave,
+  QuickLoad,
+}
+
+// What letting go of the mouse button finished
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MenuRelease {
+  Nothing,
+  Button,
+  Slider,
+}
+
+// The menu is a centered panel, its first widget is the title and the details come after the items
+pub struct Menu {
+  pub is_visible: bool,
+  // A string key, the main menu shows the game title instead
+  title: Option<&'static str>,
+  items: &'static [MenuItem],
+  bindings: Rebinding,
+  // The key binding presets found when the controls page opened and the one to import
+  presets: Vec<Preset>,
+  preset: usize,
+  // Shown next to the profile and save slot items, copied when their page opens
+  profiles: Profiles,
+  slots: Vec<Option<(usize, f32)>>,
+  autosaves: Vec<Option<(usize, f32)>>,
+  // Copied every frame the session changes it
+  lobby: Lobby,
+  // Copied whenever an answer of the leaderboard arrives, shown a page at a time
+  leaderboard: LeaderboardTable,
+  leaderboard_page: usize,
+  details: Vec<String>,
+  pub panel: Panel,
+}
+
+impl Menu {
+  pub fn new() -> Menu {
+    Menu {
+      is_visible: false,
+      title: None,
+      items: &[],
+      bindings: Rebinding::default(),
+      presets: Vec::new(),
+      preset: 0,
+      profiles: Profiles::new(),
+      slots: Vec::new(),
+      autosaves: Vec::new(),
+      lobby: Lobby::default(),
+      leaderboard: LeaderboardTable::default(),
+      leaderboard_page: 0,
+      details: Vec::new(),
+      panel: Panel {
+        align: TextAlign::Center,
+        ..Panel::new(Anchor::Center, Stacking::Vertical, MENU_WIDTH, MENU_ITEM_HEIGHT)
+      },
+    }
+  }
+
+  fn open(&mut self, state: GameState, settings: &Settings, strings: &Localization) {
+    self.is_visible = true;
+    self.title = match state {
+      GameState::Paused => Some("menu.title.paused"),
+      GameState::Settings => Some("menu.title.settings"),
+      GameState::Controls => Some("menu.title.controls"),
+      GameState::Records => Some("menu.title.records"),
+      GameState::Leaderboard => Some("menu.title.leaderboard"),
+      GameState::Stats => Some("menu.title.stats"),
+      GameState::Profiles => Some("menu.title.profiles"),
+      GameState::SaveSlots => Some("menu.title.save"),
+      GameState::LoadSlots => Some("menu.title.load"),
+      GameState::GameOver => Some("menu.title.game_over"),
+      GameState::Lobby => Some("menu.title.lobby"),
+      GameState::Browser => Some("menu.title.browser"),
+      _ => None,
+    };
+    self.items = state.menu_items();
+    self.details.clear();
+    self.refresh_widgets(settings, strings);
+    self.panel.hovered = None;
+    self.panel.pressed = None;
+    self.panel.focus_first();
+  }
+
+  fn show_details(&mut self, details: Vec<String>, settings: &Settings, strings: &Localization) {
+    self.details = details;
+    self.refresh_widgets(settings, strings);
+  }
+
+  fn close(&mut self) {
+    self.is_visible = false;
+    self.items = &[];
+  }
+
+  fn selected(&self) -> Option<MenuItem> {
+    self.panel.focused
+      .and_then(|idx| idx.checked_sub(1))
+      .and_then(|idx| self.items.get(idx))
+      .copied()
+  }
+
+  // Hovering an item focuses it, returns true when the focus moved
+  fn point(&mut self, cursor: Point2<f32>, dimensions: &Dimensions) -> bool {
+    let scale = dimensions.hidpi_factor;
+    self.panel.hovered = self.panel.widget_at(cursor.x * scale, cursor.y * scale, dimensions);
+    match self.panel.hovered {
+      Some(idx) if self.panel.focused != Some(idx) => {
+        self.panel.focused = Some(idx);
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // Returns true when the cursor is on an item
+  fn press(&mut self, cursor: Point2<f32>, dimensions: &Dimensions) -> bool {
+    self.point(cursor, dimensions);
+    self.panel.pressed = self.panel.hovered;
+    self.panel.pressed.is_some()
+  }
+
+  // A button only counts when the cursor is still on it, a slider lets go anywhere
+  fn release(&mut self) -> MenuRelease {
+    match self.panel.pressed.take() {
+      Some(idx) if matches!(self.panel.widgets.get(idx), Some(Widget::Slider(..))) => MenuRelease::Slider,
+      Some(idx) if self.panel.hovered == Some(idx) => MenuRelease::Button,
+      _ => MenuRelease::Nothing,
+    }
+  }
+
+  // Sets the pressed slider from the cursor, volumes snap to the steps of the arrow keys
+  fn slide(&mut self, cursor: Point2<f32>, dimensions: &Dimensions, settings: &mut Settings, strings: &Localization) -> bool {
+    let value = match self.panel.pressed.and_then(|idx| self.panel.slider_value_at(idx, cursor.x * dimensions.hidpi_factor, dimensions)) {
+      Some(value) => (value / VOLUME_STEP).round() * VOLUME_STEP,
+      None => return false,
+    };
+    match self.selected() {
+      Some(MenuItem::MasterVolume) => settings.master_volume = value,
+      Some(MenuItem::MusicVolume) => settings.music_volume = value,
+      Some(MenuItem::EffectsVolume) => settings.effects_volume = value,
+      _ => return false,
+    }
+    self.refresh_widgets(settings, strings);
+    true
+  }
+
+  fn select(&mut self, step: isize) {
+    self.panel.focus_step(step);
+  }
+
+  // Returns true when the selected item is a setting and it was changed
+  fn adjust(&mut self, step: isize, settings: &mut Settings, strings: &Localization) -> bool {
+    let volume_step = step as f32 * VOLUME_STEP;
+    let volume = |volume: &mut f32| *volume = (*volume + volume_step).clamp(0.0, 1.0);
+    match self.selected() {
+      Some(MenuItem::MasterVolume) => volume(&mut settings.master_volume),
+      Some(MenuItem::MusicVolume) => volume(&mut settings.music_volume),
+      Some(MenuItem::EffectsVolume) => volume(&mut settings.effects_volume),
+      Some(MenuItem::Fullscreen) => settings.video.fullscreen = !settings.video.fullscreen,
+      Some(MenuItem::Resolution) => settings.video.cycle_resolution(step),
+      Some(MenuItem::Vsync) => settings.video.vsync = !settings.video.vsync,
+      Some(MenuItem::Difficulty) => settings.difficulty = settings.difficulty.cycle(step),
+      Some(MenuItem::Language) => settings.language = settings.language.cycle(step),
+      Some(MenuItem::AutoSave) => settings.autosave = !settings.autosave,
+      Some(MenuItem::CombatLog) => settings.combat_log = !settings.combat_log,
+      _ => return false,
+    }
+    self.refresh_widgets(settings, strings);
+    true
+  }
+
+  // Options without a range step forward when selected
+  fn is_toggle(&self) -> bool {
+    matches!(self.selected(),
+      Some(MenuItem::Fullscreen) | Some(MenuItem::Resolution) | Some(MenuItem::Vsync) | Some(MenuItem::Difficulty) |
+      Some(MenuItem::AutoSave) | Some(MenuItem::Language) | Some(MenuItem::CombatLog))
+  }
+
+  // Copies the key names the window reports, the menu shows them next to the actions
+  fn sync_bindings(&mut self, rebinding: &mut Rebinding, settings: &Settings, strings: &Localization) {
+    if self.bindings.waiting != rebinding.waiting || self.bindings.key_names != rebinding.key_names {
+      self.bindings.waiting = rebinding.waiting;
+      self.bindings.key_names = rebinding.key_names.clone();
+      self.refresh_widgets(settings, strings);
+    }
+    if let Some(result) = rebinding.preset_result.take() {
+      let details = match result {
+        PresetResult::Imported(name) => vec![strings.format("preset.imported", &[("preset", name)])],
+        PresetResult::Exported(path) => {
+          self.presets = presets();
+          vec![strings.format("preset.exported", &[("file", path.display().to_string())])]
+        }
+        PresetResult::Failed(errors) => {
+          let more = errors.len().saturating_sub(PRESET_ERROR_LINES);
+          errors.iter().take(PRESET_ERROR_LINES)
+            .map(|error| error.text(strings))
+            .chain(Some(strings.format("preset.more_errors", &[("count", more.to_string())])).filter(|_| more > 0))
+            .collect()
+        }
+      };
+      self.show_details(details, settings, strings);
+    }
+  }
+
+  // Returns true when the preset item is selected, the presets wrap around
+  fn cycle_preset(&mut self, step: isize, settings: &Settings, strings: &Localization) -> bool {
+    if self.selected() != Some(MenuItem::Preset) {
+      return false;
+    }
+    if !self.presets.is_empty() {
+      let count = self.presets.len() as isize;
+      self.preset = (self.preset as isize + step).rem_euclid(count) as usize;
+      self.refresh_widgets(settings, strings);
+    }
+    true
+  }
+
+  fn preset_label(&self, settings: &Settings, strings: &Localization) -> String {
+    let name = self.presets.get(self.preset).map_or_else(|| strings.get("menu.preset_none").to_string(), |preset| preset.name.clone());
+    format!("{} - {}", MenuItem::Preset.label(settings, strings), name)
+  }
+
+  // A switched profile changes the names and the active mark
+  fn sync_profiles(&mut self, profiles: &Profiles, settings: &Settings, strings: &Localization) {
+    if self.profiles != *profiles {
+      self.profiles = profiles.clone();
+      self.refresh_widgets(settings, strings);
+    }
+  }
+
+  // The lobby's players are its details
+  fn sync_lobby(&mut self, lobby: &Lobby, settings: &Settings, strings: &Localization) {
+    if self.lobby != *lobby {
+      self.lobby = lobby.clone();
+      if self.items.contains(&MenuItem::StartMatch) {
+        self.details = lobby.lines(strings);
+      }
+      self.refresh_widgets(settings, strings);
+    }
+  }
+
+  // The leaderboard's page is its details
+  fn show_leaderboard(&mut self, table: &LeaderboardTable, settings: &Settings, strings: &Localization) {
+    self.leaderboard = table.clone();
+    self.leaderboard_page = 0;
+    self.show_details(table.lines(0, strings), settings, strings);
+  }
+
+  fn sync_leaderboard(&mut self, table: &LeaderboardTable, settings: &Settings, strings: &Localization) {
+    if self.leaderboard != *table {
+      self.leaderboard = table.clone();
+      self.leaderboard_page = self.leaderboard_page.min(table.pages() - 1);
+      if self.items.contains(&MenuItem::LeaderboardPage) {
+        self.details = table.lines(self.leaderboard_page, strings);
+      }
+      self.refresh_widgets(settings, strings);
+    }
+  }
+
+  // Returns true when the page item is selected, the pages wrap around
+  fn cycle_leaderboard_page(&mut self, step: isize, settings: &Settings, strings: &Localization) -> bool {
+    if self.selected() != Some(MenuItem::LeaderboardPage) {
+      return false;
+    }
+    let pages = self.leaderboard.pages() as isize;
+    self.leaderboard_page = (self.leaderboard_page as isize + step).rem_euclid(pages) as usize;
+    self.show_details(self.leaderboard.lines(self.leaderboard_page, strings), settings, strings);
+    true
+  }
+
+  fn leaderboard_page_label(&self, settings: &Settings, strings: &Localization) -> String {
+    format!("{} - {}", MenuItem::LeaderboardPage.label(settings, strings), strings.format("menu.leaderboard_pages",
+      &[("page", (self.leaderboard_page + 1).to_string()), ("pages", self.leaderboard.pages().to_string())]))
+  }
+
+  fn lobby_label(&self, item: MenuItem, settings: &Settings, strings: &Localization) -> String {
+    let label = item.label(settings, strings);
+    match item {
+      MenuItem::Skin => format!("{} - {}", label, strings.get(&format!("skin.{}", self.lobby.local.skin.name()))),
+      MenuItem::Ready => format!("{} - {}", label, on_off(self.lobby.local.is_ready, strings)),
+      MenuItem::StartMatch if !self.lobby.is_host => strings.get("menu.waiting_for_host").to_string(),
+      MenuItem::StartMatch if !self.lobby.can_start() => format!("{} - {}", label, strings.get("menu.waiting_for_ready")),
+      MenuItem::Session(idx) => match self.lobby.sessions.get(idx) {
+        Some(session) => format!("{} - {}", session.name, strings.format("menu.session_players",
+          &[("players", session.players.to_string()), ("max", MAX_PLAYERS.to_string())])),
+        None => format!("{} - {}", label, strings.get("menu.session_searching")),
+      },
+      _ => label,
+    }
+  }
+
+  fn binding_label(&self, action: Action, settings: &Settings, strings: &Localization) -> String {
+    if self.bindings.waiting == Some(action) {
+      format!("{} - {}", MenuItem::Binding(action).label(settings, strings), strings.get("menu.press_key"))
+    } else {
+      format!("{} - {}", MenuItem::Binding(action).label(settings, strings), self.bindings.key_name(action))
+    }
+  }
+
+  fn profile_label(&self, idx: usize, settings: &Settings, strings: &Localization) -> String {
+    let label = format!("{} - {}", MenuItem::Profile(idx).label(settings, strings), self.profiles.names[idx]);
+    if idx == self.profiles.active {
+      strings.format("menu.profile_active", &[("profile", label)])
+    } else {
+      label
+    }
+  }
+
+  fn slot_label(&self, item: MenuItem, slot: Option<&Option<(usize, f32)>>, settings: &Settings, strings: &Localization) -> String {
+    let summary = match slot {
+      Some(Some((wave, time))) => strings.format("menu.slot_summary", &[("wave", wave.to_string()), ("time", format_time(*time))]),
+      _ => strings.get("menu.slot_empty").to_string(),
+    };
+    format!("{} - {}", item.label(settings, strings), summary)
+  }
+
+  fn activate(&self, states: &mut GameStates, rebinding: &mut Rebinding, lobby: &mut Lobby) {
+    match self.selected() {
+      // A new game never continues the world left behind by quitting to the menu
+      Some(MenuItem::Play) | Some(MenuItem::Restart) => {
+        states.request(StateTransition::Reset(GameState::Playing));
+        states.is_restarting = true;
+      }
+      Some(MenuItem::HostGame) => {
+        states.net = Some(NetMode::Host);
+        states.request(StateTransition::Push(GameState::Lobby));
+      }
+      Some(MenuItem::JoinGame) => states.request(StateTransition::Push(GameState::Browser)),
+      Some(MenuItem::Session(idx)) => if let Some(session) = lobby.sessions.get(idx) {
+        states.net = Some(NetMode::Client(session.address.to_string()));
+        states.request(StateTransition::Switch(GameState::Lobby));
+      },
+      Some(MenuItem::Skin) => lobby.local.skin = lobby.local.skin.cycle(),
+      Some(MenuItem::Ready) => lobby.local.is_ready = !lobby.local.is_ready,
+      // Everyone goes from the lobby into the same new run
+      Some(MenuItem::StartMatch) if lobby.can_start() => {
+        states.request(StateTransition::Reset(GameState::Playing));
+        states.is_restarting = true;
+      }
+      Some(MenuItem::LeaveLobby) => leave_lobby(states),
+      Some(MenuItem::Resume) | Some(MenuItem::Back) => states.request(StateTransition::Pop),
+      Some(MenuItem::SaveGame) => states.request(StateTransition::Push(GameState::SaveSlots)),
+      Some(MenuItem::LoadGame) => states.request(StateTransition::Push(GameState::LoadSlots)),
+      Some(MenuItem::SaveSlot(slot)) if states.current() == GameState::SaveSlots => {
+        states.save_slot = Some(slot);
+        states.request(StateTransition::Pop);
+      }
+      // The game goes on once the save was read, an empty or broken slot leaves the menu open
+      Some(MenuItem::SaveSlot(slot)) => states.load_slot = Some(SaveFile::Slot(slot)),
+      Some(MenuItem::AutoSaveSlot(idx)) => states.load_slot = Some(SaveFile::Auto(idx)),
+      Some(MenuItem::Profiles) => states.request(StateTransition::Push(GameState::Profiles)),
+      Some(MenuItem::Profile(idx)) => {
+        states.profile = Some(idx);
+        states.request(StateTransition::Pop);
+      }
+      Some(MenuItem::Settings) => states.request(StateTransition::Push(GameState::Settings)),
+      Some(MenuItem::Controls) => states.request(StateTransition::Push(GameState::Controls)),
+      Some(MenuItem::Records) => states.request(StateTransition::Push(GameState::Records)),
+      Some(MenuItem::Leaderboard) => states.request(StateTransition::Push(GameState::Leaderboard)),
+      Some(MenuItem::Stats) => states.request(StateTransition::Push(GameState::Stats)),
+      Some(MenuItem::Binding(action)) => rebinding.waiting = Some(action),
+      Some(MenuItem::Preset) => rebinding.preset = self.presets.get(self.preset).map(|preset| PresetRequest::Import(preset.path.clone())),
+      Some(MenuItem::ExportBindings) => rebinding.preset = Some(PresetRequest::Export),
+      Some(MenuItem::QuitToMenu) => states.request(StateTransition::Reset(GameState::Menu)),
+      Some(MenuItem::Quit) => states.is_quitting = true,
+      _ => (),
+    }
+  }
+
+  fn refresh_widgets(&mut self, settings: &Settings, strings: &Localization) {
+    let title = self.title.map_or(GAME_TITLE, |key| strings.get(key));
+    let widgets = Some(Widget::Label(title.to_string())).into_iter()
+      .chain(self.items.iter()
+        .map(|item| match *item {
+          MenuItem::MasterVolume => Widget::Slider(item.label(settings, strings), settings.master_volume),
+          MenuItem::MusicVolume => Widget::Slider(item.label(settings, strings), settings.music_volume),
+          MenuItem::EffectsVolume => Widget::Slider(item.label(settings, strings), settings.effects_volume),
+          MenuItem::Binding(action) => Widget::Button(self.binding_label(action, settings, strings)),
+          MenuItem::Preset => Widget::Button(self.preset_label(settings, strings)),
+          MenuItem::LeaderboardPage => Widget::Button(self.leaderboard_page_label(settings, strings)),
+          MenuItem::Profiles => Widget::Button(format!("{} - {}", item.label(settings, strings), self.profiles.active_name())),
+          MenuItem::Profile(idx) => Widget::Button(self.profile_label(idx, settings, strings)),
+          MenuItem::SaveSlot(idx) => Widget::Button(self.slot_label(*item, self.slots.get(idx), settings, strings)),
+          MenuItem::AutoSaveSlot(idx) => Widget::Button(self.slot_label(*item, self.autosaves.get(idx), settings, strings)),
+          MenuItem::Skin | MenuItem::Ready | MenuItem::StartMatch | MenuItem::Session(_) =>
+            Widget::Button(self.lobby_label(*item, settings, strings)),
+          _ => Widget::Button(item.label(settings, strings)),
+        }))
+      .chain(self.details.iter().map(|line| Widget::Label(line.clone())))
+      .collect();
+    self.panel.set_widgets(widgets);
+  }
+}
+
+impl Default for Menu {
+  fn default() -> Menu {
+    Menu::new()
+  }
+}
+
+fn leave_lobby(states: &mut GameStates) {
+  states.net = Some(NetMode::Offline);
+  states.request(StateTransition::Reset(GameState::Menu));
+}
+
+// Select and a click on a button do the same
+fn confirm(menu: &mut Menu, states: &mut GameStates, settings: &mut Settings, mixer: &mut Mixer, rebinding: &mut Rebinding,
+           lobby: &mut Lobby, strings: &Localization) {
+  if menu.is_toggle() && menu.adjust(1, settings, strings) {
+    settings.apply(mixer);
+    settings.save(&config_path());
+  } else if !menu.cycle_leaderboard_page(1, settings, strings) {
+    menu.activate(states, rebinding, lobby);
+  }
+}
+
+// Returns true when the control gives feedback
+fn inventory_control(control: MenuControl,
+                     screen: &mut InventoryScreen,
+                     loadout: &mut Loadout,
+                     states: &mut GameStates,
+                     strings: &Localization,
+                     dimensions: &Dimensions) -> bool {
+  match control {
+    MenuControl::Inventory => states.request(StateTransition::Pop),
+    MenuControl::Back => if !screen.cancel() {
+      states.request(StateTransition::Pop);
+    },
+    MenuControl::Point(cursor) => return screen.point(cursor, dimensions),
+    MenuControl::Press(cursor) => return screen.press(cursor, dimensions),
+    MenuControl::Release => return screen.release(loadout, strings),
+    MenuControl::Left | MenuControl::Up => screen.select(-1),
+    MenuControl::Right | MenuControl::Down => screen.select(1),
+    MenuControl::Select => {
+      screen.confirm(loadout, strings);
+    }
+    MenuControl::QuickSave | MenuControl::QuickLoad => return false,
+  }
+  true
+}
+
+// Runs every frame, also while the simulation is suspended
+pub struct MenuSystem {
+  queue: channel::Receiver<MenuControl>,
+  reader: ReaderId<GameEvent>,
+}
+
+impl MenuSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> (MenuSystem, channel::Sender<MenuControl>) {
+    let (tx, rx) = channel::unbounded();
+    (MenuSystem {
+      queue: rx,
+      reader,
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for MenuSystem {
+  type SystemData = (Write<'a, GameStates>,
+                     Write<'a, Menu>,
+                     Write<'a, Mixer>,
+                     Write<'a, Settings>,
+                     Write<'a, Rebinding>,
+                     Write<'a, Localization>,
+                     Write<'a, InventoryScreen>,
+                     Write<'a, Loadout>,
+                     Write<'a, Lobby>,
+                     Read<'a, Leaderboard>,
+                     Read<'a, Profiles>,
+                     Read<'a, GameEvents>,
+                     Read<'a, Dimensions>,
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, Health>);
+
+  fn run(&mut self, (mut states, mut menu, mut mixer, mut settings, mut rebinding, mut strings, mut inventory, mut loadout, mut lobby, leaderboard, profiles, events, dim, players, health): Self::SystemData) {
+    use crate::ecs::Join;
+
+    // A co-op run goes on while any player is left standing
+    if events.read(&mut self.reader).any(|event| matches!(event, GameEvent::PlayerKilled(_))) && (&players, &health).join().all(|(_, h)| h.is_dead()) {
+      states.request(StateTransition::Switch(GameState::GameOver));
+    }
+
+    menu.sync_bindings(&mut rebinding, &settings, &strings);
+    menu.sync_profiles(&profiles, &settings, &strings);
+    menu.sync_lobby(&lobby, &settings, &strings);
+    menu.sync_leaderboard(&leaderboard.table, &settings, &strings);
+    while let Ok(control) = self.queue.try_recv() {
+      // Moving the cursor only gives feedback when it changes the selection
+      let mut is_feedback = true;
+      match (control, states.current()) {
+        (MenuControl::Back, GameState::Playing) => states.request(StateTransition::Push(GameState::Paused)),
+        (MenuControl::Back, GameState::Paused) |
+        (MenuControl::Back, GameState::Settings) |
+        (MenuControl::Back, GameState::Controls) |
+        (MenuControl::Back, GameState::Records) |
+        (MenuControl::Back, GameState::Leaderboard) |
+        (MenuControl::Back, GameState::Stats) |
+        (MenuControl::Back, GameState::Profiles) |
+        (MenuControl::Back, GameState::SaveSlots) |
+        (MenuControl::Back, GameState::LoadSlots) |
+        (MenuControl::Back, GameState::Browser) => states.request(StateTransition::Pop),
+        (MenuControl::Back, GameState::Lobby) => leave_lobby(&mut states),
+        (MenuControl::Inventory, GameState::Playing) => states.request(StateTransition::Push(GameState::Inventory)),
+        (MenuControl::QuickSave, GameState::Playing) => states.save_slot = Some(0),
+        (MenuControl::QuickLoad, GameState::Playing) => states.load_slot = Some(SaveFile::Slot(0)),
+        (_, GameState::Inventory) => is_feedback = inventory_control(control, &mut inventory, &mut loadout, &mut states, &strings, &dim),
+        (_, GameState::Playing) | (MenuControl::Back, _) | (MenuControl::Inventory, _) |
+        (MenuControl::QuickSave, _) | (MenuControl::QuickLoad, _) => (),
+        (MenuControl::Point(cursor), _) => {
+          is_feedback = menu.point(cursor, &dim);
+          // Dragging a slider follows the cursor, the settings are saved once it lets go
+          if menu.slide(cursor, &dim, &mut settings, &strings) {
+            settings.apply(&mut mixer);
+          }
+        }
+        (MenuControl::Press(cursor), _) => {
+          is_feedback = menu.press(cursor, &dim);
+          if menu.slide(cursor, &dim, &mut settings, &strings) {
+            settings.apply(&mut mixer);
+          }
+        }
+        (MenuControl::Release, _) => match menu.release() {
+          MenuRelease::Button => confirm(&mut menu, &mut states, &mut settings, &mut mixer, &mut rebinding, &mut lobby, &strings),
+          MenuRelease::Slider => {
+            settings.save(&config_path());
+            is_feedback = false;
+          }
+          MenuRelease::Nothing => is_feedback = false,
+        },
+        (MenuControl::Up, _) => menu.select(-1),
+        (MenuControl::Down, _) => menu.select(1),
+        (MenuControl::Left, _) | (MenuControl::Right, _) => {
+          let step = if control == MenuControl::Left { -1 } else { 1 };
+          if !menu.cycle_preset(step, &settings, &strings) && !menu.cycle_leaderboard_page(step, &settings, &strings) &&
+            menu.adjust(step, &mut settings, &strings) {
+            settings.apply(&mut mixer);
+            settings.save(&config_path());
+          }
+        }
+        (MenuControl::Select, _) => confirm(&mut menu, &mut states, &mut settings, &mut mixer, &mut rebinding, &mut lobby, &strings),
+      }
+      if (menu.is_visible || states.current() == GameState::Inventory) && is_feedback {
+        mixer.play(Sound::UiClick);
+      }
+    }
+    // A new language takes over the menu right away, the HUD picks it up on its next frame
+    if strings.language() != Some(settings.language) {
+      *strings = Localization::load(settings.language);
+      menu.refresh_widgets(&settings, &strings);
+    }
+  }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use json::JsonValue;
+use tracing::warn;
+
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Read, ReadStorage, ReaderId, Write};
+use crate::game::constants::{STATS_PATH, STATS_VERSION, TILE_SIZE};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::localization::Localization;
+use crate::game::profiles::user_file;
+use crate::game::snapshot::{field_f32, field_usize, Snapshot};
+use crate::game::storage;
+use crate::graphics::distance;
+
+pub fn stats_path() -> String {
+  user_file(STATS_PATH).to_string_lossy().into_owned()
+}
+
+// Lifetime totals of a profile over all runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+  // By prefab name
+  pub kills: BTreeMap<String, usize>,
+  pub shots_fired: usize,
+  pub shots_hit: usize,
+  // World units walked by all players
+  pub distance: f32,
+  pub deaths: usize,
+}
+
+impl Stats {
+  pub fn record(&mut self, event: GameEvent) {
+    match event {
+      GameEvent::ZombieKilled(_, kind) => *self.kills.entry(kind.to_string()).or_insert(0) += 1,
+      GameEvent::BulletFired(_) => self.shots_fired += 1,
+      GameEvent::BulletHit(_) => self.shots_hit += 1,
+      GameEvent::PlayerKilled(_) => self.deaths += 1,
+      _ => (),
+    }
+  }
+
+  pub fn total_kills(&self) -> usize {
+    self.kills.values().sum()
+  }
+
+  // Percent of the shots that hit a zombie
+  pub fn accuracy(&self) -> f32 {
+    if self.shots_fired == 0 {
+      return 0.0;
+    }
+    (self.shots_hit as f32 / self.shots_fired as f32 * 100.0).min(100.0)
+  }
+
+  // The stats menu page, zombie kinds are named by their `zombie.<prefab>` string
+  pub fn lines(&self, strings: &Localization) -> Vec<String> {
+    Some(strings.format("stats.kills", &[("kills", self.total_kills().to_string())])).into_iter()
+      .chain(self.kills.iter().map(|(kind, kills)| strings.format("stats.kind_kills",
+        &[("kind", strings.get(&format!("zombie.{}", kind)).to_string()), ("kills", kills.to_string())])))
+      .chain(vec![
+        strings.format("stats.shots", &[("fired", self.shots_fired.to_string()), ("hit", self.shots_hit.to_string())]),
+        strings.format("stats.accuracy", &[("accuracy", format!("{:.0}", self.accuracy()))]),
+        strings.format("stats.distance", &[("tiles", format!("{:.0}", self.distance / TILE_SIZE))]),
+        strings.format("stats.deaths", &[("deaths", self.deaths.to_string())]),
+      ])
+      .collect()
+  }
+
+  // A missing file means nothing played yet, a broken one that can't be repaired or one from a newer version starts over
+  pub fn load(filename: &str) -> Stats {
+    match storage::read_json(filename).and_then(|value| value.map(|value| Stats::from_json(&value)).transpose()) {
+      Ok(stats) => stats.unwrap_or_default(),
+      Err(e) => {
+        warn!("Stats {} load error {}", filename, e);
+        Stats::default()
+      }
+    }
+  }
+
+  pub fn save(&self, filename: &str) {
+    if let Err(e) = storage::write_json(filename, &self.to_json()) {
+      warn!("Stats {} save error {}", filename, e);
+    }
+  }
+}
+
+impl Snapshot for Stats {
+  fn to_json(&self) -> JsonValue {
+    let mut kills = JsonValue::new_object();
+    for (kind, &count) in &self.kills {
+      kills[kind.as_str()] = count.into();
+    }
+    object! {
+      "version" => STATS_VERSION,
+      "kills" => kills,
+      "shots_fired" => self.shots_fired,
+      "shots_hit" => self.shots_hit,
+      "distance" => self.distance,
+      "deaths" => self.deaths
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Stats, String> {
+    let version = field_usize(value, "version")?;
+    if version > STATS_VERSION {
+      return Err(format!("Stats version {} is newer than the supported {}", version, STATS_VERSION));
+    }
+    Ok(Stats {
+      kills: value["kills"].entries()
+        .map(|(kind, count)| count.as_usize().map(|count| (kind.to_string(), count)).ok_or(format!("Missing kills of {}", kind)))
+        .collect::<Result<_, _>>()?,
+      shots_fired: field_usize(value, "shots_fired")?,
+      shots_hit: field_usize(value, "shots_hit")?,
+      distance: field_f32(value, "distance")?,
+      deaths: field_usize(value, "deaths")?,
+    })
+  }
+}
+
+// Counts the events of every simulation step and the steps the players walk. The file is written
+// when the game pauses, ends or quits, not on every change.
+pub struct StatsSystem {
+  reader: ReaderId<GameEvent>,
+}
+
+impl StatsSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> StatsSystem {
+    StatsSystem {
+      reader,
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for StatsSystem {
+  type SystemData = (Write<'a, Stats>,
+                     Read<'a, GameEvents>,
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterInputState>);
+
+  fn run(&mut self, (mut stats, events, players, character_input): Self::SystemData) {
+    use crate::ecs::Join;
+
+    for &event in events.read(&mut self.reader) {
+      stats.record(event);
+    }
+    for (_, ci) in (&players, &character_input).join() {
+      let step = ci.movement - ci.previous_movement;
+      stats.distance += distance(step.x(), step.y());
+    }
+  }
+}
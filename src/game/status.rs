@@ -0,0 +1,122 @@
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Read, ReadStorage, ReaderId, WriteStorage};
+use crate::game::constants::{EXHAUSTED_DURATION, HURT_DURATION, STAMINA_DRAIN, STAMINA_RECOVERY};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::scratch::Scratch;
+use crate::graphics::DeltaTime;
+
+// Drains while the character moves and recovers while standing still
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stamina {
+  pub current: f32,
+  pub max: f32,
+}
+
+impl Stamina {
+  pub fn new(max: f32) -> Stamina {
+    Stamina {
+      current: max,
+      max,
+    }
+  }
+
+  // Returns true when the stamina runs out
+  pub fn update(&mut self, is_moving: bool, delta: f32) -> bool {
+    let was_empty = self.current <= 0.0;
+    let change = if is_moving { -STAMINA_DRAIN } else { STAMINA_RECOVERY };
+    self.current = (self.current + change * delta).clamp(0.0, self.max);
+    !was_empty && self.current <= 0.0
+  }
+}
+
+impl ecs::Component for Stamina {
+  type Storage = ecs::DenseVecStorage<Stamina>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusEffect {
+  Hurt,
+  Exhausted,
+}
+
+// Active effects with the seconds they have left
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusEffects {
+  effects: Vec<(StatusEffect, f32)>,
+}
+
+impl StatusEffects {
+  pub fn new() -> StatusEffects {
+    StatusEffects {
+      effects: Vec::new(),
+    }
+  }
+
+  // Applying an active effect again restarts it, the longer duration wins
+  pub fn apply(&mut self, effect: StatusEffect, duration: f32) {
+    match self.effects.iter_mut().find(|(e, _)| *e == effect) {
+      Some(active) => active.1 = active.1.max(duration),
+      None => self.effects.push((effect, duration)),
+    }
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    for active in &mut self.effects {
+      active.1 -= delta;
+    }
+    self.effects.retain(|&(_, remaining)| remaining > 0.0);
+  }
+
+  pub fn active(&self) -> impl Iterator<Item=StatusEffect> + '_ {
+    self.effects.iter().map(|&(effect, _)| effect)
+  }
+}
+
+impl ecs::Component for StatusEffects {
+  type Storage = ecs::DenseVecStorage<StatusEffects>;
+}
+
+pub struct StatusSystem {
+  reader: ReaderId<GameEvent>,
+  // Indices of the players damaged this step
+  hits: Scratch<usize>,
+}
+
+impl StatusSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> StatusSystem {
+    StatusSystem {
+      reader,
+      hits: Scratch::new(),
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for StatusSystem {
+  type SystemData = (ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, Player>,
+                     WriteStorage<'a, Stamina>,
+                     WriteStorage<'a, StatusEffects>,
+                     Read<'a, GameEvents>,
+                     Read<'a, DeltaTime>);
+
+  fn run(&mut self, (character_input, players, mut stamina, mut effects, events, dt): Self::SystemData) {
+    use crate::ecs::Join;
+
+    let delta = dt.0 as f32;
+    let hits = self.hits.fill(events.read(&mut self.reader)
+      .filter_map(|event| match *event {
+        GameEvent::PlayerDamaged(player, ..) => Some(player),
+        _ => None,
+      }));
+
+    for (ci, p, s, e) in (&character_input, &players, &mut stamina, &mut effects).join() {
+      e.tick(delta);
+      if hits.contains(&p.index) {
+        e.apply(StatusEffect::Hurt, HURT_DURATION);
+      }
+      if s.update(ci.movement != ci.previous_movement, delta) {
+        e.apply(StatusEffect::Exhausted, EXHAUSTED_DURATION);
+      }
+    }
+  }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use json::JsonValue;
+use tracing::{info, warn};
+
+const CHECKSUM_FIELD: &str = "checksum";
+
+// 64 bit FNV-1a, enough to tell a damaged file from a whole one
+pub fn checksum(text: &str) -> String {
+  let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+  format!("{:016x}", hash)
+}
+
+fn temp_path(filename: &str) -> String {
+  format!("{}.tmp", filename)
+}
+
+fn backup_path(filename: &str) -> String {
+  format!("{}.bak", filename)
+}
+
+// The file is written next to the old one and renamed over it, so a crash or a sync client
+// reading midway sees either the old or the new file. The old one is kept as a backup.
+pub fn write_json(filename: &str, value: &JsonValue) -> io::Result<()> {
+  let mut value = value.clone();
+  value.remove(CHECKSUM_FIELD);
+  value[CHECKSUM_FIELD] = checksum(&value.dump()).into();
+  let temp = temp_path(filename);
+  let mut file = fs::File::create(&temp)?;
+  file.write_all(value.pretty(2).as_bytes())?;
+  file.sync_all()?;
+  if Path::new(filename).exists() {
+    fs::copy(filename, backup_path(filename))?;
+  }
+  fs::rename(&temp, filename)
+}
+
+// Files written before checksums were added have none and are taken as they are
+fn read_checked(filename: &str) -> Result<JsonValue, String> {
+  let contents = fs::read_to_string(filename).map_err(|e| e.to_string())?;
+  let mut value = json::parse(&contents).map_err(|e| e.to_string())?;
+  let expected = value.remove(CHECKSUM_FIELD);
+  if !expected.is_null() && expected.as_str() != Some(checksum(&value.dump()).as_str()) {
+    return Err("Checksum mismatch, the file is damaged".to_string());
+  }
+  Ok(value)
+}
+
+// Ok(None) when nothing was written yet. A damaged or half written file is repaired from a
+// finished write that wasn't renamed yet or from the backup, whichever is whole.
+pub fn read_json(filename: &str) -> Result<Option<JsonValue>, String> {
+  let candidates = [filename.to_string(), temp_path(filename), backup_path(filename)];
+  if !candidates.iter().any(|path| Path::new(path).exists()) {
+    return Ok(None);
+  }
+  let error = match read_checked(filename) {
+    Ok(value) => return Ok(Some(value)),
+    Err(e) => e,
+  };
+  for candidate in &candidates[1..] {
+    if let Ok(value) = read_checked(candidate) {
+      match fs::copy(candidate, filename) {
+        Ok(_) => info!("Repaired {} from {}", filename, candidate),
+        Err(e) => warn!("{} repair error {}", filename, e),
+      }
+      return Ok(Some(value));
+    }
+  }
+  Err(error)
+}
@@ -1,9 +1,15 @@
 use crossbeam_channel as channel;
 
-use crate::audio::Effects;
 use crate::character::controls::CharacterControl;
+use crate::game::chat::ChatControl;
+use crate::game::console::ConsoleControl;
+use crate::game::inspector::InspectorControl;
+use crate::game::state::{GameState, MenuControl};
+use crate::gfx_app::input::{Action, InputContext, InputContexts};
 use crate::gfx_app::mouse_controls::MouseControl;
 use crate::graphics::camera::CameraControl;
+use crate::graphics::shader_params::ShaderParamControl;
+use crate::profiler::ProfilerEvent;
 
 pub enum Control {
   Plus,
@@ -12,22 +18,144 @@ pub enum Control {
 }
 
 pub struct TilemapControls {
-  audio_control: channel::Sender<Effects>,
   terrain_control: channel::Sender<CameraControl>,
   character_control: channel::Sender<CharacterControl>,
+  second_character_control: channel::Sender<CharacterControl>,
   mouse_control: channel::Sender<(MouseControl, Option<(f64, f64)>)>,
+  profiler_control: channel::Sender<ProfilerEvent>,
+  shader_param_control: channel::Sender<ShaderParamControl>,
+  menu_control: channel::Sender<MenuControl>,
+  console_control: channel::Sender<ConsoleControl>,
+  chat_control: channel::Sender<ChatControl>,
+  inspector_control: channel::Sender<InspectorControl>,
+  contexts: InputContexts,
+  // The key that opens the chat types its character too, nothing is typed until it is released
+  is_chat_key_held: bool,
+}
+
+// The channels the key and mouse input is routed to
+pub struct ControlSenders {
+  pub terrain_control: channel::Sender<CameraControl>,
+  pub character_control: channel::Sender<CharacterControl>,
+  pub second_character_control: channel::Sender<CharacterControl>,
+  pub mouse_control: channel::Sender<(MouseControl, Option<(f64, f64)>)>,
+  pub profiler_control: channel::Sender<ProfilerEvent>,
+  pub shader_param_control: channel::Sender<ShaderParamControl>,
+  pub menu_control: channel::Sender<MenuControl>,
+  pub console_control: channel::Sender<ConsoleControl>,
+  pub chat_control: channel::Sender<ChatControl>,
+  pub inspector_control: channel::Sender<InspectorControl>,
 }
 
 impl TilemapControls {
-  pub fn new(atc: channel::Sender<Effects>,
-             ttc: channel::Sender<CameraControl>,
-             ctc: channel::Sender<CharacterControl>,
-             mtc: channel::Sender<(MouseControl, Option<(f64, f64)>)>) -> TilemapControls {
+  pub fn new(senders: ControlSenders) -> TilemapControls {
+    let ControlSenders { terrain_control, character_control, second_character_control, mouse_control, profiler_control,
+      shader_param_control, menu_control, console_control, chat_control, inspector_control } = senders;
     TilemapControls {
-      audio_control: atc,
-      terrain_control: ttc,
-      character_control: ctc,
-      mouse_control: mtc,
+      terrain_control,
+      character_control,
+      second_character_control,
+      mouse_control,
+      profiler_control,
+      shader_param_control,
+      menu_control,
+      console_control,
+      chat_control,
+      inspector_control,
+      contexts: InputContexts::new(),
+      is_chat_key_held: false,
+    }
+  }
+
+  pub fn active_context(&self) -> InputContext {
+    self.contexts.active()
+  }
+
+  pub fn route(&self, action: Action) -> Option<InputContext> {
+    self.contexts.route(action)
+  }
+
+  // Key releases go to the context that took over, so anything held in gameplay is let go here
+  fn update_contexts<F: FnOnce(&mut InputContexts)>(&mut self, change: F) {
+    let was_gameplay = self.contexts.active() == InputContext::Gameplay;
+    change(&mut self.contexts);
+    if was_gameplay && self.contexts.active() != InputContext::Gameplay {
+      for control in &[&self.character_control, &self.second_character_control] {
+        for release in &[CharacterControl::XMoveStop, CharacterControl::YMoveStop, CharacterControl::CtrlReleased, CharacterControl::ReloadReleased] {
+          control.send(*release).expect("Character release control update error");
+        }
+      }
+      self.zoom(&Control::Released);
+      self.mouse_left_click(None);
+    }
+  }
+
+  pub fn set_game_state(&mut self, state: GameState) {
+    self.update_contexts(|contexts| if state.is_simulating() {
+      contexts.remove(InputContext::Menu)
+    } else {
+      contexts.push(InputContext::Menu)
+    });
+  }
+
+  // The console and the chat take typed characters through the text entry, which stays while
+  // either of them is open
+  fn toggle_text_entry(&mut self, context: InputContext) -> bool {
+    let is_open = self.contexts.contains(context);
+    let other = if context == InputContext::Console { InputContext::Chat } else { InputContext::Console };
+    let keeps_entry = self.contexts.contains(other);
+    self.update_contexts(|contexts| if is_open {
+      contexts.remove(context);
+      if !keeps_entry {
+        contexts.remove(InputContext::TextEntry);
+      }
+    } else {
+      contexts.push(context);
+      contexts.push(InputContext::TextEntry);
+    });
+    !is_open
+  }
+
+  pub fn toggle_console(&mut self) {
+    let is_open = self.toggle_text_entry(InputContext::Console);
+    self.console(if is_open { ConsoleControl::Open } else { ConsoleControl::Close });
+  }
+
+  pub fn console(&mut self, control: ConsoleControl) {
+    self.console_control.send(control).expect("Console control update error");
+  }
+
+  pub fn toggle_chat(&mut self) {
+    let is_open = self.toggle_text_entry(InputContext::Chat);
+    self.is_chat_key_held = is_open;
+    self.chat(if is_open { ChatControl::Open } else { ChatControl::Close });
+  }
+
+  pub fn chat(&mut self, control: ChatControl) {
+    self.chat_control.send(control).expect("Chat control update error");
+  }
+
+  pub fn chat_key_released(&mut self) {
+    self.is_chat_key_held = false;
+  }
+
+  // Typed characters go to the console while it is open over the chat
+  pub fn text_entry(&self) -> Option<InputContext> {
+    if self.contexts.contains(InputContext::Console) {
+      Some(InputContext::Console)
+    } else if self.contexts.contains(InputContext::Chat) && !self.is_chat_key_held {
+      Some(InputContext::Chat)
+    } else {
+      None
+    }
+  }
+
+  // Pause closes the chat unless the console is open over it
+  pub fn close_text_entry(&mut self, action: Action) {
+    if action == Action::Pause && self.contexts.contains(InputContext::Chat) && !self.contexts.contains(InputContext::Console) {
+      self.toggle_chat();
+    } else {
+      self.toggle_console();
     }
   }
 
@@ -39,6 +167,10 @@ impl TilemapControls {
     }.expect("Terrain control update error");
   }
 
+  pub fn zoom_by(&mut self, lines: f64) {
+    self.terrain_control.send(CameraControl::ZoomBy(lines as f32)).expect("Terrain control zoom update error");
+  }
+
   pub fn ctrl_pressed(&mut self, is_ctrl: bool) {
     if is_ctrl {
       self.character_control.send(CharacterControl::CtrlPressed)
@@ -51,6 +183,10 @@ impl TilemapControls {
     self.character_control.send(character_control).expect("Character move control update error");
   }
 
+  pub fn move_second_character(&mut self, character_control: CharacterControl) {
+    self.second_character_control.send(character_control).expect("Second character move control update error");
+  }
+
   pub fn reload_weapon(&mut self, is_reloading: bool) {
     if is_reloading {
       self.character_control.send(CharacterControl::ReloadPressed)
@@ -61,9 +197,37 @@ impl TilemapControls {
 
   pub fn mouse_left_click(&mut self, mouse_pos: Option<(f64, f64)>) {
     self.mouse_control.send((MouseControl::LeftClick, mouse_pos)).expect("Mouse control shoot update error");
-    match mouse_pos {
-      Some(_) => self.audio_control.send(Effects::PistolFire),
-      _ => self.audio_control.send(Effects::None),
-    }.expect("Audio control update error");
+  }
+
+  pub fn mouse_moved(&mut self, mouse_pos: (f64, f64)) {
+    self.mouse_control.send((MouseControl::Move, Some(mouse_pos))).expect("Mouse control move update error");
+  }
+
+  pub fn window_resized(&mut self, size: (f64, f64)) {
+    self.mouse_control.send((MouseControl::Resize, Some(size))).expect("Mouse control resize update error");
+  }
+
+  pub fn toggle_profiler(&mut self) {
+    self.profiler_control.send(ProfilerEvent::Toggle).expect("Profiler control update error");
+  }
+
+  pub fn toggle_frame_stats(&mut self) {
+    self.profiler_control.send(ProfilerEvent::ToggleStats).expect("Profiler control update error");
+  }
+
+  pub fn toggle_debug_shapes(&mut self) {
+    self.profiler_control.send(ProfilerEvent::ToggleDebugShapes).expect("Profiler control update error");
+  }
+
+  pub fn menu(&mut self, control: MenuControl) {
+    self.menu_control.send(control).expect("Menu control update error");
+  }
+
+  pub fn tweak_shader_params(&mut self, control: ShaderParamControl) {
+    self.shader_param_control.send(control).expect("Shader param control update error");
+  }
+
+  pub fn inspect(&mut self, control: InspectorControl) {
+    self.inspector_control.send(control).expect("Inspector control update error");
   }
 }
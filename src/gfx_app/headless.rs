@@ -0,0 +1,99 @@
+use crossbeam_channel as channel;
+
+use crate::audio::mixer::Mixer;
+use crate::character::controls::CharacterControl;
+use crate::ecs::{Dispatcher, ReaderId, World, WorldExt};
+use crate::game::constants::{PREFABS_PATH, RESOLUTION_X, RESOLUTION_Y, SIMULATION_STEP};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::GameRng;
+use crate::game::prefab::Prefabs;
+use crate::game::settings::Settings;
+use crate::game::state::{GameState, GameStates};
+use crate::gfx_app::init::{self, SimulationControls};
+use crate::gfx_app::mouse_controls::MouseControl;
+use crate::graphics::camera::ZoomSettings;
+use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+use crate::profiler::ProfilerEvent;
+
+// A run without a window, audio output or any gfx resources. The simulation systems of the game
+// advance it one fixed step at a time and take the same controls the window sends. Nothing is read
+// from or written to the player's profile, so tests can play through a run and look at the world.
+pub struct Headless {
+  pub world: World,
+  simulation: Dispatcher<'static, 'static>,
+  controls: SimulationControls,
+  timings: channel::Receiver<ProfilerEvent>,
+  events: ReaderId<GameEvent>,
+  simulated_time: f64,
+  pub steps: usize,
+}
+
+impl Headless {
+  pub fn new(seed: u64) -> Headless {
+    let mut world = World::new();
+    init::register_components(&mut world);
+    world.insert(Dimensions::new(RESOLUTION_X as f32, RESOLUTION_Y as f32, 1.0, true, ProjectionMode::Perspective, false));
+    world.insert(GameEvents::new());
+    world.insert(GameRng::new(Some(seed)));
+    world.insert(Prefabs::load(PREFABS_PATH));
+    world.insert(GameStates::new(GameState::Playing));
+    world.insert(Settings::new());
+    world.insert(Mixer::new());
+    init::start_run(&mut world, false);
+
+    let events = world.write_resource::<GameEvents>().register_reader();
+    let (profiler_control, timings) = channel::unbounded();
+    let (mut simulation, controls) = init::build_simulation(&mut world, ZoomSettings::new(), &profiler_control);
+    // The resources only the window or the menus would have put in start from their defaults, the
+    // tile materials too, they only pick the footstep sounds
+    simulation.setup(&mut world);
+
+    Headless {
+      world,
+      simulation,
+      controls,
+      timings,
+      events,
+      simulated_time: 0.0,
+      steps: 0,
+    }
+  }
+
+  pub fn control(&self, player: usize, control: CharacterControl) {
+    self.controls.character_controls[player].send(control).expect("Headless character control error");
+  }
+
+  // Mouse input of the first player, in window pixels like the window sends it
+  pub fn mouse(&self, control: MouseControl, position: Option<(f64, f64)>) {
+    self.controls.mouse_control.send((control, position)).expect("Headless mouse control error");
+  }
+
+  pub fn step(&mut self) {
+    self.simulated_time += SIMULATION_STEP;
+    init::step(&mut self.world, &mut self.simulation, self.simulated_time);
+    self.steps += 1;
+    // Nothing shows the system timings here
+    while self.timings.try_recv().is_ok() {}
+  }
+
+  pub fn run(&mut self, steps: usize) {
+    for _ in 0..steps {
+      self.step();
+    }
+  }
+
+  // Steps until the world passes the check and returns the steps that took, or None once the
+  // steps run out
+  pub fn run_until<F>(&mut self, max_steps: usize, mut check: F) -> Option<usize>
+    where F: FnMut(&World) -> bool {
+    (1..=max_steps).find(|_| {
+      self.step();
+      check(&self.world)
+    })
+  }
+
+  // The game events written since the last call
+  pub fn events(&mut self) -> Vec<GameEvent> {
+    self.world.read_resource::<GameEvents>().read(&mut self.events).cloned().collect()
+  }
+}
@@ -1,143 +1,627 @@
-use std::time;
+use std::{thread, time};
 
+use crossbeam_channel as channel;
 use gfx;
-use specs::{Builder, prelude::DispatcherBuilder, shred::World, world::WorldExt};
+use tracing::info;
 
+use crate::ecs::{Builder, Dispatcher, DispatcherBuilder, RunNow, World, WorldExt};
 use crate::{bullet, terrain_shape};
 use crate::audio::AudioSystem;
-use crate::bullet::bullets::Bullets;
-use crate::bullet::collision::CollisionSystem;
+use crate::audio::mixer::Mixer;
+use crate::audio::director::AudioDirectorSystem;
+use crate::bullet::collision::{CollisionSystem, Contacts};
 use crate::character;
-use crate::character::controls::CharacterControlSystem;
+use crate::character::controls::{CharacterControl, CharacterControlSystem};
+use crate::data;
 use crate::critter::CharacterSprite;
+use crate::game::chat::{Chat, ChatSystem};
+use crate::game::console::{Console, ConsoleSystem};
+use crate::game::crash::CrashRecorder;
+use crate::game::events::{EventLogSystem, GameEvent, GameEvents};
+use crate::game::GameRng;
+use crate::game::health::Health;
+use crate::game::hot_reload::{self, AssetReloader};
+use crate::game::input_buffer::{InputBuffer, InputBufferSystem};
+use crate::game::inspector::{Inspector, InspectorSystem};
+use crate::game::inventory::{InventoryScreen, Loadout};
+use crate::game::localization::Localization;
+use crate::game::prefab::Prefabs;
+use crate::game::score::{Score, ScoreSystem};
+use crate::game::leaderboard::{Leaderboard, LeaderboardSettings, LeaderboardSystem};
+use crate::game::records::{Records, records_path};
+use crate::game::profiles::Profiles;
+use crate::game::save::{self, SaveFile, SaveGame};
+use crate::game::scripting::ScriptSystem;
+use crate::game::settings::{config_path, Settings};
+use crate::game::state::{self, GameState, GameStates, Menu, MenuSystem, StateTransition};
+use crate::game::status::{Stamina, StatusEffects, StatusSystem};
+use crate::game::spawner::{self, SpawnSystem, WaveState};
+use crate::game::stats::{Stats, stats_path, StatsSystem};
 use crate::gfx_app::{Window, WindowStatus};
-use crate::gfx_app::controls::TilemapControls;
-use crate::gfx_app::mouse_controls::{MouseControlSystem, MouseInputState};
+use crate::gfx_app::controls::{ControlSenders, TilemapControls};
+use crate::gfx_app::input::Rebinding;
+use crate::gfx_app::loading::LoadingScreen;
+use crate::gfx_app::mouse_controls::{MouseControl, MouseControlSystem, MouseInputState, MouseState};
+use crate::gfx_app::replay::FrameInput;
 use crate::gfx_app::renderer::DeviceRenderer;
 use crate::gfx_app::system::DrawSystem;
 use crate::graphics;
-use crate::graphics::{DeltaTime, dimensions::Dimensions, GameTime};
-use crate::graphics::camera::CameraControlSystem;
+use crate::graphics::assets::Assets;
+use crate::graphics::{DeltaTime, dimensions::{Dimensions, ProjectionMode}, GameTime, Interpolation, StepAccumulator};
+use crate::graphics::camera::{CameraControl, CameraControlSystem, ZoomSettings};
+use crate::graphics::debug_shapes::{DebugShapes, DebugShapesSystem};
+use crate::graphics::governor::{FrameGovernor, GovernorSystem};
+use crate::graphics::render_graph::{RenderTargets, ShaderReloader};
+use crate::graphics::shader_params::{ShaderParamControl, ShaderParams, ShaderParamSystem};
+use crate::graphics::transform::{MovementSystem, Parent, Transform, TransformPropagationSystem};
 use crate::hud;
+use crate::hud::{ammo_panel::AmmoPanelSystem, banner::BannerSystem, chat_panel::ChatPanelSystem, combat_log::CombatLogSystem, crosshair::CrosshairSystem, damage_indicator::DamageIndicatorSystem, frame_graph::FrameGraphSystem, inventory::InventoryPanelSystem, menu_panel::MenuPanelSystem, nameplates::NameplateSystem, objective_arrow::ObjectiveArrowSystem, pause_overlay::PauseOverlaySystem, score_panel::ScorePanelSystem, status_bars::StatusBarSystem, toasts::ToastSystem, tooltips::TooltipSystem, ui::UiLayer};
+use crate::net::{lobby::{Lobby, SessionBrowser}, LocalPlayer, NetMode, NetSession};
+use crate::profiler::{FrameProfile, Overlay, OverlayDrawSystem, Profiled, ProfilerEvent, ProfilerSystem};
+use crate::profiler::startup::StartupProfile;
+use crate::shaders::Position;
 use crate::terrain;
+use crate::terrain::materials::TileMaterials;
 use crate::terrain_object;
 use crate::zombie;
-use crate::zombie::zombies::Zombies;
-use crate::game::constants::SMALL_HILLS;
+use crate::game::constants::{MAP_FILE_PATH, PLAYER_MAX_HEALTH, PLAYER_MAX_STAMINA, PREFABS_PATH, RESOLUTION_X, RESOLUTION_Y, SIMULATION_STEP,
+                             SMALL_HILLS};
 
-pub fn run<W, D, F>(window: &mut W)
+// Simulation systems run in stages: input -> AI -> collision -> pre-draw, drawing has its own dispatcher.
+// Each stage waits for the previous one, systems inside a stage run in parallel.
+const INPUT_STAGE: &[&str] = &["terrain-system", "character-system", "second-character-system", "third-character-system",
+  "fourth-character-system", "mouse-system", "shader-param-system"];
+const AI_STAGE: &[&str] = &["draw-prep-zombie", "spawn-system", "script-system"];
+const COLLISION_STAGE: &[&str] = &["collision-system", "zombie-hit-system", "bullet-impact-system"];
+
+pub fn run<W, D, F>(window: &mut W, mut startup: StartupProfile)
   where W: Window<D, F>,
         D: gfx::Device + 'static,
         F: gfx::Factory<D::Resources>,
         D::CommandBuffer: Send {
-
+  startup.mark("window");
   let mut w = WorldExt::new();
   let viewport_size = window.get_viewport_size();
   let dimensions = Dimensions::new(viewport_size.0,
                                    viewport_size.1,
                                    window.get_hidpi_factor(),
-                                   window.is_windowed());
-  setup_world(&mut w, dimensions);
-  dispatch_loop(window, &mut w);
+                                   window.is_windowed(),
+                                   window.get_projection_mode(),
+                                   window.is_split_screen());
+  // Without a given seed one is picked here, so a crash report can name it
+  let seed = window.get_seed().unwrap_or_else(rand::random);
+  setup_world(&mut w, dimensions, seed, true, &mut startup);
+  let crash_recorder = CrashRecorder::install(&mut w, seed);
+  dispatch_loop(window, &mut w, crash_recorder, startup);
 }
 
-fn setup_world(world: &mut World, dimensions: Dimensions) {
+// The components of a run, with or without a window
+pub fn register_components(world: &mut World) {
   world.register::<terrain::TerrainDrawable>();
   world.register::<graphics::camera::CameraInputState>();
   world.register::<character::CharacterDrawable>();
-  world.register::<hud::hud_objects::HudObjects>();
-  world.register::<terrain_object::terrain_objects::TerrainObjects>();
-  world.register::<terrain_shape::terrain_shape_objects::TerrainShapeObjects>();
-  world.register::<Zombies>();
-  world.register::<Bullets>();
+  world.register::<terrain_object::TerrainObjectDrawable>();
+  world.register::<terrain_shape::TerrainShapeDrawable>();
+  world.register::<zombie::ZombieDrawable>();
+  world.register::<bullet::BulletDrawable>();
   world.register::<CharacterSprite>();
   world.register::<character::controls::CharacterInputState>();
   world.register::<MouseInputState>();
+  world.register::<character::Player>();
+  world.register::<Health>();
+  world.register::<Stamina>();
+  world.register::<StatusEffects>();
+  world.register::<Transform>();
+  world.register::<Parent>();
+}
 
+// A dedicated server has no players of its own, they all join over the network
+fn setup_world(world: &mut World, dimensions: Dimensions, seed: u64, has_local_players: bool, startup: &mut StartupProfile) {
+  info!("Seed {}, --seed {} plays the same game again", seed, seed);
+  register_components(world);
+
+  let is_split_screen = dimensions.split_screen;
   world.insert(dimensions);
-  world.insert(character::controls::CharacterInputState::new());
-  world.insert(MouseInputState::new());
   world.insert(DeltaTime(0.0));
   world.insert(GameTime(0));
+  world.insert(Interpolation(1.0));
+  world.insert(FrameProfile::default());
+  world.insert(FrameGovernor::default());
+  world.insert(DebugShapes::default());
+  world.insert(ShaderParams::default());
+  world.insert(GameEvents::new());
+  world.insert(GameRng::new(Some(seed)));
+  world.insert(Prefabs::load(PREFABS_PATH));
+  world.insert(GameStates::new(GameState::Menu));
+  world.insert(Lobby::default());
+  world.insert(LocalPlayer::default());
+  world.insert(Menu::new());
+  world.insert(InventoryScreen::new());
+  world.insert(Console::new());
+  world.insert(Inspector::new());
+  world.insert(Chat::default());
+  world.insert(UiLayer::default());
+  world.insert(Rebinding::default());
+  world.insert(Profiles::load());
+  world.insert(Records::load(&records_path()));
+  world.insert(Stats::load(&stats_path()));
+  world.insert(Leaderboard::new(LeaderboardSettings::load(&config_path())));
+  let settings = Settings::load(&config_path());
+  let mut mixer = Mixer::new();
+  settings.apply(&mut mixer);
+  world.insert(mixer);
+  world.insert(Localization::load(settings.language));
+  world.insert(settings);
+  startup.mark("config");
+  world.insert(TileMaterials::new(&data::load_map_file(MAP_FILE_PATH)));
+  startup.mark("map");
 
-  let mut hills = terrain_shape::terrain_shape_objects::TerrainShapeObjects::new();
+  start_world(world);
+  if has_local_players {
+    spawn_local_players(world, is_split_screen);
+  }
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(1));
+  state::on_enter(GameState::Menu, world);
+  startup.mark("run");
+}
+
+// Drops every entity of the finished run, nothing of it is kept alive by a component, and starts a new one
+pub fn restart_run(world: &mut World) {
+  let is_split_screen = world.read_resource::<Dimensions>().split_screen;
+  world.delete_all();
+  world.maintain();
+  start_run(world, is_split_screen);
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(1));
+}
+
+// Settings, bindings, records and the leaderboard come from the new profile. The window reloads the
+// bindings once their names are cleared, zoom settings are read at the next start.
+fn switch_profile(world: &mut World, idx: usize) {
+  world.read_resource::<Stats>().save(&stats_path());
+  world.write_resource::<Profiles>().active = idx;
+  world.read_resource::<Profiles>().save();
+  hot_reload::reload_config(world);
+  world.insert(Records::load(&records_path()));
+  world.insert(Stats::load(&stats_path()));
+  world.insert(Leaderboard::new(LeaderboardSettings::load(&config_path())));
+}
+
+// Replaces the current run with a saved one, the wave banner announces where it goes on
+fn load_run(world: &mut World, save: &SaveGame) {
+  let is_split_screen = world.read_resource::<Dimensions>().split_screen;
+  world.delete_all();
+  world.maintain();
+  start_run(world, is_split_screen);
+  save.restore(world);
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(save.score.wave));
+}
+
+// Entities and the resources that only live as long as a run, with the players of this machine
+pub fn start_run(world: &mut World, is_split_screen: bool) {
+  start_world(world);
+  spawn_local_players(world, is_split_screen);
+}
+
+fn start_world(world: &mut World) {
+  world.insert(character::controls::CharacterInputState::new());
+  world.insert(MouseInputState::new());
+  world.insert(MouseState::new());
+  world.insert(Contacts::default());
+  world.insert(InputBuffer::new());
+  world.insert(Score::new());
+  world.insert(Loadout::new());
+  world.insert(WaveState::new());
 
   for hill in SMALL_HILLS.iter() {
-    hills.small_hill(hill[0], hill[1]);
+    for piece in terrain_shape::small_hill(hill[0], hill[1]) {
+      world.create_entity().with(piece).build();
+    }
+  }
+  for object in terrain_object::terrain_objects::terrain_objects() {
+    world.create_entity().with(object).build();
+  }
+  for zombie in zombie::zombies::first_wave() {
+    world.create_entity().with(zombie).build();
   }
+}
 
+// The first player also carries the map, which is drawn around it
+fn spawn_local_players(world: &mut World, is_split_screen: bool) {
   world.create_entity()
     .with(terrain::TerrainDrawable::new())
     .with(character::CharacterDrawable::new())
-    .with(hud::hud_objects::HudObjects::new())
-    .with(terrain_object::terrain_objects::TerrainObjects::new())
-    .with(hills)
-    .with(Zombies::new())
-    .with(Bullets::new())
     .with(CharacterSprite::new())
     .with(graphics::camera::CameraInputState::new())
     .with(character::controls::CharacterInputState::new())
+    .with(character::Player::new(0))
+    .with(Health::new(PLAYER_MAX_HEALTH))
+    .with(Stamina::new(PLAYER_MAX_STAMINA))
+    .with(StatusEffects::new())
+    .with(Transform::new(Position::origin()))
     .with(MouseInputState::new()).build();
+
+  if is_split_screen {
+    spawner::spawn_player(world.create_entity(), 1);
+  }
+}
+
+// Inputs of the simulation systems. Players 3 and 4 only join over the network.
+pub struct SimulationControls {
+  pub terrain_control: channel::Sender<CameraControl>,
+  pub character_controls: Vec<channel::Sender<CharacterControl>>,
+  pub mouse_control: channel::Sender<(MouseControl, Option<(f64, f64)>)>,
+  pub shader_param_control: channel::Sender<ShaderParamControl>,
+}
+
+// The systems that advance a run, without any drawing, shared by the game, the dedicated server and
+// the headless runs of the tests
+pub fn build_simulation<'a, 'b>(w: &mut World, zoom: ZoomSettings, profiler_control: &channel::Sender<ProfilerEvent>) -> (Dispatcher<'a, 'b>, SimulationControls) {
+  let script_system = ScriptSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let status_system = StatusSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let score_system = ScoreSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let stats_system = StatsSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let event_log_system = EventLogSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let (terrain_system, terrain_control) = CameraControlSystem::new(zoom);
+  let (character_system, character_control) = CharacterControlSystem::new(0);
+  let (second_character_system, second_character_control) = CharacterControlSystem::new(1);
+  let (third_character_system, third_character_control) = CharacterControlSystem::new(2);
+  let (fourth_character_system, fourth_character_control) = CharacterControlSystem::new(3);
+  let (mouse_system, mouse_control) = MouseControlSystem::new();
+  let (shader_param_system, shader_param_control) = ShaderParamSystem::new();
+
+  let p = profiler_control;
+  let simulation = DispatcherBuilder::new()
+    .with(Profiled::new(terrain_system, "terrain-system", p), "terrain-system", &[])
+    .with(Profiled::new(character_system, "character-system", p), "character-system", &[])
+    .with(Profiled::new(second_character_system, "second-character-system", p), "second-character-system", &["character-system"])
+    .with(Profiled::new(third_character_system, "third-character-system", p), "third-character-system", &["second-character-system"])
+    .with(Profiled::new(fourth_character_system, "fourth-character-system", p), "fourth-character-system", &["third-character-system"])
+    .with(Profiled::new(mouse_system, "mouse-system", p), "mouse-system", &[])
+    .with(shader_param_system, "shader-param-system", &[])
+    .with(InputBufferSystem, "input-buffer-system", INPUT_STAGE)
+    .with(Profiled::new(zombie::PreDrawSystem::default(), "draw-prep-zombie", p), "draw-prep-zombie", INPUT_STAGE)
+    .with(Profiled::new(SpawnSystem, "spawn-system", p), "spawn-system", &["draw-prep-zombie"])
+    .with(Profiled::new(script_system, "script-system", p), "script-system", &["spawn-system"])
+    .with(Profiled::new(bullet::PreDrawSystem, "draw-prep-bullet", p), "draw-prep-bullet", AI_STAGE)
+    .with(Profiled::new(CollisionSystem::default(), "collision-system", p), "collision-system", &["draw-prep-bullet"])
+    .with(Profiled::new(zombie::HitSystem, "zombie-hit-system", p), "zombie-hit-system", &["collision-system"])
+    .with(Profiled::new(bullet::ImpactSystem, "bullet-impact-system", p), "bullet-impact-system", &["collision-system"])
+    .with(Profiled::new(character::PreDrawSystem, "draw-prep-character", p), "draw-prep-character", COLLISION_STAGE)
+    .with(Profiled::new(terrain::PreDrawSystem, "draw-prep-terrain", p), "draw-prep-terrain", COLLISION_STAGE)
+    .with(Profiled::new(MovementSystem, "movement-system", p), "movement-system", COLLISION_STAGE)
+    .with(Profiled::new(status_system, "status-system", p), "status-system", &["draw-prep-character"])
+    .with(Profiled::new(score_system, "score-system", p), "score-system", COLLISION_STAGE)
+    .with(Profiled::new(stats_system, "stats-system", p), "stats-system", COLLISION_STAGE)
+    .with(event_log_system, "event-log-system", COLLISION_STAGE)
+    .with(Profiled::new(TransformPropagationSystem, "transform-propagation", p), "transform-propagation", &["draw-prep-character", "movement-system"])
+    .build();
+
+  (simulation, SimulationControls {
+    terrain_control,
+    character_controls: vec![character_control, second_character_control, third_character_control, fourth_character_control],
+    mouse_control,
+    shader_param_control,
+  })
+}
+
+pub fn step(w: &mut World, simulation: &mut Dispatcher, simulated_time: f64) {
+  let _span = tracing::trace_span!("step", time = simulated_time).entered();
+  *w.write_resource::<DeltaTime>() = DeltaTime(SIMULATION_STEP);
+  *w.write_resource::<GameTime>() = GameTime(simulated_time as u64);
+  simulation.dispatch(w);
+  w.maintain();
+}
+
+// A host without a window, the simulation runs alone and the drawing, audio and menus are left
+// out. Players only join over the network, the server has no character of its own.
+pub fn run_server(seed: Option<u64>, mut startup: StartupProfile) {
+  let mut w = WorldExt::new();
+  let dimensions = Dimensions::new(RESOLUTION_X as f32, RESOLUTION_Y as f32, 1.0, true, ProjectionMode::Perspective, false);
+  let seed = seed.or(Settings::load(&config_path()).seed).unwrap_or_else(rand::random);
+  setup_world(&mut w, dimensions, seed, false, &mut startup);
+  w.insert(GameStates::new(GameState::Playing));
+
+  let (mut profiler_system, profiler_control) = ProfilerSystem::new();
+  let (mut simulation, controls) = build_simulation(&mut w, ZoomSettings::load(&config_path()), &profiler_control);
+  startup.mark("simulation");
+  info!("{}", startup.summary());
+  // Nobody types or clicks on the server
+  let (_, keyboard) = channel::unbounded();
+  let (_, mouse) = channel::unbounded();
+  let mut net = NetSession::new(&NetMode::Host, controls.character_controls, controls.mouse_control, keyboard, mouse);
+  if net.mode() != NetMode::Host {
+    return;
+  }
+  w.insert(net.mode());
+
+  let mut last_time = time::Instant::now();
+  let mut accumulator = StepAccumulator::default();
+  let mut simulated_time = 0.0;
+  loop {
+    let elapsed = last_time.elapsed();
+    last_time = time::Instant::now();
+    accumulator.add(elapsed.as_secs_f64(), 1.0);
+    net.receive(&mut w);
+    let steps = accumulator.due_steps();
+    accumulator.take_steps(steps);
+    for _ in 0..steps {
+      simulated_time += SIMULATION_STEP;
+      step(&mut w, &mut simulation, simulated_time);
+    }
+    if steps > 0 {
+      net.send(&w);
+      // Drains the step timings, nothing shows them here
+      profiler_system.run_now(&w);
+    }
+    thread::sleep(time::Duration::from_secs_f64(accumulator.time_to_next_step()));
+  }
+}
+
+fn is_overlay_shown(w: &World, overlay: Overlay) -> bool {
+  match overlay {
+    Overlay::Profiler => w.read_resource::<FrameProfile>().is_visible,
+    Overlay::ShaderParams => w.read_resource::<ShaderParams>().is_visible,
+    Overlay::Console => w.read_resource::<Console>().is_visible,
+    Overlay::Inspector => w.read_resource::<Inspector>().is_visible,
+  }
 }
 
 fn dispatch_loop<W, D, F>(window: &mut W,
-                          w: &mut World)
+                          w: &mut World,
+                          mut crash_recorder: CrashRecorder,
+                          mut startup: StartupProfile)
   where W: Window<D, F>,
         D: gfx::Device + 'static,
         F: gfx::Factory<D::Resources>,
         D::CommandBuffer: Send {
   let (mut device_renderer, encoder_queue) = DeviceRenderer::new(window.create_buffers(2));
-  let draw = {
+  let (profiler_system, profiler_control) = ProfilerSystem::new();
+  let mut shader_reloader = ShaderReloader::new();
+  let mut asset_reloader = AssetReloader::new();
+
+  let audio_system = AudioSystem::new(w.write_resource::<GameEvents>().register_reader());
+  startup.mark("audio");
+  let audio_director = AudioDirectorSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let damage_indicator_system = DamageIndicatorSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let banner_system = BannerSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let toast_system = ToastSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let combat_log_system = CombatLogSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let (mut simulation, simulation_controls) = build_simulation(w, ZoomSettings::load(&config_path()), &profiler_control);
+  startup.mark("simulation");
+  let SimulationControls { terrain_control, character_controls, mouse_control, shader_param_control } = simulation_controls;
+  let second_character_control = character_controls[1].clone();
+  let (player_control, keyboard) = channel::unbounded();
+  let (player_mouse, mouse) = channel::unbounded();
+  let mut net = NetSession::new(&window.get_net_mode(), character_controls, mouse_control, keyboard, mouse);
+  w.insert(net.mode());
+  // A session started from the command line waits for its players first
+  if net.mode() != NetMode::Offline {
+    w.write_resource::<Lobby>().reset(net.mode().is_host());
+    w.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Lobby));
+  }
+  let mut browser = SessionBrowser::new();
+  let (menu_system, menu_control) = MenuSystem::new(w.write_resource::<GameEvents>().register_reader());
+  let mut autosave_reader = w.write_resource::<GameEvents>().register_reader();
+  let (console_system, console_control) = ConsoleSystem::new();
+  let (chat_system, chat_control) = ChatSystem::new();
+  let (inspector_system, inspector_control) = InspectorSystem::new();
+  let controls = TilemapControls::new(ControlSenders {
+    terrain_control,
+    character_control: player_control,
+    second_character_control,
+    mouse_control: player_mouse,
+    profiler_control: profiler_control.clone(),
+    shader_param_control,
+    menu_control,
+    console_control,
+    chat_control,
+    inspector_control,
+  });
+
+  let mut interface = DispatcherBuilder::new()
+    .with(menu_system, "menu-system", &[])
+    .with(console_system, "console-system", &[])
+    .with(chat_system, "chat-system", &[])
+    .with(inspector_system, "inspector-system", &[])
+    .with(LeaderboardSystem, "leaderboard-system", &[])
+    .build();
+
+  window.set_controls(controls);
+
+  // The textures are decoded off the render thread, a loading screen is shown until all are uploaded
+  let mut assets = Assets::load();
+  {
     let rtv = window.get_render_target_view();
     let dsv = window.get_depth_stencil_view();
-    DrawSystem::new(window.get_factory(), &rtv, &dsv, encoder_queue)
+    let mut loading_screen = LoadingScreen::new(window.get_factory(), &rtv, &dsv);
+    while !assets.is_loaded() {
+      assets.poll(window.get_factory());
+      loading_screen.draw(assets.progress(), &w.read_resource::<Dimensions>(), &w.read_resource::<Localization>(), &encoder_queue);
+      device_renderer.draw(window.get_device());
+      window.swap_window();
+      if let WindowStatus::Close = window.poll_events() {
+        return;
+      }
+    }
+  }
+  startup.mark("textures");
+  let (draw, overlay_queue, overlay_targets) = {
+    let rtv = window.get_render_target_view();
+    let dsv = window.get_depth_stencil_view();
+    let (draw, overlay_queue) = DrawSystem::new(window.get_factory(), &rtv, &dsv, &mut shader_reloader, &assets, encoder_queue, profiler_control.clone());
+    (draw, overlay_queue, RenderTargets::new(window.get_factory(), rtv, dsv))
   };
+  let mut hidden_overlays = Overlay::ALL.to_vec();
 
-  let (audio_system, audio_control) = AudioSystem::new();
-  let (terrain_system, terrain_control) = CameraControlSystem::new();
-  let (character_system, character_control) = CharacterControlSystem::new();
-  let (mouse_system, mouse_control) = MouseControlSystem::new();
-  let controls = TilemapControls::new(audio_control, terrain_control, character_control, mouse_control);
-
-  let mut dispatcher = DispatcherBuilder::new()
-    .with(draw, "drawing", &[])
-    .with(terrain::PreDrawSystem, "draw-prep-terrain", &["drawing"])
-    .with(character::PreDrawSystem, "draw-prep-character", &["drawing"])
-    .with(zombie::PreDrawSystem, "draw-prep-zombie", &["drawing"])
-    .with(bullet::PreDrawSystem, "draw-prep-bullet", &["drawing"])
-    .with(hud::PreDrawSystem, "draw-prep-hud", &[])
-    .with(terrain_system, "terrain-system", &[])
-    .with(terrain_object::PreDrawSystem, "draw-prep-terrain_object", &["terrain-system"])
-    .with(terrain_shape::PreDrawSystem, "draw-prep-terrain_shape_object", &["terrain-system"])
-    .with(character_system, "character-system", &[])
-    .with(mouse_system, "mouse-system", &[])
-    .with(audio_system, "audio-system", &[])
-    .with(CollisionSystem, "collision-system", &["mouse-system"])
+  let p = &profiler_control;
+  let mut render = DispatcherBuilder::new()
+    .with(StatusBarSystem, "hud-status-bars", &[])
+    .with(AmmoPanelSystem, "hud-ammo-panel", &[])
+    .with(damage_indicator_system, "hud-damage-indicator", &[])
+    .with(CrosshairSystem, "hud-crosshair", &[])
+    .with(hud::VersionLabelSystem, "hud-version-label", &[])
+    .with(ScorePanelSystem, "hud-score-panel", &[])
+    .with(TooltipSystem, "hud-tooltips", &[])
+    .with(NameplateSystem, "hud-nameplates", &[])
+    .with(ObjectiveArrowSystem, "hud-objective-arrow", &[])
+    .with(banner_system, "hud-banner", &[])
+    .with(toast_system, "hud-toasts", &[])
+    .with(combat_log_system, "hud-combat-log", &[])
+    .with(ChatPanelSystem, "hud-chat", &[])
+    .with(FrameGraphSystem, "hud-frame-graph", &[])
+    .with(PauseOverlaySystem, "hud-pause-overlay", &["hud-status-bars", "hud-ammo-panel", "hud-damage-indicator", "hud-crosshair",
+      "hud-version-label", "hud-score-panel", "hud-tooltips", "hud-nameplates", "hud-objective-arrow", "hud-banner", "hud-toasts", "hud-combat-log",
+      "hud-chat", "hud-frame-graph"])
+    .with(InventoryPanelSystem, "hud-inventory", &["hud-pause-overlay"])
+    .with(MenuPanelSystem, "hud-menu", &["hud-inventory"])
+    .with(DebugShapesSystem::default(), "debug-shapes", &[])
+    .with(draw, "drawing", &["hud-menu", "debug-shapes"])
+    .with(profiler_system, "profiler-system", &["drawing"])
+    .with(GovernorSystem, "frame-governor", &["profiler-system"])
+    .with(audio_director, "audio-director", &[])
+    .with(Profiled::new(audio_system, "audio-system", p), "audio-system", &["audio-director"])
     .build();
+  startup.mark("pipelines");
+  let mut startup = Some(startup);
 
-  window.set_controls(controls);
-
-  let start_time = time::Instant::now();
   let mut last_time = time::Instant::now();
-  loop {
+  let mut accumulator = StepAccumulator::default();
+  let mut simulated_time = 0.0;
+  'game: loop {
     let elapsed = last_time.elapsed();
     let delta = f64::from(elapsed.subsec_nanos()) / 1e9 + elapsed.as_secs() as f64;
-    // Throttle update speed
+    // Throttle render speed
     if delta >= 0.0083 {
       last_time = time::Instant::now();
-      dispatcher.dispatch(&w);
-      w.maintain();
+      let _span = tracing::trace_span!("frame").entered();
+
+      // A paused playback runs no recorded frames and a fast forwarded one several per drawn frame
+      for _ in 0..window.replay_frames() {
+        let frame_input = window.begin_frame();
+        if let FrameInput::Finished = frame_input {
+          info!("Replay finished after {} simulation steps", (simulated_time / SIMULATION_STEP).round());
+          break 'game;
+        }
 
-      *w.write_resource::<DeltaTime>() = DeltaTime(delta);
-      *w.write_resource::<GameTime>() = GameTime(start_time.elapsed().as_secs());
+        window.update_bindings(&mut w.write_resource::<Rebinding>());
+        interface.dispatch(w);
+        state::apply_transitions(w);
+        let game_state = w.read_resource::<GameStates>().current();
+        if w.read_resource::<GameStates>().is_quitting {
+          break 'game;
+        }
+        if std::mem::take(&mut w.write_resource::<GameStates>().is_restarting) {
+          restart_run(w);
+          accumulator.reset();
+        }
+        let save_slot = w.write_resource::<GameStates>().save_slot.take();
+        if let Some(slot) = save_slot {
+          SaveGame::capture(w).save(&SaveFile::Slot(slot).path());
+          w.write_resource::<GameEvents>().single_write(GameEvent::Toast("toast.saved"));
+        }
+        let load_slot = w.write_resource::<GameStates>().load_slot.take();
+        if let Some(file) = load_slot {
+          if let Some(save) = SaveGame::load(&file.path()) {
+            load_run(w, &save);
+            // The loaded wave already has its save
+            w.read_resource::<GameEvents>().read(&mut autosave_reader).count();
+            w.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Playing));
+            accumulator.reset();
+          }
+        }
+        let profile = w.write_resource::<GameStates>().profile.take();
+        if let Some(idx) = profile {
+          switch_profile(w, idx);
+        }
+        let net_mode = w.write_resource::<GameStates>().net.take();
+        if let Some(mode) = net_mode {
+          net.switch(&mode);
+          w.insert(net.mode());
+          w.write_resource::<Lobby>().reset(net.mode().is_host());
+          if net.mode() != mode {
+            w.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Menu));
+          }
+        }
+        if game_state == GameState::Browser {
+          browser.receive(&mut w.write_resource::<Lobby>());
+        } else {
+          browser.close();
+        }
+        net.receive(w);
+        w.insert(net.mode());
+        w.insert(net.local_player());
+        window.set_game_state(game_state);
+        window.apply_video_settings(w.read_resource::<Settings>().video);
 
+        // Simulation advances in fixed steps at the pace of the state, long stalls are capped so it can catch up
+        accumulator.add(delta, game_state.time_scale(w.read_resource::<Settings>().difficulty));
+        // A replay runs the recorded steps of the frame, so it doesn't depend on how fast frames are drawn
+        let steps = match frame_input {
+          FrameInput::Replayed(steps) => {
+            accumulator.reset();
+            steps
+          }
+          _ => {
+            // A lockstep peer waiting for a turn keeps the time it owes, up to a capped frame
+            let steps = net.allowed_steps(accumulator.due_steps());
+            // A paused simulation only runs the steps asked for from the inspector
+            let steps = w.write_resource::<Inspector>().allowed_steps(steps);
+            accumulator.take_steps(steps);
+            steps
+          }
+        };
+        for _ in 0..steps {
+          simulated_time = net.step(w).map_or(simulated_time + SIMULATION_STEP, |turn| turn as f64 * SIMULATION_STEP);
+          step(w, &mut simulation, simulated_time);
+        }
+        // A fresh run has nothing worth keeping before its second wave
+        let is_wave_started = w.read_resource::<GameEvents>().read(&mut autosave_reader)
+          .filter(|e| matches!(e, GameEvent::WaveStarted(wave) if *wave > 1))
+          .count() > 0;
+        if is_wave_started && w.read_resource::<Settings>().autosave {
+          save::autosave(w);
+        }
+        net.send(w);
+        window.end_frame(steps);
+      }
+
+      *w.write_resource::<Interpolation>() = accumulator.interpolation();
+      hidden_overlays.retain(|&overlay| {
+        if !is_overlay_shown(w, overlay) {
+          return true;
+        }
+        let system = OverlayDrawSystem::new(window.get_factory(), &overlay_targets, overlay.name(), overlay.side(), &mut shader_reloader);
+        overlay_queue.send((overlay, system)).expect("Overlay queue update error");
+        false
+      });
+      render.dispatch(w);
+      w.maintain();
+      crash_recorder.record(w);
+
+      // gfx_device_gl exposes no timestamp queries, so GPU cost is measured as flush + swap wall time
+      let gpu_start = time::Instant::now();
       device_renderer.draw(window.get_device());
 
       window.swap_window();
+      profiler_control.send(ProfilerEvent::Timing("gpu-flush-swap", gpu_start.elapsed()))
+        .expect("Profiler timing update error");
+      if let Some(mut startup) = startup.take() {
+        startup.mark("first-frame");
+        info!("{}", startup.summary());
+      }
+
+      shader_reloader.reload(window.get_factory());
+      if cfg!(debug_assertions) {
+        for asset in asset_reloader.changed() {
+          hot_reload::reload(w, asset);
+        }
+      }
     }
 
     if let WindowStatus::Close = window.poll_events() {
       break;
     }
   }
+  window.save_recording();
+  w.read_resource::<Stats>().save(&stats_path());
 }
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glutin::VirtualKeyCode as Key;
+use json::JsonValue;
+use tracing::warn;
+
+use crate::game::constants::{PRESET_DIR, USER_PRESET_DIR};
+use crate::game::localization::Localization;
+use crate::game::profiles::user_file;
+use crate::game::settings::{load_config, save_config_section};
+
+const BINDINGS_SECTION: &str = "bindings";
+
+// What a key does, the window maps raw key events to these before anything reaches the controls
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+  MoveUp,
+  MoveDown,
+  MoveLeft,
+  MoveRight,
+  SecondMoveUp,
+  SecondMoveDown,
+  SecondMoveLeft,
+  SecondMoveRight,
+  Aim,
+  Reload,
+  Inventory,
+  QuickSave,
+  QuickLoad,
+  ZoomIn,
+  ZoomOut,
+  Pause,
+  Confirm,
+  ToggleProfiler,
+  ToggleFrameStats,
+  ToggleDebugShapes,
+  ToggleInspector,
+  InspectNextEntity,
+  InspectPreviousEntity,
+  InspectNextField,
+  IncreaseInspected,
+  DecreaseInspected,
+  PauseSimulation,
+  StepSimulation,
+  ToggleShaderParams,
+  NextShaderParam,
+  IncreaseShaderParam,
+  DecreaseShaderParam,
+  ToggleConsole,
+  Chat,
+}
+
+// Config file name and default keys of every action
+const ACTIONS: &[(Action, &str, &[Key])] = &[
+  (Action::MoveUp, "move_up", &[Key::W]),
+  (Action::MoveDown, "move_down", &[Key::S]),
+  (Action::MoveLeft, "move_left", &[Key::A]),
+  (Action::MoveRight, "move_right", &[Key::D]),
+  (Action::SecondMoveUp, "second_move_up", &[Key::Up]),
+  (Action::SecondMoveDown, "second_move_down", &[Key::Down]),
+  (Action::SecondMoveLeft, "second_move_left", &[Key::Left]),
+  (Action::SecondMoveRight, "second_move_right", &[Key::Right]),
+  (Action::Aim, "aim", &[Key::LControl, Key::RControl]),
+  (Action::Reload, "reload", &[Key::R]),
+  (Action::Inventory, "inventory", &[Key::Tab]),
+  (Action::QuickSave, "quick_save", &[Key::F5]),
+  (Action::QuickLoad, "quick_load", &[Key::F9]),
+  (Action::ZoomIn, "zoom_in", &[Key::Z]),
+  (Action::ZoomOut, "zoom_out", &[Key::X]),
+  (Action::Pause, "pause", &[Key::Escape]),
+  (Action::Confirm, "confirm", &[Key::Return, Key::Space]),
+  (Action::ToggleProfiler, "toggle_profiler", &[Key::F3]),
+  (Action::ToggleFrameStats, "toggle_frame_stats", &[Key::F2]),
+  (Action::ToggleDebugShapes, "toggle_debug_shapes", &[Key::F6]),
+  (Action::ToggleInspector, "toggle_inspector", &[Key::F7]),
+  (Action::InspectNextEntity, "inspect_next_entity", &[Key::PageDown]),
+  (Action::InspectPreviousEntity, "inspect_previous_entity", &[Key::PageUp]),
+  (Action::InspectNextField, "inspect_next_field", &[Key::End]),
+  (Action::IncreaseInspected, "increase_inspected", &[Key::Equals]),
+  (Action::DecreaseInspected, "decrease_inspected", &[Key::Minus]),
+  (Action::PauseSimulation, "pause_simulation", &[Key::F8]),
+  (Action::StepSimulation, "step_simulation", &[Key::Period]),
+  (Action::ToggleShaderParams, "toggle_shader_params", &[Key::F4]),
+  (Action::NextShaderParam, "next_shader_param", &[Key::Backslash]),
+  (Action::IncreaseShaderParam, "increase_shader_param", &[Key::RBracket]),
+  (Action::DecreaseShaderParam, "decrease_shader_param", &[Key::LBracket]),
+  (Action::ToggleConsole, "toggle_console", &[Key::Grave]),
+  (Action::Chat, "chat", &[Key::T]),
+];
+
+// Keys that can be bound, written to the config file by their variant name
+const KEYS: &[Key] = &[
+  Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9, Key::Key0,
+  Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+  Key::Escape, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+  Key::Insert, Key::Home, Key::Delete, Key::End, Key::PageDown, Key::PageUp, Key::Left, Key::Up, Key::Right, Key::Down, Key::Back, Key::Return, Key::Space,
+  Key::Numpad0, Key::Numpad1, Key::Numpad2, Key::Numpad3, Key::Numpad4, Key::Numpad5, Key::Numpad6, Key::Numpad7, Key::Numpad8, Key::Numpad9,
+  Key::Apostrophe, Key::Backslash, Key::Comma, Key::Equals, Key::Grave, Key::LAlt, Key::LBracket, Key::LControl, Key::LShift,
+  Key::Minus, Key::Period, Key::RAlt, Key::RBracket, Key::RControl, Key::RShift, Key::Semicolon, Key::Slash, Key::Tab,
+];
+
+pub fn action_name(action: Action) -> &'static str {
+  ACTIONS.iter().find(|&&(a, _, _)| a == action).map_or("", |&(_, name, _)| name)
+}
+
+pub fn action_from_name(name: &str) -> Option<Action> {
+  ACTIONS.iter().find(|&&(_, n, _)| n == name).map(|&(action, _, _)| action)
+}
+
+fn key_name(key: Key) -> String {
+  format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+  KEYS.iter().find(|&&key| key_name(key) == name).cloned()
+}
+
+pub struct InputMap {
+  bindings: HashMap<Key, Action>,
+}
+
+impl InputMap {
+  // The defaults are written out on the first start, so there is a bindings section to edit
+  pub fn load(filename: &str) -> InputMap {
+    let config = load_config(filename);
+    let input_map = InputMap::from_json(&config[BINDINGS_SECTION]);
+    if !config.has_key(BINDINGS_SECTION) {
+      input_map.save(filename);
+    }
+    input_map
+  }
+
+  pub fn save(&self, filename: &str) {
+    save_config_section(filename, BINDINGS_SECTION, self.to_json());
+  }
+
+  // Actions missing from the section keep their default keys, a configured key wins over a default one
+  pub fn from_json(value: &JsonValue) -> InputMap {
+    let (configured, defaults): (Vec<_>, Vec<_>) = ACTIONS.iter()
+      .partition(|&&(_, name, _)| value.has_key(name));
+
+    let mut bindings = HashMap::new();
+    for &(action, _, keys) in defaults {
+      bindings.extend(keys.iter().map(|&key| (key, action)));
+    }
+    for &(action, name, _) in configured {
+      for key_name in value[name].members().map(|key| key.as_str().unwrap_or_default()) {
+        match key_from_name(key_name) {
+          Some(key) => {
+            bindings.insert(key, action);
+          }
+          None => warn!("Unknown key {} bound to {}", key_name, name),
+        }
+      }
+    }
+    InputMap { bindings }
+  }
+
+  pub fn to_json(&self) -> JsonValue {
+    let mut value = JsonValue::new_object();
+    for &(action, name, _) in ACTIONS {
+      let mut keys = self.bindings.iter()
+        .filter(|&(_, &bound)| bound == action)
+        .map(|(&key, _)| key)
+        .collect::<Vec<_>>();
+      keys.sort();
+      value[name] = keys.into_iter().map(key_name).collect::<Vec<_>>().into();
+    }
+    value
+  }
+
+  pub fn action(&self, key: Key) -> Option<Action> {
+    self.bindings.get(&key).cloned()
+  }
+
+  // The key replaces the keys of the action and is taken from the action it had
+  pub fn bind(&mut self, key: Key, action: Action) {
+    self.bindings.retain(|_, &mut bound| bound != action);
+    self.bindings.insert(key, action);
+  }
+
+  // A preset replaces the bindings section, actions it leaves out go back to their default keys
+  pub fn import(filename: &Path) -> Result<(String, InputMap), Vec<PresetError>> {
+    let value = fs::read_to_string(filename)
+      .map_err(|e| e.to_string())
+      .and_then(|contents| json::parse(&contents).map_err(|e| e.to_string()))
+      .map_err(|e| vec![PresetError::Unreadable(e)])?;
+    let bindings = &value[BINDINGS_SECTION];
+    let errors = preset_errors(bindings);
+    if !errors.is_empty() {
+      return Err(errors);
+    }
+    Ok((preset_name(filename, &value), InputMap::from_json(bindings)))
+  }
+
+  pub fn export(&self, filename: &Path, name: &str) -> Result<(), String> {
+    if let Some(dir) = filename.parent() {
+      fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let value = object! {
+      "name" => name,
+      BINDINGS_SECTION => self.to_json()
+    };
+    fs::write(filename, value.pretty(2)).map_err(|e| e.to_string())
+  }
+
+  pub fn key_names(&self, action: Action) -> String {
+    let mut keys = self.bindings.iter()
+      .filter(|&(_, &bound)| bound == action)
+      .map(|(&key, _)| key)
+      .collect::<Vec<_>>();
+    keys.sort();
+    keys.into_iter().map(key_name).collect::<Vec<_>>().join(", ")
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresetError {
+  Unreadable(String),
+  UnknownAction(String),
+  UnknownKey(String),
+  // The key and the two actions it would be bound to
+  Conflict(String, Action, Action),
+}
+
+impl PresetError {
+  pub fn text(&self, strings: &Localization) -> String {
+    let action = |action: Action| strings.get(&format!("action.{}", action_name(action))).to_string();
+    match self {
+      PresetError::Unreadable(e) => strings.format("preset.unreadable", &[("error", e.clone())]),
+      PresetError::UnknownAction(name) => strings.format("preset.unknown_action", &[("action", name.clone())]),
+      PresetError::UnknownKey(name) => strings.format("preset.unknown_key", &[("key", name.clone())]),
+      PresetError::Conflict(key, first, second) => strings.format("preset.conflict",
+        &[("key", key.clone()), ("first", action(*first)), ("second", action(*second))]),
+    }
+  }
+}
+
+// Checks a bindings section against the actions of this version. A key can do one thing only, so
+// it can't be listed for two actions or taken from the default keys of an action left out.
+pub fn preset_errors(value: &JsonValue) -> Vec<PresetError> {
+  if !value.is_object() {
+    return vec![PresetError::Unreadable(format!("no {} section", BINDINGS_SECTION))];
+  }
+  let mut errors = Vec::new();
+  let mut bound = HashMap::new();
+  for (name, keys) in value.entries() {
+    let action = match action_from_name(name) {
+      Some(action) => action,
+      None => {
+        errors.push(PresetError::UnknownAction(name.to_string()));
+        continue;
+      }
+    };
+    for name in keys.members().map(|key| key.as_str().unwrap_or_default()) {
+      match key_from_name(name) {
+        Some(key) => match bound.insert(key, action) {
+          Some(other) if other != action => errors.push(PresetError::Conflict(name.to_string(), other, action)),
+          _ => (),
+        },
+        None => errors.push(PresetError::UnknownKey(name.to_string())),
+      }
+    }
+  }
+  for &(action, _, keys) in ACTIONS.iter().filter(|&&(_, name, _)| !value.has_key(name)) {
+    for &key in keys {
+      if let Some(&other) = bound.get(&key) {
+        errors.push(PresetError::Conflict(key_name(key), action, other));
+      }
+    }
+  }
+  errors
+}
+
+fn preset_name(filename: &Path, value: &JsonValue) -> String {
+  value["name"].as_str()
+    .map(String::from)
+    .or_else(|| filename.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+    .unwrap_or_default()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+  pub name: String,
+  pub path: PathBuf,
+}
+
+// Where exports go, shared presets are dropped in the same folder of the profile
+pub fn user_preset_dir() -> PathBuf {
+  user_file(USER_PRESET_DIR)
+}
+
+// The presets that come with the game and then the ones of the active profile, by file name
+pub fn presets() -> Vec<Preset> {
+  [PathBuf::from(PRESET_DIR), user_preset_dir()].iter()
+    .flat_map(|dir| {
+      let mut paths = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect::<Vec<_>>())
+        .unwrap_or_default();
+      paths.retain(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"));
+      paths.sort();
+      paths
+    })
+    .map(|path| {
+      let value = fs::read_to_string(&path).ok().and_then(|contents| json::parse(&contents).ok()).unwrap_or(JsonValue::Null);
+      Preset { name: preset_name(&path, &value), path }
+    })
+    .collect()
+}
+
+// What the window did with the last preset request, shown on the controls page
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresetResult {
+  Imported(String),
+  Exported(PathBuf),
+  Failed(Vec<PresetError>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PresetRequest {
+  Import(PathBuf),
+  Export,
+}
+
+// Actions that can be rebound from the controls menu
+pub const REBINDABLE_ACTIONS: &[Action] = &[Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+  Action::Aim, Action::Reload, Action::Inventory, Action::ZoomIn, Action::ZoomOut, Action::Chat];
+
+// Shared between the controls menu and the window, which owns the input map. While an action is
+// waiting the next key press is taken for it instead of being handled, Escape cancels.
+#[derive(Default)]
+pub struct Rebinding {
+  pub waiting: Option<Action>,
+  pub key_names: Vec<(Action, String)>,
+  pub preset: Option<PresetRequest>,
+  pub preset_result: Option<PresetResult>,
+}
+
+impl Rebinding {
+  pub fn key_name(&self, action: Action) -> &str {
+    self.key_names.iter().find(|(a, _)| *a == action).map_or("", |(_, name)| name)
+  }
+}
+
+// Who receives input, ordered by priority. The highest open context gets the events and
+// captures them, nothing reaches the contexts below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InputContext {
+  Gameplay,
+  Menu,
+  Chat,
+  Console,
+  TextEntry,
+}
+
+impl InputContext {
+  fn handles(self, action: Action) -> bool {
+    match self {
+      InputContext::Gameplay => true,
+      InputContext::Menu => matches!(action, Action::MoveUp | Action::MoveDown | Action::MoveLeft | Action::MoveRight |
+        Action::SecondMoveUp | Action::SecondMoveDown | Action::SecondMoveLeft | Action::SecondMoveRight |
+        Action::Confirm | Action::Pause | Action::Inventory | Action::ToggleConsole),
+      // Typed keys arrive as characters, only the keys that close the chat and the console are actions
+      InputContext::Chat | InputContext::Console | InputContext::TextEntry => matches!(action, Action::Pause | Action::ToggleConsole),
+    }
+  }
+}
+
+pub struct InputContexts {
+  stack: Vec<InputContext>,
+}
+
+impl InputContexts {
+  pub fn new() -> InputContexts {
+    InputContexts {
+      stack: vec![InputContext::Gameplay],
+    }
+  }
+
+  pub fn active(&self) -> InputContext {
+    *self.stack.last().expect("Input context stack is empty")
+  }
+
+  pub fn contains(&self, context: InputContext) -> bool {
+    self.stack.contains(&context)
+  }
+
+  // A context opened under a higher one, like a menu under the console, waits there until that one closes
+  pub fn push(&mut self, context: InputContext) {
+    if !self.contains(context) {
+      self.stack.push(context);
+      self.stack.sort();
+    }
+  }
+
+  pub fn remove(&mut self, context: InputContext) {
+    if context != InputContext::Gameplay {
+      self.stack.retain(|&c| c != context);
+    }
+  }
+
+  pub fn route(&self, action: Action) -> Option<InputContext> {
+    let active = self.active();
+    if active.handles(action) { Some(active) } else { None }
+  }
+}
+
+impl Default for InputContexts {
+  fn default() -> InputContexts {
+    InputContexts::new()
+  }
+}
@@ -0,0 +1,63 @@
+use gfx;
+
+use crate::game::localization::Localization;
+use crate::gfx_app::{ColorFormat, DepthFormat};
+use crate::gfx_app::renderer::EncoderQueue;
+use crate::graphics::dimensions::Dimensions;
+use crate::graphics::render_graph::{RenderTargets, ShaderReloader};
+use crate::hud::text::{TextAlign, TextDrawSystem, UiText};
+use crate::hud::ui::{UiDrawSystem, UiQuad};
+
+const BAR_WIDTH: f32 = 320.0;
+const BAR_HEIGHT: f32 = 12.0;
+const TITLE_SIZE: f32 = 32.0;
+const BAR_COLOR: [f32; 4] = [0.25, 0.25, 0.28, 1.0];
+const FILL_COLOR: [f32; 4] = [0.85, 0.85, 0.8, 1.0];
+const TITLE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// A progress bar in the middle of the window, its fill follows the share of the assets loaded
+pub fn loading_bar(progress: f32, dim: &Dimensions) -> Vec<UiQuad> {
+  let x = (dim.window_width - BAR_WIDTH) / 2.0;
+  let y = (dim.window_height - BAR_HEIGHT) / 2.0;
+  vec![
+    UiQuad::new(x, y, BAR_WIDTH, BAR_HEIGHT, BAR_COLOR),
+    UiQuad::new(x, y, BAR_WIDTH * progress.clamp(0.0, 1.0), BAR_HEIGHT, FILL_COLOR),
+  ]
+}
+
+// Drawn until the textures are in. It only needs the font, which comes with the binary.
+pub struct LoadingScreen<R: gfx::Resources> {
+  targets: RenderTargets<R>,
+  ui_system: UiDrawSystem<R>,
+  text_system: TextDrawSystem<R>,
+}
+
+impl<R: gfx::Resources> LoadingScreen<R> {
+  pub fn new<F>(factory: &mut F,
+                rtv: &gfx::handle::RenderTargetView<R, ColorFormat>,
+                dsv: &gfx::handle::DepthStencilView<R, DepthFormat>) -> LoadingScreen<R>
+    where F: gfx::Factory<R> {
+    // Gone before a shader could change, its passes aren't reloaded
+    let mut reloader = ShaderReloader::new();
+    let targets = RenderTargets::new(factory, rtv.clone(), dsv.clone());
+    LoadingScreen {
+      ui_system: UiDrawSystem::new(factory, &targets, &mut reloader),
+      text_system: TextDrawSystem::new(factory, &targets, &mut reloader),
+      targets,
+    }
+  }
+
+  pub fn draw<D>(&mut self, progress: f32, dim: &Dimensions, strings: &Localization, encoder_queue: &EncoderQueue<D>)
+    where D: gfx::Device<Resources=R> {
+    let mut encoder = encoder_queue.receiver.recv().expect("Encoder error");
+    encoder.clear(&self.targets.color, [16.0 / 256.0, 16.0 / 256.0, 20.0 / 256.0, 1.0]);
+    encoder.clear_depth(&self.targets.depth, 1.0);
+    self.ui_system.draw(&loading_bar(progress, dim), dim, &mut encoder);
+    let title = UiText {
+      align: TextAlign::Center,
+      ..UiText::new(strings.get("loading.title"), dim.window_width / 2.0, dim.window_height / 2.0 - BAR_HEIGHT - TITLE_SIZE * 1.5, TITLE_SIZE, TITLE_COLOR)
+    };
+    self.text_system.draw(&[title], dim, &mut encoder);
+    encoder_queue.sender.send(encoder).expect("Encoder queue update error");
+  }
+}
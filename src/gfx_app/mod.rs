@@ -1,24 +1,42 @@
+use cgmath::Point2;
 use gfx;
 use gfx::format::SurfaceType;
 use gfx::handle::{DepthStencilView, RenderTargetView};
 use gfx::memory::Typed;
 use gfx_device_gl;
 use glutin;
-use glutin::{KeyboardInput, MouseButton, PossiblyCurrent, WindowedContext};
+use glutin::{MouseButton, MouseScrollDelta, PossiblyCurrent, VirtualKeyCode, WindowedContext};
 use glutin::dpi::LogicalSize;
 use glutin::ElementState::{Pressed, Released};
-use glutin::VirtualKeyCode::{A, D, Escape, R, S, W, X, Z};
+use tracing::info;
 use std::fmt::{Display, Formatter, Result};
 
 use crate::character::controls::CharacterControl;
-use crate::game::constants::{GAME_TITLE, RESOLUTION_X, RESOLUTION_Y};
+use crate::game::chat::ChatControl;
+use crate::game::console::ConsoleControl;
+use crate::game::inspector::InspectorControl;
+use crate::game::constants::{GAME_TITLE, ZOOM_PIXELS_PER_STEP};
+use crate::game::profiles::Profiles;
+use crate::game::settings::{config_path, Settings, VideoSettings};
+use crate::game::state::{GameState, MenuControl};
 use crate::gfx_app::controls::{Control, TilemapControls};
+use crate::gfx_app::input::{Action, InputContext, InputMap, PresetError, PresetRequest, PresetResult, REBINDABLE_ACTIONS, Rebinding, user_preset_dir};
+use crate::gfx_app::replay::{FrameInput, InputEvent, InputReplay, ReplayMode};
+use crate::graphics::dimensions::ProjectionMode;
+use crate::graphics::shader_params::ShaderParamControl;
+use crate::net::NetMode;
 
+pub mod headless;
 pub mod init;
 pub mod renderer;
 pub mod system;
 pub mod controls;
+pub mod input;
+pub mod loading;
 pub mod mouse_controls;
+pub mod replay;
+#[cfg(feature = "touch")]
+pub mod touch;
 
 pub type ColorFormat = gfx::format::Rgba8;
 pub type DepthFormat = gfx::format::DepthStencil;
@@ -29,18 +47,29 @@ pub const DEPTH_FORMAT_VALUE: SurfaceType = SurfaceType::D24_S8;
 #[derive(Debug)]
 pub struct GameOptions {
   windowed_mode: bool,
+  orthographic: bool,
+  split_screen: bool,
+  seed: Option<u64>,
+  replay: ReplayMode,
+  net: NetMode,
 }
 
 impl Display for GameOptions {
   fn fmt(&self, f: &mut Formatter) -> Result {
-    write!(f, "{}", format!("windowed_mode={}", self.windowed_mode))
+    write!(f, "windowed_mode={}, orthographic={}, split_screen={}, seed={:?}, replay={:?}, net={:?}",
+           self.windowed_mode, self.orthographic, self.split_screen, self.seed, self.replay, self.net)
   }
 }
 
 impl GameOptions {
-  pub fn new(windowed_mode: bool) -> GameOptions {
+  pub fn new(windowed_mode: bool, orthographic: bool, split_screen: bool, seed: Option<u64>, replay: ReplayMode, net: NetMode) -> GameOptions {
     GameOptions {
       windowed_mode,
+      orthographic,
+      split_screen,
+      seed,
+      replay,
+      net,
     }
   }
 }
@@ -54,20 +83,41 @@ pub struct WindowContext {
   render_target_view: RenderTargetView<gfx_device_gl::Resources, ColorFormat>,
   depth_stencil_view: DepthStencilView<gfx_device_gl::Resources, DepthFormat>,
   mouse_pos: (f64, f64),
+  input_map: InputMap,
+  is_fullscreen: bool,
+  resolution: (u32, u32),
+  awaiting_key: bool,
+  captured_key: Option<VirtualKeyCode>,
+  is_cursor_hidden: bool,
+  replay: InputReplay,
+  #[cfg(feature = "touch")]
+  touch: touch::TouchControls,
   game_options: GameOptions
 }
 
 impl WindowContext {
-  pub fn new(game_options: GameOptions) -> WindowContext {
+  pub fn new(mut game_options: GameOptions) -> WindowContext {
     let events_loop = glutin::EventsLoop::new();
 
     let window_title = glutin::WindowBuilder::new()
       .with_title(GAME_TITLE);
 
-    println!("{}", game_options);
+    // The -w flag wins over the fullscreen setting and --seed over the seed of the config
+    let settings = Settings::load(&config_path());
+    let video = settings.video;
+    game_options.windowed_mode = game_options.windowed_mode || !video.fullscreen;
+    // Every game is seeded, so a recording without a seed given is replayable too
+    game_options.seed = Some(game_options.seed.or(settings.seed).unwrap_or_else(rand::random));
+    info!("{}", game_options);
+
+    // A recording brings its own seed and player count
+    let replay_mode = std::mem::replace(&mut game_options.replay, ReplayMode::Off);
+    let replay = InputReplay::new(replay_mode, game_options.seed, game_options.split_screen);
+    game_options.seed = replay.seed;
+    game_options.split_screen = replay.split_screen;
 
     let builder = if game_options.windowed_mode {
-      let logical_size = LogicalSize::new(RESOLUTION_X.into(), RESOLUTION_Y.into());
+      let logical_size = LogicalSize::new(video.resolution.0.into(), video.resolution.1.into());
       window_title
         .with_dimensions(logical_size)
         .with_decorations(false)
@@ -86,7 +136,7 @@ impl WindowContext {
     };
 
     let window_context = glutin::ContextBuilder::new()
-      .with_vsync(true)
+      .with_vsync(video.vsync)
       .with_double_buffer(Some(true))
       .with_pixel_format(24, 8)
       .with_srgb(true)
@@ -128,6 +178,15 @@ impl WindowContext {
       render_target_view: RenderTargetView::new(rtv),
       depth_stencil_view: DepthStencilView::new(dsv),
       mouse_pos: (0.0, 0.0),
+      input_map: InputMap::load(&config_path()),
+      is_fullscreen: !game_options.windowed_mode,
+      resolution: video.resolution,
+      awaiting_key: false,
+      captured_key: None,
+      is_cursor_hidden: false,
+      replay,
+      #[cfg(feature = "touch")]
+      touch: touch::TouchControls::new(),
       game_options,
     }
   }
@@ -143,6 +202,9 @@ pub trait Window<D: gfx::Device, F: gfx::Factory<D::Resources>> {
   fn swap_window(&mut self);
   fn create_buffers(&mut self, count: usize) -> Vec<D::CommandBuffer>;
   fn set_controls(&mut self, controls: controls::TilemapControls);
+  fn set_game_state(&mut self, state: GameState);
+  fn apply_video_settings(&mut self, video: VideoSettings);
+  fn update_bindings(&mut self, rebinding: &mut Rebinding);
   fn get_viewport_size(&mut self) -> (f32, f32);
   fn get_device(&mut self) -> &mut D;
   fn get_factory(&mut self) -> &mut F;
@@ -150,7 +212,15 @@ pub trait Window<D: gfx::Device, F: gfx::Factory<D::Resources>> {
   fn get_render_target_view(&mut self) -> RenderTargetView<D::Resources, ColorFormat>;
   fn get_depth_stencil_view(&mut self) -> DepthStencilView<D::Resources, DepthFormat>;
   fn poll_events(&mut self) -> WindowStatus;
+  fn begin_frame(&mut self) -> FrameInput;
+  fn end_frame(&mut self, steps: u32);
+  fn replay_frames(&self) -> u32;
+  fn save_recording(&self);
   fn is_windowed(&self) -> bool;
+  fn get_projection_mode(&self) -> ProjectionMode;
+  fn is_split_screen(&self) -> bool;
+  fn get_seed(&self) -> Option<u64>;
+  fn get_net_mode(&self) -> NetMode;
 }
 
 impl Window<gfx_device_gl::Device, gfx_device_gl::Factory> for WindowContext {
@@ -174,9 +244,75 @@ impl Window<gfx_device_gl::Device, gfx_device_gl::Factory> for WindowContext {
     self.controls = Some(controls);
   }
 
+  fn set_game_state(&mut self, state: GameState) {
+    if let Some(ref mut c) = self.controls {
+      c.set_game_state(state);
+    }
+    // The crosshair replaces the cursor while playing
+    if state.is_simulating() != self.is_cursor_hidden {
+      self.is_cursor_hidden = state.is_simulating();
+      self.window_context.window().hide_cursor(self.is_cursor_hidden);
+    }
+  }
+
+  // Switching fullscreen keeps the render targets of the start size, the window is scaled to fit
+  fn apply_video_settings(&mut self, video: VideoSettings) {
+    if video.fullscreen != self.is_fullscreen {
+      self.is_fullscreen = video.fullscreen;
+      let window = self.window_context.window();
+      let monitor = if video.fullscreen { Some(window.get_current_monitor()) } else { None };
+      window.set_fullscreen(monitor);
+    }
+  }
+
+  // Cleared key names ask for the bindings of another profile
+  fn update_bindings(&mut self, rebinding: &mut Rebinding) {
+    let mut is_changed = rebinding.key_names.is_empty();
+    if is_changed {
+      self.input_map = InputMap::load(&config_path());
+    }
+    if let Some(key) = self.captured_key.take() {
+      if let Some(action) = rebinding.waiting.take() {
+        if key != VirtualKeyCode::Escape {
+          self.input_map.bind(key, action);
+          self.input_map.save(&config_path());
+          is_changed = true;
+        }
+      }
+    }
+    match rebinding.preset.take() {
+      Some(PresetRequest::Import(path)) => rebinding.preset_result = Some(match InputMap::import(&path) {
+        Ok((name, input_map)) => {
+          self.input_map = input_map;
+          self.input_map.save(&config_path());
+          is_changed = true;
+          PresetResult::Imported(name)
+        }
+        Err(errors) => PresetResult::Failed(errors),
+      }),
+      // Named after the profile, so a shared file tells whose keys they are
+      Some(PresetRequest::Export) => {
+        let name = Profiles::load().active_name().to_string();
+        let file_name = name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect::<String>();
+        let path = user_preset_dir().join(format!("{}.json", file_name));
+        rebinding.preset_result = Some(match self.input_map.export(&path, &name) {
+          Ok(()) => PresetResult::Exported(path),
+          Err(e) => PresetResult::Failed(vec![PresetError::Unreadable(e)]),
+        });
+      }
+      None => (),
+    }
+    self.awaiting_key = rebinding.waiting.is_some();
+    if is_changed {
+      rebinding.key_names = REBINDABLE_ACTIONS.iter()
+        .map(|&action| (action, self.input_map.key_names(action)))
+        .collect();
+    }
+  }
+
   fn get_viewport_size(&mut self) -> (f32, f32) {
     if self.game_options.windowed_mode {
-      (RESOLUTION_X as f32, RESOLUTION_Y as f32)
+      (self.resolution.0 as f32, self.resolution.1 as f32)
     } else {
       let monitor = self.events_loop.get_available_monitors().nth(0).expect("No monitor found");
       let monitor_resolution = monitor.get_dimensions();
@@ -209,7 +345,7 @@ impl Window<gfx_device_gl::Device, gfx_device_gl::Factory> for WindowContext {
   }
 
   fn poll_events(&mut self) -> WindowStatus {
-    use glutin::WindowEvent::{CursorMoved, CloseRequested, MouseInput};
+    use glutin::WindowEvent::{CursorMoved, CloseRequested, MouseInput, MouseWheel, ReceivedCharacter, Resized};
 
     let controls = match self.controls {
       Some(ref mut c) => c,
@@ -217,22 +353,68 @@ impl Window<gfx_device_gl::Device, gfx_device_gl::Factory> for WindowContext {
     };
 
     let m_pos = &mut self.mouse_pos;
+    let input_map = &self.input_map;
+    let awaiting_key = self.awaiting_key;
+    let captured_key = &mut self.captured_key;
+    let replay = &mut self.replay;
+    #[cfg(feature = "touch")]
+    let touch_controls = &mut self.touch;
+    let window_context = &self.window_context;
     let mut game_status = WindowStatus::Open;
 
     self.events_loop.poll_events(|event| {
       game_status = if let glutin::Event::WindowEvent { event, .. } = event {
         match event {
-          glutin::WindowEvent::KeyboardInput { input, .. } => { process_keyboard_input(input, controls) }
+          // A key being rebound is taken as it is and never reaches the controls
+          glutin::WindowEvent::KeyboardInput { input, .. } if awaiting_key => {
+            if input.state == Pressed && captured_key.is_none() {
+              *captured_key = input.virtual_keycode;
+            }
+            WindowStatus::Open
+          }
+          glutin::WindowEvent::KeyboardInput { input, .. } => {
+            if let Some(action) = input.virtual_keycode.and_then(|key| input_map.action(key)) {
+              dispatch_input(InputEvent::Key(action, input.state), replay, controls);
+            }
+            WindowStatus::Open
+          }
           MouseInput { state: Pressed, button: MouseButton::Left, .. } => {
-            controls.mouse_left_click(Some(*m_pos));
+            dispatch_input(InputEvent::MouseClick(Some(*m_pos)), replay, controls);
             WindowStatus::Open
           }
           MouseInput { state: Released, button: MouseButton::Left, .. } => {
-            controls.mouse_left_click(None);
+            dispatch_input(InputEvent::MouseClick(None), replay, controls);
+            WindowStatus::Open
+          }
+          MouseWheel { delta, .. } => {
+            let lines = match delta {
+              MouseScrollDelta::LineDelta(_, y) => f64::from(y),
+              MouseScrollDelta::PixelDelta(position) => position.y / ZOOM_PIXELS_PER_STEP,
+            };
+            dispatch_input(InputEvent::Zoom(lines), replay, controls);
+            WindowStatus::Open
+          }
+          #[cfg(feature = "touch")]
+          glutin::WindowEvent::Touch(touch) => {
+            let size = window_context.window().get_inner_size().expect("get_inner_size failed");
+            let location = (touch.location.x, touch.location.y);
+            for event in touch_controls.update(touch.id, touch.phase, location, (size.width, size.height)) {
+              dispatch_input(event, replay, controls);
+            }
+            WindowStatus::Open
+          }
+          ReceivedCharacter(c) => {
+            dispatch_input(InputEvent::Character(c), replay, controls);
             WindowStatus::Open
           }
           CursorMoved { position, .. } => {
             *m_pos = ((position.x as f32).into(), (position.y as f32).into());
+            dispatch_input(InputEvent::MouseMove(*m_pos), replay, controls);
+            WindowStatus::Open
+          }
+          Resized(size) => {
+            window_context.resize(size.to_physical(window_context.window().get_hidpi_factor()));
+            controls.window_resized((size.width, size.height));
             WindowStatus::Open
           }
           CloseRequested => WindowStatus::Close,
@@ -245,64 +427,188 @@ impl Window<gfx_device_gl::Device, gfx_device_gl::Factory> for WindowContext {
     game_status
   }
 
+  fn begin_frame(&mut self) -> FrameInput {
+    let (events, frame_input) = self.replay.begin_frame();
+    if let Some(ref mut controls) = self.controls {
+      for event in events {
+        process_input(event, controls);
+      }
+    }
+    frame_input
+  }
+
+  fn end_frame(&mut self, steps: u32) {
+    self.replay.end_frame(steps);
+  }
+
+  fn replay_frames(&self) -> u32 {
+    self.replay.frames_per_draw()
+  }
+
+  fn save_recording(&self) {
+    self.replay.save();
+  }
+
   fn is_windowed(&self) -> bool {
     self.game_options.windowed_mode
   }
-}
 
-fn process_keyboard_input(input: glutin::KeyboardInput, controls: &mut TilemapControls) -> WindowStatus {
-  match input {
-    KeyboardInput { state: Pressed, virtual_keycode: Some(Z), .. } => {
-      controls.zoom(&Control::Negative);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(X), .. } => {
-      controls.zoom(&Control::Plus);
-    }
-    KeyboardInput { state: Released, virtual_keycode: Some(Z), .. } |
-    KeyboardInput { state: Released, virtual_keycode: Some(X), .. } => {
-      controls.zoom(&Control::Released);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(W), .. } => {
-      controls.move_character(CharacterControl::Up);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(S), .. } => {
-      controls.move_character(CharacterControl::Down);
-    }
-    KeyboardInput { state: Released, virtual_keycode: Some(W), .. } |
-    KeyboardInput { state: Released, virtual_keycode: Some(S), .. } => {
-      controls.move_character(CharacterControl::YMoveStop);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(A), .. } => {
-      controls.move_character(CharacterControl::Left);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(D), .. } => {
-      controls.move_character(CharacterControl::Right);
-    }
-    KeyboardInput { state: Released, virtual_keycode: Some(A), .. } |
-    KeyboardInput { state: Released, virtual_keycode: Some(D), .. } => {
-      controls.move_character(CharacterControl::XMoveStop);
-    }
-    KeyboardInput { state: Pressed, virtual_keycode: Some(R), .. } => {
-      controls.reload_weapon(true);
-    }
-    KeyboardInput { state: Released, virtual_keycode: Some(R), .. } => {
-      controls.reload_weapon(false);
+  fn get_projection_mode(&self) -> ProjectionMode {
+    if self.game_options.orthographic {
+      ProjectionMode::Orthographic
+    } else {
+      ProjectionMode::Perspective
     }
-    KeyboardInput { state: Pressed, modifiers, .. } => {
-      if modifiers.ctrl {
-        controls.ctrl_pressed(true);
+  }
+
+  fn is_split_screen(&self) -> bool {
+    self.game_options.split_screen
+  }
+
+  fn get_seed(&self) -> Option<u64> {
+    self.game_options.seed
+  }
+
+  fn get_net_mode(&self) -> NetMode {
+    self.game_options.net.clone()
+  }
+}
+
+// Gameplay actions are ignored while a menu is open
+fn process_menu_input(action: Action, controls: &mut TilemapControls) {
+  match action {
+    Action::MoveUp | Action::SecondMoveUp => controls.menu(MenuControl::Up),
+    Action::MoveDown | Action::SecondMoveDown => controls.menu(MenuControl::Down),
+    Action::MoveLeft | Action::SecondMoveLeft => controls.menu(MenuControl::Left),
+    Action::MoveRight | Action::SecondMoveRight => controls.menu(MenuControl::Right),
+    Action::Confirm => controls.menu(MenuControl::Select),
+    Action::Pause => controls.menu(MenuControl::Back),
+    Action::Inventory => controls.menu(MenuControl::Inventory),
+    Action::ToggleConsole => controls.toggle_console(),
+    _ => (),
+  }
+}
+
+// Live input only steers a recording that plays back
+fn dispatch_input(event: InputEvent, replay: &mut InputReplay, controls: &mut TilemapControls) {
+  if replay.is_playing() {
+    replay.control(event);
+  } else {
+    replay.record(event);
+    process_input(event, controls);
+  }
+}
+
+fn process_input(event: InputEvent, controls: &mut TilemapControls) {
+  let context = controls.active_context();
+  match event {
+    InputEvent::Key(action, state) => {
+      if (action, state) == (Action::Chat, Released) {
+        controls.chat_key_released();
       }
+      process_action(action, state, controls);
     }
-    KeyboardInput { state: Released, modifiers, .. } => {
-      if !modifiers.ctrl {
-        controls.ctrl_pressed(false);
+    InputEvent::Character(c) => match controls.text_entry() {
+      Some(InputContext::Console) => process_console_input(c, controls),
+      Some(InputContext::Chat) => process_chat_input(c, controls),
+      _ => (),
+    },
+    InputEvent::MouseClick(Some(position)) => match context {
+      InputContext::Gameplay => controls.mouse_left_click(Some(position)),
+      InputContext::Menu => controls.menu(MenuControl::Press(Point2::new(position.0 as f32, position.1 as f32))),
+      _ => (),
+    },
+    InputEvent::MouseClick(None) => {
+      if context == InputContext::Menu {
+        controls.menu(MenuControl::Release);
       }
+      controls.mouse_left_click(None);
     }
+    InputEvent::MouseMove(position) => match context {
+      InputContext::Gameplay => controls.mouse_moved(position),
+      InputContext::Menu => controls.menu(MenuControl::Point(Point2::new(position.0 as f32, position.1 as f32))),
+      _ => (),
+    },
+    InputEvent::Zoom(lines) => if context == InputContext::Gameplay {
+      controls.zoom_by(lines);
+    },
   }
-  if let Some(Escape) = input.virtual_keycode {
-    WindowStatus::Close
-  } else {
-    WindowStatus::Open
+}
+
+// The key that opens the console also types its character, so it is left out of the input
+fn process_console_input(c: char, controls: &mut TilemapControls) {
+  match c {
+    '\u{8}' => controls.console(ConsoleControl::Backspace),
+    '\r' | '\n' => controls.console(ConsoleControl::Submit),
+    '`' | '~' => (),
+    c if !c.is_control() => controls.console(ConsoleControl::Character(c)),
+    _ => (),
+  }
+}
+
+// A line is sent with Enter, which closes the chat
+fn process_chat_input(c: char, controls: &mut TilemapControls) {
+  match c {
+    '\u{8}' => controls.chat(ChatControl::Backspace),
+    '\r' | '\n' => {
+      controls.chat(ChatControl::Submit);
+      controls.toggle_chat();
+    }
+    c if !c.is_control() => controls.chat(ChatControl::Character(c)),
+    _ => (),
   }
 }
 
+// Only the context with the focus gets the action, the ones below it don't see it
+fn process_action(action: Action, state: glutin::ElementState, controls: &mut TilemapControls) {
+  match controls.route(action) {
+    Some(InputContext::Gameplay) => process_gameplay_action(action, state, controls),
+    Some(InputContext::Menu) if state == Pressed => process_menu_input(action, controls),
+    Some(InputContext::Chat) | Some(InputContext::Console) | Some(InputContext::TextEntry) if state == Pressed => controls.close_text_entry(action),
+    _ => (),
+  }
+}
+
+fn process_gameplay_action(action: Action, state: glutin::ElementState, controls: &mut TilemapControls) {
+  match (action, state) {
+    (Action::ZoomIn, Pressed) => controls.zoom(&Control::Negative),
+    (Action::ZoomOut, Pressed) => controls.zoom(&Control::Plus),
+    (Action::ZoomIn, Released) | (Action::ZoomOut, Released) => controls.zoom(&Control::Released),
+    (Action::MoveUp, Pressed) => controls.move_character(CharacterControl::Up),
+    (Action::MoveDown, Pressed) => controls.move_character(CharacterControl::Down),
+    (Action::MoveUp, Released) | (Action::MoveDown, Released) => controls.move_character(CharacterControl::YMoveStop),
+    (Action::MoveLeft, Pressed) => controls.move_character(CharacterControl::Left),
+    (Action::MoveRight, Pressed) => controls.move_character(CharacterControl::Right),
+    (Action::MoveLeft, Released) | (Action::MoveRight, Released) => controls.move_character(CharacterControl::XMoveStop),
+    (Action::SecondMoveUp, Pressed) => controls.move_second_character(CharacterControl::Up),
+    (Action::SecondMoveDown, Pressed) => controls.move_second_character(CharacterControl::Down),
+    (Action::SecondMoveUp, Released) | (Action::SecondMoveDown, Released) => controls.move_second_character(CharacterControl::YMoveStop),
+    (Action::SecondMoveLeft, Pressed) => controls.move_second_character(CharacterControl::Left),
+    (Action::SecondMoveRight, Pressed) => controls.move_second_character(CharacterControl::Right),
+    (Action::SecondMoveLeft, Released) | (Action::SecondMoveRight, Released) => controls.move_second_character(CharacterControl::XMoveStop),
+    (Action::Aim, state) => controls.ctrl_pressed(state == Pressed),
+    (Action::Reload, state) => controls.reload_weapon(state == Pressed),
+    (Action::ToggleProfiler, Pressed) => controls.toggle_profiler(),
+    (Action::ToggleFrameStats, Pressed) => controls.toggle_frame_stats(),
+    (Action::ToggleDebugShapes, Pressed) => controls.toggle_debug_shapes(),
+    (Action::ToggleInspector, Pressed) => controls.inspect(InspectorControl::Toggle),
+    (Action::InspectNextEntity, Pressed) => controls.inspect(InspectorControl::NextEntity),
+    (Action::InspectPreviousEntity, Pressed) => controls.inspect(InspectorControl::PreviousEntity),
+    (Action::InspectNextField, Pressed) => controls.inspect(InspectorControl::NextField),
+    (Action::IncreaseInspected, Pressed) => controls.inspect(InspectorControl::Increase),
+    (Action::DecreaseInspected, Pressed) => controls.inspect(InspectorControl::Decrease),
+    (Action::PauseSimulation, Pressed) => controls.inspect(InspectorControl::Pause),
+    (Action::StepSimulation, Pressed) => controls.inspect(InspectorControl::Step),
+    (Action::ToggleShaderParams, Pressed) => controls.tweak_shader_params(ShaderParamControl::Toggle),
+    (Action::NextShaderParam, Pressed) => controls.tweak_shader_params(ShaderParamControl::SelectNext),
+    (Action::IncreaseShaderParam, Pressed) => controls.tweak_shader_params(ShaderParamControl::Increase),
+    (Action::DecreaseShaderParam, Pressed) => controls.tweak_shader_params(ShaderParamControl::Decrease),
+    (Action::Pause, Pressed) => controls.menu(MenuControl::Back),
+    (Action::Inventory, Pressed) => controls.menu(MenuControl::Inventory),
+    (Action::QuickSave, Pressed) => controls.menu(MenuControl::QuickSave),
+    (Action::QuickLoad, Pressed) => controls.menu(MenuControl::QuickLoad),
+    (Action::ToggleConsole, Pressed) => controls.toggle_console(),
+    (Action::Chat, Pressed) => controls.toggle_chat(),
+    _ => (),
+  }
+}
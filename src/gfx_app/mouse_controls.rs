@@ -1,13 +1,18 @@
 use cgmath::Point2;
 use crossbeam_channel as channel;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
 
-use crate::bullet::bullets::Bullets;
-use crate::character::{CharacterDrawable, controls::CharacterInputState};
+use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+use crate::ecs::{self, Entities, LazyUpdate, Read, ReadStorage, Write, WriteStorage};
 use crate::game::constants::SMALL_HILLS;
-use crate::graphics::{camera::CameraInputState, check_terrain_elevation, dimensions::Dimensions, direction};
+use crate::game::{GameRng, get_rand_offset};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::health::Health;
+use crate::game::input_buffer::{BufferedAction, InputBuffer};
+use crate::game::spawner;
+use crate::graphics::{camera::CameraInputState, check_terrain_elevation, coords_to_tile, dimensions::Dimensions, direction, overlaps};
+use crate::net::LocalPlayer;
 use crate::shaders::Position;
+use crate::zombie::ZombieDrawable;
 
 type MouseEvent = channel::Sender<(MouseControl, Option<(f64, f64)>)>;
 
@@ -34,12 +39,44 @@ impl Default for MouseInputState {
   }
 }
 
-impl specs::prelude::Component for MouseInputState {
-  type Storage = specs::storage::VecStorage<MouseInputState>;
+impl ecs::Component for MouseInputState {
+  type Storage = ecs::DenseVecStorage<MouseInputState>;
+}
+
+// What the cursor points at, refreshed every simulation step since zoom and movement change it without the mouse moving
+pub struct MouseState {
+  pub cursor: Point2<f32>,
+  pub position: Position,
+  pub world_position: Position,
+  pub tile: Point2<i32>,
+  pub is_left_pressed: bool,
+  // A living zombie is under the cursor
+  pub is_on_target: bool,
+}
+
+impl MouseState {
+  pub fn new() -> MouseState {
+    MouseState {
+      cursor: Point2::new(0.0, 0.0),
+      position: Position::origin(),
+      world_position: Position::origin(),
+      tile: coords_to_tile(Position::origin()),
+      is_left_pressed: false,
+      is_on_target: false,
+    }
+  }
+}
+
+impl Default for MouseState {
+  fn default() -> MouseState {
+    MouseState::new()
+  }
 }
 
 pub enum MouseControl {
   LeftClick,
+  Move,
+  Resize,
 }
 
 pub struct MouseControlSystem {
@@ -55,37 +92,76 @@ impl MouseControlSystem {
   }
 }
 
-impl<'a> specs::prelude::System<'a> for MouseControlSystem {
-  type SystemData = (WriteStorage<'a, MouseInputState>,
+impl<'a> ecs::System<'a> for MouseControlSystem {
+  type SystemData = (Entities<'a>,
+                     WriteStorage<'a, MouseInputState>,
                      WriteStorage<'a, CharacterDrawable>,
                      ReadStorage<'a, CameraInputState>,
                      ReadStorage<'a, CharacterInputState>,
-                     WriteStorage<'a, Bullets>,
-                     Read<'a, Dimensions>);
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, Health>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     Write<'a, Dimensions>,
+                     Write<'a, MouseState>,
+                     Read<'a, LazyUpdate>,
+                     Write<'a, GameEvents>,
+                     Write<'a, InputBuffer>,
+                     Write<'a, GameRng>,
+                     Read<'a, LocalPlayer>);
 
-  fn run(&mut self, (mut mouse_input, mut character_drawable, camera, character_input, mut bullets, dim): Self::SystemData) {
-    use specs::join::Join;
+  fn run(&mut self, (entities, mut mouse_input, mut character_drawable, camera, character_input, players, health, zombies, mut dim, mut mouse, lazy, mut events, mut buffer, mut rng, local): Self::SystemData) {
+    use crate::ecs::Join;
 
     while let Ok((control_value, value)) = self.queue.try_recv() {
       match control_value {
+        MouseControl::Move => if let Some((x, y)) = value {
+          mouse.cursor = Point2::new(x as f32, y as f32);
+        },
+        MouseControl::Resize => if let Some((width, height)) = value {
+          dim.resize(width as f32, height as f32);
+        },
         MouseControl::LeftClick => {
-          for (mut mi, cd, bs, ca, ci) in (&mut mouse_input, &mut character_drawable, &mut bullets, &camera, &character_input).join() {
-            if let Some(val) = value {
-              if ci.is_shooting && cd.stats.ammunition > 0 {
-                cd.stats.ammunition -= 1;
-                let start_point = Point2::new(dim.window_width / 2.0 * dim.hidpi_factor, dim.window_height / 2.0 * dim.hidpi_factor);
-                let end_point = Point2::new(val.0 as f32 * dim.hidpi_factor, val.1 as f32 * dim.hidpi_factor);
-                mi.left_click_point = Some(end_point);
-                let dir = direction(start_point, end_point);
-                let elevated_pos_y = check_terrain_elevation(ci.movement, &SMALL_HILLS);
-                Bullets::add_bullet(bs, Position::new(-ca.movement.x(), ca.movement.y() + elevated_pos_y), dir);
-              }
-            } else {
+          mouse.is_left_pressed = value.is_some();
+          match value {
+            // The mouse fires the local player, the clicks of the others come over the network
+            Some((x, y)) => buffer.press(local.0, BufferedAction::Fire(Point2::new(x as f32, y as f32))),
+            None => for mi in (&mut mouse_input).join() {
               mi.left_click_point = None;
-            }
+            },
           }
         }
       }
     }
+
+    // A click that arrives before the aim key is seen by this step is fired on a later one
+    for (e, cd, ca, ci, p, h) in (&entities, &mut character_drawable, &camera, &character_input, &players, &health).join() {
+      if h.is_dead() || !ci.is_shooting || cd.stats.ammunition == 0 || cd.stats.is_reloading() {
+        continue;
+      }
+      if let Some(BufferedAction::Fire(click)) = buffer.consume(p.index, |a| matches!(a, BufferedAction::Fire(_))) {
+        let spread = cd.stats.spread;
+        cd.stats.fire();
+        let start_point = Point2::new(dim.window_width / 2.0 * dim.hidpi_factor, dim.window_height / 2.0 * dim.hidpi_factor);
+        let end_point = Point2::new(click.x * dim.hidpi_factor, click.y * dim.hidpi_factor);
+        // The aiming pose stays only while the local button is held
+        if let Some(mi) = mouse_input.get_mut(e).filter(|_| p.index == local.0 && mouse.is_left_pressed) {
+          mi.left_click_point = Some(end_point);
+        }
+        let dir = (direction(start_point, end_point) + get_rand_offset(&mut rng, spread)).rem_euclid(360.0);
+        let elevated_pos_y = check_terrain_elevation(ci.movement, &SMALL_HILLS);
+        let position = Position::new(-ca.movement.x(), ca.movement.y() + elevated_pos_y);
+        spawner::spawn_bullet(&lazy, position, dir);
+        events.single_write(GameEvent::BulletFired(position));
+      }
+    }
+
+    // Only the first player has a mouse, drawables are placed relative to it
+    if let Some((ca, ci, _)) = (&camera, &character_input, &mouse_input).join().next() {
+      mouse.position = dim.screen_to_world(mouse.cursor, ca);
+      mouse.world_position = ci.movement - mouse.position;
+      mouse.tile = coords_to_tile(mouse.world_position);
+      mouse.is_on_target = zombies.join()
+        .any(|z| !z.is_dead() && overlaps(mouse.position, z.transform.camera_relative_position(ci), 15.0, 30.0));
+    }
   }
 }
@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::fs;
+
+use glutin::ElementState;
+use json::JsonValue;
+use tracing::{info, warn};
+
+use crate::data;
+use crate::game::constants::MAX_REPLAY_SPEED;
+use crate::gfx_app::input::{Action, action_from_name, action_name};
+
+// Input as the window hands it to the controls, recorded before the game state decides what it does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+  Key(Action, ElementState),
+  Character(char),
+  MouseClick(Option<(f64, f64)>),
+  MouseMove((f64, f64)),
+  // Wheel lines, positive zooms in
+  Zoom(f64),
+}
+
+fn point_to_json(point: (f64, f64)) -> JsonValue {
+  array![point.0, point.1]
+}
+
+fn point_from_json(value: &JsonValue) -> Option<(f64, f64)> {
+  Some((value[0].as_f64()?, value[1].as_f64()?))
+}
+
+impl InputEvent {
+  pub fn to_json(self) -> JsonValue {
+    match self {
+      InputEvent::Key(action, state) => object! {
+        "action" => action_name(action),
+        "pressed" => state == ElementState::Pressed,
+      },
+      InputEvent::Character(c) => object! {
+        "char" => c.to_string(),
+      },
+      InputEvent::MouseClick(position) => object! {
+        "click" => position.map_or(JsonValue::Null, point_to_json),
+      },
+      InputEvent::MouseMove(position) => object! {
+        "move" => point_to_json(position),
+      },
+      InputEvent::Zoom(lines) => object! {
+        "zoom" => lines,
+      },
+    }
+  }
+
+  pub fn from_json(value: &JsonValue) -> Result<InputEvent, String> {
+    if let Some(name) = value["action"].as_str() {
+      let action = action_from_name(name).ok_or_else(|| format!("Unknown action {}", name))?;
+      let state = if value["pressed"].as_bool().unwrap_or(false) { ElementState::Pressed } else { ElementState::Released };
+      Ok(InputEvent::Key(action, state))
+    } else if let Some(c) = value["char"].as_str().and_then(|c| c.chars().next()) {
+      Ok(InputEvent::Character(c))
+    } else if let Some(lines) = value["zoom"].as_f64() {
+      Ok(InputEvent::Zoom(lines))
+    } else if value.has_key("click") {
+      Ok(InputEvent::MouseClick(point_from_json(&value["click"])))
+    } else {
+      point_from_json(&value["move"])
+        .map(InputEvent::MouseMove)
+        .ok_or_else(|| format!("Unknown input event {}", value.dump()))
+    }
+  }
+}
+
+// Input polled before a frame, and how many simulation steps that frame ran
+#[derive(Debug, PartialEq)]
+pub struct RecordedFrame {
+  pub steps: u32,
+  pub events: Vec<InputEvent>,
+}
+
+impl RecordedFrame {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "steps" => self.steps,
+      "events" => self.events.iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<RecordedFrame, String> {
+    Ok(RecordedFrame {
+      steps: value["steps"].as_u32().ok_or("Frame has no steps")?,
+      events: value["events"].members().map(InputEvent::from_json).collect::<Result<Vec<_>, _>>()?,
+    })
+  }
+}
+
+#[derive(Debug)]
+pub enum ReplayMode {
+  Off,
+  Record(String),
+  Play(String),
+}
+
+pub enum FrameInput {
+  Live,
+  Replayed(u32),
+  Finished,
+}
+
+// A recording replays the same world when it runs with the recorded seed and the recorded step count of every frame.
+// Menu input is kept in its frame as well, since state transitions are applied between frames.
+pub struct InputReplay {
+  mode: ReplayMode,
+  pub seed: Option<u64>,
+  pub split_screen: bool,
+  pending: Vec<InputEvent>,
+  current: Vec<InputEvent>,
+  frames: VecDeque<RecordedFrame>,
+  is_paused: bool,
+  // Recorded frames played per drawn frame
+  speed: u32,
+}
+
+impl InputReplay {
+  pub fn new(mode: ReplayMode, seed: Option<u64>, split_screen: bool) -> InputReplay {
+    let mut replay = InputReplay {
+      mode,
+      seed,
+      split_screen,
+      pending: Vec::new(),
+      current: Vec::new(),
+      frames: VecDeque::new(),
+      is_paused: false,
+      speed: 1,
+    };
+    if let ReplayMode::Play(ref filename) = replay.mode {
+      let value = data::load_json(filename);
+      replay.seed = value["seed"].as_u64();
+      replay.split_screen = value["split_screen"].as_bool().unwrap_or(false);
+      replay.frames = match value["frames"].members().map(RecordedFrame::from_json).collect() {
+        Ok(frames) => frames,
+        Err(e) => panic!("Replay {} error {}", filename, e),
+      };
+    }
+    replay
+  }
+
+  pub fn is_playing(&self) -> bool {
+    matches!(self.mode, ReplayMode::Play(_))
+  }
+
+  // Confirm pauses and resumes a playback, left and right halve and double its speed
+  pub fn control(&mut self, event: InputEvent) {
+    let (is_paused, speed) = (self.is_paused, self.speed);
+    match event {
+      InputEvent::Key(Action::Confirm, ElementState::Pressed) => self.is_paused = !self.is_paused,
+      InputEvent::Key(Action::MoveRight, ElementState::Pressed) |
+      InputEvent::Key(Action::SecondMoveRight, ElementState::Pressed) => self.speed = (self.speed * 2).min(MAX_REPLAY_SPEED),
+      InputEvent::Key(Action::MoveLeft, ElementState::Pressed) |
+      InputEvent::Key(Action::SecondMoveLeft, ElementState::Pressed) => self.speed = (self.speed / 2).max(1),
+      _ => return,
+    }
+    if self.is_paused != is_paused || self.speed != speed {
+      info!("Replay {} at {}x speed", if self.is_paused { "paused" } else { "playing" }, self.speed);
+    }
+  }
+
+  pub fn frames_per_draw(&self) -> u32 {
+    match self.mode {
+      ReplayMode::Play(_) if self.is_paused => 0,
+      ReplayMode::Play(_) => self.speed,
+      _ => 1,
+    }
+  }
+
+  pub fn record(&mut self, event: InputEvent) {
+    if let ReplayMode::Record(_) = self.mode {
+      self.pending.push(event);
+    }
+  }
+
+  // Returns the recorded input of the next frame when playing back
+  pub fn begin_frame(&mut self) -> (Vec<InputEvent>, FrameInput) {
+    match self.mode {
+      ReplayMode::Play(_) => match self.frames.pop_front() {
+        Some(frame) => (frame.events, FrameInput::Replayed(frame.steps)),
+        None => (Vec::new(), FrameInput::Finished),
+      },
+      ReplayMode::Record(_) => {
+        self.current = std::mem::take(&mut self.pending);
+        (Vec::new(), FrameInput::Live)
+      }
+      ReplayMode::Off => (Vec::new(), FrameInput::Live),
+    }
+  }
+
+  pub fn end_frame(&mut self, steps: u32) {
+    if let ReplayMode::Record(_) = self.mode {
+      let events = std::mem::take(&mut self.current);
+      self.frames.push_back(RecordedFrame { steps, events });
+    }
+  }
+
+  pub fn to_json(&self) -> JsonValue {
+    object! {
+      "seed" => self.seed,
+      "split_screen" => self.split_screen,
+      "frames" => self.frames.iter().map(RecordedFrame::to_json).collect::<Vec<_>>(),
+    }
+  }
+
+  pub fn save(&self) {
+    if let ReplayMode::Record(ref filename) = self.mode {
+      match fs::write(filename, self.to_json().dump()) {
+        Ok(_) => info!("Input recorded to {}", filename),
+        Err(e) => warn!("Input recording {} save error {}", filename, e),
+      }
+    }
+  }
+}
@@ -1,35 +1,59 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
 
 use gfx;
-use specs;
-use specs::prelude::{Read, WriteStorage};
+use tracing::info;
 
+use crate::ecs::{self, Read, ReadStorage, Write, WriteStorage};
 use crate::{bullet, terrain_shape};
 use crate::character;
+use crate::character::controls::CharacterInputState;
 use crate::critter::CharacterSprite;
-use crate::game::constants::{CURRENT_AMMO_TEXT, GAME_VERSION, HUD_TEXTS};
+use crate::game::console::Console;
+use crate::game::inspector::Inspector;
 use crate::gfx_app::{ColorFormat, DepthFormat};
 use crate::gfx_app::renderer::EncoderQueue;
-use crate::graphics::{DeltaTime, orientation::{Orientation, Stance}};
+use crate::graphics::assets::Assets;
+use crate::graphics::{camera::CameraInputState, dimensions::Dimensions, Interpolation, orientation::{Orientation, Stance}};
+use crate::graphics::debug_shapes::{DebugShapes, DebugShapesPass};
+use crate::graphics::governor::FrameGovernor;
+use crate::graphics::post_process::HeatHazePass;
+use crate::graphics::render_graph::{ConstantCache, RenderTargets, ShaderReloader};
+use crate::graphics::shader_params::ShaderParams;
+use crate::graphics::viewport::build_views;
 use crate::graphics::Drawables;
-use crate::hud;
+use crate::hud::text::TextDrawSystem;
+use crate::hud::ui::{UiDrawSystem, UiLayer};
+use crate::net::{client::local_index, LocalPlayer};
+use crate::profiler::{BuiltOverlay, FrameProfile, Overlay, OverlayDrawSystem, ProfilerEvent};
+use crate::shaders::{Globals, Position};
 use crate::terrain;
 use crate::terrain_object;
 use crate::terrain_object::TerrainTexture;
-use crate::zombie;
+use crate::zombie::{self, lod::ZombieLod};
 
 pub struct DrawSystem<D: gfx::Device> {
-  render_target_view: gfx::handle::RenderTargetView<D::Resources, ColorFormat>,
-  depth_stencil_view: gfx::handle::DepthStencilView<D::Resources, DepthFormat>,
+  targets: RenderTargets<D::Resources>,
+  window_targets: RenderTargets<D::Resources>,
+  globals: ConstantCache<Globals>,
+  heat_haze: HeatHazePass<D::Resources>,
+  debug_shapes: DebugShapesPass<D::Resources>,
   terrain_system: terrain::TerrainDrawSystem<D::Resources>,
   character_system: character::CharacterDrawSystem<D::Resources>,
   zombie_system: zombie::ZombieDrawSystem<D::Resources>,
   bullet_system: bullet::BulletDrawSystem<D::Resources>,
   terrain_object_system: [terrain_object::TerrainObjectDrawSystem<D::Resources>; 3],
   terrain_shape_system: [terrain_shape::TerrainShapeDrawSystem<D::Resources>; 9],
-  text_system: [hud::TextDrawSystem<D::Resources>; 3],
+  text_system: TextDrawSystem<D::Resources>,
+  ui_system: UiDrawSystem<D::Resources>,
+  // Debug overlays arrive from the render thread the first time they are shown
+  overlays: Vec<(Overlay, OverlayDrawSystem<D::Resources>)>,
+  built_overlays: channel::Receiver<BuiltOverlay<D::Resources>>,
+  profiler: channel::Sender<ProfilerEvent>,
   encoder_queue: EncoderQueue<D>,
   game_time: Instant,
+  last_frame: Instant,
   frames: u32,
   cool_down: f64,
   run_cool_down: f64,
@@ -40,44 +64,54 @@ impl<D: gfx::Device> DrawSystem<D> {
   pub fn new<F>(factory: &mut F,
                 rtv: &gfx::handle::RenderTargetView<D::Resources, ColorFormat>,
                 dsv: &gfx::handle::DepthStencilView<D::Resources, DepthFormat>,
-                encoder_queue: EncoderQueue<D>)
-                -> DrawSystem<D>
+                reloader: &mut ShaderReloader<F>,
+                assets: &Assets<D::Resources>,
+                encoder_queue: EncoderQueue<D>,
+                profiler: channel::Sender<ProfilerEvent>)
+                -> (DrawSystem<D>, channel::Sender<BuiltOverlay<D::Resources>>)
     where F: gfx::Factory<D::Resources> {
-    DrawSystem {
-      render_target_view: rtv.clone(),
-      depth_stencil_view: dsv.clone(),
-      terrain_system: terrain::TerrainDrawSystem::new(factory, rtv.clone(), dsv.clone()),
-      character_system: character::CharacterDrawSystem::new(factory, rtv.clone(), dsv.clone()),
-      zombie_system: zombie::ZombieDrawSystem::new(factory, rtv.clone(), dsv.clone()),
-      bullet_system: bullet::BulletDrawSystem::new(factory, rtv.clone(), dsv.clone()),
+    let (overlay_queue, built_overlays) = channel::unbounded();
+    let window_targets = RenderTargets::new(factory, rtv.clone(), dsv.clone());
+    let (targets, scene) = window_targets.offscreen(factory);
+    (DrawSystem {
+      heat_haze: HeatHazePass::new(factory, &window_targets, scene, reloader),
+      debug_shapes: DebugShapesPass::new(factory, &targets, reloader),
+      terrain_system: terrain::TerrainDrawSystem::new(factory, &targets, assets, reloader),
+      character_system: character::CharacterDrawSystem::new(factory, &targets, assets, reloader),
+      zombie_system: zombie::ZombieDrawSystem::new(factory, &targets, assets, reloader),
+      bullet_system: bullet::BulletDrawSystem::new(factory, &targets, reloader),
       terrain_object_system: [
-        terrain_object::TerrainObjectDrawSystem::new(factory, rtv.clone(), dsv.clone(), TerrainTexture::Ammo),
-        terrain_object::TerrainObjectDrawSystem::new(factory, rtv.clone(), dsv.clone(), TerrainTexture::House),
-        terrain_object::TerrainObjectDrawSystem::new(factory, rtv.clone(), dsv.clone(), TerrainTexture::Tree)
+        terrain_object::TerrainObjectDrawSystem::new(factory, &targets, TerrainTexture::Ammo, assets, reloader),
+        terrain_object::TerrainObjectDrawSystem::new(factory, &targets, TerrainTexture::House, assets, reloader),
+        terrain_object::TerrainObjectDrawSystem::new(factory, &targets, TerrainTexture::Tree, assets, reloader)
       ],
       terrain_shape_system: [
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::Right),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::DownRight),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::Down),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::DownLeft),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::Left),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::UpLeft),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::UpRight),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::Normal),
-        terrain_shape::TerrainShapeDrawSystem::new(factory, rtv.clone(), dsv.clone(), Orientation::Up),
-      ],
-      text_system: [
-        hud::TextDrawSystem::new(factory, &HUD_TEXTS, GAME_VERSION, rtv.clone(), dsv.clone()),
-        hud::TextDrawSystem::new(factory, &HUD_TEXTS, CURRENT_AMMO_TEXT, rtv.clone(), dsv.clone()),
-        hud::TextDrawSystem::new(factory, &HUD_TEXTS, CURRENT_AMMO_TEXT, rtv.clone(), dsv.clone())
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::Right, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::DownRight, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::Down, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::DownLeft, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::Left, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::UpLeft, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::UpRight, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::Normal, assets, reloader),
+        terrain_shape::TerrainShapeDrawSystem::new(factory, &targets, Orientation::Up, assets, reloader),
       ],
+      text_system: TextDrawSystem::new(factory, &window_targets, reloader),
+      ui_system: UiDrawSystem::new(factory, &window_targets, reloader),
+      overlays: Vec::new(),
+      built_overlays,
+      targets,
+      window_targets,
+      globals: ConstantCache::default(),
+      profiler,
       encoder_queue,
       game_time: Instant::now(),
+      last_frame: Instant::now(),
       frames: 0,
       cool_down: 1.0,
       run_cool_down: 1.0,
       fire_cool_down: 1.0,
-    }
+    }, overlay_queue)
   }
 
   fn update_cooldowns(&mut self, delta: f64) {
@@ -94,57 +128,91 @@ impl<D: gfx::Device> DrawSystem<D> {
     self.run_cool_down = (self.run_cool_down - delta).max(0.0);
     self.fire_cool_down = (self.fire_cool_down - delta).max(0.0);
   }
+
+  fn report_timing(&self, name: &'static str, elapsed: Duration) {
+    self.profiler.send(ProfilerEvent::Timing(name, elapsed)).expect("Profiler timing update error");
+  }
+
+  fn draw_overlay(&mut self, overlay: Overlay, lines: &[String], encoder: &mut gfx::Encoder<D::Resources, D::CommandBuffer>) {
+    if let Some((_, system)) = self.overlays.iter_mut().find(|(o, _)| *o == overlay) {
+      system.draw(lines, encoder);
+    }
+  }
 }
 
-impl<'a, D> specs::prelude::System<'a> for DrawSystem<D>
+impl<'a, D> ecs::System<'a> for DrawSystem<D>
   where D: gfx::Device,
         D::CommandBuffer: Send {
   type SystemData = (WriteStorage<'a, terrain::TerrainDrawable>,
-                     WriteStorage<'a, terrain_shape::terrain_shape_objects::TerrainShapeObjects>,
+                     ReadStorage<'a, terrain_shape::TerrainShapeDrawable>,
                      WriteStorage<'a, character::CharacterDrawable>,
                      WriteStorage<'a, CharacterSprite>,
-                     WriteStorage<'a, hud::hud_objects::HudObjects>,
-                     WriteStorage<'a, zombie::zombies::Zombies>,
-                     WriteStorage<'a, bullet::bullets::Bullets>,
-                     WriteStorage<'a, terrain_object::terrain_objects::TerrainObjects>,
-                     Read<'a, DeltaTime>);
-
-  fn run(&mut self, (mut terrain, mut terrain_shape, mut character, mut character_sprite, mut hud_objects, mut zombies, mut bullets, mut terrain_objects, dt): Self::SystemData) {
-    use specs::join::Join;
+                     WriteStorage<'a, zombie::ZombieDrawable>,
+                     ReadStorage<'a, bullet::BulletDrawable>,
+                     ReadStorage<'a, terrain_object::TerrainObjectDrawable>,
+                     ReadStorage<'a, CameraInputState>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, character::Player>,
+                     Read<'a, Dimensions>,
+                     Read<'a, Interpolation>,
+                     Read<'a, FrameProfile>,
+                     Read<'a, ShaderParams>,
+                     Read<'a, Console>,
+                     Read<'a, Inspector>,
+                     Read<'a, LocalPlayer>,
+                     Read<'a, FrameGovernor>,
+                     Read<'a, DebugShapes>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (mut terrain, terrain_shape, mut character, mut character_sprite, mut zombies, bullets, terrain_objects,
+                     camera_input, character_input, players, dim, interpolation, profile, shader_params, console, inspector, local, governor, debug_shapes, mut ui_layer): Self::SystemData) {
+    use crate::ecs::Join;
     let mut encoder = self.encoder_queue.receiver
       .recv()
       .expect("Encoder error");
 
-    self.update_cooldowns(dt.0);
-
+    // Sprite animation follows rendered frames, the simulation step length doesn't apply here
     let current_time = Instant::now();
+    let frame_time = current_time.duration_since(self.last_frame);
+    self.last_frame = current_time;
+    self.update_cooldowns(f64::from(frame_time.subsec_nanos()) / 1e9 + frame_time.as_secs() as f64);
+
     self.frames += 1;
 
     let time_passed = current_time.duration_since(self.game_time).as_secs();
 
     if cfg!(feature = "framerate") && time_passed >= 1 {
-      println!("{:?} ms/frames", 1000.0 / f64::from(self.frames));
+      info!("{:?} ms/frames", 1000.0 / f64::from(self.frames));
       self.frames = 0;
       self.game_time = Instant::now();
     }
 
-    encoder.clear(&self.render_target_view, [16.0 / 256.0, 16.0 / 256.0, 20.0 / 256.0, 1.0]);
-    encoder.clear_depth(&self.depth_stencil_view, 1.0);
+    encoder.clear(&self.targets.color, [16.0 / 256.0, 16.0 / 256.0, 20.0 / 256.0, 1.0]);
+    encoder.clear_depth(&self.targets.depth, 1.0);
+    encoder.clear_depth(&self.window_targets.depth, 1.0);
+    self.heat_haze.clear(&mut encoder);
 
-    for (t, t_shape, c, cs, hds, zs, bs, obj) in (&mut terrain, &mut terrain_shape, &mut character, &mut character_sprite, &mut hud_objects,
-                                         &mut zombies, &mut bullets, &mut terrain_objects).join() {
-      self.terrain_system.draw(t, time_passed,  &mut encoder);
-
-      for hud in &mut hds.objects {
-        self.text_system[0].draw(hud, &mut encoder);
-        self.text_system[1].draw(hud, &mut encoder);
-      }
+    let mut player_cameras = (&camera_input, &character_input, &players).join().collect::<Vec<_>>();
+    let anchor = player_cameras.iter().find(|(_, _, p)| p.index == 0).map_or(Position::origin(), |(_, ci, _)| ci.movement);
+    player_cameras.sort_by_key(|(_, _, p)| local_index(p.index, local.0));
+    let player_cameras = player_cameras.iter().map(|(camera, ci, _)| (*camera, *ci)).collect::<Vec<_>>();
+    let views = build_views(&dim, self.targets.scissor(), &player_cameras, anchor, interpolation.0);
+    let mut draw_calls = 0;
 
+    for (c, cs) in (&character, &mut character_sprite).join() {
       if self.cool_down == 0.0 {
         if c.stance == Stance::Walking {
           cs.update_run();
         }
-        for z in &mut zs.zombies {
+      } else if self.fire_cool_down == 0.0 && c.stance == Stance::Firing {
+        cs.update_fire();
+      }
+    }
+
+    for t in (&mut terrain).join() {
+      // Zombies out of view are neither animated nor drawn
+      if self.cool_down == 0.0 {
+        for z in (&mut zombies).join().filter(|z| z.lod == ZombieLod::Near) {
           match z.stance {
             Stance::NormalDeath => z.update_death_idx(5),
             Stance::CriticalDeath => z.update_death_idx(7),
@@ -153,64 +221,147 @@ impl<'a, D> specs::prelude::System<'a> for DrawSystem<D>
             _ => ()
           };
         }
-      } else if self.fire_cool_down == 0.0 && c.stance == Stance::Firing {
-        cs.update_fire();
       }
 
       if self.run_cool_down == 0.0 {
-        for z in &mut zs.zombies {
+        for z in (&mut zombies).join().filter(|z| z.lod == ZombieLod::Near) {
           if let Stance::Running = z.stance {
             z.update_alive_idx(7)
           }
         }
       }
 
-      let mut drawables: Vec<Drawables> = vec![];
-      drawables.append(&mut bs.bullets.iter().map(|b| Drawables::Bullet(b)).collect());
-      drawables.append(&mut zs.zombies.iter_mut().map(|z| Drawables::Zombie(z)).collect());
+      let mut terrain_time = Duration::default();
+      let mut terrain_shape_time = Duration::default();
+      let mut critter_time = Duration::default();
+      let mut bullet_time = Duration::default();
+      let mut terrain_object_time = Duration::default();
+      let mut post_process_time = Duration::default();
+      // Sprites are counted as submitted, the passes may still cull them
+      draw_calls += views.len() * (1 + (&terrain_shape).join().count() + (&bullets).join().count()) + 1;
 
-      for o in &obj.objects {
-        match o.object_type {
-          TerrainTexture::Ammo => drawables.push(Drawables::TerrainAmmo(o)),
-          TerrainTexture::House => drawables.push(Drawables::TerrainHouse(o)),
-          TerrainTexture::Tree => drawables.push(Drawables::TerrainTree(o)),
-        };
-      }
+      for view in &views {
+        self.targets.update_globals(&mut self.globals, view, time_passed, &shader_params, &mut encoder);
+
+        let pass_start = Instant::now();
+        self.terrain_system.draw(t, view, &mut encoder);
+        terrain_time += pass_start.elapsed();
 
-      drawables.push(Drawables::Character(c));
-
-      drawables.sort_by(|a, b| {
-        Drawables::get_vertical_pos(b)
-          .partial_cmp(&Drawables::get_vertical_pos(a))
-          .expect("Z-axis sorting failed")
-      });
-
-      for ts in &t_shape.objects {
-        match ts.get_shape() {
-          Orientation::Right => self.terrain_shape_system[0].draw(ts, time_passed, &mut encoder),
-          Orientation::DownRight => self.terrain_shape_system[1].draw(ts, time_passed, &mut encoder),
-          Orientation::Down => self.terrain_shape_system[2].draw(ts, time_passed, &mut encoder),
-          Orientation::DownLeft => self.terrain_shape_system[3].draw(ts, time_passed, &mut encoder),
-          Orientation::Left => self.terrain_shape_system[4].draw(ts, time_passed, &mut encoder),
-          Orientation::UpLeft => self.terrain_shape_system[5].draw(ts, time_passed, &mut encoder),
-          Orientation::UpRight => self.terrain_shape_system[6].draw(ts, time_passed, &mut encoder),
-          Orientation::Normal => self.terrain_shape_system[7].draw(ts, time_passed, &mut encoder),
-          Orientation::Up => self.terrain_shape_system[8].draw(ts, time_passed, &mut encoder),
+        let mut drawables: Vec<Drawables> = vec![];
+        drawables.append(&mut (&bullets).join().map(Drawables::Bullet).collect());
+        drawables.append(&mut (&mut zombies).join().filter(|z| z.lod == ZombieLod::Near).map(Drawables::Zombie).collect());
+
+        for o in (&terrain_objects).join() {
+          match o.object_type {
+            TerrainTexture::Ammo => drawables.push(Drawables::TerrainAmmo(o)),
+            TerrainTexture::House => drawables.push(Drawables::TerrainHouse(o)),
+            TerrainTexture::Tree => drawables.push(Drawables::TerrainTree(o)),
+          };
+        }
+
+        drawables.append(&mut (&mut character, &character_sprite).join().map(|(c, cs)| Drawables::Character(c, cs)).collect());
+
+        draw_calls += drawables.len();
+        drawables.sort_by(|a, b| {
+          Drawables::get_vertical_pos(b)
+            .partial_cmp(&Drawables::get_vertical_pos(a))
+            .expect("Z-axis sorting failed")
+        });
+
+        let pass_start = Instant::now();
+        for ts in (&terrain_shape).join() {
+          match ts.get_shape() {
+            Orientation::Right => self.terrain_shape_system[0].draw(ts, view, &mut encoder),
+            Orientation::DownRight => self.terrain_shape_system[1].draw(ts, view, &mut encoder),
+            Orientation::Down => self.terrain_shape_system[2].draw(ts, view, &mut encoder),
+            Orientation::DownLeft => self.terrain_shape_system[3].draw(ts, view, &mut encoder),
+            Orientation::Left => self.terrain_shape_system[4].draw(ts, view, &mut encoder),
+            Orientation::UpLeft => self.terrain_shape_system[5].draw(ts, view, &mut encoder),
+            Orientation::UpRight => self.terrain_shape_system[6].draw(ts, view, &mut encoder),
+            Orientation::Normal => self.terrain_shape_system[7].draw(ts, view, &mut encoder),
+            Orientation::Up => self.terrain_shape_system[8].draw(ts, view, &mut encoder),
+          }
+        }
+        terrain_shape_time += pass_start.elapsed();
+
+        for e in &mut drawables {
+          let pass_start = Instant::now();
+          match *e {
+            Drawables::Bullet(e) => {
+              self.bullet_system.draw(e, view, &mut encoder);
+              bullet_time += pass_start.elapsed();
+            }
+            Drawables::Zombie(ref mut e) => {
+              self.zombie_system.draw(e, view, &mut encoder);
+              critter_time += pass_start.elapsed();
+            }
+            Drawables::TerrainAmmo(ref mut e) => {
+              self.terrain_object_system[0].draw(e, view, &mut encoder);
+              terrain_object_time += pass_start.elapsed();
+            }
+            Drawables::TerrainHouse(ref mut e) => {
+              self.terrain_object_system[1].draw(e, view, &mut encoder);
+              terrain_object_time += pass_start.elapsed();
+            }
+            Drawables::TerrainTree(ref mut e) => {
+              self.terrain_object_system[2].draw(e, view, &mut encoder);
+              terrain_object_time += pass_start.elapsed();
+            }
+            Drawables::Character(ref mut e, cs) => {
+              self.character_system.draw(e, cs, view, &mut encoder);
+              critter_time += pass_start.elapsed();
+            }
+          }
         }
-      }
 
-      for e in &mut drawables {
-        match *e {
-          Drawables::Bullet(ref e) => { self.bullet_system.draw(e, &mut encoder) }
-          Drawables::Zombie(ref mut e) => { self.zombie_system.draw(e, &mut encoder) }
-          Drawables::TerrainAmmo(ref mut e) => { self.terrain_object_system[0].draw(e, time_passed, &mut encoder) }
-          Drawables::TerrainHouse(ref mut e) => { self.terrain_object_system[1].draw(e, time_passed, &mut encoder) }
-          Drawables::TerrainTree(ref mut e) => { self.terrain_object_system[2].draw(e, time_passed, &mut encoder) }
-          Drawables::Character(ref mut e) => { self.character_system.draw(e, cs, &mut encoder) }
+        let pass_start = Instant::now();
+        // The first distortion the frame governor drops when frames run late
+        for b in (&bullets).join().take(governor.heat_haze_limit()) {
+          self.heat_haze.draw_source(b.transform.position, view, &mut encoder);
+        }
+        post_process_time += pass_start.elapsed();
+
+        if !debug_shapes.vertices().is_empty() {
+          self.debug_shapes.draw(&debug_shapes, view, &mut encoder);
         }
       }
+
+      let pass_start = Instant::now();
+      self.heat_haze.composite(&mut encoder);
+      post_process_time += pass_start.elapsed();
+
+      self.report_timing("draw-terrain", terrain_time);
+      self.report_timing("draw-terrain-shape", terrain_shape_time);
+      self.report_timing("draw-critter", critter_time);
+      self.report_timing("draw-bullet", bullet_time);
+      self.report_timing("draw-terrain-object", terrain_object_time);
+      self.report_timing("draw-post-process", post_process_time);
+    }
+
+    let pass_start = Instant::now();
+    self.ui_system.draw(&ui_layer.quads, &dim, &mut encoder);
+    draw_calls += ui_layer.quads.len() + self.text_system.draw(&ui_layer.texts, &dim, &mut encoder);
+    ui_layer.quads.clear();
+    ui_layer.texts.clear();
+    self.report_timing("draw-ui", pass_start.elapsed());
+
+    while let Ok(overlay) = self.built_overlays.try_recv() {
+      self.overlays.push(overlay);
+    }
+    if profile.is_visible {
+      self.draw_overlay(Overlay::Profiler, &profile.lines, &mut encoder);
+    }
+    if shader_params.is_visible {
+      self.draw_overlay(Overlay::ShaderParams, &shader_params.lines, &mut encoder);
+    }
+    if console.is_visible {
+      self.draw_overlay(Overlay::Console, &console.lines, &mut encoder);
+    }
+    if inspector.is_visible {
+      self.draw_overlay(Overlay::Inspector, &inspector.lines, &mut encoder);
     }
 
+    self.profiler.send(ProfilerEvent::DrawCalls(draw_calls)).expect("Profiler draw call update error");
     self.encoder_queue.sender.send(encoder).expect("Encoder queue update error");
   }
 }
@@ -0,0 +1,129 @@
+use glutin::ElementState::{Pressed, Released};
+use glutin::TouchPhase;
+
+use crate::game::constants::ZOOM_PIXELS_PER_STEP;
+use crate::gfx_app::input::Action;
+use crate::gfx_app::replay::InputEvent;
+
+// Sizes are in logical pixels
+const STICK_DEAD_ZONE: f64 = 20.0;
+const FIRE_BUTTON_SIZE: f64 = 160.0;
+const AIM_DISTANCE: f64 = 200.0;
+
+// The left half of the window is a virtual stick that starts where it is touched, the lower right corner
+// is a fire button. Both turn into the same actions as the keyboard and mouse. Two fingers on the rest
+// of the right half pinch to zoom like the mouse wheel.
+pub struct TouchControls {
+  stick: Option<(u64, (f64, f64))>,
+  fire: Option<u64>,
+  pinch: Vec<(u64, (f64, f64))>,
+  x_action: Option<Action>,
+  y_action: Option<Action>,
+  aim: (f64, f64),
+}
+
+fn axis_action(offset: f64, negative: Action, positive: Action) -> Option<Action> {
+  if offset < -STICK_DEAD_ZONE {
+    Some(negative)
+  } else if offset > STICK_DEAD_ZONE {
+    Some(positive)
+  } else {
+    None
+  }
+}
+
+// Releases the previous direction before pressing the new one, the same order as keys
+fn change_action(current: &mut Option<Action>, next: Option<Action>, events: &mut Vec<InputEvent>) {
+  if *current != next {
+    events.extend(current.map(|action| InputEvent::Key(action, Released)));
+    events.extend(next.map(|action| InputEvent::Key(action, Pressed)));
+    *current = next;
+  }
+}
+
+impl TouchControls {
+  pub fn new() -> TouchControls {
+    TouchControls {
+      stick: None,
+      fire: None,
+      pinch: Vec::new(),
+      x_action: None,
+      y_action: None,
+      aim: (1.0, 0.0),
+    }
+  }
+
+  fn is_fire_button(location: (f64, f64), window_size: (f64, f64)) -> bool {
+    location.0 > window_size.0 - FIRE_BUTTON_SIZE && location.1 > window_size.1 - FIRE_BUTTON_SIZE
+  }
+
+  // Shots go from the screen center towards the last stick direction
+  fn aim_point(&self, window_size: (f64, f64)) -> (f64, f64) {
+    (window_size.0 / 2.0 + self.aim.0 * AIM_DISTANCE, window_size.1 / 2.0 + self.aim.1 * AIM_DISTANCE)
+  }
+
+  fn pinch_distance(&self) -> Option<f64> {
+    match self.pinch.as_slice() {
+      [(_, a), (_, b)] => Some(((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()),
+      _ => None,
+    }
+  }
+
+  pub fn update(&mut self, id: u64, phase: TouchPhase, location: (f64, f64), window_size: (f64, f64)) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+    match phase {
+      TouchPhase::Started if self.fire.is_none() && TouchControls::is_fire_button(location, window_size) => {
+        self.fire = Some(id);
+        events.push(InputEvent::Key(Action::Aim, Pressed));
+        events.push(InputEvent::Key(Action::Confirm, Pressed));
+        events.push(InputEvent::MouseClick(Some(self.aim_point(window_size))));
+      }
+      TouchPhase::Started if self.stick.is_none() && location.0 < window_size.0 / 2.0 => {
+        self.stick = Some((id, location));
+      }
+      TouchPhase::Started if self.pinch.len() < 2 && location.0 >= window_size.0 / 2.0 => {
+        self.pinch.push((id, location));
+      }
+      TouchPhase::Moved if self.pinch.iter().any(|&(pinch_id, _)| pinch_id == id) => {
+        let before = self.pinch_distance();
+        for touch in self.pinch.iter_mut().filter(|(pinch_id, _)| *pinch_id == id) {
+          touch.1 = location;
+        }
+        if let (Some(before), Some(after)) = (before, self.pinch_distance()) {
+          events.push(InputEvent::Zoom((after - before) / ZOOM_PIXELS_PER_STEP));
+        }
+      }
+      TouchPhase::Moved => if let Some((_, origin)) = self.stick.filter(|&(stick_id, _)| stick_id == id) {
+        let offset = (location.0 - origin.0, location.1 - origin.1);
+        let length = (offset.0 * offset.0 + offset.1 * offset.1).sqrt();
+        if length > STICK_DEAD_ZONE {
+          self.aim = (offset.0 / length, offset.1 / length);
+        }
+        change_action(&mut self.x_action, axis_action(offset.0, Action::MoveLeft, Action::MoveRight), &mut events);
+        change_action(&mut self.y_action, axis_action(offset.1, Action::MoveUp, Action::MoveDown), &mut events);
+      },
+      TouchPhase::Ended | TouchPhase::Cancelled => {
+        if self.fire == Some(id) {
+          self.fire = None;
+          events.push(InputEvent::MouseClick(None));
+          events.push(InputEvent::Key(Action::Confirm, Released));
+          events.push(InputEvent::Key(Action::Aim, Released));
+        }
+        self.pinch.retain(|&(pinch_id, _)| pinch_id != id);
+        if self.stick.map(|(stick_id, _)| stick_id) == Some(id) {
+          self.stick = None;
+          change_action(&mut self.x_action, None, &mut events);
+          change_action(&mut self.y_action, None, &mut events);
+        }
+      }
+      _ => (),
+    }
+    events
+  }
+}
+
+impl Default for TouchControls {
+  fn default() -> TouchControls {
+    TouchControls::new()
+  }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel as channel;
+use gfx::{Factory, handle::ShaderResourceView, Resources};
+use image;
+
+use crate::game::constants::ASSET_POLL_INTERVAL;
+use crate::graphics::texture::upload_texture;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureAsset {
+  Terrain,
+  TerrainShape,
+  Character,
+  Zombie,
+  Ammo,
+  House,
+  Tree,
+}
+
+impl TextureAsset {
+  pub const ALL: [TextureAsset; 7] = [TextureAsset::Terrain, TextureAsset::TerrainShape, TextureAsset::Character, TextureAsset::Zombie,
+    TextureAsset::Ammo, TextureAsset::House, TextureAsset::Tree];
+
+  pub fn path(self) -> &'static str {
+    match self {
+      TextureAsset::Terrain => "assets/maps/terrain.png",
+      TextureAsset::TerrainShape => "assets/maps/shape.png",
+      TextureAsset::Character => "assets/character.png",
+      TextureAsset::Zombie => "assets/zombie.png",
+      TextureAsset::Ammo => "assets/maps/ammo.png",
+      TextureAsset::House => "assets/maps/house.png",
+      TextureAsset::Tree => "assets/maps/tree.png",
+    }
+  }
+}
+
+// RGBA pixels of a decoded PNG, ready to be uploaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedImage {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<u8>,
+}
+
+pub fn decode_image(data: &[u8]) -> Result<DecodedImage, String> {
+  let img = image::load(Cursor::new(data), image::PNG).map_err(|e| e.to_string())?.to_rgba();
+  let (width, height) = img.dimensions();
+  Ok(DecodedImage { width, height, pixels: img.into_raw() })
+}
+
+pub fn read_image(path: &str) -> Result<DecodedImage, String> {
+  decode_image(&fs::read(path).map_err(|e| e.to_string())?)
+}
+
+type LoadedImage = (TextureAsset, Result<DecodedImage, String>);
+
+// Every texture is read and decoded on a thread of its own, the results come back as they finish
+pub fn load_images() -> channel::Receiver<LoadedImage> {
+  let (tx, rx) = channel::unbounded();
+  for &asset in TextureAsset::ALL.iter() {
+    let tx = tx.clone();
+    thread::spawn(move || {
+      tx.send((asset, read_image(asset.path()))).expect("Asset loading error");
+    });
+  }
+  rx
+}
+
+// The textures of the draw systems. Only the upload needs the factory, so it happens on the render
+// thread while the loading screen is shown and the draw systems are built once all are in.
+pub struct Assets<R: Resources> {
+  loaded: channel::Receiver<LoadedImage>,
+  textures: HashMap<TextureAsset, ShaderResourceView<R, [f32; 4]>>,
+}
+
+impl<R: Resources> Assets<R> {
+  pub fn load() -> Assets<R> {
+    Assets {
+      loaded: load_images(),
+      textures: HashMap::new(),
+    }
+  }
+
+  // Uploads the images decoded since the last call, waiting up to a loading screen frame for one
+  pub fn poll<F>(&mut self, factory: &mut F) where F: Factory<R> {
+    let first = self.loaded.recv_timeout(Duration::from_millis(ASSET_POLL_INTERVAL)).ok();
+    let images = first.into_iter().chain(self.loaded.try_iter()).collect::<Vec<_>>();
+    for (asset, image) in images {
+      // The game can't be drawn without its textures
+      let image = image.unwrap_or_else(|e| panic!("Couldn't load texture {} {}", asset.path(), e));
+      self.textures.insert(asset, upload_texture(factory, &image));
+    }
+  }
+
+  // Share of the textures uploaded, from 0.0 to 1.0
+  pub fn progress(&self) -> f32 {
+    self.textures.len() as f32 / TextureAsset::ALL.len() as f32
+  }
+
+  pub fn is_loaded(&self) -> bool {
+    self.textures.len() == TextureAsset::ALL.len()
+  }
+
+  pub fn get(&self, asset: TextureAsset) -> Option<ShaderResourceView<R, [f32; 4]>> {
+    self.textures.get(&asset).cloned()
+  }
+
+  pub fn texture(&self, asset: TextureAsset) -> ShaderResourceView<R, [f32; 4]> {
+    self.get(asset).unwrap_or_else(|| panic!("Texture {} is not loaded", asset.path()))
+  }
+}
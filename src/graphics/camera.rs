@@ -1,8 +1,10 @@
 use crossbeam_channel as channel;
-use specs;
-use specs::prelude::WriteStorage;
+use json::JsonValue;
 
-use crate::game::constants::VIEW_DISTANCE;
+use crate::ecs::{self, Read, WriteStorage};
+use crate::game::constants::{CAMERA_MAX_DISTANCE, CAMERA_MIN_DISTANCE, CAMERA_ZOOM_SPEED, CULLING_MARGIN, VIEW_DISTANCE};
+use crate::game::settings::load_config;
+use crate::graphics::DeltaTime;
 use crate::shaders::Position;
 
 #[derive(Clone)]
@@ -18,6 +20,16 @@ impl CameraInputState {
       movement: Position::origin(),
     }
   }
+
+  // Positions are added in clip space before the perspective divide, where w is twice the camera distance
+  pub fn view_extent(&self) -> f32 {
+    self.distance * 2.0 + CULLING_MARGIN
+  }
+
+  pub fn is_visible(&self, position: Position) -> bool {
+    let extent = self.view_extent();
+    position.x().abs() < extent && position.y().abs() < extent
+  }
 }
 
 impl Default for CameraInputState {
@@ -26,14 +38,63 @@ impl Default for CameraInputState {
   }
 }
 
-impl specs::prelude::Component for CameraInputState {
-  type Storage = specs::storage::HashMapStorage<CameraInputState>;
+impl ecs::Component for CameraInputState {
+  type Storage = ecs::DenseVecStorage<CameraInputState>;
+}
+
+const CAMERA_SECTION: &str = "camera";
+
+// Mouse wheel and pinch zoom, read from the camera section of the config file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoomSettings {
+  // Camera distance per wheel line
+  pub sensitivity: f32,
+  // How fast the camera closes in on the wheel target, per second
+  pub smoothing: f32,
+}
+
+impl ZoomSettings {
+  pub fn new() -> ZoomSettings {
+    ZoomSettings {
+      sensitivity: 40.0,
+      smoothing: 10.0,
+    }
+  }
+
+  pub fn load(filename: &str) -> ZoomSettings {
+    ZoomSettings::from_json(&load_config(filename)[CAMERA_SECTION])
+  }
+
+  pub fn from_json(camera: &JsonValue) -> ZoomSettings {
+    let defaults = ZoomSettings::new();
+    ZoomSettings {
+      sensitivity: camera["zoom_sensitivity"].as_f32().unwrap_or(defaults.sensitivity),
+      smoothing: camera["zoom_smoothing"].as_f32().map_or(defaults.smoothing, |s| s.max(0.0)),
+    }
+  }
+}
+
+impl Default for ZoomSettings {
+  fn default() -> ZoomSettings {
+    ZoomSettings::new()
+  }
+}
+
+// Moves a part of the way to the target every step, no smoothing jumps straight there
+pub fn zoom_towards(distance: f32, target: f32, smoothing: f32, delta: f32) -> f32 {
+  if smoothing <= 0.0 {
+    target
+  } else {
+    distance + (target - distance) * (smoothing * delta).min(1.0)
+  }
 }
 
 pub enum CameraControl {
   ZoomOut,
   ZoomIn,
   ZoomStop,
+  // Wheel lines, positive brings the camera closer
+  ZoomBy(f32),
   Left,
   Right,
   Up,
@@ -45,37 +106,60 @@ pub enum CameraControl {
 pub struct CameraControlSystem {
   queue: channel::Receiver<CameraControl>,
   zoom_level: Option<f32>,
+  zoom_settings: ZoomSettings,
+  zoom_target: Option<f32>,
+  pending_zoom: f32,
 }
 
 impl CameraControlSystem {
-  pub fn new() -> (CameraControlSystem, channel::Sender<CameraControl>) {
+  pub fn new(zoom_settings: ZoomSettings) -> (CameraControlSystem, channel::Sender<CameraControl>) {
     let (tx, rx) = channel::unbounded();
     (CameraControlSystem {
       queue: rx,
       zoom_level: None,
+      zoom_settings,
+      zoom_target: None,
+      pending_zoom: 0.0,
     }, tx)
   }
 }
 
-impl<'a> specs::prelude::System<'a> for CameraControlSystem {
-  type SystemData = (WriteStorage<'a, CameraInputState>);
-  fn run(&mut self, mut map_input: Self::SystemData) {
-    use specs::join::Join;
+impl<'a> ecs::System<'a> for CameraControlSystem {
+  type SystemData = (WriteStorage<'a, CameraInputState>,
+                     Read<'a, DeltaTime>);
+  fn run(&mut self, (mut map_input, dt): Self::SystemData) {
+    use crate::ecs::Join;
 
     while let Ok(control) = self.queue.try_recv() {
       match control {
-        CameraControl::ZoomIn => self.zoom_level = Some(2.0),
-        CameraControl::ZoomOut => self.zoom_level = Some(-2.0),
+        CameraControl::ZoomIn => self.zoom_level = Some(CAMERA_ZOOM_SPEED),
+        CameraControl::ZoomOut => self.zoom_level = Some(-CAMERA_ZOOM_SPEED),
         CameraControl::ZoomStop => self.zoom_level = None,
+        CameraControl::ZoomBy(lines) => self.pending_zoom -= lines * self.zoom_settings.sensitivity,
         _ => (),
       }
     }
     if let Some(zoom) = self.zoom_level {
+      // Held zoom keys take over from the wheel
+      self.zoom_target = None;
+      self.pending_zoom = 0.0;
       for m in (&mut map_input).join() {
-        if m.distance > 200.0 && zoom < 0.0 || m.distance < 600.0 && zoom > 0.0 {
-          m.distance += zoom;
+        if m.distance > CAMERA_MIN_DISTANCE && zoom < 0.0 || m.distance < CAMERA_MAX_DISTANCE && zoom > 0.0 {
+          m.distance += zoom * dt.0 as f32;
         }
       }
+    } else if self.zoom_target.is_some() || self.pending_zoom != 0.0 {
+      let pending = std::mem::replace(&mut self.pending_zoom, 0.0);
+      for m in (&mut map_input).join() {
+        let target = (self.zoom_target.unwrap_or(m.distance) + pending).clamp(CAMERA_MIN_DISTANCE, CAMERA_MAX_DISTANCE);
+        m.distance = zoom_towards(m.distance, target, self.zoom_settings.smoothing, dt.0 as f32);
+        self.zoom_target = if (target - m.distance).abs() < 0.5 {
+          m.distance = target;
+          None
+        } else {
+          Some(target)
+        };
+      }
     }
   }
 }
@@ -0,0 +1,229 @@
+use std::f32::consts::PI;
+
+use cgmath::Point2;
+use gfx;
+use gfx::memory::{Bind, Usage};
+use gfx::traits::FactoryExt;
+
+use crate::bullet::BulletDrawable;
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{DEBUG_TILE_RANGE, DEBUG_VERTEX_BUF_LENGTH, PICKUP_BOX, PLAYER_HIT_BOX, ZOMBIE_CHASE_DISTANCE, ZOMBIE_HIT_BOX};
+use crate::graphics::{can_move_to_tile, coords_to_tile, distance, tile_to_coords};
+use crate::graphics::render_graph::{ConstantCache, PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader};
+use crate::graphics::viewport::View;
+use crate::profiler::FrameProfile;
+use crate::shaders::{debug_pipeline, DebugVertex, Position};
+use crate::terrain::path_finding::PathFinder;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::ZombieDrawable;
+
+const SHADER_VERT: &[u8] = include_bytes!("../shaders/debug.v.glsl");
+const SHADER_FRAG: &[u8] = include_bytes!("../shaders/debug.f.glsl");
+
+const WALKABLE_COLOR: [f32; 4] = [0.2, 0.8, 0.3, 0.35];
+const BLOCKED_COLOR: [f32; 4] = [0.95, 0.2, 0.1, 0.7];
+const ZOMBIE_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 0.9];
+const PLAYER_COLOR: [f32; 4] = [0.3, 0.7, 1.0, 0.9];
+const PICKUP_COLOR: [f32; 4] = [0.3, 1.0, 0.9, 0.9];
+const BULLET_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const PATH_COLOR: [f32; 4] = [1.0, 0.4, 1.0, 0.9];
+const CHASE_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 0.6];
+const BULLET_SIZE: f32 = 4.0;
+const WAYPOINT_SIZE: f32 = 3.0;
+const CIRCLE_SEGMENTS: usize = 48;
+
+// Line end points in pairs. They are positioned like the drawables, so an outline sits on what it
+// outlines and moves with the camera the same way.
+#[derive(Default)]
+pub struct DebugShapes {
+  vertices: Vec<DebugVertex>,
+}
+
+impl DebugShapes {
+  pub fn clear(&mut self) {
+    self.vertices.clear();
+  }
+
+  pub fn vertices(&self) -> &[DebugVertex] {
+    &self.vertices
+  }
+
+  pub fn line(&mut self, from: Position, to: Position, color: [f32; 4]) {
+    self.vertices.push(DebugVertex::new(from, color));
+    self.vertices.push(DebugVertex::new(to, color));
+  }
+
+  // Goes through the corners in order and back to the first
+  pub fn outline(&mut self, corners: &[Position], color: [f32; 4]) {
+    for (idx, &corner) in corners.iter().enumerate() {
+      self.line(corner, corners[(idx + 1) % corners.len()], color);
+    }
+  }
+
+  pub fn rect(&mut self, center: Position, half_extents: [f32; 2], color: [f32; 4]) {
+    let [w, h] = half_extents;
+    self.outline(&[center + Position::new(-w, -h), center + Position::new(w, -h),
+                   center + Position::new(w, h), center + Position::new(-w, h)], color);
+  }
+
+  pub fn cross(&mut self, center: Position, size: f32, color: [f32; 4]) {
+    self.line(center + Position::new(-size, -size), center + Position::new(size, size), color);
+    self.line(center + Position::new(-size, size), center + Position::new(size, -size), color);
+  }
+
+  pub fn circle(&mut self, center: Position, radius: f32, color: [f32; 4]) {
+    let point = |idx: usize| {
+      let angle = idx as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+      center + Position::new(radius * angle.cos(), radius * angle.sin())
+    };
+    for idx in 0..CIRCLE_SEGMENTS {
+      self.line(point(idx), point(idx + 1), color);
+    }
+  }
+}
+
+// Where a point of the tile grid is drawn, the first player stands at the origin
+fn tile_position(tile: Point2<f32>, movement: Position) -> Position {
+  movement - tile_to_coords(tile)
+}
+
+// The tiles around the first player, outlined green where zombies can walk and crossed out red
+// where they can't
+pub fn tile_grid(movement: Position, shapes: &mut DebugShapes) {
+  let center = coords_to_tile(movement);
+  for x in center.x - DEBUG_TILE_RANGE..=center.x + DEBUG_TILE_RANGE {
+    for y in center.y - DEBUG_TILE_RANGE..=center.y + DEBUG_TILE_RANGE {
+      let (x, y) = (x as f32, y as f32);
+      let corners = [tile_position(Point2::new(x, y), movement), tile_position(Point2::new(x + 1.0, y), movement),
+                     tile_position(Point2::new(x + 1.0, y + 1.0), movement), tile_position(Point2::new(x, y + 1.0), movement)];
+      if can_move_to_tile(tile_to_coords(Point2::new(x + 0.5, y + 0.5))) {
+        shapes.outline(&corners, WALKABLE_COLOR);
+      } else {
+        shapes.outline(&corners, BLOCKED_COLOR);
+        shapes.line(corners[0], corners[2], BLOCKED_COLOR);
+        shapes.line(corners[1], corners[3], BLOCKED_COLOR);
+      }
+    }
+  }
+}
+
+// Collects the shapes of the first player's surroundings while they are toggled on
+#[derive(Default)]
+pub struct DebugShapesSystem {
+  paths: PathFinder,
+  route: Vec<Point2<i32>>,
+}
+
+impl DebugShapesSystem {
+  // The route a chasing zombie takes to the player, through the middle of each tile on it
+  fn chase_route(&mut self, position: Position, movement: Position, shapes: &mut DebugShapes) {
+    self.paths.route(coords_to_tile(movement - position), coords_to_tile(movement), &mut self.route);
+    let mut from = position;
+    for tile in &self.route {
+      let waypoint = tile_position(Point2::new(tile.x as f32 + 0.5, tile.y as f32 + 0.5), movement);
+      shapes.line(from, waypoint, PATH_COLOR);
+      shapes.rect(waypoint, [WAYPOINT_SIZE, WAYPOINT_SIZE], PATH_COLOR);
+      from = waypoint;
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for DebugShapesSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, BulletDrawable>,
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     Read<'a, FrameProfile>,
+                     Write<'a, DebugShapes>);
+
+  fn run(&mut self, (players, character_input, zombies, bullets, terrain_objects, profile, mut shapes): Self::SystemData) {
+    use crate::ecs::Join;
+
+    shapes.clear();
+    if !profile.is_debug_shapes_visible {
+      return;
+    }
+    let movement = match (&players, &character_input).join().find(|(p, _)| p.index == 0) {
+      Some((_, ci)) => ci.movement,
+      None => return,
+    };
+
+    tile_grid(movement, &mut shapes);
+    // Every zombie inside the circle runs after the player
+    shapes.circle(Position::origin(), ZOMBIE_CHASE_DISTANCE, CHASE_COLOR);
+    shapes.rect(Position::origin(), PLAYER_HIT_BOX, PLAYER_COLOR);
+
+    for z in zombies.join().filter(|z| !z.is_dead()) {
+      let position = z.transform.position;
+      shapes.rect(position, ZOMBIE_HIT_BOX, ZOMBIE_COLOR);
+      if distance(position.x(), position.y()) < ZOMBIE_CHASE_DISTANCE {
+        self.chase_route(position, movement, &mut shapes);
+      }
+    }
+    for b in bullets.join() {
+      shapes.cross(b.transform.position, BULLET_SIZE, BULLET_COLOR);
+    }
+    for o in terrain_objects.join().filter(|o| o.object_type == TerrainTexture::Ammo) {
+      shapes.rect(o.transform.position, PICKUP_BOX, PICKUP_COLOR);
+    }
+  }
+}
+
+pub struct DebugShapesPass<R: gfx::Resources> {
+  bundle: gfx::pso::bundle::Bundle<R, debug_pipeline::Data<R>>,
+  pso_updates: PipelineUpdates<R, debug_pipeline::Meta>,
+  offset: ConstantCache<Position>,
+}
+
+impl<R: gfx::Resources> DebugShapesPass<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                reloader: &mut ShaderReloader<F>) -> DebugShapesPass<R>
+    where F: gfx::Factory<R> {
+    let (pso, pso_updates) = PassDescriptor::new("Debug shapes",
+                                                 ShaderFile::new("debug.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("debug.f.glsl", SHADER_FRAG),
+                                                 debug_pipeline::new())
+      .with_primitive(gfx::Primitive::LineList)
+      .build(factory, reloader);
+
+    let pipeline_data = debug_pipeline::Data {
+      vbuf: factory.create_buffer(DEBUG_VERTEX_BUF_LENGTH, gfx::buffer::Role::Vertex, Usage::Dynamic, Bind::empty())
+        .expect("Debug shape vertex buffer creation error"),
+      globals: targets.globals.clone(),
+      offset_cb: factory.create_constant_buffer(1),
+      scissor: targets.scissor(),
+      out_color: targets.color.clone(),
+    };
+    let slice = gfx::Slice {
+      start: 0,
+      end: 0,
+      base_vertex: 0,
+      instances: None,
+      buffer: gfx::IndexBuffer::Auto,
+    };
+
+    DebugShapesPass {
+      bundle: gfx::Bundle::new(slice, pso, pipeline_data),
+      pso_updates,
+      offset: ConstantCache::default(),
+    }
+  }
+
+  pub fn draw<C>(&mut self,
+                 shapes: &DebugShapes,
+                 view: &View,
+                 encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    self.pso_updates.apply(&mut self.bundle.pso);
+    self.bundle.data.scissor = view.scissor;
+    self.offset.update(&self.bundle.data.offset_cb, view.position(Position::origin()), encoder);
+    for chunk in shapes.vertices().chunks(DEBUG_VERTEX_BUF_LENGTH) {
+      encoder.update_buffer(&self.bundle.data.vbuf, chunk, 0).expect("Debug shape vertex update error");
+      self.bundle.slice.end = chunk.len() as gfx::VertexCount;
+      self.bundle.encode(encoder);
+    }
+  }
+}
@@ -1,30 +1,75 @@
 use cgmath;
-use cgmath::{Matrix4, Point3, Vector3};
+use cgmath::{Angle, Deg, Matrix4, Point2, Point3, Vector3};
 
 use crate::graphics::camera::CameraInputState;
-use crate::shaders::Projection;
+use crate::shaders::{Position, Projection};
+
+const FIELD_OF_VIEW: f32 = 75.0;
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 4000.0;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ProjectionMode {
+  #[default]
+  Perspective,
+  Orthographic,
+}
 
 #[derive(Clone, Default)]
 pub struct Dimensions {
   pub window_width: f32,
   pub window_height: f32,
   pub hidpi_factor: f32,
+  pub projection_mode: ProjectionMode,
+  pub split_screen: bool,
 }
 
 impl Dimensions {
-  pub fn new(window_width: f32, window_height: f32, hidpi_val: f32, is_windowed: bool) -> Dimensions {
+  pub fn new(window_width: f32, window_height: f32, hidpi_val: f32, is_windowed: bool, projection_mode: ProjectionMode,
+             split_screen: bool) -> Dimensions {
     let hidpi_factor = if is_windowed { 1.0 } else { hidpi_val };
     Dimensions {
       window_width,
       window_height,
       hidpi_factor,
+      projection_mode,
+      split_screen,
     }
   }
 
   pub fn world_to_projection(&self, input: &CameraInputState) -> Projection {
     let view: Matrix4<f32> = get_view_matrix(input.distance);
     let aspect_ratio = self.window_width / self.window_height;
-    get_projection(view, aspect_ratio)
+    match self.projection_mode {
+      ProjectionMode::Perspective => get_projection(view, aspect_ratio),
+      ProjectionMode::Orthographic => get_orthographic_projection(view, aspect_ratio, input.distance),
+    }
+  }
+
+  // Window events report logical sizes, the dimensions are kept in the same units as at start up
+  pub fn resize(&mut self, logical_width: f32, logical_height: f32) {
+    self.window_width = logical_width * self.hidpi_factor;
+    self.window_height = logical_height * self.hidpi_factor;
+  }
+
+  // Inverse of the sprite placement: positions are added in clip space, where w is twice the camera distance
+  // with both projections. Returns the position relative to the first player, the space drawables are in.
+  pub fn screen_to_world(&self, cursor: Point2<f32>, input: &CameraInputState) -> Position {
+    let ndc_x = cursor.x * self.hidpi_factor / self.window_width * 2.0 - 1.0;
+    let ndc_y = 1.0 - cursor.y * self.hidpi_factor / self.window_height * 2.0;
+    let eye_distance = input.distance * 2.0;
+    // The first player's split view is shifted left by a quarter screen
+    let screen_shift = if self.split_screen { input.distance } else { 0.0 };
+    Position::new(ndc_x * eye_distance + screen_shift, ndc_y * eye_distance)
+  }
+
+  // Window pixels of a position relative to the first player, the inverse of screen_to_world
+  pub fn world_to_screen(&self, position: Position, input: &CameraInputState) -> Point2<f32> {
+    let eye_distance = input.distance * 2.0;
+    let screen_shift = if self.split_screen { input.distance } else { 0.0 };
+    let ndc_x = (position.x() - screen_shift) / eye_distance;
+    let ndc_y = position.y() / eye_distance;
+    Point2::new((ndc_x + 1.0) / 2.0 * self.window_width, (1.0 - ndc_y) / 2.0 * self.window_height)
   }
 }
 
@@ -32,7 +77,21 @@ pub fn get_projection(view: Matrix4<f32>, aspect_ratio: f32) -> Projection {
   Projection {
     model: view.into(),
     view: view.into(),
-    proj: cgmath::perspective(cgmath::Deg(75.0f32), aspect_ratio, 0.1, 4000.0).into(),
+    proj: cgmath::perspective(Deg(FIELD_OF_VIEW), aspect_ratio, NEAR_PLANE, FAR_PLANE).into(),
+  }
+}
+
+pub fn get_orthographic_projection(view: Matrix4<f32>, aspect_ratio: f32, view_distance: f32) -> Projection {
+  // Model and view both translate by the view distance, so the world plane sits twice as far from the eye.
+  let eye_distance = view_distance * 2.0;
+  let half_height = eye_distance * Angle::tan(Deg(FIELD_OF_VIEW / 2.0));
+  let half_width = half_height * aspect_ratio;
+  // Scaling by eye distance keeps clip w equal to the perspective one, which the shaders' position offsets rely on
+  let proj = cgmath::ortho(-half_width, half_width, -half_height, half_height, NEAR_PLANE, FAR_PLANE) * eye_distance;
+  Projection {
+    model: view.into(),
+    view: view.into(),
+    proj: proj.into(),
   }
 }
 
@@ -0,0 +1,70 @@
+use tracing::info;
+
+use crate::ecs::{self, Read, Write};
+use crate::game::constants::GOVERNOR_HEAT_HAZE_LIMITS;
+use crate::game::settings::{GovernorSettings, Settings};
+use crate::profiler::FrameProfile;
+
+// Quality steps the frame governor goes through, 0 is full quality
+pub const GOVERNOR_LEVELS: usize = GOVERNOR_HEAT_HAZE_LIMITS.len();
+
+// Lowers the quality one step at a time while frames stay over budget and raises it again once
+// they have room to spare. The counts need a streak, so a single slow frame changes nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameGovernor {
+  pub level: usize,
+  over_budget: u32,
+  under_headroom: u32,
+}
+
+impl FrameGovernor {
+  // Returns the new level when it changed
+  pub fn update(&mut self, frame_ms: f64, settings: &GovernorSettings) -> Option<usize> {
+    if !settings.enabled {
+      let was_lowered = self.level > 0;
+      *self = FrameGovernor::default();
+      return if was_lowered { Some(0) } else { None };
+    }
+    self.over_budget = if frame_ms > settings.budget_ms { self.over_budget + 1 } else { 0 };
+    self.under_headroom = if frame_ms < settings.budget_ms * settings.headroom { self.under_headroom + 1 } else { 0 };
+
+    let level = if self.over_budget >= settings.downgrade_frames {
+      (self.level + 1).min(GOVERNOR_LEVELS - 1)
+    } else if self.under_headroom >= settings.restore_frames {
+      self.level.saturating_sub(1)
+    } else {
+      return None;
+    };
+    // The next step needs a streak of its own, measured with the new quality
+    self.over_budget = 0;
+    self.under_headroom = 0;
+    if level == self.level {
+      return None;
+    }
+    self.level = level;
+    Some(level)
+  }
+
+  // Bullets whose heat haze is drawn per view
+  pub fn heat_haze_limit(&self) -> usize {
+    GOVERNOR_HEAT_HAZE_LIMITS[self.level]
+  }
+}
+
+// Runs after the profiler took the time of the frame
+pub struct GovernorSystem;
+
+impl<'a> ecs::System<'a> for GovernorSystem {
+  type SystemData = (Read<'a, FrameProfile>,
+                     Read<'a, Settings>,
+                     Write<'a, FrameGovernor>);
+
+  fn run(&mut self, (profile, settings, mut governor): Self::SystemData) {
+    // The smoothed frame time, the raw one jumps with every hiccup
+    if let Some(level) = governor.update(profile.frame_ms(), &settings.video.governor) {
+      if cfg!(feature = "framerate") {
+        info!("Frame governor quality level {} at {:.2} ms/frame", level, profile.frame_ms());
+      }
+    }
+  }
+}
@@ -122,3 +122,2882 @@ fn tile_to_coords_test() {
 
   assert_eq!(coords_to_tile(left), Point2::new(1, 126), "Left corner");
 }
+
+#[test]
+fn orthographic_projection_test() {
+  use cgmath::{Matrix4, Vector4};
+  use crate::graphics::dimensions::{get_orthographic_projection, get_projection, get_view_matrix};
+
+  fn to_ndc(projection: crate::shaders::Projection, point: Vector4<f32>) -> (f32, f32) {
+    let clip = Matrix4::from(projection.proj) * Matrix4::from(projection.view) * Matrix4::from(projection.model) * point;
+    (clip.x / clip.w, clip.y / clip.w)
+  }
+
+  let view = get_view_matrix(300.0);
+  let point = Vector4::new(120.0, -80.0, 0.0, 1.0);
+  let (persp_x, persp_y) = to_ndc(get_projection(view, 16.0 / 9.0), point);
+  let (ortho_x, ortho_y) = to_ndc(get_orthographic_projection(view, 16.0 / 9.0, 300.0), point);
+
+  assert!((persp_x - ortho_x).abs() < 0.0001, "Ground plane x should match perspective scale");
+  assert!((persp_y - ortho_y).abs() < 0.0001, "Ground plane y should match perspective scale");
+}
+
+#[test]
+fn camera_visibility_test() {
+  use crate::graphics::camera::CameraInputState;
+  use crate::shaders::Position;
+
+  let camera = CameraInputState::new();
+  assert!(camera.is_visible(Position::origin()), "player position should be visible");
+  assert!(camera.is_visible(Position::new(500.0, -500.0)), "screen corner should be visible");
+  assert!(!camera.is_visible(Position::new(2000.0, 0.0)), "far away position should be culled");
+}
+
+#[test]
+fn shader_preprocessor_test() {
+  use crate::shaders::preprocessor::preprocess;
+
+  let source = b"#version 150 core\n#include \"lighting.glsl\"\nvoid main() {}\n";
+  let output = String::from_utf8(preprocess(source, &[("MAX_LIGHTS", "4".to_string())]).unwrap()).unwrap();
+
+  assert!(output.starts_with("#version 150 core\n#define MAX_LIGHTS 4\n"), "defines should follow the version line");
+  assert_eq!(output.matches("uniform b_Globals").count(), 1, "nested include should be expanded once");
+  assert!(!output.contains("#include"), "include directives should be replaced");
+  assert!(preprocess(b"#include \"missing.glsl\"\n", &[]).is_err(), "unknown include should fail");
+}
+
+#[test]
+fn tile_flip_encoding_test() {
+  use crate::terrain::tile_map::{encode_tile, TILE_FLAGS_OFFSET};
+
+  let offset = TILE_FLAGS_OFFSET as f32;
+  assert_eq!(encode_tile(5), 4.0, "plain tile should map to zero based index");
+  assert_eq!(encode_tile(0x8000_0005), 4.0 + offset, "horizontal flip should set the first flag");
+  assert_eq!(encode_tile(0x6000_0005), 4.0 + 6.0 * offset, "vertical and diagonal flips should combine");
+}
+
+#[test]
+fn world_snapshot_round_trip_test() {
+  use crate::game::snapshot::{PlayerSnapshot, WorldSnapshot};
+  use crate::graphics::orientation::Stance;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let snapshot = WorldSnapshot {
+    players: vec![PlayerSnapshot { index: 0, movement: Position::new(12.0, -4.0), stance: Stance::Firing, health: 40.0, ammunition: 3, magazines: 1 }],
+    zombies: vec![ZombieDrawable::new(Position::new(500.0, 40.0))],
+    pickups: vec![Position::new(-20.0, 30.0)],
+  };
+  let parsed = WorldSnapshot::parse(&snapshot.stringify()).unwrap();
+
+  assert_eq!(parsed.players[0].movement, Position::new(12.0, -4.0), "player position should survive a round trip");
+  assert!(parsed.players[0].stance == Stance::Firing, "player stance should survive a round trip");
+  assert_eq!(parsed.players[0].health, 40.0, "player health should survive a round trip");
+  assert_eq!(parsed.players[0].ammunition, 3, "player ammunition should survive a round trip");
+  assert_eq!(parsed.zombies[0].transform.position, Position::new(500.0, 40.0), "zombie position should survive a round trip");
+  assert_eq!(parsed.pickups.len(), 1, "pickups should survive a round trip");
+  assert!(WorldSnapshot::parse("{\"players\": 1}").is_err(), "malformed snapshot should fail");
+}
+
+#[test]
+fn prefab_instantiate_test() {
+  use crate::critter::palette::CritterPalette;
+  use crate::game::prefab::{Instance, Prefabs};
+  use crate::shaders::Position;
+
+  let prefabs = Prefabs::from_json(&json::parse(r#"{
+    "brute": { "zombie": {}, "health": { "max": 3.0 }, "palette": 2 },
+    "crate": { "terrain_object": "ammo" }
+  }"#).unwrap()).unwrap();
+
+  match prefabs.get("brute").unwrap().instantiate(Position::new(10.0, 20.0)) {
+    Instance::Zombie(z) => {
+      assert_eq!(z.health.max, 3.0, "prefab health should override the default");
+      assert!(z.palette == CritterPalette::Frozen, "prefab palette should be applied");
+      assert_eq!(z.transform.position, Position::new(10.0, 20.0), "instance should spawn at the given position");
+    }
+    _ => panic!("brute should be a zombie"),
+  }
+  assert!(prefabs.get("crate").is_some(), "terrain object prefab should load");
+  assert!(Prefabs::from_json(&json::parse(r#"{ "empty": {} }"#).unwrap()).is_err(), "prefab without archetype should fail");
+}
+
+#[test]
+fn transform_propagation_test() {
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::graphics::transform::{Parent, Transform, TransformPropagationSystem};
+  use crate::shaders::Position;
+
+  let mut world = World::new();
+  world.register::<Transform>();
+  world.register::<Parent>();
+
+  let root = world.create_entity().with(Transform::new(Position::new(100.0, 50.0)).with_rotation(std::f32::consts::PI / 2.0)).build();
+  let weapon = world.create_entity().with(Transform::new(Position::origin())).with(Parent::new(root, Position::new(10.0, 0.0))).build();
+  let sight = world.create_entity().with(Transform::new(Position::origin())).with(Parent::new(weapon, Position::new(5.0, 0.0))).build();
+
+  TransformPropagationSystem.run_now(&world);
+  world.maintain();
+
+  let transforms = world.read_storage::<Transform>();
+  let sight_position = transforms.get(sight).unwrap().position;
+  assert!((sight_position.x() - 100.0).abs() < 0.001 && (sight_position.y() - 65.0).abs() < 0.001,
+          "chained attachment should follow the rotated parent");
+  drop(transforms);
+
+  world.delete_entity(root).unwrap();
+  TransformPropagationSystem.run_now(&world);
+  world.maintain();
+  assert!(!world.is_alive(weapon) && !world.is_alive(sight), "attachments should despawn with their parent");
+}
+
+#[test]
+fn seeded_rng_test() {
+  use crate::game::{GameRng, get_rand_from_range};
+
+  let mut a = GameRng::new(Some(42));
+  let mut b = GameRng::new(Some(42));
+  let rolls_a = (0..16).map(|_| get_rand_from_range(&mut a, 0, 359)).collect::<Vec<i32>>();
+  let rolls_b = (0..16).map(|_| get_rand_from_range(&mut b, 0, 359)).collect::<Vec<i32>>();
+  assert_eq!(rolls_a, rolls_b, "same seed should produce the same rolls");
+}
+
+#[test]
+fn spatialize_test() {
+  use crate::audio::mixer::spatialize;
+  use crate::shaders::Position;
+
+  let (near, center) = spatialize(Position::origin());
+  let (far, left) = spatialize(Position::new(-900.0, 0.0));
+  assert!((near - 1.0).abs() < 0.001 && center.abs() < 0.001, "sounds at the listener should be centered at full volume");
+  assert!(far < near && (left + 1.0).abs() < 0.001, "sounds far to the left should be quieter and panned left");
+}
+
+#[test]
+fn input_map_test() {
+  use glutin::VirtualKeyCode;
+  use crate::gfx_app::input::{Action, InputMap};
+
+  let input_map = InputMap::from_json(&object! { "move_up" => array!["Z"], "move_left" => array!["Q"] });
+  assert_eq!(Some(Action::MoveUp), input_map.action(VirtualKeyCode::Z), "rebound key should trigger its action");
+  assert_eq!(None, input_map.action(VirtualKeyCode::W), "replaced default key should be unbound");
+  assert_eq!(Some(Action::MoveDown), input_map.action(VirtualKeyCode::S), "actions missing from the config keep their defaults");
+
+  let saved = InputMap::from_json(&input_map.to_json());
+  assert_eq!(Some(Action::MoveLeft), saved.action(VirtualKeyCode::Q), "bindings should survive a save and load");
+}
+
+#[test]
+fn screen_to_world_test() {
+  use cgmath::{Matrix4, Point2, Vector4};
+  use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, ProjectionMode}};
+  use crate::shaders::Position;
+
+  // Places a sprite like the vertex shaders do and picks it back from the resulting window coordinates
+  fn round_trip(dim: &Dimensions, camera: &CameraInputState, position: Position) -> Position {
+    let projection = dim.world_to_projection(camera);
+    let clip = Matrix4::from(projection.proj) * Matrix4::from(projection.view) * Matrix4::from(projection.model) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let ndc = Point2::new((clip.x + position.x()) / clip.w, (clip.y + position.y()) / clip.w);
+    let cursor = Point2::new((ndc.x + 1.0) / 2.0 * dim.window_width, (1.0 - ndc.y) / 2.0 * dim.window_height);
+    dim.screen_to_world(cursor, camera)
+  }
+
+  let position = Position::new(240.0, -130.0);
+  let mut camera = CameraInputState::new();
+  for &mode in &[ProjectionMode::Perspective, ProjectionMode::Orthographic] {
+    let mut dim = Dimensions::new(1920.0, 1080.0, 1.0, true, mode, false);
+    for &(distance, width, height) in &[(300.0, 1920.0, 1080.0), (550.0, 1920.0, 1080.0), (300.0, 800.0, 600.0)] {
+      camera.distance = distance;
+      dim.resize(width, height);
+      let picked = round_trip(&dim, &camera, position);
+      assert!((picked.x() - position.x()).abs() < 0.01 && (picked.y() - position.y()).abs() < 0.01,
+              "Picked position should match at distance {} and size {}x{}", distance, width, height);
+    }
+  }
+}
+
+#[test]
+fn input_event_test() {
+  use glutin::ElementState;
+  use crate::gfx_app::input::Action;
+  use crate::gfx_app::replay::InputEvent;
+
+  let events = [
+    InputEvent::Key(Action::Reload, ElementState::Pressed),
+    InputEvent::Key(Action::MoveLeft, ElementState::Released),
+    InputEvent::MouseClick(Some((412.0, 96.5))),
+    InputEvent::MouseClick(None),
+    InputEvent::MouseMove((10.0, 20.0)),
+  ];
+  for &event in &events {
+    assert_eq!(Ok(event), InputEvent::from_json(&event.to_json()), "recorded input should replay unchanged");
+  }
+}
+
+#[test]
+fn input_context_test() {
+  use crate::gfx_app::input::{Action, InputContext, InputContexts};
+
+  let mut contexts = InputContexts::new();
+  assert_eq!(Some(InputContext::Gameplay), contexts.route(Action::MoveUp), "gameplay gets input when nothing is open");
+
+  contexts.push(InputContext::Console);
+  contexts.push(InputContext::Menu);
+  assert_eq!(InputContext::Console, contexts.active(), "a menu opened under the console should not take the focus");
+  assert_eq!(None, contexts.route(Action::MoveUp), "the console should capture movement");
+  assert_eq!(Some(InputContext::Console), contexts.route(Action::Pause), "the console closes with pause");
+
+  contexts.remove(InputContext::Console);
+  assert_eq!(Some(InputContext::Menu), contexts.route(Action::MoveUp), "the menu navigates with movement");
+  assert_eq!(None, contexts.route(Action::Reload), "the menu should capture gameplay actions");
+}
+
+#[test]
+fn input_buffer_test() {
+  use cgmath::Point2;
+  use crate::game::constants::INPUT_BUFFER_STEPS;
+  use crate::game::input_buffer::{BufferedAction, InputBuffer};
+
+  let is_reload = |a| a == BufferedAction::Reload;
+  let mut buffer = InputBuffer::new();
+  buffer.press(0, BufferedAction::Fire(Point2::new(1.0, 2.0)));
+  buffer.press(0, BufferedAction::Reload);
+  assert_eq!(None, buffer.consume(1, is_reload), "presses belong to their player");
+  assert_eq!(Some(BufferedAction::Reload), buffer.consume(0, is_reload), "a press should wait for a step to consume it");
+  assert_eq!(None, buffer.consume(0, is_reload), "a press is consumed once");
+
+  for _ in 0..INPUT_BUFFER_STEPS {
+    buffer.expire();
+  }
+  assert!(buffer.consume(0, |a| a != BufferedAction::Reload).is_some(), "a press should survive the buffer window");
+
+  buffer.press(0, BufferedAction::Reload);
+  for _ in 0..=INPUT_BUFFER_STEPS {
+    buffer.expire();
+  }
+  assert_eq!(None, buffer.consume(0, is_reload), "stale presses should be dropped");
+}
+
+#[test]
+fn zoom_test() {
+  use crate::graphics::camera::{zoom_towards, ZoomSettings};
+
+  assert_eq!(250.0, zoom_towards(200.0, 300.0, 10.0, 0.05), "smoothing should move part of the way");
+  assert_eq!(300.0, zoom_towards(200.0, 300.0, 10.0, 1.0), "smoothing should not overshoot the target");
+  assert_eq!(300.0, zoom_towards(200.0, 300.0, 0.0, 0.05), "no smoothing should jump to the target");
+
+  let settings = ZoomSettings::from_json(&object! { "zoom_sensitivity" => 10.0 });
+  assert_eq!(10.0, settings.sensitivity);
+  assert_eq!(ZoomSettings::new().smoothing, settings.smoothing, "missing settings should keep the defaults");
+}
+
+#[test]
+fn status_hud_test() {
+  use crate::game::health::Health;
+  use crate::game::status::{Stamina, StatusEffect, StatusEffects};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::status_bars::status_quads;
+
+  let mut effects = StatusEffects::new();
+  effects.apply(StatusEffect::Hurt, 1.0);
+  effects.apply(StatusEffect::Hurt, 0.5);
+  effects.tick(0.75);
+  assert_eq!(vec![StatusEffect::Hurt], effects.active().collect::<Vec<_>>(), "reapplying should keep the longer duration");
+  effects.tick(0.5);
+  assert_eq!(0, effects.active().count(), "effects should expire");
+
+  let mut stamina = Stamina::new(1.0);
+  assert!(!stamina.update(true, 0.01));
+  assert!(stamina.update(true, 1.0), "running out of stamina should be reported");
+  assert!(!stamina.update(true, 1.0), "running out is reported once");
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut health = Health::new(100.0);
+  health.take_damage(25.0);
+  effects.apply(StatusEffect::Exhausted, 1.0);
+  let quads = status_quads(0, &dim, &health, &Stamina::new(100.0), &effects);
+  assert_eq!(5, quads.len(), "two bars with backgrounds and one effect icon");
+  assert_eq!(quads[0].rect[2] * 0.75, quads[1].rect[2], "the health bar should show the remaining health");
+  assert!(status_quads(1, &dim, &health, &Stamina::new(100.0), &effects)[0].rect[0] > 400.0, "the second player is on the right");
+}
+
+#[test]
+fn reload_test() {
+  use crate::character::character_stats::CharacterStats;
+  use crate::game::constants::{MAGAZINE_SIZE, RELOAD_DURATION};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::ammo_panel::ammo_quads;
+
+  let mut stats = CharacterStats::new();
+  assert!(!stats.can_reload(), "a full magazine is not reloaded");
+  stats.ammunition = 3;
+  stats.start_reload();
+  stats.update_reload(RELOAD_DURATION / 2.0);
+  assert_eq!(Some(0.5), stats.reload_progress());
+  assert_eq!(3, stats.ammunition, "the magazine is swapped when the reload finishes");
+  assert!(!stats.can_reload(), "a reload in progress can't be restarted");
+  stats.update_reload(RELOAD_DURATION);
+  assert_eq!((MAGAZINE_SIZE, 0, None), (stats.ammunition, stats.magazines, stats.reload_progress()));
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let idle = ammo_quads(0, &dim, &stats);
+  stats.magazines = 1;
+  stats.ammunition = 0;
+  stats.start_reload();
+  assert!(ammo_quads(0, &dim, &stats).len() < idle.len(), "rounds should turn into a progress bar while reloading");
+}
+
+#[test]
+fn pause_ducking_test() {
+  use crate::audio::mixer::{Bus, Mixer};
+  use crate::game::constants::DUCKED_GAIN;
+
+  let mut mixer = Mixer::new();
+  mixer.is_ducked = true;
+  assert_eq!(DUCKED_GAIN, mixer.bus_gain(Bus::Music), "music should duck under the pause menu");
+  assert_eq!(1.0, mixer.bus_gain(Bus::Interface), "menu sounds should stay at full volume");
+}
+
+#[test]
+fn settings_test() {
+  use crate::game::settings::{Difficulty, RESOLUTIONS, Settings};
+
+  let mut settings = Settings::new();
+  settings.video.fullscreen = false;
+  settings.video.cycle_resolution(1);
+  settings.difficulty = settings.difficulty.cycle(1);
+  let config = object! {
+    "audio" => object! { "master" => settings.master_volume },
+    "video" => settings.video.to_json(),
+    "gameplay" => object! { "difficulty" => settings.difficulty.name() },
+  };
+  assert_eq!(settings, Settings::from_json(&config));
+  assert_eq!(Difficulty::Hard, settings.difficulty);
+  assert_eq!(Difficulty::Easy, Difficulty::Hard.cycle(1), "difficulty should wrap around");
+
+  settings.video.resolution = (123, 45);
+  settings.video.cycle_resolution(-1);
+  assert_eq!(RESOLUTIONS[0], settings.video.resolution, "an unlisted size should step to a listed one");
+}
+
+#[test]
+fn score_streak_test() {
+  use crate::game::constants::{SCORE_PER_KILL, STREAK_STEP, STREAK_WINDOW};
+  use crate::game::localization::Localization;
+  use crate::game::score::Score;
+  use crate::game::settings::Language;
+
+  let mut score = Score::new();
+  for _ in 0..STREAK_STEP {
+    score.register_kill();
+  }
+  assert_eq!(2, score.multiplier());
+  assert_eq!(SCORE_PER_KILL * (STREAK_STEP + 1), score.score, "the kill that reaches a step already counts double");
+
+  score.tick(STREAK_WINDOW);
+  assert_eq!((1, 0, STREAK_STEP), (score.multiplier(), score.streak, score.best_streak), "a pause between kills should end the streak");
+
+  score.wave = 0;
+  let summary = score.summary(&Localization::load(Language::English));
+  assert!(summary.contains(&"waves cleared 0".to_string()), "a save from before the first wave should have no cleared waves");
+}
+
+#[test]
+fn damage_indicator_test() {
+  use std::f32::consts::FRAC_PI_2;
+  use crate::game::constants::DAMAGE_INDICATOR_FADE;
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::damage_indicator::{DamageIndicators, indicator_quads};
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let right = indicator_quads(0.0, 1.0, &dim);
+  assert!(right.iter().all(|q| q.rect[0] + q.rect[2] == 800.0), "an attacker on the right should mark the right edge");
+  let above = indicator_quads(FRAC_PI_2, 1.0, &dim);
+  assert!(above.iter().all(|q| q.rect[1] == 0.0), "an attacker above should mark the top edge");
+
+  let mut indicators = DamageIndicators::default();
+  indicators.hit(0.0);
+  indicators.hit(0.01);
+  assert_eq!(right.len(), indicators.quads(&dim).len(), "hits from the same direction should share an arc");
+  indicators.tick(DAMAGE_INDICATOR_FADE);
+  assert!(indicators.quads(&dim).is_empty());
+}
+
+#[test]
+fn crosshair_spread_test() {
+  use crate::character::character_stats::CharacterStats;
+  use crate::game::constants::{MAGAZINE_SIZE, MAX_SPREAD, SHOT_SPREAD, SPREAD_RECOVERY};
+  use crate::hud::crosshair::crosshair_quads;
+
+  let mut stats = CharacterStats::new();
+  stats.fire();
+  assert_eq!(SHOT_SPREAD, stats.spread);
+  for _ in 0..MAGAZINE_SIZE - 1 {
+    stats.fire();
+  }
+  assert_eq!(MAX_SPREAD, stats.spread, "spread should stop growing at the maximum");
+  stats.recover_spread(MAX_SPREAD / SPREAD_RECOVERY);
+  assert_eq!(0.0, stats.spread);
+
+  let still = crosshair_quads(100.0, 100.0, 0.0, false, 1.0);
+  let spread = crosshair_quads(100.0, 100.0, MAX_SPREAD, true, 1.0);
+  assert!(spread[0].rect[0] < still[0].rect[0], "spread should push the arms out");
+  assert_ne!(still[0].color, spread[0].color, "a target under the crosshair should tint it");
+}
+
+#[test]
+fn glyph_cache_test() {
+  use crate::hud::font::load_font;
+  use crate::hud::text::{GlyphCache, layout_text, TextAlign, UiText};
+
+  let font = load_font();
+  let mut cache = GlyphCache::new();
+  let text = UiText::new("Score 100", 100.0, 50.0, 20.0, [1.0; 4]);
+  let quads = layout_text(&font, &mut cache, &text);
+  assert_eq!(8, quads.len(), "the space has no pixels to draw");
+  assert_eq!(7, cache.uploads.len(), "the repeated glyph should be rasterized once");
+  assert!(quads.windows(2).all(|pair| pair[0].rect[0] < pair[1].rect[0]));
+
+  cache.uploads.clear();
+  layout_text(&font, &mut cache, &text);
+  assert!(cache.uploads.is_empty(), "cached glyphs should not be uploaded again");
+  layout_text(&font, &mut cache, &UiText { size: 40.0, ..text.clone() });
+  assert_eq!(7, cache.uploads.len(), "another size needs its own glyphs");
+
+  let right = layout_text(&font, &mut cache, &UiText { align: TextAlign::Right, ..text.clone() });
+  let last = right.last().expect("no glyphs");
+  assert!((last.rect[0] + last.rect[2] - text.x).abs() < 3.0, "right aligned text should end at x");
+}
+
+#[test]
+fn widget_panel_test() {
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::widgets::{Anchor, Panel, Stacking, Widget, WidgetState};
+
+  let dim = Dimensions::new(800.0, 600.0, 2.0, false, ProjectionMode::Perspective, false);
+  let mut panel = Panel {
+    spacing: 10.0,
+    ..Panel::new(Anchor::Center, Stacking::Vertical, 100.0, 20.0)
+  };
+  panel.set_widgets(vec![
+    Widget::Label("Title".to_string()),
+    Widget::Button("Play".to_string()),
+    Widget::Slider("Volume".to_string(), 0.5),
+    Widget::Label("Details".to_string()),
+  ]);
+
+  // 4 items of 20 with 3 gaps of 10, doubled by the hidpi factor
+  assert_eq!([300.0, 190.0, 200.0, 220.0], panel.bounds(&dim));
+  assert_eq!([300.0, 250.0, 200.0, 40.0], panel.layout(&dim)[1]);
+  assert_eq!(Some(1), panel.widget_at(310.0, 260.0, &dim));
+  assert_eq!(None, panel.widget_at(310.0, 200.0, &dim), "labels can't be pointed at");
+  assert_eq!(Some(0.25), panel.slider_value_at(2, 350.0, &dim));
+
+  panel.focus_first();
+  assert_eq!(Some(1), panel.focused, "the title label should be skipped");
+  panel.focus_step(1);
+  panel.focus_step(1);
+  assert_eq!(Some(1), panel.focused, "the focus should wrap around past the details");
+  panel.pressed = Some(1);
+  assert_eq!(WidgetState::Pressed, panel.state(1));
+  assert_eq!(WidgetState::Idle, panel.state(2));
+}
+
+#[test]
+fn pickup_tooltip_test() {
+  use cgmath::Point2;
+  use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, ProjectionMode}, set_position};
+  use crate::game::localization::Localization;
+  use crate::game::settings::Language;
+  use crate::hud::tooltips::tooltip_texts;
+  use crate::shaders::Position;
+  use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut camera = CameraInputState::new();
+  camera.distance = 150.0;
+  let position = Position::new(100.0, 60.0);
+  let point = dim.world_to_screen(position, &camera);
+  let back = dim.screen_to_world(point, &camera);
+  assert!((back.x() - position.x()).abs() < 0.01 && (back.y() - position.y()).abs() < 0.01, "projection should invert the cursor mapping");
+
+  let objects = vec![
+    TerrainObjectDrawable::new(Position::new(200.0, 0.0), TerrainTexture::Ammo),
+    TerrainObjectDrawable::new(Position::new(20.0, 0.0), TerrainTexture::Tree),
+    TerrainObjectDrawable::new(set_position(-13, -12), TerrainTexture::Ammo),
+  ];
+  let strings = Localization::load(Language::English);
+  let texts = tooltip_texts(objects.iter(), Position::origin(), &camera, &dim, &strings);
+  assert_eq!(1, texts.len(), "only the nearby pickup should be labelled");
+  assert_eq!(Point2::new(800.0 * (1.0 + 200.0 / 300.0) / 2.0, 300.0), dim.world_to_screen(objects[0].transform.position, &camera));
+  assert!(texts[0].y < 300.0, "the label should be above the pickup");
+
+  let split = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, true);
+  assert!(tooltip_texts(objects.iter(), Position::origin(), &camera, &split, &strings).is_empty(), "labels outside the first player's view should be hidden");
+}
+
+#[test]
+fn wave_banner_test() {
+  use crate::game::constants::{BANNER_HOLD, BANNER_SLIDE};
+  use crate::game::localization::Localization;
+  use crate::game::settings::Language;
+  use crate::hud::banner::{Banner, banner_offset, Banners};
+
+  assert_eq!(Some(-1.0), banner_offset(0.0), "a banner should start off screen on the left");
+  assert_eq!(Some(0.0), banner_offset(BANNER_SLIDE + BANNER_HOLD / 2.0));
+  assert!(matches!(banner_offset(BANNER_SLIDE * 2.0 + BANNER_HOLD - 0.01), Some(offset) if offset > 0.9), "it should leave on the right");
+  assert_eq!(None, banner_offset(BANNER_SLIDE * 2.0 + BANNER_HOLD));
+
+  let strings = Localization::load(Language::English);
+  assert_eq!(None, Banner::for_wave(4, &strings).subtitle);
+  assert_eq!(Some("Boss incoming".to_string()), Banner::for_wave(5, &strings).subtitle);
+
+  let mut banners = Banners::default();
+  banners.push(Banner::for_wave(1, &strings));
+  banners.push(Banner::for_wave(2, &strings));
+  banners.tick(BANNER_SLIDE * 2.0 + BANNER_HOLD);
+  assert_eq!("Wave 2", banners.current().expect("the second banner should be queued").title);
+}
+
+#[test]
+fn frame_graph_test() {
+  use std::time::Duration;
+  use crate::game::constants::FRAME_GRAPH_SAMPLES;
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::frame_graph::frame_graph;
+  use crate::hud::ui::UiLayer;
+  use crate::profiler::FrameProfile;
+
+  let mut profile = FrameProfile::default();
+  for _ in 0..FRAME_GRAPH_SAMPLES + 10 {
+    profile.record_frame(Duration::from_millis(20));
+  }
+  assert_eq!(FRAME_GRAPH_SAMPLES, profile.frame_history.len(), "the graph should only keep the recent frames");
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut layer = UiLayer::default();
+  frame_graph(&profile, &dim, &mut layer);
+  // Background, a bar per frame and the 60 fps line
+  assert_eq!(FRAME_GRAPH_SAMPLES + 2, layer.quads.len());
+  assert_eq!(3, layer.texts.len());
+}
+
+#[test]
+fn localization_test() {
+  use crate::game::localization::Localization;
+  use crate::game::score::Score;
+  use crate::game::settings::Language;
+  use crate::hud::font::load_font;
+
+  let english = Localization::load(Language::English);
+  for &language in &[Language::German, Language::French] {
+    let strings = Localization::load(language);
+    assert_eq!(Some(language), strings.language());
+    assert_ne!(english.get("menu.resume"), strings.get("menu.resume"), "{:?} should have its own strings", language);
+  }
+
+  let table = object! { "hud.kills" => "Abschüsse {kills}" };
+  let fallback = object! { "hud.kills" => "Kills {kills}", "hud.wave" => "Wave {wave}" };
+  let strings = Localization::from_json(Language::German, &table, &fallback);
+  let mut score = Score::new();
+  score.register_kill();
+  assert_eq!(vec!["hud.score", "Abschüsse 1", "Wave 1"], score.hud_lines(&strings), "missing strings should fall back to English, then to the key");
+  assert_eq!("1 - 2", Localization::from_json(Language::French, &object! { "k" => "{b} - {a}" }, &object! {}).format("k", &[("a", "2".to_string()), ("b", "1".to_string())]));
+
+  let font = load_font();
+  for c in "äöüßéèçôœ".chars() {
+    assert_ne!(0, font.glyph(c).id().0, "the font should cover {}", c);
+  }
+}
+
+#[test]
+fn toast_stack_test() {
+  use crate::game::constants::{MAX_TOASTS, TOAST_DURATION};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::toasts::Toasts;
+  use crate::hud::ui::UiLayer;
+
+  let mut toasts = Toasts::default();
+  for idx in 0..=MAX_TOASTS {
+    toasts.push(format!("toast {}", idx));
+    toasts.tick(0.5);
+  }
+  let texts = toasts.iter().map(|toast| toast.text.as_str()).collect::<Vec<_>>();
+  assert_eq!(MAX_TOASTS, texts.len());
+  assert_eq!("toast 1", texts[0], "the oldest toast should make room");
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut layer = UiLayer::default();
+  toasts.draw(&dim, &mut layer);
+  assert_eq!(MAX_TOASTS, layer.quads.len());
+  assert!(layer.quads[0].rect[1] < layer.quads[1].rect[1], "newer toasts should stack below");
+
+  // Just past the end of the oldest toast left
+  toasts.tick(TOAST_DURATION - 0.5 * MAX_TOASTS as f32 + 0.25);
+  assert_eq!(MAX_TOASTS - 1, toasts.iter().count(), "toasts should expire on their own");
+}
+
+#[test]
+fn zombie_nameplate_test() {
+  use crate::game::{events::GameEvents, GameRng};
+  use crate::game::localization::Localization;
+  use crate::game::prefab::{Instance, Prefabs};
+  use crate::game::settings::Language;
+  use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, ProjectionMode}};
+  use crate::hud::nameplates::nameplates;
+  use crate::hud::ui::UiLayer;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let prefabs = Prefabs::load(crate::game::constants::PREFABS_PATH);
+  let boss = match prefabs.get("boss_zombie").expect("the boss prefab should exist").instantiate(Position::new(60.0, 0.0)) {
+    Instance::Zombie(zombie) => zombie,
+    _ => panic!("the boss should be a zombie"),
+  };
+  let mut zombies = vec![boss, ZombieDrawable::new(Position::new(-60.0, 0.0))];
+  zombies[1].is_aggroed = true;
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut camera = CameraInputState::new();
+  camera.distance = 300.0;
+  let strings = Localization::load(Language::English);
+  let draw = |zombies: &[ZombieDrawable], camera: &CameraInputState| {
+    let mut layer = UiLayer::default();
+    nameplates(zombies.iter(), Position::origin(), camera, &dim, &strings, &mut layer);
+    layer
+  };
+  assert!(draw(&zombies, &camera).texts.is_empty(), "a boss that has not noticed anyone should have no nameplate, a plain zombie never has one");
+
+  zombies[0].take_damage(0.5, &mut GameEvents::new(), &mut GameRng::new(Some(1)));
+  let layer = draw(&zombies, &camera);
+  assert_eq!(vec!["Plague lord"], layer.texts.iter().map(|t| t.text.as_str()).collect::<Vec<_>>());
+  assert_eq!(0.75, layer.quads[1].rect[2] / layer.quads[0].rect[2], "the bar should show the health left");
+
+  camera.distance = 600.0;
+  assert_eq!(layer.quads[0].rect[2], draw(&zombies, &camera).quads[0].rect[2], "the nameplate should keep its size when the camera zooms");
+}
+
+#[test]
+fn inventory_slots_test() {
+  use cgmath::Point2;
+  use crate::game::inventory::{InventoryScreen, Loadout, Weapon};
+  use crate::game::localization::Localization;
+  use crate::game::settings::{Difficulty, Language};
+  use crate::game::state::GameState;
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::hud::widgets::Widget;
+
+  let strings = Localization::load(Language::English);
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut loadout = Loadout::new();
+  let mut screen = InventoryScreen::new();
+  screen.open(&loadout, &strings);
+  assert_eq!(Widget::Button("1: Pistol".to_string()), screen.slots.widgets[0]);
+
+  screen.confirm(&mut loadout, &strings);
+  screen.select(1);
+  screen.select(1);
+  assert!(screen.confirm(&mut loadout, &strings), "confirming another slot should put the held weapon down");
+  assert_eq!([None, None, Some(Weapon::Pistol)], loadout.slots);
+
+  let centers = screen.slots.layout(&dim).iter().map(|r| Point2::new(r[0] + r[2] / 2.0, r[1] + r[3] / 2.0)).collect::<Vec<_>>();
+  screen.press(centers[2], &dim);
+  screen.point(centers[0], &dim);
+  assert!(screen.release(&mut loadout, &strings), "dragging onto a slot should swap them");
+  assert_eq!(Some(Weapon::Pistol), loadout.slots[0]);
+  assert_eq!(Widget::Button("3: empty".to_string()), screen.slots.widgets[2]);
+
+  screen.confirm(&mut loadout, &strings);
+  assert!(screen.cancel(), "backing out should put a held weapon back first");
+  assert!(!screen.cancel());
+
+  assert_eq!(0.0, GameState::Inventory.time_scale(Difficulty::Easy));
+  assert!(GameState::Inventory.time_scale(Difficulty::Hard) > 0.0, "harder difficulties should only slow the world down");
+  assert_eq!(0.0, GameState::Paused.time_scale(Difficulty::Hard));
+}
+
+#[test]
+fn combat_log_test() {
+  use crate::game::constants::{COMBAT_LOG_DURATION, COMBAT_LOG_LINES};
+  use crate::game::events::GameEvent;
+  use crate::game::localization::Localization;
+  use crate::game::settings::Language;
+  use crate::hud::combat_log::{CombatEntry, CombatLog};
+  use crate::shaders::Position;
+
+  let strings = Localization::load(Language::English);
+  let mut log = CombatLog::default();
+  let events = [
+    GameEvent::BulletFired(Position::origin()),
+    GameEvent::ZombieHit(Position::origin(), 0.5),
+    GameEvent::PlayerDamaged(0, Position::origin(), 10.0),
+    GameEvent::PlayerDamaged(0, Position::origin(), 10.0),
+    GameEvent::PickupCollected(Position::origin()),
+  ];
+  for entry in events.iter().filter_map(|&event| CombatEntry::from_event(event)) {
+    log.push(entry);
+  }
+  let lines = log.entries().map(|entry| entry.text(&strings)).collect::<Vec<_>>();
+  assert_eq!(vec!["Hit a zombie for 0.5", "Took 20 damage", "Picked up ammo"], lines, "contact damage should add up in one line");
+
+  for _ in 0..COMBAT_LOG_LINES {
+    log.push(CombatEntry::Kill);
+  }
+  assert_eq!(COMBAT_LOG_LINES, log.entries().count(), "old lines should scroll off");
+  assert!(log.entries().all(|entry| entry == CombatEntry::Kill));
+  log.tick(COMBAT_LOG_DURATION);
+  assert_eq!(0, log.entries().count(), "lines should expire");
+}
+
+#[test]
+fn objective_arrow_test() {
+  use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, ProjectionMode}};
+  use crate::hud::objective_arrow::{nearest_ammo, objective_arrow};
+  use crate::shaders::Position;
+  use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+
+  let objects = vec![
+    TerrainObjectDrawable::new(Position::new(-3000.0, 0.0), TerrainTexture::Ammo),
+    TerrainObjectDrawable::new(Position::new(2000.0, 0.0), TerrainTexture::Ammo),
+    TerrainObjectDrawable::new(Position::new(10.0, 0.0), TerrainTexture::House),
+  ];
+  let target = nearest_ammo(objects.iter()).expect("there should be ammo");
+  assert_eq!((2000.0, 0.0), (target.x(), target.y()), "the closest ammo pickup should be the objective, not the closer house");
+
+  let dim = Dimensions::new(800.0, 600.0, 1.0, true, ProjectionMode::Perspective, false);
+  let mut camera = CameraInputState::new();
+  camera.distance = 300.0;
+  let quads = objective_arrow(target, Position::origin(), &camera, &dim).expect("an off-screen objective should get an arrow");
+  assert!(!quads.is_empty());
+  let right = quads.iter().map(|q| q.rect[0] + q.rect[2]).fold(f32::MIN, f32::max);
+  let top = quads.iter().map(|q| q.rect[1]).fold(f32::MAX, f32::min);
+  assert!(right > 700.0 && right <= 800.0, "the arrow should sit at the right edge, was at {}", right);
+  assert!(top > 250.0 && top < 300.0, "the arrow should point straight right from the middle of the view, was at {}", top);
+  assert!(quads.iter().all(|q| q.rect[1] + q.rect[3] < 350.0), "the arrow should stay around the middle row");
+
+  assert!(objective_arrow(Position::new(100.0, 50.0), Position::origin(), &camera, &dim).is_none(), "an objective in view should need no arrow");
+  camera.distance = 3000.0;
+  assert!(objective_arrow(target, Position::origin(), &camera, &dim).is_none(), "zooming out should bring the objective into view");
+}
+
+#[test]
+fn save_game_round_trip_test() {
+  use crate::game::inventory::{Loadout, Weapon};
+  use crate::game::save::SaveGame;
+  use crate::game::score::Score;
+  use crate::game::snapshot::{Snapshot, WorldSnapshot};
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let mut boss = ZombieDrawable::new(Position::new(30.0, 40.0));
+  boss.health.max = 2.0;
+  boss.health.current = 1.5;
+  let mut score = Score::new();
+  score.wave = 4;
+  score.register_kill();
+  score.time = 95.5;
+  let mut loadout = Loadout::new();
+  loadout.swap(0, 2);
+  let save = SaveGame {
+    world: WorldSnapshot { players: vec![], zombies: vec![boss], pickups: vec![Position::new(5.0, 6.0)] },
+    score,
+    loadout,
+  };
+
+  let mut value = save.to_json();
+  let loaded = SaveGame::from_json(&value).unwrap();
+  assert_eq!(4, loaded.score.wave);
+  assert_eq!(1, loaded.score.kills);
+  assert_eq!(95.5, loaded.score.time);
+  assert_eq!(0, loaded.score.streak, "a running streak should not be saved");
+  assert_eq!([None, None, Some(Weapon::Pistol)], loaded.loadout.slots);
+  assert_eq!((2.0, 1.5), (loaded.world.zombies[0].health.max, loaded.world.zombies[0].health.current));
+  assert_eq!(1, loaded.world.pickups.len());
+
+  value["version"] = 99.into();
+  assert!(SaveGame::from_json(&value).is_err(), "a save from a newer version should be refused");
+  value.remove("version");
+  assert!(SaveGame::from_json(&value).is_err(), "a save without a version should be refused");
+}
+
+#[test]
+fn settings_file_test() {
+  use crate::game::settings::{import_legacy_config, Language, load_config, save_config_section, Settings};
+
+  let path = std::env::temp_dir().join(format!("hinterland-settings-{}.toml", std::process::id()));
+  let filename = path.to_string_lossy();
+  assert_eq!(Settings::new(), Settings::load(&filename), "a missing file should give the defaults");
+
+  save_config_section(&filename, "bindings", object! { "reload" => array!["T"] });
+  let mut settings = Settings::new();
+  settings.music_volume = 0.3;
+  settings.language = Language::French;
+  settings.save(&filename);
+  assert_eq!(settings, Settings::load(&filename));
+  assert_eq!("T", load_config(&filename)["bindings"]["reload"][0], "saving the settings should keep the other sections");
+  let contents = std::fs::read_to_string(&path).unwrap();
+  assert!(contents.contains("[audio]") && contents.contains("reload = [\"T\"]"), "the file should be TOML with one table per section:\n{}", contents);
+  std::fs::remove_file(&path).unwrap();
+
+  let legacy = std::env::temp_dir().join(format!("hinterland-config-{}.json", std::process::id()));
+  std::fs::write(&legacy, "{\"audio\": {\"music\": 0.3}, \"gameplay\": {\"seed\": null}, \"bindings\": {\"reload\": [\"T\"]}}").unwrap();
+  import_legacy_config(&legacy.to_string_lossy(), &filename);
+  assert_eq!(0.3, Settings::load(&filename).music_volume, "a JSON config of an earlier version should be imported");
+  assert_eq!("T", load_config(&filename)["bindings"]["reload"][0]);
+  assert!(load_config(&filename)["gameplay"]["seed"].is_null(), "a null should be left out");
+  std::fs::remove_file(&legacy).unwrap();
+  std::fs::remove_file(&path).unwrap();
+
+  std::fs::write(&path, "[audio\nmusic_volume = ").unwrap();
+  assert_eq!(Settings::new(), Settings::load(&filename), "a broken file should give the defaults");
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn records_test() {
+  use crate::game::constants::{RECORD_RUNS, RECORDS_VERSION};
+  use crate::game::localization::Localization;
+  use crate::game::records::{Records, RunRecord};
+  use crate::game::settings::Language;
+  use crate::game::snapshot::Snapshot;
+
+  let run = |score, kills, time| RunRecord { score, kills, wave: 2, best_streak: 1, time };
+  let mut records = Records::default();
+  assert_eq!(vec!["no runs yet"], records.lines(&Localization::load(Language::English)));
+  assert_eq!(3, records.add(run(500, 5, 60.0)).len(), "the first run should set every record");
+  assert_eq!(vec!["records.new_longest_time"], records.add(run(100, 1, 75.0)));
+  assert_eq!((500, 5, 75.0), (records.best_score, records.most_kills, records.longest_time));
+  assert_eq!(100, records.runs[0].score, "the newest run should come first");
+
+  for _ in 0..RECORD_RUNS {
+    records.add(run(0, 0, 1.0));
+  }
+  assert_eq!(RECORD_RUNS, records.runs.len(), "old runs should drop off the history");
+  assert_eq!(500, records.best_score, "a best should outlive the run that set it");
+
+  let mut value = records.to_json();
+  assert_eq!(records, Records::from_json(&value).unwrap());
+  value["version"] = (RECORDS_VERSION + 1).into();
+  assert!(Records::from_json(&value).is_err(), "records from a newer version should be refused");
+}
+
+#[test]
+fn replay_speed_test() {
+  use glutin::ElementState::Pressed;
+  use crate::game::constants::MAX_REPLAY_SPEED;
+  use crate::gfx_app::input::Action;
+  use crate::gfx_app::replay::{InputEvent, InputReplay, ReplayMode};
+
+  let path = std::env::temp_dir().join(format!("hinterland-replay-{}.json", std::process::id()));
+  std::fs::write(&path, "{\"seed\": 1, \"frames\": [{\"steps\": 1, \"events\": []}]}").unwrap();
+  let mut replay = InputReplay::new(ReplayMode::Play(path.to_string_lossy().into_owned()), None, false);
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(Some(1), replay.seed);
+  assert_eq!(1, replay.frames_per_draw());
+
+  replay.control(InputEvent::Key(Action::Confirm, Pressed));
+  assert_eq!(0, replay.frames_per_draw(), "a paused playback should run no frames");
+  replay.control(InputEvent::Key(Action::Confirm, Pressed));
+  for _ in 0..5 {
+    replay.control(InputEvent::Key(Action::MoveRight, Pressed));
+  }
+  assert_eq!(MAX_REPLAY_SPEED, replay.frames_per_draw());
+  replay.control(InputEvent::Key(Action::MoveLeft, Pressed));
+  assert_eq!(MAX_REPLAY_SPEED / 2, replay.frames_per_draw());
+
+  let mut live = InputReplay::new(ReplayMode::Off, None, false);
+  live.control(InputEvent::Key(Action::Confirm, Pressed));
+  assert_eq!(1, live.frames_per_draw(), "live input should always run one frame");
+}
+
+#[test]
+fn profiles_test() {
+  use crate::game::constants::MAX_PROFILES;
+  use crate::game::profiles::Profiles;
+
+  let profiles = Profiles::from_json(&object! { "active" => 2, "profiles" => array!["Anna", 7] });
+  assert_eq!(MAX_PROFILES, profiles.names.len(), "missing profiles should be filled in");
+  assert_eq!(("Anna", "Player 2"), (profiles.names[0].as_str(), profiles.names[1].as_str()), "a name that is not text should keep its default");
+  assert_eq!("Player 3", profiles.active_name());
+  assert_eq!(profiles, Profiles::from_json(&profiles.to_json()));
+  assert_eq!(0, Profiles::from_json(&object! { "active" => MAX_PROFILES }).active, "an unknown profile should fall back to the first");
+
+  assert_eq!(profiles.dir(2).join("config.json"), profiles.file("config.json"), "files should belong to the active profile");
+  assert!(profiles.dir(1) != profiles.dir(2));
+}
+
+#[test]
+fn hot_reload_test() {
+  use std::fs;
+
+  use crate::ecs::{World, WorldExt};
+
+  use crate::game::hot_reload::{self, Asset, WatchedFile};
+  use crate::game::prefab::Prefabs;
+
+  let path = std::env::temp_dir().join("hinterland_hot_reload_test.json").to_string_lossy().into_owned();
+  let _ = fs::remove_file(&path);
+  let mut file = WatchedFile::new(&path);
+  assert!(!file.has_changed(&path), "a missing file should not count as a change");
+  fs::write(&path, "{}").unwrap();
+  assert!(file.has_changed(&path), "a new file should count as a change");
+  assert!(!file.has_changed(&path), "a change should be reported once");
+  fs::remove_file(&path).unwrap();
+  assert!(!file.has_changed(&path));
+
+  let mut world = World::new();
+  world.insert(Prefabs::default());
+  hot_reload::reload(&mut world, Asset::Prefabs);
+  assert!(world.read_resource::<Prefabs>().get("zombie").is_some(), "prefabs should be loaded into the world");
+}
+
+#[test]
+fn crash_report_test() {
+  use crate::game::constants::CRASH_LOG_FRAMES;
+  use crate::game::crash::CrashReport;
+
+  let mut report = CrashReport::new(42);
+  report.log("Playing: WaveStarted(1)".to_string());
+  report.frame = CRASH_LOG_FRAMES - 1;
+  report.log("Playing: ZombieKilled(0)".to_string());
+  assert_eq!(2, report.log.len());
+  report.frame = CRASH_LOG_FRAMES;
+  report.log("GameOver: ".to_string());
+  assert_eq!(vec![CRASH_LOG_FRAMES - 1, CRASH_LOG_FRAMES], report.log.iter().map(|&(frame, _)| frame).collect::<Vec<_>>(), "lines of older frames should be dropped");
+
+  let value = report.to_json("boom");
+  assert_eq!((Some(42), Some("boom")), (value["seed"].as_u64(), value["message"].as_str()));
+  assert_eq!(Some(format!("{} GameOver: ", CRASH_LOG_FRAMES).as_str()), value["log"][1].as_str());
+  assert!(value["save"].is_null(), "a report before the first snapshot should have no save");
+}
+
+#[test]
+fn binding_preset_test() {
+  use std::path::Path;
+
+  use glutin::VirtualKeyCode as Key;
+
+  use crate::gfx_app::input::{Action, InputMap, preset_errors, PresetError, presets};
+
+  let preset = presets().into_iter().find(|preset| preset.name == "Left handed").expect("left handed preset");
+  let (name, input_map) = InputMap::import(&preset.path).expect("left handed preset should import");
+  assert_eq!("Left handed", name);
+  assert_eq!((Some(Action::MoveUp), None), (input_map.action(Key::I), input_map.action(Key::W)));
+  assert_eq!(Some(Action::SecondMoveUp), input_map.action(Key::Up), "actions left out should keep their default keys");
+
+  let errors = preset_errors(&object! { "move_up" => array!["Q", "Nope"], "reload" => array!["Q"], "fly" => array!["E"], "aim" => array!["Up"] });
+  assert!(errors.contains(&PresetError::UnknownKey("Nope".to_string())));
+  assert!(errors.contains(&PresetError::UnknownAction("fly".to_string())));
+  assert!(errors.contains(&PresetError::Conflict("Q".to_string(), Action::MoveUp, Action::Reload)));
+  assert!(errors.contains(&PresetError::Conflict("Up".to_string(), Action::SecondMoveUp, Action::Aim)), "a default key of an action left out should conflict");
+  assert_eq!(4, errors.len());
+
+  let path = std::env::temp_dir().join("hinterland_preset_test").join("exported.json");
+  input_map.export(&path, "Exported").unwrap();
+  let (name, imported) = InputMap::import(&path).expect("an export should import");
+  assert_eq!(("Exported", input_map.to_json()), (name.as_str(), imported.to_json()));
+  assert!(InputMap::import(Path::new("missing_preset.json")).is_err());
+}
+
+#[test]
+fn stats_test() {
+  use crate::game::events::{GameEvent, GameEvents};
+  use crate::game::GameRng;
+  use crate::game::localization::Localization;
+  use crate::game::prefab::{Instance, intern, Prefabs};
+  use crate::game::settings::Language;
+  use crate::game::snapshot::Snapshot;
+  use crate::game::stats::Stats;
+  use crate::shaders::Position;
+
+  let prefabs = Prefabs::load(crate::game::constants::PREFABS_PATH);
+  let mut zombie = match prefabs.get("frozen_zombie").unwrap().instantiate(Position::origin()) {
+    Instance::Zombie(zombie) => zombie,
+    Instance::TerrainObject(_) => panic!("frozen_zombie should be a zombie"),
+  };
+  assert!(std::ptr::eq(zombie.kind, intern("frozen_zombie")), "prefab names should be interned once");
+
+  let mut events = GameEvents::new();
+  let mut reader = events.register_reader();
+  zombie.take_damage(zombie.health.max, &mut events, &mut GameRng::new(Some(1)));
+  let mut stats = Stats::default();
+  for &event in events.read(&mut reader) {
+    stats.record(event);
+  }
+  for &event in &[GameEvent::BulletFired(Position::origin()), GameEvent::BulletFired(Position::origin()),
+    GameEvent::BulletHit(Position::origin()), GameEvent::ZombieKilled(Position::origin(), "zombie"), GameEvent::PlayerKilled(0)] {
+    stats.record(event);
+  }
+  assert_eq!((Some(&1), Some(&1), 2), (stats.kills.get("frozen_zombie"), stats.kills.get("zombie"), stats.total_kills()));
+  assert_eq!((2, 1, 1), (stats.shots_fired, stats.shots_hit, stats.deaths));
+  assert_eq!(50.0, stats.accuracy());
+  assert_eq!(0.0, Stats::default().accuracy(), "no shots should not divide by zero");
+
+  stats.distance = 480.0;
+  assert_eq!(stats, Stats::from_json(&stats.to_json()).unwrap());
+  assert!(Stats::from_json(&object! { "version" => 99 }).is_err(), "stats from a newer version should be refused");
+  assert_eq!(7, stats.lines(&Localization::load(Language::English)).len());
+}
+
+#[test]
+fn autosave_test() {
+  use std::fs;
+
+  use crate::game::constants::AUTOSAVES;
+  use crate::game::save::{rotate_files, SaveFile};
+
+  let dir = std::env::temp_dir().join("hinterland_autosave_test");
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  let paths = (0..AUTOSAVES).map(|idx| dir.join(format!("{}.json", idx)).to_string_lossy().into_owned()).collect::<Vec<_>>();
+  for wave in 1..=AUTOSAVES + 1 {
+    rotate_files(&paths);
+    fs::write(&paths[0], wave.to_string()).unwrap();
+  }
+  let kept = paths.iter().map(|path| fs::read_to_string(path).unwrap()).collect::<Vec<_>>();
+  assert_eq!(AUTOSAVES, fs::read_dir(&dir).unwrap().count(), "the oldest auto-save should be dropped");
+  assert_eq!((AUTOSAVES + 1).to_string(), kept[0], "the newest auto-save should come first");
+  assert_eq!("2", kept[AUTOSAVES - 1]);
+  let _ = fs::remove_dir_all(&dir);
+
+  assert!(SaveFile::Auto(0).path().ends_with("autosave_1.json"));
+  assert!(SaveFile::Slot(0).path() != SaveFile::Auto(0).path(), "auto-saves should not overwrite the save slots");
+}
+
+#[test]
+fn atomic_save_test() {
+  use std::fs;
+
+  use crate::game::storage::{read_json, write_json};
+
+  let dir = std::env::temp_dir().join("hinterland_atomic_save_test");
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("save.json").to_string_lossy().into_owned();
+  assert_eq!(Ok(None), read_json(&path), "a file never written should not be an error");
+
+  let first = object! { "wave" => 2, "time" => 61.7_f32, "positions" => array![object! { "x" => -0.1_f32, "y" => 1e-7_f32 }] };
+  let second = object! { "wave" => 3, "time" => 95.25_f32 };
+  write_json(&path, &first).unwrap();
+  write_json(&path, &second).unwrap();
+  assert_eq!(Ok(Some(second.clone())), read_json(&path), "the checksum should survive reading the numbers back");
+
+  let contents = fs::read_to_string(&path).unwrap();
+  fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+  assert_eq!(Ok(Some(first.clone())), read_json(&path), "a half written file should be repaired from the backup");
+  assert_eq!(Ok(Some(first)), read_json(&path), "the repaired file should be whole again");
+
+  fs::write(&path, contents.replace("95.25", "95.5")).unwrap();
+  fs::remove_file(format!("{}.bak", path)).unwrap();
+  assert!(read_json(&path).is_err(), "a changed file without a backup should be reported");
+
+  fs::write(&path, second.dump()).unwrap();
+  assert_eq!(Ok(Some(second)), read_json(&path), "files from before checksums should load");
+  let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn net_protocol_test() {
+  use crate::character::controls::CharacterControl;
+  use crate::game::score::Score;
+  use crate::net::client::local_index;
+  use crate::net::protocol::Message;
+  use crate::net::replication::NetSnapshot;
+
+  let input = Message::Input(2, 5, 17, vec![CharacterControl::Left, CharacterControl::CtrlPressed, CharacterControl::YMoveStop], vec![Some((12.5, -3.0)), None]);
+  match Message::decode(&input.encode()) {
+    Ok(Message::Input(2, 5, 17, controls, clicks)) => {
+      assert_eq!(vec![CharacterControl::Left, CharacterControl::CtrlPressed, CharacterControl::YMoveStop], controls);
+      assert_eq!(vec![Some((12.5, -3.0)), None], clicks, "clicks should fire the client's character on the host");
+    }
+    _ => panic!("input should survive the round trip"),
+  }
+  let state = Message::State(NetSnapshot::default().delta(42, None, &Score::new()));
+  match Message::decode(&state.encode()) {
+    Ok(Message::State(delta)) => assert_eq!((42, 0, Score::new()), (delta.frame, delta.baseline, delta.score)),
+    _ => panic!("state should survive the round trip"),
+  }
+  assert!(Message::decode(b"{\"type\":\"input\",\"player\":1,\"controls\":[\"Jump\"]}").is_err(), "unknown controls should be refused");
+  assert!(Message::decode(b"{\"type\":\"sta").is_err());
+
+  assert_eq!((0, 2, 1, 3), (local_index(2, 2), local_index(0, 2), local_index(1, 2), local_index(3, 2)),
+             "a client's own character should swap places with the host's");
+}
+
+#[test]
+fn state_delta_test() {
+  use crate::game::score::Score;
+  use crate::net::replication::{EntityState, KIND_PICKUP, KIND_ZOMBIE, NetSnapshot, StateDelta};
+
+  let zombie = EntityState { kind: KIND_ZOMBIE, x: 120, y: -35, stance: 1, orientation: 4, health: 100, ..EntityState::default() };
+  let pickup = EntityState { kind: KIND_PICKUP, x: 10, y: 10, ..EntityState::default() };
+  let mut first = NetSnapshot::default();
+  first.entities.insert(1, zombie);
+  first.entities.insert(2, pickup);
+  let mut second = first.clone();
+  second.entities.remove(&2);
+  second.entities.insert(1, EntityState { x: 125, ..zombie });
+  second.entities.insert(3, EntityState { health: 40, ..zombie });
+
+  let full = second.delta(8, None, &Score::new());
+  let delta = second.delta(8, Some((7, &first)), &Score::new());
+  assert_eq!(vec![2], delta.removed, "a pickup that is gone should be removed");
+  assert_eq!(vec![1, 3], delta.changed.iter().map(|(id, _, _)| *id).collect::<Vec<_>>());
+
+  let mut full_bytes = Vec::new();
+  full.encode(&mut full_bytes);
+  let mut bytes = Vec::new();
+  delta.encode(&mut bytes);
+  assert!(bytes.len() < full_bytes.len(), "a moved zombie should only send its x");
+  let decoded = StateDelta::decode(&bytes).expect("delta should decode");
+  assert_eq!((delta.frame, delta.baseline, &delta.removed), (decoded.frame, decoded.baseline, &decoded.removed));
+  assert_eq!(second, first.apply(&decoded), "the delta should rebuild the state on top of its baseline");
+  assert_eq!(second, NetSnapshot::default().apply(&full));
+  assert!(StateDelta::decode(&bytes[..bytes.len() - 1]).is_err(), "a cut datagram should be refused");
+}
+
+#[test]
+fn prediction_test() {
+  use crate::net::client::Prediction;
+  use crate::shaders::Position;
+
+  let mut prediction = Prediction::default();
+  prediction.record(0, Position::new(0.0, 0.0));
+  prediction.record(1, Position::new(2.0, 0.0));
+  prediction.record(2, Position::new(4.0, 1.0));
+  prediction.record(3, Position::new(6.0, 2.0));
+  assert_eq!(Position::new(4.0, 2.0), prediction.reconcile(1, Position::new(1.5, 0.0)),
+             "the inputs the host hasn't applied should be replayed");
+  // The host was behind, the next input moves on from the reconciled position
+  prediction.record(4, Position::new(7.5, 2.0));
+  assert_eq!(Position::new(2.0, 0.0), prediction.reconcile(3, Position::new(6.0, 2.0)));
+  assert_eq!(Position::origin(), prediction.reconcile(4, Position::new(7.5, 2.0)), "nothing is left once every input is applied");
+}
+
+#[test]
+fn lobby_test() {
+  use crate::critter::palette::CritterPalette;
+  use crate::net::lobby::{Lobby, LobbyPlayer};
+  use crate::net::protocol::Message;
+
+  let players = vec![LobbyPlayer { index: 0, skin: CritterPalette::Frozen, is_ready: true },
+                     LobbyPlayer { index: 2, skin: CritterPalette::Toxic, is_ready: false }];
+  match Message::decode(&Message::Lobby(players.clone()).encode()) {
+    Ok(Message::Lobby(decoded)) => assert_eq!(players, decoded),
+    _ => panic!("the lobby should survive the round trip"),
+  }
+  match Message::decode(&Message::Ready(2, CritterPalette::Toxic, true).encode()) {
+    Ok(Message::Ready(2, CritterPalette::Toxic, true)) => (),
+    _ => panic!("a ready message should survive the round trip"),
+  }
+  match Message::decode(&Message::Advert("1.0".to_string(), "Default".to_string(), 3).encode()) {
+    Ok(Message::Advert(version, name, 3)) => assert_eq!(("1.0", "Default"), (version.as_str(), name.as_str())),
+    _ => panic!("an advert should survive the round trip"),
+  }
+
+  let mut lobby = Lobby::default();
+  lobby.reset(true);
+  lobby.players = players;
+  assert!(!lobby.can_start(), "the host should wait for every player to be ready");
+  lobby.players[1].is_ready = true;
+  assert!(lobby.can_start());
+  lobby.local.skin = lobby.local.skin.cycle();
+  lobby.reset(false);
+  assert!(!lobby.can_start(), "only the host starts the match");
+  assert_eq!(CritterPalette::Toxic, lobby.local.skin, "the skin should be kept for the next session");
+  assert_eq!(CritterPalette::Original, CritterPalette::Frozen.cycle(), "skins should wrap around");
+}
+
+#[test]
+fn chat_test() {
+  use crate::game::chat::{Chat, ChatLine};
+  use crate::net::protocol::Message;
+
+  match Message::decode(&Message::Chat(2, "över here".to_string()).encode()) {
+    Ok(Message::Chat(2, text)) => assert_eq!("över here", text),
+    _ => panic!("a chat line should survive the round trip"),
+  }
+
+  let mut chat = Chat::default();
+  let say = |chat: &mut Chat, text: &str, own: usize| {
+    text.chars().for_each(|c| chat.type_character(c));
+    chat.submit(own);
+  };
+  say(&mut chat, "  hello ", 1);
+  assert_eq!(vec!["hello".to_string()], chat.outgoing, "a line should wait for the session trimmed");
+  assert_eq!("", chat.input());
+  say(&mut chat, "/mute 3", 1);
+  assert!(chat.is_muted(2));
+  chat.receive(ChatLine::Said(2, "spam".to_string()));
+  chat.receive(ChatLine::Said(0, "hi".to_string()));
+  assert_eq!(vec![ChatLine::Said(1, "hello".to_string()), ChatLine::Muted(2), ChatLine::Said(0, "hi".to_string())],
+             chat.lines().cloned().collect::<Vec<_>>(), "lines of a muted player should be dropped");
+  say(&mut chat, "/mute 2", 1);
+  assert!(!chat.is_muted(1), "a player can't mute themselves");
+  say(&mut chat, "/unmute 3", 1);
+  assert!(!chat.is_muted(2));
+  assert_eq!(1, chat.outgoing.len(), "commands should not be sent");
+
+  chat.tick(60.0);
+  assert_eq!(0, chat.lines().count(), "old lines should fade out");
+  chat.is_open = true;
+  assert_eq!(ChatLine::Unmuted(2), *chat.lines().last().unwrap(), "an open chat should show the kept lines");
+}
+
+#[test]
+fn lockstep_test() {
+  use crate::character::controls::CharacterControl;
+  use crate::game::constants::LOCKSTEP_DELAY;
+  use crate::net::lockstep::{Lockstep, StepInput, Turn, TurnCollector};
+  use crate::net::protocol::Message;
+
+  let seed = u64::MAX - 1;
+  match Message::decode(&Message::Start(seed, vec![1, 3]).encode()) {
+    Ok(Message::Start(s, players)) => {
+      assert_eq!(seed, s, "a seed beyond a JSON number's precision should survive the round trip");
+      assert_eq!(vec![1, 3], players);
+    }
+    _ => panic!("a start should survive the round trip"),
+  }
+  let fire = StepInput { controls: vec![CharacterControl::Up], clicks: vec![Some((-12.5, 40.0)), None] };
+  match Message::decode(&Message::Steps(2, 9, vec![(10, fire.clone()), (11, StepInput::default())], Some((60, seed))).encode()) {
+    Ok(Message::Steps(2, 9, inputs, Some((60, hash)))) => {
+      assert_eq!(vec![(10, fire.clone()), (11, StepInput::default())], inputs);
+      assert_eq!(seed, hash);
+    }
+    _ => panic!("steps should survive the round trip"),
+  }
+  let turn = Turn { step: 4, inputs: vec![(0, fire.clone())] };
+  match Message::decode(&Message::Turns(3, vec![turn.clone()], None).encode()) {
+    Ok(Message::Turns(3, turns, None)) => assert_eq!(vec![turn], turns),
+    _ => panic!("turns should survive the round trip"),
+  }
+
+  let mut collector = TurnCollector::default();
+  collector.add(0, 1, fire.clone());
+  assert!(collector.finalize(&[0, 2]).is_empty(), "a turn should wait for every player");
+  collector.add(2, 1, StepInput::default());
+  collector.add(2, 2, StepInput::default());
+  assert_eq!(2, collector.input_ack(2));
+  let turns = collector.finalize(&[0, 2]);
+  assert_eq!(vec![Turn { step: 1, inputs: vec![(0, fire.clone())] }], turns, "empty inputs should be left out");
+  assert_eq!(1, collector.turns_after(0).len());
+  collector.forget(1);
+  assert!(collector.turns_after(0).is_empty());
+
+  let mut peer = Lockstep::default();
+  peer.start();
+  peer.push_control(CharacterControl::Up);
+  let scheduled = peer.schedule();
+  assert_eq!(LOCKSTEP_DELAY as usize, scheduled.len());
+  assert_eq!((1, StepInput { controls: vec![CharacterControl::Up], clicks: vec![] }), scheduled[0]);
+  assert!(peer.schedule().is_empty(), "no more steps should be scheduled until one runs");
+  peer.acknowledge(2);
+  assert_eq!(LOCKSTEP_DELAY as usize - 2, peer.unacknowledged().len());
+  peer.receive(Turn { step: 2, inputs: vec![] });
+  assert_eq!(0, peer.ready_steps(), "a step should wait for its turn");
+  assert!(peer.next_turn().is_none());
+  peer.receive(Turn { step: 1, inputs: vec![] });
+  assert_eq!(2, peer.turn_ack());
+  assert_eq!(Some(1), peer.next_turn().map(|t| t.step));
+  assert_eq!(1, peer.ready_steps());
+
+  peer.record_hash(60, 7);
+  assert!(!peer.check_hash(60, 7));
+  assert!(!peer.check_hash(120, 8), "a step without an own hash can't be compared");
+  assert!(peer.check_hash(60, 8), "a different hash should be reported");
+  assert!(!peer.check_hash(60, 9), "a desync should be reported once");
+  assert!(peer.is_desynced);
+}
+
+#[test]
+fn leaderboard_test() {
+  use crate::game::leaderboard::{LeaderboardSettings, LeaderboardStatus, LeaderboardTable, top_entries};
+  use crate::game::localization::Localization;
+  use crate::game::settings::Language;
+
+  let settings = LeaderboardSettings::from_json(&object! { "endpoint" => "https://example.com/api/", "secret" => "s" }, " Player\n1 ");
+  assert_eq!("https://example.com/api", settings.endpoint);
+  assert_eq!("Player1", settings.name, "the profile's name should be the default, without control characters");
+  assert_eq!("", LeaderboardSettings::from_json(&json::JsonValue::Null, "Player 1").endpoint, "no section should mean no leaderboard");
+
+  let scores = json::parse(r#"{"scores": [{"name": "Ann", "score": 900, "kills": 30, "wave": 4, "time": 300.5},
+    {"name": "Bob", "score": 400, "kills": 12, "wave": 2, "time": 120.0}]}"#).unwrap();
+  let entries = top_entries(&scores).unwrap();
+  assert_eq!(2, entries.len());
+  assert_eq!(("Ann".to_string(), 900, 4), (entries[0].name.clone(), entries[0].score, entries[0].wave));
+  assert!(top_entries(&json::parse(r#"{"scores": [{"name": "Ann"}]}"#).unwrap()).is_err(), "an entry without a score should be refused");
+  assert!(top_entries(&json::parse("[]").unwrap()).is_err());
+
+  let strings = Localization::load(Language::English);
+  let table = LeaderboardTable { status: LeaderboardStatus::Loaded, entries: entries.iter().cycle().take(23).cloned().collect() };
+  assert_eq!(3, table.pages());
+  assert_eq!(10, table.lines(0, &strings).len());
+  assert_eq!(3, table.lines(2, &strings).len(), "the last page should hold the rest");
+  assert!(table.lines(2, &strings)[0].starts_with("21. "), "ranks should go on across pages");
+  assert_eq!(1, LeaderboardTable::default().pages());
+  assert_eq!(1, LeaderboardTable::default().lines(0, &strings).len());
+}
+
+#[cfg(feature = "leaderboard")]
+#[test]
+fn leaderboard_signature_test() {
+  use crate::game::leaderboard::signature;
+
+  // RFC 4231 test case 2
+  assert_eq!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843", signature("Jefe", "what do ya want for nothing?"));
+}
+
+#[test]
+fn host_migration_test() {
+  use std::net::IpAddr;
+  use crate::net::client::{Migration, successor};
+  use crate::net::protocol::{join_message, Message};
+
+  let first: IpAddr = "192.168.1.20".parse().unwrap();
+  let second: IpAddr = "192.168.1.21".parse().unwrap();
+  match Message::decode(&Message::Peers(vec![(2, first), (1, second)]).encode()) {
+    Ok(Message::Peers(peers)) => assert_eq!(vec![(2, first), (1, second)], peers),
+    _ => panic!("peers should survive the round trip"),
+  }
+  match Message::decode(&join_message(Some(2)).encode()) {
+    Ok(Message::Join(_, rejoin)) => assert_eq!(Some(2), rejoin),
+    _ => panic!("a join should survive the round trip"),
+  }
+  match Message::decode(&join_message(None).encode()) {
+    Ok(Message::Join(_, rejoin)) => assert_eq!(None, rejoin, "a first join should claim no index"),
+    _ => panic!("a join should survive the round trip"),
+  }
+
+  let peers = vec![(3, first), (1, second), (2, first)];
+  assert_eq!(Some(Migration::Host(1, vec![3, 2])), successor(1, &peers), "the lowest index should take over");
+  assert_eq!(Some(Migration::Join("192.168.1.21:7777".to_string(), 3)), successor(3, &peers), "the others should join it on the game port");
+  assert_eq!(None, successor(1, &[]));
+}
+
+#[test]
+fn asset_loading_test() {
+  use crate::gfx_app::loading::loading_bar;
+  use crate::graphics::assets::{load_images, TextureAsset};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+
+  let loaded = load_images().iter().collect::<Vec<_>>();
+  assert_eq!(TextureAsset::ALL.len(), loaded.len(), "every texture should come back once");
+  for (asset, image) in loaded {
+    let image = image.unwrap_or_else(|e| panic!("{} should decode {}", asset.path(), e));
+    assert!(image.width > 0 && image.height > 0);
+    assert_eq!((image.width * image.height * 4) as usize, image.pixels.len());
+  }
+
+  let dim = Dimensions::new(1280.0, 720.0, 1.0, true, ProjectionMode::Perspective, false);
+  let bar = loading_bar(0.5, &dim);
+  assert_eq!(2, bar.len());
+  assert_eq!(bar[0].rect[2] / 2.0, bar[1].rect[2], "the fill should cover the share loaded");
+  assert_eq!(dim.window_width / 2.0, bar[0].rect[0] + bar[0].rect[2] / 2.0, "the bar should be centered");
+  assert_eq!(bar[0].rect[2], loading_bar(2.0, &dim)[1].rect[2]);
+}
+
+// Counts the allocations of the thread it runs on, tests run on threads of their own
+#[cfg(test)]
+mod allocation_counter {
+  use std::alloc::{GlobalAlloc, Layout, System};
+  use std::cell::Cell;
+
+  thread_local!(static ALLOCATIONS: Cell<usize> = const { Cell::new(0) });
+
+  struct CountingAllocator;
+
+  unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+      let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+      System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+      System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+      let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+      System.realloc(ptr, layout, new_size)
+    }
+  }
+
+  #[global_allocator]
+  static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+  pub fn count() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+  }
+}
+
+#[test]
+fn frame_allocation_test() {
+  use cgmath::Point2;
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::bullet::{self, BulletDrawable, collision::{CollisionSystem, Contacts}};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::health::Health;
+  use crate::game::constants::SIMULATION_STEP;
+  use crate::game::events::GameEvents;
+  use crate::game::GameRng;
+  use crate::graphics::{camera::CameraInputState, DeltaTime, GameTime};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::net::NetMode;
+  use crate::shaders::Position;
+  use crate::terrain::path_finding::PathFinder;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::zombie::{self, ZombieDrawable};
+
+  // Around the first house, an end on it moves to the first free tile next to it
+  let mut paths = PathFinder::new();
+  assert_eq!(Some(Point2::new(55, 53)), paths.next_tile(Point2::new(54, 54), Point2::new(57, 54)));
+  assert_eq!(Some(Point2::new(55, 53)), paths.next_tile(Point2::new(54, 54), Point2::new(56, 54)));
+  assert_eq!(Some(Point2::new(54, 54)), paths.next_tile(Point2::new(54, 54), Point2::new(54, 54)));
+  assert_eq!(None, paths.next_tile(Point2::new(-1, 54), Point2::new(54, 54)), "tiles off the map have no route");
+
+  let mut world = World::new();
+  world.register::<ZombieDrawable>();
+  world.register::<BulletDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.register::<CharacterInputState>();
+  world.register::<CameraInputState>();
+  world.register::<Player>();
+  world.register::<Health>();
+  world.insert(Dimensions::new(1280.0, 720.0, 1.0, true, ProjectionMode::Perspective, false));
+  world.insert(DeltaTime(SIMULATION_STEP));
+  world.insert(GameTime(0));
+  world.insert(GameRng::new(Some(1)));
+  world.insert(NetMode::Offline);
+  world.insert(Contacts::default());
+  world.insert(GameEvents::new());
+
+  // One zombie close enough to chase the player, the others wander
+  world.create_entity().with(ZombieDrawable::new(Position::new(120.0, 40.0))).build();
+  for idx in 0..8 {
+    world.create_entity().with(BulletDrawable::new(Position::origin(), idx as f32 * 45.0)).build();
+  }
+  world.create_entity()
+    .with(CharacterInputState::new())
+    .with(CameraInputState::new())
+    .with(Player::new(0))
+    .with(Health::new(100.0))
+    .build();
+
+  let mut zombie_system = zombie::PreDrawSystem::default();
+  let mut collision_system = CollisionSystem::default();
+  let mut step = |world: &World, idx: u64| {
+    *world.write_resource::<GameTime>() = GameTime((idx as f64 * SIMULATION_STEP) as u64);
+    zombie_system.run_now(world);
+    bullet::PreDrawSystem.run_now(world);
+    collision_system.run_now(world);
+    zombie::HitSystem.run_now(world);
+    bullet::ImpactSystem.run_now(world);
+  };
+
+  // The buffers grow during the first steps
+  for idx in 0..300 {
+    step(&world, idx);
+  }
+  let before = allocation_counter::count();
+  for idx in 300..600 {
+    step(&world, idx);
+  }
+  let allocations = allocation_counter::count() - before;
+  assert!(world.read_storage::<ZombieDrawable>().join().any(|z| z.is_aggroed), "a zombie should have chased the player");
+  assert_eq!(0, allocations, "steady state steps shouldn't allocate");
+}
+
+#[test]
+fn constant_cache_test() {
+  use crate::graphics::render_graph::ConstantCache;
+  use crate::shaders::{Position, Rotation};
+
+  let mut position = ConstantCache::default();
+  assert!(position.changed(Position::new(1.0, 2.0)), "the first value should always be uploaded");
+  assert!(!position.changed(Position::new(1.0, 2.0)), "the same value again should be skipped");
+  assert!(position.changed(Position::new(1.0, 3.0)));
+  assert!(position.changed(Position::new(1.0, 2.0)), "a value the buffer held earlier has been overwritten since");
+
+  let mut rotation = ConstantCache::default();
+  let angles = [0.0, 0.0, 0.0, 90.0, 90.0, 0.0];
+  let uploads = angles.iter().filter(|&&angle| rotation.changed(Rotation::new(angle))).count();
+  assert_eq!(3, uploads, "bullets drawn one after another in the same direction should share their rotation upload");
+}
+
+#[test]
+fn parallel_simulation_test() {
+  use rayon::ThreadPoolBuilder;
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::bullet::{self, BulletDrawable, collision::{CollisionSystem, Contacts}};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::health::Health;
+  use crate::game::constants::{PARALLEL_MIN_BULLETS, PARALLEL_MIN_ZOMBIES, SIMULATION_STEP};
+  use crate::game::GameRng;
+  use crate::game::snapshot::Snapshot;
+  use crate::graphics::{camera::CameraInputState, DeltaTime, GameTime};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::net::NetMode;
+  use crate::shaders::Position;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::zombie::{self, ZombieDrawable};
+
+  // A horde around the player large enough to be split up, some close enough to chase
+  let run = |threads: usize| {
+    let mut world = World::new();
+    world.register::<ZombieDrawable>();
+    world.register::<BulletDrawable>();
+    world.register::<TerrainObjectDrawable>();
+    world.register::<CharacterInputState>();
+    world.register::<CameraInputState>();
+    world.register::<Player>();
+    world.register::<Health>();
+    world.insert(Dimensions::new(1280.0, 720.0, 1.0, true, ProjectionMode::Perspective, false));
+    world.insert(DeltaTime(SIMULATION_STEP));
+    world.insert(GameRng::new(Some(7)));
+    world.insert(NetMode::Offline);
+    world.insert(Contacts::default());
+
+    for idx in 0..PARALLEL_MIN_ZOMBIES * 2 {
+      world.create_entity().with(ZombieDrawable::new(Position::new((idx % 32) as f32 * 40.0 - 640.0, (idx / 32) as f32 * 40.0 - 320.0))).build();
+    }
+    for idx in 0..PARALLEL_MIN_BULLETS * 2 {
+      world.create_entity().with(BulletDrawable::new(Position::origin(), idx as f32 * 2.8)).build();
+    }
+    world.create_entity()
+      .with(CharacterInputState::new())
+      .with(CameraInputState::new())
+      .with(Player::new(0))
+      .with(Health::new(100.0))
+      .build();
+
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build().expect("Thread pool error");
+    let mut zombie_system = zombie::PreDrawSystem::default();
+    let mut collision_system = CollisionSystem::default();
+    let mut contacts = Vec::new();
+    pool.install(|| for idx in 0..120 {
+      world.insert(GameTime((idx as f64 * SIMULATION_STEP * 30.0) as u64));
+      zombie_system.run_now(&world);
+      bullet::PreDrawSystem.run_now(&world);
+      collision_system.run_now(&world);
+      contacts.extend(world.read_resource::<Contacts>().0.iter().cloned());
+    });
+    let zombies = world.read_storage::<ZombieDrawable>().join()
+      .map(Snapshot::to_json)
+      .collect::<Vec<_>>();
+    (zombies, contacts)
+  };
+
+  let (zombies, contacts) = run(1);
+  assert!(!contacts.is_empty(), "the bullets should have hit something");
+  for &threads in &[2, 4] {
+    let (parallel_zombies, parallel_contacts) = run(threads);
+    assert_eq!(zombies, parallel_zombies, "zombies should end up the same on {} threads", threads);
+    assert_eq!(contacts, parallel_contacts, "contacts should come in the same order on {} threads", threads);
+  }
+}
+
+// Not run by default, `cargo test --release horde_scaling_benchmark -- --ignored --nocapture` prints
+// the time of a step for a growing number of threads
+#[test]
+#[ignore]
+fn horde_scaling_benchmark() {
+  use std::time::Instant;
+  use rayon::ThreadPoolBuilder;
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::bullet::{BulletDrawable, collision::{CollisionSystem, Contacts}};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::constants::SIMULATION_STEP;
+  use crate::game::GameRng;
+  use crate::graphics::{camera::CameraInputState, DeltaTime, GameTime};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::net::NetMode;
+  use crate::shaders::Position;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::zombie::{self, ZombieDrawable};
+
+  const STEPS: u32 = 200;
+
+  for &count in &[1000, 4000] {
+    for &threads in &[1, 2, 4, 8, 16] {
+      let mut world = World::new();
+      world.register::<ZombieDrawable>();
+      world.register::<BulletDrawable>();
+      world.register::<TerrainObjectDrawable>();
+      world.register::<CharacterInputState>();
+      world.register::<CameraInputState>();
+      world.register::<Player>();
+      // Split screen turns the level of detail off, every zombie is updated each step
+      world.insert(Dimensions::new(1280.0, 720.0, 1.0, true, ProjectionMode::Perspective, true));
+      world.insert(DeltaTime(SIMULATION_STEP));
+      world.insert(GameRng::new(Some(1)));
+      world.insert(NetMode::Offline);
+      world.insert(Contacts::default());
+
+      for idx in 0..count {
+        world.create_entity().with(ZombieDrawable::new(Position::new((idx % 64) as f32 * 25.0 - 800.0, (idx / 64) as f32 * 25.0 - 800.0))).build();
+      }
+      for idx in 0..128 {
+        world.create_entity().with(BulletDrawable::new(Position::origin(), idx as f32 * 2.8)).build();
+      }
+      world.create_entity()
+        .with(CharacterInputState::new())
+        .with(CameraInputState::new())
+        .with(Player::new(0))
+        .build();
+
+      let pool = ThreadPoolBuilder::new().num_threads(threads).build().expect("Thread pool error");
+      let mut zombie_system = zombie::PreDrawSystem::default();
+      let mut collision_system = CollisionSystem::default();
+      let start = Instant::now();
+      pool.install(|| for idx in 0..STEPS {
+        world.insert(GameTime((f64::from(idx) * SIMULATION_STEP * 30.0) as u64));
+        zombie_system.run_now(&world);
+        collision_system.run_now(&world);
+      });
+      let step = start.elapsed().as_secs_f64() * 1000.0 / f64::from(STEPS);
+      println!("{} zombies, {} threads: {:.3} ms per step", count, threads, step);
+    }
+  }
+}
+
+#[test]
+fn sprite_sheet_test() {
+  use image::{Rgba, RgbaImage};
+  use crate::data::{self, atlas::{pack_frames, SpriteSheet}};
+  use crate::shaders::SpriteFrame;
+
+  // Where the shader samples the texture for a point of the quad
+  let sample = |frame: SpriteFrame, x: f32, y: f32| {
+    let local = ((x - frame.trim[0]) / frame.trim[2], (y - frame.trim[1]) / frame.trim[3]);
+    (frame.rect[0] + local.0 * frame.rect[2], frame.rect[1] + local.1 * frame.rect[3])
+  };
+  let near = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4;
+
+  // The shipped sheets are strips of equal cells, they sample the same as by strip index
+  let character = data::load_character();
+  assert!(near((92.25 / 288.0, 0.75), sample(character.frame("run", 2, 0), 0.25, 0.75)), "run frames start 64 cells in");
+  assert!(near((25.75 / 288.0, 0.25), sample(character.frame("fire", 6, 1), 0.75, 0.25)), "fire frames come first, 4 to a direction");
+  let zombie = data::load_zombie();
+  assert!(near(((32.0 + 3.0 * 8.0 + 5.25) / 112.0, 0.875), sample(zombie.frame("walk", 3, 5), 0.25, 0.75)), "walk frames are on the second row");
+  assert!(near(((64.0 + 6.0 + 2.5) / 112.0, 0.25), sample(zombie.frame("normal", 1, 2), 0.5, 0.5)));
+  assert_eq!(zombie.frame("still", 0, 1), zombie.frame("still", 8, 5), "directions and frames should wrap around");
+
+  // A block of color in a mostly empty frame, its position and color tell the frame apart
+  let frame_image = |animation: u8, direction: u8, frame: u8| {
+    let mut image = RgbaImage::new(64, 64);
+    for (x, y) in (0..10).flat_map(|x| (0..20).map(move |y| (x, y))) {
+      image.put_pixel(20 + x + u32::from(frame), 30 + y, Rgba([animation, direction, frame, 255]));
+    }
+    (format!("{}_{}_{}", ["walk", "still"][animation as usize], direction, frame), image)
+  };
+  let mut frames = (0..2).flat_map(|a| (0..2).flat_map(move |d| (0..3).map(move |f| frame_image(a, d, f)))).collect::<Vec<_>>();
+  frames.push(("blank_0_0".to_string(), RgbaImage::new(64, 64)));
+  let (atlas, json) = pack_frames("test", &frames).expect("Packing error");
+  assert!(atlas.width() * atlas.height() < 64 * 64 * 3, "trimmed frames should take a fraction of the untrimmed area");
+  assert_eq!(json["meta"]["cell"]["w"], 64);
+  assert_eq!(json["frames"]["still_1_2"]["spriteSourceSize"]["x"], 22);
+  assert_eq!(json["frames"]["blank_0_0"]["frame"]["w"], 1);
+
+  let sheet = SpriteSheet::from_json(&json).expect("Sprite sheet error");
+  let (u, v) = sample(sheet.frame("still", 1, 2), 27.5 / 64.0, 40.5 / 64.0);
+  let pixel = atlas.get_pixel((u * atlas.width() as f32) as u32, (v * atlas.height() as f32) as u32);
+  assert_eq!(Rgba([1, 1, 2, 255]), *pixel, "the quad should show the frame's own pixels");
+  let (u, v) = sample(sheet.frame("walk", 0, 0), 5.0 / 64.0, 5.0 / 64.0);
+  assert!(u < sheet.frame("walk", 0, 0).rect[0] && v < sheet.frame("walk", 0, 0).rect[1], "the trimmed border falls outside the frame");
+
+  let mut missing = json.clone();
+  missing["frames"].remove("walk_1_1");
+  assert!(SpriteSheet::from_json(&missing).is_err(), "an animation missing a frame should be refused");
+}
+
+#[test]
+fn zombie_lod_test() {
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::health::Health;
+  use crate::game::constants::{SIMULATION_STEP, ZOMBIE_LOD_FAR_INTERVAL, ZOMBIE_LOD_MID_INTERVAL};
+  use crate::game::GameRng;
+  use crate::graphics::{camera::CameraInputState, DeltaTime, GameTime};
+  use crate::graphics::dimensions::{Dimensions, ProjectionMode};
+  use crate::net::NetMode;
+  use crate::shaders::Position;
+  use crate::zombie::{self, lod::ZombieLod, ZombieDrawable};
+
+  let camera = CameraInputState::new();
+  assert_eq!(ZombieLod::Near, ZombieLod::of(&camera, Position::new(100.0, -200.0)));
+  assert_eq!(ZombieLod::Mid, ZombieLod::of(&camera, Position::new(-1000.0, 0.0)));
+  assert_eq!(ZombieLod::Far, ZombieLod::of(&camera, Position::new(0.0, 3000.0)));
+  for idx in 0..8 {
+    let turns = (0..ZOMBIE_LOD_MID_INTERVAL).filter(|&step| ZombieLod::Mid.is_due(step, idx)).count();
+    assert_eq!(1, turns, "a mid zombie should get one turn in every interval");
+  }
+
+  let run = |split_screen: bool| {
+    let mut world = World::new();
+    world.register::<ZombieDrawable>();
+    world.register::<CharacterInputState>();
+    world.register::<CameraInputState>();
+    world.register::<Player>();
+    world.register::<Health>();
+    world.insert(Dimensions::new(1280.0, 720.0, 1.0, true, ProjectionMode::Perspective, split_screen));
+    world.insert(DeltaTime(SIMULATION_STEP));
+    world.insert(GameTime(10));
+    world.insert(GameRng::new(Some(3)));
+    world.insert(NetMode::Offline);
+    world.create_entity().with(ZombieDrawable::new(Position::new(100.0, 50.0))).build();
+    let far = world.create_entity().with(ZombieDrawable::new(Position::new(4000.0, 0.0))).build();
+    world.create_entity()
+      .with(CharacterInputState::new())
+      .with(CameraInputState::new())
+      .with(Player::new(0))
+      .with(Health::new(100.0))
+      .build();
+
+    // Steps the far zombie moved in
+    let mut system = zombie::PreDrawSystem::default();
+    let mut moves = 0;
+    for _ in 0..ZOMBIE_LOD_FAR_INTERVAL * 2 {
+      let position = |world: &World| world.read_storage::<ZombieDrawable>().get(far).map(|z| z.transform.position);
+      let before = position(&world);
+      system.run_now(&world);
+      moves += (position(&world) != before) as usize;
+    }
+    let lods = world.read_storage::<ZombieDrawable>().join().map(|z| z.lod).collect::<Vec<_>>();
+    (lods, moves)
+  };
+
+  let (lods, moves) = run(false);
+  assert_eq!(vec![ZombieLod::Near, ZombieLod::Far], lods);
+  assert_eq!(2, moves, "a far zombie should only move on its turns");
+  let (lods, moves) = run(true);
+  assert_eq!(vec![ZombieLod::Near, ZombieLod::Near], lods, "split screen should update every zombie in full");
+  assert!(moves > 2);
+}
+
+#[test]
+fn frame_governor_test() {
+  use crate::game::constants::GOVERNOR_HEAT_HAZE_LIMITS;
+  use crate::game::settings::{GovernorSettings, VideoSettings};
+  use crate::gfx_app::headless::Headless;
+  use crate::graphics::governor::{FrameGovernor, GOVERNOR_LEVELS};
+  use crate::net::lockstep::state_hash;
+
+  let settings = GovernorSettings { budget_ms: 20.0, headroom: 0.5, downgrade_frames: 3, restore_frames: 5, ..GovernorSettings::new() };
+  let mut governor = FrameGovernor::default();
+  assert_eq!(usize::MAX, governor.heat_haze_limit());
+  assert_eq!(None, governor.update(30.0, &settings));
+  assert_eq!(None, governor.update(30.0, &settings));
+  assert_eq!(None, governor.update(15.0, &settings), "a frame within budget should break the streak");
+  let changes = (0..3).map(|_| governor.update(30.0, &settings)).collect::<Vec<_>>();
+  assert_eq!(vec![None, None, Some(1)], changes);
+  assert_eq!(GOVERNOR_HEAT_HAZE_LIMITS[1], governor.heat_haze_limit());
+
+  for _ in 0..100 {
+    governor.update(100.0, &settings);
+  }
+  assert_eq!(GOVERNOR_LEVELS - 1, governor.level, "the quality should stop at the lowest level");
+  assert_eq!(0, governor.heat_haze_limit());
+
+  // Between the headroom and the budget nothing changes
+  assert!((0..20).all(|_| governor.update(15.0, &settings).is_none()));
+  let restored = (0..5).filter_map(|_| governor.update(5.0, &settings)).collect::<Vec<_>>();
+  assert_eq!(vec![GOVERNOR_LEVELS - 2], restored, "quality should come back one level per streak");
+
+  assert_eq!(Some(0), governor.update(100.0, &GovernorSettings { enabled: false, ..settings }));
+  assert_eq!(FrameGovernor::default(), governor);
+
+  let video = VideoSettings::from_json(&object! { "governor" => object! { "budget_ms" => 12.5, "headroom" => 3.0, "restore_frames" => 0 } });
+  assert_eq!(12.5, video.governor.budget_ms);
+  assert_eq!(1.0, video.governor.headroom, "the headroom should be clamped to the budget");
+  assert_eq!(1, video.governor.restore_frames);
+  assert_eq!(GovernorSettings::new().downgrade_frames, video.governor.downgrade_frames);
+  assert_eq!(video, VideoSettings::from_json(&video.to_json()));
+
+  // Replays and lockstep games need the same simulation at every quality level
+  let run = |level: usize| {
+    let mut game = Headless::new(4);
+    let mut governor = FrameGovernor::default();
+    governor.level = level;
+    game.world.insert(governor);
+    game.run(200);
+    state_hash(&game.world)
+  };
+  assert_eq!(run(0), run(GOVERNOR_LEVELS - 1), "the quality level should only change what is drawn");
+}
+
+#[test]
+fn scratch_buffer_test() {
+  use rayon::ThreadPoolBuilder;
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::bullet::{BulletDrawable, collision::{CollisionSystem, Contacts}};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::health::Health;
+  use crate::game::constants::PARALLEL_MIN_BULLETS;
+  use crate::game::scratch::{Scratch, ScratchRows, VecPool};
+  use crate::shaders::Position;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::zombie::ZombieDrawable;
+
+  let mut scratch = Scratch::new();
+  assert_eq!(&[1, 2, 3], scratch.fill(1..4));
+  let before = allocation_counter::count();
+  assert_eq!(&[4, 5], scratch.fill(4..6), "the last frame's items should be gone");
+  assert_eq!(0, allocation_counter::count() - before, "a buffer that has grown shouldn't allocate again");
+
+  let mut pool = VecPool::new();
+  let mut items = pool.take();
+  items.extend(0..16);
+  pool.give(items);
+  let items: Vec<i32> = pool.take();
+  assert!(items.is_empty() && items.capacity() >= 16, "a Vec given back should come out empty with its capacity");
+
+  let mut rows = ScratchRows::new();
+  rows.rows(3)[2].push(1);
+  assert_eq!(2, rows.rows(2).len());
+  assert!(rows.rows(3).iter().all(Vec::is_empty), "rows should be emptied for the next frame");
+
+  // Enough bullets to split the contacts up between the rayon workers, all on the zombies
+  let mut world = World::new();
+  world.register::<ZombieDrawable>();
+  world.register::<BulletDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.register::<CharacterInputState>();
+  world.register::<Player>();
+  world.register::<Health>();
+  world.insert(Contacts::default());
+  world.create_entity().with(ZombieDrawable::new(Position::new(4.0, 4.0))).build();
+  world.create_entity().with(ZombieDrawable::new(Position::new(-4.0, -4.0))).build();
+  for idx in 0..PARALLEL_MIN_BULLETS * 2 {
+    world.create_entity().with(BulletDrawable::new(Position::origin(), idx as f32)).build();
+  }
+  world.create_entity()
+    .with(CharacterInputState::new())
+    .with(Player::new(0))
+    .with(Health::new(100.0))
+    .build();
+
+  // On a pool of one the split up work runs on the thread that counts
+  let pool = ThreadPoolBuilder::new().num_threads(1).build().expect("Thread pool error");
+  let mut system = CollisionSystem::default();
+  let (hits, allocations) = pool.install(|| {
+    system.run_now(&world);
+    let hits = world.read_resource::<Contacts>().0.clone();
+    let before = allocation_counter::count();
+    for _ in 0..10 {
+      system.run_now(&world);
+    }
+    (hits, allocation_counter::count() - before)
+  });
+  assert_eq!(PARALLEL_MIN_BULLETS * 2 * 2 + 2, hits.len(), "every bullet should touch both zombies, and both zombies the player");
+  assert_eq!(hits, world.read_resource::<Contacts>().0);
+  assert!(allocations < 10, "split up contacts shouldn't allocate per bullet, {} allocations", allocations);
+}
+
+#[test]
+fn startup_profile_test() {
+  use std::thread;
+  use std::time::{Duration, Instant};
+  use crate::profiler::Overlay;
+  use crate::profiler::startup::StartupProfile;
+
+  let mut startup = StartupProfile::new(Instant::now());
+  thread::sleep(Duration::from_millis(5));
+  startup.mark("window");
+  startup.mark("config");
+  let names = startup.spans().iter().map(|&(name, _)| name).collect::<Vec<_>>();
+  assert_eq!(vec!["window", "config"], names);
+  assert!(startup.spans()[0].1 >= Duration::from_millis(5));
+  assert_eq!(startup.total(), startup.spans().iter().map(|&(_, elapsed)| elapsed).sum::<Duration>(), "the spans should cover the whole start");
+  let summary = startup.summary();
+  assert!(summary.starts_with("Started in ") && summary.contains("(window ") && summary.contains(", config "), "{}", summary);
+
+  let mut names = Overlay::ALL.iter().map(|o| o.name()).collect::<Vec<_>>();
+  names.dedup();
+  assert_eq!(Overlay::ALL.len(), names.len(), "every overlay should have a pass name of its own");
+}
+
+#[test]
+fn terrain_shape_entities_test() {
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::audio::mixer::Mixer;
+  use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+  use crate::graphics::transform::MovementSystem;
+  use crate::graphics::{DeltaTime, set_position};
+  use crate::shaders::Position;
+  use crate::terrain::materials::TileMaterials;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::terrain_shape::{small_hill, TerrainShapeDrawable};
+  use crate::zombie::ZombieDrawable;
+
+  let mut world = World::new();
+  world.register::<CharacterInputState>();
+  world.register::<Player>();
+  world.register::<CharacterDrawable>();
+  world.register::<ZombieDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.register::<TerrainShapeDrawable>();
+  world.insert(TileMaterials::default());
+  world.insert(DeltaTime(0.0));
+  world.insert(Mixer::new());
+
+  let mut first = CharacterInputState::new();
+  first.movement = Position::new(30.0, -20.0);
+  let mut second = CharacterInputState::new();
+  second.movement = Position::new(-500.0, 500.0);
+  world.create_entity().with(second).with(Player::new(1)).build();
+  world.create_entity().with(first).with(Player::new(0)).build();
+  for piece in small_hill(4, 2) {
+    world.create_entity().with(piece).build();
+  }
+
+  MovementSystem.run_now(&world);
+
+  let shapes = world.read_storage::<TerrainShapeDrawable>();
+  assert_eq!(shapes.join().count(), 9, "every piece of a hill should be an entity of its own");
+  let top = set_position(4, 2) + Position::new(30.0, -20.0);
+  assert!(shapes.join().any(|ts| ts.transform.position == top), "hill pieces should follow the first player's camera");
+}
+
+#[test]
+fn debug_shapes_test() {
+  use cgmath::Point2;
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::bullet::BulletDrawable;
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::graphics::{coords_to_tile, tile_to_coords};
+  use crate::graphics::debug_shapes::{DebugShapes, DebugShapesSystem};
+  use crate::profiler::FrameProfile;
+  use crate::shaders::Position;
+  use crate::terrain::path_finding::PathFinder;
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::zombie::ZombieDrawable;
+
+  for &(x, y) in [(10, 20), (64, 64), (100, 3)].iter() {
+    let middle = tile_to_coords(Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+    assert_eq!(coords_to_tile(middle), Point2::new(x, y), "the middle of a tile should map back to it");
+  }
+
+  let mut paths = PathFinder::new();
+  let mut route = Vec::new();
+  let (start, end) = (Point2::new(60, 60), Point2::new(66, 63));
+  paths.route(start, end, &mut route);
+  assert_eq!(route.first().cloned(), paths.next_tile(start, end), "the route should start with the tile a zombie steps to");
+  assert_eq!(route.last().cloned(), Some(end), "the route should end at the end tile");
+
+  let mut world = World::new();
+  world.register::<Player>();
+  world.register::<CharacterInputState>();
+  world.register::<ZombieDrawable>();
+  world.register::<BulletDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.insert(FrameProfile::default());
+  world.insert(DebugShapes::default());
+
+  let zombie = world.create_entity().with(ZombieDrawable::new(Position::new(150.0, 0.0))).build();
+  let mut ci = CharacterInputState::new();
+  ci.movement = tile_to_coords(Point2::new(64.5, 64.5));
+  world.create_entity()
+    .with(Player::new(0))
+    .with(ci)
+    .build();
+
+  let mut system = DebugShapesSystem::default();
+  system.run_now(&world);
+  assert!(world.read_resource::<DebugShapes>().vertices().is_empty(), "nothing should be collected while the shapes are hidden");
+
+  world.write_resource::<FrameProfile>().is_debug_shapes_visible = true;
+  system.run_now(&world);
+  let count = world.read_resource::<DebugShapes>().vertices().len();
+  assert!(count > 0 && count % 2 == 0, "shown shapes should be whole lines");
+
+  world.delete_entity(zombie).expect("Zombie despawn error");
+  system.run_now(&world);
+  // The hit box alone is four lines
+  assert!(count - world.read_resource::<DebugShapes>().vertices().len() > 8, "a chasing zombie should add its hit box and route");
+}
+
+#[test]
+fn inspector_test() {
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::game::health::Health;
+  use crate::game::inspector::{Inspector, InspectorControl, InspectorSystem};
+
+  let mut world = World::new();
+  world.register::<crate::character::Player>();
+  world.register::<crate::character::controls::CharacterInputState>();
+  world.register::<Health>();
+  world.register::<crate::game::status::Stamina>();
+  world.register::<crate::graphics::camera::CameraInputState>();
+  world.register::<crate::graphics::transform::Transform>();
+  world.register::<crate::game::status::StatusEffects>();
+  world.register::<crate::zombie::ZombieDrawable>();
+  world.register::<crate::bullet::BulletDrawable>();
+  world.register::<crate::terrain_object::TerrainObjectDrawable>();
+  world.register::<crate::terrain_shape::TerrainShapeDrawable>();
+  world.insert(Inspector::new());
+  world.create_entity().with(Health::new(100.0)).build();
+
+  let (mut system, control) = InspectorSystem::new();
+  system.run_now(&world);
+  assert!(world.read_resource::<Inspector>().lines.is_empty());
+
+  for c in [InspectorControl::Toggle, InspectorControl::Decrease, InspectorControl::Decrease] {
+    control.send(c).unwrap();
+  }
+  system.run_now(&world);
+  {
+    let inspector = world.read_resource::<Inspector>();
+    assert!(inspector.lines[0].starts_with("entity 0 (1 of 1), simulation running"));
+    assert_eq!(inspector.lines.last().unwrap(), "> health 90.00");
+  }
+  let health = world.read_storage::<Health>();
+  assert_eq!((&health).join().next().unwrap().current, 90.0);
+  drop(health);
+
+  control.send(InspectorControl::Step).unwrap();
+  control.send(InspectorControl::Pause).unwrap();
+  control.send(InspectorControl::Step).unwrap();
+  system.run_now(&world);
+  let mut inspector = world.write_resource::<Inspector>();
+  assert!(inspector.is_paused);
+  assert_eq!(inspector.allowed_steps(3), 1);
+  assert_eq!(inspector.allowed_steps(3), 0);
+  inspector.is_paused = false;
+  assert_eq!(inspector.allowed_steps(3), 3);
+}
+
+#[test]
+fn event_log_test() {
+  use std::io;
+  use std::sync::{Arc, Mutex};
+  use crate::ecs::{RunNow, World, WorldExt};
+  use crate::game::events::{EventLogSystem, GameEvent, GameEvents};
+  use crate::profiler::Profiled;
+  use crate::shaders::Position;
+
+  #[derive(Clone, Default)]
+  struct Lines(Arc<Mutex<Vec<u8>>>);
+
+  impl io::Write for Lines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  let mut world = World::new();
+  world.insert(GameEvents::new());
+  let reader = world.write_resource::<GameEvents>().register_reader();
+  let (profiler, _timings) = crossbeam_channel::unbounded();
+  let mut system = Profiled::new(EventLogSystem::new(reader), "event-log-system", &profiler);
+  world.write_resource::<GameEvents>().single_write(GameEvent::ZombieKilled(Position::new(10.0, 20.0), "runner"));
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(3));
+
+  let lines = Lines::default();
+  let writer = lines.clone();
+  let subscriber = tracing_subscriber::fmt()
+    .with_max_level(tracing::Level::TRACE)
+    .with_ansi(false)
+    .with_writer(move || writer.clone())
+    .finish();
+  tracing::subscriber::with_default(subscriber, || system.run_now(&world));
+
+  let log = String::from_utf8(lines.0.lock().unwrap().clone()).unwrap();
+  assert!(log.contains("system{name=\"event-log-system\"}"), "events should be logged in the span of their system: {}", log);
+  assert!(log.contains("zombie killed position=10, 20 prefab=\"runner\""), "{}", log);
+  assert!(log.contains("wave started wave=3"), "{}", log);
+}
+
+#[test]
+fn config_seed_test() {
+  use crate::game::settings::{load_config, Settings};
+
+  let path = std::env::temp_dir().join(format!("hinterland-seed-{}.toml", std::process::id()));
+  let filename = path.to_string_lossy();
+  Settings::new().save(&filename);
+  assert!(load_config(&filename)["gameplay"]["seed"].is_null(), "an unseeded config should not get a seed");
+
+  let settings = Settings::from_json(&object! { "gameplay" => object! { "seed" => 1234 } });
+  assert_eq!(Some(1234), settings.seed);
+  settings.save(&filename);
+  assert_eq!(Some(1234), Settings::load(&filename).seed, "saving the settings from the menu should keep the seed");
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn headless_zombie_reaches_player_test() {
+  use crate::ecs::{Builder, Join, WorldExt};
+  use crate::game::events::GameEvent;
+  use crate::game::health::Health;
+  use crate::gfx_app::headless::Headless;
+  use crate::net::lockstep::state_hash;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let start = |seed| {
+    let mut game = Headless::new(seed);
+    let zombies = (&game.world.entities(), &game.world.read_storage::<ZombieDrawable>()).join().map(|(e, _)| e).collect::<Vec<_>>();
+    game.world.delete_entities(&zombies).expect("Zombie despawn error");
+    game.world.create_entity().with(ZombieDrawable::new(Position::new(150.0, 0.0))).build();
+    game
+  };
+  let mut game = start(10);
+  let steps = game.run_until(600, |w| w.read_storage::<Health>().join().any(|h| h.current < h.max));
+  assert!(steps.is_some(), "a zombie 150 px away should reach the player within 10 s");
+  assert!(game.events().iter().any(|e| matches!(e, GameEvent::PlayerDamaged(..))));
+
+  let mut again = start(10);
+  again.run(game.steps);
+  assert_eq!(state_hash(&game.world), state_hash(&again.world), "the same seed and steps should give the same world");
+}
+
+#[test]
+fn headless_bullet_kill_test() {
+  use crate::ecs::{Builder, Join, WorldExt};
+  use crate::character::controls::CharacterControl;
+  use crate::game::constants::{RESOLUTION_X, RESOLUTION_Y};
+  use crate::game::events::GameEvent;
+  use crate::game::score::Score;
+  use crate::gfx_app::headless::Headless;
+  use crate::gfx_app::mouse_controls::MouseControl;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let mut game = Headless::new(7);
+  let zombies = (&game.world.entities(), &game.world.read_storage::<ZombieDrawable>()).join().map(|(e, _)| e).collect::<Vec<_>>();
+  game.world.delete_entities(&zombies).expect("Zombie despawn error");
+  game.world.create_entity().with(ZombieDrawable::new(Position::new(300.0, 0.0))).build();
+  // Aims and clicks to the right of the player twice, a zombie takes two hits
+  game.control(0, CharacterControl::CtrlPressed);
+  let mut events = Vec::new();
+  for _ in 0..2 {
+    game.mouse(MouseControl::LeftClick, Some((f64::from(RESOLUTION_X) / 2.0 + 100.0, f64::from(RESOLUTION_Y) / 2.0)));
+    game.step();
+    game.mouse(MouseControl::LeftClick, None);
+    game.run(20);
+    events.extend(game.events());
+  }
+  assert_eq!(2, events.iter().filter(|e| matches!(e, GameEvent::BulletFired(..))).count());
+  assert_eq!(2, events.iter().filter(|e| matches!(e, GameEvent::ZombieHit(..))).count());
+  assert!(events.iter().any(|e| matches!(e, GameEvent::ZombieKilled(..))), "the second hit should kill the zombie: {:?}", events);
+  assert_eq!(1, game.world.read_resource::<Score>().kills);
+}
+
+#[test]
+fn off_screen_bullet_test() {
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::bullet::{self, BulletDrawable, collision::Collision};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::graphics::{camera::CameraInputState, DeltaTime, dimensions::Dimensions};
+  use crate::shaders::Position;
+
+  let mut world = World::new();
+  world.register::<BulletDrawable>();
+  world.register::<CharacterInputState>();
+  world.register::<CameraInputState>();
+  world.register::<Player>();
+  world.insert(Dimensions::default());
+  world.insert(DeltaTime(1.0 / 60.0));
+
+  let visible = world.create_entity().with(BulletDrawable::new(Position::origin(), 0.0)).build();
+  let culled = world.create_entity().with(BulletDrawable::new(Position::new(2000.0, 0.0), 0.0)).build();
+  world.create_entity()
+    .with(CharacterInputState::new())
+    .with(CameraInputState::new())
+    .with(Player::new(0))
+    .build();
+
+  bullet::PreDrawSystem.run_now(&world);
+  bullet::PreDrawSystem.run_now(&world);
+
+  let bullets = world.read_storage::<BulletDrawable>();
+  assert!((&bullets).join().all(|b| b.status == Collision::Flying), "culled bullets shouldn't be despawned");
+  assert!(bullets.get(visible).unwrap().transform.position != Position::origin(), "visible bullets should fly");
+  assert!(bullets.get(culled).unwrap().transform.position == Position::new(2000.0, 0.0), "bullets out of view should wait");
+}
+
+#[test]
+fn game_event_channel_test() {
+  use crate::game::events::{GameEvent, GameEvents};
+  use crate::shaders::Position;
+
+  let mut events = GameEvents::new();
+  let mut audio = events.register_reader();
+  let mut score = events.register_reader();
+  events.single_write(GameEvent::BulletFired(Position::origin()));
+  events.single_write(GameEvent::ZombieHit(Position::new(10.0, 0.0), 1.0));
+
+  let heard: Vec<GameEvent> = events.read(&mut audio).cloned().collect();
+  assert_eq!(vec![GameEvent::BulletFired(Position::origin()), GameEvent::ZombieHit(Position::new(10.0, 0.0), 1.0)], heard);
+  assert_eq!(0, events.read(&mut audio).count(), "events should be read only once per reader");
+  assert_eq!(2, events.read(&mut score).count(), "every reader should see every event");
+
+  events.single_write(GameEvent::WaveStarted(2));
+  assert_eq!(vec![GameEvent::WaveStarted(2)], events.read(&mut score).cloned().collect::<Vec<_>>());
+  assert_eq!(1, events.read(&mut audio).count(), "a reader that fell behind should catch up");
+}
+
+#[test]
+fn spawner_wave_test() {
+  use crate::ecs::{Join, WorldExt};
+  use crate::game::constants::ZOMBIE_WAVE_GROWTH;
+  use crate::game::events::GameEvent;
+  use crate::game::spawner::WaveState;
+  use crate::gfx_app::headless::Headless;
+  use crate::zombie::ZombieDrawable;
+
+  let mut game = Headless::new(3);
+  for z in (&mut game.world.write_storage::<ZombieDrawable>()).join() {
+    z.health.current = 0.0;
+  }
+  game.run(1);
+  assert_eq!(1, game.world.read_resource::<WaveState>().wave, "the next wave should wait a few seconds");
+
+  let steps = game.run_until(600, |w| w.read_resource::<WaveState>().wave == 2);
+  assert!(steps.is_some(), "a cleared wave should be followed by the next one");
+  assert!(game.events().contains(&GameEvent::WaveStarted(2)));
+  let zombies = game.world.read_storage::<ZombieDrawable>();
+  assert_eq!(ZOMBIE_WAVE_GROWTH * 3, zombies.join().count(), "the dead zombies should be despawned and the new wave spawned");
+  assert!(zombies.join().all(|z| !z.is_dead()));
+}
+
+#[test]
+fn step_accumulator_test() {
+  use crate::game::constants::{MAX_FRAME_TIME, SIMULATION_STEP};
+  use crate::graphics::StepAccumulator;
+
+  let mut accumulator = StepAccumulator::default();
+  accumulator.add(SIMULATION_STEP * 2.5, 1.0);
+  assert_eq!(2, accumulator.due_steps());
+  accumulator.take_steps(2);
+  assert!((accumulator.interpolation().0 - 0.5).abs() < 0.0001, "half a step should be left over");
+  assert_eq!(0, accumulator.due_steps());
+
+  accumulator.add(SIMULATION_STEP * 0.75, 1.0);
+  assert_eq!(1, accumulator.due_steps(), "the leftover should carry over to the next frame");
+  accumulator.take_steps(1);
+  assert!((accumulator.interpolation().0 - 0.25).abs() < 0.0001);
+
+  accumulator.add(SIMULATION_STEP, 0.5);
+  assert_eq!(0, accumulator.due_steps(), "slow motion should run fewer steps");
+
+  accumulator.reset();
+  accumulator.add(10.0, 1.0);
+  assert_eq!((MAX_FRAME_TIME / SIMULATION_STEP) as u32, accumulator.due_steps(), "a long stall should be capped");
+  accumulator.take_steps(0);
+  assert!(accumulator.interpolation().0 <= (MAX_FRAME_TIME / SIMULATION_STEP) as f32, "time owed by a waiting peer should stay capped");
+}
+
+#[test]
+fn collision_contacts_test() {
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::bullet::{BulletDrawable, collision::{Collision, CollisionSystem, Contact, Contacts}};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::health::Health;
+  use crate::shaders::Position;
+  use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+  use crate::zombie::ZombieDrawable;
+
+  let mut world = World::new();
+  world.register::<BulletDrawable>();
+  world.register::<ZombieDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.register::<CharacterInputState>();
+  world.register::<Player>();
+  world.register::<Health>();
+  world.insert(Contacts::default());
+
+  let zombie = |world: &mut World, position| world.create_entity().with(ZombieDrawable::new(position)).build();
+  let far_zombie = zombie(&mut world, Position::new(200.0, 0.0));
+  let near_zombie = zombie(&mut world, Position::new(0.0, 10.0));
+  let behind_zombie = zombie(&mut world, Position::new(-300.0, 0.0));
+  let mut dead = ZombieDrawable::new(Position::new(-300.0, 0.0));
+  dead.health.current = 0.0;
+  world.create_entity().with(dead).build();
+  let bullet = |world: &mut World, position| world.create_entity().with(BulletDrawable::new(position, 0.0)).build();
+  let hitting = bullet(&mut world, Position::new(205.0, 0.0));
+  bullet(&mut world, Position::new(100.0, 100.0));
+  let lost = bullet(&mut world, Position::new(100_000.0, 100_000.0));
+  let behind = bullet(&mut world, Position::new(-300.0, 5.0));
+  world.create_entity().with(TerrainObjectDrawable::new(Position::new(500.0, 500.0), TerrainTexture::Ammo)).build();
+  world.create_entity().with(TerrainObjectDrawable::new(Position::new(5.0, -5.0), TerrainTexture::House)).build();
+  let pickup = world.create_entity().with(TerrainObjectDrawable::new(Position::new(5.0, -5.0), TerrainTexture::Ammo)).build();
+  let far_pickup = world.create_entity().with(TerrainObjectDrawable::new(Position::new(205.0, -5.0), TerrainTexture::Ammo)).build();
+  // The second player stands by the far zombie, the fourth where the first one does and the dead
+  // third by the zombie behind
+  let player = |world: &mut World, index, movement, health| {
+    let mut ci = CharacterInputState::new();
+    ci.movement = movement;
+    world.create_entity().with(ci).with(Player::new(index)).with(Health { current: health, max: 100.0 }).build();
+  };
+  player(&mut world, 1, Position::new(-200.0, 0.0), 100.0);
+  player(&mut world, 0, Position::origin(), 100.0);
+  player(&mut world, 3, Position::origin(), 100.0);
+  player(&mut world, 2, Position::new(300.0, 0.0), 0.0);
+
+  CollisionSystem::default().run_now(&world);
+
+  assert_eq!(vec![
+    Contact::BulletZombie { bullet: hitting, zombie: far_zombie },
+    Contact::BulletTile { bullet: lost, collision: Collision::OutOfBounds },
+    Contact::BulletZombie { bullet: behind, zombie: behind_zombie },
+    Contact::ZombiePlayer { zombie: near_zombie, player: 0 },
+    Contact::ZombiePlayer { zombie: far_zombie, player: 1 },
+    Contact::ZombiePlayer { zombie: near_zombie, player: 3 },
+    Contact::PlayerPickup { pickup, player: 0 },
+    Contact::PlayerPickup { pickup: far_pickup, player: 1 },
+  ], world.read_resource::<Contacts>().0, "dead zombies and players, misses and other objects shouldn't make contacts");
+}
+
+#[test]
+fn health_test() {
+  use crate::game::health::Health;
+
+  let mut health = Health::new(10.0);
+  assert!(!health.take_damage(4.0), "a hit that leaves health shouldn't kill");
+  assert_eq!(6.0, health.current);
+  assert!(!health.is_dead());
+
+  assert!(health.take_damage(50.0), "the hit that takes the last health should kill");
+  assert_eq!(0.0, health.current, "health shouldn't go below zero");
+  assert!(health.is_dead());
+
+  assert!(!health.take_damage(1.0), "death should only be reported once");
+  assert_eq!(0.0, health.current);
+  assert_eq!(10.0, health.max, "damage shouldn't change the maximum");
+}
+
+#[test]
+fn script_operation_limit_test() {
+  use crate::ecs::{RunNow, World, WorldExt};
+  use crate::character::Player;
+  use crate::game::console::Console;
+  use crate::game::events::GameEvents;
+  use crate::game::GameRng;
+  use crate::game::health::Health;
+  use crate::game::prefab::Prefabs;
+  use crate::game::scripting::ScriptSystem;
+  use crate::graphics::GameTime;
+  use crate::zombie::ZombieDrawable;
+
+  let mut world = World::new();
+  world.register::<ZombieDrawable>();
+  world.register::<Health>();
+  world.register::<Player>();
+  world.insert(Prefabs::default());
+  world.insert(GameTime(0));
+  world.insert(GameRng::new(Some(1)));
+  world.insert(Console::new());
+  let mut events = GameEvents::new();
+  let reader = events.register_reader();
+  world.insert(events);
+
+  let mut runaway = ScriptSystem::with_script(reader, "let ticks = 0; fn on_tick(time) { ticks += 1; loop {} }");
+  runaway.run_now(&world);
+  world.write_resource::<Console>().commands.push("40 + 2".to_string());
+  runaway.run_now(&world);
+  assert_eq!(Some(&"42".to_string()), world.read_resource::<Console>().lines.first(), "the scope should outlive a stopped hook");
+
+  let reader = world.write_resource::<GameEvents>().register_reader();
+  let mut broken = ScriptSystem::with_script(reader, "fn on_tick(time) {");
+  world.write_resource::<Console>().commands.push("1 + 1".to_string());
+  broken.run_now(&world);
+  assert_eq!(Some(&"2".to_string()), world.read_resource::<Console>().lines.get(1), "a script that doesn't compile should be left out");
+}
+
+#[test]
+fn game_state_stack_test() {
+  use crate::ecs::{World, WorldExt};
+  use crate::game::localization::Localization;
+  use crate::game::settings::Settings;
+  use crate::game::state::{apply_transitions, GameState, GameStates, Menu, StateTransition};
+
+  let mut world = World::new();
+  world.insert(GameStates::new(GameState::Playing));
+  world.insert(Menu::new());
+  world.insert(Settings::new());
+  world.insert(Localization::default());
+
+  world.write_resource::<GameStates>().request(StateTransition::Push(GameState::Settings));
+  assert_eq!(GameState::Playing, world.read_resource::<GameStates>().current(), "transitions should wait for the end of the frame");
+  apply_transitions(&mut world);
+  assert_eq!(GameState::Settings, world.read_resource::<GameStates>().current());
+  assert!(world.read_resource::<Menu>().is_visible, "entering a menu state should open its menu");
+
+  world.write_resource::<GameStates>().request(StateTransition::Switch(GameState::Controls));
+  apply_transitions(&mut world);
+  assert_eq!(GameState::Controls, world.read_resource::<GameStates>().current());
+  assert!(world.read_resource::<GameStates>().contains(GameState::Playing), "replacing the top should keep the states below");
+  assert!(!world.read_resource::<GameStates>().contains(GameState::Settings));
+
+  world.write_resource::<GameStates>().request(StateTransition::Pop);
+  world.write_resource::<GameStates>().request(StateTransition::Pop);
+  apply_transitions(&mut world);
+  assert_eq!(GameState::Playing, world.read_resource::<GameStates>().current(), "the last state shouldn't be popped");
+  assert!(!world.read_resource::<Menu>().is_visible, "leaving a menu state should close its menu");
+
+  world.write_resource::<GameStates>().request(StateTransition::Push(GameState::Settings));
+  world.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Menu));
+  apply_transitions(&mut world);
+  assert_eq!(GameState::Menu, world.read_resource::<GameStates>().current());
+  assert!(!world.read_resource::<GameStates>().contains(GameState::Playing), "a reset should drop the whole stack");
+}
+
+#[test]
+fn music_track_selection_test() {
+  use crate::audio::director::AudioDirectorSystem;
+  use crate::audio::mixer::Mixer;
+  use crate::audio::music::{MusicTrack, parse_playlist};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::ecs::{Builder, RunNow, World, WorldExt};
+  use crate::game::events::{GameEvent, GameEvents};
+  use crate::game::health::Health;
+  use crate::game::state::{GameState, GameStates};
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let playlist = json::parse(r#"{
+    "menu": { "file": "assets/music/menu.ogg" },
+    "exploration": { "file": "assets/music/exploration.ogg", "volume": 0.6 },
+    "combat": { "file": "assets/music/combat.ogg" },
+    "boss": { "file": "assets/music/boss.ogg" }
+  }"#).unwrap();
+  let tracks = parse_playlist(&playlist).expect("the fixture playlist should parse");
+  assert_eq!(vec![MusicTrack::Menu, MusicTrack::Exploration, MusicTrack::Combat, MusicTrack::Boss], tracks.iter().map(|t| t.track).collect::<Vec<_>>());
+  assert_eq!(0.6, tracks[1].volume);
+  assert_eq!(1.0, tracks[0].volume, "the volume should default to full");
+  assert!(parse_playlist(&json::parse(r#"{ "lullaby": { "file": "a.ogg" } }"#).unwrap()).is_err(), "unknown tracks should be rejected");
+  assert!(parse_playlist(&json::parse(r#"{ "menu": {} }"#).unwrap()).is_err(), "a track needs a file");
+
+  let mut world = World::new();
+  world.register::<ZombieDrawable>();
+  world.register::<Health>();
+  world.register::<Player>();
+  world.register::<CharacterInputState>();
+  world.insert(GameStates::new(GameState::Menu));
+  world.insert(Mixer::new());
+  let mut events = GameEvents::new();
+  let mut director = AudioDirectorSystem::new(events.register_reader());
+  world.insert(events);
+  world.create_entity().with(CharacterInputState::new()).build();
+
+  director.run_now(&world);
+  assert_eq!(Some(MusicTrack::Menu), world.read_resource::<Mixer>().music);
+
+  *world.write_resource::<GameStates>() = GameStates::new(GameState::Playing);
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(1));
+  director.run_now(&world);
+  assert_eq!(Some(MusicTrack::Exploration), world.read_resource::<Mixer>().music, "a quiet run should explore");
+
+  for idx in 0..6 {
+    world.create_entity().with(ZombieDrawable::new(Position::new(50.0 * idx as f32, 0.0))).build();
+  }
+  director.run_now(&world);
+  assert_eq!(Some(MusicTrack::Combat), world.read_resource::<Mixer>().music, "a nearby horde should start the combat track");
+
+  world.write_resource::<GameEvents>().single_write(GameEvent::WaveStarted(5));
+  director.run_now(&world);
+  assert_eq!(Some(MusicTrack::Boss), world.read_resource::<Mixer>().music, "boss waves have their own track");
+}
+
+#[test]
+fn volume_settings_test() {
+  use crate::audio::mixer::{Bus, Mixer};
+  use crate::game::constants::DUCKED_GAIN;
+  use crate::game::settings::Settings;
+
+  let settings = Settings::from_json(&object! { "audio" => object! { "master" => 1.5, "music" => -0.2 } });
+  assert_eq!(1.0, settings.master_volume, "volumes above full should be clamped");
+  assert_eq!(0.0, settings.music_volume, "negative volumes should be clamped");
+  assert_eq!(Settings::new().effects_volume, settings.effects_volume, "a missing volume should keep its default");
+
+  let mut settings = Settings::new();
+  settings.master_volume = 0.5;
+  settings.music_volume = 0.4;
+  settings.effects_volume = 0.8;
+  let mut mixer = Mixer::new();
+  settings.apply(&mut mixer);
+  assert!((mixer.bus_gain(Bus::Music) - 0.2).abs() < 0.0001, "bus gains should multiply with the master volume");
+  assert!((mixer.bus_gain(Bus::Effects) - 0.4).abs() < 0.0001);
+  assert!((mixer.bus_gain(Bus::Interface) - 0.4).abs() < 0.0001, "the interface should follow the effects volume");
+
+  mixer.is_ducked = true;
+  assert!((mixer.bus_gain(Bus::Music) - 0.2 * DUCKED_GAIN).abs() < 0.0001, "the pause menu should duck the music");
+  assert!((mixer.bus_gain(Bus::Interface) - 0.4).abs() < 0.0001, "menu clicks shouldn't be ducked");
+}
+
+#[test]
+fn footstep_sounds_test() {
+  use crate::audio::mixer::{Mixer, MixerCommand, parse_manifest, Sound};
+  use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+  use crate::ecs::{Builder, Join, RunNow, World, WorldExt};
+  use crate::game::constants::{FOOTSTEP_INTERVAL, SIMULATION_STEP};
+  use crate::graphics::DeltaTime;
+  use crate::graphics::transform::MovementSystem;
+  use crate::shaders::Position;
+  use crate::terrain::materials::{Material, TileMaterials};
+  use crate::terrain_object::TerrainObjectDrawable;
+  use crate::terrain_shape::TerrainShapeDrawable;
+  use crate::zombie::ZombieDrawable;
+
+  let manifest = json::parse(r#"{
+    "footstep_grass": { "file": ["assets/sounds/grass_1.ogg", "assets/sounds/grass_2.ogg", "assets/sounds/grass_3.ogg"], "volume": 0.5 },
+    "footstep_water": { "file": "assets/sounds/water.ogg" },
+    "footstep_stone": { "file": ["assets/sounds/stone_1.ogg", "assets/sounds/stone_2.ogg"] }
+  }"#).unwrap();
+  let sounds = parse_manifest(&manifest).expect("the fixture manifest should parse");
+  assert_eq!(vec![Sound::FootstepGrass, Sound::FootstepWater, Sound::FootstepStone], sounds.iter().map(|s| s.sound).collect::<Vec<_>>());
+  assert_eq!(3, sounds[0].paths.len(), "a list of files should become a sample set");
+  assert_eq!(1, sounds[1].paths.len());
+  assert!(parse_manifest(&json::parse(r#"{ "footstep_grass": { "file": [] } }"#).unwrap()).is_err(), "an empty sample set should be rejected");
+
+  assert_eq!(Sound::FootstepGrass, Sound::footstep(Material::Grass));
+  assert_eq!(Sound::FootstepWater, Sound::footstep(Material::Water));
+  assert_eq!(Sound::FootstepStone, Sound::footstep(Material::Stone));
+  assert_eq!(Material::Grass, TileMaterials::default().at(Position::origin()), "tiles without a material should be grass");
+
+  let mut world = World::new();
+  world.register::<CharacterInputState>();
+  world.register::<Player>();
+  world.register::<CharacterDrawable>();
+  world.register::<ZombieDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.register::<TerrainShapeDrawable>();
+  world.insert(TileMaterials::default());
+  world.insert(DeltaTime(SIMULATION_STEP));
+  world.insert(Mixer::new());
+  world.create_entity()
+    .with(CharacterInputState::new())
+    .with(Player::new(0))
+    .with(CharacterDrawable::new())
+    .build();
+
+  let steps = (FOOTSTEP_INTERVAL as f64 * 2.5 / SIMULATION_STEP) as usize;
+  let mut footsteps = 0;
+  for idx in 0..steps * 2 {
+    let is_walking = idx < steps;
+    for ci in (&mut world.write_storage::<CharacterInputState>()).join() {
+      ci.previous_movement = ci.movement;
+      if is_walking {
+        ci.movement = ci.movement + Position::new(1.0, 0.0);
+      }
+    }
+    MovementSystem.run_now(&world);
+    footsteps += std::mem::take(&mut world.write_resource::<Mixer>().commands).iter()
+      .filter(|c| matches!(c, MixerCommand::Play(_, Sound::FootstepGrass, _, None)))
+      .count();
+  }
+  assert_eq!(2, footsteps, "walking should step every footstep interval and standing still shouldn't");
+}
+
+#[test]
+fn music_stems_test() {
+  use crate::audio::music::{layer_gain, parse_playlist};
+  use crate::game::constants::MUSIC_STEM_FADE_RANGE;
+
+  let playlist = json::parse(r#"{
+    "combat": {
+      "file": "assets/music/combat.ogg",
+      "stems": [
+        { "file": "assets/music/combat_drums.ogg", "intensity": 0.5 },
+        { "file": "assets/music/combat_brass.ogg" }
+      ]
+    }
+  }"#).unwrap();
+  let tracks = parse_playlist(&playlist).expect("the fixture playlist should parse");
+  let stems = &tracks[0].stems;
+  assert_eq!(vec!["assets/music/combat_drums.ogg", "assets/music/combat_brass.ogg"], stems.iter().map(|s| s.path.as_str()).collect::<Vec<_>>());
+  assert_eq!(0.5, stems[0].intensity);
+  assert_eq!(1.0, stems[1].intensity, "a stem should be fully in at full intensity by default");
+  assert!(parse_playlist(&json::parse(r#"{ "combat": { "file": "a.ogg", "stems": [{}] } }"#).unwrap()).is_err(), "a stem needs a file");
+
+  assert_eq!(1.0, layer_gain(None, 0.0), "the track itself should always play at full gain");
+  assert_eq!(0.0, layer_gain(Some(0.5), 0.5 - MUSIC_STEM_FADE_RANGE), "a stem should be silent below its fade range");
+  assert!((layer_gain(Some(0.5), 0.5 - MUSIC_STEM_FADE_RANGE / 2.0) - 0.5).abs() < 1e-5, "a stem should be half in halfway up its fade range");
+  assert_eq!(1.0, layer_gain(Some(0.5), 0.5));
+  assert_eq!(1.0, layer_gain(Some(0.5), 1.0), "a stem should stay fully in above its intensity");
+}
+
+#[test]
+fn sound_occlusion_test() {
+  use cgmath::Point2;
+  use crate::audio::mixer::is_occluded;
+  use crate::graphics::tile_to_coords;
+
+  let listener = tile_to_coords(Point2::new(53.5, 54.5));
+  let behind_house = tile_to_coords(Point2::new(58.5, 54.5));
+  assert!(is_occluded(listener, listener - behind_house), "a house between the listener and the emitter should occlude it");
+  assert!(!is_occluded(listener, listener - listener), "an emitter on the listener shouldn't be occluded");
+
+  let open_listener = tile_to_coords(Point2::new(53.5, 60.5));
+  let open_emitter = tile_to_coords(Point2::new(58.5, 60.5));
+  assert!(!is_occluded(open_listener, open_listener - open_emitter), "an open line shouldn't be occluded");
+}
+
+#[test]
+fn restart_run_test() {
+  use crate::character::CharacterDrawable;
+  use crate::ecs::{Join, WorldExt};
+  use crate::game::events::GameEvent;
+  use crate::game::health::Health;
+  use crate::game::score::Score;
+  use crate::game::spawner::WaveState;
+  use crate::gfx_app::{headless::Headless, init};
+  use crate::zombie::ZombieDrawable;
+
+  let mut game = Headless::new(5);
+  game.run(10);
+  let entities = game.world.entities().join().count();
+  let zombie_count = (&game.world.read_storage::<ZombieDrawable>()).join().count();
+  for h in (&mut game.world.write_storage::<Health>()).join() {
+    h.current = 0.0;
+  }
+  {
+    let mut score = game.world.write_resource::<Score>();
+    score.kills = 12;
+    score.wave = 4;
+  }
+  game.world.write_resource::<WaveState>().wave = 4;
+  game.events();
+
+  init::restart_run(&mut game.world);
+  assert_eq!(entities, game.world.entities().join().count(), "nothing of the finished run should be left behind");
+  assert_eq!(1, (&game.world.read_storage::<CharacterDrawable>()).join().count());
+  assert!((&game.world.read_storage::<Health>()).join().all(|h| h.current == h.max), "the new run should start at full health");
+  assert_eq!(0, game.world.read_resource::<Score>().kills);
+  assert_eq!(1, game.world.read_resource::<WaveState>().wave, "the new run should start from the first wave");
+  assert!(game.events().contains(&GameEvent::WaveStarted(1)));
+
+  game.run(10);
+  assert_eq!(zombie_count, (&game.world.read_storage::<ZombieDrawable>()).join().count(), "the first wave should spawn again");
+}
+
+#[test]
+fn server_simulation_test() {
+  use crate::ecs::{Builder, Join, WorldExt};
+  use crate::game::state::{GameState, GameStates};
+  use crate::gfx_app::headless::Headless;
+  use crate::net::NetMode;
+  use crate::shaders::Position;
+  use crate::zombie::{lod::ZombieLod, ZombieDrawable};
+
+  // The server runs the same simulation systems as the headless game, as the host of a session
+  let lod_out_of_view = |net_mode: NetMode| {
+    let mut game = Headless::new(9);
+    game.world.insert(net_mode);
+    let zombies = (&game.world.entities(), &game.world.read_storage::<ZombieDrawable>()).join().map(|(e, _)| e).collect::<Vec<_>>();
+    game.world.delete_entities(&zombies).expect("Zombie despawn error");
+    let zombie = game.world.create_entity().with(ZombieDrawable::new(Position::new(-1000.0, 0.0))).build();
+    game.run(10);
+    assert_eq!(GameState::Playing, game.world.read_resource::<GameStates>().current());
+    let zombies = game.world.read_storage::<ZombieDrawable>();
+    zombies.get(zombie).unwrap().lod
+  };
+  assert_eq!(ZombieLod::Mid, lod_out_of_view(NetMode::Offline));
+  assert_eq!(ZombieLod::Near, lod_out_of_view(NetMode::Host), "remote players look at other parts of the map, so no zombie should be culled");
+}
+
+#[test]
+fn drawable_entities_test() {
+  use crate::ecs::{Join, LazyUpdate, RunNow, World, WorldExt};
+  use crate::bullet::{self, BulletDrawable, collision::{Collision, Contact, Contacts}};
+  use crate::game::spawner::{despawn_dead_zombies, spawn_bullet, spawn_pickup, spawn_zombie};
+  use crate::critter::palette::CritterPalette;
+  use crate::shaders::Position;
+  use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+  use crate::zombie::ZombieDrawable;
+
+  let mut world = World::new();
+  world.register::<ZombieDrawable>();
+  world.register::<BulletDrawable>();
+  world.register::<TerrainObjectDrawable>();
+  world.insert(Contacts::default());
+
+  // Spawned from a system, every drawable becomes an entity of its own once the world is maintained
+  {
+    let lazy = world.read_resource::<LazyUpdate>();
+    spawn_zombie(&lazy, Position::new(100.0, 0.0), CritterPalette::Original);
+    spawn_zombie(&lazy, Position::new(-100.0, 0.0), CritterPalette::Toxic);
+    spawn_bullet(&lazy, Position::origin(), 0.0);
+    spawn_bullet(&lazy, Position::origin(), 90.0);
+    spawn_pickup(&lazy, Position::new(0.0, 50.0));
+  }
+  assert_eq!(0, world.read_storage::<ZombieDrawable>().join().count(), "spawns should wait for the end of the step");
+  world.maintain();
+  assert_eq!(2, world.read_storage::<ZombieDrawable>().join().count());
+  assert_eq!(2, world.read_storage::<BulletDrawable>().join().count());
+  let objects = world.read_storage::<TerrainObjectDrawable>();
+  assert!(objects.join().count() == 1 && objects.join().all(|o| o.object_type == TerrainTexture::Ammo));
+  drop(objects);
+
+  let bullets = (&world.entities(), &world.read_storage::<BulletDrawable>()).join().map(|(e, _)| e).collect::<Vec<_>>();
+  world.write_resource::<Contacts>().0.push(Contact::BulletTile { bullet: bullets[0], collision: Collision::Hit });
+  bullet::ImpactSystem.run_now(&world);
+  world.maintain();
+  assert!(!world.is_alive(bullets[0]), "a bullet that hit a wall should be despawned");
+  assert!(world.is_alive(bullets[1]));
+
+  for z in (&mut world.write_storage::<ZombieDrawable>()).join().take(1) {
+    z.health.current = 0.0;
+  }
+  despawn_dead_zombies(&world.read_resource::<LazyUpdate>());
+  world.maintain();
+  assert_eq!(1, world.read_storage::<ZombieDrawable>().join().count(), "only the dead zombie should be despawned");
+}
+
+#[test]
+fn zombie_spawn_order_test() {
+  use crate::ecs::{Builder, Join, WorldExt};
+  use crate::game::spawner::spawn_zombie;
+  use crate::gfx_app::headless::Headless;
+  use crate::net::lockstep::state_hash;
+  use crate::zombie::ZombieDrawable;
+  use crate::critter::palette::CritterPalette;
+  use crate::shaders::Position;
+
+  let mut game = Headless::new(7);
+  let mut orders = game.world.read_storage::<ZombieDrawable>().join().map(|z| z.spawn_order).collect::<Vec<_>>();
+  orders.sort_unstable();
+  assert_eq!((0..orders.len()).collect::<Vec<_>>(), orders, "the first wave should be numbered in order");
+  spawn_zombie(&game.world.read_resource(), Position::new(900.0, 0.0), CritterPalette::Original);
+  spawn_zombie(&game.world.read_resource(), Position::new(-900.0, 0.0), CritterPalette::Original);
+  game.world.maintain();
+  let last = game.world.read_storage::<ZombieDrawable>().join().map(|z| z.spawn_order).max();
+  assert_eq!(Some(orders.len() + 1), last, "spawned zombies should be numbered after the others");
+
+  // A lockstep peer can hold the same zombies under other entities
+  let mut other = Headless::new(7);
+  let zombies = (&other.world.entities(), &other.world.read_storage::<ZombieDrawable>()).join()
+    .map(|(e, z)| (e, z.clone()))
+    .collect::<Vec<_>>();
+  other.world.delete_entities(&zombies.iter().map(|(e, _)| *e).collect::<Vec<_>>()).expect("Zombie despawn error");
+  for _ in 0..5 {
+    other.world.create_entity().build();
+  }
+  for (_, z) in zombies.into_iter().rev() {
+    other.world.create_entity().with(z).build();
+  }
+  let mut game = Headless::new(7);
+  game.run(300);
+  other.run(300);
+  assert_eq!(state_hash(&game.world), state_hash(&other.world), "zombies should not depend on their entities");
+}
+
+#[test]
+fn headless_coop_test() {
+  use crate::ecs::{Builder, Join, RunNow, System, WorldExt};
+  use crate::character::{CharacterDrawable, controls::{CharacterControl, CharacterInputState}, Player};
+  use crate::game::events::{GameEvent, GameEvents};
+  use crate::game::health::Health;
+  use crate::game::spawner;
+  use crate::game::state::{apply_transitions, GameState, GameStates, MenuSystem};
+  use crate::game::status::{StatusEffect, StatusEffects};
+  use crate::gfx_app::headless::Headless;
+  use crate::net::press_click;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  let mut game = Headless::new(5);
+  let zombies = (&game.world.entities(), &game.world.read_storage::<ZombieDrawable>()).join().map(|(e, _)| e).collect::<Vec<_>>();
+  game.world.delete_entities(&zombies).expect("Zombie despawn error");
+  // The second player stands out of the first one's reach, with a zombie right by it
+  let second = spawner::spawn_player(game.world.create_entity(), 1);
+  game.world.write_storage::<CharacterInputState>().get_mut(second).unwrap().movement = Position::new(600.0, 0.0);
+  game.world.create_entity().with(ZombieDrawable::new(Position::new(-600.0, 60.0))).build();
+  let health = |game: &Headless, index| (&game.world.read_storage::<Player>(), &game.world.read_storage::<Health>()).join()
+    .find(|(p, _)| p.index == index)
+    .map(|(_, h)| h.current)
+    .unwrap();
+
+  let steps = game.run_until(600, |w| w.read_storage::<Health>().get(second).is_some_and(|h| h.current < h.max));
+  assert!(steps.is_some(), "the zombie should chase the player nearest to it");
+  assert_eq!(100.0, health(&game, 0), "the first player is out of reach");
+  let events = game.events();
+  assert!(events.iter().any(|e| matches!(e, GameEvent::PlayerDamaged(1, ..))));
+  assert!(!events.iter().any(|e| matches!(e, GameEvent::PlayerDamaged(0, ..))));
+  assert!(game.world.read_storage::<StatusEffects>().get(second).unwrap().active().any(|e| e == StatusEffect::Hurt));
+
+  // Every player fires its own character
+  let ammunition = |game: &Headless| game.world.read_storage::<CharacterDrawable>().get(second).unwrap().stats.ammunition;
+  let before = ammunition(&game);
+  game.control(1, CharacterControl::CtrlPressed);
+  game.step();
+  press_click(&game.world, 1, (0.0, 100.0));
+  game.step();
+  assert_eq!(before - 1, ammunition(&game), "a click of the second player should fire its character");
+  assert!(game.events().iter().any(|e| matches!(e, GameEvent::BulletFired(_))));
+
+  // One death doesn't end a co-op run
+  let mut menu = MenuSystem::new(game.world.write_resource::<GameEvents>().register_reader()).0;
+  System::setup(&mut menu, &mut game.world);
+  game.world.write_storage::<Health>().get_mut(second).unwrap().current = 0.01;
+  assert!(game.run_until(600, |w| w.read_storage::<Health>().get(second).unwrap().is_dead()).is_some());
+  assert!(game.events().contains(&GameEvent::PlayerKilled(1)));
+  menu.run_now(&game.world);
+  apply_transitions(&mut game.world);
+  assert_eq!(GameState::Playing, game.world.read_resource::<GameStates>().current(), "the first player is still standing");
+  game.run(10);
+  assert!(!game.events().iter().any(|e| matches!(e, GameEvent::PlayerDamaged(1, ..))), "a dead player takes no more hits");
+}
+
+#[test]
+fn headless_server_test() {
+  use crate::ecs::{Builder, Join, WorldExt};
+  use crate::character::{controls::CharacterInputState, Player};
+  use crate::game::events::GameEvent;
+  use crate::game::health::Health;
+  use crate::game::spawner;
+  use crate::gfx_app::headless::Headless;
+  use crate::shaders::Position;
+  use crate::zombie::ZombieDrawable;
+
+  // Like a dedicated server, the run has no player 0 and the one who joined stands away from the start
+  let mut game = Headless::new(5);
+  let removed = (&game.world.entities(), &game.world.read_storage::<Player>()).join()
+    .map(|(e, _)| e)
+    .chain((&game.world.entities(), &game.world.read_storage::<ZombieDrawable>()).join().map(|(e, _)| e))
+    .collect::<Vec<_>>();
+  game.world.delete_entities(&removed).expect("Entity despawn error");
+  let joined = spawner::spawn_player(game.world.create_entity(), 1);
+  game.world.write_storage::<CharacterInputState>().get_mut(joined).unwrap().movement = Position::new(600.0, 0.0);
+  game.world.create_entity().with(ZombieDrawable::new(Position::new(-600.0, 60.0))).build();
+
+  let steps = game.run_until(600, |w| w.read_storage::<Health>().get(joined).is_some_and(|h| h.current < h.max));
+  assert!(steps.is_some(), "the simulation should run around the players who joined");
+  assert!(game.events().iter().all(|e| !matches!(e, GameEvent::PlayerDamaged(0, ..))));
+  assert_eq!(vec![1], game.world.read_storage::<Player>().join().map(|p| p.index).collect::<Vec<_>>());
+}
+
+#[cfg(feature = "touch")]
+#[test]
+fn touch_controls_test() {
+  use glutin::ElementState::{Pressed, Released};
+  use glutin::TouchPhase;
+  use crate::gfx_app::input::Action;
+  use crate::gfx_app::replay::InputEvent;
+  use crate::gfx_app::touch::TouchControls;
+
+  let window = (800.0, 600.0);
+  let mut touch = TouchControls::new();
+  assert!(touch.update(0, TouchPhase::Started, (100.0, 300.0), window).is_empty(), "the stick should start where it is touched");
+  assert!(touch.update(0, TouchPhase::Moved, (110.0, 300.0), window).is_empty(), "small moves are in the dead zone");
+  assert_eq!(vec![InputEvent::Key(Action::MoveRight, Pressed)], touch.update(0, TouchPhase::Moved, (150.0, 300.0), window));
+  assert_eq!(vec![InputEvent::Key(Action::MoveRight, Released), InputEvent::Key(Action::MoveLeft, Pressed)],
+             touch.update(0, TouchPhase::Moved, (50.0, 300.0), window), "changing direction should release the old one");
+
+  let fire = touch.update(1, TouchPhase::Started, (750.0, 550.0), window);
+  assert_eq!(Some(&InputEvent::MouseClick(Some((200.0, 300.0)))), fire.last(), "shots should aim along the stick");
+  assert_eq!(vec![InputEvent::Key(Action::MoveLeft, Released)], touch.update(0, TouchPhase::Ended, (50.0, 300.0), window));
+
+  assert!(touch.update(2, TouchPhase::Started, (500.0, 100.0), window).is_empty());
+  assert!(touch.update(3, TouchPhase::Started, (600.0, 100.0), window).is_empty());
+  match touch.update(3, TouchPhase::Moved, (700.0, 100.0), window).as_slice() {
+    [InputEvent::Zoom(lines)] => assert!(*lines > 0.0, "spreading two fingers should zoom in"),
+    events => panic!("pinch should zoom, got {:?}", events),
+  }
+}
@@ -6,7 +6,8 @@ use num::{Num, NumCast};
 
 use crate::bullet::BulletDrawable;
 use crate::character::CharacterDrawable;
-use crate::game::{constants::{RESOLUTION_Y, TERRAIN_OBJECTS, TILE_SIZE, TILES_PCS_H, TILES_PCS_W, Y_OFFSET}, get_rand_from_range};
+use crate::critter::CharacterSprite;
+use crate::game::{constants::{MAX_FRAME_TIME, RESOLUTION_Y, SIMULATION_STEP, TERRAIN_OBJECTS, TILE_SIZE, TILES_PCS_H, TILES_PCS_W, Y_OFFSET}, GameRng, get_rand_from_range};
 use crate::game::constants::TILE_WIDTH;
 use crate::gfx_app::{mouse_controls::MouseInputState};
 use crate::graphics::{dimensions::Dimensions, orientation::Orientation};
@@ -14,12 +15,20 @@ use crate::shaders::Position;
 use crate::terrain_object::TerrainObjectDrawable;
 use crate::zombie::ZombieDrawable;
 
+pub mod assets;
 pub mod camera;
+pub mod debug_shapes;
 pub mod dimensions;
+pub mod governor;
 mod graphics_test;
 pub mod mesh;
 pub mod orientation;
+pub mod post_process;
+pub mod render_graph;
+pub mod shader_params;
 pub mod texture;
+pub mod transform;
+pub mod viewport;
 
 const Y_MODIFIER: f32 = 0.9;
 
@@ -29,6 +38,45 @@ pub struct DeltaTime(pub f64);
 #[derive(Default)]
 pub struct GameTime(pub u64);
 
+// How far rendering is between the last two simulation steps, from 0.0 to 1.0. Only the scroll
+// of the scene is interpolated, zombies and bullets are drawn where the last step left them, so
+// their own movement advances in 60 Hz steps even on faster displays.
+#[derive(Default)]
+pub struct Interpolation(pub f32);
+
+// Frame time owed to the fixed simulation steps. Long stalls are capped, so the simulation catches
+// up without running a burst of steps.
+#[derive(Default)]
+pub struct StepAccumulator(f64);
+
+impl StepAccumulator {
+  pub fn add(&mut self, frame_time: f64, time_scale: f64) {
+    self.0 += frame_time.min(MAX_FRAME_TIME) * time_scale;
+  }
+
+  pub fn due_steps(&self) -> u32 {
+    (self.0 / SIMULATION_STEP) as u32
+  }
+
+  // A lockstep peer waiting for its turn or a paused simulation may run fewer steps than are due,
+  // the time they owe is kept up to a capped frame
+  pub fn take_steps(&mut self, steps: u32) {
+    self.0 = (self.0 - steps as f64 * SIMULATION_STEP).min(MAX_FRAME_TIME);
+  }
+
+  pub fn reset(&mut self) {
+    self.0 = 0.0;
+  }
+
+  pub fn time_to_next_step(&self) -> f64 {
+    SIMULATION_STEP - self.0
+  }
+
+  pub fn interpolation(&self) -> Interpolation {
+    Interpolation((self.0 / SIMULATION_STEP) as f32)
+  }
+}
+
 pub fn flip_y_axel(point: Point2<f32>) -> Point2<f32> {
   Point2::new(point.x, RESOLUTION_Y as f32 - point.y)
 }
@@ -140,15 +188,22 @@ pub fn coords_to_tile(position: Position) -> Point2<i32> {
   Point2::new(((pos.x + pos.y) / TILE_WIDTH) as i32, ((pos.y - pos.x) / TILE_WIDTH) as i32)
 }
 
+// Map coordinates of a corner of the tile grid, the inverse of coords_to_tile. The middle of a tile
+// is half a tile further on both axes.
+pub fn tile_to_coords(tile: Point2<f32>) -> Position {
+  let (x, y) = ((tile.x - tile.y) * TILE_WIDTH / 2.0, (tile.x + tile.y) * TILE_WIDTH / 2.0);
+  Position::new(-x, (y - Y_OFFSET) * Y_MODIFIER)
+}
+
 fn round(number: f32, precision: usize) -> f32 {
   let ten: f32 = 10.0;
   let divider = ten.powf(precision as f32);
   (number * divider).round() / divider
 }
 
-pub fn get_nearest_random_tile_position(pos: Position) -> Position {
-  fn iter(pos: Position) -> Position {
-    let offset = Position::new(get_rand_from_range(-2, 2) as f32, get_rand_from_range(-2, 2) as f32);
+pub fn get_nearest_random_tile_position(pos: Position, rng: &mut GameRng) -> Position {
+  fn iter(pos: Position, rng: &mut GameRng) -> Position {
+    let offset = Position::new(get_rand_from_range(rng, -2, 2) as f32, get_rand_from_range(rng, -2, 2) as f32);
     let offset_point = Position::new(
       round(offset.x() * TILE_WIDTH - offset.y() / TILE_WIDTH * Y_MODIFIER, 3),
       round(offset.y() * TILE_WIDTH - offset.y() / TILE_WIDTH * Y_MODIFIER, 3),
@@ -156,7 +211,7 @@ pub fn get_nearest_random_tile_position(pos: Position) -> Position {
     pos + offset_point
   }
   loop {
-    let res = iter(pos);
+    let res = iter(pos, rng);
     if can_move_to_tile(res) {
       return res;
     }
@@ -167,6 +222,16 @@ pub fn distance(a: f32, b: f32) -> f32 {
   (a.powf(2.0) + b.powf(2.0)).sqrt()
 }
 
+// Samples the segment every half tile, the end points themselves are not tested
+pub fn is_line_blocked(from: Position, to: Position) -> bool {
+  let steps = (position_distance(from, to) / (TILE_SIZE / 2.0)) as usize;
+  (1..steps).any(|step| {
+    let t = step as f32 / steps as f32;
+    let point = Position::new(from.x() + (to.x() - from.x()) * t, from.y() + (to.y() - from.y()) * t);
+    !can_move_to_tile(point)
+  })
+}
+
 fn position_distance(a: Position, b: Position) -> f32 {
   let d = a - b;
   distance(d.x(), d.y())
@@ -174,7 +239,7 @@ fn position_distance(a: Position, b: Position) -> f32 {
 
 pub enum Drawables<'b> {
   Bullet(&'b BulletDrawable),
-  Character(&'b mut CharacterDrawable),
+  Character(&'b mut CharacterDrawable, &'b CharacterSprite),
   TerrainAmmo(&'b TerrainObjectDrawable),
   TerrainHouse(&'b TerrainObjectDrawable),
   TerrainTree(&'b TerrainObjectDrawable),
@@ -184,12 +249,12 @@ pub enum Drawables<'b> {
 impl<'b> Drawables<'b> {
   pub fn get_vertical_pos(drawable: &Drawables) -> f32 {
     match drawable {
-      Drawables::Bullet(e) => e.position.y(),
-      Drawables::Zombie(e) => e.position.y(),
-      Drawables::TerrainAmmo(e) => e.position.y(),
-      Drawables::TerrainHouse(e) => e.position.y(),
-      Drawables::TerrainTree(e) => e.position.y(),
-      Drawables::Character(e) => e.position.y(),
+      Drawables::Bullet(e) => e.transform.position.y(),
+      Drawables::Zombie(e) => e.transform.position.y(),
+      Drawables::TerrainAmmo(e) => e.transform.position.y(),
+      Drawables::TerrainHouse(e) => e.transform.position.y(),
+      Drawables::TerrainTree(e) => e.transform.position.y(),
+      Drawables::Character(e, _) => e.position.y(),
     }
   }
 }
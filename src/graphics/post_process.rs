@@ -0,0 +1,109 @@
+use std::time::Instant;
+
+use cgmath::Point2;
+use gfx;
+use gfx::handle::{RenderTargetView, ShaderResourceView};
+
+use crate::game::constants::{HEAT_HAZE_RADIUS, HEAT_HAZE_STRENGTH};
+use crate::graphics::mesh::PlainMesh;
+use crate::graphics::render_graph::{PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader};
+use crate::graphics::viewport::View;
+use crate::profiler::duration_to_ms;
+use crate::shaders::{distortion_pipeline, DistortionSource, Position, post_process_pipeline};
+
+const DISTORTION_VERT: &[u8] = include_bytes!("../shaders/distortion.v.glsl");
+const DISTORTION_FRAG: &[u8] = include_bytes!("../shaders/distortion.f.glsl");
+const POST_PROCESS_VERT: &[u8] = include_bytes!("../shaders/post_process.v.glsl");
+const POST_PROCESS_FRAG: &[u8] = include_bytes!("../shaders/post_process.f.glsl");
+
+// Heat sources write screen space offsets into a float target, which the composite pass uses to shift scene lookups
+pub struct HeatHazePass<R: gfx::Resources> {
+  distortion: gfx::pso::bundle::Bundle<R, distortion_pipeline::Data<R>>,
+  distortion_updates: PipelineUpdates<R, distortion_pipeline::Meta>,
+  composite: gfx::pso::bundle::Bundle<R, post_process_pipeline::Data<R>>,
+  composite_updates: PipelineUpdates<R, post_process_pipeline::Meta>,
+  distortion_target: RenderTargetView<R, gfx::format::Rgba16F>,
+  start_time: Instant,
+}
+
+impl<R: gfx::Resources> HeatHazePass<R> {
+  pub fn new<F>(factory: &mut F,
+                window: &RenderTargets<R>,
+                scene: ShaderResourceView<R, [f32; 4]>,
+                reloader: &mut ShaderReloader<F>) -> HeatHazePass<R>
+    where F: gfx::Factory<R> {
+    use gfx::traits::FactoryExt;
+
+    let (w, h, _, _) = window.color.get_dimensions();
+    let (_, distortion_view, distortion_target) = factory.create_render_target::<gfx::format::Rgba16F>(w, h)
+      .expect("Distortion target creation error");
+
+    let (distortion_pso, distortion_updates) = PassDescriptor::new("Heat haze distortion",
+                                                                   ShaderFile::new("distortion.v.glsl", DISTORTION_VERT),
+                                                                   ShaderFile::new("distortion.f.glsl", DISTORTION_FRAG),
+                                                                   distortion_pipeline::new())
+      .build(factory, reloader);
+
+    let source_mesh = PlainMesh::new_with_data(factory, Point2::new(HEAT_HAZE_RADIUS, HEAT_HAZE_RADIUS), None, None, None);
+    let distortion_data = distortion_pipeline::Data {
+      vbuf: source_mesh.vertex_buffer,
+      globals: window.globals.clone(),
+      source_cb: factory.create_constant_buffer(1),
+      scissor: window.scissor(),
+      out_distortion: distortion_target.clone(),
+    };
+
+    let (composite_pso, composite_updates) = PassDescriptor::new("Post process",
+                                                                 ShaderFile::new("post_process.v.glsl", POST_PROCESS_VERT),
+                                                                 ShaderFile::new("post_process.f.glsl", POST_PROCESS_FRAG),
+                                                                 post_process_pipeline::new())
+      .build(factory, reloader);
+
+    let screen_mesh = PlainMesh::new_with_data(factory, Point2::new(1.0, 1.0), None, None, None);
+    let sampler = factory.create_sampler_linear();
+    let composite_data = post_process_pipeline::Data {
+      vbuf: screen_mesh.vertex_buffer,
+      scene: (scene, sampler.clone()),
+      distortion: (distortion_view, sampler),
+      out_color: window.color.clone(),
+    };
+
+    HeatHazePass {
+      distortion: gfx::Bundle::new(source_mesh.slice, distortion_pso, distortion_data),
+      distortion_updates,
+      composite: gfx::Bundle::new(screen_mesh.slice, composite_pso, composite_data),
+      composite_updates,
+      distortion_target,
+      start_time: Instant::now(),
+    }
+  }
+
+  pub fn clear<C>(&self, encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    encoder.clear(&self.distortion_target, [0.0, 0.0, 0.0, 0.0]);
+  }
+
+  pub fn draw_source<C>(&mut self,
+                        position: Position,
+                        view: &View,
+                        encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    if !view.is_visible(position) {
+      return;
+    }
+    self.distortion_updates.apply(&mut self.distortion.pso);
+    self.distortion.data.scissor = view.scissor;
+    encoder.update_constant_buffer(&self.distortion.data.source_cb, &DistortionSource {
+      position: view.position(position).position,
+      strength: HEAT_HAZE_STRENGTH,
+      phase: (duration_to_ms(self.start_time.elapsed()) / 1000.0) as f32,
+    });
+    self.distortion.encode(encoder);
+  }
+
+  pub fn composite<C>(&mut self, encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    self.composite_updates.apply(&mut self.composite.pso);
+    self.composite.encode(encoder);
+  }
+}
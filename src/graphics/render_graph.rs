@@ -0,0 +1,431 @@
+use std::fs;
+use std::time::{Instant, SystemTime};
+
+use cgmath::Point2;
+use crossbeam_channel as channel;
+use gfx;
+use gfx::handle::{Buffer, DepthStencilView, RenderTargetView, ShaderResourceView};
+use gfx::pso::{PipelineInit, PipelineState};
+use gfx::traits::FactoryExt;
+use tracing::{info, warn};
+
+use crate::critter::palette::{CritterPalette, palette_texture_data, PALETTE_ROWS, PALETTE_WIDTH};
+use crate::game::constants::{CRITTER_INSTANCE_BUF_LENGTH, SHADER_RELOAD_INTERVAL};
+use crate::gfx_app::{ColorFormat, DepthFormat};
+use crate::graphics::mesh::RectangularTexturedMesh;
+use crate::graphics::texture::load_raw_texture;
+use crate::graphics::shader_params::ShaderParams;
+use crate::graphics::viewport::{full_screen_rect, View};
+use crate::shaders::preprocessor::{includes_modified, preprocess, read_shader, shader_path};
+use crate::shaders::{critter_fallback_pipeline, critter_pipeline, CritterInstance, CritterLocals, Globals, Position, SpriteFrame, static_element_pipeline};
+
+const STATIC_ELEMENT_VERT: &[u8] = include_bytes!("../shaders/static_element.v.glsl");
+const STATIC_ELEMENT_FRAG: &[u8] = include_bytes!("../shaders/static_element.f.glsl");
+const CRITTER_VERT: &[u8] = include_bytes!("../shaders/character.v.glsl");
+const CRITTER_FRAG: &[u8] = include_bytes!("../shaders/character.f.glsl");
+
+#[derive(Clone)]
+pub struct RenderTargets<R: gfx::Resources> {
+  pub color: RenderTargetView<R, ColorFormat>,
+  pub depth: DepthStencilView<R, DepthFormat>,
+  pub globals: Buffer<R, Globals>,
+}
+
+impl<R: gfx::Resources> RenderTargets<R> {
+  pub fn new<F>(factory: &mut F, color: RenderTargetView<R, ColorFormat>, depth: DepthStencilView<R, DepthFormat>) -> RenderTargets<R>
+    where F: gfx::Factory<R> {
+    RenderTargets {
+      color,
+      depth,
+      globals: factory.create_constant_buffer(1),
+    }
+  }
+
+  pub fn scissor(&self) -> gfx::Rect {
+    full_screen_rect(&self.color)
+  }
+
+  // Same size targets sharing the globals buffer, so world passes can render into a texture for post processing
+  pub fn offscreen<F>(&self, factory: &mut F) -> (RenderTargets<R>, ShaderResourceView<R, [f32; 4]>)
+    where F: gfx::Factory<R> {
+    let (w, h, _, _) = self.color.get_dimensions();
+    let (_, scene, color) = factory.create_render_target::<ColorFormat>(w, h)
+      .expect("Offscreen color target creation error");
+    let depth = factory.create_depth_stencil_view_only::<DepthFormat>(w, h)
+      .expect("Offscreen depth target creation error");
+    (RenderTargets {
+      color,
+      depth,
+      globals: self.globals.clone(),
+    }, scene)
+  }
+
+  // Every pipeline binds the same globals buffer, so it is written once per view instead of once per draw call.
+  // Targets made by offscreen share the buffer, the cache belongs to the only one that writes it.
+  pub fn update_globals<C>(&self, cache: &mut ConstantCache<Globals>, view: &View, time_passed: u64, params: &ShaderParams,
+                           encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    cache.update(&self.globals, Globals::new(&view.projection, time_passed, params), encoder);
+  }
+}
+
+// The value a constant buffer was last given. A pass draws everything through its one buffer, so
+// an upload is only recorded when the value differs from what the buffer already holds, like the
+// position of a still camera or the sprite row of critters drawn one after another.
+pub struct ConstantCache<T> {
+  last: Option<T>,
+}
+
+impl<T: Copy + PartialEq> ConstantCache<T> {
+  // True when the value needs an upload, it is taken as uploaded from then on
+  pub fn changed(&mut self, value: T) -> bool {
+    if self.last == Some(value) {
+      return false;
+    }
+    self.last = Some(value);
+    true
+  }
+
+  pub fn update<R, C>(&mut self, buffer: &Buffer<R, T>, value: T, encoder: &mut gfx::Encoder<R, C>)
+    where R: gfx::Resources, C: gfx::CommandBuffer<R> {
+    if self.changed(value) {
+      encoder.update_constant_buffer(buffer, &value);
+    }
+  }
+}
+
+impl<T> Default for ConstantCache<T> {
+  fn default() -> ConstantCache<T> {
+    ConstantCache { last: None }
+  }
+}
+
+pub struct ShaderFile {
+  file_name: &'static str,
+  embedded: &'static [u8],
+  defines: Vec<(&'static str, String)>,
+  modified: Option<SystemTime>,
+}
+
+impl ShaderFile {
+  pub fn new(file_name: &'static str, embedded: &'static [u8]) -> ShaderFile {
+    let mut shader = ShaderFile {
+      file_name,
+      embedded,
+      defines: Vec::new(),
+      modified: None,
+    };
+    shader.modified = shader.last_modified();
+    shader
+  }
+
+  pub fn define<T: ToString>(mut self, name: &'static str, value: T) -> ShaderFile {
+    self.defines.push((name, value.to_string()));
+    self
+  }
+
+  fn last_modified(&self) -> Option<SystemTime> {
+    let modified = fs::metadata(shader_path(self.file_name)).and_then(|m| m.modified()).ok();
+    modified.max(includes_modified())
+  }
+
+  fn source(&self) -> Result<Vec<u8>, String> {
+    preprocess(&read_shader(self.file_name, self.embedded), &self.defines)
+      .map_err(|e| format!("{}: {}", self.file_name, e))
+  }
+
+  fn has_changed(&mut self) -> bool {
+    let modified = self.last_modified();
+    if modified != self.modified {
+      self.modified = modified;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+pub struct PassDescriptor<I: PipelineInit> {
+  pub name: &'static str,
+  pub vertex_shader: ShaderFile,
+  pub fragment_shader: ShaderFile,
+  pub init: I,
+  pub primitive: gfx::Primitive,
+}
+
+impl<I: PipelineInit + Clone + 'static> PassDescriptor<I> {
+  pub fn new(name: &'static str, vertex_shader: ShaderFile, fragment_shader: ShaderFile, init: I) -> PassDescriptor<I> {
+    PassDescriptor {
+      name,
+      vertex_shader,
+      fragment_shader,
+      init,
+      primitive: gfx::Primitive::TriangleList,
+    }
+  }
+
+  pub fn with_primitive(self, primitive: gfx::Primitive) -> PassDescriptor<I> {
+    PassDescriptor { primitive, ..self }
+  }
+
+  fn create<R, F>(&self, factory: &mut F) -> Result<PipelineState<R, I::Meta>, String>
+    where R: gfx::Resources, F: gfx::Factory<R> {
+    let vertex_source = self.vertex_shader.source()?;
+    let fragment_source = self.fragment_shader.source()?;
+    let shaders = factory.create_shader_set(&vertex_source, &fragment_source)
+      .map_err(|e| format!("{:?}", e))?;
+    factory.create_pipeline_state(&shaders, self.primitive, gfx::state::Rasterizer::new_fill(), self.init.clone())
+      .map_err(|e| format!("{:?}", e))
+  }
+
+  pub fn build<R, F>(self, factory: &mut F, reloader: &mut ShaderReloader<F>) -> (PipelineState<R, I::Meta>, PipelineUpdates<R, I::Meta>)
+    where R: gfx::Resources, F: gfx::Factory<R> {
+    let pso = self.create(factory)
+      .unwrap_or_else(|e| panic!("{} shader loading error: {}", self.name, e));
+    let (tx, rx) = channel::unbounded();
+    if cfg!(debug_assertions) {
+      reloader.watch(self, tx);
+    }
+    (pso, PipelineUpdates { queue: rx })
+  }
+}
+
+pub struct PipelineUpdates<R: gfx::Resources, M> {
+  queue: channel::Receiver<PipelineState<R, M>>,
+}
+
+impl<R: gfx::Resources, M> PipelineUpdates<R, M> {
+  pub fn latest(&self) -> Option<PipelineState<R, M>> {
+    self.queue.try_iter().last()
+  }
+
+  pub fn apply(&self, pso: &mut PipelineState<R, M>) {
+    if let Some(reloaded) = self.latest() {
+      *pso = reloaded;
+    }
+  }
+}
+
+type PassReload<F> = Box<dyn FnMut(&mut F)>;
+
+pub struct ShaderReloader<F> {
+  passes: Vec<PassReload<F>>,
+  last_check: Instant,
+}
+
+impl<F> ShaderReloader<F> {
+  pub fn new() -> ShaderReloader<F> {
+    ShaderReloader {
+      passes: Vec::new(),
+      last_check: Instant::now(),
+    }
+  }
+
+  fn watch<R, I>(&mut self, mut pass: PassDescriptor<I>, sender: channel::Sender<PipelineState<R, I::Meta>>)
+    where R: gfx::Resources, F: gfx::Factory<R>, I: PipelineInit + Clone + 'static {
+    self.passes.push(Box::new(move |factory: &mut F| {
+      let vertex_changed = pass.vertex_shader.has_changed();
+      let fragment_changed = pass.fragment_shader.has_changed();
+      if vertex_changed || fragment_changed {
+        match pass.create(factory) {
+          Ok(pso) => {
+            info!("Reloaded {} shaders", pass.name);
+            sender.send(pso).expect("Shader reload update error");
+          }
+          Err(e) => warn!("{} shader reload error: {}", pass.name, e),
+        }
+      }
+    }));
+  }
+
+  pub fn reload(&mut self, factory: &mut F) {
+    let elapsed = self.last_check.elapsed();
+    if elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_millis()) < SHADER_RELOAD_INTERVAL {
+      return;
+    }
+    self.last_check = Instant::now();
+    for pass in &mut self.passes {
+      pass(factory);
+    }
+  }
+}
+
+impl<F> Default for ShaderReloader<F> {
+  fn default() -> Self {
+    ShaderReloader::new()
+  }
+}
+
+pub struct StaticElementPass<R: gfx::Resources> {
+  bundle: gfx::pso::bundle::Bundle<R, static_element_pipeline::Data<R>>,
+  pso_updates: PipelineUpdates<R, static_element_pipeline::Meta>,
+  position: ConstantCache<Position>,
+}
+
+impl<R: gfx::Resources> StaticElementPass<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                name: &'static str,
+                mesh: RectangularTexturedMesh<R>,
+                reloader: &mut ShaderReloader<F>) -> StaticElementPass<R>
+    where F: gfx::Factory<R> {
+    let (pso, pso_updates) = PassDescriptor::new(name,
+                                                 ShaderFile::new("static_element.v.glsl", STATIC_ELEMENT_VERT),
+                                                 ShaderFile::new("static_element.f.glsl", STATIC_ELEMENT_FRAG),
+                                                 static_element_pipeline::new())
+      .build(factory, reloader);
+
+    let pipeline_data = static_element_pipeline::Data {
+      vbuf: mesh.mesh.vertex_buffer,
+      position_cb: factory.create_constant_buffer(1),
+      globals: targets.globals.clone(),
+      static_element_sheet: (mesh.mesh.texture.raw, factory.create_sampler_linear()),
+      scissor: targets.scissor(),
+      out_color: targets.color.clone(),
+      out_depth: targets.depth.clone(),
+    };
+
+    StaticElementPass {
+      bundle: gfx::Bundle::new(mesh.mesh.slice, pso, pipeline_data),
+      pso_updates,
+      position: ConstantCache::default(),
+    }
+  }
+
+  pub fn draw<C>(&mut self,
+                 position: Position,
+                 view: &View,
+                 encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    self.pso_updates.apply(&mut self.bundle.pso);
+    self.bundle.data.scissor = view.scissor;
+    self.position.update(&self.bundle.data.position_cb, view.position(position), encoder);
+    self.bundle.encode(encoder);
+  }
+}
+
+fn critter_fragment_shader() -> ShaderFile {
+  ShaderFile::new("character.f.glsl", CRITTER_FRAG)
+    .define("PALETTE_WIDTH", PALETTE_WIDTH)
+    .define("PALETTE_ROWS", PALETTE_ROWS)
+}
+
+enum CritterBundle<R: gfx::Resources> {
+  Instanced {
+    bundle: gfx::pso::bundle::Bundle<R, critter_pipeline::Data<R>>,
+    pso_updates: PipelineUpdates<R, critter_pipeline::Meta>,
+  },
+  Fallback {
+    bundle: gfx::pso::bundle::Bundle<R, critter_fallback_pipeline::Data<R>>,
+    pso_updates: PipelineUpdates<R, critter_fallback_pipeline::Meta>,
+  },
+}
+
+// Instanced arrays need GL 3.3, older drivers get one draw call per critter through a uniform block instead
+pub struct CritterPass<R: gfx::Resources> {
+  bundle: CritterBundle<R>,
+  instance: ConstantCache<CritterLocals>,
+}
+
+impl<R: gfx::Resources> CritterPass<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                name: &'static str,
+                mesh: RectangularTexturedMesh<R>,
+                reloader: &mut ShaderReloader<F>) -> CritterPass<R>
+    where F: gfx::Factory<R> {
+    let sampler = factory.create_sampler_linear();
+    let palette = load_raw_texture(factory, &palette_texture_data(), Point2::new(PALETTE_WIDTH as i32, PALETTE_ROWS as i32));
+    let bundle = if factory.get_capabilities().instance_rate_supported {
+      let (pso, pso_updates) = PassDescriptor::new(name,
+                                                   ShaderFile::new("character.v.glsl", CRITTER_VERT).define("INSTANCED", 1),
+                                                   critter_fragment_shader(),
+                                                   critter_pipeline::new())
+        .build(factory, reloader);
+
+      let pipeline_data = critter_pipeline::Data {
+        vbuf: mesh.mesh.vertex_buffer,
+        instances: factory.create_buffer(CRITTER_INSTANCE_BUF_LENGTH,
+                                         gfx::buffer::Role::Vertex,
+                                         gfx::memory::Usage::Dynamic,
+                                         gfx::memory::Bind::empty())
+          .expect("Critter instance buffer creation error"),
+        globals: targets.globals.clone(),
+        charactersheet: (mesh.mesh.texture.raw, sampler.clone()),
+        palette: (palette, sampler),
+        scissor: targets.scissor(),
+        out_color: targets.color.clone(),
+        out_depth: targets.depth.clone(),
+      };
+      CritterBundle::Instanced {
+        bundle: gfx::Bundle::new(mesh.mesh.slice, pso, pipeline_data),
+        pso_updates,
+      }
+    } else {
+      let (pso, pso_updates) = PassDescriptor::new(name,
+                                                   ShaderFile::new("character.v.glsl", CRITTER_VERT),
+                                                   critter_fragment_shader(),
+                                                   critter_fallback_pipeline::new())
+        .build(factory, reloader);
+
+      let pipeline_data = critter_fallback_pipeline::Data {
+        vbuf: mesh.mesh.vertex_buffer,
+        instance_cb: factory.create_constant_buffer(1),
+        globals: targets.globals.clone(),
+        charactersheet: (mesh.mesh.texture.raw, sampler.clone()),
+        palette: (palette, sampler),
+        scissor: targets.scissor(),
+        out_color: targets.color.clone(),
+        out_depth: targets.depth.clone(),
+      };
+      CritterBundle::Fallback {
+        bundle: gfx::Bundle::new(mesh.mesh.slice, pso, pipeline_data),
+        pso_updates,
+      }
+    };
+
+    CritterPass {
+      bundle,
+      instance: ConstantCache::default(),
+    }
+  }
+
+  pub fn draw<C>(&mut self,
+                 position: Position,
+                 sprite: &SpriteFrame,
+                 palette: CritterPalette,
+                 view: &View,
+                 encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    let mut instance = CritterInstance::new(view.screen_position(position), sprite);
+    instance.palette = palette.index();
+    self.draw_instances(&[instance], view, encoder);
+  }
+
+  // Instance positions are expected to be already offset by the view
+  pub fn draw_instances<C>(&mut self,
+                           instances: &[CritterInstance],
+                           view: &View,
+                           encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    match self.bundle {
+      CritterBundle::Instanced { ref mut bundle, ref pso_updates } => {
+        pso_updates.apply(&mut bundle.pso);
+        bundle.data.scissor = view.scissor;
+        for chunk in instances.chunks(CRITTER_INSTANCE_BUF_LENGTH) {
+          encoder.update_buffer(&bundle.data.instances, chunk, 0).expect("Critter instance update error");
+          bundle.slice.instances = Some((chunk.len() as gfx::InstanceCount, 0));
+          bundle.encode(encoder);
+        }
+      }
+      CritterBundle::Fallback { ref mut bundle, ref pso_updates } => {
+        pso_updates.apply(&mut bundle.pso);
+        bundle.data.scissor = view.scissor;
+        for instance in instances {
+          self.instance.update(&bundle.data.instance_cb, CritterLocals::from(*instance), encoder);
+          bundle.encode(encoder);
+        }
+      }
+    }
+  }
+}
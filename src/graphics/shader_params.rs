@@ -0,0 +1,109 @@
+use crossbeam_channel as channel;
+
+use crate::ecs::{self, Write};
+use crate::game::constants::{AMBIENT_LIGHT, SHADER_PARAM_STEP};
+
+pub enum ShaderParamControl {
+  Toggle,
+  SelectNext,
+  Increase,
+  Decrease,
+}
+
+#[derive(Clone, Copy)]
+enum ShaderParam {
+  AmbientRed,
+  AmbientGreen,
+  AmbientBlue,
+  FogDensity,
+}
+
+const SHADER_PARAMS: [ShaderParam; 4] = [ShaderParam::AmbientRed, ShaderParam::AmbientGreen, ShaderParam::AmbientBlue, ShaderParam::FogDensity];
+
+pub struct ShaderParams {
+  pub is_visible: bool,
+  pub ambient_light: [f32; 4],
+  pub fog_density: f32,
+  selected: usize,
+  pub lines: Vec<String>,
+}
+
+impl ShaderParams {
+  pub fn new() -> ShaderParams {
+    let mut params = ShaderParams {
+      is_visible: false,
+      ambient_light: AMBIENT_LIGHT,
+      fog_density: 0.0,
+      selected: 0,
+      lines: Vec::new(),
+    };
+    params.refresh_lines();
+    params
+  }
+
+  fn value_mut(&mut self, param: ShaderParam) -> &mut f32 {
+    match param {
+      ShaderParam::AmbientRed => &mut self.ambient_light[0],
+      ShaderParam::AmbientGreen => &mut self.ambient_light[1],
+      ShaderParam::AmbientBlue => &mut self.ambient_light[2],
+      ShaderParam::FogDensity => &mut self.fog_density,
+    }
+  }
+
+  fn adjust(&mut self, step: f32) {
+    let param = SHADER_PARAMS[self.selected];
+    let value = self.value_mut(param);
+    *value = (*value + step).max(0.0);
+  }
+
+  fn refresh_lines(&mut self) {
+    let values = [("ambient red", self.ambient_light[0]),
+      ("ambient green", self.ambient_light[1]),
+      ("ambient blue", self.ambient_light[2]),
+      ("fog density", self.fog_density)];
+    let selected = self.selected;
+    self.lines = values.iter()
+      .enumerate()
+      .map(|(idx, (name, value))| {
+        let marker = if idx == selected { ">" } else { " " };
+        format!("{} {} {:.2}", marker, name, value)
+      })
+      .collect();
+  }
+}
+
+impl Default for ShaderParams {
+  fn default() -> ShaderParams {
+    ShaderParams::new()
+  }
+}
+
+pub struct ShaderParamSystem {
+  queue: channel::Receiver<ShaderParamControl>,
+}
+
+impl ShaderParamSystem {
+  pub fn new() -> (ShaderParamSystem, channel::Sender<ShaderParamControl>) {
+    let (tx, rx) = channel::unbounded();
+    (ShaderParamSystem {
+      queue: rx,
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for ShaderParamSystem {
+  type SystemData = Write<'a, ShaderParams>;
+
+  fn run(&mut self, mut params: Self::SystemData) {
+    while let Ok(control) = self.queue.try_recv() {
+      match control {
+        ShaderParamControl::Toggle => params.is_visible = !params.is_visible,
+        _ if !params.is_visible => (),
+        ShaderParamControl::SelectNext => params.selected = (params.selected + 1) % SHADER_PARAMS.len(),
+        ShaderParamControl::Increase => params.adjust(SHADER_PARAM_STEP),
+        ShaderParamControl::Decrease => params.adjust(-SHADER_PARAM_STEP),
+      }
+      params.refresh_lines();
+    }
+  }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+
+use crate::audio::mixer::{Mixer, Sound};
+use crate::character::{CharacterDrawable, Player};
+use crate::character::controls::CharacterInputState;
+use crate::ecs::{self, Entities, Entity, Read, ReadStorage, Write, WriteStorage};
+use crate::game::constants::{FOOTSTEP_INTERVAL, FOOTSTEP_ZOMBIE_DISTANCE};
+use crate::graphics::{DeltaTime, distance};
+use crate::graphics::orientation::Stance;
+use crate::shaders::Position;
+use crate::terrain::materials::TileMaterials;
+use crate::terrain_object::TerrainObjectDrawable;
+use crate::terrain_shape::TerrainShapeDrawable;
+use crate::zombie::ZombieDrawable;
+
+// World drawables are placed relative to the player, so they shift by the player's movement since the last update
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+  pub position: Position,
+  pub rotation: f32,
+  pub scale: f32,
+  camera_anchor: Position,
+}
+
+impl Transform {
+  pub fn new(position: Position) -> Transform {
+    Transform {
+      position,
+      rotation: 0.0,
+      scale: 1.0,
+      camera_anchor: Position::origin(),
+    }
+  }
+
+  pub fn with_rotation(self, rotation: f32) -> Transform {
+    Transform { rotation, ..self }
+  }
+
+  pub fn camera_delta(&self, ci: &CharacterInputState) -> Position {
+    ci.movement - self.camera_anchor
+  }
+
+  pub fn camera_relative_position(&self, ci: &CharacterInputState) -> Position {
+    self.position + self.camera_delta(ci)
+  }
+
+  pub fn follow_camera(&mut self, ci: &CharacterInputState) {
+    self.position = self.camera_relative_position(ci);
+    self.camera_anchor = ci.movement;
+  }
+
+  // Where the transform is regardless of the camera, a player at the negated movement stands there
+  pub fn world_position(&self) -> Position {
+    self.position - self.camera_anchor
+  }
+
+  pub fn set_world_position(&mut self, world: Position, ci: &CharacterInputState) {
+    self.position = world + ci.movement;
+    self.camera_anchor = ci.movement;
+  }
+
+  pub fn translate(&mut self, step: Position) {
+    self.position = self.position + step;
+  }
+}
+
+impl ecs::Component for Transform {
+  type Storage = ecs::DenseVecStorage<Transform>;
+}
+
+// Attaches an entity to another, its Transform is resolved from the parent's each step
+#[derive(Clone, Copy, Debug)]
+pub struct Parent {
+  pub entity: Entity,
+  pub offset: Position,
+  pub rotation: f32,
+}
+
+impl Parent {
+  pub fn new(entity: Entity, offset: Position) -> Parent {
+    Parent {
+      entity,
+      offset,
+      rotation: 0.0,
+    }
+  }
+
+  // Offset is rotated with the parent, so an attachment keeps its place on a turning sprite
+  fn resolve(&self, parent: &Transform) -> (Position, f32) {
+    let (sin, cos) = parent.rotation.sin_cos();
+    let offset = Position::new((self.offset.x() * cos - self.offset.y() * sin) * parent.scale,
+                               (self.offset.x() * sin + self.offset.y() * cos) * parent.scale);
+    (parent.position + offset, parent.rotation + self.rotation)
+  }
+}
+
+impl ecs::Component for Parent {
+  type Storage = ecs::HashMapStorage<Parent>;
+}
+
+pub struct TransformPropagationSystem;
+
+impl<'a> ecs::System<'a> for TransformPropagationSystem {
+  type SystemData = (Entities<'a>,
+                     ReadStorage<'a, Parent>,
+                     WriteStorage<'a, Transform>);
+
+  fn run(&mut self, (entities, parents, mut transforms): Self::SystemData) {
+    use crate::ecs::Join;
+
+    let mut resolved = (&entities, &transforms, !&parents).join()
+      .map(|(e, _, _)| e)
+      .collect::<HashSet<_>>();
+    let mut pending = (&entities, &parents).join()
+      .map(|(e, p)| (e, *p))
+      .collect::<Vec<_>>();
+    let mut despawned = HashSet::new();
+
+    // Each pass resolves one more level of the hierarchy, whatever is left over has no reachable root
+    while !pending.is_empty() {
+      let remaining = pending.len();
+      pending.retain(|(child, parent)| {
+        if !entities.is_alive(parent.entity) || despawned.contains(&parent.entity) {
+          entities.delete(*child).expect("Attachment despawn error");
+          despawned.insert(*child);
+          return false;
+        }
+        if !resolved.contains(&parent.entity) {
+          return true;
+        }
+        if let Some(parent_transform) = transforms.get(parent.entity).cloned() {
+          let (position, rotation) = parent.resolve(&parent_transform);
+          if let Some(transform) = transforms.get_mut(*child) {
+            transform.position = position;
+            transform.rotation = rotation;
+          }
+        }
+        resolved.insert(*child);
+        false
+      });
+      if pending.len() == remaining {
+        break;
+      }
+    }
+  }
+}
+
+// Walking time since the last footstep
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StepCadence(f32);
+
+impl StepCadence {
+  // Returns true when a footstep is due, standing still restarts the cadence
+  pub fn advance(&mut self, is_moving: bool, delta: f32, interval: f32) -> bool {
+    if !is_moving {
+      self.0 = 0.0;
+      return false;
+    }
+    self.0 += delta;
+    if self.0 >= interval {
+      self.0 -= interval;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+// Static world elements only follow the camera, walking characters get their footsteps here
+pub struct MovementSystem;
+
+impl<'a> ecs::System<'a> for MovementSystem {
+  type SystemData = (ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, Player>,
+                     WriteStorage<'a, CharacterDrawable>,
+                     WriteStorage<'a, ZombieDrawable>,
+                     WriteStorage<'a, TerrainObjectDrawable>,
+                     WriteStorage<'a, TerrainShapeDrawable>,
+                     Read<'a, TileMaterials>,
+                     Read<'a, DeltaTime>,
+                     Write<'a, Mixer>);
+
+  fn run(&mut self, (character_input, players, mut character_drawable, mut zombies, mut terrain_objects, mut terrain_shapes, materials, delta, mut mixer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    let delta = delta.0 as f32;
+    for (ci, cd) in (&character_input, &mut character_drawable).join() {
+      let is_moving = ci.movement != ci.previous_movement;
+      if cd.steps.advance(is_moving, delta, FOOTSTEP_INTERVAL) {
+        mixer.play(Sound::footstep(materials.at(ci.movement)));
+      }
+    }
+
+    // Zombies, terrain objects and hill pieces are placed relative to the first player
+    let ci = match (&character_input, &players).join().min_by_key(|(_, p)| p.index) {
+      Some((ci, _)) => ci,
+      None => return,
+    };
+
+    for z in (&mut zombies).join().filter(|z| !z.is_dead()) {
+      let position = z.transform.position;
+      let interval = if z.stance == Stance::Running { FOOTSTEP_INTERVAL / 2.0 } else { FOOTSTEP_INTERVAL };
+      let is_moving = z.stance == Stance::Walking || z.stance == Stance::Running;
+      if z.steps.advance(is_moving, delta, interval) && distance(position.x(), position.y()) < FOOTSTEP_ZOMBIE_DISTANCE {
+        mixer.play_at(Sound::footstep(materials.at(ci.movement - position)), position);
+      }
+    }
+
+    for o in (&mut terrain_objects).join() {
+      o.transform.follow_camera(ci);
+    }
+
+    for ts in (&mut terrain_shapes).join() {
+      ts.transform.follow_camera(ci);
+    }
+  }
+}
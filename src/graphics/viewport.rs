@@ -0,0 +1,82 @@
+use gfx;
+use gfx::handle::RenderTargetView;
+
+use crate::character::controls::CharacterInputState;
+use crate::gfx_app::ColorFormat;
+use crate::graphics::{camera::CameraInputState, dimensions::Dimensions};
+use crate::shaders::{Position, Projection};
+
+pub struct View {
+  pub camera: CameraInputState,
+  pub projection: Projection,
+  pub offset: Position,
+  pub scroll: Position,
+  pub scissor: gfx::Rect,
+}
+
+impl View {
+  pub fn position(&self, position: Position) -> Position {
+    position + self.offset + self.scroll
+  }
+
+  // Characters are anchored to the view, so they skip the interpolated scroll
+  pub fn screen_position(&self, position: Position) -> Position {
+    position + self.offset
+  }
+
+  pub fn is_visible(&self, position: Position) -> bool {
+    self.camera.is_visible(self.position(position))
+  }
+}
+
+pub fn full_screen_rect<R: gfx::Resources>(rtv: &RenderTargetView<R, ColorFormat>) -> gfx::Rect {
+  let (w, h, _, _) = rtv.get_dimensions();
+  gfx::Rect { x: 0, y: 0, w, h }
+}
+
+// Positions are relative to the first player, so the other players' views pan by the difference in movement,
+// and the anchor is the first player's movement. The views follow the given players, the local one first.
+// Split views shift the scene by a quarter screen, which before the perspective divide equals the camera distance.
+// World positions already include the latest simulation step, so the scene is scrolled back towards the previous one
+pub fn interpolated_scroll(ci: &CharacterInputState, interpolation: f32) -> Position {
+  let step = ci.movement - ci.previous_movement;
+  Position::new(step.x() * (interpolation - 1.0), step.y() * (interpolation - 1.0))
+}
+
+pub fn build_views(dim: &Dimensions, screen: gfx::Rect, players: &[(&CameraInputState, &CharacterInputState)], anchor: Position,
+                   interpolation: f32) -> Vec<View> {
+  if players.is_empty() {
+    return vec![];
+  }
+
+  if !dim.split_screen || players.len() < 2 {
+    let (camera, ci) = players[0];
+    return vec![View {
+      camera: camera.clone(),
+      projection: dim.world_to_projection(camera),
+      offset: ci.movement - anchor,
+      scroll: interpolated_scroll(ci, interpolation),
+      scissor: screen,
+    }];
+  }
+
+  let half_width = screen.w / 2;
+  players.iter()
+    .take(2)
+    .enumerate()
+    .map(|(idx, (camera, ci))| {
+      let (screen_shift, scissor_x) = if idx == 0 {
+        (-camera.distance, screen.x)
+      } else {
+        (camera.distance, screen.x + half_width)
+      };
+      View {
+        camera: (*camera).clone(),
+        projection: dim.world_to_projection(camera),
+        offset: ci.movement - anchor + Position::new(screen_shift, 0.0),
+        scroll: interpolated_scroll(ci, interpolation),
+        scissor: gfx::Rect { x: scissor_x, y: screen.y, w: half_width, h: screen.h },
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,71 @@
+use crate::character::{CharacterDrawable, character_stats::CharacterStats, Player};
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{HUD_BAR_HEIGHT, HUD_BAR_WIDTH, HUD_ICON_SIZE, HUD_MARGIN, HUD_SPACING, MAGAZINE_SIZE, MAX_MAGAZINES};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::{UiLayer, UiQuad};
+use crate::net::{client::local_index, LocalPlayer};
+
+const WEAPON_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 0.9];
+const ROUND_COLOR: [f32; 4] = [0.9, 0.7, 0.2, 0.9];
+const EMPTY_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const RELOAD_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.9];
+
+// Loaded rounds and reserve magazines next to the pistol, the rounds turn into a progress bar while
+// reloading. The first player is in the bottom left corner and the second one in the bottom right.
+pub fn ammo_quads(player_index: usize, dimensions: &Dimensions, stats: &CharacterStats) -> Vec<UiQuad> {
+  let scale = dimensions.hidpi_factor;
+  let margin = HUD_MARGIN * scale;
+  let spacing = HUD_SPACING * scale;
+  let icon = HUD_ICON_SIZE * scale;
+  let panel_width = icon * 2.0 + spacing + HUD_BAR_WIDTH * scale;
+  let x = if player_index == 0 { margin } else { dimensions.window_width - margin - panel_width };
+  let y = dimensions.window_height - margin - icon;
+
+  let mut quads = vec![
+    // Pistol barrel and grip
+    UiQuad::new(x, y, icon * 2.0, icon * 0.4, WEAPON_COLOR),
+    UiQuad::new(x + icon * 1.4, y, icon * 0.6, icon, WEAPON_COLOR),
+  ];
+
+  let rounds_x = x + icon * 2.0 + spacing;
+  let rounds_width = HUD_BAR_WIDTH * scale;
+  let rounds_height = HUD_BAR_HEIGHT * scale;
+  match stats.reload_progress() {
+    Some(progress) => {
+      quads.push(UiQuad::new(rounds_x, y, rounds_width, rounds_height, EMPTY_COLOR));
+      quads.push(UiQuad::new(rounds_x, y, rounds_width * progress, rounds_height, RELOAD_COLOR));
+    }
+    None => {
+      let slot = rounds_width / MAGAZINE_SIZE as f32;
+      for round in 0..MAGAZINE_SIZE {
+        let color = if round < stats.ammunition { ROUND_COLOR } else { EMPTY_COLOR };
+        quads.push(UiQuad::new(rounds_x + round as f32 * slot, y, slot * 0.7, rounds_height, color));
+      }
+    }
+  }
+
+  let magazine_y = y + rounds_height + spacing * 0.5;
+  for magazine in 0..MAX_MAGAZINES {
+    let color = if magazine < stats.magazines { WEAPON_COLOR } else { EMPTY_COLOR };
+    quads.push(UiQuad::new(rounds_x + magazine as f32 * icon, magazine_y, icon * 0.8, icon - rounds_height - spacing * 0.5, color));
+  }
+  quads
+}
+
+pub struct AmmoPanelSystem;
+
+impl<'a> ecs::System<'a> for AmmoPanelSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterDrawable>,
+                     Read<'a, Dimensions>,
+                     Read<'a, LocalPlayer>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, character, dim, local, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    for (p, c) in (&players, &character).join() {
+      layer.quads.extend(ammo_quads(local_index(p.index, local.0), &dim, &c.stats));
+    }
+  }
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::{BANNER_HOLD, BANNER_SLIDE, HUD_TEXT};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::localization::Localization;
+use crate::game::spawner::is_boss_wave;
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const BANNER_TEXT: f32 = 48.0;
+const BANNER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BOSS_COLOR: [f32; 4] = [1.0, 0.3, 0.2, 1.0];
+const BAND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+// Horizontal offset in window widths, the banner eases in from the left, holds at the center and
+// eases out to the right. None once it has left.
+pub fn banner_offset(elapsed: f32) -> Option<f32> {
+  if elapsed < BANNER_SLIDE {
+    Some(-(1.0 - elapsed / BANNER_SLIDE).powi(3))
+  } else if elapsed < BANNER_SLIDE + BANNER_HOLD {
+    Some(0.0)
+  } else if elapsed < BANNER_SLIDE * 2.0 + BANNER_HOLD {
+    Some(((elapsed - BANNER_SLIDE - BANNER_HOLD) / BANNER_SLIDE).powi(3))
+  } else {
+    None
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Banner {
+  pub title: String,
+  pub subtitle: Option<String>,
+  pub color: [f32; 4],
+  elapsed: f32,
+}
+
+impl Banner {
+  pub fn new(title: String, subtitle: Option<String>, color: [f32; 4]) -> Banner {
+    Banner {
+      title,
+      subtitle,
+      color,
+      elapsed: 0.0,
+    }
+  }
+
+  pub fn for_wave(wave: usize, strings: &Localization) -> Banner {
+    let title = strings.format("banner.wave", &[("wave", wave.to_string())]);
+    if is_boss_wave(wave) {
+      Banner::new(title, Some(strings.get("banner.boss").to_string()), BOSS_COLOR)
+    } else {
+      Banner::new(title, None, BANNER_COLOR)
+    }
+  }
+}
+
+// Banners are shown one at a time in the order they were pushed
+#[derive(Default)]
+pub struct Banners {
+  queue: VecDeque<Banner>,
+}
+
+impl Banners {
+  pub fn push(&mut self, banner: Banner) {
+    self.queue.push_back(banner);
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    if let Some(banner) = self.queue.front_mut() {
+      banner.elapsed += delta;
+      if banner_offset(banner.elapsed).is_none() {
+        self.queue.pop_front();
+      }
+    }
+  }
+
+  pub fn current(&self) -> Option<&Banner> {
+    self.queue.front()
+  }
+
+  // A band across the middle of the window with the text sliding over it, both fade with the offset
+  pub fn draw(&self, dimensions: &Dimensions, layer: &mut UiLayer) {
+    let (banner, offset) = match self.current().and_then(|b| banner_offset(b.elapsed).map(|offset| (b, offset))) {
+      Some(current) => current,
+      None => return,
+    };
+    let scale = dimensions.hidpi_factor;
+    let (title_size, subtitle_size) = (BANNER_TEXT * scale, HUD_TEXT * scale);
+    let height = title_size + subtitle_size * 2.0;
+    let top = (dimensions.window_height - height) / 2.0;
+    let fade = 1.0 - offset.abs();
+    let [r, g, b, a] = BAND_COLOR;
+    layer.quads.push(UiQuad::new(0.0, top, dimensions.window_width, height, [r, g, b, a * fade]));
+
+    let x = dimensions.window_width * (0.5 + offset);
+    let [r, g, b, a] = banner.color;
+    let color = [r, g, b, a * fade];
+    let title_y = if banner.subtitle.is_some() { top + subtitle_size * 0.5 } else { top + subtitle_size };
+    layer.texts.push(UiText {
+      align: TextAlign::Center,
+      ..UiText::new(&banner.title, x, title_y, title_size, color)
+    });
+    if let Some(ref subtitle) = banner.subtitle {
+      layer.texts.push(UiText {
+        align: TextAlign::Center,
+        ..UiText::new(subtitle, x, title_y + title_size, subtitle_size, color)
+      });
+    }
+  }
+}
+
+pub struct BannerSystem {
+  reader: ReaderId<GameEvent>,
+  last_frame: Instant,
+  banners: Banners,
+}
+
+impl BannerSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> BannerSystem {
+    BannerSystem {
+      reader,
+      last_frame: Instant::now(),
+      banners: Banners::default(),
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for BannerSystem {
+  type SystemData = (Read<'a, GameEvents>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (events, strings, states, dim, mut layer): Self::SystemData) {
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+
+    for event in events.read(&mut self.reader) {
+      if let GameEvent::WaveStarted(wave) = *event {
+        self.banners.push(Banner::for_wave(wave, &strings));
+      }
+    }
+    // A banner waits out the pause menu where it is
+    if states.current().is_simulating() {
+      self.banners.tick(delta);
+      self.banners.draw(&dim, &mut layer);
+    }
+  }
+}
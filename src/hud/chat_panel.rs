@@ -0,0 +1,51 @@
+use crate::ecs::{self, Read, Write};
+use crate::game::chat::{Chat, ChatLine};
+use crate::game::constants::{HUD_ICON_SIZE, HUD_MARGIN, HUD_SMALL_TEXT};
+use crate::game::localization::Localization;
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::UiText;
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const CHAT_WIDTH: f32 = 360.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const SAID_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const NOTICE_COLOR: [f32; 4] = [0.9, 0.8, 0.4, 0.9];
+const INPUT_COLOR: [f32; 4] = [0.6, 0.9, 1.0, 1.0];
+
+// Newest line at the bottom left corner above the first player's ammo, the input line under it
+// while the chat is open
+pub fn chat_texts(chat: &Chat, strings: &Localization, dimensions: &Dimensions, layer: &mut UiLayer) {
+  let lines = chat.lines()
+    .map(|line| (line.text(strings), if let ChatLine::Said(..) = line { SAID_COLOR } else { NOTICE_COLOR }))
+    .chain(if chat.is_open { Some((format!("> {}_", chat.input()), INPUT_COLOR)) } else { None })
+    .collect::<Vec<_>>();
+  if lines.is_empty() {
+    return;
+  }
+  let scale = dimensions.hidpi_factor;
+  let size = HUD_SMALL_TEXT * scale;
+  let margin = HUD_MARGIN * scale;
+  let height = size * lines.len() as f32;
+  let top = dimensions.window_height - margin * 2.0 - HUD_ICON_SIZE * scale - height;
+  layer.quads.push(UiQuad::new(margin, top, CHAT_WIDTH * scale, height, BACKGROUND_COLOR));
+  for (idx, (text, color)) in lines.iter().enumerate() {
+    layer.texts.push(UiText::new(text, margin + margin / 2.0, top + idx as f32 * size, size, *color));
+  }
+}
+
+pub struct ChatPanelSystem;
+
+impl<'a> ecs::System<'a> for ChatPanelSystem {
+  type SystemData = (Read<'a, Chat>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (chat, strings, states, dim, mut layer): Self::SystemData) {
+    if states.current().is_simulating() {
+      chat_texts(&chat, &strings, &dim, &mut layer);
+    }
+  }
+}
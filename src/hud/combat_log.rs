@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::{COMBAT_LOG_DURATION, COMBAT_LOG_LINES, HUD_MARGIN, HUD_SMALL_TEXT};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::localization::Localization;
+use crate::game::settings::Settings;
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const LOG_WIDTH: f32 = 280.0;
+// Seconds of contact damage that still add up into the last line
+const DAMAGE_MERGE: f32 = 0.5;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const HIT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.9];
+const DAMAGE_COLOR: [f32; 4] = [1.0, 0.45, 0.35, 0.9];
+const PICKUP_COLOR: [f32; 4] = [0.5, 0.9, 0.5, 0.9];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CombatEntry {
+  Hit(f32),
+  Kill,
+  DamageTaken(f32),
+  Pickup,
+  Wave(usize),
+  Died,
+}
+
+impl CombatEntry {
+  pub fn from_event(event: GameEvent) -> Option<CombatEntry> {
+    match event {
+      GameEvent::ZombieHit(_, damage) => Some(CombatEntry::Hit(damage)),
+      GameEvent::ZombieKilled(..) => Some(CombatEntry::Kill),
+      GameEvent::PlayerDamaged(_, _, damage) => Some(CombatEntry::DamageTaken(damage)),
+      GameEvent::PickupCollected(_) => Some(CombatEntry::Pickup),
+      GameEvent::WaveStarted(wave) => Some(CombatEntry::Wave(wave)),
+      GameEvent::PlayerKilled(_) => Some(CombatEntry::Died),
+      GameEvent::BulletFired(_) | GameEvent::BulletHit(_) | GameEvent::Toast(_) => None,
+    }
+  }
+
+  // Lines are formatted when drawn, so they follow a language change
+  pub fn text(self, strings: &Localization) -> String {
+    match self {
+      CombatEntry::Hit(damage) => strings.format("combat.hit", &[("damage", format!("{:.1}", damage))]),
+      CombatEntry::Kill => strings.get("combat.kill").to_string(),
+      CombatEntry::DamageTaken(damage) => strings.format("combat.damage", &[("damage", format!("{:.0}", damage.ceil()))]),
+      CombatEntry::Pickup => strings.get("combat.pickup").to_string(),
+      CombatEntry::Wave(wave) => strings.format("combat.wave", &[("wave", wave.to_string())]),
+      CombatEntry::Died => strings.get("combat.died").to_string(),
+    }
+  }
+
+  fn color(self) -> [f32; 4] {
+    match self {
+      CombatEntry::DamageTaken(_) | CombatEntry::Died => DAMAGE_COLOR,
+      CombatEntry::Pickup | CombatEntry::Wave(_) => PICKUP_COLOR,
+      CombatEntry::Hit(_) | CombatEntry::Kill => HIT_COLOR,
+    }
+  }
+}
+
+// The latest entries with their age in seconds, the oldest scroll off the top
+#[derive(Default)]
+pub struct CombatLog {
+  entries: VecDeque<(CombatEntry, f32)>,
+}
+
+impl CombatLog {
+  // Contact damage arrives every simulation step, it adds up in one line while it keeps coming
+  pub fn push(&mut self, entry: CombatEntry) {
+    if let (CombatEntry::DamageTaken(damage), Some((CombatEntry::DamageTaken(total), age))) = (entry, self.entries.back_mut()) {
+      if *age < DAMAGE_MERGE {
+        *total += damage;
+        *age = 0.0;
+        return;
+      }
+    }
+    if self.entries.len() == COMBAT_LOG_LINES {
+      self.entries.pop_front();
+    }
+    self.entries.push_back((entry, 0.0));
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    for (_, age) in &mut self.entries {
+      *age += delta;
+    }
+    self.entries.retain(|&(_, age)| age < COMBAT_LOG_DURATION);
+  }
+
+  pub fn entries(&self) -> impl Iterator<Item=CombatEntry> + '_ {
+    self.entries.iter().map(|&(entry, _)| entry)
+  }
+
+  // Newest line at the bottom right corner, above the version label's row
+  pub fn draw(&self, strings: &Localization, dimensions: &Dimensions, layer: &mut UiLayer) {
+    if self.entries.is_empty() {
+      return;
+    }
+    let scale = dimensions.hidpi_factor;
+    let size = HUD_SMALL_TEXT * scale;
+    let width = LOG_WIDTH * scale;
+    let margin = HUD_MARGIN * scale;
+    let height = size * self.entries.len() as f32;
+    let (x, top) = (dimensions.window_width - margin - width, dimensions.window_height - margin * 2.0 - size - height);
+    layer.quads.push(UiQuad::new(x, top, width, height, BACKGROUND_COLOR));
+    for (idx, &(entry, _)) in self.entries.iter().enumerate() {
+      layer.texts.push(UiText {
+        align: TextAlign::Right,
+        ..UiText::new(&entry.text(strings), x + width - margin / 2.0, top + idx as f32 * size, size, entry.color())
+      });
+    }
+  }
+}
+
+// Follows the game events and keeps logging while hidden, so turning it on shows what just happened
+pub struct CombatLogSystem {
+  reader: ReaderId<GameEvent>,
+  last_frame: Instant,
+  log: CombatLog,
+}
+
+impl CombatLogSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> CombatLogSystem {
+    CombatLogSystem {
+      reader,
+      last_frame: Instant::now(),
+      log: CombatLog::default(),
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for CombatLogSystem {
+  type SystemData = (Read<'a, GameEvents>,
+                     Read<'a, Settings>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (events, settings, strings, states, dim, mut layer): Self::SystemData) {
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+
+    for &event in events.read(&mut self.reader) {
+      if let Some(entry) = CombatEntry::from_event(event) {
+        self.log.push(entry);
+      }
+    }
+    if states.current().is_simulating() {
+      self.log.tick(delta);
+      if settings.combat_log {
+        self.log.draw(&strings, &dim, &mut layer);
+      }
+    }
+  }
+}
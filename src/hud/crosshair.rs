@@ -0,0 +1,49 @@
+use crate::character::{CharacterDrawable, Player};
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{CROSSHAIR_GAP, CROSSHAIR_SPREAD_GAP};
+use crate::game::state::GameStates;
+use crate::gfx_app::mouse_controls::MouseState;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const CROSSHAIR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const TARGET_COLOR: [f32; 4] = [0.95, 0.15, 0.1, 0.9];
+const ARM_LENGTH: f32 = 8.0;
+const ARM_WIDTH: f32 = 2.0;
+
+// Four arms around the aim point, pushed apart by the weapon spread
+pub fn crosshair_quads(x: f32, y: f32, spread: f32, is_on_target: bool, scale: f32) -> Vec<UiQuad> {
+  let gap = (CROSSHAIR_GAP + spread * CROSSHAIR_SPREAD_GAP) * scale;
+  let (length, width) = (ARM_LENGTH * scale, ARM_WIDTH * scale);
+  let color = if is_on_target { TARGET_COLOR } else { CROSSHAIR_COLOR };
+  vec![
+    UiQuad::new(x - gap - length, y - width / 2.0, length, width, color),
+    UiQuad::new(x + gap, y - width / 2.0, length, width, color),
+    UiQuad::new(x - width / 2.0, y - gap - length, width, length, color),
+    UiQuad::new(x - width / 2.0, y + gap, width, length, color),
+  ]
+}
+
+// Follows the mouse of the first player while playing
+pub struct CrosshairSystem;
+
+impl<'a> ecs::System<'a> for CrosshairSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterDrawable>,
+                     Read<'a, MouseState>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, character, mouse, states, dim, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    if !states.current().is_simulating() {
+      return;
+    }
+    if let Some((cd, _)) = (&character, &players).join().find(|(_, p)| p.index == 0) {
+      let scale = dim.hidpi_factor;
+      layer.quads.extend(crosshair_quads(mouse.cursor.x * scale, mouse.cursor.y * scale, cd.stats.spread, mouse.is_on_target, scale));
+    }
+  }
+}
@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::DAMAGE_INDICATOR_FADE;
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::{UiLayer, UiQuad};
+use crate::net::LocalPlayer;
+
+const INDICATOR_COLOR: [f32; 3] = [0.9, 0.05, 0.05];
+const INDICATOR_SIZE: f32 = 14.0;
+// The arc is drawn as squares this many radians apart, hits inside its half width refresh it
+const ARC_STEP: f32 = 0.05;
+const ARC_SEGMENTS: isize = 4;
+
+// Squares along the window edge where a ray from the center at the angle leaves the window,
+// fading towards both ends of the arc. Angles are in world space, counterclockwise from the right.
+pub fn indicator_quads(angle: f32, alpha: f32, dimensions: &Dimensions) -> Vec<UiQuad> {
+  let size = INDICATOR_SIZE * dimensions.hidpi_factor;
+  let (half_width, half_height) = (dimensions.window_width / 2.0, dimensions.window_height / 2.0);
+  (-ARC_SEGMENTS..=ARC_SEGMENTS)
+    .map(|segment| {
+      let a = angle + segment as f32 * ARC_STEP;
+      // Screen y grows downwards
+      let (dx, dy) = (a.cos(), -a.sin());
+      let t = (half_width / dx.abs()).min(half_height / dy.abs());
+      let x = (half_width + dx * t - size / 2.0).clamp(0.0, dimensions.window_width - size);
+      let y = (half_height + dy * t - size / 2.0).clamp(0.0, dimensions.window_height - size);
+      let falloff = 1.0 - segment.abs() as f32 / (ARC_SEGMENTS + 1) as f32;
+      let [r, g, b] = INDICATOR_COLOR;
+      UiQuad::new(x, y, size, size, [r, g, b, alpha * falloff])
+    })
+    .collect()
+}
+
+fn angle_difference(a: f32, b: f32) -> f32 {
+  let difference = (a - b).rem_euclid(2.0 * PI);
+  difference.min(2.0 * PI - difference)
+}
+
+// Hits towards the attacker, each with the seconds it has left
+#[derive(Default)]
+pub struct DamageIndicators {
+  hits: Vec<(f32, f32)>,
+}
+
+impl DamageIndicators {
+  // Contact damage arrives every step, a hit from the same direction restarts the arc
+  pub fn hit(&mut self, angle: f32) {
+    let half_arc = ARC_STEP * ARC_SEGMENTS as f32;
+    match self.hits.iter_mut().find(|(a, _)| angle_difference(*a, angle) < half_arc) {
+      Some(hit) => *hit = (angle, DAMAGE_INDICATOR_FADE),
+      None => self.hits.push((angle, DAMAGE_INDICATOR_FADE)),
+    }
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    for hit in &mut self.hits {
+      hit.1 -= delta;
+    }
+    self.hits.retain(|&(_, remaining)| remaining > 0.0);
+  }
+
+  pub fn quads(&self, dimensions: &Dimensions) -> Vec<UiQuad> {
+    self.hits.iter()
+      .flat_map(|&(angle, remaining)| indicator_quads(angle, remaining / DAMAGE_INDICATOR_FADE, dimensions))
+      .collect()
+  }
+}
+
+pub struct DamageIndicatorSystem {
+  reader: ReaderId<GameEvent>,
+  last_frame: Instant,
+  indicators: DamageIndicators,
+}
+
+impl DamageIndicatorSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> DamageIndicatorSystem {
+    DamageIndicatorSystem {
+      reader,
+      last_frame: Instant::now(),
+      indicators: DamageIndicators::default(),
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for DamageIndicatorSystem {
+  type SystemData = (Read<'a, GameEvents>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Read<'a, LocalPlayer>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (events, states, dim, local, mut layer): Self::SystemData) {
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+
+    // Indicators hold still with the rest of the frame while the simulation is suspended
+    if states.current().is_simulating() {
+      self.indicators.tick(delta);
+    }
+    for event in events.read(&mut self.reader) {
+      match *event {
+        GameEvent::PlayerDamaged(player, attacker, _) if player == local.0 => self.indicators.hit(attacker.y().atan2(attacker.x())),
+        _ => (),
+      }
+    }
+    layer.quads.extend(self.indicators.quads(&dim));
+  }
+}
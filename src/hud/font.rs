@@ -1,5 +1,13 @@
 use cgmath::Point2;
-use rusttype::{Font, point, Scale};
+use rusttype::{Font, FontCollection, point, Scale};
+
+// All text is drawn with the one bundled font
+pub fn load_font() -> Font<'static> {
+  let font_bytes = &include_bytes!("../../assets/DejaVuSans.ttf")[..];
+  FontCollection::from_bytes(font_bytes)
+    .unwrap_or_else(|e| panic!("Font loading error: {}", e))
+    .into_font().unwrap_or_else(|e| panic!("into_font error: {}", e))
+}
 
 pub fn draw_text(font: &Font, font_size: f32, text: &str) -> (Point2<i32>, Vec<u8>) {
   let scale = Scale {
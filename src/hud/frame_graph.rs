@@ -0,0 +1,72 @@
+use crate::ecs::{self, Read, Write};
+use crate::game::constants::{FRAME_GRAPH_MAX_MS, FRAME_GRAPH_SAMPLES, HUD_MARGIN, HUD_SMALL_TEXT, HUD_SPACING};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::UiText;
+use crate::hud::ui::{UiLayer, UiQuad};
+use crate::profiler::FrameProfile;
+
+const GRAPH_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.9];
+const TARGET_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.3];
+// Frames at 60 fps
+const TARGET_MS: f64 = 1000.0 / 60.0;
+
+fn bar_color(ms: f64) -> [f32; 4] {
+  if ms <= TARGET_MS {
+    [0.3, 0.9, 0.3, 0.9]
+  } else if ms <= TARGET_MS * 2.0 {
+    [0.95, 0.8, 0.2, 0.9]
+  } else {
+    [0.95, 0.2, 0.1, 0.9]
+  }
+}
+
+// A bar per recent frame in the bottom left corner, the newest on the right, with the 60 fps line
+// across it and the frame rate and counts above it
+pub fn frame_graph(profile: &FrameProfile, dimensions: &Dimensions, layer: &mut UiLayer) {
+  let scale = dimensions.hidpi_factor;
+  let (width, height) = (GRAPH_WIDTH * scale, GRAPH_HEIGHT * scale);
+  let x = HUD_MARGIN * scale;
+  let bottom = dimensions.window_height - HUD_MARGIN * scale;
+  let text_size = HUD_SMALL_TEXT * scale;
+  let top = bottom - height;
+
+  layer.quads.push(UiQuad::new(x, top, width, height, BACKGROUND_COLOR));
+  let bar_width = width / FRAME_GRAPH_SAMPLES as f32;
+  let first = FRAME_GRAPH_SAMPLES.saturating_sub(profile.frame_history.len());
+  for (idx, &ms) in profile.frame_history.iter().enumerate() {
+    let bar_height = (ms / FRAME_GRAPH_MAX_MS).min(1.0) as f32 * height;
+    let bar_x = x + (first + idx) as f32 * bar_width;
+    layer.quads.push(UiQuad::new(bar_x, bottom - bar_height, bar_width, bar_height, bar_color(ms)));
+  }
+  let target_y = bottom - (TARGET_MS / FRAME_GRAPH_MAX_MS) as f32 * height;
+  layer.quads.push(UiQuad::new(x, target_y, width, scale, TARGET_COLOR));
+
+  let frame_ms = profile.frame_ms();
+  let counts = profile.counts;
+  let lines = [
+    format!("{:.0} fps {:.2} ms", 1000.0 / frame_ms.max(0.001), frame_ms),
+    format!("entities {} zombies {} bullets {}", counts.entities, counts.zombies, counts.bullets),
+    format!("draw calls {}", counts.draw_calls),
+  ];
+  let text_top = top - HUD_SPACING * scale - text_size * lines.len() as f32;
+  for (idx, line) in lines.iter().enumerate() {
+    layer.texts.push(UiText::new(line, x, text_top + idx as f32 * text_size, text_size, TEXT_COLOR));
+  }
+}
+
+pub struct FrameGraphSystem;
+
+impl<'a> ecs::System<'a> for FrameGraphSystem {
+  type SystemData = (Read<'a, FrameProfile>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (profile, dim, mut layer): Self::SystemData) {
+    if profile.is_stats_visible {
+      frame_graph(&profile, &dim, &mut layer);
+    }
+  }
+}
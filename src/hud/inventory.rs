@@ -0,0 +1,94 @@
+use crate::character::{CharacterDrawable, Player};
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{HUD_TEXT, MAGAZINE_SIZE, MAX_MAGAZINES};
+use crate::game::health::Health;
+use crate::game::inventory::{InventoryScreen, Loadout};
+use crate::game::localization::Localization;
+use crate::game::score::Score;
+use crate::game::state::{GameState, GameStates};
+use crate::game::status::Stamina;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::TextAlign;
+use crate::hud::ui::UiLayer;
+use crate::hud::widgets::{Anchor, Panel, Stacking, Widget};
+
+const COLUMN_WIDTH: f32 = 240.0;
+const COLUMN_MARGIN: f32 = 60.0;
+const HEADER_COLOR: [f32; 4] = [1.0, 0.85, 0.5, 1.0];
+
+fn column(anchor: Anchor, align: TextAlign, lines: Vec<String>) -> Panel {
+  Panel {
+    offset: (COLUMN_MARGIN, COLUMN_MARGIN),
+    spacing: 0.0,
+    align,
+    widgets: lines.into_iter().map(Widget::Label).collect(),
+    ..Panel::new(anchor, Stacking::Vertical, COLUMN_WIDTH, HUD_TEXT * 1.5)
+  }
+}
+
+// Carried items on the left, the weapon slots in the middle and the first player's stats on the right
+pub struct InventoryPanelSystem;
+
+impl<'a> ecs::System<'a> for InventoryPanelSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterDrawable>,
+                     ReadStorage<'a, Health>,
+                     ReadStorage<'a, Stamina>,
+                     Read<'a, InventoryScreen>,
+                     Read<'a, Loadout>,
+                     Read<'a, Score>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, character, health, stamina, screen, loadout, score, strings, states, dim, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    if states.current() != GameState::Inventory {
+      return;
+    }
+    let first = (&players, &character, &health, &stamina).join().find(|(p, ..)| p.index == 0);
+    let (c, health, stamina) = match first {
+      Some((_, c, health, stamina)) => (c, health, stamina),
+      None => return,
+    };
+
+    Panel {
+      offset: (0.0, COLUMN_MARGIN),
+      text_size: HUD_TEXT * 1.5,
+      align: TextAlign::Center,
+      text_color: HEADER_COLOR,
+      widgets: vec![Widget::Label(strings.get("inventory.title").to_string())],
+      ..Panel::new(Anchor::Top, Stacking::Vertical, COLUMN_WIDTH, HUD_TEXT * 1.5)
+    }.draw(&dim, &mut layer);
+
+    let items = Some(strings.get("inventory.items").to_string()).into_iter()
+      .chain(loadout.slots.iter().flatten().map(|weapon| strings.get(weapon.name_key()).to_string()))
+      .chain(vec![
+        strings.format("inventory.rounds", &[("rounds", c.stats.ammunition.to_string()), ("size", MAGAZINE_SIZE.to_string())]),
+        strings.format("inventory.magazines", &[("magazines", c.stats.magazines.to_string()), ("max", MAX_MAGAZINES.to_string())]),
+      ])
+      .collect();
+    column(Anchor::Left, TextAlign::Left, items).draw(&dim, &mut layer);
+
+    let stats = vec![
+      strings.get("inventory.stats").to_string(),
+      strings.format("inventory.health", &[("health", health.current.ceil().to_string()), ("max", health.max.to_string())]),
+      strings.format("inventory.stamina", &[("stamina", stamina.current.ceil().to_string()), ("max", stamina.max.to_string())]),
+      strings.format("hud.kills", &[("kills", score.kills.to_string())]),
+      strings.format("hud.score", &[("score", score.score.to_string())]),
+      strings.format("hud.wave", &[("wave", score.wave.to_string())]),
+    ];
+    column(Anchor::Right, TextAlign::Right, stats).draw(&dim, &mut layer);
+
+    screen.slots.draw(&dim, &mut layer);
+    let hint = if screen.held().is_some() { "inventory.hint_held" } else { "inventory.hint" };
+    Panel {
+      offset: (0.0, COLUMN_MARGIN),
+      align: TextAlign::Center,
+      widgets: vec![Widget::Label(strings.get(hint).to_string())],
+      ..Panel::new(Anchor::Bottom, Stacking::Vertical, COLUMN_WIDTH, HUD_TEXT)
+    }.draw(&dim, &mut layer);
+  }
+}
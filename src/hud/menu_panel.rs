@@ -0,0 +1,19 @@
+use crate::ecs::{self, Read, Write};
+use crate::game::state::Menu;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::UiLayer;
+
+// The open menu over everything else, including the pause dimming
+pub struct MenuPanelSystem;
+
+impl<'a> ecs::System<'a> for MenuPanelSystem {
+  type SystemData = (Read<'a, Menu>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (menu, dim, mut layer): Self::SystemData) {
+    if menu.is_visible {
+      menu.panel.draw(&dim, &mut layer);
+    }
+  }
+}
@@ -1,120 +1,47 @@
-use std::collections::HashMap;
-
-use cgmath::Point2;
-use gfx;
-use rusttype::FontCollection;
-use specs;
-use specs::{ReadStorage, WriteStorage};
-
-use crate::character::CharacterDrawable;
-use crate::gfx_app::ColorFormat;
-use crate::gfx_app::DepthFormat;
-use crate::graphics::{mesh::RectangularTexturedMesh};
-use crate::graphics::texture::{text_texture, Texture};
-use crate::shaders::{Position, text_pipeline};
-use crate::graphics::mesh::Geometry;
-
+use crate::ecs::{self, Read, Write};
+use crate::game::constants::{GAME_VERSION, HUD_BAR_WIDTH, HUD_SMALL_TEXT};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::TextAlign;
+use crate::hud::ui::UiLayer;
+use crate::hud::widgets::{Anchor, Panel, Stacking, Widget};
+
+pub mod ammo_panel;
+pub mod banner;
+pub mod chat_panel;
+pub mod combat_log;
+pub mod crosshair;
+pub mod damage_indicator;
 pub mod font;
-pub mod hud_objects;
-
-const SHADER_VERT: &[u8] = include_bytes!("../shaders/text.v.glsl");
-const SHADER_FRAG: &[u8] = include_bytes!("../shaders/text.f.glsl");
-
-pub struct TextDrawable {
-  text: String,
-  position: Position,
-}
-
-impl<'a> TextDrawable {
-  pub fn new(text: &str, position: Position) -> TextDrawable {
-    TextDrawable {
-      text: text.to_string(),
-      position,
-    }
-  }
-
-  pub fn update(&mut self, new_text: String) {
-    self.text = new_text;
-  }
-}
-
-impl specs::prelude::Component for TextDrawable {
-  type Storage = specs::storage::HashMapStorage<TextDrawable>;
-}
-
-pub struct TextDrawSystem<R: gfx::Resources> {
-  bundle: gfx::pso::bundle::Bundle<R, text_pipeline::Data<R>>,
-  texture_cache: HashMap<String, Texture<R>>,
-  pub current_text: String,
-}
-
-impl<R: gfx::Resources> TextDrawSystem<R> {
-  pub fn new<F>(factory: &mut F,
-                texts: &[&str],
-                current_text: &str,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>) -> TextDrawSystem<R>
-    where F: gfx::Factory<R> {
-    use gfx::traits::FactoryExt;
-
-    let font_bytes = &include_bytes!("../../assets/DejaVuSans.ttf")[..];
-    let font = FontCollection::from_bytes(font_bytes as &[u8])
-      .unwrap_or_else(|e| panic!("Font loading error: {}", e))
-      .into_font().unwrap_or_else(|e| panic!("into_font error: {}", e));
-
-    let mut texture_cache: HashMap<String, Texture<R>> = HashMap::new();
-
-    text_texture(factory, &font, texts, &mut texture_cache);
-
-    let pso = factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, text_pipeline::new())
-      .expect("HUD shader loading error");
-
-    let texture = texture_cache[current_text].clone();
-
-    let rect_mesh = RectangularTexturedMesh::new(factory, texture, Geometry::Rectangle, Point2::new(1.0, 1.0), None, None, None);
-
-    let pipeline_data = text_pipeline::Data {
-      vbuf: rect_mesh.mesh.vertex_buffer,
-      position_cb: factory.create_constant_buffer(1),
-      text_sheet: (rect_mesh.mesh.texture.raw, factory.create_sampler_linear()),
-      out_color: rtv,
-      out_depth: dsv,
-    };
-
-    TextDrawSystem {
-      bundle: gfx::Bundle::new(rect_mesh.mesh.slice, pso, pipeline_data),
-      texture_cache,
-      current_text: current_text.to_string(),
-    }
-  }
-
-  pub fn draw<C>(&mut self,
-                 drawable: &TextDrawable,
-                 encoder: &mut gfx::Encoder<R, C>)
-    where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    if self.current_text.trim() != drawable.text.trim() {
-      self.current_text = drawable.text.to_owned();
-      self.bundle.data.text_sheet.0 = self.texture_cache[&drawable.text].raw.clone();
-    }
-    self.bundle.encode(encoder);
-  }
-}
-
-pub struct PreDrawSystem;
-
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
-  type SystemData = (ReadStorage<'a, CharacterDrawable>,
-                     WriteStorage<'a, hud_objects::HudObjects>);
-
-  fn run(&mut self, (character_drawable, mut hud_objects): Self::SystemData) {
-    use specs::join::Join;
-
-    for (cd, huds) in (&character_drawable, &mut hud_objects).join() {
-      let new_ammo_text = format!("Ammo {}", cd.stats.ammunition);
-      let new_mag_text = format!("Magazines {}/2", cd.stats.magazines);
-      huds.objects[1].update(new_ammo_text);
-      huds.objects[2].update(new_mag_text);
-    }
+pub mod frame_graph;
+pub mod inventory;
+pub mod menu_panel;
+pub mod nameplates;
+pub mod objective_arrow;
+pub mod pause_overlay;
+pub mod score_panel;
+pub mod status_bars;
+pub mod text;
+pub mod toasts;
+pub mod tooltips;
+pub mod ui;
+pub mod widgets;
+
+const VERSION_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+
+// The game version at the bottom center, clear of the player panels in the corners
+pub struct VersionLabelSystem;
+
+impl<'a> ecs::System<'a> for VersionLabelSystem {
+  type SystemData = (Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (dim, mut layer): Self::SystemData) {
+    Panel {
+      text_size: HUD_SMALL_TEXT,
+      align: TextAlign::Center,
+      text_color: VERSION_COLOR,
+      widgets: vec![Widget::Label(GAME_VERSION.to_string())],
+      ..Panel::new(Anchor::Bottom, Stacking::Vertical, HUD_BAR_WIDTH, HUD_SMALL_TEXT)
+    }.draw(&dim, &mut layer);
   }
 }
@@ -0,0 +1,82 @@
+use crate::character::controls::CharacterInputState;
+use crate::character::Player;
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::HUD_SMALL_TEXT;
+use crate::game::localization::Localization;
+use crate::game::state::GameStates;
+use crate::graphics::{camera::CameraInputState, dimensions::Dimensions, Interpolation};
+use crate::graphics::viewport::interpolated_scroll;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::tooltips::anchor_point;
+use crate::hud::ui::{UiLayer, UiQuad};
+use crate::shaders::Position;
+use crate::zombie::ZombieDrawable;
+
+// Logical pixels, the nameplate keeps its size at any camera distance
+const NAMEPLATE_WIDTH: f32 = 80.0;
+const NAMEPLATE_BAR_HEIGHT: f32 = 5.0;
+// Between the zombie's feet and the bottom of the health bar, clear of the sprite
+const NAMEPLATE_LIFT: f32 = 40.0;
+const NAME_COLOR: [f32; 4] = [1.0, 0.85, 0.6, 0.95];
+const BAR_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const BAR_COLOR: [f32; 4] = [0.85, 0.15, 0.1, 0.9];
+
+// A name and a health bar above each living zombie with a nameplate that has noticed a player,
+// in the first player's view. They go into the UI layer, so the scene never covers them.
+pub fn nameplates<'z, I>(zombies: I,
+                         scroll: Position,
+                         camera: &CameraInputState,
+                         dimensions: &Dimensions,
+                         strings: &Localization,
+                         layer: &mut UiLayer)
+  where I: Iterator<Item=&'z ZombieDrawable> {
+  let scale = dimensions.hidpi_factor;
+  let (width, bar_height) = (NAMEPLATE_WIDTH * scale, NAMEPLATE_BAR_HEIGHT * scale);
+  let size = HUD_SMALL_TEXT * scale;
+  let lift = NAMEPLATE_LIFT * scale;
+  for zombie in zombies.filter(|z| z.is_aggroed && !z.is_dead()) {
+    let name = match zombie.nameplate {
+      Some(ref key) => strings.get(key),
+      None => continue,
+    };
+    let point = match anchor_point(zombie.transform.position, scroll, camera, dimensions, lift + size) {
+      Some(point) => point,
+      None => continue,
+    };
+    let (x, bar_y) = (point.x - width / 2.0, point.y - lift);
+    let fraction = (zombie.health.current / zombie.health.max).clamp(0.0, 1.0);
+    layer.quads.push(UiQuad::new(x, bar_y, width, bar_height, BAR_BACKGROUND));
+    layer.quads.push(UiQuad::new(x, bar_y, width * fraction, bar_height, BAR_COLOR));
+    layer.texts.push(UiText {
+      align: TextAlign::Center,
+      ..UiText::new(name, point.x, bar_y - size, size, NAME_COLOR)
+    });
+  }
+}
+
+pub struct NameplateSystem;
+
+impl<'a> ecs::System<'a> for NameplateSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CameraInputState>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Interpolation>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, camera, character_input, zombies, strings, states, interpolation, dim, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    if !states.current().is_simulating() {
+      return;
+    }
+    let first = (&players, &camera, &character_input).join().find(|(p, _, _)| p.index == 0);
+    if let Some((_, camera, ci)) = first {
+      let scroll = interpolated_scroll(ci, interpolation.0);
+      nameplates(zombies.join(), scroll, camera, &dim, &strings, &mut layer);
+    }
+  }
+}
@@ -0,0 +1,79 @@
+use cgmath::{InnerSpace, Point2, Vector2};
+
+use crate::character::{controls::CharacterInputState, CharacterDrawable, Player};
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{HUD_MARGIN, MAX_MAGAZINES, OBJECTIVE_ARROW_SIZE};
+use crate::game::state::GameStates;
+use crate::graphics::{camera::CameraInputState, dimensions::Dimensions, distance, Interpolation};
+use crate::graphics::viewport::interpolated_scroll;
+use crate::hud::ui::{triangle_quads, UiLayer, UiQuad};
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+
+const ARROW_STRIPS: usize = 12;
+const ARROW_COLOR: [f32; 4] = [1.0, 0.85, 0.3, 0.85];
+
+// The closest ammo pickup, positions are relative to the first player
+pub fn nearest_ammo<'o, I>(objects: I) -> Option<Position>
+  where I: Iterator<Item=&'o TerrainObjectDrawable> {
+  objects
+    .filter(|o| o.object_type == TerrainTexture::Ammo)
+    .map(|o| o.transform.position)
+    .min_by(|a, b| distance(a.x(), a.y()).partial_cmp(&distance(b.x(), b.y())).unwrap())
+}
+
+// An arrow at the edge of the first player's view pointing at a target outside of it. The tip
+// sits where the line from the view center to the target leaves the view, inset by the margin.
+pub fn objective_arrow(target: Position, scroll: Position, camera: &CameraInputState, dimensions: &Dimensions) -> Option<Vec<UiQuad>> {
+  let view_width = if dimensions.split_screen { dimensions.window_width / 2.0 } else { dimensions.window_width };
+  let point = dimensions.world_to_screen(target + scroll, camera);
+  if point.x >= 0.0 && point.x <= view_width && point.y >= 0.0 && point.y <= dimensions.window_height {
+    return None;
+  }
+  let scale = dimensions.hidpi_factor;
+  let size = OBJECTIVE_ARROW_SIZE * scale;
+  let inset = HUD_MARGIN * scale + size;
+  let center = Point2::new(view_width / 2.0, dimensions.window_height / 2.0);
+  let direction = point - center;
+  let (half_width, half_height) = ((center.x - inset).max(0.0), (center.y - inset).max(0.0));
+  let reach = (half_width / direction.x.abs()).min(half_height / direction.y.abs());
+  let tip = center + direction * reach;
+  let forward = direction.normalize();
+  let side = Vector2::new(-forward.y, forward.x) * size / 2.0;
+  let base = tip - forward * size;
+  Some(triangle_quads([tip.into(), (base + side).into(), (base - side).into()], ARROW_STRIPS, ARROW_COLOR))
+}
+
+// Points the first player to the nearest ammo while there is room for another magazine
+pub struct ObjectiveArrowSystem;
+
+impl<'a> ecs::System<'a> for ObjectiveArrowSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CharacterDrawable>,
+                     ReadStorage<'a, CameraInputState>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     Read<'a, GameStates>,
+                     Read<'a, Interpolation>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, character, camera, character_input, terrain_objects, states, interpolation, dim, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    if !states.current().is_simulating() {
+      return;
+    }
+    let first = (&players, &character, &camera, &character_input).join().find(|(p, ..)| p.index == 0);
+    if let Some((_, c, camera, ci)) = first {
+      if c.stats.magazines >= MAX_MAGAZINES {
+        return;
+      }
+      let scroll = interpolated_scroll(ci, interpolation.0);
+      let arrow = nearest_ammo(terrain_objects.join()).and_then(|target| objective_arrow(target, scroll, camera, &dim));
+      if let Some(quads) = arrow {
+        layer.quads.extend(quads);
+      }
+    }
+  }
+}
@@ -0,0 +1,21 @@
+use crate::ecs::{self, Read, Write};
+use crate::game::state::{GameState, GameStates};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const DIM_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+// Dims the frame and the HUD under the pause menu and the inventory, their text is drawn on top
+pub struct PauseOverlaySystem;
+
+impl<'a> ecs::System<'a> for PauseOverlaySystem {
+  type SystemData = (Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (states, dim, mut layer): Self::SystemData) {
+    if states.contains(GameState::Paused) || states.current() == GameState::Inventory {
+      layer.quads.push(UiQuad::new(0.0, 0.0, dim.window_width, dim.window_height, DIM_COLOR));
+    }
+  }
+}
@@ -0,0 +1,41 @@
+use crate::ecs::{self, Read, Write};
+use crate::game::constants::{HUD_BAR_HEIGHT, HUD_BAR_WIDTH, HUD_ICON_SIZE, HUD_MARGIN, HUD_SPACING, HUD_TEXT};
+use crate::game::localization::Localization;
+use crate::game::score::Score;
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::TextAlign;
+use crate::hud::ui::UiLayer;
+use crate::hud::widgets::{Anchor, Panel, Stacking, Widget};
+
+// Score, kills and wave in the top right corner, below the second player's status bars in split screen
+pub fn score_panel(score: &Score, dimensions: &Dimensions, strings: &Localization) -> Panel {
+  let top = if dimensions.split_screen {
+    HUD_MARGIN + (HUD_BAR_HEIGHT + HUD_SPACING) * 2.0 + HUD_ICON_SIZE + HUD_SPACING
+  } else {
+    HUD_MARGIN
+  };
+  Panel {
+    offset: (HUD_MARGIN, top),
+    spacing: 0.0,
+    align: TextAlign::Right,
+    widgets: score.hud_lines(strings).into_iter().map(Widget::Label).collect(),
+    ..Panel::new(Anchor::TopRight, Stacking::Vertical, HUD_BAR_WIDTH, HUD_TEXT)
+  }
+}
+
+pub struct ScorePanelSystem;
+
+impl<'a> ecs::System<'a> for ScorePanelSystem {
+  type SystemData = (Read<'a, Score>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (score, strings, states, dim, mut layer): Self::SystemData) {
+    if states.current().is_simulating() {
+      score_panel(&score, &dim, &strings).draw(&dim, &mut layer);
+    }
+  }
+}
@@ -0,0 +1,67 @@
+use crate::character::Player;
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{HUD_BAR_HEIGHT, HUD_BAR_WIDTH, HUD_ICON_SIZE, HUD_MARGIN, HUD_SPACING};
+use crate::game::health::Health;
+use crate::game::status::{Stamina, StatusEffect, StatusEffects};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::ui::{UiLayer, UiQuad};
+use crate::net::{client::local_index, LocalPlayer};
+
+const BAR_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const HEALTH_COLOR: [f32; 4] = [0.8, 0.1, 0.1, 0.9];
+const STAMINA_COLOR: [f32; 4] = [0.9, 0.8, 0.2, 0.9];
+
+fn effect_color(effect: StatusEffect) -> [f32; 4] {
+  match effect {
+    StatusEffect::Hurt => [1.0, 0.3, 0.1, 0.9],
+    StatusEffect::Exhausted => [0.4, 0.5, 0.8, 0.9],
+  }
+}
+
+fn bar(x: f32, y: f32, scale: f32, fraction: f32, color: [f32; 4], quads: &mut Vec<UiQuad>) {
+  let (width, height) = (HUD_BAR_WIDTH * scale, HUD_BAR_HEIGHT * scale);
+  quads.push(UiQuad::new(x, y, width, height, BAR_BACKGROUND));
+  quads.push(UiQuad::new(x, y, width * fraction.clamp(0.0, 1.0), height, color));
+}
+
+// Health and stamina bars with the effect icons below them, the first player in the top left
+// corner and the second one in the top right
+pub fn status_quads(player_index: usize, dimensions: &Dimensions, health: &Health, stamina: &Stamina,
+                    effects: &StatusEffects) -> Vec<UiQuad> {
+  let scale = dimensions.hidpi_factor;
+  let margin = HUD_MARGIN * scale;
+  let spacing = HUD_SPACING * scale;
+  let x = if player_index == 0 { margin } else { dimensions.window_width - margin - HUD_BAR_WIDTH * scale };
+  let mut y = margin;
+
+  let mut quads = Vec::new();
+  bar(x, y, scale, health.current / health.max, HEALTH_COLOR, &mut quads);
+  y += (HUD_BAR_HEIGHT + HUD_SPACING) * scale;
+  bar(x, y, scale, stamina.current / stamina.max, STAMINA_COLOR, &mut quads);
+  y += (HUD_BAR_HEIGHT + HUD_SPACING) * scale;
+  for (idx, effect) in effects.active().enumerate() {
+    let icon_x = x + idx as f32 * (HUD_ICON_SIZE * scale + spacing);
+    quads.push(UiQuad::new(icon_x, y, HUD_ICON_SIZE * scale, HUD_ICON_SIZE * scale, effect_color(effect)));
+  }
+  quads
+}
+
+pub struct StatusBarSystem;
+
+impl<'a> ecs::System<'a> for StatusBarSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, Health>,
+                     ReadStorage<'a, Stamina>,
+                     ReadStorage<'a, StatusEffects>,
+                     Read<'a, Dimensions>,
+                     Read<'a, LocalPlayer>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, health, stamina, effects, dim, local, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    for (p, h, s, e) in (&players, &health, &stamina, &effects).join() {
+      layer.quads.extend(status_quads(local_index(p.index, local.0), &dim, h, s, e));
+    }
+  }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use gfx;
+use gfx::format::{ChannelType, R8_G8_B8_A8, Rgba8, Swizzle};
+use gfx::memory::{Bind, Usage};
+use gfx::texture::{AaMode, Kind};
+use rusttype::{Font, GlyphId, point, Scale, ScaledGlyph};
+
+use crate::graphics::{dimensions::Dimensions, mesh::PlainMesh, render_graph::{PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader}};
+use crate::hud::font::load_font;
+use crate::hud::ui::ui_projection;
+use crate::shaders::{GlyphLocals, text_pipeline, VertexData};
+
+const SHADER_VERT: &[u8] = include_bytes!("../shaders/text.v.glsl");
+const SHADER_FRAG: &[u8] = include_bytes!("../shaders/text.f.glsl");
+
+const ATLAS_SIZE: u16 = 512;
+// Keeps the linear sampler from bleeding neighbouring glyphs in
+const GLYPH_PADDING: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAlign {
+  Left,
+  Center,
+  Right,
+}
+
+// A line of text in window pixels, x and y are the top of the line at its alignment point
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiText {
+  pub text: String,
+  pub x: f32,
+  pub y: f32,
+  // Line height in pixels
+  pub size: f32,
+  pub color: [f32; 4],
+  pub align: TextAlign,
+}
+
+impl UiText {
+  pub fn new(text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) -> UiText {
+    UiText {
+      text: text.to_string(),
+      x,
+      y,
+      size,
+      color,
+      align: TextAlign::Left,
+    }
+  }
+}
+
+// Where a cached glyph is in the atlas and where it is drawn relative to the pen on the baseline
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphEntry {
+  pub uv: [f32; 4],
+  pub bounds: [f32; 4],
+}
+
+// A glyph ready to draw: window pixel rectangle and atlas rectangle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+  pub rect: [f32; 4],
+  pub uv: [f32; 4],
+}
+
+// Glyphs are rasterized once per size into shelves of one atlas texture, the new ones wait in
+// uploads until the draw system copies them over. A full atlas starts over from empty.
+pub struct GlyphCache {
+  glyphs: HashMap<(GlyphId, u32), Option<GlyphEntry>>,
+  shelf_x: u16,
+  shelf_y: u16,
+  shelf_height: u16,
+  pub uploads: Vec<([u16; 4], Vec<[u8; 4]>)>,
+}
+
+impl GlyphCache {
+  pub fn new() -> GlyphCache {
+    GlyphCache {
+      glyphs: HashMap::new(),
+      shelf_x: 0,
+      shelf_y: 0,
+      shelf_height: 0,
+      uploads: Vec::new(),
+    }
+  }
+
+  fn clear(&mut self) {
+    self.glyphs.clear();
+    self.shelf_x = 0;
+    self.shelf_y = 0;
+    self.shelf_height = 0;
+  }
+
+  // Top left corner of a free area, moving to the next shelf when the current one is full
+  fn allocate(&mut self, width: u16, height: u16) -> Option<(u16, u16)> {
+    if width + GLYPH_PADDING > ATLAS_SIZE || height + GLYPH_PADDING > ATLAS_SIZE {
+      return None;
+    }
+    if self.shelf_x + width + GLYPH_PADDING > ATLAS_SIZE {
+      self.shelf_x = 0;
+      self.shelf_y += self.shelf_height;
+      self.shelf_height = 0;
+    }
+    if self.shelf_y + height + GLYPH_PADDING > ATLAS_SIZE {
+      self.clear();
+    }
+    let corner = (self.shelf_x, self.shelf_y);
+    self.shelf_x += width + GLYPH_PADDING;
+    self.shelf_height = self.shelf_height.max(height + GLYPH_PADDING);
+    Some(corner)
+  }
+
+  // None for glyphs without pixels, like a space
+  pub fn entry(&mut self, glyph: &ScaledGlyph<'static>, size: f32) -> Option<GlyphEntry> {
+    let key = (glyph.id(), size.round() as u32);
+    if let Some(entry) = self.glyphs.get(&key) {
+      return *entry;
+    }
+
+    let positioned = glyph.clone().positioned(point(0.0, 0.0));
+    let entry = positioned.pixel_bounding_box().and_then(|bb| {
+      let (width, height) = (bb.width() as u16, bb.height() as u16);
+      let (x, y) = self.allocate(width, height)?;
+      let mut pixels = vec![[0u8; 4]; usize::from(width) * usize::from(height)];
+      positioned.draw(|px, py, v| pixels[px as usize + py as usize * usize::from(width)] = [255, 255, 255, (v * 255.0 + 0.5) as u8]);
+      self.uploads.push(([x, y, width, height], pixels));
+
+      let atlas = f32::from(ATLAS_SIZE);
+      Some(GlyphEntry {
+        uv: [f32::from(x) / atlas, f32::from(y) / atlas, f32::from(width) / atlas, f32::from(height) / atlas],
+        bounds: [bb.min.x as f32, bb.min.y as f32, f32::from(width), f32::from(height)],
+      })
+    });
+    self.glyphs.insert(key, entry);
+    entry
+  }
+}
+
+impl Default for GlyphCache {
+  fn default() -> GlyphCache {
+    GlyphCache::new()
+  }
+}
+
+pub fn text_width(font: &Font<'static>, text: &str, size: f32) -> f32 {
+  let scale = Scale::uniform(size);
+  font.layout(text, scale, point(0.0, 0.0))
+    .last()
+    .map_or(0.0, |g| g.position().x + g.unpositioned().h_metrics().advance_width)
+}
+
+// Pen positions come from rusttype with kerning, they are rounded to whole pixels so one cached
+// rasterization of a glyph fits everywhere
+pub fn layout_text(font: &Font<'static>, cache: &mut GlyphCache, text: &UiText) -> Vec<GlyphQuad> {
+  let scale = Scale::uniform(text.size);
+  let x = match text.align {
+    TextAlign::Left => text.x,
+    TextAlign::Center => text.x - text_width(font, &text.text, text.size) / 2.0,
+    TextAlign::Right => text.x - text_width(font, &text.text, text.size),
+  };
+  let baseline = (text.y + font.v_metrics(scale).ascent).round();
+  font.layout(&text.text, scale, point(0.0, 0.0))
+    .filter_map(|glyph| {
+      let pen = (x + glyph.position().x).round();
+      cache.entry(glyph.unpositioned(), text.size).map(|entry| GlyphQuad {
+        rect: [pen + entry.bounds[0], baseline + entry.bounds[1], entry.bounds[2], entry.bounds[3]],
+        uv: entry.uv,
+      })
+    })
+    .collect()
+}
+
+pub struct TextDrawSystem<R: gfx::Resources> {
+  font: Font<'static>,
+  cache: GlyphCache,
+  atlas: gfx::handle::Texture<R, R8_G8_B8_A8>,
+  bundle: gfx::pso::bundle::Bundle<R, text_pipeline::Data<R>>,
+  pso_updates: PipelineUpdates<R, text_pipeline::Meta>,
+}
+
+impl<R: gfx::Resources> TextDrawSystem<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                reloader: &mut ShaderReloader<F>) -> TextDrawSystem<R>
+    where F: gfx::Factory<R> {
+    use gfx::traits::FactoryExt;
+
+    let vertices = [
+      VertexData::new([0.0, 0.0], [0.0, 0.0]),
+      VertexData::new([1.0, 0.0], [1.0, 0.0]),
+      VertexData::new([1.0, 1.0], [1.0, 1.0]),
+      VertexData::new([0.0, 1.0], [0.0, 1.0]),
+    ];
+    let mesh = PlainMesh::new(factory, &vertices, &[0, 1, 2, 2, 3, 0]);
+
+    let kind = Kind::D2(ATLAS_SIZE, ATLAS_SIZE, AaMode::Single);
+    let atlas = factory.create_texture::<R8_G8_B8_A8>(kind, 1, Bind::SHADER_RESOURCE | Bind::TRANSFER_DST,
+                                                      Usage::Dynamic, Some(ChannelType::Unorm))
+      .expect("Glyph atlas creation error");
+    let view = factory.view_texture_as_shader_resource::<Rgba8>(&atlas, (0, 0), Swizzle::new())
+      .expect("Glyph atlas view error");
+
+    let (pso, pso_updates) = PassDescriptor::new("Text",
+                                                 ShaderFile::new("text.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("text.f.glsl", SHADER_FRAG),
+                                                 text_pipeline::new())
+      .build(factory, reloader);
+
+    let pipeline_data = text_pipeline::Data {
+      vbuf: mesh.vertex_buffer,
+      locals: factory.create_constant_buffer(1),
+      glyph_atlas: (view, factory.create_sampler_linear()),
+      out_color: targets.color.clone(),
+    };
+
+    TextDrawSystem {
+      font: load_font(),
+      cache: GlyphCache::new(),
+      atlas,
+      bundle: gfx::Bundle::new(mesh.slice, pso, pipeline_data),
+      pso_updates,
+    }
+  }
+
+  // Returns the number of glyph quads drawn
+  pub fn draw<C>(&mut self,
+                 texts: &[UiText],
+                 dimensions: &Dimensions,
+                 encoder: &mut gfx::Encoder<R, C>) -> usize
+    where C: gfx::CommandBuffer<R> {
+    self.pso_updates.apply(&mut self.bundle.pso);
+    let proj = ui_projection(dimensions);
+    let mut draw_calls = 0;
+    for text in texts {
+      let quads = layout_text(&self.font, &mut self.cache, text);
+      for ([x, y, width, height], pixels) in self.cache.uploads.drain(..) {
+        let info = gfx::texture::ImageInfoCommon {
+          xoffset: x,
+          yoffset: y,
+          zoffset: 0,
+          width,
+          height,
+          depth: 0,
+          format: (),
+          mipmap: 0,
+        };
+        encoder.update_texture::<R8_G8_B8_A8, Rgba8>(&self.atlas, None, info, &pixels)
+          .expect("Glyph atlas update error");
+      }
+      draw_calls += quads.len();
+      for quad in quads {
+        encoder.update_constant_buffer(&self.bundle.data.locals, &GlyphLocals {
+          proj,
+          rect: quad.rect,
+          uv_rect: quad.uv,
+          color: text.color,
+        });
+        self.bundle.encode(encoder);
+      }
+    }
+    draw_calls
+  }
+}
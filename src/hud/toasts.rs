@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::ecs::{self, Read, ReaderId, Write};
+use crate::game::constants::{HUD_MARGIN, HUD_SPACING, HUD_TEXT, MAX_TOASTS, TOAST_DURATION};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::localization::Localization;
+use crate::game::state::GameStates;
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const TOAST_WIDTH: f32 = 320.0;
+const TOAST_HEIGHT: f32 = 30.0;
+// Seconds at the end of a toast it takes to fade out
+const TOAST_FADE: f32 = 0.5;
+const TOAST_COLOR: [f32; 4] = [0.1, 0.1, 0.12, 0.75];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.95];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+  pub text: String,
+  remaining: f32,
+}
+
+// Toasts stack down from the top center, the oldest first
+#[derive(Default)]
+pub struct Toasts {
+  queue: VecDeque<Toast>,
+}
+
+impl Toasts {
+  pub fn push(&mut self, text: String) {
+    if self.queue.len() == MAX_TOASTS {
+      self.queue.pop_front();
+    }
+    self.queue.push_back(Toast {
+      text,
+      remaining: TOAST_DURATION,
+    });
+  }
+
+  pub fn tick(&mut self, delta: f32) {
+    for toast in &mut self.queue {
+      toast.remaining -= delta;
+    }
+    self.queue.retain(|toast| toast.remaining > 0.0);
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item=&Toast> {
+    self.queue.iter()
+  }
+
+  pub fn draw(&self, dimensions: &Dimensions, layer: &mut UiLayer) {
+    let scale = dimensions.hidpi_factor;
+    let (width, height) = (TOAST_WIDTH * scale, TOAST_HEIGHT * scale);
+    let size = HUD_TEXT * scale;
+    let x = (dimensions.window_width - width) / 2.0;
+    for (idx, toast) in self.iter().enumerate() {
+      let y = HUD_MARGIN * scale + idx as f32 * (height + HUD_SPACING * scale);
+      let fade = (toast.remaining / TOAST_FADE).min(1.0);
+      let [r, g, b, a] = TOAST_COLOR;
+      layer.quads.push(UiQuad::new(x, y, width, height, [r, g, b, a * fade]));
+      let [r, g, b, a] = TEXT_COLOR;
+      layer.texts.push(UiText {
+        align: TextAlign::Center,
+        ..UiText::new(&toast.text, x + width / 2.0, y + (height - size) / 2.0, size, [r, g, b, a * fade])
+      });
+    }
+  }
+}
+
+// Any system can show a toast by writing GameEvent::Toast with a string key
+pub struct ToastSystem {
+  reader: ReaderId<GameEvent>,
+  last_frame: Instant,
+  toasts: Toasts,
+}
+
+impl ToastSystem {
+  pub fn new(reader: ReaderId<GameEvent>) -> ToastSystem {
+    ToastSystem {
+      reader,
+      last_frame: Instant::now(),
+      toasts: Toasts::default(),
+    }
+  }
+}
+
+impl<'a> ecs::System<'a> for ToastSystem {
+  type SystemData = (Read<'a, GameEvents>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (events, strings, states, dim, mut layer): Self::SystemData) {
+    let delta = self.last_frame.elapsed().as_secs_f32();
+    self.last_frame = Instant::now();
+
+    for event in events.read(&mut self.reader) {
+      if let GameEvent::Toast(key) = *event {
+        self.toasts.push(strings.get(key).to_string());
+      }
+    }
+    // Toasts wait out the pause menu like the banners
+    if states.current().is_simulating() {
+      self.toasts.tick(delta);
+      self.toasts.draw(&dim, &mut layer);
+    }
+  }
+}
@@ -0,0 +1,89 @@
+use cgmath::Point2;
+
+use crate::character::controls::CharacterInputState;
+use crate::character::Player;
+use crate::ecs::{self, Read, ReadStorage, Write};
+use crate::game::constants::{HUD_SMALL_TEXT, TOOLTIP_RANGE};
+use crate::game::localization::Localization;
+use crate::game::state::GameStates;
+use crate::graphics::{camera::CameraInputState, dimensions::Dimensions, distance, Interpolation};
+use crate::graphics::viewport::interpolated_scroll;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::ui::UiLayer;
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+
+const TOOLTIP_COLOR: [f32; 4] = [1.0, 0.95, 0.7, 0.9];
+// Logical pixels between the label and the object it belongs to
+const TOOLTIP_LIFT: f32 = 28.0;
+
+fn tooltip_key(object_type: TerrainTexture) -> Option<&'static str> {
+  match object_type {
+    TerrainTexture::Ammo => Some("tooltip.ammo"),
+    TerrainTexture::House | TerrainTexture::Tree => None,
+  }
+}
+
+// Window pixels of a position relative to the first player, the scroll is the interpolation the
+// view applies to it. None when the point and the given height of UI above it leave the view.
+pub fn anchor_point(position: Position, scroll: Position, camera: &CameraInputState, dimensions: &Dimensions, height: f32) -> Option<Point2<f32>> {
+  // The first player's split view is the left half of the window
+  let view_width = if dimensions.split_screen { dimensions.window_width / 2.0 } else { dimensions.window_width };
+  let point = dimensions.world_to_screen(position + scroll, camera);
+  if point.x < 0.0 || point.x > view_width || point.y - height < 0.0 || point.y > dimensions.window_height {
+    None
+  } else {
+    Some(point)
+  }
+}
+
+// Labels above the objects near the first player, projected into its view. Labels that would
+// leave the view are dropped.
+pub fn tooltip_texts<'o, I>(objects: I,
+                            scroll: Position,
+                            camera: &CameraInputState,
+                            dimensions: &Dimensions,
+                            strings: &Localization) -> Vec<UiText>
+  where I: Iterator<Item=&'o TerrainObjectDrawable> {
+  let scale = dimensions.hidpi_factor;
+  let size = HUD_SMALL_TEXT * scale;
+  let lift = TOOLTIP_LIFT * scale + size;
+  objects
+    .filter(|o| distance(o.transform.position.x(), o.transform.position.y()) < TOOLTIP_RANGE)
+    .filter_map(|o| {
+      let text = strings.get(tooltip_key(o.object_type)?);
+      let point = anchor_point(o.transform.position, scroll, camera, dimensions, lift)?;
+      Some(UiText {
+        align: TextAlign::Center,
+        ..UiText::new(text, point.x, point.y - lift, size, TOOLTIP_COLOR)
+      })
+    })
+    .collect()
+}
+
+pub struct TooltipSystem;
+
+impl<'a> ecs::System<'a> for TooltipSystem {
+  type SystemData = (ReadStorage<'a, Player>,
+                     ReadStorage<'a, CameraInputState>,
+                     ReadStorage<'a, CharacterInputState>,
+                     ReadStorage<'a, TerrainObjectDrawable>,
+                     Read<'a, Localization>,
+                     Read<'a, GameStates>,
+                     Read<'a, Interpolation>,
+                     Read<'a, Dimensions>,
+                     Write<'a, UiLayer>);
+
+  fn run(&mut self, (players, camera, character_input, terrain_objects, strings, states, interpolation, dim, mut layer): Self::SystemData) {
+    use crate::ecs::Join;
+
+    if !states.current().is_simulating() {
+      return;
+    }
+    let first = (&players, &camera, &character_input).join().find(|(p, _, _)| p.index == 0);
+    if let Some((_, camera, ci)) = first {
+      let scroll = interpolated_scroll(ci, interpolation.0);
+      layer.texts.extend(tooltip_texts(terrain_objects.join(), scroll, camera, &dim, &strings));
+    }
+  }
+}
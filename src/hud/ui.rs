@@ -0,0 +1,119 @@
+use cgmath;
+use gfx;
+
+use crate::graphics::{dimensions::Dimensions, mesh::PlainMesh, render_graph::{PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader}};
+use crate::hud::text::UiText;
+use crate::shaders::{ui_pipeline, UiLocals, VertexData};
+
+const SHADER_VERT: &[u8] = include_bytes!("../shaders/ui.v.glsl");
+const SHADER_FRAG: &[u8] = include_bytes!("../shaders/ui.f.glsl");
+
+// A rectangle in window pixels from the top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiQuad {
+  pub rect: [f32; 4],
+  pub color: [f32; 4],
+}
+
+impl UiQuad {
+  pub fn new(x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> UiQuad {
+    UiQuad {
+      rect: [x, y, width, height],
+      color,
+    }
+  }
+}
+
+// A filled triangle in window pixels as a stack of horizontal strips, each as wide as the triangle
+// at the middle of the strip
+pub fn triangle_quads(points: [[f32; 2]; 3], strips: usize, color: [f32; 4]) -> Vec<UiQuad> {
+  let top = points.iter().map(|p| p[1]).fold(f32::MAX, f32::min);
+  let bottom = points.iter().map(|p| p[1]).fold(f32::MIN, f32::max);
+  let height = (bottom - top) / strips as f32;
+  (0..strips)
+    .filter_map(|idx| {
+      let y = top + (idx as f32 + 0.5) * height;
+      let crossings = (0..3)
+        .map(|i| (points[i], points[(i + 1) % 3]))
+        .filter(|(a, b)| (a[1] <= y && y < b[1]) || (b[1] <= y && y < a[1]))
+        .map(|(a, b)| a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]))
+        .collect::<Vec<_>>();
+      let left = crossings.iter().cloned().fold(f32::MAX, f32::min);
+      let right = crossings.iter().cloned().fold(f32::MIN, f32::max);
+      if crossings.len() < 2 {
+        None
+      } else {
+        Some(UiQuad::new(left, top + idx as f32 * height, right - left, height, color))
+      }
+    })
+    .collect()
+}
+
+// Quads and texts pushed by the HUD systems of a frame, drawn over the scene and cleared by the draw system.
+// Texts go over the quads.
+#[derive(Default)]
+pub struct UiLayer {
+  pub quads: Vec<UiQuad>,
+  pub texts: Vec<UiText>,
+}
+
+// The UI has its own projection in window pixels, camera movement and zoom never reach it
+pub fn ui_projection(dimensions: &Dimensions) -> [[f32; 4]; 4] {
+  cgmath::ortho(0.0, dimensions.window_width, dimensions.window_height, 0.0, -1.0, 1.0).into()
+}
+
+pub struct UiDrawSystem<R: gfx::Resources> {
+  bundle: gfx::pso::bundle::Bundle<R, ui_pipeline::Data<R>>,
+  pso_updates: PipelineUpdates<R, ui_pipeline::Meta>,
+}
+
+impl<R: gfx::Resources> UiDrawSystem<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                reloader: &mut ShaderReloader<F>) -> UiDrawSystem<R>
+    where F: gfx::Factory<R> {
+    use gfx::traits::FactoryExt;
+
+    let vertices = [
+      VertexData::new([0.0, 0.0], [0.0, 0.0]),
+      VertexData::new([1.0, 0.0], [1.0, 0.0]),
+      VertexData::new([1.0, 1.0], [1.0, 1.0]),
+      VertexData::new([0.0, 1.0], [0.0, 1.0]),
+    ];
+    let mesh = PlainMesh::new(factory, &vertices, &[0, 1, 2, 2, 3, 0]);
+
+    let (pso, pso_updates) = PassDescriptor::new("UI",
+                                                 ShaderFile::new("ui.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("ui.f.glsl", SHADER_FRAG),
+                                                 ui_pipeline::new())
+      .build(factory, reloader);
+
+    let pipeline_data = ui_pipeline::Data {
+      vbuf: mesh.vertex_buffer,
+      locals: factory.create_constant_buffer(1),
+      out_color: targets.color.clone(),
+    };
+
+    UiDrawSystem {
+      bundle: gfx::Bundle::new(mesh.slice, pso, pipeline_data),
+      pso_updates,
+    }
+  }
+
+  pub fn draw<C>(&mut self,
+                 quads: &[UiQuad],
+                 dimensions: &Dimensions,
+                 encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    self.pso_updates.apply(&mut self.bundle.pso);
+    let proj = ui_projection(dimensions);
+    for quad in quads {
+      encoder.update_constant_buffer(&self.bundle.data.locals, &UiLocals {
+        proj,
+        rect: quad.rect,
+        color: quad.color,
+      });
+      self.bundle.encode(encoder);
+    }
+  }
+}
@@ -0,0 +1,231 @@
+use crate::game::constants::{HUD_MARGIN, HUD_SPACING, HUD_TEXT};
+use crate::graphics::dimensions::Dimensions;
+use crate::hud::text::{TextAlign, UiText};
+use crate::hud::ui::{UiLayer, UiQuad};
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const IDLE_COLOR: [f32; 4] = [0.1, 0.1, 0.12, 0.7];
+const HOVERED_COLOR: [f32; 4] = [0.25, 0.25, 0.3, 0.8];
+const FOCUSED_COLOR: [f32; 4] = [0.45, 0.35, 0.1, 0.85];
+const PRESSED_COLOR: [f32; 4] = [0.7, 0.5, 0.1, 0.9];
+const SLIDER_COLOR: [f32; 4] = [0.8, 0.6, 0.2, 0.6];
+
+// Where a panel sits in the window, its offset points inwards from that edge or corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+  TopLeft,
+  Top,
+  TopRight,
+  Left,
+  Center,
+  Right,
+  BottomLeft,
+  Bottom,
+  BottomRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stacking {
+  Vertical,
+  Horizontal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Widget {
+  Label(String),
+  Button(String),
+  // The value goes from 0 to 1
+  Slider(String, f32),
+}
+
+impl Widget {
+  pub fn is_interactive(&self) -> bool {
+    !matches!(self, Widget::Label(_))
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WidgetState {
+  Idle,
+  Hovered,
+  Focused,
+  Pressed,
+}
+
+fn state_color(state: WidgetState) -> [f32; 4] {
+  match state {
+    WidgetState::Idle => IDLE_COLOR,
+    WidgetState::Hovered => HOVERED_COLOR,
+    WidgetState::Focused => FOCUSED_COLOR,
+    WidgetState::Pressed => PRESSED_COLOR,
+  }
+}
+
+// A container stacking same sized widgets from its anchor. Sizes are in logical pixels, the layout
+// scales them with the window. The panel keeps its widgets between frames along with the focus,
+// hover and press state, labels never take any of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Panel {
+  pub anchor: Anchor,
+  pub stacking: Stacking,
+  pub offset: (f32, f32),
+  pub item_size: (f32, f32),
+  pub spacing: f32,
+  pub text_size: f32,
+  pub align: TextAlign,
+  pub text_color: [f32; 4],
+  pub widgets: Vec<Widget>,
+  pub focused: Option<usize>,
+  pub hovered: Option<usize>,
+  pub pressed: Option<usize>,
+}
+
+impl Panel {
+  pub fn new(anchor: Anchor, stacking: Stacking, width: f32, height: f32) -> Panel {
+    Panel {
+      anchor,
+      stacking,
+      offset: (HUD_MARGIN, HUD_MARGIN),
+      item_size: (width, height),
+      spacing: HUD_SPACING,
+      text_size: HUD_TEXT,
+      align: TextAlign::Left,
+      text_color: TEXT_COLOR,
+      widgets: Vec::new(),
+      focused: None,
+      hovered: None,
+      pressed: None,
+    }
+  }
+
+  // Replaces the widgets and keeps the state of the ones that are still there
+  pub fn set_widgets(&mut self, widgets: Vec<Widget>) {
+    let count = widgets.len();
+    self.widgets = widgets;
+    let keep = |idx: Option<usize>| idx.filter(|&idx| idx < count);
+    self.focused = keep(self.focused);
+    self.hovered = keep(self.hovered);
+    self.pressed = keep(self.pressed);
+  }
+
+  fn is_interactive(&self, idx: usize) -> bool {
+    matches!(self.widgets.get(idx), Some(widget) if widget.is_interactive())
+  }
+
+  // Whole panel in window pixels
+  pub fn bounds(&self, dimensions: &Dimensions) -> [f32; 4] {
+    let scale = dimensions.hidpi_factor;
+    let count = self.widgets.len() as f32;
+    let gaps = (count - 1.0).max(0.0) * self.spacing;
+    let (width, height) = match self.stacking {
+      Stacking::Vertical => (self.item_size.0, self.item_size.1 * count + gaps),
+      Stacking::Horizontal => (self.item_size.0 * count + gaps, self.item_size.1),
+    };
+    let (width, height) = (width * scale, height * scale);
+    let (offset_x, offset_y) = (self.offset.0 * scale, self.offset.1 * scale);
+    let x = match self.anchor {
+      Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => offset_x,
+      Anchor::Top | Anchor::Center | Anchor::Bottom => (dimensions.window_width - width) / 2.0,
+      Anchor::TopRight | Anchor::Right | Anchor::BottomRight => dimensions.window_width - offset_x - width,
+    };
+    let y = match self.anchor {
+      Anchor::TopLeft | Anchor::Top | Anchor::TopRight => offset_y,
+      Anchor::Left | Anchor::Center | Anchor::Right => (dimensions.window_height - height) / 2.0,
+      Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => dimensions.window_height - offset_y - height,
+    };
+    [x, y, width, height]
+  }
+
+  // Widget rectangles in window pixels
+  pub fn layout(&self, dimensions: &Dimensions) -> Vec<[f32; 4]> {
+    let scale = dimensions.hidpi_factor;
+    let [x, y, _, _] = self.bounds(dimensions);
+    let (width, height) = (self.item_size.0 * scale, self.item_size.1 * scale);
+    (0..self.widgets.len())
+      .map(|idx| {
+        let step = idx as f32;
+        match self.stacking {
+          Stacking::Vertical => [x, y + step * (height + self.spacing * scale), width, height],
+          Stacking::Horizontal => [x + step * (width + self.spacing * scale), y, width, height],
+        }
+      })
+      .collect()
+  }
+
+  // The interactive widget under a point in window pixels
+  pub fn widget_at(&self, x: f32, y: f32, dimensions: &Dimensions) -> Option<usize> {
+    self.layout(dimensions).iter()
+      .position(|r| x >= r[0] && x < r[0] + r[2] && y >= r[1] && y < r[1] + r[3])
+      .filter(|&idx| self.is_interactive(idx))
+  }
+
+  // Slider value under a window pixel column
+  pub fn slider_value_at(&self, idx: usize, x: f32, dimensions: &Dimensions) -> Option<f32> {
+    match self.widgets.get(idx) {
+      Some(Widget::Slider(..)) => self.layout(dimensions).get(idx).map(|r| ((x - r[0]) / r[2]).clamp(0.0, 1.0)),
+      _ => None,
+    }
+  }
+
+  pub fn focus_first(&mut self) {
+    self.focused = (0..self.widgets.len()).find(|&idx| self.is_interactive(idx));
+  }
+
+  // Moves the focus to the next interactive widget in either direction, wrapping around
+  pub fn focus_step(&mut self, step: isize) {
+    let count = self.widgets.len() as isize;
+    let mut idx = match self.focused {
+      Some(idx) => idx as isize,
+      None => return self.focus_first(),
+    };
+    for _ in 0..count {
+      idx = (idx + step.signum()).rem_euclid(count);
+      if self.is_interactive(idx as usize) {
+        self.focused = Some(idx as usize);
+        return;
+      }
+    }
+  }
+
+  pub fn state(&self, idx: usize) -> WidgetState {
+    if self.pressed == Some(idx) {
+      WidgetState::Pressed
+    } else if self.focused == Some(idx) {
+      WidgetState::Focused
+    } else if self.hovered == Some(idx) {
+      WidgetState::Hovered
+    } else {
+      WidgetState::Idle
+    }
+  }
+
+  pub fn draw(&self, dimensions: &Dimensions, layer: &mut UiLayer) {
+    let size = self.text_size * dimensions.hidpi_factor;
+    let padding = self.spacing * dimensions.hidpi_factor;
+    for (idx, (widget, rect)) in self.widgets.iter().zip(self.layout(dimensions)).enumerate() {
+      let [x, y, width, height] = rect;
+      let text_y = y + (height - size) / 2.0;
+      let text_x = match self.align {
+        TextAlign::Left => x + padding,
+        TextAlign::Center => x + width / 2.0,
+        TextAlign::Right => x + width - padding,
+      };
+      let text = match widget {
+        Widget::Label(text) => text,
+        Widget::Button(text) => {
+          layer.quads.push(UiQuad::new(x, y, width, height, state_color(self.state(idx))));
+          text
+        }
+        Widget::Slider(text, value) => {
+          layer.quads.push(UiQuad::new(x, y, width, height, state_color(self.state(idx))));
+          layer.quads.push(UiQuad::new(x, y, width * value.clamp(0.0, 1.0), height, SLIDER_COLOR));
+          text
+        }
+      };
+      layer.texts.push(UiText {
+        align: self.align,
+        ..UiText::new(text, text_x, text_y, size, self.text_color)
+      });
+    }
+  }
+}
@@ -0,0 +1,22 @@
+#[macro_use]
+extern crate gfx;
+#[macro_use]
+extern crate json;
+
+pub mod audio;
+pub mod bullet;
+pub mod gfx_app;
+pub mod game;
+pub mod data;
+pub mod ecs;
+pub mod critter;
+pub mod graphics;
+pub mod hud;
+pub mod net;
+pub mod profiler;
+pub mod terrain_object;
+pub mod terrain_shape;
+pub mod terrain;
+pub mod character;
+pub mod shaders;
+pub mod zombie;
@@ -1,29 +1,19 @@
 extern crate getopts;
-#[macro_use]
-extern crate gfx;
+
+use std::time::Instant;
 
 use getopts::Options;
 
-use crate::game::constants::{GAME_TITLE, GAME_VERSION};
-use crate::gfx_app::GameOptions;
-
-mod audio;
-mod bullet;
-mod gfx_app;
-mod game;
-mod data;
-mod critter;
-pub mod graphics;
-mod hud;
-mod terrain_object;
-mod terrain_shape;
-mod terrain;
-mod character;
-mod shaders;
-mod zombie;
+use hinterland::data;
+use hinterland::game::constants::{GAME_TITLE, GAME_VERSION};
+use hinterland::gfx_app::{self, GameOptions};
+use hinterland::gfx_app::replay::ReplayMode;
+use hinterland::net::NetMode;
+use hinterland::profiler::startup::StartupProfile;
+use hinterland::profiler::trace;
 
 fn print_usage() {
-  println!("USAGE:\nhinterland [FLAGS]\n\nFLAGS:\n-h, --help\t\t\tPrints help information\n-v, --version\t\t\tPrints version information\n-w, --windowed_mode\t\tRun game in windowed mode");
+  println!("USAGE:\nhinterland [FLAGS]\n\nFLAGS:\n-h, --help\t\t\tPrints help information\n-v, --version\t\t\tPrints version information\n-w, --windowed_mode\t\tRun game in windowed mode\n-o, --orthographic\t\tUse orthographic camera projection\n-s, --split_screen\t\tLocal co-op with a split screen view for a second player\n\nOPTIONS:\n--seed <SEED>\t\t\tDeterministic simulation seeded with the given number\n--record <FILE>\t\t\tRecord input to a file for replaying\n--replay <FILE>\t\t\tPlay back recorded input instead of reading the keyboard and mouse\n--host\t\t\t\tHost a LAN co-op game for up to 4 players\n--lockstep\t\t\tWith --host, players exchange only inputs and all run the simulation\n--join <ADDRESS>\t\tJoin the LAN co-op game hosted at the address\n--server\t\t\tHost a LAN co-op game without a window or a player of its own\n--pack_sprites <DIR>\t\tPack the frame PNGs of a directory into a sprite sheet next to it\n--chrome_trace <FILE>\t\tWrite the frame, simulation step and system spans to a Chrome trace file");
 }
 
 fn print_version() {
@@ -31,9 +21,21 @@ fn print_version() {
 }
 
 pub fn main() {
+  let start = Instant::now();
   let args = std::env::args().collect::<Vec<String>>();
   let mut opts = Options::new();
   opts.optflag("w", "windowed_mode", "Run game in windowed mode");
+  opts.optflag("o", "orthographic", "Use orthographic camera projection");
+  opts.optflag("s", "split_screen", "Local co-op with a split screen view for a second player");
+  opts.optopt("", "seed", "Deterministic simulation seeded with the given number", "SEED");
+  opts.optopt("", "record", "Record input to a file for replaying", "FILE");
+  opts.optopt("", "replay", "Play back recorded input instead of reading the keyboard and mouse", "FILE");
+  opts.optflag("", "host", "Host a LAN co-op game for up to 4 players");
+  opts.optflag("", "lockstep", "Players of the hosted LAN game exchange only inputs and all run the simulation");
+  opts.optopt("", "join", "Join the LAN co-op game hosted at the address", "ADDRESS");
+  opts.optflag("", "server", "Host a LAN co-op game without a window or a player of its own");
+  opts.optopt("", "pack_sprites", "Pack the frame PNGs of a directory into a sprite sheet next to it", "DIR");
+  opts.optopt("", "chrome_trace", "Write the frame, simulation step and system spans to a Chrome trace file", "FILE");
   opts.optflag("h", "help", "Prints help information");
   opts.optflag("v", "version", "Prints version information");
 
@@ -52,7 +54,37 @@ pub fn main() {
     return;
   }
 
-  let game_opt = GameOptions::new(matches.opt_present("windowed_mode"));
+  // Kept until the game exits, the trace file is finished when it is dropped
+  let _trace = trace::init(matches.opt_str("chrome_trace"));
+
+  if let Some(dir) = matches.opt_str("pack_sprites") {
+    data::atlas::pack_directory(&dir).unwrap_or_else(|e| panic!("Sprite packing error {}", e));
+    return;
+  }
+
+  let seed = matches.opt_str("seed").map(|s| s.parse::<u64>().unwrap_or_else(|e| panic!("Seed {} parse error {}", s, e)));
+  if matches.opt_present("server") {
+    gfx_app::init::run_server(seed, StartupProfile::new(start));
+    return;
+  }
+  let replay = match (matches.opt_str("replay"), matches.opt_str("record")) {
+    (Some(file), _) => ReplayMode::Play(file),
+    (None, Some(file)) => ReplayMode::Record(file),
+    (None, None) => ReplayMode::Off,
+  };
+  let net = match (matches.opt_present("host"), matches.opt_str("join")) {
+    (true, Some(_)) => {
+      eprintln!("--host and --join can't be used together");
+      print_usage();
+      std::process::exit(1);
+    }
+    (false, Some(address)) => NetMode::Client(address),
+    (true, None) if matches.opt_present("lockstep") => NetMode::LockstepHost,
+    (true, None) => NetMode::Host,
+    (false, None) => NetMode::Offline,
+  };
+  let game_opt = GameOptions::new(matches.opt_present("windowed_mode"), matches.opt_present("orthographic"),
+                                  matches.opt_present("split_screen"), seed, replay, net);
   let mut window = gfx_app::WindowContext::new(game_opt);
-  gfx_app::init::run(&mut window);
+  gfx_app::init::run(&mut window, StartupProfile::new(start));
 }
@@ -0,0 +1,352 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, UdpSocket};
+use std::time::Instant;
+
+use crossbeam_channel as channel;
+use tracing::{info, warn};
+
+use crate::character::{controls::{CharacterControl, CharacterInputState}, Player};
+use crate::ecs::{Join, World, WorldExt};
+use crate::game::chat::{Chat, chat_text, ChatLine};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::constants::{NET_INPUT_HISTORY, NET_JOIN_INTERVAL, NET_PACKET_SIZE, NET_PORT, NET_SNAPSHOT_HISTORY, NET_TIMEOUT};
+use crate::game::state::{GameState, GameStates, StateTransition};
+use crate::net::lobby::Lobby;
+use crate::net::lockstep::{Lockstep, start_players};
+use crate::net::protocol::{join_message, Message};
+use crate::net::replication::NetSnapshot;
+use crate::shaders::Position;
+
+// The client's own character is player 0 of its world, so the camera and the HUD follow it, and
+// takes the index of the host's character in return
+pub fn local_index(index: usize, own: usize) -> usize {
+  if index == own {
+    0
+  } else if index == 0 {
+    own
+  } else {
+    index
+  }
+}
+
+// The movement each input caused locally. The host's state is behind by the inputs it hasn't
+// applied yet, their movement is added on top of it.
+#[derive(Default)]
+pub struct Prediction {
+  pending: VecDeque<(u64, Position)>,
+  // Where the own character stood after the last state or input
+  base: Option<Position>,
+}
+
+impl Prediction {
+  pub fn record(&mut self, input: u64, movement: Position) {
+    if let Some(base) = self.base {
+      self.pending.push_back((input, movement - base));
+      while self.pending.len() > NET_INPUT_HISTORY {
+        self.pending.pop_front();
+      }
+    }
+    self.base = Some(movement);
+  }
+
+  // The movement of the inputs after the one the host applied last, and the own character's
+  // movement goes on from the reconciled one
+  pub fn reconcile(&mut self, applied: u64, authoritative: Position) -> Position {
+    self.pending.retain(|(input, _)| *input > applied);
+    let replayed = self.pending.iter().fold(Position::origin(), |sum, (_, step)| sum + *step);
+    self.base = Some(authoritative + replayed);
+    replayed
+  }
+
+  pub fn clear(&mut self) {
+    self.pending.clear();
+    self.base = None;
+  }
+}
+
+// Movement is the only thing a client predicts, shooting and reloading wait for the host
+fn is_predicted(control: CharacterControl) -> bool {
+  matches!(control, CharacterControl::Left | CharacterControl::Right | CharacterControl::Up | CharacterControl::Down |
+                    CharacterControl::XMoveStop | CharacterControl::YMoveStop)
+}
+
+// What a client does when its host is lost in the middle of a match. The player of the lowest
+// index left hosts the run from the state it shows, with its player index and those of the others,
+// and the others join it with their index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Migration {
+  Host(usize, Vec<usize>),
+  Join(String, usize),
+}
+
+// The new host of the players the lost host shared, none when the client is alone
+pub fn successor(own: usize, peers: &[(usize, IpAddr)]) -> Option<Migration> {
+  let &(index, ip) = peers.iter().min_by_key(|(index, _)| *index)?;
+  if index == own {
+    let others = peers.iter().map(|(index, _)| *index).filter(|&index| index != own).collect::<Vec<_>>();
+    Some(Migration::Host(own, others))
+  } else {
+    Some(Migration::Join(format!("{}:{}", ip, NET_PORT), own))
+  }
+}
+
+// Shows the host's world. Its own controls go to the host and move its own character right away,
+// the other characters move when the host's state arrives.
+pub struct NetClient {
+  pub address: String,
+  socket: UdpSocket,
+  queue: channel::Receiver<CharacterControl>,
+  local: channel::Sender<CharacterControl>,
+  index: Option<usize>,
+  input: u64,
+  // Clicks since the last input, from the center of the window
+  clicks: Vec<Option<(f32, f32)>>,
+  prediction: Prediction,
+  frame: u64,
+  // States rebuilt from the deltas, the host may pick any of them as the next baseline
+  received: VecDeque<(u64, NetSnapshot)>,
+  last_seen: Instant,
+  last_join: Option<Instant>,
+  // Set when the host plays lockstep, with the seed and players of a match about to start
+  lockstep: Option<Lockstep>,
+  start: Option<(u64, Vec<usize>)>,
+  // The other clients as the host last shared them, the index to join a new host with and what
+  // the session does about a lost host
+  peers: Vec<(usize, IpAddr)>,
+  rejoin: Option<usize>,
+  pub migration: Option<Migration>,
+}
+
+impl NetClient {
+  pub fn connect(address: &str, rejoin: Option<usize>, queue: channel::Receiver<CharacterControl>,
+                 local: channel::Sender<CharacterControl>) -> io::Result<NetClient> {
+    let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, NET_PORT) };
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(&address)?;
+    socket.set_nonblocking(true)?;
+    info!("Joining {}", address);
+    Ok(NetClient {
+      address,
+      socket,
+      queue,
+      local,
+      index: None,
+      input: 0,
+      clicks: Vec::new(),
+      prediction: Prediction::default(),
+      frame: 0,
+      received: VecDeque::new(),
+      last_seen: Instant::now(),
+      last_join: None,
+      lockstep: None,
+      start: None,
+      peers: Vec::new(),
+      rejoin,
+      migration: None,
+    })
+  }
+
+  pub fn index(&self) -> Option<usize> {
+    self.index
+  }
+
+  pub fn is_lockstep(&self) -> bool {
+    self.lockstep.is_some()
+  }
+
+  pub fn lockstep(&mut self) -> Option<&mut Lockstep> {
+    self.lockstep.as_mut()
+  }
+
+  pub fn receive(&mut self, world: &mut World) {
+    let mut buffer = [0; NET_PACKET_SIZE];
+    let mut latest = None;
+    loop {
+      match self.socket.recv(&mut buffer) {
+        Ok(len) => match Message::decode(&buffer[..len]) {
+          Ok(Message::Welcome(index, is_lockstep)) if self.index.is_none() => {
+            info!("Joined as player {}", index + 1);
+            self.index = Some(index);
+            self.rejoin = None;
+            self.lockstep = if is_lockstep { Some(Lockstep::default()) } else { None };
+            self.prediction.clear();
+            self.last_seen = Instant::now();
+            world.write_resource::<Lobby>().local.index = index;
+          }
+          Ok(Message::Lobby(players)) if self.index.is_some() => {
+            self.last_seen = Instant::now();
+            world.write_resource::<Lobby>().players = players;
+          }
+          Ok(Message::Peers(peers)) if self.index.is_some() => self.peers = peers,
+          Ok(Message::Chat(player, text)) if self.index.is_some() => world.write_resource::<Chat>().receive(ChatLine::Said(player, chat_text(&text))),
+          // The run starts over on the next frame, the players are added to it then
+          Ok(Message::Start(seed, players)) if self.lockstep.as_ref().is_some_and(|l| !l.is_started) && self.start.is_none() => {
+            self.last_seen = Instant::now();
+            self.start = Some((seed, players));
+            let mut states = world.write_resource::<GameStates>();
+            states.request(StateTransition::Reset(GameState::Playing));
+            states.is_restarting = true;
+          }
+          Ok(Message::Turns(ack, turns, hash)) => if let Some(ref mut peer) = self.lockstep {
+            self.last_seen = Instant::now();
+            peer.acknowledge(ack);
+            for turn in turns {
+              peer.receive(turn);
+            }
+            if let Some((step, hash)) = hash {
+              if peer.check_hash(step, hash) {
+                warn!("Out of sync with the host at step {}", step);
+                world.write_resource::<GameEvents>().single_write(GameEvent::Toast("toast.desync"));
+              }
+            }
+          },
+          Ok(Message::Refused(reason)) => warn!("Join refused: {}", reason),
+          // Datagrams can arrive out of order, an older state than the shown one is dropped and
+          // so is a delta against a state the client no longer has
+          Ok(Message::State(delta)) if delta.frame > self.frame => {
+            let baseline = match delta.baseline {
+              0 => Some(NetSnapshot::default()),
+              frame => self.received.iter().find(|(f, _)| *f == frame).map(|(_, s)| s.clone()),
+            };
+            if let Some(baseline) = baseline {
+              let snapshot = baseline.apply(&delta);
+              self.frame = delta.frame;
+              self.last_seen = Instant::now();
+              self.received.push_back((delta.frame, snapshot.clone()));
+              while self.received.len() > NET_SNAPSHOT_HISTORY {
+                self.received.pop_front();
+              }
+              latest = Some((snapshot, delta.input, delta.score));
+            }
+            // The host started the match, or it was already running when the client joined
+            let mut states = world.write_resource::<GameStates>();
+            if states.current() == GameState::Lobby {
+              states.request(StateTransition::Reset(GameState::Playing));
+            }
+          }
+          Ok(_) => (),
+          Err(e) => warn!("Host message error {}", e),
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        // Nothing listens on the host's port yet, the join is repeated
+        Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused || e.kind() == io::ErrorKind::ConnectionReset => break,
+        Err(e) => {
+          warn!("Client receive error {}", e);
+          break;
+        }
+      }
+    }
+
+    if let Some(ref mut peer) = self.lockstep {
+      if world.read_resource::<GameStates>().current() == GameState::Playing {
+        if let Some((seed, players)) = self.start.take() {
+          start_players(world, seed, &players);
+          peer.start();
+        }
+      }
+      if peer.is_started {
+        peer.schedule();
+      }
+    }
+
+    if let (Some(own), Some((snapshot, input, score))) = (self.index, latest) {
+      let authoritative = snapshot.player_position(own);
+      let prediction = authoritative.map_or(Position::origin(), |p| self.prediction.reconcile(input, p));
+      snapshot.show(world, own, prediction);
+      world.insert(score);
+    }
+    let is_timed_out = self.last_seen.elapsed().as_secs_f64() * 1000.0 >= NET_TIMEOUT;
+    // A match goes on with a new host, in the lobby and after the run there is nothing to keep
+    if let (Some(own), true) = (self.index, is_timed_out) {
+      let is_running = world.read_resource::<GameStates>().contains(GameState::Playing);
+      if is_running && self.lockstep.is_none() {
+        self.migration = successor(own, &self.peers);
+        if self.migration.is_some() {
+          warn!("Host lost, moving to a new host");
+          return;
+        }
+      }
+    }
+    if self.rejoin.is_some() && self.index.is_none() && is_timed_out {
+      warn!("New host not found");
+      self.rejoin = None;
+      world.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Lobby));
+    }
+    if self.index.is_some() && is_timed_out {
+      warn!("Host lost");
+      self.index = None;
+      self.last_join = None;
+      self.frame = 0;
+      self.received.clear();
+      self.prediction.clear();
+      self.lockstep = None;
+      self.start = None;
+      world.write_resource::<Lobby>().players.clear();
+      world.write_resource::<GameStates>().request(StateTransition::Reset(GameState::Lobby));
+    }
+  }
+
+  pub fn push_click(&mut self, click: Option<(f32, f32)>) {
+    self.clicks.push(click);
+  }
+
+  pub fn send(&mut self, world: &World) {
+    let controls = self.queue.try_iter().collect::<Vec<_>>();
+    let clicks = std::mem::take(&mut self.clicks);
+    let chat = std::mem::take(&mut world.write_resource::<Chat>().outgoing);
+    if let Some(index) = self.index {
+      for text in chat {
+        self.send_message(&Message::Chat(index, text));
+      }
+    }
+    match (self.index, &self.lockstep) {
+      // The controls of the lobby's menu aren't meant for the character
+      (Some(index), _) if world.read_resource::<GameStates>().current() == GameState::Lobby => {
+        let local = world.read_resource::<Lobby>().local;
+        self.send_message(&Message::Ready(index, local.skin, local.is_ready));
+      }
+      // Inputs go out once the match started, the host keeps sending the start until then
+      (Some(index), Some(peer)) => if peer.is_started {
+        self.send_message(&Message::Steps(index, peer.turn_ack(), peer.unacknowledged(), peer.latest_hash()));
+      },
+      (Some(index), None) => {
+        if let Some(movement) = own_movement(world) {
+          self.prediction.record(self.input, movement);
+        }
+        self.input += 1;
+        for &control in controls.iter().filter(|&&c| is_predicted(c)) {
+          self.local.send(control).expect("Predicted character control update error");
+        }
+        self.send_message(&Message::Input(index, self.input, self.frame, controls, clicks));
+      }
+      (None, _) => match self.last_join {
+        Some(at) if at.elapsed().as_secs_f64() * 1000.0 < NET_JOIN_INTERVAL => (),
+        _ => {
+          self.last_join = Some(Instant::now());
+          self.send_message(&join_message(self.rejoin));
+        }
+      },
+    }
+  }
+
+  fn send_message(&self, message: &Message) {
+    if let Err(e) = self.socket.send(&message.encode()) {
+      warn!("Message send error {}", e);
+    }
+  }
+}
+
+fn own_movement(world: &World) -> Option<Position> {
+  (&world.read_storage::<Player>(), &world.read_storage::<CharacterInputState>()).join()
+    .find(|(p, _)| p.index == 0)
+    .map(|(_, ci)| ci.movement)
+}
+
+impl Drop for NetClient {
+  fn drop(&mut self) {
+    if let Some(index) = self.index {
+      self.send_message(&Message::Leave(index));
+    }
+  }
+}
@@ -0,0 +1,426 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Instant;
+
+use crossbeam_channel as channel;
+use tracing::{info, warn};
+
+use crate::character::{CharacterDrawable, controls::{CharacterControl, CharacterInputState}, Player};
+use crate::critter::palette::CritterPalette;
+use crate::ecs::{self, Join, World, WorldExt};
+use crate::game::chat::{Chat, chat_text, ChatLine};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::GameRng;
+use crate::game::constants::{GAME_VERSION, MAX_PLAYERS, NET_ADVERTISE_INTERVAL, NET_DISCOVERY_PORT, NET_PACKET_SIZE, NET_PEERS_INTERVAL,
+                             NET_SNAPSHOT_HISTORY, NET_TIMEOUT};
+use crate::game::profiles::Profiles;
+use crate::game::score::Score;
+use crate::game::spawner::{self, WaveState};
+use crate::game::state::{GameState, GameStates};
+use crate::net::lobby::{Lobby, LobbyPlayer};
+use crate::net::lockstep::{Lockstep, start_players, TurnCollector};
+use crate::net::protocol::Message;
+use crate::net::replication::{NetSnapshot, StateDelta};
+use crate::net::press_click;
+use crate::shaders::Position;
+
+struct RemotePlayer {
+  addr: SocketAddr,
+  index: usize,
+  last_seen: Instant,
+  // The latest state the client got and the ones sent since, deltas are taken against it
+  acked: u64,
+  sent: VecDeque<(u64, NetSnapshot)>,
+  // The latest input applied, the client replays its own movement since then
+  input: u64,
+  skin: CritterPalette,
+  is_ready: bool,
+  // Lockstep, the last turn the client got and whether it started the match
+  turn_ack: u64,
+  is_started: bool,
+}
+
+impl RemotePlayer {
+  fn new(addr: SocketAddr, index: usize) -> RemotePlayer {
+    RemotePlayer {
+      addr,
+      index,
+      last_seen: Instant::now(),
+      acked: 0,
+      sent: VecDeque::new(),
+      input: 0,
+      skin: CritterPalette::Original,
+      is_ready: false,
+      turn_ack: 0,
+      is_started: false,
+    }
+  }
+}
+
+// Runs the only simulation of the session. Remote players are characters like the split screen
+// second player, their controls reach the character systems of their index through the channels.
+pub struct NetHost {
+  socket: UdpSocket,
+  players: Vec<RemotePlayer>,
+  controls: Vec<channel::Sender<CharacterControl>>,
+  frame: u64,
+  // Broadcasts the adverts the session browsers of the LAN list
+  advert: Option<UdpSocket>,
+  last_advert: Option<Instant>,
+  // A lockstep host sends turns instead of states and runs them like the clients do
+  pub is_lockstep: bool,
+  peer: Lockstep,
+  collector: TurnCollector,
+  // The seed and the players of the lockstep run
+  start: Option<(u64, Vec<usize>)>,
+  last_peers: Option<Instant>,
+  // After taking over from a lost host, the players whose characters wait for them to join again
+  rejoining: Vec<usize>,
+  took_over: Option<Instant>,
+}
+
+pub fn player_entity(world: &World, index: usize) -> Option<ecs::Entity> {
+  (&world.entities(), &world.read_storage::<Player>()).join()
+    .find(|(_, p)| p.index == index)
+    .map(|(e, _)| e)
+}
+
+impl NetHost {
+  pub fn bind(port: u16, controls: Vec<channel::Sender<CharacterControl>>, is_lockstep: bool) -> io::Result<NetHost> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_nonblocking(true)?;
+    info!("Hosting on port {}", port);
+    // A LAN without broadcast can still join by address
+    let advert = UdpSocket::bind(("0.0.0.0", 0))
+      .and_then(|advert| advert.set_broadcast(true).map(|_| advert))
+      .map_err(|e| warn!("Session advert error {}", e))
+      .ok();
+    Ok(NetHost {
+      socket,
+      players: Vec::new(),
+      controls,
+      frame: 0,
+      advert,
+      last_advert: None,
+      is_lockstep,
+      peer: Lockstep::default(),
+      collector: TurnCollector::default(),
+      start: None,
+      last_peers: None,
+      rejoining: Vec::new(),
+      took_over: None,
+    })
+  }
+
+  pub fn lockstep(&mut self) -> Option<&mut Lockstep> {
+    if self.is_lockstep { Some(&mut self.peer) } else { None }
+  }
+
+  // Goes on with the run a client showed when its host was lost. The lost host's character is the
+  // one the client showed in place of its own, the other clients join again with their index.
+  pub fn take_over(&mut self, world: &mut World, own: usize, others: Vec<usize>) {
+    if let Some(entity) = player_entity(world, own) {
+      world.delete_entity(entity).expect("Player delete error");
+    }
+    let wave = world.read_resource::<Score>().wave;
+    world.insert(WaveState::resume(wave));
+    let mut lobby = world.write_resource::<Lobby>();
+    lobby.is_host = true;
+    lobby.local.index = 0;
+    self.rejoining = others;
+    self.took_over = Some(Instant::now());
+  }
+
+  pub fn receive(&mut self, world: &mut World) {
+    let mut buffer = [0; NET_PACKET_SIZE];
+    loop {
+      match self.socket.recv_from(&mut buffer) {
+        Ok((len, addr)) => match Message::decode(&buffer[..len]) {
+          Ok(message) => self.handle(world, addr, message),
+          Err(e) => warn!("Message from {} error {}", addr, e),
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        // A client that went away shows up as a reset connection on some platforms
+        Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => (),
+        Err(e) => {
+          warn!("Host receive error {}", e);
+          break;
+        }
+      }
+    }
+
+    let timed_out = self.players.iter()
+      .filter(|p| p.last_seen.elapsed().as_secs_f64() * 1000.0 >= NET_TIMEOUT)
+      .map(|p| p.index)
+      .collect::<Vec<_>>();
+    for index in timed_out {
+      warn!("Player {} timed out", index + 1);
+      self.leave(world, index);
+    }
+
+    // Characters of players who didn't join again are removed
+    if self.took_over.is_some_and(|at| at.elapsed().as_secs_f64() * 1000.0 >= NET_TIMEOUT) {
+      for index in std::mem::take(&mut self.rejoining) {
+        warn!("Player {} didn't return", index + 1);
+        self.leave(world, index);
+      }
+      self.took_over = None;
+    }
+
+    if self.is_lockstep {
+      self.run_lockstep(world);
+    }
+
+    // A restarted or loaded run starts with the local players only
+    for p in &self.players {
+      if player_entity(world, p.index).is_none() {
+        spawner::spawn_player(world.create_entity(), p.index);
+      }
+    }
+
+    let mut lobby = world.write_resource::<Lobby>();
+    let local = LobbyPlayer { index: 0, is_ready: true, ..lobby.local };
+    lobby.players = Some(local).into_iter()
+      .chain(self.players.iter().map(|p| LobbyPlayer { index: p.index, skin: p.skin, is_ready: p.is_ready }))
+      .collect();
+    for (p, cd) in (&world.read_storage::<Player>(), &mut world.write_storage::<CharacterDrawable>()).join() {
+      if let Some(player) = lobby.players.iter().find(|player| player.index == p.index) {
+        cd.skin = player.skin;
+      }
+    }
+  }
+
+  fn handle(&mut self, world: &mut World, addr: SocketAddr, message: Message) {
+    if let Some(p) = self.players.iter_mut().find(|p| p.addr == addr) {
+      p.last_seen = Instant::now();
+    }
+    match message {
+      Message::Join(version, rejoin) => {
+        let reply = self.join(world, addr, &version, rejoin);
+        self.send_to(&reply, addr);
+      }
+      Message::Input(index, input, ack, controls, clicks) => {
+        // An input that arrives after a newer one is dropped, the client already predicted past it
+        if let Some(p) = self.players.iter_mut().find(|p| p.addr == addr && p.index == index && input > p.input) {
+          p.input = input;
+          p.acked = p.acked.max(ack);
+          for control in controls {
+            self.controls[index].send(control).expect("Remote character control update error");
+          }
+          for click in clicks.into_iter().flatten() {
+            press_click(world, index, click);
+          }
+        }
+      }
+      Message::Steps(index, turn_ack, inputs, hash) if self.is_lockstep => {
+        if let Some(p) = self.players.iter_mut().find(|p| p.addr == addr && p.index == index) {
+          p.turn_ack = p.turn_ack.max(turn_ack);
+          p.is_started = true;
+          for (step, input) in inputs {
+            self.collector.add(index, step, input);
+          }
+          if let Some((step, hash)) = hash {
+            if self.peer.check_hash(step, hash) {
+              warn!("Player {} is out of sync at step {}", index + 1, step);
+              world.write_resource::<GameEvents>().single_write(GameEvent::Toast("toast.desync"));
+            }
+          }
+        }
+      }
+      Message::Ready(index, skin, is_ready) => {
+        if let Some(p) = self.players.iter_mut().find(|p| p.addr == addr && p.index == index) {
+          p.skin = skin;
+          p.is_ready = is_ready;
+        }
+      }
+      // The sender already shows its own line
+      Message::Chat(index, text) => {
+        if self.players.iter().any(|p| p.addr == addr && p.index == index) {
+          let text = chat_text(&text);
+          let relay = Message::Chat(index, text.clone());
+          for p in self.players.iter().filter(|p| p.addr != addr) {
+            self.send_to(&relay, p.addr);
+          }
+          world.write_resource::<Chat>().receive(ChatLine::Said(index, text));
+        }
+      }
+      Message::Leave(index) => {
+        if self.players.iter().any(|p| p.addr == addr && p.index == index) {
+          info!("Player {} left", index + 1);
+          self.leave(world, index);
+        }
+      }
+      _ => warn!("Unexpected message from {}", addr),
+    }
+  }
+
+  // A repeated join of a known client gets its welcome again, the first one may have been lost
+  fn join(&mut self, world: &mut World, addr: SocketAddr, version: &str, rejoin: Option<usize>) -> Message {
+    if version != GAME_VERSION {
+      return Message::Refused(format!("Host runs {}", GAME_VERSION));
+    }
+    if let Some(p) = self.players.iter().find(|p| p.addr == addr) {
+      return Message::Welcome(p.index, self.is_lockstep);
+    }
+    // A player of a lost host takes its character back
+    if let Some(index) = rejoin.filter(|index| self.rejoining.contains(index)) {
+      self.rejoining.retain(|&idx| idx != index);
+      self.players.push(RemotePlayer::new(addr, index));
+      info!("Player {} returned from {}", index + 1, addr);
+      return Message::Welcome(index, self.is_lockstep);
+    }
+    // A lockstep client can't catch up with a running match
+    if self.is_lockstep && world.read_resource::<GameStates>().current() != GameState::Lobby {
+      return Message::Refused("Match already started".to_string());
+    }
+    let taken = world.read_storage::<Player>().join().map(|p| p.index).collect::<Vec<_>>();
+    match (1..MAX_PLAYERS.min(self.controls.len())).find(|idx| !taken.contains(idx)) {
+      Some(index) => {
+        spawner::spawn_player(world.create_entity(), index);
+        self.players.push(RemotePlayer::new(addr, index));
+        info!("Player {} joined from {}", index + 1, addr);
+        Message::Welcome(index, self.is_lockstep)
+      }
+      None => Message::Refused("Session is full".to_string()),
+    }
+  }
+
+  // The character of a player who leaves a lockstep match stays, every peer has it
+  fn leave(&mut self, world: &mut World, index: usize) {
+    self.players.retain(|p| p.index != index);
+    if self.peer.is_started {
+      return;
+    }
+    if let Some(entity) = player_entity(world, index) {
+      world.delete_entity(entity).expect("Player delete error");
+    }
+  }
+
+  pub fn send(&mut self, world: &World) {
+    self.advertise(world);
+    for text in std::mem::take(&mut world.write_resource::<Chat>().outgoing) {
+      let line = Message::Chat(0, text);
+      for p in &self.players {
+        self.send_to(&line, p.addr);
+      }
+    }
+    if self.players.is_empty() {
+      return;
+    }
+    if world.read_resource::<GameStates>().current() == GameState::Lobby {
+      let lobby = Message::Lobby(world.read_resource::<Lobby>().players.clone());
+      for p in &self.players {
+        self.send_to(&lobby, p.addr);
+      }
+      return;
+    }
+    if self.is_lockstep {
+      self.send_turns();
+      return;
+    }
+    self.share_peers();
+    self.frame += 1;
+    let score = world.read_resource::<Score>();
+    for p in &mut self.players {
+      let movement = player_entity(world, p.index)
+        .and_then(|e| world.read_storage::<CharacterInputState>().get(e).map(|ci| ci.movement))
+        .unwrap_or_else(Position::origin);
+      let snapshot = NetSnapshot::capture(world, movement);
+      // A client that is too far behind gets the full state again
+      let acked = p.acked;
+      let baseline = p.sent.iter().find(|(frame, _)| *frame == acked).map(|(frame, s)| (*frame, s));
+      let delta = StateDelta { input: p.input, ..snapshot.delta(self.frame, baseline, &score) };
+      let bytes = Message::State(delta).encode();
+      if let Err(e) = self.socket.send_to(&bytes, p.addr) {
+        warn!("State send to {} error {}", p.addr, e);
+      }
+      p.sent.retain(|(frame, _)| *frame >= acked);
+      p.sent.push_back((self.frame, snapshot));
+      while p.sent.len() > NET_SNAPSHOT_HISTORY {
+        p.sent.pop_front();
+      }
+    }
+  }
+
+  // The match starts when the host leaves the lobby, every peer seeds the run the same way and
+  // waits for the turns of all players that were in the lobby
+  fn run_lockstep(&mut self, world: &mut World) {
+    let state = world.read_resource::<GameStates>().current();
+    if state == GameState::Lobby {
+      self.peer = Lockstep::default();
+      self.start = None;
+      return;
+    }
+    if !self.peer.is_started && state == GameState::Playing {
+      // Drawn from the host's own seed, so a seeded host starts the same match every time
+      let seed = world.write_resource::<GameRng>().next_seed();
+      let mut players = self.players.iter().map(|p| p.index).collect::<Vec<_>>();
+      players.sort_unstable();
+      start_players(world, seed, &players);
+      self.peer.start();
+      self.collector = TurnCollector::default();
+      for p in &mut self.players {
+        p.turn_ack = 0;
+        p.is_started = false;
+      }
+      self.start = Some((seed, players));
+    }
+    if !self.peer.is_started {
+      return;
+    }
+    for (step, input) in self.peer.schedule() {
+      self.collector.add(0, step, input);
+      self.peer.acknowledge(step);
+    }
+    let players = Some(0).into_iter().chain(self.players.iter().map(|p| p.index)).collect::<Vec<_>>();
+    for turn in self.collector.finalize(&players) {
+      self.peer.receive(turn);
+    }
+    let acked = self.players.iter().map(|p| p.turn_ack).min().unwrap_or_else(|| self.peer.step());
+    self.collector.forget(acked.min(self.peer.step()));
+  }
+
+  fn send_turns(&self) {
+    for p in &self.players {
+      if let (Some((seed, players)), false) = (&self.start, p.is_started) {
+        self.send_to(&Message::Start(*seed, players.clone()), p.addr);
+      }
+      let turns = Message::Turns(self.collector.input_ack(p.index), self.collector.turns_after(p.turn_ack), self.peer.latest_hash());
+      self.send_to(&turns, p.addr);
+    }
+  }
+
+  // Every client learns where the others are, the one with the lowest index hosts if this host is lost
+  fn share_peers(&mut self) {
+    if self.last_peers.is_some_and(|at| at.elapsed().as_secs_f64() * 1000.0 < NET_PEERS_INTERVAL) {
+      return;
+    }
+    self.last_peers = Some(Instant::now());
+    let peers = Message::Peers(self.players.iter().map(|p| (p.index, p.addr.ip())).collect());
+    for p in &self.players {
+      self.send_to(&peers, p.addr);
+    }
+  }
+
+  fn advertise(&mut self, world: &World) {
+    match (&self.advert, self.last_advert) {
+      (None, _) => return,
+      (Some(_), Some(at)) if at.elapsed().as_secs_f64() * 1000.0 < NET_ADVERTISE_INTERVAL => return,
+      _ => (),
+    }
+    self.last_advert = Some(Instant::now());
+    let advert = Message::Advert(GAME_VERSION.to_string(), world.read_resource::<Profiles>().active_name().to_string(), self.players.len() + 1);
+    if let Some(ref socket) = self.advert {
+      if let Err(e) = socket.send_to(&advert.encode(), ("255.255.255.255", NET_DISCOVERY_PORT)) {
+        warn!("Session advert error {}", e);
+      }
+    }
+  }
+
+  fn send_to(&self, message: &Message, addr: SocketAddr) {
+    if let Err(e) = self.socket.send_to(&message.encode(), addr) {
+      warn!("Message send to {} error {}", addr, e);
+    }
+  }
+}
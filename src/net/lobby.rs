@@ -0,0 +1,128 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Instant;
+use tracing::warn;
+
+use crate::critter::palette::CritterPalette;
+use crate::game::constants::{GAME_VERSION, NET_DISCOVERY_PORT, NET_PACKET_SIZE, NET_PORT, NET_SESSIONS, NET_TIMEOUT};
+use crate::game::localization::Localization;
+use crate::net::protocol::Message;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LobbyPlayer {
+  pub index: usize,
+  pub skin: CritterPalette,
+  pub is_ready: bool,
+}
+
+// A host found on the LAN
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+  pub address: SocketAddr,
+  pub name: String,
+  pub players: usize,
+  last_seen: Instant,
+}
+
+// Kept as a resource, the menu shows it and changes the local player's choices, the session
+// fills in the rest
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lobby {
+  pub is_host: bool,
+  // The index is the one the host gave, the first player's on the host
+  pub local: LobbyPlayer,
+  pub players: Vec<LobbyPlayer>,
+  pub sessions: Vec<Session>,
+}
+
+impl Lobby {
+  // A new session keeps the picked skin, everyone readies up again
+  pub fn reset(&mut self, is_host: bool) {
+    self.is_host = is_host;
+    self.local = LobbyPlayer { index: 0, is_ready: false, ..self.local };
+    self.players.clear();
+  }
+
+  // The host is the one who starts, so only the players who joined have to be ready
+  pub fn can_start(&self) -> bool {
+    self.is_host && self.players.iter().all(|p| p.index == 0 || p.is_ready)
+  }
+
+  pub fn lines(&self, strings: &Localization) -> Vec<String> {
+    if self.players.is_empty() {
+      return vec![strings.get("lobby.joining").to_string()];
+    }
+    self.players.iter()
+      .map(|p| {
+        let state = if p.index == 0 { "lobby.host" } else if p.is_ready { "lobby.ready" } else { "lobby.not_ready" };
+        let line = strings.format("lobby.player", &[("player", (p.index + 1).to_string()),
+          ("skin", strings.get(&format!("skin.{}", p.skin.name())).to_string()), ("state", strings.get(state).to_string())]);
+        if p.index == self.local.index { strings.format("lobby.you", &[("player", line)]) } else { line }
+      })
+      .collect()
+  }
+}
+
+// Listens for the adverts of hosts while the session list is open
+pub struct SessionBrowser {
+  socket: Option<UdpSocket>,
+  is_open: bool,
+}
+
+impl SessionBrowser {
+  pub fn new() -> SessionBrowser {
+    SessionBrowser {
+      socket: None,
+      is_open: false,
+    }
+  }
+
+  fn open(&mut self) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", NET_DISCOVERY_PORT))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+  }
+
+  pub fn receive(&mut self, lobby: &mut Lobby) {
+    if !self.is_open {
+      self.is_open = true;
+      self.socket = self.open().map_err(|e| warn!("Session browser on port {} error {}", NET_DISCOVERY_PORT, e)).ok();
+    }
+    let socket = match self.socket {
+      Some(ref socket) => socket,
+      None => return,
+    };
+    let mut buffer = [0; NET_PACKET_SIZE];
+    loop {
+      match socket.recv_from(&mut buffer) {
+        // Hosts of another version are left out, they would refuse the join
+        Ok((len, addr)) => if let Ok(Message::Advert(version, name, players)) = Message::decode(&buffer[..len]) {
+          if version == GAME_VERSION {
+            let address = SocketAddr::new(addr.ip(), NET_PORT);
+            lobby.sessions.retain(|s| s.address != address);
+            lobby.sessions.push(Session { address, name, players, last_seen: Instant::now() });
+          }
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        Err(e) => {
+          warn!("Session browser receive error {}", e);
+          break;
+        }
+      }
+    }
+    lobby.sessions.retain(|s| s.last_seen.elapsed().as_secs_f64() * 1000.0 < NET_TIMEOUT);
+    lobby.sessions.sort_by_key(|s| s.address);
+    lobby.sessions.truncate(NET_SESSIONS);
+  }
+
+  pub fn close(&mut self) {
+    self.socket = None;
+    self.is_open = false;
+  }
+}
+
+impl Default for SessionBrowser {
+  fn default() -> SessionBrowser {
+    SessionBrowser::new()
+  }
+}
@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::character::{controls::{CharacterControl, CharacterInputState}, Player};
+use crate::ecs::{Join, World, WorldExt};
+use crate::game::constants::{LOCKSTEP_DELAY, LOCKSTEP_HASHES, LOCKSTEP_WINDOW};
+use crate::game::GameRng;
+use crate::game::health::Health;
+use crate::game::score::Score;
+use crate::game::spawner;
+use crate::net::host::player_entity;
+use crate::zombie::ZombieDrawable;
+
+// What a player did in one simulation step. Clicks are the first player's mouse, measured from the
+// center of the window so they aim the same way in a window of any size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepInput {
+  pub controls: Vec<CharacterControl>,
+  pub clicks: Vec<Option<(f32, f32)>>,
+}
+
+impl StepInput {
+  pub fn is_empty(&self) -> bool {
+    self.controls.is_empty() && self.clicks.is_empty()
+  }
+}
+
+// The inputs of all players for one step, every peer runs the step with the same turn
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Turn {
+  pub step: u64,
+  pub inputs: Vec<(usize, StepInput)>,
+}
+
+// A peer of a lockstep match, the host is one too. Input is given a step a few steps ahead and the
+// simulation only runs the steps whose turn came back from the host.
+#[derive(Default)]
+pub struct Lockstep {
+  pub is_started: bool,
+  // The last step simulated
+  step: u64,
+  // Input of the frame that has no step yet
+  pending: StepInput,
+  // Own inputs by step, sent again until the host has them
+  inputs: VecDeque<(u64, StepInput)>,
+  scheduled: u64,
+  turns: BTreeMap<u64, Turn>,
+  // State hashes of the own simulation by step
+  hashes: VecDeque<(u64, u64)>,
+  pub is_desynced: bool,
+}
+
+impl Lockstep {
+  pub fn start(&mut self) {
+    *self = Lockstep { is_started: true, ..Lockstep::default() };
+  }
+
+  pub fn step(&self) -> u64 {
+    self.step
+  }
+
+  pub fn push_control(&mut self, control: CharacterControl) {
+    self.pending.controls.push(control);
+  }
+
+  pub fn push_click(&mut self, click: Option<(f32, f32)>) {
+    self.pending.clicks.push(click);
+  }
+
+  // The frame's input goes to the step LOCKSTEP_DELAY ahead, the steps skipped since the last
+  // frame get none, and the new inputs are returned
+  pub fn schedule(&mut self) -> Vec<(u64, StepInput)> {
+    let target = self.step + LOCKSTEP_DELAY;
+    let mut scheduled = Vec::new();
+    if target > self.scheduled {
+      scheduled.push((self.scheduled + 1, std::mem::take(&mut self.pending)));
+      scheduled.extend((self.scheduled + 2..=target).map(|step| (step, StepInput::default())));
+      self.scheduled = target;
+    }
+    self.inputs.extend(scheduled.iter().cloned());
+    scheduled
+  }
+
+  // Every input the host hasn't acknowledged, the oldest are dropped when there are too many
+  pub fn unacknowledged(&self) -> Vec<(u64, StepInput)> {
+    self.inputs.iter().take(LOCKSTEP_WINDOW).cloned().collect()
+  }
+
+  pub fn acknowledge(&mut self, step: u64) {
+    self.inputs.retain(|(s, _)| *s > step);
+  }
+
+  pub fn receive(&mut self, turn: Turn) {
+    if turn.step > self.step {
+      self.turns.entry(turn.step).or_insert(turn);
+    }
+  }
+
+  // The last step the turns are complete up to
+  pub fn turn_ack(&self) -> u64 {
+    (self.step + 1..).find(|step| !self.turns.contains_key(step)).map_or(self.step, |step| step - 1)
+  }
+
+  pub fn ready_steps(&self) -> u64 {
+    self.turn_ack() - self.step
+  }
+
+  pub fn next_turn(&mut self) -> Option<Turn> {
+    let turn = self.turns.remove(&(self.step + 1))?;
+    self.step = turn.step;
+    Some(turn)
+  }
+
+  pub fn record_hash(&mut self, step: u64, hash: u64) {
+    self.hashes.push_back((step, hash));
+    while self.hashes.len() > LOCKSTEP_HASHES {
+      self.hashes.pop_front();
+    }
+  }
+
+  pub fn latest_hash(&self) -> Option<(u64, u64)> {
+    self.hashes.back().cloned()
+  }
+
+  // True for the first hash that differs from the own one of the same step
+  pub fn check_hash(&mut self, step: u64, hash: u64) -> bool {
+    let is_different = self.hashes.iter().any(|&(s, h)| s == step && h != hash);
+    if is_different && !self.is_desynced {
+      self.is_desynced = true;
+      return true;
+    }
+    false
+  }
+}
+
+// The host's side of the turns, the inputs of each step wait until every player has sent theirs
+#[derive(Default)]
+pub struct TurnCollector {
+  inputs: BTreeMap<u64, Vec<(usize, StepInput)>>,
+  finalized: u64,
+  // Turns handed out, kept until every client has them
+  turns: VecDeque<Turn>,
+}
+
+impl TurnCollector {
+  pub fn add(&mut self, player: usize, step: u64, input: StepInput) {
+    if step > self.finalized {
+      let inputs = self.inputs.entry(step).or_default();
+      if !inputs.iter().any(|(p, _)| *p == player) {
+        inputs.push((player, input));
+      }
+    }
+  }
+
+  // Turns for the steps that all players sent their input for. A player who left isn't waited for.
+  pub fn finalize(&mut self, players: &[usize]) -> Vec<Turn> {
+    let mut turns = Vec::new();
+    loop {
+      let step = self.finalized + 1;
+      let is_complete = self.inputs.get(&step)
+        .is_some_and(|inputs| players.iter().all(|player| inputs.iter().any(|(p, _)| p == player)));
+      if !is_complete {
+        break;
+      }
+      let mut inputs = self.inputs.remove(&step).unwrap_or_default();
+      inputs.retain(|(p, input)| players.contains(p) && !input.is_empty());
+      inputs.sort_by_key(|(p, _)| *p);
+      let turn = Turn { step, inputs };
+      self.finalized = step;
+      self.turns.push_back(turn.clone());
+      turns.push(turn);
+    }
+    turns
+  }
+
+  // The last step the player's inputs are complete up to
+  pub fn input_ack(&self, player: usize) -> u64 {
+    (self.finalized + 1..)
+      .find(|step| !self.inputs.get(step).is_some_and(|inputs| inputs.iter().any(|(p, _)| *p == player)))
+      .map_or(self.finalized, |step| step - 1)
+  }
+
+  pub fn turns_after(&self, step: u64) -> Vec<Turn> {
+    self.turns.iter().filter(|turn| turn.step > step).take(LOCKSTEP_WINDOW).cloned().collect()
+  }
+
+  // Turns every client acknowledged aren't sent again
+  pub fn forget(&mut self, step: u64) {
+    while self.turns.front().is_some_and(|turn| turn.step <= step) {
+      self.turns.pop_front();
+    }
+  }
+}
+
+// A new lockstep run on a peer, after the world was rebuilt with the first player. The other
+// players are added in the same order everywhere.
+pub fn start_players(world: &mut World, seed: u64, players: &[usize]) {
+  world.insert(GameRng::new(Some(seed)));
+  for &index in players {
+    if player_entity(world, index).is_none() {
+      spawner::spawn_player(world.create_entity(), index);
+    }
+  }
+}
+
+// Sums up what the simulation decides, peers that agree on it are still in sync. The zombie ids are
+// left out, they count the zombies a peer ever made.
+pub fn state_hash(world: &World) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  let mut players = (&world.read_storage::<Player>(), &world.read_storage::<CharacterInputState>(), &world.read_storage::<Health>()).join()
+    .map(|(p, ci, h)| (p.index, ci.movement.x().to_bits(), ci.movement.y().to_bits(), h.current.to_bits()))
+    .collect::<Vec<_>>();
+  players.sort_unstable();
+  players.hash(&mut hasher);
+  // Entities are numbered apart on every peer, the spawn order is not
+  let mut zombies = world.read_storage::<ZombieDrawable>().join()
+    .map(|z| (z.spawn_order, z.transform.position.x().to_bits(), z.transform.position.y().to_bits(), z.health.current.to_bits()))
+    .collect::<Vec<_>>();
+  zombies.sort_unstable();
+  zombies.hash(&mut hasher);
+  let score = world.read_resource::<Score>();
+  (score.kills, score.score, score.wave).hash(&mut hasher);
+  hasher.finish()
+}
@@ -0,0 +1,261 @@
+use cgmath::Point2;
+use crossbeam_channel as channel;
+use tracing::warn;
+
+use crate::character::controls::CharacterControl;
+use crate::ecs::{World, WorldExt};
+use crate::game::chat::Chat;
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::constants::{LOCKSTEP_HASH_INTERVAL, NET_PORT};
+use crate::game::input_buffer::{BufferedAction, InputBuffer};
+use crate::gfx_app::mouse_controls::MouseControl;
+use crate::graphics::dimensions::Dimensions;
+use crate::net::client::{Migration, NetClient};
+use crate::net::host::NetHost;
+use crate::net::lockstep::{Lockstep, state_hash};
+
+pub mod client;
+pub mod host;
+pub mod lobby;
+pub mod lockstep;
+pub mod protocol;
+pub mod replication;
+
+type MouseEvent = (MouseControl, Option<(f64, f64)>);
+
+// Kept as a resource, systems that only the host may run check it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum NetMode {
+  #[default]
+  Offline,
+  Host,
+  // Host address, the port defaults to NET_PORT
+  Client(String),
+  // Every peer runs the simulation on the inputs of all players
+  LockstepHost,
+  LockstepClient(String),
+}
+
+impl NetMode {
+  // A client that shows the host's world instead of running the simulation
+  pub fn is_client(&self) -> bool {
+    matches!(self, NetMode::Client(_))
+  }
+
+  pub fn is_host(&self) -> bool {
+    matches!(self, NetMode::Host | NetMode::LockstepHost)
+  }
+}
+
+// The player the view and the HUD follow. A lockstep client keeps the host's player indices, its
+// own character isn't the first one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LocalPlayer(pub usize);
+
+// A click of a player on another machine, taken from the center of its window, fires the player's
+// character the same way the local mouse does
+pub fn press_click(world: &World, player: usize, (x, y): (f32, f32)) {
+  let dim = world.read_resource::<Dimensions>();
+  let click = Point2::new(dim.window_width / 2.0 + x, dim.window_height / 2.0 + y);
+  world.write_resource::<InputBuffer>().press(player, BufferedAction::Fire(click));
+}
+
+enum NetLink {
+  Offline,
+  Host(NetHost),
+  Client(NetClient),
+}
+
+fn link_lockstep(link: &mut NetLink) -> Option<&mut Lockstep> {
+  match link {
+    NetLink::Offline => None,
+    NetLink::Host(host) => host.lockstep(),
+    NetLink::Client(client) => client.lockstep(),
+  }
+}
+
+// LAN co-op over UDP, polled by the game loop around the simulation steps of a frame. The local
+// keyboard drives the first character system, or the host's on a client, and the mouse is the
+// first player's. In lockstep both go into the turns.
+pub struct NetSession {
+  link: NetLink,
+  keyboard: channel::Receiver<CharacterControl>,
+  mouse: channel::Receiver<MouseEvent>,
+  // The control channels of the character systems by player index and of the mouse system
+  controls: Vec<channel::Sender<CharacterControl>>,
+  mouse_control: channel::Sender<MouseEvent>,
+}
+
+impl NetSession {
+  pub fn new(mode: &NetMode, controls: Vec<channel::Sender<CharacterControl>>, mouse_control: channel::Sender<MouseEvent>,
+             keyboard: channel::Receiver<CharacterControl>, mouse: channel::Receiver<MouseEvent>) -> NetSession {
+    let mut session = NetSession { link: NetLink::Offline, keyboard, mouse, controls, mouse_control };
+    session.switch(mode);
+    session
+  }
+
+  // The old session is closed first, so a new host can take its port. A session that can't start
+  // is reported and the game goes on offline.
+  pub fn switch(&mut self, mode: &NetMode) {
+    self.link = NetLink::Offline;
+    self.link = match mode {
+      NetMode::Offline => NetLink::Offline,
+      NetMode::Host | NetMode::LockstepHost => match NetHost::bind(NET_PORT, self.controls.clone(), *mode == NetMode::LockstepHost) {
+        Ok(host) => NetLink::Host(host),
+        Err(e) => {
+          warn!("Host on port {} error {}", NET_PORT, e);
+          NetLink::Offline
+        }
+      },
+      // The client predicts its movement with the first character system. Whether it plays
+      // lockstep is up to the host.
+      NetMode::Client(address) | NetMode::LockstepClient(address) => match NetClient::connect(address, None, self.keyboard.clone(), self.controls[0].clone()) {
+        Ok(client) => NetLink::Client(client),
+        Err(e) => {
+          warn!("Join {} error {}", address, e);
+          NetLink::Offline
+        }
+      },
+    };
+  }
+
+  // The mode the session runs in, offline when it couldn't start
+  pub fn mode(&self) -> NetMode {
+    match self.link {
+      NetLink::Offline => NetMode::Offline,
+      NetLink::Host(ref host) if host.is_lockstep => NetMode::LockstepHost,
+      NetLink::Host(_) => NetMode::Host,
+      NetLink::Client(ref client) if client.is_lockstep() => NetMode::LockstepClient(client.address.clone()),
+      NetLink::Client(ref client) => NetMode::Client(client.address.clone()),
+    }
+  }
+
+  pub fn local_player(&self) -> LocalPlayer {
+    match self.link {
+      NetLink::Client(ref client) if client.is_lockstep() => LocalPlayer(client.index().unwrap_or(0)),
+      _ => LocalPlayer(0),
+    }
+  }
+
+  pub fn receive(&mut self, world: &mut World) {
+    let is_client = self.mode().is_client();
+    let center = {
+      let dim = world.read_resource::<Dimensions>();
+      (dim.window_width / 2.0, dim.window_height / 2.0)
+    };
+    let from_center = |value: Option<(f64, f64)>| value.map(|(x, y)| (x as f32 - center.0, y as f32 - center.1));
+    match link_lockstep(&mut self.link) {
+      // Clicks are taken from the center of the window, they come back with the turn of the step
+      Some(peer) => {
+        for control in self.keyboard.try_iter() {
+          peer.push_control(control);
+        }
+        for (control, value) in self.mouse.try_iter() {
+          match control {
+            MouseControl::LeftClick => peer.push_click(from_center(value)),
+            _ => self.mouse_control.send((control, value)).expect("Mouse control update error"),
+          }
+        }
+      }
+      None => {
+        // A client fires right away like it moves, the host fires its character with the click too
+        for (control, value) in self.mouse.try_iter() {
+          if let (MouseControl::LeftClick, NetLink::Client(ref mut client)) = (&control, &mut self.link) {
+            client.push_click(from_center(value));
+          }
+          self.mouse_control.send((control, value)).expect("Mouse control update error");
+        }
+        if !is_client {
+          for control in self.keyboard.try_iter() {
+            self.controls[0].send(control).expect("Character control update error");
+          }
+        }
+      }
+    }
+    match self.link {
+      NetLink::Offline => (),
+      NetLink::Host(ref mut host) => host.receive(world),
+      NetLink::Client(ref mut client) => client.receive(world),
+    }
+    let migration = match self.link {
+      NetLink::Client(ref mut client) => client.migration.take(),
+      _ => None,
+    };
+    if let Some(migration) = migration {
+      self.migrate(world, migration);
+    }
+  }
+
+  // A client whose host was lost hosts the run itself or joins the player who does. When that
+  // fails the run is left like any lost session.
+  fn migrate(&mut self, world: &mut World, migration: Migration) {
+    self.link = NetLink::Offline;
+    let (link, toast) = match migration {
+      Migration::Host(own, others) => match NetHost::bind(NET_PORT, self.controls.clone(), false) {
+        Ok(mut host) => {
+          host.take_over(world, own, others);
+          (NetLink::Host(host), "toast.hosting")
+        }
+        Err(e) => {
+          warn!("Host on port {} error {}", NET_PORT, e);
+          (NetLink::Offline, "toast.host_lost")
+        }
+      },
+      Migration::Join(address, own) => match NetClient::connect(&address, Some(own), self.keyboard.clone(), self.controls[0].clone()) {
+        Ok(client) => (NetLink::Client(client), "toast.new_host"),
+        Err(e) => {
+          warn!("Join {} error {}", address, e);
+          (NetLink::Offline, "toast.host_lost")
+        }
+      },
+    };
+    self.link = link;
+    world.write_resource::<GameEvents>().single_write(GameEvent::Toast(toast));
+  }
+
+  // A lockstep peer runs only the steps whose turn arrived, until its match starts none
+  pub fn allowed_steps(&mut self, steps: u32) -> u32 {
+    match link_lockstep(&mut self.link) {
+      Some(peer) if peer.is_started => steps.min(peer.ready_steps() as u32),
+      Some(_) => 0,
+      None => steps,
+    }
+  }
+
+  // Before each simulation step a lockstep peer gives the turn's inputs to the character and mouse
+  // systems and returns the step, the game time follows it so it is the same on every peer
+  pub fn step(&mut self, world: &World) -> Option<u64> {
+    let peer = link_lockstep(&mut self.link)?;
+    let last = peer.step();
+    if last % LOCKSTEP_HASH_INTERVAL == 0 {
+      peer.record_hash(last, state_hash(world));
+    }
+    let turn = peer.next_turn()?;
+    let local = self.local_player().0;
+    let dim = world.read_resource::<Dimensions>();
+    for (player, input) in turn.inputs {
+      for control in input.controls {
+        self.controls[player].send(control).expect("Lockstep character control update error");
+      }
+      // The own clicks go through the mouse, which also holds the aiming pose and knows the button
+      for click in input.clicks {
+        if player == local {
+          let click = click.map(|(x, y)| (f64::from(dim.window_width / 2.0 + x), f64::from(dim.window_height / 2.0 + y)));
+          self.mouse_control.send((MouseControl::LeftClick, click)).expect("Lockstep mouse control update error");
+        } else if let Some(click) = click {
+          press_click(world, player, click);
+        }
+      }
+    }
+    Some(turn.step)
+  }
+
+  pub fn send(&mut self, world: &World) {
+    match self.link {
+      // Nobody else reads the lines of an offline game
+      NetLink::Offline => world.write_resource::<Chat>().outgoing.clear(),
+      NetLink::Host(ref mut host) => host.send(world),
+      NetLink::Client(ref mut client) => client.send(world),
+    }
+  }
+}
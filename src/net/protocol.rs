@@ -0,0 +1,276 @@
+use std::net::IpAddr;
+
+use json::JsonValue;
+
+use crate::character::controls::CharacterControl;
+use crate::critter::palette::CritterPalette;
+use crate::game::constants::GAME_VERSION;
+use crate::game::snapshot::{field_usize, Snapshot};
+use crate::net::lobby::LobbyPlayer;
+use crate::net::lockstep::{StepInput, Turn};
+use crate::net::replication::StateDelta;
+
+fn control_name(control: CharacterControl) -> &'static str {
+  match control {
+    CharacterControl::Left => "Left",
+    CharacterControl::Right => "Right",
+    CharacterControl::Up => "Up",
+    CharacterControl::Down => "Down",
+    CharacterControl::XMoveStop => "XMoveStop",
+    CharacterControl::YMoveStop => "YMoveStop",
+    CharacterControl::CtrlPressed => "CtrlPressed",
+    CharacterControl::CtrlReleased => "CtrlReleased",
+    CharacterControl::ReloadPressed => "ReloadPressed",
+    CharacterControl::ReloadReleased => "ReloadReleased",
+  }
+}
+
+fn control_from_name(name: &str) -> Option<CharacterControl> {
+  match name {
+    "Left" => Some(CharacterControl::Left),
+    "Right" => Some(CharacterControl::Right),
+    "Up" => Some(CharacterControl::Up),
+    "Down" => Some(CharacterControl::Down),
+    "XMoveStop" => Some(CharacterControl::XMoveStop),
+    "YMoveStop" => Some(CharacterControl::YMoveStop),
+    "CtrlPressed" => Some(CharacterControl::CtrlPressed),
+    "CtrlReleased" => Some(CharacterControl::CtrlReleased),
+    "ReloadPressed" => Some(CharacterControl::ReloadPressed),
+    "ReloadReleased" => Some(CharacterControl::ReloadReleased),
+    _ => None,
+  }
+}
+
+fn controls_to_json(controls: &[CharacterControl]) -> JsonValue {
+  JsonValue::Array(controls.iter().map(|&c| control_name(c).into()).collect())
+}
+
+fn controls_from_json(value: &JsonValue) -> Result<Vec<CharacterControl>, String> {
+  value.members()
+    .map(|c| c.as_str().and_then(control_from_name).ok_or_else(|| format!("Unknown control {}", c)))
+    .collect()
+}
+
+// Presses from the center of the window, a release is null
+fn clicks_to_json(clicks: &[Option<(f32, f32)>]) -> JsonValue {
+  JsonValue::Array(clicks.iter().map(|click| click.map_or(JsonValue::Null, |(x, y)| array![x, y])).collect())
+}
+
+fn clicks_from_json(value: &JsonValue) -> Vec<Option<(f32, f32)>> {
+  value.members()
+    .map(|click| click[0].as_f32().and_then(|x| click[1].as_f32().map(|y| (x, y))))
+    .collect()
+}
+
+fn input_to_json(input: &StepInput) -> JsonValue {
+  object! {
+    "controls" => controls_to_json(&input.controls),
+    "clicks" => clicks_to_json(&input.clicks)
+  }
+}
+
+fn input_from_json(value: &JsonValue) -> Result<StepInput, String> {
+  Ok(StepInput {
+    controls: controls_from_json(&value["controls"])?,
+    clicks: clicks_from_json(&value["clicks"]),
+  })
+}
+
+// Hashes and seeds use all 64 bits, more than a JSON number keeps
+fn hex(value: u64) -> String {
+  format!("{:016x}", value)
+}
+
+fn field_hex(value: &JsonValue, name: &str) -> Result<u64, String> {
+  value[name].as_str().and_then(|hex| u64::from_str_radix(hex, 16).ok()).ok_or_else(|| format!("Invalid {}", name))
+}
+
+fn hash_to_json(hash: Option<(u64, u64)>) -> JsonValue {
+  hash.map_or(JsonValue::Null, |(step, hash)| object! { "step" => step, "hash" => hex(hash) })
+}
+
+fn hash_from_json(value: &JsonValue) -> Result<Option<(u64, u64)>, String> {
+  if value.is_null() {
+    return Ok(None);
+  }
+  Ok(Some((value["step"].as_u64().ok_or("Invalid hash step")?, field_hex(value, "hash")?)))
+}
+
+// Leads the binary state messages, a JSON message never starts with it
+const STATE_TAG: u8 = 0;
+
+// One message per datagram. Clients send their numbered controls every frame, which doubles as
+// their heartbeat and acknowledges the latest state they got, and the host answers with the state
+// of the world after its simulation steps.
+pub enum Message {
+  // The game version and the index a player had before its host left
+  Join(String, Option<usize>),
+  // The player index and whether the host plays lockstep
+  Welcome(usize, bool),
+  Refused(String),
+  // Player, input number, acknowledged state, the controls and the clicks
+  Input(usize, u64, u64, Vec<CharacterControl>, Vec<Option<(f32, f32)>>),
+  State(StateDelta),
+  Leave(usize),
+  // Broadcast to the LAN with the game version, the host's name and its player count
+  Advert(String, String, usize),
+  // The host's lobby and a client's choices in it, sent every frame until the match starts
+  Lobby(Vec<LobbyPlayer>),
+  Ready(usize, CritterPalette, bool),
+  // A chat line of a player, clients send theirs to the host and it passes them on to the others
+  Chat(usize, String),
+  // Lockstep, the seed and the players of the new run, sent until the client sends its inputs
+  Start(u64, Vec<usize>),
+  // A client's inputs by step with the last turn it got and its latest state hash
+  Steps(usize, u64, Vec<(u64, StepInput)>, Option<(u64, u64)>),
+  // The turns a client is missing with the last step of its inputs the host got and the host's hash
+  Turns(u64, Vec<Turn>, Option<(u64, u64)>),
+  // The addresses of the clients by player index, so they find the one who takes over a lost host
+  Peers(Vec<(usize, IpAddr)>),
+}
+
+impl Message {
+  pub fn encode(&self) -> Vec<u8> {
+    match self {
+      Message::State(delta) => {
+        let mut bytes = vec![STATE_TAG];
+        delta.encode(&mut bytes);
+        bytes
+      }
+      _ => self.to_json().dump().into_bytes(),
+    }
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Message, String> {
+    if let Some((&STATE_TAG, state)) = bytes.split_first() {
+      return StateDelta::decode(state).map(Message::State);
+    }
+    let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+    let value = json::parse(text).map_err(|e| e.to_string())?;
+    Message::from_json(&value)
+  }
+}
+
+impl Snapshot for Message {
+  fn to_json(&self) -> JsonValue {
+    match self {
+      Message::Join(version, rejoin) => object! {
+        "type" => "join",
+        "version" => version.as_str(),
+        "rejoin" => rejoin.map_or(JsonValue::Null, |index| index.into())
+      },
+      Message::Welcome(player, is_lockstep) => object! { "type" => "welcome", "player" => *player, "lockstep" => *is_lockstep },
+      Message::Refused(reason) => object! { "type" => "refused", "reason" => reason.as_str() },
+      Message::Input(player, input, ack, controls, clicks) => object! {
+        "type" => "input",
+        "player" => *player,
+        "input" => *input,
+        "ack" => *ack,
+        "controls" => controls_to_json(controls),
+        "clicks" => clicks_to_json(clicks)
+      },
+      // Sent as bytes
+      Message::State(delta) => object! { "type" => "state", "frame" => delta.frame },
+      Message::Leave(player) => object! { "type" => "leave", "player" => *player },
+      Message::Advert(version, name, players) => object! {
+        "type" => "advert",
+        "version" => version.as_str(),
+        "name" => name.as_str(),
+        "players" => *players
+      },
+      Message::Lobby(players) => object! {
+        "type" => "lobby",
+        "players" => JsonValue::Array(players.iter()
+          .map(|p| object! { "player" => p.index, "skin" => p.skin as usize, "ready" => p.is_ready })
+          .collect())
+      },
+      Message::Ready(player, skin, is_ready) => object! {
+        "type" => "ready",
+        "player" => *player,
+        "skin" => *skin as usize,
+        "ready" => *is_ready
+      },
+      Message::Chat(player, text) => object! { "type" => "chat", "player" => *player, "text" => text.as_str() },
+      Message::Start(seed, players) => object! {
+        "type" => "start",
+        "seed" => hex(*seed),
+        "players" => JsonValue::Array(players.iter().map(|&p| p.into()).collect())
+      },
+      Message::Steps(player, ack, inputs, hash) => object! {
+        "type" => "steps",
+        "player" => *player,
+        "ack" => *ack,
+        "inputs" => JsonValue::Array(inputs.iter().map(|(step, input)| {
+          let mut value = input_to_json(input);
+          value["step"] = (*step).into();
+          value
+        }).collect()),
+        "hash" => hash_to_json(*hash)
+      },
+      // Players without input in a step are left out
+      Message::Turns(ack, turns, hash) => object! {
+        "type" => "turns",
+        "ack" => *ack,
+        "turns" => JsonValue::Array(turns.iter().map(|turn| object! {
+          "step" => turn.step,
+          "inputs" => JsonValue::Array(turn.inputs.iter().map(|(player, input)| {
+            let mut value = input_to_json(input);
+            value["player"] = (*player).into();
+            value
+          }).collect())
+        }).collect()),
+        "hash" => hash_to_json(*hash)
+      },
+      Message::Peers(peers) => object! {
+        "type" => "peers",
+        "peers" => JsonValue::Array(peers.iter().map(|(player, ip)| object! { "player" => *player, "address" => ip.to_string() }).collect())
+      },
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<Message, String> {
+    match value["type"].as_str() {
+      Some("join") => Ok(Message::Join(value["version"].as_str().unwrap_or_default().to_string(), value["rejoin"].as_usize())),
+      Some("welcome") => Ok(Message::Welcome(field_usize(value, "player")?, value["lockstep"].as_bool().unwrap_or(false))),
+      Some("refused") => Ok(Message::Refused(value["reason"].as_str().unwrap_or_default().to_string())),
+      Some("input") => Ok(Message::Input(field_usize(value, "player")?, value["input"].as_u64().unwrap_or(0), value["ack"].as_u64().unwrap_or(0),
+                                         controls_from_json(&value["controls"])?, clicks_from_json(&value["clicks"]))),
+      Some("leave") => Ok(Message::Leave(field_usize(value, "player")?)),
+      Some("advert") => Ok(Message::Advert(value["version"].as_str().unwrap_or_default().to_string(),
+                                           value["name"].as_str().unwrap_or_default().to_string(), field_usize(value, "players")?)),
+      Some("lobby") => Ok(Message::Lobby(value["players"].members()
+        .map(|p| Ok(LobbyPlayer {
+          index: field_usize(p, "player")?,
+          skin: CritterPalette::from_index(field_usize(p, "skin")?),
+          is_ready: p["ready"].as_bool().unwrap_or(false),
+        }))
+        .collect::<Result<_, String>>()?)),
+      Some("ready") => Ok(Message::Ready(field_usize(value, "player")?, CritterPalette::from_index(field_usize(value, "skin")?),
+                                         value["ready"].as_bool().unwrap_or(false))),
+      Some("start") => Ok(Message::Start(field_hex(value, "seed")?, value["players"].members()
+        .map(|p| p.as_usize().ok_or_else(|| format!("Invalid player {}", p)))
+        .collect::<Result<_, _>>()?)),
+      Some("steps") => Ok(Message::Steps(field_usize(value, "player")?, value["ack"].as_u64().unwrap_or(0), value["inputs"].members()
+        .map(|input| Ok((input["step"].as_u64().ok_or("Invalid step")?, input_from_json(input)?)))
+        .collect::<Result<_, String>>()?, hash_from_json(&value["hash"])?)),
+      Some("turns") => Ok(Message::Turns(value["ack"].as_u64().unwrap_or(0), value["turns"].members()
+        .map(|turn| Ok(Turn {
+          step: turn["step"].as_u64().ok_or("Invalid step")?,
+          inputs: turn["inputs"].members()
+            .map(|input| Ok((field_usize(input, "player")?, input_from_json(input)?)))
+            .collect::<Result<_, String>>()?,
+        }))
+        .collect::<Result<_, String>>()?, hash_from_json(&value["hash"])?)),
+      Some("chat") => Ok(Message::Chat(field_usize(value, "player")?, value["text"].as_str().unwrap_or_default().to_string())),
+      Some("peers") => Ok(Message::Peers(value["peers"].members()
+        .map(|p| Ok((field_usize(p, "player")?, p["address"].as_str().and_then(|a| a.parse().ok()).ok_or("Invalid peer address")?)))
+        .collect::<Result<_, String>>()?)),
+      _ => Err(format!("Unknown message {}", value["type"])),
+    }
+  }
+}
+
+// Host and clients have to run the same build, the simulation isn't versioned
+pub fn join_message(rejoin: Option<usize>) -> Message {
+  Message::Join(GAME_VERSION.to_string(), rejoin)
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+
+use crate::character::{CharacterDrawable, controls::CharacterInputState, Player};
+use crate::critter::palette::CritterPalette;
+use crate::ecs::{Builder, Join, World, WorldExt};
+use crate::game::constants::{NET_INTEREST_RADIUS, NET_POSITION_SCALE};
+use crate::game::health::Health;
+use crate::game::score::Score;
+use crate::game::snapshot::Snapshot;
+use crate::game::spawner;
+use crate::graphics::{camera::CameraInputState, distance};
+use crate::graphics::orientation::{Orientation, Stance};
+use crate::net::client::local_index;
+use crate::net::host::player_entity;
+use crate::shaders::Position;
+use crate::terrain_object::{TerrainObjectDrawable, TerrainTexture};
+use crate::zombie::ZombieDrawable;
+
+pub const KIND_PLAYER: u8 = 0;
+pub const KIND_ZOMBIE: u8 = 1;
+pub const KIND_PICKUP: u8 = 2;
+
+// Fields sent for an entity, the unchanged ones are left out of a delta
+const FIELD_KIND: u8 = 1;
+const FIELD_X: u8 = 1 << 1;
+const FIELD_Y: u8 = 1 << 2;
+const FIELD_SPRITE: u8 = 1 << 3;
+const FIELD_HEALTH: u8 = 1 << 4;
+const FIELD_AMMO: u8 = 1 << 5;
+const ALL_FIELDS: u8 = FIELD_KIND | FIELD_X | FIELD_Y | FIELD_SPRITE | FIELD_HEALTH | FIELD_AMMO;
+
+const STANCES: [Stance; 6] = [Stance::Walking, Stance::Running, Stance::Firing, Stance::Still, Stance::NormalDeath, Stance::CriticalDeath];
+const ORIENTATIONS: [Orientation; 9] = [Orientation::Right, Orientation::UpRight, Orientation::Up, Orientation::UpLeft, Orientation::Left,
+  Orientation::DownLeft, Orientation::Down, Orientation::DownRight, Orientation::Normal];
+
+// What a client needs to draw an entity, quantized. The variant is the index of a player and the
+// palette is the skin of a player or the colors of a zombie, positions are in 1 / NET_POSITION_SCALE
+// world units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EntityState {
+  pub kind: u8,
+  pub variant: u8,
+  pub palette: u8,
+  pub x: i32,
+  pub y: i32,
+  pub stance: u8,
+  pub orientation: u8,
+  pub health: u16,
+  pub ammunition: u8,
+  pub magazines: u8,
+}
+
+impl EntityState {
+  pub fn position(&self) -> Position {
+    Position::new(self.x as f32 / NET_POSITION_SCALE, self.y as f32 / NET_POSITION_SCALE)
+  }
+
+  fn at(kind: u8, variant: u8, position: Position) -> EntityState {
+    EntityState {
+      kind,
+      variant,
+      x: (position.x() * NET_POSITION_SCALE).round() as i32,
+      y: (position.y() * NET_POSITION_SCALE).round() as i32,
+      ..EntityState::default()
+    }
+  }
+
+  fn sprite(self, stance: &Stance, orientation: Orientation) -> EntityState {
+    EntityState { stance: stance.clone() as u8, orientation: orientation as u8, ..self }
+  }
+
+  fn stance(&self) -> Stance {
+    STANCES.get(self.stance as usize).cloned().unwrap_or(Stance::Still)
+  }
+
+  fn orientation(&self) -> Orientation {
+    ORIENTATIONS.get(self.orientation as usize).cloned().unwrap_or(Orientation::Normal)
+  }
+
+  fn changed_fields(&self, base: &EntityState) -> u8 {
+    [(FIELD_KIND, (self.kind, self.variant, self.palette) != (base.kind, base.variant, base.palette)),
+      (FIELD_X, self.x != base.x),
+      (FIELD_Y, self.y != base.y),
+      (FIELD_SPRITE, (self.stance, self.orientation) != (base.stance, base.orientation)),
+      (FIELD_HEALTH, self.health != base.health),
+      (FIELD_AMMO, (self.ammunition, self.magazines) != (base.ammunition, base.magazines))]
+      .iter()
+      .filter(|(_, is_changed)| *is_changed)
+      .fold(0, |fields, (field, _)| fields | field)
+  }
+}
+
+// The ids keep the kinds apart, players are numbered by index, pickups by their order and
+// zombies by the id they got when spawned
+fn entity_id(kind: u8, idx: u32) -> u32 {
+  u32::from(kind) << 24 | (idx & 0x00ff_ffff)
+}
+
+// Drawables sit relative to the first player's movement, one at the negated movement of a
+// player stands where that player does
+fn is_near(position: Position, movement: Position) -> bool {
+  let offset = position + movement;
+  distance(offset.x(), offset.y()) <= NET_INTEREST_RADIUS
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetSnapshot {
+  pub entities: BTreeMap<u32, EntityState>,
+}
+
+impl NetSnapshot {
+  // Everything a player at the given movement is close enough to see, and all the players
+  pub fn capture(world: &World, movement: Position) -> NetSnapshot {
+    let mut entities = BTreeMap::new();
+    for (p, cd, ci, h) in (&world.read_storage::<Player>(), &world.read_storage::<CharacterDrawable>(),
+                           &world.read_storage::<CharacterInputState>(), &world.read_storage::<Health>()).join() {
+      let state = EntityState {
+        health: h.current.round() as u16,
+        ammunition: cd.stats.ammunition as u8,
+        magazines: cd.stats.magazines as u8,
+        palette: cd.skin as u8,
+        ..EntityState::at(KIND_PLAYER, p.index as u8, ci.movement).sprite(&cd.stance, cd.orientation)
+      };
+      entities.insert(entity_id(KIND_PLAYER, p.index as u32), state);
+    }
+    for z in world.read_storage::<ZombieDrawable>().join().filter(|z| is_near(z.transform.world_position(), movement)) {
+      let state = EntityState {
+        health: z.health.current.round() as u16,
+        palette: z.palette as u8,
+        ..EntityState::at(KIND_ZOMBIE, 0, z.transform.world_position()).sprite(&z.stance, z.orientation)
+      };
+      entities.insert(entity_id(KIND_ZOMBIE, z.id), state);
+    }
+    let objects = world.read_storage::<TerrainObjectDrawable>();
+    let pickups = objects.join().filter(|o| o.object_type == TerrainTexture::Ammo).enumerate();
+    for (idx, o) in pickups.filter(|(_, o)| is_near(o.transform.world_position(), movement)) {
+      entities.insert(entity_id(KIND_PICKUP, idx as u32), EntityState::at(KIND_PICKUP, 0, o.transform.world_position()));
+    }
+    NetSnapshot { entities }
+  }
+
+  pub fn delta(&self, frame: u64, baseline: Option<(u64, &NetSnapshot)>, score: &Score) -> StateDelta {
+    let empty = NetSnapshot::default();
+    let (baseline_frame, base) = baseline.unwrap_or((0, &empty));
+    StateDelta {
+      frame,
+      baseline: baseline_frame,
+      input: 0,
+      score: score.clone(),
+      changed: self.entities.iter()
+        .filter_map(|(&id, state)| {
+          let fields = base.entities.get(&id).map_or(ALL_FIELDS, |b| state.changed_fields(b));
+          if fields == 0 { None } else { Some((id, fields, *state)) }
+        })
+        .collect(),
+      removed: base.entities.keys().filter(|id| !self.entities.contains_key(id)).cloned().collect(),
+    }
+  }
+
+  pub fn apply(&self, delta: &StateDelta) -> NetSnapshot {
+    let mut entities = self.entities.clone();
+    for id in &delta.removed {
+      entities.remove(id);
+    }
+    for &(id, fields, ref state) in &delta.changed {
+      let entity = entities.entry(id).or_default();
+      if fields & FIELD_KIND != 0 {
+        entity.kind = state.kind;
+        entity.variant = state.variant;
+        entity.palette = state.palette;
+      }
+      if fields & FIELD_X != 0 {
+        entity.x = state.x;
+      }
+      if fields & FIELD_Y != 0 {
+        entity.y = state.y;
+      }
+      if fields & FIELD_SPRITE != 0 {
+        entity.stance = state.stance;
+        entity.orientation = state.orientation;
+      }
+      if fields & FIELD_HEALTH != 0 {
+        entity.health = state.health;
+      }
+      if fields & FIELD_AMMO != 0 {
+        entity.ammunition = state.ammunition;
+        entity.magazines = state.magazines;
+      }
+    }
+    NetSnapshot { entities }
+  }
+
+  pub fn player_position(&self, index: usize) -> Option<Position> {
+    self.entities.get(&entity_id(KIND_PLAYER, index as u32)).map(|e| e.position())
+  }
+
+  // Shows the state in a client's world, where its own character is player 0 and stands ahead of
+  // the host's state by the predicted movement. Zombies out of reach of the client's player are
+  // dropped until they come close again.
+  pub fn show(&self, world: &mut World, own: usize, prediction: Position) {
+    let players = self.entities.values()
+      .filter(|e| e.kind == KIND_PLAYER)
+      .map(|e| (local_index(e.variant as usize, own), e))
+      .collect::<Vec<_>>();
+    let shown = world.read_storage::<Player>().join().map(|p| p.index).collect::<Vec<_>>();
+    for index in shown.iter().filter(|&&idx| idx > 0 && !players.iter().any(|(i, _)| *i == idx)) {
+      if let Some(entity) = player_entity(world, *index) {
+        world.delete_entity(entity).expect("Player delete error");
+      }
+    }
+    for (index, _) in players.iter().filter(|(i, _)| !shown.contains(i)) {
+      spawner::spawn_player(world.create_entity(), *index);
+    }
+
+    let mut main = CharacterInputState::new();
+    for (p, cd, ci, h, camera) in (&world.read_storage::<Player>(), &mut world.write_storage::<CharacterDrawable>(),
+                                   &mut world.write_storage::<CharacterInputState>(), &mut world.write_storage::<Health>(),
+                                   (&mut world.write_storage::<CameraInputState>()).maybe()).join() {
+      if let Some((_, e)) = players.iter().find(|(i, _)| *i == p.index) {
+        let movement = if p.index == 0 { e.position() + prediction } else { e.position() };
+        // The camera moves with the character like a step of the character system does
+        if let Some(camera) = camera {
+          let correction = movement - ci.movement;
+          camera.movement = camera.movement + Position::new(correction.x(), -correction.y());
+        }
+        ci.previous_movement = ci.movement;
+        ci.movement = movement;
+        ci.orientation = e.orientation();
+        cd.orientation = e.orientation();
+        cd.stance = e.stance();
+        h.current = f32::from(e.health);
+        cd.stats.ammunition = e.ammunition as usize;
+        cd.stats.magazines = e.magazines as usize;
+        cd.skin = CritterPalette::from_index(e.palette as usize);
+      }
+      if p.index == 0 {
+        main.movement = ci.movement;
+      }
+    }
+
+    // A zombie that is already shown keeps its entity and its animation
+    let mut previous = (&world.entities(), &world.read_storage::<ZombieDrawable>()).join()
+      .map(|(e, z)| (entity_id(KIND_ZOMBIE, z.id), e))
+      .collect::<BTreeMap<_, _>>();
+    for (&id, e) in self.entities.iter().filter(|(_, e)| e.kind == KIND_ZOMBIE) {
+      let entity = match previous.remove(&id) {
+        Some(entity) => entity,
+        None => world.create_entity().with(ZombieDrawable::new(Position::origin())).build(),
+      };
+      if let Some(z) = world.write_storage::<ZombieDrawable>().get_mut(entity) {
+        z.transform.set_world_position(e.position(), &main);
+        z.stance = e.stance();
+        z.orientation = e.orientation();
+        z.health.current = f32::from(e.health);
+        z.palette = CritterPalette::from_index(e.palette as usize);
+        z.id = id & 0x00ff_ffff;
+      }
+    }
+    world.delete_entities(&previous.into_values().collect::<Vec<_>>()).expect("Zombie despawn error");
+
+    let pickups = (&world.entities(), &world.read_storage::<TerrainObjectDrawable>()).join()
+      .filter(|(_, o)| o.object_type == TerrainTexture::Ammo)
+      .map(|(e, _)| e)
+      .collect::<Vec<_>>();
+    world.delete_entities(&pickups).expect("Pickup despawn error");
+    for e in self.entities.values().filter(|e| e.kind == KIND_PICKUP) {
+      world.create_entity().with(TerrainObjectDrawable::new(e.position(), TerrainTexture::Ammo)).build();
+    }
+  }
+}
+
+// The entities of a frame that differ from the baseline frame the client acknowledged, frame 0
+// being the empty world. Sent as bytes, every entity takes a few bytes per changed field.
+#[derive(Debug, PartialEq)]
+pub struct StateDelta {
+  pub frame: u64,
+  pub baseline: u64,
+  // The latest input of the client the host applied before this frame
+  pub input: u64,
+  pub score: Score,
+  pub changed: Vec<(u32, u8, EntityState)>,
+  pub removed: Vec<u32>,
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+    if self.bytes.len() < len {
+      return Err("State message is cut short".to_string());
+    }
+    let (taken, rest) = self.bytes.split_at(len);
+    self.bytes = rest;
+    Ok(taken)
+  }
+
+  fn u8(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, String> {
+    let b = self.take(2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+  }
+
+  fn u32(&mut self) -> Result<u32, String> {
+    let b = self.take(4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+  }
+
+  fn i32(&mut self) -> Result<i32, String> {
+    self.u32().map(|v| v as i32)
+  }
+
+  fn u64(&mut self) -> Result<u64, String> {
+    let b = self.take(8)?;
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(b);
+    Ok(u64::from_le_bytes(bytes))
+  }
+}
+
+impl StateDelta {
+  pub fn encode(&self, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&self.frame.to_le_bytes());
+    bytes.extend_from_slice(&self.baseline.to_le_bytes());
+    bytes.extend_from_slice(&self.input.to_le_bytes());
+    let score = self.score.to_json().dump();
+    bytes.extend_from_slice(&(score.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(score.as_bytes());
+    bytes.extend_from_slice(&(self.changed.len() as u16).to_le_bytes());
+    for &(id, fields, ref e) in &self.changed {
+      bytes.extend_from_slice(&id.to_le_bytes());
+      bytes.push(fields);
+      if fields & FIELD_KIND != 0 {
+        bytes.extend_from_slice(&[e.kind, e.variant, e.palette]);
+      }
+      if fields & FIELD_X != 0 {
+        bytes.extend_from_slice(&e.x.to_le_bytes());
+      }
+      if fields & FIELD_Y != 0 {
+        bytes.extend_from_slice(&e.y.to_le_bytes());
+      }
+      if fields & FIELD_SPRITE != 0 {
+        bytes.extend_from_slice(&[e.stance, e.orientation]);
+      }
+      if fields & FIELD_HEALTH != 0 {
+        bytes.extend_from_slice(&e.health.to_le_bytes());
+      }
+      if fields & FIELD_AMMO != 0 {
+        bytes.extend_from_slice(&[e.ammunition, e.magazines]);
+      }
+    }
+    bytes.extend_from_slice(&(self.removed.len() as u16).to_le_bytes());
+    for id in &self.removed {
+      bytes.extend_from_slice(&id.to_le_bytes());
+    }
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<StateDelta, String> {
+    let mut r = Reader { bytes };
+    let frame = r.u64()?;
+    let baseline = r.u64()?;
+    let input = r.u64()?;
+    let score_len = r.u16()? as usize;
+    let score = std::str::from_utf8(r.take(score_len)?).map_err(|e| e.to_string())?;
+    let score = Score::from_json(&json::parse(score).map_err(|e| e.to_string())?)?;
+    let mut changed = Vec::new();
+    for _ in 0..r.u16()? {
+      let id = r.u32()?;
+      let fields = r.u8()?;
+      let mut e = EntityState::default();
+      if fields & FIELD_KIND != 0 {
+        e.kind = r.u8()?;
+        e.variant = r.u8()?;
+        e.palette = r.u8()?;
+      }
+      if fields & FIELD_X != 0 {
+        e.x = r.i32()?;
+      }
+      if fields & FIELD_Y != 0 {
+        e.y = r.i32()?;
+      }
+      if fields & FIELD_SPRITE != 0 {
+        e.stance = r.u8()?;
+        e.orientation = r.u8()?;
+      }
+      if fields & FIELD_HEALTH != 0 {
+        e.health = r.u16()?;
+      }
+      if fields & FIELD_AMMO != 0 {
+        e.ammunition = r.u8()?;
+        e.magazines = r.u8()?;
+      }
+      changed.push((id, fields, e));
+    }
+    let removed = (0..r.u16()?).map(|_| r.u32()).collect::<Result<_, _>>()?;
+    Ok(StateDelta { frame, baseline, input, score, changed, removed })
+  }
+}
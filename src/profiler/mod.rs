@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use cgmath::Point2;
+use crossbeam_channel as channel;
+use gfx;
+use gfx::format::{ChannelType, R8_G8_B8_A8, Rgba8, Swizzle};
+use gfx::memory::{Bind, Usage};
+use gfx::texture::{AaMode, Kind};
+use rusttype::Font;
+
+use crate::bullet::BulletDrawable;
+use crate::ecs::{self, Entities, ReadStorage, Write};
+use crate::game::constants::{FRAME_GRAPH_SAMPLES, PROFILER_MAX_LINES, PROFILER_REFRESH_INTERVAL, RESOLUTION_X, RESOLUTION_Y};
+use crate::graphics::mesh::{Geometry, RectangularTexturedMesh};
+use crate::graphics::render_graph::{ConstantCache, PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader};
+use crate::graphics::texture::Texture;
+use crate::hud::font::{draw_text, load_font};
+use crate::shaders::{overlay_pipeline, Position};
+use crate::zombie::ZombieDrawable;
+
+pub mod startup;
+pub mod trace;
+
+const SHADER_VERT: &[u8] = include_bytes!("../shaders/overlay.v.glsl");
+const SHADER_FRAG: &[u8] = include_bytes!("../shaders/overlay.f.glsl");
+
+const LINE_TEXTURE_WIDTH: u16 = 512;
+const LINE_TEXTURE_HEIGHT: u16 = 24;
+const SMOOTHING: f64 = 0.1;
+
+pub enum ProfilerEvent {
+  Timing(&'static str, Duration),
+  // Draw calls encoded by the draw system in a frame
+  DrawCalls(usize),
+  Toggle,
+  ToggleStats,
+  ToggleDebugShapes,
+}
+
+struct ProfileEntry {
+  name: &'static str,
+  average_ms: f64,
+  peak_ms: f64,
+}
+
+// Counts of the last frame for the frame stats graph
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameCounts {
+  pub entities: usize,
+  pub zombies: usize,
+  pub bullets: usize,
+  pub draw_calls: usize,
+}
+
+#[derive(Default)]
+pub struct FrameProfile {
+  pub is_visible: bool,
+  pub is_stats_visible: bool,
+  pub is_debug_shapes_visible: bool,
+  frame_ms: f64,
+  entries: Vec<ProfileEntry>,
+  // Unsmoothed frame times, oldest first
+  pub frame_history: VecDeque<f64>,
+  pub counts: FrameCounts,
+  pub lines: Vec<String>,
+}
+
+impl FrameProfile {
+  pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+    let ms = duration_to_ms(elapsed);
+    match self.entries.iter_mut().find(|e| e.name == name) {
+      Some(entry) => {
+        entry.average_ms += (ms - entry.average_ms) * SMOOTHING;
+        entry.peak_ms = entry.peak_ms.max(ms);
+      }
+      None => self.entries.push(ProfileEntry { name, average_ms: ms, peak_ms: ms }),
+    }
+  }
+
+  pub fn record_frame(&mut self, elapsed: Duration) {
+    let ms = duration_to_ms(elapsed);
+    self.frame_ms += (ms - self.frame_ms) * SMOOTHING;
+    self.frame_history.push_back(ms);
+    while self.frame_history.len() > FRAME_GRAPH_SAMPLES {
+      self.frame_history.pop_front();
+    }
+  }
+
+  pub fn frame_ms(&self) -> f64 {
+    self.frame_ms
+  }
+
+  fn refresh_lines(&mut self) {
+    self.lines.clear();
+    self.lines.push(format!("frame {:.2} ms ({:.0} fps)", self.frame_ms, 1000.0 / self.frame_ms.max(0.001)));
+    for e in self.entries.iter().take(PROFILER_MAX_LINES - 1) {
+      self.lines.push(format!("{} {:.2} ms (peak {:.2})", e.name, e.average_ms, e.peak_ms));
+    }
+    for e in &mut self.entries {
+      e.peak_ms = e.average_ms;
+    }
+  }
+}
+
+pub fn duration_to_ms(elapsed: Duration) -> f64 {
+  elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_nanos()) / 1e6
+}
+
+pub struct ProfilerSystem {
+  queue: channel::Receiver<ProfilerEvent>,
+  last_frame: Instant,
+  last_refresh: Instant,
+}
+
+impl ProfilerSystem {
+  pub fn new() -> (ProfilerSystem, channel::Sender<ProfilerEvent>) {
+    let (tx, rx) = channel::unbounded();
+    (ProfilerSystem {
+      queue: rx,
+      last_frame: Instant::now(),
+      last_refresh: Instant::now(),
+    }, tx)
+  }
+}
+
+impl<'a> ecs::System<'a> for ProfilerSystem {
+  type SystemData = (Entities<'a>,
+                     ReadStorage<'a, ZombieDrawable>,
+                     ReadStorage<'a, BulletDrawable>,
+                     Write<'a, FrameProfile>);
+
+  fn run(&mut self, (entities, zombies, bullets, mut profile): Self::SystemData) {
+    use crate::ecs::Join;
+
+    while let Ok(event) = self.queue.try_recv() {
+      match event {
+        ProfilerEvent::Timing(name, elapsed) => profile.record(name, elapsed),
+        ProfilerEvent::DrawCalls(count) => profile.counts.draw_calls = count,
+        ProfilerEvent::Toggle => profile.is_visible = !profile.is_visible,
+        ProfilerEvent::ToggleStats => profile.is_stats_visible = !profile.is_stats_visible,
+        ProfilerEvent::ToggleDebugShapes => profile.is_debug_shapes_visible = !profile.is_debug_shapes_visible,
+      }
+    }
+
+    if profile.is_stats_visible {
+      profile.counts.entities = entities.join().count();
+      profile.counts.zombies = zombies.join().filter(|z| !z.is_dead()).count();
+      profile.counts.bullets = bullets.join().count();
+    }
+
+    profile.record_frame(self.last_frame.elapsed());
+    self.last_frame = Instant::now();
+
+    if profile.is_visible && duration_to_ms(self.last_refresh.elapsed()) >= PROFILER_REFRESH_INTERVAL {
+      profile.refresh_lines();
+      self.last_refresh = Instant::now();
+    }
+  }
+}
+
+pub struct Profiled<S> {
+  name: &'static str,
+  system: S,
+  sender: channel::Sender<ProfilerEvent>,
+}
+
+impl<S> Profiled<S> {
+  pub fn new(system: S, name: &'static str, sender: &channel::Sender<ProfilerEvent>) -> Profiled<S> {
+    Profiled {
+      name,
+      system,
+      sender: sender.clone(),
+    }
+  }
+}
+
+impl<'a, S> ecs::System<'a> for Profiled<S> where S: ecs::System<'a> {
+  type SystemData = S::SystemData;
+
+  fn run(&mut self, data: Self::SystemData) {
+    let _span = tracing::trace_span!("system", name = self.name).entered();
+    let start = Instant::now();
+    self.system.run(data);
+    self.sender.send(ProfilerEvent::Timing(self.name, start.elapsed())).expect("Profiler timing update error");
+  }
+
+  fn setup(&mut self, world: &mut ecs::World) {
+    self.system.setup(world);
+  }
+}
+
+struct OverlayLine<R: gfx::Resources> {
+  bundle: gfx::pso::bundle::Bundle<R, overlay_pipeline::Data<R>>,
+  texture: gfx::handle::Texture<R, R8_G8_B8_A8>,
+  position: Position,
+  // The position never changes, it is uploaded with the first draw
+  uploaded: ConstantCache<Position>,
+  text: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum OverlaySide {
+  Left,
+  Right,
+}
+
+// The debug overlays, their textures and shaders are only built once they are first shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overlay {
+  Profiler,
+  ShaderParams,
+  Console,
+  Inspector,
+}
+
+impl Overlay {
+  pub const ALL: [Overlay; 4] = [Overlay::Profiler, Overlay::ShaderParams, Overlay::Console, Overlay::Inspector];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Overlay::Profiler => "Profiler overlay",
+      Overlay::ShaderParams => "Shader param overlay",
+      Overlay::Console => "Console overlay",
+      Overlay::Inspector => "Inspector overlay",
+    }
+  }
+
+  pub fn side(self) -> OverlaySide {
+    match self {
+      Overlay::Profiler | Overlay::Inspector => OverlaySide::Left,
+      Overlay::ShaderParams | Overlay::Console => OverlaySide::Right,
+    }
+  }
+}
+
+// An overlay built on the render thread, on its way to the draw system
+pub type BuiltOverlay<R> = (Overlay, OverlayDrawSystem<R>);
+
+pub struct OverlayDrawSystem<R: gfx::Resources> {
+  font: Font<'static>,
+  lines: Vec<OverlayLine<R>>,
+  pso_updates: PipelineUpdates<R, overlay_pipeline::Meta>,
+}
+
+impl<R: gfx::Resources> OverlayDrawSystem<R> {
+  pub fn new<F>(factory: &mut F,
+                targets: &RenderTargets<R>,
+                name: &'static str,
+                side: OverlaySide,
+                reloader: &mut ShaderReloader<F>) -> OverlayDrawSystem<R>
+    where F: gfx::Factory<R> {
+    use gfx::traits::FactoryExt;
+
+    let (pso, pso_updates) = PassDescriptor::new(name,
+                                                 ShaderFile::new("overlay.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("overlay.f.glsl", SHADER_FRAG),
+                                                 overlay_pipeline::new())
+      .build(factory, reloader);
+
+    let size = Point2::new(f32::from(LINE_TEXTURE_WIDTH) / RESOLUTION_X as f32,
+                           f32::from(LINE_TEXTURE_HEIGHT) / RESOLUTION_Y as f32);
+    let x = match side {
+      OverlaySide::Left => -1.0 + size.x,
+      OverlaySide::Right => 1.0 - size.x,
+    };
+
+    let lines = (0..PROFILER_MAX_LINES)
+      .map(|idx| {
+        let kind = Kind::D2(LINE_TEXTURE_WIDTH, LINE_TEXTURE_HEIGHT, AaMode::Single);
+        let texture = factory.create_texture::<R8_G8_B8_A8>(kind, 1, Bind::SHADER_RESOURCE | Bind::TRANSFER_DST,
+                                                            Usage::Dynamic, Some(ChannelType::Unorm))
+          .expect("Overlay texture creation error");
+        let view = factory.view_texture_as_shader_resource::<Rgba8>(&texture, (0, 0), Swizzle::new())
+          .expect("Overlay texture view error");
+
+        let mesh = RectangularTexturedMesh::new(factory, Texture::new(view, None), Geometry::Rectangle, size, None, None, None);
+
+        let pipeline_data = overlay_pipeline::Data {
+          vbuf: mesh.mesh.vertex_buffer,
+          position_cb: factory.create_constant_buffer(1),
+          overlay_sheet: (mesh.mesh.texture.raw, factory.create_sampler_linear()),
+          out_color: targets.color.clone(),
+          out_depth: targets.depth.clone(),
+        };
+
+        OverlayLine {
+          bundle: gfx::Bundle::new(mesh.mesh.slice, pso.clone(), pipeline_data),
+          texture,
+          position: Position::new(x, 1.0 - size.y * (2 * idx + 1) as f32),
+          uploaded: ConstantCache::default(),
+          text: String::new(),
+        }
+      })
+      .collect();
+
+    OverlayDrawSystem {
+      font: load_font(),
+      lines,
+      pso_updates,
+    }
+  }
+
+  pub fn draw<C>(&mut self,
+                 lines: &[String],
+                 encoder: &mut gfx::Encoder<R, C>)
+    where C: gfx::CommandBuffer<R> {
+    if let Some(pso) = self.pso_updates.latest() {
+      for line in &mut self.lines {
+        line.bundle.pso = pso.clone();
+      }
+    }
+
+    for (line, text) in self.lines.iter_mut().zip(lines.iter()) {
+      if line.text != *text {
+        let pixels = rasterize_line(&self.font, text);
+        encoder.update_texture::<R8_G8_B8_A8, Rgba8>(&line.texture, None, line.texture.get_info().to_image_info(0), &pixels)
+          .expect("Overlay texture update error");
+        line.text = text.to_owned();
+      }
+      line.uploaded.update(&line.bundle.data.position_cb, line.position, encoder);
+      line.bundle.encode(encoder);
+    }
+  }
+}
+
+fn rasterize_line(font: &Font, text: &str) -> Vec<[u8; 4]> {
+  let width = usize::from(LINE_TEXTURE_WIDTH);
+  let height = usize::from(LINE_TEXTURE_HEIGHT);
+  let (size, data) = draw_text(font, f32::from(LINE_TEXTURE_HEIGHT), text);
+  let text_width = (size.x.max(0) as usize).min(width);
+  let text_height = (size.y.max(0) as usize).min(height);
+
+  let mut pixels = vec![[0u8; 4]; width * height];
+  for y in 0..text_height {
+    for x in 0..text_width {
+      let i = (x + y * size.x as usize) * 4;
+      pixels[x + y * width] = [data[i], data[i + 1], data[i + 2], data[i + 3]];
+    }
+  }
+  pixels
+}
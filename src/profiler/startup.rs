@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use crate::profiler::duration_to_ms;
+
+// Wall time of the steps between the start of the process and the first drawn frame, printed once
+// that frame is on screen
+pub struct StartupProfile {
+  start: Instant,
+  last: Instant,
+  spans: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+  pub fn new(start: Instant) -> StartupProfile {
+    StartupProfile {
+      start,
+      last: start,
+      spans: Vec::new(),
+    }
+  }
+
+  // Ends the span of the step that just finished, it started at the previous mark
+  pub fn mark(&mut self, name: &'static str) {
+    let now = Instant::now();
+    self.spans.push((name, now.duration_since(self.last)));
+    self.last = now;
+  }
+
+  pub fn total(&self) -> Duration {
+    self.last.duration_since(self.start)
+  }
+
+  pub fn spans(&self) -> &[(&'static str, Duration)] {
+    &self.spans
+  }
+
+  pub fn summary(&self) -> String {
+    let spans = self.spans.iter()
+      .map(|&(name, elapsed)| format!("{} {:.0}", name, duration_to_ms(elapsed)))
+      .collect::<Vec<_>>();
+    format!("Started in {:.0} ms ({})", duration_to_ms(self.total()), spans.join(", "))
+  }
+}
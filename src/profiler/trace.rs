@@ -0,0 +1,52 @@
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+use crate::game::constants::{DEFAULT_LOG_FILTER, LOG_FILTER_ENV};
+
+// Holds the Chrome trace open, it is written out in full once this is dropped
+pub struct TraceGuard {
+  #[cfg(feature = "chrome_trace")]
+  _chrome: Option<tracing_chrome::FlushGuard>,
+}
+
+fn log_filter() -> EnvFilter {
+  match std::env::var(LOG_FILTER_ENV) {
+    Ok(filter) => EnvFilter::try_new(&filter).unwrap_or_else(|e| {
+      eprintln!("Log filter {} parse error {}, using {}", filter, e, DEFAULT_LOG_FILTER);
+      EnvFilter::new(DEFAULT_LOG_FILTER)
+    }),
+    Err(_) => EnvFilter::new(DEFAULT_LOG_FILTER),
+  }
+}
+
+// Log lines go to stdout through the filter. A Chrome trace gets every frame, simulation step and
+// system span whatever the filter, it opens in chrome://tracing or Perfetto.
+#[cfg(feature = "chrome_trace")]
+pub fn init(chrome_trace: Option<String>) -> TraceGuard {
+  let (chrome, guard) = match chrome_trace {
+    Some(file) => {
+      let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+        .file(file)
+        .include_args(true)
+        .build();
+      (Some(layer), Some(guard))
+    }
+    None => (None, None),
+  };
+  tracing_subscriber::registry()
+    .with(tracing_subscriber::fmt::layer().with_target(false).with_filter(log_filter()))
+    .with(chrome)
+    .init();
+  TraceGuard { _chrome: guard }
+}
+
+#[cfg(not(feature = "chrome_trace"))]
+pub fn init(chrome_trace: Option<String>) -> TraceGuard {
+  tracing_subscriber::registry()
+    .with(tracing_subscriber::fmt::layer().with_target(false).with_filter(log_filter()))
+    .init();
+  if let Some(file) = chrome_trace {
+    tracing::warn!("Chrome trace {} needs a build with --features chrome_trace", file);
+  }
+  TraceGuard {}
+}
@@ -1,12 +1,8 @@
 #version 150 core
 
-in vec3 a_Pos;
+in vec2 a_Pos;
 
-uniform b_VsLocals {
-  mat4 u_Model;
-  mat4 u_View;
-  mat4 u_Proj;
-};
+#include "globals.glsl"
 
 uniform b_BulletPosition {
   vec2 a_position;
@@ -19,7 +15,7 @@ uniform b_BulletRotation {
 void main() {
   vec3 rot_pos = mat3(cos(a_rotation),  -sin(a_rotation),  0.0,
                       sin(a_rotation),  cos(a_rotation),   0.0,
-                      0.0,              0.0,               1.0) * a_Pos;
+                      0.0,              0.0,               1.0) * vec3(a_Pos, 0.0);
 
   gl_Position = vec4(a_position, 0.0, 0.0) + vec4(rot_pos, 1.0) * u_Proj * u_View * u_Model;
 }
@@ -1,11 +1,21 @@
 #version 150 core
 
 in vec2 v_BufPos;
+in vec2 v_FramePos;
+in vec2 v_FogCoord;
+in vec4 v_Tint;
+in float v_Palette;
 out vec4 Target0;
 
 uniform sampler2D t_CharacterSheet;
+uniform sampler2D t_Palette;
+
+#include "lighting.glsl"
 
 void main() {
+  if (any(lessThan(v_FramePos, vec2(0.0))) || any(greaterThan(v_FramePos, vec2(1.0)))) {
+    discard;
+  }
   vec4 tex = texture(t_CharacterSheet, v_BufPos).rgba;
   if(tex.a < 0.1) {
     discard;
@@ -13,5 +23,12 @@ void main() {
   tex.r = smoothstep(0.1, 1.0, tex.r);
   tex.g = smoothstep(0.1, 1.0, tex.g);
   tex.b = smoothstep(0.1, 1.0, tex.b);
-  Target0 = tex;
+  if (v_Palette > 0.5) {
+    float luminance = dot(tex.rgb, vec3(0.299, 0.587, 0.114));
+    float paletteX = (luminance * float(PALETTE_WIDTH - 1) + 0.5) / float(PALETTE_WIDTH);
+    float paletteY = (v_Palette - 0.5) / float(PALETTE_ROWS);
+    tex.rgb = texture(t_Palette, vec2(paletteX, paletteY)).rgb;
+  }
+  tex *= v_Tint;
+  Target0 = vec4(fog(tex.rgb, v_FogCoord), tex.a);
 }
@@ -1,35 +1,45 @@
 #version 150 core
 
-in vec3 a_Pos;
+in vec2 a_Pos;
 in vec2 a_BufPos;
 out vec2 v_BufPos;
+out vec2 v_FramePos;
+out vec2 v_FogCoord;
+out vec4 v_Tint;
+out float v_Palette;
 
-uniform b_VsLocals {
-  mat4 u_Model;
-  mat4 u_View;
-  mat4 u_Proj;
-};
-
-layout (std140) uniform b_CharacterSprite {
-  float x_div;
-  float y_div;
-  int a_row;
-  float a_index;
-};
+#include "globals.glsl"
 
-uniform b_CharacterPosition {
-  vec2 a_position;
+#ifdef INSTANCED
+in vec2 i_Translate;
+in float i_Flip;
+in float i_Palette;
+in vec4 i_Tint;
+in vec4 i_SpriteRect;
+in vec4 i_SpriteTrim;
+#else
+layout (std140) uniform b_CritterInstance {
+  vec2 i_Translate;
+  float i_Flip;
+  float i_Palette;
+  vec4 i_Tint;
+  vec4 i_SpriteRect;
+  vec4 i_SpriteTrim;
 };
+#endif
 
 void main() {
-  v_BufPos = vec2(a_BufPos);
-
-  v_BufPos.y += y_div;
-  if (a_row > 1) {
-    v_BufPos.y /= 2.0;
+  vec2 quad_pos = a_BufPos;
+  if (i_Flip > 0.5) {
+    quad_pos.x = 1.0 - quad_pos.x;
   }
-  v_BufPos.x /= x_div;
-  v_BufPos.x += a_index / x_div;
 
-  gl_Position = vec4(a_position, 0.0, 0.0) + u_Proj * u_View * u_Model * vec4(a_Pos, 1.0);
+  // Outside 0..1 is the transparent border the packer trimmed away
+  v_FramePos = (quad_pos - i_SpriteTrim.xy) / i_SpriteTrim.zw;
+  v_BufPos = i_SpriteRect.xy + v_FramePos * i_SpriteRect.zw;
+  v_Tint = i_Tint;
+  v_Palette = i_Palette;
+
+  gl_Position = vec4(i_Translate, 0.0, 0.0) + u_Proj * u_View * u_Model * vec4(a_Pos, 0.0, 1.0);
+  v_FogCoord = gl_Position.xy / gl_Position.w;
 }
@@ -0,0 +1,16 @@
+#version 150 core
+
+in vec2 a_Pos;
+in vec4 a_Color;
+out vec4 v_Color;
+
+#include "globals.glsl"
+
+uniform b_DebugOffset {
+  vec2 a_position;
+};
+
+void main() {
+  v_Color = a_Color;
+  gl_Position = vec4(a_Pos + a_position, 0.0, 0.0) + u_Proj * u_View * u_Model * vec4(0.0, 0.0, 0.0, 1.0);
+}
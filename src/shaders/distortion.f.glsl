@@ -0,0 +1,20 @@
+#version 150 core
+
+in vec2 v_Local;
+out vec4 Target0;
+
+layout (std140) uniform b_DistortionSource {
+  vec2 a_position;
+  float a_strength;
+  float a_phase;
+};
+
+void main() {
+  float radius = length(v_Local);
+  if (radius > 1.0) {
+    discard;
+  }
+  float falloff = (1.0 - radius) * a_strength;
+  vec2 shimmer = vec2(sin(v_Local.y * 18.0 + a_phase * 6.0), cos(v_Local.x * 14.0 + a_phase * 5.0));
+  Target0 = vec4(shimmer * falloff, 0.0, 0.0);
+}
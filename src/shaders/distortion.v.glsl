@@ -0,0 +1,18 @@
+#version 150 core
+
+in vec2 a_Pos;
+in vec2 a_BufPos;
+out vec2 v_Local;
+
+#include "globals.glsl"
+
+layout (std140) uniform b_DistortionSource {
+  vec2 a_position;
+  float a_strength;
+  float a_phase;
+};
+
+void main() {
+  v_Local = a_BufPos * 2.0 - 1.0;
+  gl_Position = vec4(a_position, 0.0, 0.0) + u_Proj * u_View * u_Model * vec4(a_Pos, 0.0, 1.0);
+}
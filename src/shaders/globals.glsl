@@ -0,0 +1,8 @@
+layout (std140) uniform b_Globals {
+  mat4 u_Model;
+  mat4 u_View;
+  mat4 u_Proj;
+  vec4 u_AmbientLight;
+  float u_Time;
+  float u_FogDensity;
+};
@@ -0,0 +1,28 @@
+#include "globals.glsl"
+
+const float PI = 3.14159;
+const vec3 lightOrigPos = vec3(-200.0, 150.0, 0.0);
+const vec3 Normal = vec3(0.0, 1.0, 0.0);
+const vec3 lightColor = vec3(0.8, 0.5, 0.5);
+
+vec3 lighting(vec2 bufPos) {
+  float lightAngle = (u_Time + 1) * 4;
+  float lightAngleRad = lightAngle * PI / 180.0;
+
+  vec3 lightPos = mat3(cos(lightAngleRad),  -sin(lightAngleRad),  0.0,
+                        sin(lightAngleRad),  cos(lightAngleRad),  0.0,
+                        0.0,       0.0,       1.0) * lightOrigPos;
+
+  vec3 norm = normalize(Normal);
+  vec3 lightDir = normalize(lightPos - vec3(bufPos, 0.0));
+
+  float diff = max(dot(norm, lightDir), 0.0);
+  return diff * lightColor + u_AmbientLight.rgb;
+}
+
+const vec3 fogColor = vec3(0.0625, 0.0625, 0.078);
+
+vec3 fog(vec3 color, vec2 fogCoord) {
+  float fogAmount = 1.0 - exp(-u_FogDensity * dot(fogCoord, fogCoord));
+  return mix(color, fogColor, clamp(fogAmount, 0.0, 1.0));
+}
@@ -4,6 +4,10 @@ use std::{fmt::{Display, Formatter, Result}, ops::{Add, Sub}};
 use cgmath::BaseFloat;
 use gfx;
 
+use crate::graphics::shader_params::ShaderParams;
+
+pub mod preprocessor;
+
 gfx_defines! {
   constant TileMapData {
     data: [f32; 4] = "data",
@@ -17,9 +21,6 @@ gfx_defines! {
     rotation: f32 = "a_rotation",
   }
 
-  constant Time {
-    time_modulo: f32 = "a_time",
-  }
 
   constant TilemapSettings {
     world_size: [f32; 2] = "u_WorldSize",
@@ -31,28 +32,52 @@ gfx_defines! {
     uv: [f32; 2] = "a_BufPos",
   }
 
-  constant CharacterSheet {
-    x_div: f32 = "x_div",
-    y_div: f32 = "y_div",
-    row_idx: u32 = "a_row",
-    index: f32 = "a_index",
+  vertex CritterInstance {
+    translate: [f32; 2] = "i_Translate",
+    flip: f32 = "i_Flip",
+    palette: f32 = "i_Palette",
+    tint: [f32; 4] = "i_Tint",
+    sprite_rect: [f32; 4] = "i_SpriteRect",
+    sprite_trim: [f32; 4] = "i_SpriteTrim",
+  }
+
+  constant CritterLocals {
+    translate: [f32; 2] = "i_Translate",
+    flip: f32 = "i_Flip",
+    palette: f32 = "i_Palette",
+    tint: [f32; 4] = "i_Tint",
+    sprite_rect: [f32; 4] = "i_SpriteRect",
+    sprite_trim: [f32; 4] = "i_SpriteTrim",
   }
 
   pipeline bullet_pipeline {
     vbuf: gfx::VertexBuffer<VertexData> = (),
-    projection_cb: gfx::ConstantBuffer<Projection> = "b_VsLocals",
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
     position_cb: gfx::ConstantBuffer<Position> = "b_BulletPosition",
     rotation_cb: gfx::ConstantBuffer<Rotation> = "b_BulletRotation",
+    scissor: gfx::Scissor = (),
     out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
     out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
   }
 
   pipeline critter_pipeline {
     vbuf: gfx::VertexBuffer<VertexData> = (),
-    projection_cb: gfx::ConstantBuffer<Projection> = "b_VsLocals",
-    position_cb: gfx::ConstantBuffer<Position> = "b_CharacterPosition",
-    character_sprite_cb: gfx::ConstantBuffer<CharacterSheet> = "b_CharacterSprite",
+    instances: gfx::InstanceBuffer<CritterInstance> = (),
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
     charactersheet: gfx::TextureSampler<[f32; 4]> = "t_CharacterSheet",
+    palette: gfx::TextureSampler<[f32; 4]> = "t_Palette",
+    scissor: gfx::Scissor = (),
+    out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
+    out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
+  }
+
+  pipeline critter_fallback_pipeline {
+    vbuf: gfx::VertexBuffer<VertexData> = (),
+    instance_cb: gfx::ConstantBuffer<CritterLocals> = "b_CritterInstance",
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
+    charactersheet: gfx::TextureSampler<[f32; 4]> = "t_CharacterSheet",
+    palette: gfx::TextureSampler<[f32; 4]> = "t_Palette",
+    scissor: gfx::Scissor = (),
     out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
     out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
   }
@@ -60,11 +85,11 @@ gfx_defines! {
   pipeline tilemap_pipeline {
     vbuf: gfx::VertexBuffer<VertexData> = (),
     position_cb: gfx::ConstantBuffer<Position> = "b_TileMapPosition",
-    time_passed_cb: gfx::ConstantBuffer<Time> = "b_TimeModulo",
-    projection_cb: gfx::ConstantBuffer<Projection> = "b_VsLocals",
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
     tilemap: gfx::ConstantBuffer<TileMapData> = "b_TileMap",
     tilemap_cb: gfx::ConstantBuffer<TilemapSettings> = "b_PsLocals",
     tilesheet: gfx::TextureSampler<[f32; 4]> = "t_TileSheet",
+    scissor: gfx::Scissor = (),
     out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
     out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
   }
@@ -72,19 +97,82 @@ gfx_defines! {
   pipeline static_element_pipeline {
     vbuf: gfx::VertexBuffer<VertexData> = (),
     position_cb: gfx::ConstantBuffer<Position> = "b_StaticElementPosition",
-    time_passed_cb: gfx::ConstantBuffer<Time> = "b_TimeModulo",
-    projection_cb: gfx::ConstantBuffer<Projection> = "b_VsLocals",
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
     static_element_sheet: gfx::TextureSampler<[f32; 4]> = "t_StaticElementSheet",
+    scissor: gfx::Scissor = (),
     out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
     out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
   }
 
-  pipeline text_pipeline {
+  constant DistortionSource {
+    position: [f32; 2] = "a_position",
+    strength: f32 = "a_strength",
+    phase: f32 = "a_phase",
+  }
+
+  pipeline distortion_pipeline {
     vbuf: gfx::VertexBuffer<VertexData> = (),
-    position_cb: gfx::ConstantBuffer<Position> = "b_TextPosition",
-    text_sheet: gfx::TextureSampler<[f32; 4]> = "t_TextSheet",
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
+    source_cb: gfx::ConstantBuffer<DistortionSource> = "b_DistortionSource",
+    scissor: gfx::Scissor = (),
+    out_distortion: gfx::BlendTarget<gfx::format::Rgba16F> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ADD),
+  }
+
+  pipeline post_process_pipeline {
+    vbuf: gfx::VertexBuffer<VertexData> = (),
+    scene: gfx::TextureSampler<[f32; 4]> = "t_Scene",
+    distortion: gfx::TextureSampler<[f32; 4]> = "t_Distortion",
     out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
-    out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
+  }
+
+  pipeline overlay_pipeline {
+    vbuf: gfx::VertexBuffer<VertexData> = (),
+    position_cb: gfx::ConstantBuffer<Position> = "b_OverlayPosition",
+    overlay_sheet: gfx::TextureSampler<[f32; 4]> = "t_OverlaySheet",
+    out_color: gfx::RenderTarget<gfx::format::Rgba8> = "Target0",
+    out_depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::PASS_TEST,
+  }
+
+  constant UiLocals {
+    proj: [[f32; 4]; 4] = "u_Proj",
+    rect: [f32; 4] = "u_Rect",
+    color: [f32; 4] = "u_Color",
+  }
+
+  // Screen space quads drawn over the finished frame, blended and without depth
+  pipeline ui_pipeline {
+    vbuf: gfx::VertexBuffer<VertexData> = (),
+    locals: gfx::ConstantBuffer<UiLocals> = "b_UiLocals",
+    out_color: gfx::BlendTarget<gfx::format::Rgba8> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+  }
+
+  constant GlyphLocals {
+    proj: [[f32; 4]; 4] = "u_Proj",
+    rect: [f32; 4] = "u_Rect",
+    uv_rect: [f32; 4] = "u_UvRect",
+    color: [f32; 4] = "u_Color",
+  }
+
+  // Glyphs from the glyph cache atlas, placed like UI quads and tinted with the text color
+  pipeline text_pipeline {
+    vbuf: gfx::VertexBuffer<VertexData> = (),
+    locals: gfx::ConstantBuffer<GlyphLocals> = "b_GlyphLocals",
+    glyph_atlas: gfx::TextureSampler<[f32; 4]> = "t_GlyphAtlas",
+    out_color: gfx::BlendTarget<gfx::format::Rgba8> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+  }
+
+  vertex DebugVertex {
+    pos: [f32; 2] = "a_Pos",
+    color: [f32; 4] = "a_Color",
+  }
+
+  // Colored lines in world space over the scene, blended and without depth
+  pipeline debug_pipeline {
+    vbuf: gfx::VertexBuffer<DebugVertex> = (),
+    globals: gfx::ConstantBuffer<Globals> = "b_Globals",
+    offset_cb: gfx::ConstantBuffer<Position> = "b_DebugOffset",
+    scissor: gfx::Scissor = (),
+    out_color: gfx::BlendTarget<gfx::format::Rgba8> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
   }
 
   constant Projection {
@@ -92,13 +180,63 @@ gfx_defines! {
     view: [[f32; 4]; 4] = "u_View",
     proj: [[f32; 4]; 4] = "u_Proj",
   }
+
+  constant Globals {
+    model: [[f32; 4]; 4] = "u_Model",
+    view: [[f32; 4]; 4] = "u_View",
+    proj: [[f32; 4]; 4] = "u_Proj",
+    ambient_light: [f32; 4] = "u_AmbientLight",
+    time_modulo: f32 = "u_Time",
+    fog_density: f32 = "u_FogDensity",
+    padding: [f32; 2] = "_padding",
+  }
+}
+
+impl Globals {
+  pub fn new(projection: &Projection, time_passed: u64, params: &ShaderParams) -> Globals {
+    Globals {
+      model: projection.model,
+      view: projection.view,
+      proj: projection.proj,
+      ambient_light: params.ambient_light,
+      time_modulo: (time_passed % 89) as f32,
+      fog_density: params.fog_density,
+      padding: [0.0; 2],
+    }
+  }
+}
+
+// A frame of a sprite sheet. The rect is where it is in the texture and the trim where its pixels
+// are on the quad, both as fractions. Frames packed without their transparent border cover less
+// than the whole quad.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteFrame {
+  pub rect: [f32; 4],
+  pub trim: [f32; 4],
 }
 
-impl Time {
-  pub fn new(time_passed: u64) -> Time {
-    let time_modulo = (time_passed % 89) as f32;
-    Time {
-      time_modulo
+impl CritterInstance {
+  pub fn new(position: Position, sprite: &SpriteFrame) -> CritterInstance {
+    CritterInstance {
+      translate: position.position,
+      flip: 0.0,
+      palette: 0.0,
+      tint: [1.0, 1.0, 1.0, 1.0],
+      sprite_rect: sprite.rect,
+      sprite_trim: sprite.trim,
+    }
+  }
+}
+
+impl From<CritterInstance> for CritterLocals {
+  fn from(instance: CritterInstance) -> CritterLocals {
+    CritterLocals {
+      translate: instance.translate,
+      flip: instance.flip,
+      palette: instance.palette,
+      tint: instance.tint,
+      sprite_rect: instance.sprite_rect,
+      sprite_trim: instance.sprite_trim,
     }
   }
 }
@@ -120,6 +258,15 @@ impl VertexData {
   }
 }
 
+impl DebugVertex {
+  pub fn new(position: Position, color: [f32; 4]) -> DebugVertex {
+    DebugVertex {
+      pos: position.position,
+      color,
+    }
+  }
+}
+
 impl Iterator for VertexData {
   type Item = VertexData;
 
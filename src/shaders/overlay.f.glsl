@@ -0,0 +1,14 @@
+#version 150 core
+
+in vec2 v_BufPos;
+out vec4 Target0;
+
+uniform sampler2D t_OverlaySheet;
+
+void main() {
+  vec4 tex = texture(t_OverlaySheet, v_BufPos).rgba;
+  if(tex.a < 0.1) {
+    discard;
+  }
+  Target0 = vec4(tex.rgb * vec3(0.6, 1.0, 0.6), tex.a);
+}
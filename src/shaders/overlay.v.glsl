@@ -0,0 +1,14 @@
+#version 150 core
+
+in vec2 a_Pos;
+in vec2 a_BufPos;
+out vec2 v_BufPos;
+
+uniform b_OverlayPosition {
+  vec2 a_position;
+};
+
+void main() {
+  v_BufPos = a_BufPos;
+  gl_Position = vec4(a_Pos + a_position, 0.0, 1.0);
+}
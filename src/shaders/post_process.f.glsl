@@ -0,0 +1,12 @@
+#version 150 core
+
+in vec2 v_Uv;
+out vec4 Target0;
+
+uniform sampler2D t_Scene;
+uniform sampler2D t_Distortion;
+
+void main() {
+  vec2 offset = texture(t_Distortion, v_Uv).xy;
+  Target0 = texture(t_Scene, v_Uv + offset);
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const INCLUDES: [(&str, &[u8]); 2] = [
+  ("globals.glsl", include_bytes!("globals.glsl")),
+  ("lighting.glsl", include_bytes!("lighting.glsl")),
+];
+
+pub fn shader_path(file_name: &str) -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders").join(file_name)
+}
+
+pub fn read_shader(file_name: &str, embedded: &[u8]) -> Vec<u8> {
+  if cfg!(debug_assertions) {
+    fs::read(shader_path(file_name)).unwrap_or_else(|_| embedded.to_vec())
+  } else {
+    embedded.to_vec()
+  }
+}
+
+// Latest modification time of the shared includes, so editing one reloads every shader using it
+pub fn includes_modified() -> Option<SystemTime> {
+  INCLUDES.iter()
+    .filter_map(|(name, _)| fs::metadata(shader_path(name)).and_then(|m| m.modified()).ok())
+    .max()
+}
+
+fn read_include(name: &str) -> Result<String, String> {
+  INCLUDES.iter()
+    .find(|(include, _)| *include == name)
+    .map(|(include, embedded)| String::from_utf8_lossy(&read_shader(include, embedded)).into_owned())
+    .ok_or_else(|| format!("Unknown shader include \"{}\"", name))
+}
+
+fn expand_includes(source: &str, included: &mut Vec<String>, output: &mut String) -> Result<(), String> {
+  for line in source.lines() {
+    if let Some(include) = line.trim().strip_prefix("#include") {
+      let name = include.trim().trim_matches('"');
+      if !included.iter().any(|i| i == name) {
+        included.push(name.to_string());
+        expand_includes(&read_include(name)?, included, output)?;
+      }
+    } else {
+      output.push_str(line);
+      output.push('\n');
+    }
+  }
+  Ok(())
+}
+
+// Defines go right after the #version line, which GLSL requires to come first
+pub fn preprocess(source: &[u8], defines: &[(&'static str, String)]) -> Result<Vec<u8>, String> {
+  let source = String::from_utf8_lossy(source);
+  let mut expanded = String::new();
+  expand_includes(&source, &mut vec![], &mut expanded)?;
+
+  let define_lines = defines.iter()
+    .map(|(name, value)| format!("#define {} {}\n", name, value))
+    .collect::<String>();
+
+  let output = if expanded.trim_start().starts_with("#version") {
+    let version_end = expanded.find('\n').map_or(expanded.len(), |idx| idx + 1);
+    format!("{}{}{}", &expanded[..version_end], define_lines, &expanded[version_end..])
+  } else {
+    format!("{}{}", define_lines, expanded)
+  };
+  Ok(output.into_bytes())
+}
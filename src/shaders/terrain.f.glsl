@@ -1,14 +1,13 @@
 #version 150 core
 
 in vec2 v_BufPos;
+in vec2 v_FogCoord;
 out vec4 Target0;
 
 struct TileMapData {
   vec4 data;
 };
 
-const int TILEMAP_BUF_LENGTH = 4096;
-
 layout (std140) uniform b_TileMap {
   TileMapData u_Data[TILEMAP_BUF_LENGTH];
 };
@@ -20,55 +19,50 @@ layout (std140) uniform b_PsLocals {
 
 uniform sampler2D t_TileSheet;
 
-uniform b_TimeModulo {
-  float a_time;
-};
-
-const float PI = 3.14159;
-const vec3 lightOrigPos = vec3(-200.0, 150.0, 0.0);
-const vec3 Normal = vec3(0.0, 1.0, 0.0);
-const vec3 lightColor = vec3(0.8, 0.5, 0.5);
-const vec3 ambientColor = vec3(0.15, 0.15, 0.15);
+#include "lighting.glsl"
 
-void main() {
-  float lightAngle = (a_time + 1) * 4;
-  float lightAngleRad = lightAngle * PI / 180.0;
+vec2 tileCoords(float entry) {
+  float tileId = mod(entry, float(TILE_FLAGS_OFFSET));
+  return vec2(mod(tileId, u_TilesheetSize.y), floor(tileId / u_TilesheetSize.x));
+}
 
-  vec3 lightPos = mat3(cos(lightAngleRad),  -sin(lightAngleRad),  0.0,
-                        sin(lightAngleRad),  cos(lightAngleRad),  0.0,
-                        0.0,       0.0,       1.0) * lightOrigPos;
+vec2 flipTile(vec2 uv, float entry) {
+  int flags = int(entry) / TILE_FLAGS_OFFSET;
+  if ((flags & 4) != 0) {
+    uv = uv.yx;
+  }
+  if ((flags & 1) != 0) {
+    uv.x = 1.0 - uv.x;
+  }
+  if ((flags & 2) != 0) {
+    uv.y = 1.0 - uv.y;
+  }
+  return uv;
+}
 
+void main() {
   vec2 bufTileCoords = floor(v_BufPos);
   vec2 rawUvOffsets = vec2(v_BufPos.x - bufTileCoords.x, 1.0 - (v_BufPos.y - bufTileCoords.y));
 
   int bufIdx = int((bufTileCoords.y * u_WorldSize.x) + bufTileCoords.x);
   vec4 entry = u_Data[bufIdx].data;
-  vec2 coords = vec2(0.0, 0.0);
+  float tile = 0.0;
 
   if (bufIdx < TILEMAP_BUF_LENGTH) {
-    coords = vec2(mod(entry.x, u_TilesheetSize.y), floor(entry.x / u_TilesheetSize.x));
+    tile = entry.x;
   } else if (bufIdx < (TILEMAP_BUF_LENGTH * 2)) {
-    entry = u_Data[bufIdx - TILEMAP_BUF_LENGTH].data;
-    coords = vec2(mod(entry.y, u_TilesheetSize.y), floor(entry.y / u_TilesheetSize.x));
+    tile = u_Data[bufIdx - TILEMAP_BUF_LENGTH].data.y;
   } else if (bufIdx < (TILEMAP_BUF_LENGTH * 3)) {
-    entry = u_Data[bufIdx - TILEMAP_BUF_LENGTH * 2].data;
-    coords = vec2(mod(entry.z, u_TilesheetSize.y), floor(entry.z / u_TilesheetSize.x));
+    tile = u_Data[bufIdx - TILEMAP_BUF_LENGTH * 2].data.z;
   } else {
-    entry = u_Data[bufIdx-TILEMAP_BUF_LENGTH * 3].data;
-    coords = vec2(mod(entry.w, u_TilesheetSize.y), floor(entry.w / u_TilesheetSize.x));
+    tile = u_Data[bufIdx-TILEMAP_BUF_LENGTH * 3].data.w;
   }
-  vec2 uvCoords = (coords.xy + rawUvOffsets) / u_TilesheetSize.xy;
-
-  vec3 norm = normalize(Normal);
-  vec3 lightDir = normalize(lightPos - vec3(v_BufPos, 0.0));
-
-  float diff = max(dot(norm, lightDir), 0.0);
-  vec3 diffuse = diff * lightColor;
+  vec2 uvCoords = (tileCoords(tile) + flipTile(rawUvOffsets, tile)) / u_TilesheetSize.xy;
 
   vec4 tex = texture(t_TileSheet, uvCoords);
-  tex *= vec4(diffuse + ambientColor, 1.0);
+  tex *= vec4(lighting(v_BufPos), 1.0);
   if(tex.a < 0.1) {
     discard;
   }
-  Target0 = tex;
+  Target0 = vec4(fog(tex.rgb, v_FogCoord), tex.a);
 }
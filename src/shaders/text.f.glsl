@@ -1,14 +1,17 @@
 #version 150 core
 
-in vec2 v_BufPos;
+in vec2 v_Uv;
 out vec4 Target0;
 
-uniform sampler2D t_TextSheet;
+uniform sampler2D t_GlyphAtlas;
+
+uniform b_GlyphLocals {
+  mat4 u_Proj;
+  vec4 u_Rect;
+  vec4 u_UvRect;
+  vec4 u_Color;
+};
 
 void main() {
-  vec4 tex = texture(t_TextSheet, v_BufPos).rgba;
-  if(tex.a < 0.1) {
-    discard;
-  }
-  Target0 = tex;
+  Target0 = vec4(u_Color.rgb, u_Color.a * texture(t_GlyphAtlas, v_Uv).a);
 }
@@ -1,14 +1,17 @@
 #version 150 core
 
-in vec3 a_Pos;
+in vec2 a_Pos;
 in vec2 a_BufPos;
-out vec2 v_BufPos;
+out vec2 v_Uv;
 
-uniform b_TextPosition {
-  vec2 a_position;
+uniform b_GlyphLocals {
+  mat4 u_Proj;
+  vec4 u_Rect;
+  vec4 u_UvRect;
+  vec4 u_Color;
 };
 
 void main() {
-  v_BufPos = a_BufPos * vec2(25.0, 50.0);
-  gl_Position = vec4(a_position, 0.0, 0.0) + vec4(a_Pos, 1.0);
+  v_Uv = u_UvRect.xy + a_BufPos * u_UvRect.zw;
+  gl_Position = u_Proj * vec4(u_Rect.xy + a_Pos * u_Rect.zw, 0.0, 1.0);
 }
@@ -0,0 +1,13 @@
+#version 150 core
+
+out vec4 Target0;
+
+uniform b_UiLocals {
+  mat4 u_Proj;
+  vec4 u_Rect;
+  vec4 u_Color;
+};
+
+void main() {
+  Target0 = u_Color;
+}
@@ -0,0 +1,14 @@
+#version 150 core
+
+in vec2 a_Pos;
+in vec2 a_BufPos;
+
+uniform b_UiLocals {
+  mat4 u_Proj;
+  vec4 u_Rect;
+  vec4 u_Color;
+};
+
+void main() {
+  gl_Position = u_Proj * vec4(u_Rect.xy + a_Pos * u_Rect.zw, 0.0, 1.0);
+}
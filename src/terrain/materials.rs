@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use cgmath::Point2;
+use tiled::{Map, PropertyValue};
+
+use crate::data::get_map_tile;
+use crate::game::constants::{TILES_PCS_H, TILES_PCS_W};
+use crate::graphics::{coords_to_tile, is_within_map_borders};
+use crate::shaders::Position;
+use crate::terrain::tile_map::strip_flips;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Material {
+  Grass,
+  Water,
+  Stone,
+}
+
+impl Material {
+  fn from_name(name: &str) -> Option<Material> {
+    match name {
+      "grass" => Some(Material::Grass),
+      "water" => Some(Material::Water),
+      "stone" => Some(Material::Stone),
+      _ => None,
+    }
+  }
+}
+
+// Ground material of every map tile, read from the `material` property of the tileset tiles.
+// Tiles without the property are grass.
+#[derive(Default)]
+pub struct TileMaterials {
+  tiles: Vec<Material>,
+}
+
+impl TileMaterials {
+  pub fn new(map: &Map) -> TileMaterials {
+    let by_gid = map.tilesets.iter()
+      .flat_map(|tile_set| tile_set.tiles.iter()
+        .filter_map(move |tile| match tile.properties.get("material") {
+          Some(PropertyValue::StringValue(name)) => Material::from_name(name).map(|m| (tile_set.first_gid + tile.id, m)),
+          _ => None,
+        }))
+      .collect::<HashMap<u32, Material>>();
+
+    let tiles = (0..TILES_PCS_H)
+      .flat_map(|y| (0..TILES_PCS_W).map(move |x| (x, y)))
+      .map(|(x, y)| *by_gid.get(&strip_flips(get_map_tile(map, 0, x, y))).unwrap_or(&Material::Grass))
+      .collect();
+    TileMaterials { tiles }
+  }
+
+  pub fn at(&self, position: Position) -> Material {
+    let tile = coords_to_tile(position);
+    if tile.x < 0 || tile.y < 0 || !is_within_map_borders(Point2::new(tile.x as usize, tile.y as usize)) {
+      return Material::Grass;
+    }
+    let idx = tile.y as usize * TILES_PCS_W + tile.x as usize;
+    self.tiles.get(idx).cloned().unwrap_or(Material::Grass)
+  }
+}
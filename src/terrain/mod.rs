@@ -1,17 +1,16 @@
 use cgmath::Point2;
 use genmesh::{generators::{IndexedPolygon, Plane, SharedVertex}, Triangulate, Vertices};
 use gfx;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
 
-use crate::character::controls::CharacterInputState;
-use crate::game::constants::{ASPECT_RATIO, TILE_SIZE, TILES_PCS_H, TILES_PCS_W, VIEW_DISTANCE};
-use crate::gfx_app::{ColorFormat, DepthFormat};
-use crate::graphics::{camera::CameraInputState, can_move_to_tile, coords_to_tile, dimensions::{Dimensions, get_projection, get_view_matrix}};
+use crate::character::{controls::CharacterInputState, Player};
+use crate::ecs::{self, ReadStorage, WriteStorage};
+use crate::game::constants::{TILE_SIZE, TILES_PCS_H, TILES_PCS_W};
+use crate::graphics::{can_move_to_tile, coords_to_tile, render_graph::{ConstantCache, PassDescriptor, PipelineUpdates, RenderTargets, ShaderFile, ShaderReloader}, viewport::View};
 use crate::graphics::mesh::TexturedMesh;
-use crate::graphics::texture::{load_texture, Texture};
-use crate::shaders::{Position, Projection, tilemap_pipeline, TilemapSettings, Time, VertexData};
+use crate::graphics::{assets::{Assets, TextureAsset}, texture::Texture};
+use crate::shaders::{Position, tilemap_pipeline, TilemapSettings, VertexData};
 
+pub mod materials;
 pub mod path_finding;
 pub mod tile_map;
 
@@ -20,24 +19,19 @@ fn cartesian_to_isometric(point_x: f32, point_y: f32) -> (f32, f32) {
 }
 
 pub struct TerrainDrawable {
-  projection: Projection,
   pub position: Position,
   pub tile_position: Point2<i32>,
 }
 
 impl TerrainDrawable {
   pub fn new() -> TerrainDrawable {
-    let view = get_view_matrix(VIEW_DISTANCE);
-    let projection = get_projection(view, ASPECT_RATIO);
     TerrainDrawable {
-      projection,
       position: Position::origin(),
       tile_position: coords_to_tile(Position::origin()),
     }
   }
 
-  pub fn update(&mut self, world_to_clip: &Projection, ci: &mut CharacterInputState) {
-    self.projection = *world_to_clip;
+  pub fn update(&mut self, ci: &mut CharacterInputState) {
     if can_move_to_tile(ci.movement) {
       ci.is_colliding = false;
       self.position = ci.movement;
@@ -48,8 +42,8 @@ impl TerrainDrawable {
   }
 }
 
-impl specs::prelude::Component for TerrainDrawable {
-  type Storage = specs::storage::HashMapStorage<TerrainDrawable>;
+impl ecs::Component for TerrainDrawable {
+  type Storage = ecs::HashMapStorage<TerrainDrawable>;
 }
 
 const SHADER_VERT: &[u8] = include_bytes!("../shaders/terrain.v.glsl");
@@ -57,13 +51,16 @@ const SHADER_FRAG: &[u8] = include_bytes!("../shaders/terrain.f.glsl");
 
 pub struct TerrainDrawSystem<R: gfx::Resources> {
   bundle: gfx::pso::bundle::Bundle<R, tilemap_pipeline::Data<R>>,
-  is_tile_map_dirty: bool,
+  pso_updates: PipelineUpdates<R, tilemap_pipeline::Meta>,
+  position: ConstantCache<Position>,
+  tile_map: ConstantCache<TilemapSettings>,
 }
 
 impl<R: gfx::Resources> TerrainDrawSystem<R> {
   pub fn new<F>(factory: &mut F,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>)
+                targets: &RenderTargets<R>,
+                assets: &Assets<R>,
+                reloader: &mut ShaderReloader<F>)
                 -> TerrainDrawSystem<R>
     where F: gfx::Factory<R> {
     use gfx::traits::FactoryExt;
@@ -93,52 +90,52 @@ impl<R: gfx::Resources> TerrainDrawSystem<R> {
         .map(|i| i as u16)
         .collect::<Vec<u16>>();
 
-    let tile_sheet_bytes = &include_bytes!("../../assets/maps/terrain.png")[..];
-    let tile_texture = load_texture(factory, tile_sheet_bytes);
+    let tile_texture = Texture::new(assets.texture(TextureAsset::Terrain), None);
+    let mesh = TexturedMesh::new(factory, vertex_data.as_slice(), index_data.as_slice(), tile_texture);
 
-    let mesh = TexturedMesh::new(factory, &vertex_data.as_slice(), index_data.as_slice(), Texture::new(tile_texture, None));
-
-    let pso = factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, tilemap_pipeline::new())
-      .expect("Terrain shader loading error");
+    let (pso, pso_updates) = PassDescriptor::new("Terrain",
+                                                 ShaderFile::new("terrain.v.glsl", SHADER_VERT),
+                                                 ShaderFile::new("terrain.f.glsl", SHADER_FRAG).define("TILEMAP_BUF_LENGTH", tile_map::QUARTER_BUF_LENGTH)
+                                                   .define("TILE_FLAGS_OFFSET", tile_map::TILE_FLAGS_OFFSET),
+                                                 tilemap_pipeline::new())
+      .build(factory, reloader);
 
     let terrain = tile_map::Terrain::new();
 
     let pipeline_data = tilemap_pipeline::Data {
       vbuf: mesh.vertex_buffer,
       position_cb: factory.create_constant_buffer(1),
-      time_passed_cb: factory.create_constant_buffer(1),
-      projection_cb: factory.create_constant_buffer(1),
+      globals: targets.globals.clone(),
       tilemap: factory.create_buffer_immutable(&terrain.tiles.as_slice(),
                                                gfx::buffer::Role::Constant,
                                                gfx::memory::Bind::empty()).unwrap(),
       tilemap_cb: factory.create_constant_buffer(1),
       tilesheet: (mesh.texture.raw, factory.create_sampler_linear()),
-      out_color: rtv,
-      out_depth: dsv,
+      scissor: targets.scissor(),
+      out_color: targets.color.clone(),
+      out_depth: targets.depth.clone(),
     };
 
     TerrainDrawSystem {
       bundle: gfx::Bundle::new(mesh.slice, pso, pipeline_data),
-      is_tile_map_dirty: true,
+      pso_updates,
+      position: ConstantCache::default(),
+      tile_map: ConstantCache::default(),
     }
   }
 
   pub fn draw<C>(&mut self,
                  drawable: &TerrainDrawable,
-                 time_passed: u64,
+                 view: &View,
                  encoder: &mut gfx::Encoder<R, C>)
     where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.projection_cb, &drawable.projection);
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    encoder.update_constant_buffer(&self.bundle.data.time_passed_cb, &Time::new(time_passed));
-
-    if self.is_tile_map_dirty {
-      encoder.update_constant_buffer(&self.bundle.data.tilemap_cb, &TilemapSettings {
-        world_size: [TILES_PCS_W as f32, TILES_PCS_H as f32],
-        tilesheet_size: [32.0, 32.0],
-      });
-      self.is_tile_map_dirty = false
-    }
+    self.pso_updates.apply(&mut self.bundle.pso);
+    self.bundle.data.scissor = view.scissor;
+    self.position.update(&self.bundle.data.position_cb, view.position(drawable.position), encoder);
+    self.tile_map.update(&self.bundle.data.tilemap_cb, TilemapSettings {
+      world_size: [TILES_PCS_W as f32, TILES_PCS_H as f32],
+      tilesheet_size: [32.0, 32.0],
+    }, encoder);
 
     self.bundle.encode(encoder);
   }
@@ -146,18 +143,20 @@ impl<R: gfx::Resources> TerrainDrawSystem<R> {
 
 pub struct PreDrawSystem;
 
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
+impl<'a> ecs::System<'a> for PreDrawSystem {
   type SystemData = (WriteStorage<'a, TerrainDrawable>,
-                     ReadStorage<'a, CameraInputState>,
                      WriteStorage<'a, CharacterInputState>,
-                     Read<'a, Dimensions>);
+                     ReadStorage<'a, Player>);
 
-  fn run(&mut self, (mut terrain, camera_input, mut character_input, dim): Self::SystemData) {
-    use specs::join::Join;
+  fn run(&mut self, (mut terrain, mut character_input, players): Self::SystemData) {
+    use crate::ecs::Join;
+
+    for (t, ci) in (&mut terrain, &mut character_input).join() {
+      t.update(ci);
+    }
 
-    for (t, camera, ci) in (&mut terrain, &camera_input, &mut character_input).join() {
-      let world_to_clip = dim.world_to_projection(camera);
-      t.update(&world_to_clip, ci);
+    for (ci, _, _) in (&mut character_input, &players, !&terrain).join() {
+      ci.is_colliding = !can_move_to_tile(ci.movement);
     }
   }
 }
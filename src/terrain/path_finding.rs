@@ -1,79 +1,184 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use cgmath::Point2;
-use pathfinding::{directed::astar::astar, utils::absdiff};
 
 use crate::game::constants::{TILES_PCS_H, TILES_PCS_W, TERRAIN_OBJECTS};
-use crate::game::get_rand_from_range;
+use crate::game::{GameRng, get_rand_from_range};
 use crate::graphics::coords_to_tile;
 use crate::shaders::Position;
 
-fn neighbours<'c>(curr_pos: Point2<i32>, impassable_tiles: &[[i32; 2]], neighbour_tiles: &'c mut Vec<Point2<i32>>) -> Vec<&'c Point2<i32>> {
-  neighbour_tiles.push(Point2::new(curr_pos.x - 1, curr_pos.y));
-  neighbour_tiles.push(Point2::new(curr_pos.x - 1, curr_pos.y - 1));
-  neighbour_tiles.push(Point2::new(curr_pos.x, curr_pos.y - 1));
-  neighbour_tiles.push(Point2::new(curr_pos.x + 1, curr_pos.y));
-  neighbour_tiles.push(Point2::new(curr_pos.x + 1, curr_pos.y + 1));
-  neighbour_tiles.push(Point2::new(curr_pos.x, curr_pos.y + 1));
-  neighbour_tiles.push(Point2::new(curr_pos.x - 1, curr_pos.y + 1));
-  neighbour_tiles.push(Point2::new(curr_pos.x + 1, curr_pos.y - 1));
-
-  neighbour_tiles
-    .iter()
-    .filter(|ref e| e.x >= 0 && e.x < TILES_PCS_W as i32 && e.y >= 0 && e.y < TILES_PCS_H as i32)
-    .filter(|ref e| !impassable_tiles.contains(&[e.x, e.y]))
-    .collect()
+const NEIGHBOURS: [(i32, i32); 8] = [(-1, 0), (-1, -1), (0, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (1, -1)];
+
+fn tile_index(tile: Point2<i32>) -> Option<usize> {
+  if tile.x >= 0 && tile.x < TILES_PCS_W as i32 && tile.y >= 0 && tile.y < TILES_PCS_H as i32 {
+    Some(tile.x as usize * TILES_PCS_H + tile.y as usize)
+  } else {
+    None
+  }
 }
 
-fn tiles(p: Point2<i32>, impassable_tiles: &[[i32; 2]]) -> Vec<(Point2<i32>, i32)> {
-  neighbours(p, &impassable_tiles, &mut vec![])
-    .iter()
-    .map(|p| (**p, 1))
-    .collect()
+fn index_tile(idx: usize) -> Point2<i32> {
+  Point2::new((idx / TILES_PCS_H) as i32, (idx % TILES_PCS_H) as i32)
 }
 
-fn find_next_best_endpoint<'c>(end_point: &'c Point2<i32>, impassable_tiles: &[[i32; 2]], neighbour_tiles: &'c mut Vec<Point2<i32>>) -> &'c Point2<i32> {
-  if impassable_tiles.iter().any(|e| e[0] == end_point.x && e[1] == end_point.y) {
-    neighbours(*end_point, &impassable_tiles, neighbour_tiles)[0]
-  } else {
-    &end_point
-  }
+fn estimate(tile: Point2<i32>, end: Point2<i32>) -> i32 {
+  (tile.x - end.x).abs() + (tile.y - end.y).abs()
 }
 
-pub fn calc_route(start_point: Position, end_point: Position, impassable_tiles: &[[i32; 2]]) -> Option<(Vec<Point2<i32>>, i32)> {
-  let mut neighbour_tiles = vec![];
-  let end_tile = coords_to_tile(end_point);
-  let start_tile = coords_to_tile(start_point);
+// A* over the tile map with every step costing the same. The buffers are kept between searches, a
+// zombie deciding where to go allocates nothing once the open list has grown to its usual size.
+pub struct PathFinder {
+  is_blocked: Vec<bool>,
+  costs: Vec<i32>,
+  parents: Vec<usize>,
+  // The search a tile's cost and parent were set by, so they never need to be cleared
+  visited: Vec<u32>,
+  search: u32,
+  // Estimated total cost, the cost so far reversed to prefer tiles closer to the end, and the tile
+  open: BinaryHeap<Reverse<(i32, Reverse<i32>, usize)>>,
+}
 
-  let end = find_next_best_endpoint(&end_tile, &impassable_tiles, &mut neighbour_tiles);
+impl PathFinder {
+  pub fn new() -> PathFinder {
+    let tiles = TILES_PCS_W * TILES_PCS_H;
+    let mut is_blocked = vec![false; tiles];
+    for &[x, y] in TERRAIN_OBJECTS.iter() {
+      if let Some(idx) = tile_index(Point2::new(x, y)) {
+        is_blocked[idx] = true;
+      }
+    }
+    PathFinder {
+      is_blocked,
+      costs: vec![0; tiles],
+      parents: vec![0; tiles],
+      visited: vec![0; tiles],
+      search: 0,
+      open: BinaryHeap::new(),
+    }
+  }
+
+  fn neighbours(&self, tile: Point2<i32>) -> impl Iterator<Item=usize> + '_ {
+    NEIGHBOURS.iter()
+      .filter_map(move |&(x, y)| tile_index(Point2::new(tile.x + x, tile.y + y)))
+      .filter(move |&idx| !self.is_blocked[idx])
+  }
 
-  astar(&start_tile,
-        |p: &Point2<i32>| tiles(*p, &impassable_tiles),
-        |p: &Point2<i32>| absdiff(p.x, end.x) + absdiff(p.y, end.y),
-        |p: &Point2<i32>| p.x == end.x && p.y == end.y)
+  // An end on an impassable tile moves to the first free tile next to it
+  fn free_end(&self, end: Point2<i32>) -> Option<usize> {
+    let idx = tile_index(end)?;
+    if self.is_blocked[idx] {
+      self.neighbours(end).next()
+    } else {
+      Some(idx)
+    }
+  }
+
+  // The first tile of the route from start to end, the start itself when it is the end
+  pub fn next_tile(&mut self, start: Point2<i32>, end: Point2<i32>) -> Option<Point2<i32>> {
+    let start_idx = tile_index(start)?;
+    let end_idx = self.free_end(end)?;
+    if start_idx == end_idx {
+      return Some(start);
+    }
+    if !self.search(start_idx, end_idx) {
+      return None;
+    }
+    let mut step = end_idx;
+    while self.parents[step] != start_idx {
+      step = self.parents[step];
+    }
+    Some(index_tile(step))
+  }
+
+  // Every tile of the route after the start up to the end, left empty when there is none. Only the
+  // debug shapes want the whole route, a zombie takes one tile at a time.
+  pub fn route(&mut self, start: Point2<i32>, end: Point2<i32>, tiles: &mut Vec<Point2<i32>>) {
+    tiles.clear();
+    let (start_idx, end_idx) = match (tile_index(start), self.free_end(end)) {
+      (Some(start_idx), Some(end_idx)) => (start_idx, end_idx),
+      _ => return,
+    };
+    if start_idx == end_idx || !self.search(start_idx, end_idx) {
+      return;
+    }
+    let mut step = end_idx;
+    while step != start_idx {
+      tiles.push(index_tile(step));
+      step = self.parents[step];
+    }
+    tiles.reverse();
+  }
+
+  // True once the end is reached, its parents then lead back to the start
+  fn search(&mut self, start_idx: usize, end_idx: usize) -> bool {
+    let (start, end) = (index_tile(start_idx), index_tile(end_idx));
+    self.search = self.search.wrapping_add(1);
+    if self.search == 0 {
+      self.visited.iter_mut().for_each(|v| *v = 0);
+      self.search = 1;
+    }
+    self.open.clear();
+    self.visited[start_idx] = self.search;
+    self.costs[start_idx] = 0;
+    self.parents[start_idx] = start_idx;
+    self.open.push(Reverse((estimate(start, end), Reverse(0), start_idx)));
+
+    while let Some(Reverse((_, Reverse(cost), idx))) = self.open.pop() {
+      // A tile is pushed again when a cheaper way to it turns up, the older entry is skipped
+      if cost > self.costs[idx] {
+        continue;
+      }
+      if idx == end_idx {
+        return true;
+      }
+      let tile = index_tile(idx);
+      for n in NEIGHBOURS.iter().filter_map(|&(x, y)| tile_index(Point2::new(tile.x + x, tile.y + y))) {
+        let next_cost = cost + 1;
+        if self.is_blocked[n] || (self.visited[n] == self.search && self.costs[n] <= next_cost) {
+          continue;
+        }
+        self.visited[n] = self.search;
+        self.costs[n] = next_cost;
+        self.parents[n] = idx;
+        self.open.push(Reverse((next_cost + estimate(index_tile(n), end), Reverse(next_cost), n)));
+      }
+    }
+    false
+  }
+
+  pub fn calc_next_movement(&mut self, start_point: Position, end_point: Position, rng: &mut GameRng) -> i32 {
+    let start = coords_to_tile(start_point);
+    let next_step = self.next_tile(start, coords_to_tile(end_point)).unwrap_or_else(|| Point2::new(0, 0));
+    let diff: (i32, i32) = (next_step.x - start.x, next_step.y - start.y);
+
+    match diff {
+      (1, 0) => 315,
+      (1, 1) => 270,
+      (0, 1) => 225,
+      (-1, 1) => 180,
+      (-1, 0) => 135,
+      (-1, -1) => 90,
+      (0, -1) => 45,
+      (1, -1) => 0,
+      _ => get_rand_from_range(rng, 0, 359),
+    }
+  }
 }
 
-pub fn calc_next_movement(start_point: Position, end_point: Position) -> i32 {
-  let next_step: Point2<i32> = calc_route(start_point, end_point, &TERRAIN_OBJECTS.to_vec())
-    .map_or_else(|| Point2::new(0, 0),
-                 |(route, ..)| {
-                   if route.len() > 1 {
-                     route[1]
-                   } else {
-                     route[0]
-                   }
-                 });
-
-  let start = coords_to_tile(start_point);
-  let diff: (i32, i32) = (next_step.x - start.x, next_step.y - start.y);
-
-  match diff {
-    (1, 0) => 315,
-    (1, 1) => 270,
-    (0, 1) => 225,
-    (-1, 1) => 180,
-    (-1, 0) => 135,
-    (-1, -1) => 90,
-    (0, -1) => 45,
-    (1, -1) => 0,
-    _ => get_rand_from_range(0, 359),
+impl Default for PathFinder {
+  fn default() -> PathFinder {
+    PathFinder::new()
   }
 }
+
+thread_local! {
+  static PATH_FINDER: RefCell<PathFinder> = RefCell::new(PathFinder::new());
+}
+
+// Zombies updated on the rayon workers share the path finder of the thread they run on
+pub fn with_path_finder<T, F>(f: F) -> T
+  where F: FnOnce(&mut PathFinder) -> T {
+  PATH_FINDER.with(|paths| f(&mut paths.borrow_mut()))
+}
@@ -5,7 +5,33 @@ use crate::game::constants::{MAP_FILE_PATH, TILES_PCS_H, TILES_PCS_W};
 use crate::shaders::TileMapData;
 
 const TILEMAP_BUF_LENGTH: usize = TILES_PCS_H * TILES_PCS_H;
-const QUARTER_BUF_LENGTH: usize = TILEMAP_BUF_LENGTH / 4;
+pub const QUARTER_BUF_LENGTH: usize = TILEMAP_BUF_LENGTH / 4;
+
+// Tiled stores flips in the top bits of the global tile id
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+pub const TILE_FLAGS_OFFSET: u32 = 1024;
+
+// Flags are packed above the tile index so the value stays exact as f32
+pub fn encode_tile(gid: u32) -> f32 {
+  let mut flags = 0;
+  if gid & FLIPPED_HORIZONTALLY != 0 {
+    flags |= 1;
+  }
+  if gid & FLIPPED_VERTICALLY != 0 {
+    flags |= 2;
+  }
+  if gid & FLIPPED_DIAGONALLY != 0 {
+    flags |= 4;
+  }
+  let tile_id = strip_flips(gid) - 1;
+  (tile_id + flags * TILE_FLAGS_OFFSET) as f32
+}
+
+pub fn strip_flips(gid: u32) -> u32 {
+  gid & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY)
+}
 
 fn calc_index(x_pos: usize, y_pos: usize) -> usize {
   (y_pos * TILES_PCS_W) + x_pos
@@ -14,21 +40,21 @@ fn calc_index(x_pos: usize, y_pos: usize) -> usize {
 fn populate_tile_map<'a>(tiles: &'a mut Vec<TileMapData>, map: &Map) -> &'a mut Vec<TileMapData> {
   for y_pos in 0..TILES_PCS_H {
     for x_pos in 0..TILES_PCS_W {
-      let map_val = get_map_tile(map, 0, x_pos, y_pos) - 1;
+      let map_val = encode_tile(get_map_tile(map, 0, x_pos, y_pos));
       let idx = calc_index(x_pos, y_pos);
 
       if idx < QUARTER_BUF_LENGTH {
         tiles[idx] =
-          TileMapData::new([map_val as f32, 0.0, 0.0, 0.0]);
+          TileMapData::new([map_val, 0.0, 0.0, 0.0]);
       } else if idx < QUARTER_BUF_LENGTH * 2 {
         tiles[idx - QUARTER_BUF_LENGTH] =
-          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH].data[0], map_val as f32, 0.0, 0.0]);
+          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH].data[0], map_val, 0.0, 0.0]);
       } else if idx < QUARTER_BUF_LENGTH * 3 {
         tiles[idx - QUARTER_BUF_LENGTH * 2] =
-          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH * 2].data[0], tiles[idx - QUARTER_BUF_LENGTH * 2].data[1], map_val as f32, 0.0]);
+          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH * 2].data[0], tiles[idx - QUARTER_BUF_LENGTH * 2].data[1], map_val, 0.0]);
       } else {
         tiles[idx - QUARTER_BUF_LENGTH * 3] =
-          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH * 3].data[0], tiles[idx - QUARTER_BUF_LENGTH * 3].data[1], tiles[idx - QUARTER_BUF_LENGTH * 3].data[2], map_val as f32]);
+          TileMapData::new([tiles[idx - QUARTER_BUF_LENGTH * 3].data[0], tiles[idx - QUARTER_BUF_LENGTH * 3].data[1], tiles[idx - QUARTER_BUF_LENGTH * 3].data[2], map_val]);
       }
     }
   }
@@ -1,50 +1,31 @@
 use cgmath::Point2;
 use gfx;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
 
-use crate::character::controls::CharacterInputState;
-use crate::game::constants::{ASPECT_RATIO, VIEW_DISTANCE};
-use crate::gfx_app::{ColorFormat, DepthFormat};
-use crate::graphics::{camera::CameraInputState, dimensions::{Dimensions, get_projection, get_view_matrix}, texture::load_texture};
+use crate::ecs;
+use crate::graphics::{assets::{Assets, TextureAsset}, render_graph::{RenderTargets, ShaderReloader, StaticElementPass}, viewport::View};
 use crate::graphics::mesh::{RectangularTexturedMesh, Geometry};
 use crate::graphics::texture::Texture;
-use crate::shaders::{Position, Projection, static_element_pipeline, Time};
-use crate::terrain_object::terrain_objects::TerrainObjects;
+use crate::graphics::transform::Transform;
+use crate::shaders::Position;
 
 pub mod terrain_objects;
 
-const SHADER_VERT: &[u8] = include_bytes!("../shaders/static_element.v.glsl");
-const SHADER_FRAG: &[u8] = include_bytes!("../shaders/static_element.f.glsl");
-
 pub struct TerrainObjectDrawable {
-  projection: Projection,
-  pub position: Position,
-  previous_position: Position,
+  pub transform: Transform,
   pub object_type: TerrainTexture,
 }
 
 impl TerrainObjectDrawable {
   pub fn new(position: Position, object_type: TerrainTexture) -> TerrainObjectDrawable {
-    let view = get_view_matrix(VIEW_DISTANCE);
-    let projection = get_projection(view, ASPECT_RATIO);
     TerrainObjectDrawable {
-      projection,
-      position,
-      previous_position: Position::origin(),
+      transform: Transform::new(position),
       object_type,
     }
   }
-
-  pub fn update(&mut self, world_to_clip: &Projection, ci: &CharacterInputState) {
-    self.projection = *world_to_clip;
-    self.position = self.position + ci.movement - self.previous_position;
-    self.previous_position = ci.movement;
-  }
 }
 
-impl specs::prelude::Component for TerrainObjectDrawable {
-  type Storage = specs::storage::VecStorage<TerrainObjectDrawable>;
+impl ecs::Component for TerrainObjectDrawable {
+  type Storage = ecs::DenseVecStorage<TerrainObjectDrawable>;
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -55,74 +36,38 @@ pub enum TerrainTexture {
 }
 
 pub struct TerrainObjectDrawSystem<R: gfx::Resources> {
-  bundle: gfx::pso::bundle::Bundle<R, static_element_pipeline::Data<R>>,
+  pass: StaticElementPass<R>,
 }
 
 impl<R: gfx::Resources> TerrainObjectDrawSystem<R> {
   pub fn new<F>(factory: &mut F,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>,
-                texture: TerrainTexture) -> TerrainObjectDrawSystem<R>
+                targets: &RenderTargets<R>,
+                texture: TerrainTexture,
+                assets: &Assets<R>,
+                reloader: &mut ShaderReloader<F>) -> TerrainObjectDrawSystem<R>
     where F: gfx::Factory<R> {
-    use gfx::traits::FactoryExt;
-
-    let (texture_size, texture_bytes) = match texture {
-      TerrainTexture::Ammo => (Point2::new(5.0, 7.0), &include_bytes!("../../assets/maps/ammo.png")[..]),
-      TerrainTexture::House => (Point2::new(125.0, 125.0), &include_bytes!("../../assets/maps/house.png")[..]),
-      TerrainTexture::Tree => (Point2::new(120.0, 120.0), &include_bytes!("../../assets/maps/tree.png")[..]),
+    let (texture_size, asset) = match texture {
+      TerrainTexture::Ammo => (Point2::new(5.0, 7.0), TextureAsset::Ammo),
+      TerrainTexture::House => (Point2::new(125.0, 125.0), TextureAsset::House),
+      TerrainTexture::Tree => (Point2::new(120.0, 120.0), TextureAsset::Tree),
     };
 
-    let terrain_object_texture = load_texture(factory, texture_bytes);
+    let terrain_object_texture = assets.texture(asset);
 
     let mesh = RectangularTexturedMesh::new(factory, Texture::new(terrain_object_texture, None), Geometry::Rectangle, texture_size, None, None, None);
 
-    let pso = factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, static_element_pipeline::new())
-      .expect("Terrain object shader loading error");
-
-    let pipeline_data = static_element_pipeline::Data {
-      vbuf: mesh.mesh.vertex_buffer,
-      position_cb: factory.create_constant_buffer(1),
-      time_passed_cb: factory.create_constant_buffer(1),
-      projection_cb: factory.create_constant_buffer(1),
-      static_element_sheet: (mesh.mesh.texture.raw, factory.create_sampler_linear()),
-      out_color: rtv,
-      out_depth: dsv,
-    };
-
     TerrainObjectDrawSystem {
-      bundle: gfx::Bundle::new(mesh.mesh.slice, pso, pipeline_data),
+      pass: StaticElementPass::new(factory, targets, "Terrain object", mesh, reloader),
     }
   }
 
-  pub fn draw<C>(&self,
+  pub fn draw<C>(&mut self,
                  drawable: &TerrainObjectDrawable,
-                 time_passed: u64,
+                 view: &View,
                  encoder: &mut gfx::Encoder<R, C>)
     where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.projection_cb, &drawable.projection);
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    encoder.update_constant_buffer(&self.bundle.data.time_passed_cb, &Time::new(time_passed));
-    self.bundle.encode(encoder);
-  }
-}
-
-pub struct PreDrawSystem;
-
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
-  type SystemData = (ReadStorage<'a, CameraInputState>,
-                     ReadStorage<'a, CharacterInputState>,
-                     WriteStorage<'a, TerrainObjects>,
-                     Read<'a, Dimensions>);
-
-  fn run(&mut self, (camera_input, character_input, mut terrain_objects, dim): Self::SystemData) {
-    use specs::join::Join;
-
-    for (camera, ci, obj) in (&camera_input, &character_input, &mut terrain_objects).join() {
-      let world_to_clip = dim.world_to_projection(camera);
-
-      for o in &mut obj.objects {
-        o.update(&world_to_clip, ci);
-      }
+    if view.is_visible(drawable.transform.position) {
+      self.pass.draw(drawable.transform.position, view, encoder);
     }
   }
 }
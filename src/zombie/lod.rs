@@ -0,0 +1,42 @@
+use crate::game::constants::{ZOMBIE_LOD_FAR_INTERVAL, ZOMBIE_LOD_FAR_RANGE, ZOMBIE_LOD_MID_INTERVAL};
+use crate::graphics::camera::CameraInputState;
+use crate::shaders::Position;
+
+// How much care a zombie gets, by its distance from the view. Only near zombies are animated and
+// drawn, the others walk straight and are updated every few steps with a longer time step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ZombieLod {
+  #[default]
+  Near,
+  Mid,
+  Far,
+}
+
+impl ZombieLod {
+  // The position is relative to the camera, like the zombies keep theirs
+  pub fn of(camera: &CameraInputState, position: Position) -> ZombieLod {
+    let extent = camera.view_extent();
+    let distance = position.x().abs().max(position.y().abs());
+    if distance < extent {
+      ZombieLod::Near
+    } else if distance < extent * ZOMBIE_LOD_FAR_RANGE {
+      ZombieLod::Mid
+    } else {
+      ZombieLod::Far
+    }
+  }
+
+  // Steps between two updates
+  pub fn interval(self) -> u64 {
+    match self {
+      ZombieLod::Near => 1,
+      ZombieLod::Mid => ZOMBIE_LOD_MID_INTERVAL,
+      ZombieLod::Far => ZOMBIE_LOD_FAR_INTERVAL,
+    }
+  }
+
+  // The zombies of a band take turns by index, so a horde is spread over the steps of the interval
+  pub fn is_due(self, step: u64, idx: usize) -> bool {
+    (step + idx as u64).is_multiple_of(self.interval())
+  }
+}
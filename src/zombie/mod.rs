@@ -1,34 +1,47 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use cgmath::Point2;
 use gfx;
-use specs;
-use specs::prelude::{Read, ReadStorage, WriteStorage};
-
-use crate::bullet::{BulletDrawable, bullets::Bullets};
-use crate::character::controls::CharacterInputState;
-use crate::critter::CritterData;
-use crate::data;
-use crate::game::constants::{ASPECT_RATIO, NORMAL_DEATH_SPRITE_OFFSET, SMALL_HILLS, SPRITE_OFFSET, VIEW_DISTANCE, ZOMBIE_SHEET_TOTAL_WIDTH, ZOMBIE_STILL_SPRITE_OFFSET};
-use crate::game::get_random_bool;
-use crate::gfx_app::{ColorFormat, DepthFormat};
-use crate::graphics::{camera::CameraInputState, can_move_to_tile, check_terrain_elevation, direction, direction_movement, direction_movement_180, distance, GameTime, get_nearest_random_tile_position, orientation::{Orientation, Stance}, orientation_to_direction, overlaps};
-use crate::graphics::dimensions::{Dimensions, get_projection, get_view_matrix};
+use json::JsonValue;
+use rayon::prelude::*;
+
+use crate::bullet::collision::{Contact, Contacts};
+use crate::character::{controls::CharacterInputState, Player};
+use crate::critter::palette::CritterPalette;
+use crate::data::{self, atlas::SpriteSheet};
+use crate::ecs::{self, Read, ReadStorage, Write, WriteStorage};
+use crate::game::constants::{BULLET_DAMAGE, PARALLEL_MIN_ZOMBIES, SMALL_HILLS, ZOMBIE_CHASE_DISTANCE, ZOMBIE_MAX_HEALTH, ZOMBIE_SPEED};
+use crate::game::events::{GameEvent, GameEvents};
+use crate::game::snapshot::{field_f32, Snapshot};
+use crate::game::{GameRng, get_rand_from_range, get_random_bool};
+use crate::game::health::Health;
+use crate::game::prefab::intern;
+use crate::game::scratch::Scratch;
+use crate::graphics::{camera::CameraInputState, can_move_to_tile, check_terrain_elevation, direction, direction_movement, direction_movement_180, distance, DeltaTime, GameTime, get_nearest_random_tile_position, orientation::{Orientation, Stance}, orientation_to_direction};
 use crate::graphics::mesh::{Geometry, RectangularTexturedMesh};
-use crate::graphics::texture::{load_texture, Texture};
-use crate::shaders::{CharacterSheet, critter_pipeline, Position, Projection};
-use crate::terrain::path_finding::calc_next_movement;
-use crate::zombie::zombies::Zombies;
-
+use crate::graphics::dimensions::Dimensions;
+use crate::graphics::{assets::{Assets, TextureAsset}, texture::Texture};
+use crate::graphics::transform::{StepCadence, Transform};
+use crate::graphics::render_graph::{CritterPass, RenderTargets, ShaderReloader};
+use crate::graphics::viewport::View;
+use crate::net::NetMode;
+use crate::shaders::{CritterInstance, Position, SpriteFrame};
+use crate::terrain::path_finding::{PathFinder, with_path_finder};
+use crate::zombie::lod::ZombieLod;
+
+pub mod lod;
 pub mod zombies;
 
-const SHADER_VERT: &[u8] = include_bytes!("../shaders/character.v.glsl");
-const SHADER_FRAG: &[u8] = include_bytes!("../shaders/character.f.glsl");
+// Zombies spawned without a prefab count as the plain one
+const DEFAULT_KIND: &str = "zombie";
 
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+#[derive(Clone)]
 pub struct ZombieDrawable {
-  projection: Projection,
-  pub position: Position,
-  previous_position: Position,
+  pub transform: Transform,
   previous_elevation: f32,
-  orientation: Orientation,
+  pub orientation: Orientation,
   pub stance: Stance,
   direction: Orientation,
   last_decision: i64,
@@ -36,17 +49,26 @@ pub struct ZombieDrawable {
   zombie_idx: usize,
   zombie_death_idx: usize,
   movement_speed: f32,
-  health: f32,
+  pub health: Health,
+  pub palette: CritterPalette,
+  pub steps: StepCadence,
+  // Prefab name, kills are counted by it
+  pub kind: &'static str,
+  // String key of the name shown above elites and bosses
+  pub nameplate: Option<String>,
+  // Set for good once the zombie chased a player or got hit
+  pub is_aggroed: bool,
+  // Matches a zombie across the states a host sends
+  pub id: u32,
+  // Counts the zombies of a run as they spawn, unlike entities it is the same on every lockstep peer
+  pub spawn_order: usize,
+  pub lod: ZombieLod,
 }
 
 impl ZombieDrawable {
   pub fn new(position: Position) -> ZombieDrawable {
-    let view = get_view_matrix(VIEW_DISTANCE);
-    let projection = get_projection(view, ASPECT_RATIO);
     ZombieDrawable {
-      projection,
-      position,
-      previous_position: Position::origin(),
+      transform: Transform::new(position),
       orientation: Orientation::Left,
       stance: Stance::Still,
       direction: Orientation::Left,
@@ -56,48 +78,81 @@ impl ZombieDrawable {
       zombie_idx: 0,
       zombie_death_idx: 0,
       movement_speed: 0.0,
-      health: 1.0,
+      health: Health::new(ZOMBIE_MAX_HEALTH),
+      palette: CritterPalette::Original,
+      steps: StepCadence::default(),
+      kind: DEFAULT_KIND,
+      nameplate: None,
+      is_aggroed: false,
+      id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+      spawn_order: 0,
+      lod: ZombieLod::Near,
     }
   }
 
-  pub fn update(&mut self, world_to_clip: &Projection, ci: &CharacterInputState, game_time: u64) {
-    self.projection = *world_to_clip;
-
-    let elevated_pos_y = check_terrain_elevation(ci.movement - self.position, &SMALL_HILLS);
+  pub fn is_dead(&self) -> bool {
+    self.health.is_dead() || self.stance == Stance::NormalDeath || self.stance == Stance::CriticalDeath
+  }
 
-    let offset_delta = ci.movement - self.previous_position;
-    self.previous_position = ci.movement;
+  // Placed relative to the first player's movement, chases the player whose movement is the target
+  pub fn update(&mut self, ci: &CharacterInputState, target: Position, game_time: u64, delta: f32, rng: &mut GameRng, paths: &mut PathFinder) {
+    let elevated_pos_y = check_terrain_elevation(ci.movement - self.transform.position, &SMALL_HILLS);
 
-    let x_y_distance_to_player = self.position - offset_delta;
+    let x_y_distance_to_player = self.transform.position - self.transform.camera_delta(ci) - (ci.movement - target);
 
     let distance_to_player = distance(x_y_distance_to_player.x().abs(), x_y_distance_to_player.y().abs());
 
-    let is_alive = self.health > 0.0 && self.stance != Stance::NormalDeath && self.stance != Stance::CriticalDeath;
-
-    if is_alive {
-      let zombie_pos = ci.movement - self.position;
+    if !self.is_dead() {
+      let zombie_pos = ci.movement - self.transform.position;
 
-      if distance_to_player < 400.0 {
-        let dir = calc_next_movement(zombie_pos, self.previous_position) as f32;
+      if distance_to_player < ZOMBIE_CHASE_DISTANCE {
+        let dir = paths.calc_next_movement(zombie_pos, target, rng) as f32;
         self.direction = orientation_to_direction(dir);
         self.movement_direction = direction_movement(dir);
         self.stance = Stance::Running;
-        self.movement_speed = 2.0 * self.health;
+        self.is_aggroed = true;
+        self.movement_speed = 2.0 * ZOMBIE_SPEED * self.health.current;
       } else {
-        self.idle_direction_movement(zombie_pos, game_time as i64);
-        self.movement_speed = self.health;
+        self.idle_direction_movement(zombie_pos, game_time as i64, rng, paths);
+        self.movement_speed = ZOMBIE_SPEED * self.health.current;
       }
     } else {
       self.movement_direction = Point2::new(0.0, 0.0);
     }
 
-    self.position = Position::new(self.position.position[0] + self.movement_direction.x * self.movement_speed,
-                                  self.position.position[1] + (elevated_pos_y - self.previous_elevation) + self.movement_direction.y * self.movement_speed) + offset_delta;
+    let step = self.movement_speed * delta;
+    self.transform.translate(Position::new(self.movement_direction.x * step,
+                                           (elevated_pos_y - self.previous_elevation) + self.movement_direction.y * step));
+    self.transform.follow_camera(ci);
     self.previous_elevation = elevated_pos_y;
+  }
+
+  // Out of view a zombie wanders in straight lines without path finding, hills and chasing wait
+  // until it is near again
+  pub fn update_distant(&mut self, ci: &CharacterInputState, game_time: u64, delta: f32, rng: &mut GameRng) {
+    if !self.is_dead() {
+      let zombie_pos = ci.movement - self.transform.position;
+      if !can_move_to_tile(zombie_pos) {
+        self.movement_direction = direction_movement_180(self.movement_direction);
+      }
+      if self.last_decision + 2 < game_time as i64 {
+        self.stance = Stance::Walking;
+        self.last_decision = game_time as i64;
+        let dir = get_rand_from_range(rng, 0, 359) as f32;
+        self.movement_direction = direction_movement(dir);
+        self.direction = orientation_to_direction(dir);
+      }
+      self.movement_speed = ZOMBIE_SPEED * self.health.current;
+    } else {
+      self.movement_direction = Point2::new(0.0, 0.0);
+    }
 
+    let step = self.movement_speed * delta;
+    self.transform.translate(Position::new(self.movement_direction.x * step, self.movement_direction.y * step));
+    self.transform.follow_camera(ci);
   }
 
-  fn idle_direction_movement(&mut self, zombie_pos: Position, game_time: i64) {
+  fn idle_direction_movement(&mut self, zombie_pos: Position, game_time: i64, rng: &mut GameRng, paths: &mut PathFinder) {
     if !can_move_to_tile(zombie_pos) {
       let dir = direction(self.movement_direction, Point2::new(0.0, 0.0));
       self.movement_direction = direction_movement_180(self.movement_direction);
@@ -108,18 +163,20 @@ impl ZombieDrawable {
     if self.last_decision + 2 < game_time {
       self.stance = Stance::Walking;
       self.last_decision = game_time;
-      let end_point = get_nearest_random_tile_position(zombie_pos);
-      let dir = calc_next_movement(zombie_pos, end_point) as f32;
+      let end_point = get_nearest_random_tile_position(zombie_pos, rng);
+      let dir = paths.calc_next_movement(zombie_pos, end_point, rng) as f32;
       self.movement_direction = direction_movement(dir);
       self.direction = orientation_to_direction(dir);
     }
   }
 
-  fn handle_bullet_hit(&mut self) {
-    self.health -= 0.5;
-    if self.health <= 0.0 {
+  pub fn take_damage(&mut self, amount: f32, events: &mut GameEvents, rng: &mut GameRng) {
+    self.is_aggroed = true;
+    events.single_write(GameEvent::ZombieHit(self.transform.position, amount));
+    if self.health.take_damage(amount) {
+      events.single_write(GameEvent::ZombieKilled(self.transform.position, self.kind));
       self.stance =
-        if get_random_bool() {
+        if get_random_bool(rng) {
           Stance::NormalDeath
         } else {
           Stance::CriticalDeath
@@ -127,14 +184,6 @@ impl ZombieDrawable {
     }
   }
 
-  fn check_bullet_hits(&mut self, bullets: &[BulletDrawable]) {
-    bullets.iter().for_each(|bullet| {
-      if overlaps(self.position, bullet.position, 15.0, 15.0) && self.stance != Stance::NormalDeath && self.stance != Stance::CriticalDeath {
-        self.handle_bullet_hit()
-      }
-    });
-  }
-
   pub fn update_alive_idx(&mut self, max_idx: usize) {
     if self.zombie_idx < max_idx {
       self.zombie_idx += 1;
@@ -150,116 +199,174 @@ impl ZombieDrawable {
   }
 }
 
+impl ecs::Component for ZombieDrawable {
+  type Storage = ecs::VecStorage<ZombieDrawable>;
+}
+
+impl Snapshot for ZombieDrawable {
+  fn to_json(&self) -> JsonValue {
+    object! {
+      "position" => self.transform.position.to_json(),
+      "health" => self.health.current,
+      "max_health" => self.health.max,
+      "stance" => self.stance.to_json(),
+      "palette" => self.palette.to_json(),
+      "kind" => self.kind,
+      "nameplate" => self.nameplate.clone(),
+      "aggroed" => self.is_aggroed
+    }
+  }
+
+  fn from_json(value: &JsonValue) -> Result<ZombieDrawable, String> {
+    let mut zombie = ZombieDrawable::new(Position::from_json(&value["position"])?);
+    zombie.health.current = field_f32(value, "health")?;
+    // Older snapshots only had the current health of zombies with the default maximum
+    if let Some(max) = value["max_health"].as_f32() {
+      zombie.health.max = max;
+    }
+    zombie.stance = Stance::from_json(&value["stance"])?;
+    zombie.palette = CritterPalette::from_json(&value["palette"])?;
+    zombie.kind = value["kind"].as_str().map_or(DEFAULT_KIND, intern);
+    zombie.nameplate = value["nameplate"].as_str().map(String::from);
+    zombie.is_aggroed = value["aggroed"].as_bool().unwrap_or(false);
+    Ok(zombie)
+  }
+}
+
 pub struct ZombieDrawSystem<R: gfx::Resources> {
-  bundle: gfx::pso::bundle::Bundle<R, critter_pipeline::Data<R>>,
-  data: Vec<CritterData>,
+  pass: CritterPass<R>,
+  sheet: SpriteSheet,
 }
 
 impl<R: gfx::Resources> ZombieDrawSystem<R> {
   pub fn new<F>(factory: &mut F,
-                rtv: gfx::handle::RenderTargetView<R, ColorFormat>,
-                dsv: gfx::handle::DepthStencilView<R, DepthFormat>) -> ZombieDrawSystem<R>
+                targets: &RenderTargets<R>,
+                assets: &Assets<R>,
+                reloader: &mut ShaderReloader<F>) -> ZombieDrawSystem<R>
     where F: gfx::Factory<R> {
-    use gfx::traits::FactoryExt;
-
-    let zombie_bytes = include_bytes!("../../assets/zombie.png");
-    let char_texture = load_texture(factory, zombie_bytes);
+    let char_texture = assets.texture(TextureAsset::Zombie);
 
     let rect_mesh =
       RectangularTexturedMesh::new(factory, Texture::new(char_texture, None), Geometry::Rectangle, Point2::new(25.0, 35.0), None, None, None);
 
-    let pso =
-      factory.create_pipeline_simple(SHADER_VERT, SHADER_FRAG, critter_pipeline::new())
-        .expect("Zombie shader loading error");
-
-    let pipeline_data = critter_pipeline::Data {
-      vbuf: rect_mesh.mesh.vertex_buffer,
-      projection_cb: factory.create_constant_buffer(1),
-      position_cb: factory.create_constant_buffer(1),
-      character_sprite_cb: factory.create_constant_buffer(1),
-      charactersheet: (rect_mesh.mesh.texture.raw, factory.create_sampler_linear()),
-      out_color: rtv,
-      out_depth: dsv,
-    };
-
-    let data = data::load_zombie();
+    let sheet = data::load_zombie();
 
     ZombieDrawSystem {
-      bundle: gfx::Bundle::new(rect_mesh.mesh.slice, pso, pipeline_data),
-      data,
+      pass: CritterPass::new(factory, targets, "Zombie", rect_mesh, reloader),
+      sheet,
     }
   }
 
-  fn get_next_sprite(&self, drawable: &mut ZombieDrawable) -> CharacterSheet {
-    let sprite_idx = match drawable.stance {
-      Stance::Still => {
-        (drawable.direction as usize * 4 + drawable.zombie_idx)
-      }
-      Stance::Walking if drawable.orientation != Orientation::Normal => {
-        (drawable.direction as usize * 8 + drawable.zombie_idx + ZOMBIE_STILL_SPRITE_OFFSET)
-      }
-      Stance::Running if drawable.orientation != Orientation::Normal => {
-        (drawable.direction as usize * 8 + drawable.zombie_idx + ZOMBIE_STILL_SPRITE_OFFSET)
-      }
-      Stance::NormalDeath if drawable.orientation != Orientation::Normal => {
-        (drawable.direction as usize * 6 + drawable.zombie_death_idx + NORMAL_DEATH_SPRITE_OFFSET)
-      }
-      Stance::CriticalDeath if drawable.orientation != Orientation::Normal => {
-        (drawable.direction as usize * 8 + drawable.zombie_death_idx)
-      }
+  fn get_next_sprite(&self, drawable: &mut ZombieDrawable) -> SpriteFrame {
+    let direction = drawable.direction as usize;
+    match drawable.stance {
+      Stance::Still => self.sheet.frame("still", direction, drawable.zombie_idx),
+      Stance::Walking | Stance::Running if drawable.orientation != Orientation::Normal => self.sheet.frame("walk", direction, drawable.zombie_idx),
+      Stance::NormalDeath if drawable.orientation != Orientation::Normal => self.sheet.frame("normal", direction, drawable.zombie_death_idx),
+      Stance::CriticalDeath if drawable.orientation != Orientation::Normal => self.sheet.frame("critical", direction, drawable.zombie_death_idx),
       _ => {
         drawable.direction = drawable.orientation;
-        (drawable.orientation as usize * 8 + drawable.zombie_idx + ZOMBIE_STILL_SPRITE_OFFSET)
+        self.sheet.frame("walk", drawable.orientation as usize, drawable.zombie_idx)
       }
-    } as usize;
-
-    let (y_div, row_idx) =
-      if drawable.stance == Stance::NormalDeath || drawable.stance == Stance::CriticalDeath {
-        (0.0, 2)
-      } else {
-        (1.0, 2)
-      };
-
-    let elements_x = ZOMBIE_SHEET_TOTAL_WIDTH / (self.data[sprite_idx].data[2] + SPRITE_OFFSET);
-    CharacterSheet {
-      x_div: elements_x,
-      y_div,
-      row_idx,
-      index: sprite_idx as f32,
     }
   }
 
   pub fn draw<C>(&mut self,
-                 mut drawable: &mut ZombieDrawable,
+                 drawable: &mut ZombieDrawable,
+                 view: &View,
                  encoder: &mut gfx::Encoder<R, C>)
     where C: gfx::CommandBuffer<R> {
-    encoder.update_constant_buffer(&self.bundle.data.projection_cb, &drawable.projection);
-    encoder.update_constant_buffer(&self.bundle.data.position_cb, &drawable.position);
-    encoder.update_constant_buffer(&self.bundle.data.character_sprite_cb,
-                                   &self.get_next_sprite(&mut drawable));
-    self.bundle.encode(encoder);
+    if view.is_visible(drawable.transform.position) {
+      let sprite = self.get_next_sprite(drawable);
+      let mut instance = CritterInstance::new(view.position(drawable.transform.position), &sprite);
+      instance.palette = drawable.palette.index();
+      self.pass.draw_instances(&[instance], view, encoder);
+    }
   }
 }
 
-pub struct PreDrawSystem;
+// Zombies are independent of each other during a step, large hordes are split up between the rayon
+// workers. Each zombie draws from a generator of its own, so the outcome doesn't depend on the threads.
+#[derive(Default)]
+pub struct PreDrawSystem {
+  // Steps run, the zombies out of view take turns by it
+  step: u64,
+  // Movements of the living players, a zombie chases the nearest
+  targets: Scratch<Position>,
+}
 
-impl<'a> specs::prelude::System<'a> for PreDrawSystem {
-  type SystemData = (WriteStorage<'a, Zombies>,
+fn nearest_target(targets: &[Position], zombie: Position) -> Option<Position> {
+  let to = |target: Position| {
+    let offset = zombie - target;
+    distance(offset.x().abs(), offset.y().abs())
+  };
+  targets.iter().cloned().min_by(|&a, &b| to(a).total_cmp(&to(b)))
+}
+
+impl<'a> ecs::System<'a> for PreDrawSystem {
+  type SystemData = (WriteStorage<'a, ZombieDrawable>,
                      ReadStorage<'a, CameraInputState>,
                      ReadStorage<'a, CharacterInputState>,
-                     ReadStorage<'a, Bullets>,
+                     ReadStorage<'a, Player>,
+                     ReadStorage<'a, Health>,
                      Read<'a, Dimensions>,
-                     Read<'a, GameTime>);
-
-  fn run(&mut self, (mut zombies, camera_input, character_input, bullets, dim, gt): Self::SystemData) {
-    use specs::join::Join;
-
-    for (zs, camera, ci, bs) in (&mut zombies, &camera_input, &character_input, &bullets).join() {
-      let world_to_clip = dim.world_to_projection(camera);
+                     Read<'a, GameTime>,
+                     Read<'a, DeltaTime>,
+                     Write<'a, GameRng>,
+                     Read<'a, NetMode>);
+
+  fn run(&mut self, (mut zombies, camera_input, character_input, players, health, dim, gt, dt, mut rng, net_mode): Self::SystemData) {
+    use crate::ecs::{Join, ParJoin};
+
+    // Split screen and remote players look at other parts of the map than the first player
+    let has_lod = !dim.split_screen && *net_mode == NetMode::Offline;
+    let (game_time, delta, step) = (gt.0, dt.0 as f32, self.step);
+    self.step += 1;
+    // Zombies are placed relative to the first player
+    let (camera, ci) = match (&camera_input, &character_input, &players).join().min_by_key(|(_, _, p)| p.index) {
+      Some((camera, ci, _)) => (camera, ci),
+      None => return,
+    };
+    let targets = self.targets.fill((&character_input, &players, &health).join()
+      .filter(|(_, _, h)| !h.is_dead())
+      .map(|(ci, _, _)| ci.movement));
+    let seed = rng.next_seed();
+    let update = |z: &mut ZombieDrawable| {
+      let idx = z.spawn_order;
+      z.lod = if has_lod { ZombieLod::of(camera, z.transform.camera_relative_position(ci)) } else { ZombieLod::Near };
+      if !z.lod.is_due(step, idx) {
+        z.transform.follow_camera(ci);
+      } else if z.lod == ZombieLod::Near {
+        let target = nearest_target(targets, ci.movement - z.transform.camera_relative_position(ci)).unwrap_or(ci.movement);
+        with_path_finder(|paths| z.update(ci, target, game_time, delta, &mut GameRng::for_entity(seed, idx), paths));
+      } else {
+        // Catches up on the steps skipped since its last turn
+        z.update_distant(ci, game_time, delta * z.lod.interval() as f32, &mut GameRng::for_entity(seed, idx));
+      }
+    };
+    if (&zombies).join().count() < PARALLEL_MIN_ZOMBIES {
+      (&mut zombies).join().for_each(update);
+    } else {
+      (&mut zombies).par_join().for_each(update);
+    }
+  }
+}
 
-      for z in &mut zs.zombies {
-        z.update(&world_to_clip, ci, gt.0);
-        z.check_bullet_hits(&bs.bullets);
+pub struct HitSystem;
+
+impl<'a> ecs::System<'a> for HitSystem {
+  type SystemData = (WriteStorage<'a, ZombieDrawable>,
+                     Read<'a, Contacts>,
+                     Write<'a, GameEvents>,
+                     Write<'a, GameRng>);
+
+  fn run(&mut self, (mut zombies, contacts, mut events, mut rng): Self::SystemData) {
+    for contact in &contacts.0 {
+      if let Contact::BulletZombie { zombie, .. } = *contact {
+        if let Some(z) = zombies.get_mut(zombie).filter(|z| !z.is_dead()) {
+          events.single_write(GameEvent::BulletHit(z.transform.position));
+          z.take_damage(BULLET_DAMAGE, &mut events, &mut rng);
+        }
       }
     }
   }